@@ -3,9 +3,21 @@ fn main() {
     // If we make a second pkg-config call, rustc complains with
     // "warning: redundant linker flag specified for library `cairo`"
 
-    pkg_config::Config::new()
+    let poppler = pkg_config::Config::new()
         // FIXME: Do we actually require a more recent version than this?
         .atleast_version("0.16.0")
         .probe("poppler-glib")
         .unwrap();
+
+    // Bindings to more recent functions are only compiled if the installed Poppler has them.
+    let version: Vec<u32> = poppler
+        .version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    println!("cargo:rustc-check-cfg=cfg(poppler_dests_tree)");
+    if version[..] >= [22, 6][..] {
+        // For poppler_document_create_dests_tree
+        println!("cargo:rustc-cfg=poppler_dests_tree");
+    }
 }
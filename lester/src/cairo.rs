@@ -25,6 +25,70 @@ pub struct Argb32Pixels<'data> {
     pub buffer: &'data mut [u32],
 }
 
+impl Argb32Pixels<'_> {
+    /// Convert to 8 bits per channel RGBA with straight (non-premultiplied) alpha,
+    /// as used by many image libraries.
+    /// The pixel at position `(x, y)` starts at index `4 * (x + width * y)`.
+    ///
+    /// The alpha channel is only meaningful if the image is in ARGB32 format.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        rgba8_from_argb32(self.buffer)
+    }
+
+    /// Replace the pixels of this image from 8 bits per channel RGBA
+    /// with straight (non-premultiplied) alpha, in the same order as `to_rgba8`.
+    ///
+    /// Return an “invalid size” error if `rgba` is not `4 * width * height` bytes long.
+    pub fn copy_from_rgba8(&mut self, rgba: &[u8]) -> Result<(), CairoError> {
+        if rgba.len() != self.buffer.len() * 4 {
+            return CairoError::check(CAIRO_STATUS_INVALID_SIZE);
+        }
+        for (pixel, rgba) in self.buffer.iter_mut().zip(rgba.chunks(4)) {
+            let alpha = rgba[3];
+            let premultiply = |c: u8| ((u32::from(c) * u32::from(alpha) + 0x7F) / 0xFF) as u8;
+            *pixel = u32::from_be_bytes([
+                alpha,
+                premultiply(rgba[0]),
+                premultiply(rgba[1]),
+                premultiply(rgba[2]),
+            ])
+        }
+        Ok(())
+    }
+
+    /// Convert to an `image::RgbaImage`, see `to_rgba8`.
+    ///
+    /// This requires the `image` Cargo feature.
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(
+            self.width.try_into().unwrap(),
+            self.height.try_into().unwrap(),
+            self.to_rgba8(),
+        )
+        .unwrap()
+    }
+}
+
+/// Cairo uses premultiplied alpha
+fn rgba8_from_argb32(pixels: &[u32]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(pixels.len() * 4);
+    for &pixel in pixels {
+        let [alpha, red, green, blue] = pixel.to_be_bytes();
+        let unpremultiply = |c: u8| match alpha {
+            0 => 0,
+            _ => ((u32::from(c) * 0xFF + u32::from(alpha) / 2) / u32::from(alpha)) as u8,
+        };
+        rgba.extend_from_slice(&[
+            unpremultiply(red),
+            unpremultiply(green),
+            unpremultiply(blue),
+            alpha,
+        ])
+    }
+    rgba
+}
+
 /// A cairo “image surface”: an in-memory pixel buffer.
 ///
 /// Only the RGB24 and ARGB32 pixel formats (which have compatible memory representation)
@@ -89,6 +153,16 @@ impl ImageSurface {
 
     /// Access the pixels of this image surface
     pub fn pixels<'data>(&'data mut self) -> Argb32Pixels<'data> {
+        let (width, height, data) = self.raw_pixels();
+        Argb32Pixels {
+            width,
+            height,
+            buffer: unsafe { slice::from_raw_parts_mut(data, width * height) },
+        }
+    }
+
+    /// Flush pending drawing, then return the width, height, and pixel data of this surface.
+    fn raw_pixels(&self) -> (usize, usize, *mut u32) {
         unsafe {
             cairo_surface_flush(self.ptr);
             let data = cairo_image_surface_get_data(self.ptr);
@@ -115,17 +189,43 @@ impl ImageSurface {
                 "Expected cairo to allocated data aligned to 32 bits"
             );
 
-            Argb32Pixels {
-                width: width.try_into().unwrap(),
-                height: height.try_into().unwrap(),
-                buffer: slice::from_raw_parts_mut(
-                    data as *mut u32,
-                    (width * height).try_into().unwrap(),
-                ),
-            }
+            (
+                width.try_into().unwrap(),
+                height.try_into().unwrap(),
+                data as *mut u32,
+            )
         }
     }
 
+    /// Create a new ARGB32 image surface from 8 bits per channel RGBA pixels
+    /// with straight (non-premultiplied) alpha, see `Argb32Pixels::to_rgba8`.
+    ///
+    /// Return an “invalid size” error if `rgba` is not `4 * width * height` bytes long.
+    pub fn from_rgba8(width: usize, height: usize, rgba: &[u8]) -> Result<Self, CairoError> {
+        let mut surface = Self::new_argb32(width, height)?;
+        surface.pixels().copy_from_rgba8(rgba)?;
+        unsafe { cairo_surface_mark_dirty(surface.ptr) }
+        Ok(surface)
+    }
+
+    /// Create a new ARGB32 image surface from an `image::RgbaImage`.
+    ///
+    /// This requires the `image` Cargo feature.
+    #[cfg(feature = "image")]
+    pub fn from_rgba_image(image: &image::RgbaImage) -> Result<Self, CairoError> {
+        Self::from_rgba8(
+            image.width().try_into().unwrap(),
+            image.height().try_into().unwrap(),
+            image,
+        )
+    }
+
+    /// Whether this surface is in the ARGB32 format, as opposed to RGB24.
+    #[cfg(any(feature = "jpeg-encoder", feature = "tiff"))]
+    fn has_alpha(&self) -> bool {
+        unsafe { cairo_image_surface_get_format(self.ptr) == CAIRO_FORMAT_ARGB32 }
+    }
+
     /// Read and decode a PNG image from the given file name and create an image surface for it.
     pub fn read_from_png_file<P: AsRef<path::Path>>(filename: P) -> Result<Self, LesterError> {
         Self::read_from_png(io::BufReader::new(fs::File::open(filename)?))
@@ -135,10 +235,75 @@ impl ImageSurface {
     pub fn write_to_png_file<P: AsRef<path::Path>>(&self, filename: P) -> Result<(), LesterError> {
         self.write_to_png(io::BufWriter::new(fs::File::create(filename)?))
     }
+
+    /// Encode this image to JPEG and write it into the file with the given name.
+    ///
+    /// `quality` ranges from 1 (smallest file) to 100 (best quality).
+    /// JPEG has no alpha channel, so an ARGB32 image is composited onto a white background.
+    ///
+    /// This requires the `jpeg-encoder` Cargo feature.
+    #[cfg(feature = "jpeg-encoder")]
+    pub fn write_to_jpeg_file<P: AsRef<path::Path>>(
+        &self,
+        filename: P,
+        quality: u8,
+    ) -> Result<(), LesterError> {
+        let (width, height, data) = self.raw_pixels();
+        let pixels = unsafe { slice::from_raw_parts(data, width * height) };
+        let has_alpha = self.has_alpha();
+        let mut rgb = Vec::with_capacity(pixels.len() * 3);
+        for &pixel in pixels {
+            let [alpha, red, green, blue] = pixel.to_be_bytes();
+            // Premultiplied color over white is `color + (1 - alpha) * white`
+            let white = if has_alpha { 0xFF - alpha } else { 0 };
+            rgb.extend_from_slice(&[red + white, green + white, blue + white])
+        }
+        let encoder = jpeg_encoder::Encoder::new_file(filename, quality)?;
+        encoder.encode(
+            &rgb,
+            width.try_into().unwrap(),
+            height.try_into().unwrap(),
+            jpeg_encoder::ColorType::Rgb,
+        )?;
+        Ok(())
+    }
+
+    /// Encode this image to TIFF and write it into the file with the given name.
+    ///
+    /// An ARGB32 image is written with (non-premultiplied) alpha, an RGB24 image without.
+    ///
+    /// This requires the `tiff` Cargo feature.
+    #[cfg(feature = "tiff")]
+    pub fn write_to_tiff_file<P: AsRef<path::Path>>(&self, filename: P) -> Result<(), LesterError> {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let (width, height, data) = self.raw_pixels();
+        let pixels = unsafe { slice::from_raw_parts(data, width * height) };
+        let file = io::BufWriter::new(fs::File::create(filename)?);
+        let mut encoder = TiffEncoder::new(file)?;
+        let width = width.try_into().unwrap();
+        let height = height.try_into().unwrap();
+        if self.has_alpha() {
+            let rgba = rgba8_from_argb32(pixels);
+            encoder.write_image::<colortype::RGBA8>(width, height, &rgba)?
+        } else {
+            let mut rgb = Vec::with_capacity(pixels.len() * 3);
+            for &pixel in pixels {
+                let [_, red, green, blue] = pixel.to_be_bytes();
+                rgb.extend_from_slice(&[red, green, blue])
+            }
+            encoder.write_image::<colortype::RGB8>(width, height, &rgb)?
+        }
+        Ok(())
+    }
 }
 
-// Private
-pub(crate) struct CairoContext {
+/// A cairo drawing context, targeting an image surface.
+///
+/// This only exposes a small part of the cairo API, see `Page::render_with`.
+/// Methods that modify the current path or the clip affect later drawing, including the page’s.
+/// Lengths are in user space units, which are pixels unless a transformation was applied.
+pub struct CairoContext {
     pub(crate) ptr: *mut cairo_t,
 }
 
@@ -147,19 +312,72 @@ impl CairoContext {
         CairoError::check(unsafe { cairo_status(self.ptr) })
     }
 
-    pub(crate) fn set_source_rgb(&mut self, r: f64, g: f64, b: f64) {
+    /// Push a copy of the current drawing state (transformation, clip, source, …) on a stack.
+    pub fn save(&mut self) {
+        unsafe { cairo_save(self.ptr) }
+    }
+
+    /// Pop the drawing state pushed by the last unmatched call to `save`.
+    pub fn restore(&mut self) {
+        unsafe { cairo_restore(self.ptr) }
+    }
+
+    /// Set the source to an opaque color, with components from 0 to 1.
+    pub fn set_source_rgb(&mut self, r: f64, g: f64, b: f64) {
         unsafe { cairo_set_source_rgb(self.ptr, r, g, b) }
     }
 
-    pub(crate) fn paint(&mut self) {
+    /// Set the source to a color with (non-premultiplied) alpha, with components from 0 to 1.
+    pub fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        unsafe { cairo_set_source_rgba(self.ptr, r, g, b, a) }
+    }
+
+    /// Set the line width used by `stroke`.
+    pub fn set_line_width(&mut self, width: f64) {
+        unsafe { cairo_set_line_width(self.ptr, width) }
+    }
+
+    /// Paint the source everywhere within the clip.
+    pub fn paint(&mut self) {
         unsafe { cairo_paint(self.ptr) }
     }
 
-    pub(crate) fn scale(&mut self, x: f64, y: f64) {
+    /// Add a rectangle to the current path.
+    pub fn rectangle(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        unsafe { cairo_rectangle(self.ptr, x, y, width, height) }
+    }
+
+    /// Fill the current path with the source, then clear the path.
+    pub fn fill(&mut self) {
+        unsafe { cairo_fill(self.ptr) }
+    }
+
+    /// Stroke the current path with the source, then clear the path.
+    pub fn stroke(&mut self) {
+        unsafe { cairo_stroke(self.ptr) }
+    }
+
+    /// Intersect the clip with the current path, then clear the path.
+    pub fn clip(&mut self) {
+        unsafe { cairo_clip(self.ptr) }
+    }
+
+    /// Move the origin of user space.
+    pub fn translate(&mut self, x: f64, y: f64) {
+        unsafe { cairo_translate(self.ptr, x, y) }
+    }
+
+    /// Scale user space.
+    pub fn scale(&mut self, x: f64, y: f64) {
         unsafe {
             cairo_scale(self.ptr, x, y);
         }
     }
+
+    /// Rotate user space by the given angle in radians.
+    pub fn rotate(&mut self, angle: f64) {
+        unsafe { cairo_rotate(self.ptr, angle) }
+    }
 }
 
 impl Drop for CairoContext {
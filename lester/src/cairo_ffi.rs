@@ -25,11 +25,22 @@ extern "C" {
         closure: *mut c_void,
     ) -> cairo_status_t;
     pub fn cairo_surface_flush(surface: *mut cairo_surface_t);
+    pub fn cairo_surface_mark_dirty(surface: *mut cairo_surface_t);
 
     pub fn cairo_create(target: *mut cairo_surface_t) -> *mut cairo_t;
+    pub fn cairo_save(cr: *mut cairo_t);
+    pub fn cairo_restore(cr: *mut cairo_t);
     pub fn cairo_set_source_rgb(cr: *mut cairo_t, red: f64, green: f64, blue: f64);
+    pub fn cairo_set_source_rgba(cr: *mut cairo_t, red: f64, green: f64, blue: f64, alpha: f64);
+    pub fn cairo_set_line_width(cr: *mut cairo_t, width: f64);
     pub fn cairo_paint(cr: *mut cairo_t);
+    pub fn cairo_rectangle(cr: *mut cairo_t, x: f64, y: f64, width: f64, height: f64);
+    pub fn cairo_fill(cr: *mut cairo_t);
+    pub fn cairo_stroke(cr: *mut cairo_t);
+    pub fn cairo_clip(cr: *mut cairo_t);
+    pub fn cairo_translate(cr: *mut cairo_t, tx: f64, ty: f64);
     pub fn cairo_scale(cr: *mut cairo_t, sx: f64, sy: f64);
+    pub fn cairo_rotate(cr: *mut cairo_t, angle: f64);
     pub fn cairo_status(cr: *mut cairo_t) -> cairo_status_t;
     pub fn cairo_destroy(cr: *mut cairo_t);
 
@@ -42,6 +53,7 @@ pub type cairo_format_t = c_int;
 pub const CAIRO_STATUS_SUCCESS: cairo_status_t = 0;
 pub const CAIRO_STATUS_READ_ERROR: cairo_status_t = 10;
 pub const CAIRO_STATUS_WRITE_ERROR: cairo_status_t = 11;
+pub const CAIRO_STATUS_INVALID_SIZE: cairo_status_t = 32;
 
 pub const CAIRO_FORMAT_ARGB32: cairo_format_t = 0;
 pub const CAIRO_FORMAT_RGB24: cairo_format_t = 1;
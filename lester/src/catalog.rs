@@ -0,0 +1,455 @@
+//! Entries of the document catalog that Poppler’s glib API does not expose,
+//! read directly from the PDF syntax.
+//!
+//! FIXME: compressed object streams and cross-reference streams (PDF 1.5) are not supported,
+//! since this does not decode `FlateDecode`. Objects that a compressed cross-reference stream
+//! points to are found by searching for their `obj` header instead.
+
+use crate::poppler::OpenAction;
+use std::collections::HashMap;
+use std::io;
+use std::str;
+
+/// The `OpenAction` entry of the catalog of the PDF document `pdf`.
+pub(crate) fn open_action(pdf: &[u8]) -> io::Result<Option<OpenAction>> {
+    let value = match catalog_entry(pdf, "OpenAction")? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    // Either a destination array, or an action dictionary with its type in `S`.
+    let kind = if value.starts_with(b"[") {
+        "GoTo".to_owned()
+    } else {
+        let kind = dictionary_entry(value, "S")
+            .filter(|name| name.starts_with(b"/"))
+            .ok_or_else(|| invalid("invalid /OpenAction"))?;
+        String::from_utf8_lossy(&kind[1..]).into_owned()
+    };
+    Ok(Some(OpenAction {
+        kind,
+        source: String::from_utf8_lossy(value).into_owned(),
+    }))
+}
+
+/// The value of the `key` entry of the document catalog, with an indirect reference resolved.
+fn catalog_entry<'a>(pdf: &'a [u8], key: &str) -> io::Result<Option<&'a [u8]>> {
+    let xref = CrossReferences::read(pdf)?;
+    let (number, generation) = dictionary_entry(xref.trailer, "Root")
+        .and_then(reference)
+        .ok_or_else(|| invalid("invalid /Root entry"))?
+        .0;
+    let catalog = xref.object(number, generation)?;
+    match dictionary_entry(catalog, key) {
+        Some(value) => match reference(value) {
+            Some(((number, generation), _)) => xref.object(number, generation).map(Some),
+            None => Ok(Some(value)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The trailer of the latest revision of a PDF file, and where its objects are.
+///
+/// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1642397
+struct CrossReferences<'a> {
+    pdf: &'a [u8],
+    /// The trailer dictionary, or the dictionary of a cross-reference stream
+    trailer: &'a [u8],
+    /// Objects in earlier sections are replaced by those in later revisions.
+    locations: HashMap<u32, Location>,
+    /// Whether some cross-reference streams are compressed, and their objects not in `locations`
+    has_compressed_sections: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Location {
+    /// Byte offset of the `obj` header in the file
+    Offset(usize),
+    /// Object stream number, and index of the object in that stream
+    InStream(u32, usize),
+}
+
+impl<'a> CrossReferences<'a> {
+    fn read(pdf: &'a [u8]) -> io::Result<Self> {
+        let start = rfind(pdf, b"startxref").ok_or_else(|| invalid("no startxref"))?;
+        let (mut offset, _) = integer(skip_whitespace(&pdf[start + b"startxref".len()..]))
+            .ok_or_else(|| invalid("invalid startxref"))?;
+        let mut xref = CrossReferences {
+            pdf,
+            trailer: &[],
+            locations: HashMap::new(),
+            has_compressed_sections: false,
+        };
+        let mut visited = Vec::new();
+        loop {
+            if visited.contains(&offset) {
+                return Err(invalid("cross-reference sections form a loop"));
+            }
+            visited.push(offset);
+            let dictionary = xref.read_section(offset)?;
+            if xref.trailer.is_empty() {
+                xref.trailer = dictionary
+            }
+            // In files that also have a cross-reference table for older readers,
+            // the stream has objects that the table does not.
+            if let Some(stream_offset) = dictionary_entry(dictionary, "XRefStm") {
+                let stream_offset = parse_integer(stream_offset)?;
+                xref.read_section(stream_offset)?;
+            }
+            match dictionary_entry(dictionary, "Prev") {
+                Some(prev) => offset = parse_integer(prev)?,
+                None => return Ok(xref),
+            }
+        }
+    }
+
+    /// Read the cross-reference table or stream at `offset`, and return its trailer dictionary.
+    fn read_section(&mut self, offset: usize) -> io::Result<&'a [u8]> {
+        let section = self.pdf.get(offset..).unwrap_or(&[]);
+        if section.starts_with(b"xref") {
+            self.read_table(skip_whitespace(&section[b"xref".len()..]))
+        } else {
+            let (dictionary, data) = self.stream(object_value(section)?)?;
+            match data {
+                Some(data) => self.read_stream(dictionary, data)?,
+                None => self.has_compressed_sections = true,
+            }
+            Ok(dictionary)
+        }
+    }
+
+    fn read_table(&mut self, mut rest: &'a [u8]) -> io::Result<&'a [u8]> {
+        let invalid_table = || invalid("invalid cross-reference table");
+        while !rest.starts_with(b"trailer") {
+            let (first, len) = integer(rest).ok_or_else(invalid_table)?;
+            rest = skip_whitespace(&rest[len..]);
+            let (count, len) = integer(rest).ok_or_else(invalid_table)?;
+            rest = skip_whitespace(&rest[len..]);
+            for number in first..first.saturating_add(count) {
+                // `offset generation n` for objects in use, `f` for free ones
+                let (offset, len) = integer(rest).ok_or_else(invalid_table)?;
+                rest = skip_whitespace(&rest[len..]);
+                let (_generation, len) = integer(rest).ok_or_else(invalid_table)?;
+                rest = skip_whitespace(&rest[len..]);
+                let in_use = rest.first() == Some(&b'n');
+                rest = skip_whitespace(rest.get(1..).ok_or_else(invalid_table)?);
+                if in_use {
+                    self.add(number, Location::Offset(offset))
+                }
+            }
+        }
+        let trailer = skip_whitespace(&rest[b"trailer".len()..]);
+        let len = value_len(trailer).ok_or_else(invalid_table)?;
+        Ok(&trailer[..len])
+    }
+
+    fn read_stream(&mut self, dictionary: &[u8], data: &[u8]) -> io::Result<()> {
+        let invalid_stream = || invalid("invalid cross-reference stream");
+        let widths = dictionary_entry(dictionary, "W")
+            .and_then(integers)
+            .filter(|widths| widths.len() == 3)
+            .ok_or_else(invalid_stream)?;
+        let size = dictionary_entry(dictionary, "Size").ok_or_else(invalid_stream)?;
+        let index = match dictionary_entry(dictionary, "Index") {
+            Some(index) => integers(index).ok_or_else(invalid_stream)?,
+            None => vec![0, parse_integer(size)?],
+        };
+        let mut entries = data.chunks_exact(widths.iter().sum::<usize>().max(1));
+        if index.len() % 2 != 0 {
+            return Err(invalid_stream());
+        }
+        for subsection in index.chunks(2) {
+            let (first, count) = (subsection[0], subsection[1]);
+            for number in first..first.saturating_add(count) {
+                let mut entry = entries.next().ok_or_else(invalid_stream)?;
+                let mut field = |i: usize| {
+                    let (bytes, rest) = entry.split_at(widths[i]);
+                    entry = rest;
+                    bytes.iter().fold(0, |n, &b| n << 8 | usize::from(b))
+                };
+                // The type defaults to 1 when its field has zero width.
+                let kind = if widths[0] == 0 { 1 } else { field(0) };
+                let (second, third) = (field(1), field(2));
+                match kind {
+                    1 => self.add(number, Location::Offset(second)),
+                    2 => self.add(number, Location::InStream(second as u32, third)),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, number: usize, location: Location) {
+        self.locations.entry(number as u32).or_insert(location);
+    }
+
+    /// The value of the indirect object `number generation obj`.
+    fn object(&self, number: u32, generation: u32) -> io::Result<&'a [u8]> {
+        let value = match self.locations.get(&number) {
+            Some(&Location::Offset(offset)) => return self.object_at(offset),
+            Some(&Location::InStream(stream_number, index)) => {
+                self.object_in_stream(stream_number, index)?
+            }
+            None => find_object(self.pdf, number, generation).map_err(|error| {
+                if self.has_compressed_sections {
+                    invalid("compressed cross-reference streams are not supported")
+                } else {
+                    error
+                }
+            })?,
+        };
+        value_len(value)
+            .map(|len| &value[..len])
+            .ok_or_else(|| invalid("invalid object"))
+    }
+
+    /// The value of the indirect object whose `obj` header is at `offset`.
+    fn object_at(&self, offset: usize) -> io::Result<&'a [u8]> {
+        let value = object_value(self.pdf.get(offset..).unwrap_or(&[]))?;
+        value_len(value)
+            .map(|len| &value[..len])
+            .ok_or_else(|| invalid("invalid object"))
+    }
+
+    /// The object at `index` in the object stream `stream_number`, followed by the rest of it.
+    fn object_in_stream(&self, stream_number: u32, index: usize) -> io::Result<&'a [u8]> {
+        let invalid_stream = || invalid("invalid object stream");
+        let offset = match self.locations.get(&stream_number) {
+            Some(&Location::Offset(offset)) => offset,
+            _ => return Err(invalid_stream()),
+        };
+        let pdf = self.pdf.get(offset..).unwrap_or(&[]);
+        let (dictionary, data) = self.stream(object_value(pdf)?)?;
+        let data = data.ok_or_else(|| invalid("compressed object streams are not supported"))?;
+        let first = dictionary_entry(dictionary, "First").ok_or_else(invalid_stream)?;
+        let first = parse_integer(first)?;
+        // Pairs of object number and offset from `first`
+        let header = data.get(..first).ok_or_else(invalid_stream)?;
+        let header = str::from_utf8(header).map_err(|_| invalid_stream())?;
+        let offset = header
+            .split_ascii_whitespace()
+            .nth(index * 2 + 1)
+            .and_then(|offset| offset.parse::<usize>().ok())
+            .ok_or_else(invalid_stream)?;
+        data.get(first + offset..).ok_or_else(invalid_stream)
+    }
+
+    /// The dictionary of the stream object `value`,
+    /// and its data unless it is encoded with a filter.
+    fn stream(&self, value: &'a [u8]) -> io::Result<(&'a [u8], Option<&'a [u8]>)> {
+        let invalid_stream = || invalid("invalid stream");
+        let len = value_len(value).ok_or_else(invalid_stream)?;
+        let dictionary = &value[..len];
+        let rest = skip_whitespace(&value[len..]);
+        if !rest.starts_with(b"stream") {
+            return Err(invalid_stream());
+        }
+        // The keyword is followed by an end of line: CRLF or LF, but not CR alone.
+        let rest = &rest[b"stream".len()..];
+        let rest = match rest.first() {
+            Some(b'\r') if rest.get(1) == Some(&b'\n') => &rest[2..],
+            Some(b'\n') => &rest[1..],
+            _ => return Err(invalid_stream()),
+        };
+        if dictionary_entry(dictionary, "Filter").is_some() {
+            return Ok((dictionary, None));
+        }
+        let length = dictionary_entry(dictionary, "Length").ok_or_else(invalid_stream)?;
+        // An indirect length is not in an object stream.
+        let length = match reference(length) {
+            Some(((number, _), _)) => match self.locations.get(&number) {
+                Some(&Location::Offset(offset)) => self.object_at(offset)?,
+                _ => return Err(invalid_stream()),
+            },
+            None => length,
+        };
+        let data = rest
+            .get(..parse_integer(length)?)
+            .ok_or_else(invalid_stream)?;
+        Ok((dictionary, Some(data)))
+    }
+}
+
+/// The value of the indirect object whose `number generation obj` header starts `bytes`,
+/// followed by the rest of `bytes`.
+fn object_value(bytes: &[u8]) -> io::Result<&[u8]> {
+    let invalid_object = || invalid("invalid object");
+    let (_, len) = integer(bytes).ok_or_else(invalid_object)?;
+    let rest = skip_whitespace(&bytes[len..]);
+    let (_, len) = integer(rest).ok_or_else(invalid_object)?;
+    let rest = skip_whitespace(&rest[len..]);
+    if !rest.starts_with(b"obj") {
+        return Err(invalid_object());
+    }
+    Ok(skip_whitespace(&rest[b"obj".len()..]))
+}
+
+/// The value of the indirect object `number generation obj` found by searching backwards,
+/// for files where it is not in a cross-reference table or stream that can be read.
+fn find_object(pdf: &[u8], number: u32, generation: u32) -> io::Result<&[u8]> {
+    let header = format!("{} {} obj", number, generation);
+    let mut end = pdf.len();
+    while let Some(start) = rfind(&pdf[..end], header.as_bytes()) {
+        if start == 0 || is_whitespace(pdf[start - 1]) {
+            return Ok(skip_whitespace(&pdf[start + header.len()..]));
+        }
+        end = start
+    }
+    Err(invalid(&format!(
+        "object {} {} R not found",
+        number, generation
+    )))
+}
+
+/// The value of the entry named `key` of the dictionary `dictionary`, as PDF syntax.
+fn dictionary_entry<'a>(dictionary: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    if !dictionary.starts_with(b"<<") {
+        return None;
+    }
+    let mut rest = skip_whitespace(&dictionary[2..]);
+    while !rest.starts_with(b">>") {
+        let name_len = value_len(rest)?;
+        let name = &rest[..name_len];
+        rest = skip_whitespace(&rest[name_len..]);
+        let len = value_len(rest)?;
+        if name.starts_with(b"/") && &name[1..] == key.as_bytes() {
+            return Some(&rest[..len]);
+        }
+        rest = skip_whitespace(&rest[len..]);
+    }
+    None
+}
+
+/// The length of the PDF object at the start of `bytes`.
+fn value_len(bytes: &[u8]) -> Option<usize> {
+    if let Some((_, len)) = reference(bytes) {
+        return Some(len);
+    }
+    match *bytes.first()? {
+        b'<' if bytes.get(1) == Some(&b'<') => container_len(bytes, 2, b">>"),
+        b'[' => container_len(bytes, 1, b"]"),
+        b'<' => bytes.iter().position(|&b| b == b'>').map(|i| i + 1),
+        b'(' => {
+            // Parentheses in literal strings are balanced, or escaped.
+            let mut depth = 0;
+            let mut i = 0;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'\\' => i += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1
+            }
+            None
+        }
+        b'/' => Some(1 + regular_len(&bytes[1..])),
+        // Numbers, booleans and null
+        _ => Some(regular_len(bytes)).filter(|&len| len > 0),
+    }
+}
+
+fn container_len(bytes: &[u8], open_len: usize, close: &[u8]) -> Option<usize> {
+    let mut i = open_len;
+    loop {
+        i = bytes.len() - skip_whitespace(&bytes[i..]).len();
+        if bytes[i..].starts_with(close) {
+            return Some(i + close.len());
+        }
+        i += value_len(&bytes[i..])?
+    }
+}
+
+/// An indirect reference such as `12 0 R` at the start of `bytes`, and its length.
+fn reference(bytes: &[u8]) -> Option<((u32, u32), usize)> {
+    let integer = |bytes: &[u8]| -> Option<(u32, usize)> {
+        let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+        let value = str::from_utf8(&bytes[..len]).ok()?.parse().ok()?;
+        // Followed by whitespace
+        match bytes.get(len) {
+            Some(&b) if is_whitespace(b) => Some((value, len)),
+            _ => None,
+        }
+    };
+    let (number, len) = integer(bytes)?;
+    let rest = skip_whitespace(&bytes[len..]);
+    let (generation, len) = integer(rest)?;
+    let rest = skip_whitespace(&rest[len..]);
+    if rest.first() != Some(&b'R') || regular_len(rest) != 1 {
+        return None;
+    }
+    Some(((number, generation), bytes.len() - rest.len() + 1))
+}
+
+/// A non-negative integer at the start of `bytes`, and its length.
+fn integer(bytes: &[u8]) -> Option<(usize, usize)> {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    let value = str::from_utf8(&bytes[..len]).ok()?.parse().ok()?;
+    Some((value, len))
+}
+
+/// The value of the integer object `value`.
+fn parse_integer(value: &[u8]) -> io::Result<usize> {
+    match integer(value) {
+        Some((integer, len)) if len == value.len() => Ok(integer),
+        _ => Err(invalid("expected an integer")),
+    }
+}
+
+/// The values of the array of integers `array`.
+fn integers(array: &[u8]) -> Option<Vec<usize>> {
+    if !array.starts_with(b"[") || !array.ends_with(b"]") {
+        return None;
+    }
+    str::from_utf8(&array[1..array.len() - 1])
+        .ok()?
+        .split_ascii_whitespace()
+        .map(|integer| integer.parse().ok())
+        .collect()
+}
+
+/// The length of the run of regular characters at the start of `bytes`.
+fn regular_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take_while(|&&b| !is_whitespace(b) && !b"()<>[]{}/%".contains(&b))
+        .count()
+}
+
+/// `bytes` without its leading whitespace and comments.
+fn skip_whitespace(mut bytes: &[u8]) -> &[u8] {
+    loop {
+        match bytes.first() {
+            Some(&b) if is_whitespace(b) => bytes = &bytes[1..],
+            Some(b'%') => {
+                let len = bytes
+                    .iter()
+                    .take_while(|&&b| b != b'\n' && b != b'\r')
+                    .count();
+                bytes = &bytes[len..]
+            }
+            _ => return bytes,
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    b"\0\t\n\x0C\r ".contains(&byte)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
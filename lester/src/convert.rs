@@ -97,5 +97,7 @@ macro_rules! try_into_both_bounded {
 
 try_into_both_bounded!(f64, i32);
 try_into_upper_bounded!(usize, i32);
+try_into_upper_bounded!(usize, u16);
+try_into_upper_bounded!(usize, u32);
 try_into_lower_bounded!(i32, usize);
 try_into_unbounded!(u32, usize);
@@ -67,16 +67,18 @@ c_error_impls! {
 }
 
 macro_rules! error_enum {
-    ($( $Variant: ident ($Type: ty), )+) => {
+    ($( $(#[$attr: meta])* $Variant: ident ($Type: ty), )+) => {
         /// An error returned by Lester.
         #[derive(Debug)]
         pub enum LesterError {
             $(
+                $(#[$attr])*
                 $Variant($Type),
             )+
         }
 
         $(
+            $(#[$attr])*
             impl From<$Type> for LesterError {
                 fn from(e: $Type) -> Self {
                     LesterError::$Variant(e)
@@ -90,4 +92,8 @@ error_enum! {
     Io(io::Error),
     Cairo(CairoError),
     Glib(GlibError),
+    #[cfg(feature = "jpeg-encoder")]
+    Jpeg(jpeg_encoder::EncodingError),
+    #[cfg(feature = "tiff")]
+    Tiff(tiff::TiffError),
 }
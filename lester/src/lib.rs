@@ -3,7 +3,7 @@
 //! This is a reimplementation of [Poppler]’s `pdftocairo` utility program as a Rust library.
 //! It loads PDF documents with Poppler
 //! and renders (rasterizes) them to in-memory pixel buffers with [cairo].
-//! It can then export to PNG.
+//! It can then export to PNG, or to JPEG and TIFF with the `jpeg-encoder` and `tiff` Cargo features.
 //!
 //! Lester is primarily intended to help test the visual rendering of PDF files generated by Victor.
 //! Reimplementing `pdftocairo` enables skipping the overhead of cross-process communication
@@ -29,13 +29,8 @@
 //! Converting a PDF file to a series of PNG files:
 //!
 //! ```rust
-//! use std::fs::File;
-//! use std::io::Read;
-//!
 //! # fn _foo() -> Result<(), lester::LesterError> {
-//! let mut bytes = Vec::new();
-//! File::open("foo.pdf")?.read_to_end(&mut bytes)?;
-//! let doc = lester::PdfDocument::from_bytes(&bytes)?;
+//! let doc = lester::PdfDocument::from_file("foo.pdf")?;
 //!
 //! for (index, page) in doc.pages().enumerate() {
 //!     let filename = format!("foo_page{}.png", index + 1);
@@ -48,6 +43,7 @@
 mod cairo;
 mod errors;
 mod poppler;
+pub mod reftest;
 
 pub use crate::cairo::*;
 pub use crate::errors::*;
@@ -55,6 +51,7 @@ pub use crate::poppler::*;
 
 // Not re-exported:
 mod cairo_ffi;
+mod catalog;
 mod convert;
 mod poppler_ffi;
 
@@ -1,49 +1,42 @@
 use crate::cairo::*;
 use crate::cairo_ffi::{CAIRO_FORMAT_ARGB32, CAIRO_FORMAT_RGB24};
+use crate::catalog;
 use crate::convert::TryInto;
-use crate::errors::{CairoError, GlibError};
+use crate::errors::{CairoError, GlibError, LesterError};
 use crate::poppler_ffi::*;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
 use std::marker::PhantomData;
 use std::ops::Range;
 use std::os::raw::*;
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::slice;
 use std::str::Utf8Error;
 
 /// A PDF document parsed by Poppler.
 pub struct PdfDocument<'data> {
     ptr: *mut PopplerDocument,
-    phantom: PhantomData<&'data [u8]>,
+    source: Source<'data>,
+}
+
+/// Where a document was loaded from, to read what Poppler does not expose.
+enum Source<'data> {
+    Bytes(&'data [u8]),
+    File(PathBuf),
 }
 
 impl<'data> PdfDocument<'data> {
     /// Parse the given bytes as PDF.
-    pub fn from_bytes(mut bytes: &'data [u8]) -> Result<Self, GlibError> {
-        // Work around https://bugs.freedesktop.org/show_bug.cgi?id=103552
-        if bytes.is_empty() {
-            bytes = b"";
-        }
-
-        let mut error = ptr::null_mut();
-        let ptr = unsafe {
-            poppler_document_new_from_data(
-                // Although this function takes *mut c_char rather than *const c_char,
-                // that pointer is only passed to Poppler’s `MemStream` abstraction
-                // which appears to only provide read access.
-                bytes.as_ptr() as *const c_char as *mut c_char,
-                bytes.len().try_into().unwrap(),
-                ptr::null(),
-                &mut error,
-            )
-        };
-        if ptr.is_null() {
-            Err(GlibError { ptr: error })
-        } else {
-            Ok(PdfDocument {
-                ptr,
-                phantom: PhantomData,
-            })
-        }
+    ///
+    /// The bytes are borrowed for the lifetime of the document.
+    /// See also `from_owned_bytes` and `from_file`.
+    pub fn from_bytes(bytes: &'data [u8]) -> Result<Self, GlibError> {
+        Ok(PdfDocument {
+            ptr: unsafe { new_from_data(bytes)? },
+            source: Source::Bytes(bytes),
+        })
     }
 
     /// Make an iterator of the pages in this document.
@@ -71,6 +64,270 @@ impl<'data> PdfDocument<'data> {
     pub fn producer(&self) -> Option<GlibString> {
         unsafe { GlibString::from_nullable_ptr(poppler_document_get_producer(self.ptr)) }
     }
+
+    /// Return where the named destination `name` points to,
+    /// or `None` if the document does not define it.
+    pub fn named_destination(&self, name: &str) -> Option<Destination> {
+        let name = CString::new(name).ok()?;
+        unsafe {
+            let ptr = poppler_document_find_dest(self.ptr, name.as_ptr());
+            if ptr.is_null() {
+                return None;
+            }
+            let result = Destination::from_poppler(&*ptr);
+            poppler_dest_free(ptr);
+            Some(result)
+        }
+    }
+
+    /// Return the names and positions of all named destinations in the document,
+    /// sorted by name.
+    ///
+    /// Only available when Lester is built with Poppler 22.06 or later.
+    #[cfg(poppler_dests_tree)]
+    pub fn named_destinations(&self) -> Vec<(String, Destination)> {
+        unsafe extern "C" fn push(key: gpointer, value: gpointer, data: gpointer) -> gboolean {
+            let destinations = &mut *(data as *mut Vec<(String, Destination)>);
+            let name = CStr::from_ptr(key as *const gchar);
+            let destination = Destination::from_poppler(&*(value as *const PopplerDest));
+            destinations.push((name.to_string_lossy().into_owned(), destination));
+            // Continue the traversal
+            0
+        }
+        let mut destinations = Vec::new();
+        unsafe {
+            let tree = poppler_document_create_dests_tree(self.ptr);
+            if !tree.is_null() {
+                let data = &mut destinations as *mut Vec<_> as gpointer;
+                g_tree_foreach(tree, Some(push), data);
+                g_tree_destroy(tree)
+            }
+        }
+        destinations
+    }
+
+    /// Return the `OpenAction` entry of the document catalog:
+    /// what viewers do when opening the document, or `None` if nothing.
+    ///
+    /// Poppler’s glib API does not expose it, so it is read from the PDF syntax.
+    /// This returns an error for documents whose catalog is in a compressed object stream,
+    /// or only in a compressed cross-reference stream.
+    pub fn open_action(&self) -> io::Result<Option<OpenAction>> {
+        match &self.source {
+            Source::Bytes(bytes) => catalog::open_action(bytes),
+            Source::File(path) => catalog::open_action(&fs::read(path)?),
+        }
+    }
+
+    /// Return the logical structure of a tagged PDF document,
+    /// or an empty vector if the document is not tagged.
+    ///
+    /// Marked content and object references (the leaves of the structure tree
+    /// that point into page content) are not included, only structure elements.
+    pub fn structure_tree(&self) -> Vec<StructureElement> {
+        unsafe { structure_elements(poppler_structure_element_iter_new(self.ptr)) }
+    }
+}
+
+/// A position in a document, see `PdfDocument::named_destination`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Destination {
+    /// The index of the page, starting at 0, or `None` if it could not be resolved.
+    pub page: Option<usize>,
+
+    /// In PostScript points from the bottom-left corner of the page as stored in PDF.
+    /// Only meaningful for destinations that specify a position within the page.
+    pub left: f64,
+    pub top: f64,
+}
+
+impl Destination {
+    fn from_poppler(dest: &PopplerDest) -> Self {
+        Destination {
+            // Poppler numbers pages from 1, or 0 if the destination is not resolved
+            page: TryInto::<usize>::try_into(dest.page_num)
+                .ok()
+                .and_then(|n| n.checked_sub(1)),
+            left: dest.left,
+            top: dest.top,
+        }
+    }
+}
+
+/// The action that a viewer performs when opening a document,
+/// see `PdfDocument::open_action`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenAction {
+    /// The action type, such as `GoTo`, `URI` or `JavaScript`.
+    /// A destination given instead of an action counts as `GoTo`.
+    pub kind: String,
+
+    /// The PDF syntax of the action dictionary or destination array.
+    /// Indirect references within it are not resolved.
+    pub source: String,
+}
+
+/// An element of the logical structure of a tagged PDF document,
+/// see `PdfDocument::structure_tree`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureElement {
+    /// The standard structure type of this element, such as `P`, `H1`, `Table` or `Figure`.
+    ///
+    /// See § 14.8.4 “Standard Structure Types” of ISO 32000-1 (PDF 1.7).
+    pub kind: &'static str,
+
+    /// The index of the page this element is on, starting at 0,
+    /// or `None` if it does not specify one.
+    pub page: Option<usize>,
+
+    /// The `BBox` attribute of this element: `[x1, y1, x2, y2]` in PostScript points,
+    /// from the bottom-left corner of the page as stored in PDF.
+    /// Only some elements, such as figures and tables, have one.
+    pub bounding_box: Option<[f64; 4]>,
+
+    pub children: Vec<StructureElement>,
+}
+
+/// Names of `PopplerStructureElementKind` values, in order.
+#[rustfmt::skip]
+static STRUCTURE_TYPES: &[&str] = &[
+    "MCR", "OBJR", "Document", "Part", "Art", "Sect", "Div", "Span", "Quote", "Note",
+    "Reference", "BibEntry", "Code", "Link", "Annot", "BlockQuote", "Caption", "NonStruct",
+    "TOC", "TOCI", "Index", "Private", "P", "H", "H1", "H2", "H3", "H4", "H5", "H6", "L", "LI",
+    "Lbl", "LBody", "Table", "TR", "TH", "TD", "THead", "TFoot", "TBody", "Ruby", "RB", "RT",
+    "RP", "Warichu", "WT", "WP", "Figure", "Formula", "Form",
+];
+
+/// Collect the structure elements of `iter` and its siblings, then free it.
+unsafe fn structure_elements(iter: *mut PopplerStructureElementIter) -> Vec<StructureElement> {
+    let mut elements = Vec::new();
+    if iter.is_null() {
+        return elements;
+    }
+    loop {
+        let ptr = poppler_structure_element_iter_get_element(iter);
+        if poppler_structure_element_is_content(ptr) == 0 {
+            let kind = poppler_structure_element_get_kind(ptr);
+            let page = poppler_structure_element_get_page(ptr);
+            let mut rect = PopplerRectangle {
+                x1: 0.,
+                y1: 0.,
+                x2: 0.,
+                y2: 0.,
+            };
+            let has_bounding_box = poppler_structure_element_get_bounding_box(ptr, &mut rect) != 0;
+            elements.push(StructureElement {
+                kind: STRUCTURE_TYPES.get(kind as usize).copied().unwrap_or("?"),
+                page: page.try_into().ok(),
+                bounding_box: if has_bounding_box {
+                    Some([rect.x1, rect.y1, rect.x2, rect.y2])
+                } else {
+                    None
+                },
+                children: structure_elements(poppler_structure_element_iter_get_child(iter)),
+            })
+        }
+        g_object_unref(ptr as *mut c_void);
+        if poppler_structure_element_iter_next(iter) == 0 {
+            break;
+        }
+    }
+    poppler_structure_element_iter_free(iter);
+    elements
+}
+
+impl PdfDocument<'static> {
+    /// Parse the given bytes as PDF, taking ownership of them.
+    pub fn from_owned_bytes(bytes: Vec<u8>) -> Result<Self, GlibError> {
+        // Pages keep their document alive, so the bytes are attached to the Poppler document
+        // rather than kept in this struct. glib frees them when the document is finalized,
+        // after Poppler is done with them.
+        unsafe extern "C" fn drop_vec(user_data: gpointer) {
+            drop(Box::from_raw(user_data as *mut Vec<u8>))
+        }
+        let bytes = Box::new(bytes);
+        unsafe {
+            let ptr = new_from_data(&bytes)?;
+            // Valid as long as `ptr` is
+            let source = Source::Bytes(slice::from_raw_parts(bytes.as_ptr(), bytes.len()));
+            g_object_set_data_full(
+                ptr as gpointer,
+                b"lester-owned-bytes\0".as_ptr() as *const gchar,
+                Box::into_raw(bytes) as gpointer,
+                Some(drop_vec),
+            );
+            Ok(PdfDocument { ptr, source })
+        }
+    }
+
+    /// Parse the PDF file at the given path.
+    ///
+    /// Poppler reads the file as needed rather than loading it into memory all at once.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LesterError> {
+        // `g_filename_to_uri` requires an absolute path
+        let path = fs::canonicalize(path)?;
+        let filename = path_to_c_string(&path)?;
+        let mut error = ptr::null_mut();
+        let uri = unsafe {
+            GlibString::from_nullable_ptr(g_filename_to_uri(
+                filename.as_ptr(),
+                ptr::null(),
+                &mut error,
+            ))
+        };
+        let uri = uri.ok_or_else(|| GlibError { ptr: error })?;
+        let mut error = ptr::null_mut();
+        let ptr = unsafe { poppler_document_new_from_file(uri.ptr, ptr::null(), &mut error) };
+        if ptr.is_null() {
+            return Err(GlibError { ptr: error }.into());
+        }
+        Ok(PdfDocument {
+            ptr,
+            source: Source::File(path),
+        })
+    }
+}
+
+unsafe fn new_from_data(mut bytes: &[u8]) -> Result<*mut PopplerDocument, GlibError> {
+    // Work around https://bugs.freedesktop.org/show_bug.cgi?id=103552
+    if bytes.is_empty() {
+        bytes = b"";
+    }
+
+    let mut error = ptr::null_mut();
+    let ptr = poppler_document_new_from_data(
+        // Although this function takes *mut c_char rather than *const c_char,
+        // that pointer is only passed to Poppler’s `MemStream` abstraction
+        // which appears to only provide read access.
+        bytes.as_ptr() as *const c_char as *mut c_char,
+        bytes.len().try_into().unwrap(),
+        ptr::null(),
+        &mut error,
+    );
+    if ptr.is_null() {
+        Err(GlibError { ptr: error })
+    } else {
+        Ok(ptr)
+    }
+}
+
+#[cfg(unix)]
+fn path_to_c_string(path: &Path) -> io::Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(not(unix))]
+fn path_to_c_string(path: &Path) -> io::Result<CString> {
+    // FIXME: glib expects the GLib file name encoding, which is UTF-8 on Windows
+    let path = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file name is not valid Unicode",
+        )
+    })?;
+    CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
 }
 
 impl<'data> Drop for PdfDocument<'data> {
@@ -165,6 +422,44 @@ impl<'data> Page<'data> {
 
     /// Render (rasterize) this page with the given options to a new image surface.
     pub fn render_with_options(&self, options: RenderOptions) -> Result<ImageSurface, CairoError> {
+        self.render_with(options, |_, _| {})
+    }
+
+    /// Render (rasterize) this page with the given options to a new image surface,
+    /// calling `callback` with the cairo context before and after the page itself is drawn.
+    ///
+    /// At `RenderStage::BeforePage` the backdrop (if any) has been painted,
+    /// and user space is in pixels.
+    /// Transformations and clipping set up at that stage apply to the page
+    /// and are still in effect at `RenderStage::AfterPage`.
+    ///
+    /// ```rust
+    /// # use lester::{RenderOptions, RenderStage};
+    /// # fn _foo(page: lester::Page) -> Result<(), lester::CairoError> {
+    /// // Only render the top-left quarter of the page, and outline it in red.
+    /// let (width, height) = page.size_in_css_px();
+    /// page.render_with(RenderOptions::default(), |cr, stage| match stage {
+    ///     RenderStage::BeforePage => {
+    ///         cr.rectangle(0., 0., width / 2., height / 2.);
+    ///         cr.clip();
+    ///     }
+    ///     RenderStage::AfterPage => {
+    ///         cr.set_source_rgb(1., 0., 0.);
+    ///         cr.rectangle(0., 0., width / 2., height / 2.);
+    ///         cr.stroke();
+    ///     }
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render_with<F>(
+        &self,
+        options: RenderOptions,
+        mut callback: F,
+    ) -> Result<ImageSurface, CairoError>
+    where
+        F: FnMut(&mut CairoContext, RenderStage),
+    {
         let RenderOptions {
             dppx_x,
             dppx_y,
@@ -185,6 +480,8 @@ impl<'data> Page<'data> {
             context.set_source_rgb(1., 1., 1.);
             context.paint();
         }
+        callback(&mut context, RenderStage::BeforePage);
+        context.save();
         context.scale(dppx_x * PX_PER_PT, dppx_y * PX_PER_PT);
         unsafe {
             if for_printing {
@@ -193,6 +490,8 @@ impl<'data> Page<'data> {
                 poppler_page_render(self.ptr, context.ptr)
             }
         }
+        context.restore();
+        callback(&mut context, RenderStage::AfterPage);
         context.check_status()?;
         Ok(surface)
     }
@@ -246,7 +545,11 @@ pub struct RenderOptions {
     pub backdrop: Backdrop,
 
     /// Whether to use `poppler_page_render_for_printing` instead of `poppler_page_render`.
-    /// What that does excactly doesn’t seem well-documented.
+    ///
+    /// This renders the page as it would be printed:
+    /// annotations are drawn only if their Print flag is set, form field widgets included,
+    /// and images are not smoothed when scaled (see the `pattern_4x4_pdf` test).
+    /// Since Victor’s output is meant for print, reftests should generally set this.
     pub for_printing: bool,
 }
 
@@ -261,6 +564,15 @@ impl Default for RenderOptions {
     }
 }
 
+/// When a `Page::render_with` callback is called
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderStage {
+    /// Before the page is drawn, after the backdrop
+    BeforePage,
+    /// After the page is drawn
+    AfterPage,
+}
+
 /// What background to render pages on
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Backdrop {
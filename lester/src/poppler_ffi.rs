@@ -10,18 +10,66 @@ extern "C" {
         password: *const c_char,
         error: *mut *mut GError,
     ) -> *mut PopplerDocument;
+    pub fn poppler_document_new_from_file(
+        uri: *const c_char,
+        password: *const c_char,
+        error: *mut *mut GError,
+    ) -> *mut PopplerDocument;
     pub fn poppler_document_get_n_pages(document: *mut PopplerDocument) -> c_int;
     pub fn poppler_document_get_page(
         document: *mut PopplerDocument,
         index: c_int,
     ) -> *mut PopplerPage;
     pub fn poppler_document_get_producer(document: *mut PopplerDocument) -> *mut gchar;
+    pub fn poppler_document_find_dest(
+        document: *mut PopplerDocument,
+        link_name: *const gchar,
+    ) -> *mut PopplerDest;
+    pub fn poppler_dest_free(dest: *mut PopplerDest);
+    #[cfg(poppler_dests_tree)]
+    pub fn poppler_document_create_dests_tree(document: *mut PopplerDocument) -> *mut GTree;
 
     pub fn poppler_page_get_size(page: *mut PopplerPage, width: *mut f64, height: *mut f64);
     pub fn poppler_page_render(page: *mut PopplerPage, cairo: *mut cairo_t);
     pub fn poppler_page_render_for_printing(page: *mut PopplerPage, cairo: *mut cairo_t);
     pub fn poppler_page_get_text(page: *mut PopplerPage) -> *mut c_char;
 
+    pub fn poppler_structure_element_iter_new(
+        document: *mut PopplerDocument,
+    ) -> *mut PopplerStructureElementIter;
+    pub fn poppler_structure_element_iter_get_child(
+        parent: *mut PopplerStructureElementIter,
+    ) -> *mut PopplerStructureElementIter;
+    pub fn poppler_structure_element_iter_get_element(
+        iter: *mut PopplerStructureElementIter,
+    ) -> *mut PopplerStructureElement;
+    pub fn poppler_structure_element_iter_next(iter: *mut PopplerStructureElementIter) -> gboolean;
+    pub fn poppler_structure_element_iter_free(iter: *mut PopplerStructureElementIter);
+    pub fn poppler_structure_element_get_kind(
+        element: *mut PopplerStructureElement,
+    ) -> PopplerStructureElementKind;
+    pub fn poppler_structure_element_is_content(element: *mut PopplerStructureElement) -> gboolean;
+    pub fn poppler_structure_element_get_page(element: *mut PopplerStructureElement) -> gint;
+    pub fn poppler_structure_element_get_bounding_box(
+        element: *mut PopplerStructureElement,
+        bounding_box: *mut PopplerRectangle,
+    ) -> gboolean;
+
+    pub fn g_filename_to_uri(
+        filename: *const gchar,
+        hostname: *const gchar,
+        error: *mut *mut GError,
+    ) -> *mut gchar;
+    pub fn g_object_set_data_full(
+        object: gpointer,
+        key: *const gchar,
+        data: gpointer,
+        destroy: GDestroyNotify,
+    );
+    #[cfg(poppler_dests_tree)]
+    pub fn g_tree_foreach(tree: *mut GTree, func: GTraverseFunc, user_data: gpointer);
+    #[cfg(poppler_dests_tree)]
+    pub fn g_tree_destroy(tree: *mut GTree);
     pub fn g_error_free(error: *mut GError);
     pub fn g_object_unref(object: gpointer);
     pub fn g_free(mem: gpointer);
@@ -30,8 +78,15 @@ extern "C" {
 pub type gpointer = *mut c_void;
 pub type gchar = c_char;
 pub type gint = c_int;
+pub type gboolean = gint;
 pub type guint32 = c_uint;
 pub type GQuark = guint32;
+pub type GDestroyNotify = Option<unsafe extern "C" fn(data: gpointer)>;
+#[cfg(poppler_dests_tree)]
+pub type GTraverseFunc =
+    Option<unsafe extern "C" fn(key: gpointer, value: gpointer, data: gpointer) -> gboolean>;
+pub type PopplerStructureElementKind = c_uint;
+pub type PopplerDestType = c_uint;
 
 #[repr(C)]
 pub struct PopplerDocument {
@@ -43,6 +98,44 @@ pub struct PopplerPage {
     opaque: [u8; 0],
 }
 
+/// The bit fields `change_left`, `change_top` and `change_zoom` that end the struct
+/// are not declared: it is only read through pointers returned by Poppler.
+#[repr(C)]
+pub struct PopplerDest {
+    pub type_: PopplerDestType,
+    pub page_num: c_int,
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+    pub top: f64,
+    pub zoom: f64,
+    pub named_dest: *mut gchar,
+}
+
+#[repr(C)]
+pub struct PopplerStructureElementIter {
+    opaque: [u8; 0],
+}
+
+#[repr(C)]
+pub struct PopplerStructureElement {
+    opaque: [u8; 0],
+}
+
+#[repr(C)]
+pub struct PopplerRectangle {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
+
+#[cfg(poppler_dests_tree)]
+#[repr(C)]
+pub struct GTree {
+    opaque: [u8; 0],
+}
+
 #[repr(C)]
 pub struct GError {
     pub domain: GQuark,
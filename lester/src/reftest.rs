@@ -0,0 +1,222 @@
+//! Comparing renderings, for reference tests.
+//!
+//! A reference test renders two documents that are expected to look the same
+//! (for example a test case, and a simpler way to achieve the same rendering)
+//! and compares the resulting pixels.
+
+use crate::cairo::{Argb32Pixels, ImageSurface};
+use crate::errors::CairoError;
+use crate::poppler::{PdfDocument, RenderOptions};
+use std::env;
+use std::path::PathBuf;
+
+/// How much two images may differ while still being considered a match.
+///
+/// This type implements the `Default` trait, which only accepts identical images.
+#[derive(Clone, Debug)]
+pub struct MatchOptions {
+    /// The number of pixels that may differ by more than `per_channel_tolerance`.
+    pub max_differing_pixels: usize,
+
+    /// How much each channel (alpha, red, green, and blue, from 0 to 255)
+    /// of a pixel may differ for that pixel to count as the same in both images.
+    pub per_channel_tolerance: u8,
+
+    /// Where `assert_images_match` writes images on failure.
+    ///
+    /// The default is a `lester-reftest` directory in `std::env::temp_dir()`.
+    pub output_dir: PathBuf,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            max_differing_pixels: 0,
+            per_channel_tolerance: 0,
+            output_dir: env::temp_dir().join("lester-reftest"),
+        }
+    }
+}
+
+/// Return whether two images have the same size
+/// and no more differences than `options` allow.
+pub fn images_match(a: &Argb32Pixels, b: &Argb32Pixels, options: &MatchOptions) -> bool {
+    match count_differing_pixels(a, b, options.per_channel_tolerance) {
+        Some(count) => count <= options.max_differing_pixels,
+        None => false,
+    }
+}
+
+/// Panic if two images do not match per `images_match`.
+///
+/// Before panicking, this writes `a.png`, `b.png`, and `diff.png` to `options.output_dir`.
+/// In `diff.png`, pixels that differ are opaque red
+/// and others are a faded copy of the first image.
+pub fn assert_images_match(a: &mut ImageSurface, b: &mut ImageSurface, options: &MatchOptions) {
+    let (a_pixels, b_pixels) = (a.pixels(), b.pixels());
+    let differing = count_differing_pixels(&a_pixels, &b_pixels, options.per_channel_tolerance);
+    let summary = match differing {
+        Some(count) if count <= options.max_differing_pixels => return,
+        Some(count) => format!(
+            "{} pixels differ by more than {} per channel, at most {} allowed",
+            count, options.per_channel_tolerance, options.max_differing_pixels
+        ),
+        None => format!(
+            "sizes differ: {}×{} and {}×{}",
+            a_pixels.width, a_pixels.height, b_pixels.width, b_pixels.height
+        ),
+    };
+    let diff = differing.map(|_| diff_image(&a_pixels, &b_pixels, options.per_channel_tolerance));
+
+    let dir = &options.output_dir;
+    std::fs::create_dir_all(dir).unwrap();
+    a.write_to_png_file(dir.join("a.png")).unwrap();
+    b.write_to_png_file(dir.join("b.png")).unwrap();
+    let mut written = vec![dir.join("a.png"), dir.join("b.png")];
+    if let Some(diff) = diff {
+        diff.write_to_png_file(dir.join("diff.png")).unwrap();
+        written.push(dir.join("diff.png"))
+    }
+    panic!(
+        "images do not match: {}\n{}",
+        summary,
+        written
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// How one page of a document differs from the page at the same index in another,
+/// see `compare_documents`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageDiff {
+    /// The page index in both documents, starting at 0
+    pub index: usize,
+
+    /// The width and height of the rendering of the page in the first document,
+    /// or `None` if it has fewer pages.
+    pub a_size: Option<(usize, usize)>,
+
+    /// Same as `a_size`, for the second document
+    pub b_size: Option<(usize, usize)>,
+
+    /// The number of pixels that differ by more than `per_channel_tolerance`,
+    /// or `None` if the sizes differ.
+    pub differing_pixels: Option<usize>,
+
+    /// The largest difference in any channel of any pixel.
+    /// This is zero if the sizes differ.
+    pub max_channel_difference: u8,
+}
+
+impl PageDiff {
+    /// Return whether these pages match, in the same way as `images_match`.
+    pub fn is_match(&self, options: &MatchOptions) -> bool {
+        match self.differing_pixels {
+            Some(count) => count <= options.max_differing_pixels,
+            None => false,
+        }
+    }
+}
+
+/// Render each pair of pages at the same index in two documents with the same options,
+/// and compare the resulting pixels with `options.per_channel_tolerance`.
+///
+/// The result has one item per page of the longer document.
+/// Use `PageDiff::is_match` to check them against `options`.
+pub fn compare_documents(
+    a: &PdfDocument,
+    b: &PdfDocument,
+    render_options: RenderOptions,
+    options: &MatchOptions,
+) -> Result<Vec<PageDiff>, CairoError> {
+    let render = |doc: &PdfDocument, index| {
+        doc.pages()
+            .nth(index)
+            .map(|page| page.render_with_options(render_options))
+            .transpose()
+    };
+    let page_count = a.pages().len().max(b.pages().len());
+    (0..page_count)
+        .map(|index| {
+            let (mut a, mut b) = (render(a, index)?, render(b, index)?);
+            let a_pixels = a.as_mut().map(|a| a.pixels());
+            let b_pixels = b.as_mut().map(|b| b.pixels());
+            let size = |pixels: &Option<Argb32Pixels>| pixels.as_ref().map(|p| (p.width, p.height));
+            let (a_size, b_size) = (size(&a_pixels), size(&b_pixels));
+            let (differing_pixels, max_channel_difference) = match (&a_pixels, &b_pixels) {
+                (Some(a), Some(b)) if a_size == b_size => (
+                    count_differing_pixels(a, b, options.per_channel_tolerance),
+                    max_channel_difference(a, b),
+                ),
+                _ => (None, 0),
+            };
+            Ok(PageDiff {
+                index,
+                a_size,
+                b_size,
+                differing_pixels,
+                max_channel_difference,
+            })
+        })
+        .collect()
+}
+
+fn max_channel_difference(a: &Argb32Pixels, b: &Argb32Pixels) -> u8 {
+    a.buffer
+        .iter()
+        .zip(b.buffer.iter())
+        .flat_map(|(&a, &b)| {
+            let (a, b) = (a.to_be_bytes(), b.to_be_bytes());
+            (0..4).map(move |i| a[i].max(b[i]) - a[i].min(b[i]))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn pixels_match(a: u32, b: u32, per_channel_tolerance: u8) -> bool {
+    let (a, b) = (a.to_be_bytes(), b.to_be_bytes());
+    a.iter()
+        .zip(&b)
+        .all(|(&a, &b)| (i16::from(a) - i16::from(b)).abs() <= i16::from(per_channel_tolerance))
+}
+
+/// `None` if the sizes differ
+fn count_differing_pixels(
+    a: &Argb32Pixels,
+    b: &Argb32Pixels,
+    per_channel_tolerance: u8,
+) -> Option<usize> {
+    if (a.width, a.height) != (b.width, b.height) {
+        return None;
+    }
+    Some(
+        a.buffer
+            .iter()
+            .zip(b.buffer.iter())
+            .filter(|&(&a, &b)| !pixels_match(a, b, per_channel_tolerance))
+            .count(),
+    )
+}
+
+fn diff_image(a: &Argb32Pixels, b: &Argb32Pixels, per_channel_tolerance: u8) -> ImageSurface {
+    const RED: u32 = 0xFFFF_0000;
+    let mut diff = ImageSurface::new_argb32(a.width, a.height).unwrap();
+    let diff_pixels = diff.pixels();
+    for ((pixel, &a), &b) in diff_pixels
+        .buffer
+        .iter_mut()
+        .zip(a.buffer.iter())
+        .zip(b.buffer.iter())
+    {
+        *pixel = if pixels_match(a, b, per_channel_tolerance) {
+            // Premultiplied alpha, so all channels fade together
+            (a >> 2) & 0x3F3F_3F3F
+        } else {
+            RED
+        }
+    }
+    diff
+}
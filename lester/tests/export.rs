@@ -0,0 +1,35 @@
+#![cfg(any(feature = "jpeg-encoder", feature = "tiff"))]
+
+use lester::ImageSurface;
+use std::fs;
+use std::path::PathBuf;
+
+static PNG_BYTES: &[u8] = include_bytes!("pattern_4x4.png");
+
+fn read_back_and_remove(path: PathBuf) -> Vec<u8> {
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    bytes
+}
+
+#[test]
+#[cfg(feature = "jpeg-encoder")]
+fn write_jpeg() {
+    let surface = ImageSurface::read_from_png(PNG_BYTES).unwrap();
+    let path = std::env::temp_dir().join("lester_pattern_4x4.jpg");
+    surface.write_to_jpeg_file(&path, 90).unwrap();
+    let bytes = read_back_and_remove(path);
+    // Start of image, then end of image markers
+    assert_eq!(&bytes[..2], b"\xFF\xD8");
+    assert_eq!(&bytes[bytes.len() - 2..], b"\xFF\xD9");
+}
+
+#[test]
+#[cfg(feature = "tiff")]
+fn write_tiff() {
+    let surface = ImageSurface::read_from_png(PNG_BYTES).unwrap();
+    let path = std::env::temp_dir().join("lester_pattern_4x4.tiff");
+    surface.write_to_tiff_file(&path).unwrap();
+    let bytes = read_back_and_remove(path);
+    assert!(bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*"));
+}
@@ -26,3 +26,86 @@ out = cairo.PDFSurface(os.path.join(this, "pattern_4x4.pdf"),
 ctx = cairo.Context(out)
 ctx.set_source(pattern)
 ctx.paint()
+
+
+def write_pdf(path, objects):
+    """Write a PDF file with the given objects, numbered from 1, and the catalog first."""
+    out = b"%PDF-1.4\n"
+    offsets = []
+    for number, body in enumerate(objects, 1):
+        offsets.append(len(out))
+        out += b"%d 0 obj\n%s\nendobj\n" % (number, body)
+    xref = len(out)
+    out += b"xref\n0 %d\n0000000000 65535 f \n" % (len(objects) + 1)
+    for offset in offsets:
+        out += b"%010d 00000 n \n" % offset
+    out += b"trailer\n<< /Size %d /Root 1 0 R >>\nstartxref\n%d\n%%%%EOF\n" % (
+        len(objects) + 1, xref)
+    with open(path, "wb") as f:
+        f.write(out)
+
+write_pdf(os.path.join(this, "destinations.pdf"), [
+    b"<< /Type /Catalog /Pages 2 0 R /Dests 5 0 R /OpenAction 6 0 R >>",
+    b"<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+    b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+    b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+    b"<< /second [4 0 R /XYZ 10 150 null] /first [3 0 R /Fit] >>",
+    b"<< /S /GoTo /D /second >>",
+])
+
+
+def write_pdf_with_object_stream(path, compressed, objects):
+    """Write a PDF 1.5 file with a cross-reference stream.
+
+    The `compressed` objects (the catalog first) are numbered from 1 and stored in an object stream,
+    which is not actually compressed. The other `objects` are numbered after them,
+    and written after the cross-reference stream.
+    """
+    numbers = range(1, len(compressed) + 1)
+    header = b"".join(b"%d %d " % (n, sum(map(len, compressed[:n - 1])))
+                      for n in numbers)
+    object_stream_number = len(compressed) + len(objects) + 1
+    xref_number = object_stream_number + 1
+    object_stream = b"<< /Type /ObjStm /N %d /First %d /Length %d >>\nstream\n%s%s\nendstream" % (
+        len(compressed), len(header), len(header + b"".join(compressed)),
+        header, b"".join(compressed))
+
+    def write(offsets):
+        out = b"%PDF-1.5\n"
+        new_offsets = {}
+        def add(number, body):
+            new_offsets[number] = len(out)
+            return b"%d 0 obj\n%s\nendobj\n" % (number, body)
+        out += add(object_stream_number, object_stream)
+        # Type 0 for the free object 0, 1 for an offset, 2 for an index in the object stream
+        entries = [(0, 0, 0xFFFF)]
+        entries += [(2, object_stream_number, i) for i in range(len(compressed))]
+        entries += [(1, offsets.get(n, 0), 0)
+                    for n in range(len(compressed) + 1, xref_number + 1)]
+        data = b"".join(bytes(bytearray([t, a >> 8, a & 0xFF, b >> 8, b & 0xFF]))
+                        for t, a, b in entries)
+        xref = b"<< /Type /XRef /Size %d /W [1 2 2] /Root 1 0 R /Length %d >>\nstream\n%s\nendstream" % (
+            xref_number + 1, len(data), data)
+        xref_offset = len(out)
+        out += add(xref_number, xref)
+        for number, body in enumerate(objects, len(compressed) + 1):
+            out += add(number, body)
+        out += b"startxref\n%d\n%%%%EOF\n" % xref_offset
+        return out, new_offsets
+
+    # Object sizes do not depend on the offsets, so the second pass has the right ones.
+    out, offsets = write({})
+    out, _ = write(offsets)
+    with open(path, "wb") as f:
+        f.write(out)
+
+# The `/Root` in the content stream, after the cross-reference stream, is not that of the trailer.
+content = b"% /Root 3 0 R\n"
+write_pdf_with_object_stream(os.path.join(this, "object_streams.pdf"), [
+    b"<< /Type /Catalog /Pages 2 0 R /OpenAction 5 0 R >>",
+    b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+    b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R >>",
+], [
+    b"<< /Length %d >>\nstream\n%s\nendstream" % (len(content), content),
+    b"<< /S /GoTo /D [3 0 R /Fit] >>",
+])
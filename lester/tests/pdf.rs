@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate lester;
 
-use lester::{PdfDocument, RenderOptions};
+use lester::{Destination, PdfDocument, RenderOptions, RenderStage};
 use std::error::Error;
 
 #[test]
@@ -34,6 +34,68 @@ fn blank_pdf() {
     let (width, height) = page.size_in_ps_points();
     assert_approx_eq!(width, millimeters_to_poscript_points(210.));
     assert_approx_eq!(height, millimeters_to_poscript_points(297.));
+    assert_eq!(doc.structure_tree(), []);
+    assert_eq!(doc.named_destination("foo"), None);
+    assert_eq!(doc.open_action().unwrap(), None);
+}
+
+#[test]
+fn destinations() {
+    static PDF_BYTES: &[u8] = include_bytes!("destinations.pdf");
+    let doc = PdfDocument::from_bytes(PDF_BYTES).unwrap();
+    let second = Destination {
+        page: Some(1),
+        left: 10.,
+        top: 150.,
+    };
+    assert_eq!(doc.named_destination("second"), Some(second));
+    assert_eq!(doc.named_destination("first").unwrap().page, Some(0));
+    assert_eq!(doc.named_destination("third"), None);
+    #[cfg(poppler_dests_tree)]
+    {
+        let destinations = doc.named_destinations();
+        let names: Vec<_> = destinations.iter().map(|(name, _)| &**name).collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(destinations[1].1, second);
+    }
+
+    let action = doc.open_action().unwrap().unwrap();
+    assert_eq!(action.kind, "GoTo");
+    assert_eq!(action.source, "<< /S /GoTo /D /second >>");
+}
+
+#[test]
+fn object_streams() {
+    // The catalog is in an object stream, found through a cross-reference stream.
+    // A `/Root` in a content stream after them is not mistaken for that of the trailer.
+    static PDF_BYTES: &[u8] = include_bytes!("object_streams.pdf");
+    let doc = PdfDocument::from_bytes(PDF_BYTES).unwrap();
+    assert_eq!(doc.pages().len(), 1);
+    let action = doc.open_action().unwrap().unwrap();
+    assert_eq!(action.kind, "GoTo");
+    assert_eq!(action.source, "<< /S /GoTo /D [3 0 R /Fit] >>");
+}
+
+#[test]
+fn owned_and_file_pdf() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/A4_one_empty_page.pdf");
+    let doc = PdfDocument::from_file(path).unwrap();
+    assert_eq!(doc.pages().len(), 1);
+    assert_eq!(doc.open_action().unwrap(), None);
+
+    let doc = PdfDocument::from_owned_bytes(std::fs::read(path).unwrap()).unwrap();
+    assert_eq!(doc.pages().len(), 1);
+    let page = doc.pages().next().unwrap();
+    // The page keeps the document and its bytes alive
+    drop(doc);
+    let (width, _) = page.size_in_ps_points();
+    assert_approx_eq!(width, millimeters_to_poscript_points(210.));
+
+    match PdfDocument::from_file("does-not-exist.pdf") {
+        Err(lester::LesterError::Io(_)) => {}
+        Err(err) => panic!("expected an IO error, got {:?}", err),
+        Ok(_) => panic!("expected error"),
+    }
 }
 
 fn millimeters_to_poscript_points(mm: f64) -> f64 {
@@ -81,3 +143,45 @@ fn pattern_4x4_pdf() {
     let pixels = surface.pixels();
     assert_eq!((pixels.width, pixels.height), (8, 12));
 }
+
+#[test]
+fn render_with_callback() {
+    static PDF_BYTES: &[u8] = include_bytes!("pattern_4x4.pdf");
+    let doc = PdfDocument::from_bytes(PDF_BYTES).unwrap();
+    let page = doc.pages().next().unwrap();
+    let options = RenderOptions {
+        for_printing: true,
+        ..RenderOptions::default()
+    };
+    let mut stages = Vec::new();
+    let mut surface = page
+        .render_with(options, |cr, stage| {
+            stages.push(stage);
+            match stage {
+                RenderStage::BeforePage => {
+                    cr.rectangle(0., 0., 2., 2.);
+                    cr.clip();
+                }
+                RenderStage::AfterPage => {
+                    cr.set_source_rgb(0., 1., 0.);
+                    cr.rectangle(1., 1., 1., 1.);
+                    cr.fill();
+                }
+            }
+        })
+        .unwrap();
+    assert_eq!(stages, [RenderStage::BeforePage, RenderStage::AfterPage]);
+    const RED: u32 = 0xFFFF_0000;
+    const BLUE: u32 = 0xFF00_00FF;
+    const LIME: u32 = 0xFF00_FF00;
+    #[rustfmt::skip]
+    assert_pixels_eq!(
+        surface.pixels().buffer,
+        &[
+            RED,  BLUE, 0, 0,
+            BLUE, LIME, 0, 0,
+            0,    0,    0, 0,
+            0,    0,    0, 0,
+        ]
+    );
+}
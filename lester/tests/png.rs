@@ -34,6 +34,26 @@ fn round_trip_png() {
     assert_expected_pixels(surface2.pixels());
 }
 
+#[test]
+fn rgba8_round_trip() {
+    #[rustfmt::skip]
+    let rgba = [
+        0xFF, 0x00, 0x00, 0xFF,  0x00, 0x00, 0xFF, 0x80,
+        0x00, 0x00, 0x00, 0x00,  0x00, 0x33, 0x99, 0xCC,
+    ];
+    let mut surface = ImageSurface::from_rgba8(2, 2, &rgba).unwrap();
+    let pixels = surface.pixels();
+    // Premultiplied
+    assert_eq!(
+        pixels.buffer,
+        &[0xFFFF_0000, 0x8000_0080, 0x0000_0000, 0xCC00_297A]
+    );
+    assert_eq!(pixels.to_rgba8(), rgba);
+
+    assert!(ImageSurface::from_rgba8(2, 2, &rgba[4..]).is_err());
+    assert!(surface.pixels().copy_from_rgba8(&rgba[4..]).is_err());
+}
+
 #[test]
 fn zero_bytes_png() {
     expect_io_error_kind(
@@ -0,0 +1,80 @@
+use lester::reftest::{assert_images_match, compare_documents, images_match, MatchOptions};
+use lester::{ImageSurface, PdfDocument, RenderOptions};
+
+static PNG_BYTES: &[u8] = include_bytes!("pattern_4x4.png");
+
+/// Two 4×4 images where one pixel differs by 2 in the blue channel
+/// and another is completely different.
+fn slightly_different_images() -> (ImageSurface, ImageSurface) {
+    let a = ImageSurface::read_from_png(PNG_BYTES).unwrap();
+    let mut b = ImageSurface::read_from_png(PNG_BYTES).unwrap();
+    let pixels = b.pixels();
+    pixels.buffer[5] -= 2;
+    pixels.buffer[6] = 0;
+    (a, b)
+}
+
+#[test]
+fn match_with_tolerance() {
+    let (mut a, mut b) = slightly_different_images();
+    let (a, b) = (a.pixels(), b.pixels());
+    let options = |max_differing_pixels, per_channel_tolerance| MatchOptions {
+        max_differing_pixels,
+        per_channel_tolerance,
+        ..MatchOptions::default()
+    };
+    assert!(images_match(&a, &a, &MatchOptions::default()));
+    assert!(!images_match(&a, &b, &MatchOptions::default()));
+    assert!(images_match(&a, &b, &options(2, 0)));
+    assert!(images_match(&a, &b, &options(1, 2)));
+    assert!(!images_match(&a, &b, &options(1, 1)));
+}
+
+#[test]
+fn size_mismatch() {
+    let mut a = ImageSurface::read_from_png(PNG_BYTES).unwrap();
+    let mut b = ImageSurface::new_argb32(4, 5).unwrap();
+    assert!(!images_match(
+        &a.pixels(),
+        &b.pixels(),
+        &MatchOptions {
+            max_differing_pixels: 100,
+            ..MatchOptions::default()
+        }
+    ));
+}
+
+#[test]
+#[should_panic(expected = "2 pixels differ by more than 0 per channel, at most 1 allowed")]
+fn assert_failure() {
+    let (mut a, mut b) = slightly_different_images();
+    assert_images_match(
+        &mut a,
+        &mut b,
+        &MatchOptions {
+            max_differing_pixels: 1,
+            output_dir: std::env::temp_dir().join("lester-reftest-assert-failure"),
+            ..MatchOptions::default()
+        },
+    );
+}
+
+#[test]
+fn compare_pdf_documents() {
+    let pattern = PdfDocument::from_bytes(include_bytes!("pattern_4x4.pdf")).unwrap();
+    let a4 = PdfDocument::from_bytes(include_bytes!("A4_one_empty_page.pdf")).unwrap();
+    let options = MatchOptions::default();
+
+    let diffs = compare_documents(&pattern, &pattern, RenderOptions::default(), &options).unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].a_size, Some((4, 4)));
+    assert_eq!(diffs[0].differing_pixels, Some(0));
+    assert_eq!(diffs[0].max_channel_difference, 0);
+    assert!(diffs[0].is_match(&options));
+
+    let diffs = compare_documents(&pattern, &a4, RenderOptions::default(), &options).unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_ne!(diffs[0].a_size, diffs[0].b_size);
+    assert_eq!(diffs[0].differing_pixels, None);
+    assert!(!diffs[0].is_match(&options));
+}
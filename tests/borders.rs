@@ -0,0 +1,29 @@
+use victor::dom::{DashedBorders, Document};
+
+#[test]
+fn dashed_borders() {
+    let mut doc = Document::parse_html(
+        br#"
+        <body style="margin: 0">
+            <div style="width: 12px; border-top: 2px dashed #00f"></div>
+        </body>
+        "#,
+    );
+    const BLUE: u32 = 0xFF00_00FF;
+    const ____: u32 = 0x0000_0000;
+    for &mode in &[DashedBorders::Segments, DashedBorders::DashPattern] {
+        doc.set_dashed_borders(mode);
+        let pdf_bytes = doc.to_pdf_bytes();
+        let pdf = lester::PdfDocument::from_bytes(&pdf_bytes).unwrap();
+        let mut surface = pdf.pages().next().unwrap().render().unwrap();
+        let pixels = surface.pixels();
+        let row = |y: usize| &pixels.buffer[y * pixels.width..][..13];
+
+        // Dashes twice as long as the border is wide, with gaps as long as it is wide
+        #[rustfmt::skip]
+        let dashes = [BLUE, BLUE, BLUE, BLUE, ____, ____, BLUE, BLUE, BLUE, BLUE, ____, ____, ____];
+        assert_eq!(row(0), dashes, "{:?}", mode);
+        assert_eq!(row(1), dashes, "{:?}", mode);
+        assert_eq!(row(2), [____; 13], "{:?}", mode);
+    }
+}
@@ -0,0 +1,24 @@
+use victor::dom::{CancellationToken, Document, RenderError};
+use victor::fonts::FontContext;
+
+#[test]
+fn cancellation() {
+    let html: &[u8] = b"<p id=a>a<div style='position: absolute'><p id=b>b</div>";
+    let mut doc = Document::parse_html(html);
+    let token = CancellationToken::new();
+    doc.set_cancellation_token(Some(token.clone()));
+
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert!(report.anchor("a").is_some());
+    assert!(report.anchor("b").is_some());
+
+    token.cancel();
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert!(report.anchors.is_empty());
+    assert_eq!(report.page_count(), 1);
+    assert_eq!(
+        doc.try_to_pdf_bytes_and_layout_report(FontContext::shared())
+            .map(|_| ()),
+        Err(RenderError::Cancelled)
+    );
+}
@@ -0,0 +1,441 @@
+use victor::computed_style::{Display, LengthOrPercentageOrAuto};
+use victor::dom::Document;
+use victor::primitives::RGBA;
+
+#[test]
+fn computed_style_of() {
+    let doc = Document::parse_html(
+        br#"
+        <style>
+            p { margin: 1em auto 10%; color: rgb(0, 128, 255) }
+            .big { font-size: 20px; float: left }
+        </style>
+        <body style="font-size: 10px">
+            <p>One</p>
+            <p class=big>Two</p>
+            <span style="display: flow-root">Three</span>
+        </body>
+        "#,
+    );
+
+    let p = doc.computed_style_of("p").unwrap();
+    assert_eq!(p.display(), Display::Block);
+    assert_eq!(p.font_size(), 10.);
+    let margin = p.margin();
+    assert_eq!(margin.top, LengthOrPercentageOrAuto::Length(10.));
+    assert_eq!(margin.right, LengthOrPercentageOrAuto::Auto);
+    assert_eq!(margin.bottom, LengthOrPercentageOrAuto::Percentage(10.));
+    assert_eq!(margin.left, LengthOrPercentageOrAuto::Auto);
+    assert!(p.color() == RGBA(0., 128. / 255., 1., 1.));
+
+    let big = doc.computed_style_of("p.big").unwrap();
+    assert_eq!(big.font_size(), 20.);
+    assert_eq!(big.margin().top, LengthOrPercentageOrAuto::Length(20.));
+
+    let span = doc.computed_style_of("body > span").unwrap();
+    assert_eq!(span.display(), Display::FlowRoot);
+    assert!(span.background_color() == RGBA(0., 0., 0., 0.));
+
+    assert!(doc.computed_style_of("div").is_none());
+    assert!(doc.computed_style_of("p[").is_none());
+}
+
+#[test]
+fn hidden_attribute_and_details() {
+    let doc = Document::parse_html(
+        br#"
+        <p hidden>One</p>
+        <details><p>Two</p><summary>Three</summary></details>
+        "#,
+    );
+    let display = |selectors| doc.computed_style_of(selectors).unwrap().display();
+    assert_eq!(display("p[hidden]"), Display::None);
+    assert_eq!(display("details"), Display::Block);
+    // A disclosure marker, like for list items
+    assert_eq!(display("summary"), Display::ListItem);
+}
+
+#[test]
+fn list_item_display() {
+    let doc = Document::parse_html(
+        br#"
+        <style>.item { display: list-item } .floating { float: left }</style>
+        <ul><li>One</ul>
+        <div class=item>Two</div>
+        <span class="item floating">Three</span>
+        "#,
+    );
+    let display = |selectors| doc.computed_style_of(selectors).unwrap().display();
+    assert_eq!(display("ul"), Display::Block);
+    assert_eq!(display("li"), Display::ListItem);
+    assert_eq!(display("div"), Display::ListItem);
+    // Blockification keeps list items
+    assert_eq!(display("span"), Display::ListItem);
+}
+
+#[test]
+fn math_display() {
+    let doc = Document::parse_html(
+        br#"
+        <style>.block { display: block math }</style>
+        <p><math><mi>x</mi></math></p>
+        <math display=BLOCK id=b><mi>y</mi></math>
+        <div class=block></div>
+        "#,
+    );
+    let display = |selectors| doc.computed_style_of(selectors).unwrap().display();
+    assert_eq!(display("math"), Display::Math);
+    assert_eq!(display("#b"), Display::BlockMath);
+    assert_eq!(display(".block"), Display::BlockMath);
+    // Descendants are laid out by math layout
+    assert_eq!(display("mi"), Display::Inline);
+}
+
+#[test]
+fn target() {
+    let mut doc = Document::parse_html(
+        br#"
+        <style>:target { color: red }</style>
+        <p id=intro>One</p>
+        <p id=details>Two</p>
+        <a name=end></a>
+        "#,
+    );
+    assert!(doc.computed_style_of(":target").is_none());
+
+    doc.set_target(Some("details"));
+    assert!(doc.computed_style_of(":target").unwrap().color() == RGBA(1., 0., 0., 1.));
+    assert!(doc.computed_style_of("#intro").unwrap().color() == RGBA(0., 0., 0., 1.));
+    assert!(doc.computed_style_of("#details:target").is_some());
+
+    doc.set_target(Some("end"));
+    assert!(doc.computed_style_of("a:target").is_some());
+
+    doc.set_target(Some("missing"));
+    assert!(doc.computed_style_of(":target").is_none());
+}
+
+#[test]
+fn supports() {
+    let doc = Document::parse_html(
+        br#"
+        <style>
+            @supports (display: flow-root) { p { font-size: 10px } }
+            @supports (display: grid) { p { font-size: 20px } }
+            @supports not (display: grid) { p { margin-left: 1px } }
+            @supports (color: red) and (unknown: 0) { p { margin-right: 2px } }
+            @supports (color: red) or (unknown: 0) { p { margin-top: 3px } }
+            @supports ((color: red) and (margin: 0)) or (grid: none) {
+                @supports selector(p:target) { p { margin-bottom: 4px } }
+            }
+            @supports selector(p:hover) { p { color: red } }
+            @supports unknown-function(p) { p { color: red } }
+            @supports (color: red) and (margin: 0) or (float: left) { p { color: red } }
+            /* Not laid out yet */
+            @supports (border-collapse: collapse) { p { color: red } }
+            @supports (caption-side: bottom) { p { color: red } }
+            @supports (table-layout: fixed) { p { color: red } }
+            @supports (empty-cells: hide) { p { color: red } }
+            @supports (font-variation-settings: "wght" 700) and (font-stretch: condensed) {
+                p { font-variation-settings: "GRAD" 1 }
+            }
+        </style>
+        <p>Text</p>
+        "#,
+    );
+    let p = doc.computed_style_of("p").unwrap();
+    assert_eq!(p.font_size(), 10.);
+    let margin = p.margin();
+    assert_eq!(margin.left, LengthOrPercentageOrAuto::Length(1.));
+    assert_eq!(margin.right, LengthOrPercentageOrAuto::Length(0.));
+    assert_eq!(margin.top, LengthOrPercentageOrAuto::Length(3.));
+    assert_eq!(margin.bottom, LengthOrPercentageOrAuto::Length(4.));
+    assert!(p.color() == RGBA(0., 0., 0., 1.));
+    assert!(p.font_variations().contains(&(*b"GRAD", 1.)));
+}
+
+#[test]
+fn case_sensitivity() {
+    let html = Document::parse_html(
+        br#"
+        <style>
+            P { color: red }
+            [LANG] { font-size: 20px }
+            [title=FOO i] { margin-left: 1px }
+            [title=FOO] { margin-right: 1px }
+            RECT { font-size: 20px }
+            [viewbox] { font-size: 20px }
+            [viewBox] { color: red }
+        </style>
+        <P LANG=en TITLE=foo>Text</P>
+        <SVG><rect viewBox="0 0 1 1"/></SVG>
+        "#,
+    );
+    let p = html.computed_style_of("p").unwrap();
+    assert!(p.color() == RGBA(1., 0., 0., 1.));
+    assert_eq!(p.font_size(), 20.);
+    assert_eq!(p.margin().left, LengthOrPercentageOrAuto::Length(1.));
+    assert_eq!(p.margin().right, LengthOrPercentageOrAuto::Length(0.));
+    // Names of SVG elements and attributes are case-sensitive, even in HTML documents
+    let rect = html.computed_style_of("rect").unwrap();
+    assert!(rect.color() == RGBA(1., 0., 0., 1.));
+    assert_eq!(rect.font_size(), 16.);
+
+    // XML documents are case-sensitive, including for HTML elements
+    let xml = Document::parse_xml(
+        br#"<html xmlns="http://www.w3.org/1999/xhtml"><P TITLE="foo">Text</P></html>"#,
+    )
+    .unwrap();
+    assert!(xml.computed_style_of("P").is_some());
+    assert!(xml.computed_style_of("p").is_none());
+    assert!(xml.computed_style_of("[TITLE]").is_some());
+    assert!(xml.computed_style_of("[title]").is_none());
+    assert!(xml.computed_style_of("[TITLE=FOO i]").is_some());
+}
+
+#[test]
+fn namespaces() {
+    let doc = Document::parse_html(
+        br#"
+        <style>
+            @namespace svg url(http://www.w3.org/2000/svg);
+            @namespace "http://www.w3.org/1999/xhtml";
+            svg|rect { color: red }
+            svg|p { color: red }
+            rect { font-size: 10px }
+            *|rect { margin-left: 1px }
+            @supports selector(svg|rect) { p { font-size: 20px } }
+            @namespace math url(http://www.w3.org/1998/Math/MathML);
+            math|mi { color: red }
+        </style>
+        <p>Text</p>
+        <svg><rect/></svg>
+        <math><mi>x</mi></math>
+        "#,
+    );
+    let p = doc.computed_style_of("p").unwrap();
+    assert!(p.color() == RGBA(0., 0., 0., 1.));
+    assert_eq!(p.font_size(), 20.);
+    let rect = doc.computed_style_of("rect").unwrap();
+    assert!(rect.color() == RGBA(1., 0., 0., 1.));
+    // Unprefixed type selectors are in the default namespace
+    assert_eq!(rect.font_size(), 16.);
+    assert_eq!(rect.margin().left, LengthOrPercentageOrAuto::Length(1.));
+    // `@namespace` after other rules is invalid, so the `math` prefix is undeclared.
+    let mi = doc.computed_style_of("mi").unwrap();
+    assert!(mi.color() == RGBA(0., 0., 0., 1.));
+    let messages: Vec<_> = doc
+        .css_diagnostics()
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "`@namespace` rules must come before other rules",
+            "invalid or unsupported selector `math|mi`",
+        ]
+    );
+}
+
+#[test]
+fn font_features() {
+    let doc = Document::parse_html(
+        br#"
+        <style>
+            body { font-variant-numeric: oldstyle-nums tabular-nums }
+            p { font-variant-ligatures: no-common-ligatures discretionary-ligatures }
+            .settings { font-feature-settings: "smcp", "tnum" off, "salt" 2, "liga" on }
+            .invalid { font-feature-settings: "toolong" 1; font-variant-numeric: lining-nums onum }
+            .none { font-variant-ligatures: none }
+            .caps { font-variant-caps: all-small-caps }
+            section { font-variant-ligatures: NONE; font-variant-numeric: Lining-Nums Tabular-Nums }
+        </style>
+        <body>
+            <p>One</p>
+            <p class=settings>Two</p>
+            <p class=invalid>Three</p>
+            <div class=none>Four</div>
+            <p class=caps>Five</p>
+            <section>Six</section>
+        </body>
+        "#,
+    );
+    let features = |selectors| doc.computed_style_of(selectors).unwrap().font_features();
+    let tags = |list: &[(&[u8; 4], u32)]| {
+        list.iter()
+            .map(|&(tag, value)| (*tag, value))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        features("html"),
+        tags(&[
+            (b"calt", 1),
+            (b"ccmp", 1),
+            (b"clig", 1),
+            (b"liga", 1),
+            (b"rlig", 1)
+        ])
+    );
+    assert_eq!(
+        features("p"),
+        tags(&[
+            (b"calt", 1),
+            (b"ccmp", 1),
+            (b"clig", 0),
+            (b"dlig", 1),
+            (b"liga", 0),
+            (b"onum", 1),
+            (b"rlig", 1),
+            (b"tnum", 1),
+        ])
+    );
+    assert_eq!(
+        features("p.settings"),
+        tags(&[
+            (b"calt", 1),
+            (b"ccmp", 1),
+            (b"clig", 0),
+            (b"dlig", 1),
+            (b"liga", 1),
+            (b"onum", 1),
+            (b"rlig", 1),
+            (b"salt", 2),
+            (b"smcp", 1),
+            (b"tnum", 0),
+        ])
+    );
+    assert_eq!(features("p.invalid"), features("p"));
+    assert_eq!(
+        features("div"),
+        tags(&[
+            (b"calt", 0),
+            (b"ccmp", 1),
+            (b"clig", 0),
+            (b"dlig", 0),
+            (b"hlig", 0),
+            (b"liga", 0),
+            (b"onum", 1),
+            (b"rlig", 1),
+            (b"tnum", 1),
+        ])
+    );
+    assert_eq!(
+        features("p.caps"),
+        tags(&[
+            (b"c2sc", 1),
+            (b"calt", 1),
+            (b"ccmp", 1),
+            (b"clig", 0),
+            (b"dlig", 1),
+            (b"liga", 0),
+            (b"onum", 1),
+            (b"rlig", 1),
+            (b"smcp", 1),
+            (b"tnum", 1),
+        ])
+    );
+    assert_eq!(
+        features("section"),
+        tags(&[
+            (b"calt", 0),
+            (b"ccmp", 1),
+            (b"clig", 0),
+            (b"dlig", 0),
+            (b"hlig", 0),
+            (b"liga", 0),
+            (b"lnum", 1),
+            (b"rlig", 1),
+            (b"tnum", 1),
+        ])
+    );
+}
+
+#[test]
+fn font_variations() {
+    let doc = Document::parse_html(
+        br#"
+        <style>
+            body { font-size: 10px; font-stretch: condensed }
+            em { font-style: oblique 10deg }
+            .settings { font-variation-settings: "wght" 250, "GRAD" -0.5 }
+            .invalid { font-weight: 1001; font-style: oblique 91deg }
+            .lighter { font-weight: lighter }
+        </style>
+        <body>
+            <h1>One</h1>
+            <p><b>Two</b> <em>Three</em> <i class=settings>Four</i></p>
+            <address class="invalid lighter">Five</address>
+        </body>
+        "#,
+    );
+    let variations = |selectors| doc.computed_style_of(selectors).unwrap().font_variations();
+    let tags = |list: &[(&[u8; 4], f32)]| {
+        list.iter()
+            .map(|&(tag, value)| (*tag, value))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        variations("p"),
+        tags(&[(b"opsz", 10.), (b"wdth", 75.), (b"wght", 400.)])
+    );
+    assert_eq!(
+        variations("h1"),
+        tags(&[(b"opsz", 20.), (b"wdth", 75.), (b"wght", 700.)])
+    );
+    assert_eq!(
+        variations("b"),
+        tags(&[(b"opsz", 10.), (b"wdth", 75.), (b"wght", 700.)])
+    );
+    assert_eq!(
+        variations("em"),
+        tags(&[
+            (b"opsz", 10.),
+            (b"slnt", -10.),
+            (b"wdth", 75.),
+            (b"wght", 400.)
+        ])
+    );
+    assert_eq!(
+        variations("i"),
+        tags(&[
+            (b"GRAD", -0.5),
+            (b"ital", 1.),
+            (b"opsz", 10.),
+            (b"wdth", 75.),
+            (b"wght", 250.),
+        ])
+    );
+    assert_eq!(
+        variations("address"),
+        tags(&[
+            (b"ital", 1.),
+            (b"opsz", 10.),
+            (b"wdth", 75.),
+            (b"wght", 100.)
+        ])
+    );
+}
+
+#[test]
+fn relative_font_weights() {
+    let doc = Document::parse_html(
+        br#"
+        <p style="font-weight: 550"><b>One</b></p>
+        <div style="font-weight: 750"><b>Two</b></div>
+        <address style="font-weight: 800"><b>Three</b></address>
+        "#,
+    );
+    let weight = |selectors| {
+        let variations = doc.computed_style_of(selectors).unwrap().font_variations();
+        variations
+            .into_iter()
+            .find(|(tag, _)| tag == b"wght")
+            .unwrap()
+            .1
+    };
+    // https://drafts.csswg.org/css-fonts-4/#relative-weights
+    assert_eq!(weight("p b"), 900.);
+    assert_eq!(weight("div b"), 750.);
+    assert_eq!(weight("address b"), 800.);
+}
@@ -0,0 +1,127 @@
+use victor::dom::{CssDiagnostic, CssDiagnosticKind, Document, ElementLocation};
+
+fn element(name: &str, line: u32, column: u32) -> ElementLocation {
+    ElementLocation {
+        name: name.to_owned(),
+        line,
+        column,
+    }
+}
+
+#[test]
+fn css_diagnostics() {
+    let doc = Document::parse_html(
+        b"<!doctype html>
+<style>
+  p { colour: blue; width: 10qq }
+  @media print {}
+</style>
+<link rel=stylesheet href='data:text/css,a{b:c}'>
+<p style='color: red; foo: bar'>
+",
+    );
+    let diagnostic = |message: &str, line, column, elements: &[ElementLocation]| CssDiagnostic {
+        kind: CssDiagnosticKind::Unsupported,
+        message: message.to_owned(),
+        line,
+        column,
+        elements: elements.to_vec(),
+    };
+    let p = [element("p", 7, 1)];
+    let invalid_width = "invalid or unsupported declaration `width: 10qq`";
+    assert_eq!(
+        doc.css_diagnostics(),
+        [
+            diagnostic("unsupported property `colour`", 3, 7, &p),
+            diagnostic(invalid_width, 3, 21, &p),
+            diagnostic("unsupported at-rule `@media`", 4, 3, &[]),
+            diagnostic("unsupported property `b`", 6, 3, &[]),
+            diagnostic("unsupported property `foo`", 7, 13, &p),
+        ]
+    );
+}
+
+#[test]
+fn trailing_tokens() {
+    let doc = Document::parse_html(
+        b"<p style='width: 10px 20px; transform-origin: left top 0; height: 1px'>",
+    );
+    let messages: Vec<_> = doc
+        .css_diagnostics()
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "invalid or unsupported declaration `width: 10px 20px`",
+            "invalid or unsupported declaration `transform-origin: left top 0`",
+        ]
+    );
+}
+
+#[test]
+fn not_renderable() {
+    let doc = Document::parse_html(
+        b"<!doctype html>
+<style>
+  .glass { backdrop-filter: blur(4px); -webkit-backdrop-filter: blur(4px) }
+  p { transition: color 1s; animation-name: fade; Transition-Delay: 1s }
+</style>
+<div style='filter: grayscale(1) blur(2px)'>
+<nav class=glass></nav><aside class=glass></aside>
+",
+    );
+    let diagnostic = |name, line, column, elements: &[ElementLocation]| CssDiagnostic {
+        kind: CssDiagnosticKind::NotRenderable,
+        message: format!("`{}` cannot be rendered in a static PDF", name),
+        line,
+        column,
+        elements: elements.to_vec(),
+    };
+    let glass = [element("nav", 7, 1), element("aside", 7, 24)];
+    assert_eq!(
+        doc.css_diagnostics(),
+        [
+            diagnostic("backdrop-filter", 3, 12, &glass),
+            diagnostic("-webkit-backdrop-filter", 3, 40, &glass),
+            diagnostic("transition", 4, 7, &[]),
+            diagnostic("animation-name", 4, 29, &[]),
+            diagnostic("Transition-Delay", 4, 51, &[]),
+            diagnostic("blur()", 6, 1, &[element("div", 6, 1)]),
+        ]
+    );
+}
+
+#[test]
+fn approximated() {
+    let doc = Document::parse_html(
+        b"<!doctype html>
+<style>
+  th { position: sticky; top: 0 }
+</style>
+<div style='position: relative'></div><div style='position:sticky'></div>
+<div style='position: relative; z-index: auto'></div><div style='z-index: 2'></div>
+<img style='filter: opacity(0.5) sepia() grayscale(0)'><img style='filter: grayscale(0%)'>
+",
+    );
+    let diagnostic = |message: &str, line, column, elements: &[ElementLocation]| CssDiagnostic {
+        kind: CssDiagnosticKind::Approximated,
+        message: message.to_owned(),
+        line,
+        column,
+        elements: elements.to_vec(),
+    };
+    let sticky = "`position: sticky` is rendered like `position: relative`";
+    let z_index = "`z-index` does not change the painting order";
+    let sepia = "`sepia()` leaves the colors of JPEG images unchanged";
+    assert_eq!(
+        doc.css_diagnostics(),
+        [
+            diagnostic(sticky, 3, 8, &[]),
+            diagnostic(sticky, 5, 1, &[element("div", 5, 39)]),
+            diagnostic(z_index, 6, 1, &[element("div", 6, 54)]),
+            diagnostic(sepia, 7, 1, &[element("img", 7, 1)]),
+        ]
+    );
+}
@@ -0,0 +1,393 @@
+#!/usr/bin/env python3
+"""
+Write Stems.ttf, a tiny variable TrueType font for testing font instancing.
+
+`I` is a stem 100 units wide, which becomes wider along the `wght` axis:
+200 units wider at 900 (with explicit point numbers, the other points are interpolated),
+50 units narrower at 100, and its advance width changes by as much.
+Along the `wdth` axis, at 200 it moves 50 units to the right and is 50 units wider.
+The `avar` table maps `wght` 650 (0.5 normalized) to 0.75.
+`H` is a composite glyph of two `I`, the second one following the advance of the first.
+There is a period, but no ellipsis character.
+"""
+
+import os
+import struct
+
+UNITS_PER_EM = 1000
+ASCENDER = 800
+DESCENDER = -200
+
+# (name, advance width, contours or components)
+I = [[(100, 0), (200, 0), (200, 700), (100, 700)]]
+GLYPHS = [
+    (".notdef", 500, None),
+    ("space", 250, None),
+    ("I", 300, I),
+    ("H", 600, [(2, 0, 0), (2, 300, 0)]),
+    ("period", 250, [[(50, 0), (150, 0), (150, 100), (50, 100)]]),
+]
+CMAP = {ord(" "): 1, ord("."): 4, ord("I"): 2, ord("H"): 3}
+
+# tag, min, default, max, name ID
+AXES = [(b"wght", 100, 400, 900, 256), (b"wdth", 50, 100, 200, 257)]
+# subfamily name ID, coordinates, PostScript name ID
+INSTANCES = [(258, (400, 100), 0xFFFF), (259, (700, 100), 260)]
+NAMES = {
+    1: "Stems",
+    2: "Regular",
+    4: "Stems Regular",
+    6: "Stems-Regular",
+    256: "Weight",
+    257: "Width",
+    258: "Regular",
+    259: "Bold",
+    260: "Stems-Bold",
+}
+AVAR = [
+    [(-1, -1), (0, 0), (0.5, 0.75), (1, 1)],
+    [(-1, -1), (0, 0), (1, 1)],
+]
+
+WGHT_MAX = (1, 0)
+WGHT_MIN = (-1, 0)
+WDTH_MAX = (0, 1)
+# For each glyph: (peak, point numbers or None for all points, x deltas)
+# Points are those of the outline followed by 4 phantom points,
+# the second of which gives the advance width.
+VARIATIONS = [
+    [],
+    [(WDTH_MAX, None, [0, 125, 0, 0])],
+    [
+        (WGHT_MAX, [0, 1, 5], [0, 200, 200]),
+        (WGHT_MIN, None, [0, -50, -50, 0, 0, -50, 0, 0]),
+        (WDTH_MAX, None, [50, 100, 100, 50, 0, 150, 0, 0]),
+    ],
+    [
+        (WGHT_MAX, None, [0, 200, 0, 400, 0, 0]),
+        (WGHT_MIN, None, [0, -50, 0, -100, 0, 0]),
+        (WDTH_MAX, None, [0, 150, 0, 300, 0, 0]),
+    ],
+    [],
+]
+SHARED_TUPLES = [WGHT_MAX, WGHT_MIN]
+# Variation regions of HVAR, and advance width deltas for each glyph
+REGIONS = [WGHT_MAX, WDTH_MAX, WGHT_MIN]
+ADVANCE_DELTAS = [[0, 0, 0], [0, 125, 0], [200, 150, -50], [400, 300, -100], [0, 0, 0]]
+
+
+def f2dot14(value):
+    return struct.pack(">h", round(value * 16384))
+
+
+def fixed(value):
+    return struct.pack(">i", round(value * 65536))
+
+
+def bounding_box(points):
+    xs = [x for x, _ in points]
+    ys = [y for _, y in points]
+    return min(xs), min(ys), max(xs), max(ys)
+
+
+def glyph_points(index):
+    _, _, outline = GLYPHS[index]
+    if outline is None:
+        return []
+    if isinstance(outline[0], tuple):
+        return [
+            (x + dx, y + dy)
+            for component, dx, dy in outline
+            for x, y in glyph_points(component)
+        ]
+    return [point for contour in outline for point in contour]
+
+
+def glyf_and_loca():
+    glyf = b""
+    loca = [0]
+    for index, (_, _, outline) in enumerate(GLYPHS):
+        if outline is not None:
+            if isinstance(outline[0], tuple):
+                glyf += struct.pack(">hhhhh", -1, *bounding_box(glyph_points(index)))
+                for i, (component, dx, dy) in enumerate(outline):
+                    more_components = 0x0020 if i + 1 < len(outline) else 0
+                    # ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES
+                    flags = 0x0001 | 0x0002 | more_components
+                    glyf += struct.pack(">HHhh", flags, component, dx, dy)
+            else:
+                points = glyph_points(index)
+                glyf += struct.pack(">hhhhh", len(outline), *bounding_box(points))
+                end = -1
+                for contour in outline:
+                    end += len(contour)
+                    glyf += struct.pack(">H", end)
+                # No instructions
+                glyf += struct.pack(">H", 0)
+                # On curve, with coordinates as 16-bit deltas
+                glyf += bytes([0x01] * len(points))
+                previous = (0, 0)
+                for axis in (0, 1):
+                    for point in points:
+                        glyf += struct.pack(">h", point[axis] - previous[axis])
+                        previous = point
+                    previous = (0, 0)
+            glyf += b"\0" * (-len(glyf) % 4)
+        loca.append(len(glyf))
+    return glyf, struct.pack(">%sI" % len(loca), *loca)
+
+
+def packed_points(numbers):
+    if numbers is None:
+        return b"\0"
+    data = bytes([len(numbers), len(numbers) - 1])
+    previous = 0
+    for number in numbers:
+        data += bytes([number - previous])
+        previous = number
+    return data
+
+
+def packed_deltas(deltas):
+    # One run of 16-bit deltas
+    return bytes([0x40 | (len(deltas) - 1)]) + struct.pack(">%sh" % len(deltas), *deltas)
+
+
+def gvar():
+    shared_tuples = b"".join(f2dot14(a) + f2dot14(b) for a, b in SHARED_TUPLES)
+    glyph_data = []
+    for variations in VARIATIONS:
+        if not variations:
+            glyph_data.append(b"")
+            continue
+        headers = b""
+        serialized = b""
+        # Tuples without explicit point numbers use the shared "all points"
+        serialized += packed_points(None)
+        for peak, numbers, x_deltas in variations:
+            data = b""
+            tuple_index = 0
+            if numbers is not None:
+                tuple_index |= 0x2000  # PRIVATE_POINT_NUMBERS
+                data += packed_points(numbers)
+            data += packed_deltas(x_deltas) + packed_deltas([0] * len(x_deltas))
+            if peak in SHARED_TUPLES:
+                tuple_index |= SHARED_TUPLES.index(peak)
+                headers += struct.pack(">HH", len(data), tuple_index)
+            else:
+                tuple_index |= 0x8000  # EMBEDDED_PEAK_TUPLE
+                headers += struct.pack(">HH", len(data), tuple_index)
+                headers += f2dot14(peak[0]) + f2dot14(peak[1])
+            serialized += data
+        count = 0x8000 | len(variations)  # SHARED_POINT_NUMBERS
+        data = struct.pack(">HH", count, 4 + len(headers)) + headers + serialized
+        glyph_data.append(data + b"\0" * (len(data) % 2))
+    header_size = 20
+    offsets_size = 4 * (len(GLYPHS) + 1)
+    shared_tuples_offset = header_size + offsets_size
+    data_offset = shared_tuples_offset + len(shared_tuples)
+    offsets = [0]
+    for data in glyph_data:
+        offsets.append(offsets[-1] + len(data))
+    return (
+        struct.pack(
+            ">HHHHIHHI",
+            1,
+            0,
+            len(AXES),
+            len(SHARED_TUPLES),
+            shared_tuples_offset,
+            len(GLYPHS),
+            1,  # 32-bit offsets
+            data_offset,
+        )
+        + struct.pack(">%sI" % len(offsets), *offsets)
+        + shared_tuples
+        + b"".join(glyph_data)
+    )
+
+
+def hvar():
+    # Item variation store
+    region_list = struct.pack(">HH", len(AXES), len(REGIONS))
+    for peak in REGIONS:
+        for value in peak:
+            region_list += f2dot14(min(value, 0)) + f2dot14(value) + f2dot14(max(value, 0))
+    # The first two regions with 16-bit deltas, the third with 8-bit deltas
+    item_data = struct.pack(">HHH", len(GLYPHS), 2, len(REGIONS))
+    item_data += struct.pack(">HHH", 0, 1, 2)
+    for a, b, c in ADVANCE_DELTAS:
+        item_data += struct.pack(">hhb", a, b, c)
+    store_header_size = 2 + 4 + 2 + 4
+    store = struct.pack(
+        ">HIHI",
+        1,
+        store_header_size,
+        1,
+        store_header_size + len(region_list),
+    )
+    store += region_list + item_data
+    # Advance width mapping: 1-byte entries with 3 bits for the inner index
+    mapping = struct.pack(">BBH", 0, 0x02, len(GLYPHS)) + bytes(range(len(GLYPHS)))
+    header_size = 20
+    return (
+        struct.pack(">HHIIII", 1, 0, header_size, header_size + len(store), 0, 0)
+        + store
+        + mapping
+    )
+
+
+def fvar():
+    axis_size = 20
+    instance_size = 4 + 4 * len(AXES) + 2
+    data = struct.pack(
+        ">HHHHHHHH", 1, 0, 16, 2, len(AXES), axis_size, len(INSTANCES), instance_size
+    )
+    for tag, minimum, default, maximum, name_id in AXES:
+        data += tag + fixed(minimum) + fixed(default) + fixed(maximum)
+        data += struct.pack(">HH", 0, name_id)
+    for subfamily_name_id, coordinates, postscript_name_id in INSTANCES:
+        data += struct.pack(">HH", subfamily_name_id, 0)
+        data += b"".join(fixed(value) for value in coordinates)
+        data += struct.pack(">H", postscript_name_id)
+    return data
+
+
+def avar():
+    data = struct.pack(">HHHH", 1, 0, 0, len(AVAR))
+    for segments in AVAR:
+        data += struct.pack(">H", len(segments))
+        for from_value, to_value in segments:
+            data += f2dot14(from_value) + f2dot14(to_value)
+    return data
+
+
+def cmap():
+    # Format 4, one segment per character and the final 0xFFFF segment
+    segments = sorted(CMAP.items()) + [(0xFFFF, 0)]
+    count = len(segments)
+    subtable = struct.pack(">HHHHHHH", 4, 16 + 8 * count, 0, 2 * count, 0, 0, 0)
+    subtable += b"".join(struct.pack(">H", code) for code, _ in segments)
+    subtable += struct.pack(">H", 0)
+    subtable += b"".join(struct.pack(">H", code) for code, _ in segments)
+    subtable += b"".join(struct.pack(">H", (glyph - code) % 0x10000) for code, glyph in segments)
+    subtable += b"".join(struct.pack(">H", 0) for _ in segments)
+    # Microsoft, Unicode BMP
+    return struct.pack(">HHHHI", 0, 1, 3, 1, 12) + subtable
+
+
+def name():
+    records = b""
+    strings = b""
+    for name_id, string in sorted(NAMES.items()):
+        encoded = string.encode("utf-16-be")
+        # Microsoft, Unicode BMP, English (US)
+        records += struct.pack(">HHHHHH", 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+    return struct.pack(">HHH", 0, len(NAMES), 6 + len(records)) + records + strings
+
+
+def head(bounding_box):
+    return struct.pack(
+        ">iiIIHHqqhhhhHHhhh",
+        0x00010000,
+        0x00010000,
+        0,  # checksum adjustment, set below
+        0x5F0F3CF5,
+        0x0003,
+        UNITS_PER_EM,
+        0,
+        0,
+        *bounding_box,
+        0,  # mac style
+        8,
+        2,
+        1,  # long offsets in loca
+        0,
+    )
+
+
+def hhea():
+    return struct.pack(
+        ">ihhhHhhhhhh8xhH",
+        0x00010000,
+        ASCENDER,
+        DESCENDER,
+        0,
+        max(advance for _, advance, _ in GLYPHS),
+        0,
+        0,
+        0,
+        1,
+        0,
+        0,
+        0,
+        len(GLYPHS),
+    )
+
+
+def hmtx():
+    data = b""
+    for index, (_, advance, _) in enumerate(GLYPHS):
+        points = glyph_points(index)
+        lsb = bounding_box(points)[0] if points else 0
+        data += struct.pack(">Hh", advance, lsb)
+    return data
+
+
+def maxp():
+    return struct.pack(
+        ">iHHHHHHHHHHHHHH", 0x00010000, len(GLYPHS), 4, 1, 8, 2, 2, 0, 0, 0, 0, 0, 0, 2, 1
+    )
+
+
+def post():
+    # Version 3: no glyph names
+    return struct.pack(">iihhIIIII", 0x00030000, 0, -100, 50, 0, 0, 0, 0, 0)
+
+
+def checksum(data):
+    data += b"\0" * (-len(data) % 4)
+    return sum(struct.unpack(">%sI" % (len(data) // 4), data)) & 0xFFFFFFFF
+
+
+def font():
+    glyf, loca = glyf_and_loca()
+    all_points = [point for index in range(len(GLYPHS)) for point in glyph_points(index)]
+    tables = {
+        b"HVAR": hvar(),
+        b"avar": avar(),
+        b"cmap": cmap(),
+        b"fvar": fvar(),
+        b"glyf": glyf,
+        b"gvar": gvar(),
+        b"head": head(bounding_box(all_points)),
+        b"hhea": hhea(),
+        b"hmtx": hmtx(),
+        b"loca": loca,
+        b"maxp": maxp(),
+        b"name": name(),
+        b"post": post(),
+    }
+    count = len(tables)
+    entry_selector = count.bit_length() - 1
+    search_range = 16 << entry_selector
+    directory = struct.pack(
+        ">IHHHH", 0x00010000, count, search_range, entry_selector, 16 * count - search_range
+    )
+    offset = len(directory) + 16 * count
+    data = b""
+    for tag, table in sorted(tables.items()):
+        directory += tag + struct.pack(">III", checksum(table), offset + len(data), len(table))
+        data += table + b"\0" * (-len(table) % 4)
+    font = bytearray(directory + data)
+    head_offset = struct.unpack(">I", directory[directory.index(b"head") + 8:][:4])[0]
+    font[head_offset + 8:head_offset + 12] = struct.pack(
+        ">I", (0xB1B0AFBA - checksum(bytes(font))) & 0xFFFFFFFF
+    )
+    return bytes(font)
+
+
+if __name__ == "__main__":
+    with open(os.path.join(os.path.dirname(__file__), "Stems.ttf"), "wb") as f:
+        f.write(font())
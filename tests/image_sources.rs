@@ -0,0 +1,164 @@
+use victor::dom::Document;
+use victor::fonts::FontContext;
+
+#[test]
+fn srcset_and_picture() {
+    let mut doc = Document::parse_html(
+        br#"
+        <img src=plain.jpg>
+        <img src=small.jpg srcset="medium.jpg 2x, large.jpg 4x">
+        <img srcset="w400.jpg 400w, w800.jpg 800w, w1600.jpg 1600w" sizes="200px">
+        <img srcset="data:image/png;base64,AAAA, invalid.jpg 2q">
+        <picture>
+            <source srcset="pic.webp" type="image/webp">
+            <source srcset="pic-screen.jpg" media="screen">
+            <source srcset="pic-1x.jpg, pic-3x.jpg 3x" type="image/jpeg">
+            <img src=pic-fallback.jpg>
+        </picture>
+        "#,
+    );
+    let sources = |doc: &Document| {
+        doc.html_image_sources()
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sources(&doc),
+        [
+            "plain.jpg",
+            "small.jpg",
+            "w400.jpg",
+            "data:image/png;base64,AAAA",
+            "pic-1x.jpg",
+        ]
+    );
+
+    // 300dpi is 3.125 device pixels per px: the smallest image that covers it,
+    // or the largest one if none does.
+    let at_300dpi = [
+        "plain.jpg",
+        "large.jpg",
+        "w800.jpg",
+        "data:image/png;base64,AAAA",
+        "pic-3x.jpg",
+    ];
+    doc.set_max_image_resolution(Some(300.));
+    assert_eq!(sources(&doc), at_300dpi);
+
+    // Without a maximum resolution, that of pixel snapping
+    doc.set_max_image_resolution(None);
+    doc.set_pixel_snapping(Some(3.125));
+    assert_eq!(sources(&doc), at_300dpi);
+}
+
+/// A 4×2 JPEG image, red on the left and blue on the right
+const JPEG_BASE64: &str = concat!(
+    "/9j/4AAQSkZJRgABAgAAAQABAAD/wAARCAACAAQDABEAAREBAhEB/9sAQwADAgIDAgIDAwMDBAMDBAUIBQUEBAUK",
+    "BwcGCAwKDAwLCgsLDQ4SEA0OEQ4LCxAWEBETFBUVFQwPFxgWFBgSFBUU/9sAQwEDBAQFBAUJBQUJFA0LDRQUFBQU",
+    "FBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQU/8QAHwAAAQUBAQEBAQEAAAAAAAAA",
+    "AAECAwQFBgcICQoL/8QAtRAAAgEDAwIEAwUFBAQAAAF9AQIDAAQRBRIhMUEGE1FhByJxFDKBkaEII0KxwRVS0fAk",
+    "M2JyggkKFhcYGRolJicoKSo0NTY3ODk6Q0RFRkdISUpTVFVWV1hZWmNkZWZnaGlqc3R1dnd4eXqDhIWGh4iJipKT",
+    "lJWWl5iZmqKjpKWmp6ipqrKztLW2t7i5usLDxMXGx8jJytLT1NXW19jZ2uHi4+Tl5ufo6erx8vP09fb3+Pn6/8QA",
+    "HwEAAwEBAQEBAQEBAQAAAAAAAAECAwQFBgcICQoL/8QAtREAAgECBAQDBAcFBAQAAQJ3AAECAxEEBSExBhJBUQdh",
+    "cRMiMoEIFEKRobHBCSMzUvAVYnLRChYkNOEl8RcYGRomJygpKjU2Nzg5OkNERUZHSElKU1RVVldYWVpjZGVmZ2hp",
+    "anN0dXZ3eHl6goOEhYaHiImKkpOUlZaXmJmaoqOkpaanqKmqsrO0tba3uLm6wsPExcbHyMnK0tPU1dbX2Nna4uPk",
+    "5ebn6Onq8vP09fb3+Pn6/9oADAMAAAERAhEAPwD4W8ef8jXff9s//Ra1/cPgl/yQGXf9xf8A0/UPq/F//kt8f/3D",
+    "/wDTNM//2Q==",
+);
+
+/// The same image, with an EXIF orientation that rotates it clockwise: 2×4, red on top
+const JPEG_ROTATED_BASE64: &str = concat!(
+    "/9j/4AAQSkZJRgABAgAAAQABAAD/4QAiRXhpZgAATU0AKgAAAAgAAQESAAMAAAABAAYAAAAAAAD/wAARCAACAAQD",
+    "ABEAAREBAhEB/9sAQwADAgIDAgIDAwMDBAMDBAUIBQUEBAUKBwcGCAwKDAwLCgsLDQ4SEA0OEQ4LCxAWEBETFBUV",
+    "FQwPFxgWFBgSFBUU/9sAQwEDBAQFBAUJBQUJFA0LDRQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQU",
+    "FBQUFBQUFBQUFBQUFBQU/8QAHwAAAQUBAQEBAQEAAAAAAAAAAAECAwQFBgcICQoL/8QAtRAAAgEDAwIEAwUFBAQA",
+    "AAF9AQIDAAQRBRIhMUEGE1FhByJxFDKBkaEII0KxwRVS0fAkM2JyggkKFhcYGRolJicoKSo0NTY3ODk6Q0RFRkdI",
+    "SUpTVFVWV1hZWmNkZWZnaGlqc3R1dnd4eXqDhIWGh4iJipKTlJWWl5iZmqKjpKWmp6ipqrKztLW2t7i5usLDxMXG",
+    "x8jJytLT1NXW19jZ2uHi4+Tl5ufo6erx8vP09fb3+Pn6/8QAHwEAAwEBAQEBAQEBAQAAAAAAAAECAwQFBgcICQoL",
+    "/8QAtREAAgECBAQDBAcFBAQAAQJ3AAECAxEEBSExBhJBUQdhcRMiMoEIFEKRobHBCSMzUvAVYnLRChYkNOEl8RcY",
+    "GRomJygpKjU2Nzg5OkNERUZHSElKU1RVVldYWVpjZGVmZ2hpanN0dXZ3eHl6goOEhYaHiImKkpOUlZaXmJmaoqOk",
+    "paanqKmqsrO0tba3uLm6wsPExcbHyMnK0tPU1dbX2Nna4uPk5ebn6Onq8vP09fb3+Pn6/9oADAMAAAERAhEAPwD4",
+    "W8ef8jXff9s//Ra1/cPgl/yQGXf9xf8A0/UPq/F//kt8f/3D/wDTNM//2Q==",
+);
+
+#[test]
+fn jpeg_data_url() {
+    let html = format!(
+        "<img src='data:image/jpeg;base64,{}' style='width: 8px'> <img src=missing.jpg>",
+        JPEG_BASE64
+    );
+    let doc = Document::parse_html(html.as_bytes());
+    let pdf_bytes = doc.to_pdf_bytes_with_font_context(FontContext::shared());
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert_eq!(pdf.matches("/Subtype /Image").count(), 1);
+    assert!(pdf.contains("/Width 4 /Height 2 /ColorSpace /DeviceRGB /BitsPerComponent 8"));
+    assert!(pdf.contains("/Filter /DCTDecode"));
+    // Scaled to 8×4 CSS px, with the aspect ratio of the image
+    assert!(pdf.contains("\n8.0 0.0 0.0 -4.0 "));
+}
+
+#[test]
+fn background_origin() {
+    let html = format!(
+        "<body style='margin: 0'>
+        <div style='width: 10px; height: 2px; padding: 3px;
+                    border-width: 2px; border-style: solid; border-color: transparent;
+                    background: url(data:image/jpeg;base64,{}) Content-Box border-box'></div>",
+        JPEG_BASE64
+    );
+    let doc = Document::parse_html(html.as_bytes());
+    let pdf_bytes = doc.to_pdf_bytes_with_font_context(FontContext::shared());
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // Written once, painted in tiles that cover the 20×12 border box
+    assert_eq!(pdf.matches("/Subtype /Image").count(), 1);
+    assert!(pdf.contains("\n0.0 0.0 20.0 12.0 re\nW\nn\n"));
+    assert_eq!(pdf.matches(" Do\n").count(), 6 * 7);
+    // The 4×2 tiles are aligned with the top-left corner of the content box, at (5, 5)
+    assert!(pdf.contains("\n4.0 0.0 0.0 -2.0 -3.0 1.0 cm\n"));
+    assert!(pdf.contains("\n4.0 0.0 0.0 -2.0 5.0 7.0 cm\n"));
+}
+
+#[test]
+fn exif_orientation() {
+    let html = format!(
+        "<body style='margin: 0'>
+        <img src='data:image/jpeg;base64,{0}' style='width: 4px; display: block'>
+        <img src='data:image/jpeg;base64,{0}' style='width: 4px; display: block;
+                                                     image-orientation: none'>",
+        JPEG_ROTATED_BASE64
+    );
+    let doc = Document::parse_html(html.as_bytes());
+    let pdf_bytes = doc.to_pdf_bytes_with_font_context(FontContext::shared());
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // Laid out as 2×4 and scaled to 4×8, then painted rotated from the stored 8×4
+    assert!(pdf.contains("\n0.0 1.0 -1.0 0.0 4.0 0.0 cm\nq\n8.0 0.0 0.0 -4.0 0.0 4.0 cm\n"));
+    // `image-orientation: none` paints the image as stored, 4×2 below the first one
+    assert!(pdf.contains("\n4.0 0.0 0.0 -2.0 0.0 10.0 cm\n"));
+}
+
+#[test]
+fn object_fit_and_position() {
+    let img = |style: &str| {
+        format!(
+            "<img src='data:image/jpeg;base64,{}' style='display: block; width: 8px; height: 8px; {}'>",
+            JPEG_BASE64, style
+        )
+    };
+    let html = format!(
+        "<body style='margin: 0'>{}{}{}",
+        img("object-fit: contain"),
+        img("object-fit: cover"),
+        img("object-fit: none; object-position: right bottom"),
+    );
+    let doc = Document::parse_html(html.as_bytes());
+    let pdf_bytes = doc.to_pdf_bytes_with_font_context(FontContext::shared());
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // 8×4 to fit in the 8×8 box, centered vertically
+    assert!(pdf.contains("\nq\n8.0 0.0 0.0 -4.0 0.0 6.0 cm\n/i0 Do\nQ\n"));
+    // 16×8 to cover the box below, centered horizontally and clipped to the box
+    assert!(pdf.contains("\n0.0 8.0 m\n8.0 8.0 l\n8.0 16.0 l\n0.0 16.0 l\nh\nW\nn\n"));
+    assert!(pdf.contains("\n16.0 0.0 0.0 -8.0 -4.0 16.0 cm\n"));
+    // 4×2 as is, in the bottom-right corner of the third box
+    assert!(pdf.contains("\nq\n4.0 0.0 0.0 -2.0 4.0 24.0 cm\n/i2 Do\nQ\n"));
+    assert_eq!(pdf.matches("\nW\n").count(), 1);
+}
@@ -0,0 +1,126 @@
+use lester::PdfDocument;
+use victor::dom::{Document, ElementLocation, OverflowReport, PageReport};
+use victor::fonts::FontContext;
+use victor::primitives::{point, size, SideOffsets, Size};
+
+#[test]
+fn layout_report() {
+    let doc = Document::parse_html(b"<p>Content</p>");
+    let (pdf_bytes, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert_eq!(report.page_count(), 1);
+    assert_eq!(
+        report.pages[0],
+        PageReport {
+            size: Size::new(600., 800.),
+            name: None,
+        }
+    );
+    let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(pdf.pages().len(), report.page_count());
+}
+
+#[test]
+fn page_size_and_margins() {
+    let mut doc = Document::parse_html(b"<p id=intro style='margin: 0'>Content</p>");
+    doc.set_page_size(size(400., 300.));
+    doc.set_page_margins(SideOffsets::new(10., 20., 30., 40.));
+    let (pdf_bytes, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert_eq!(report.pages[0].size, size(400., 300.));
+    // The body’s 8px margin, inside the page margins
+    assert_eq!(report.anchor("intro").unwrap().position, point(48., 18.));
+    let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let page = pdf.pages().next().unwrap();
+    assert_eq!(page.size_in_css_px(), (400., 300.));
+}
+
+#[test]
+fn page_range() {
+    let mut doc = Document::parse_html(b"<p>Content</p>");
+    doc.set_page_range(Some(1..=3));
+    let pdf_bytes = doc.to_pdf_bytes();
+    let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(pdf.pages().len(), 1);
+
+    // Past the end, the last page is still written
+    doc.set_page_range(Some(2..=3));
+    let (pdf_bytes, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert_eq!(report.page_count(), 1);
+    let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(pdf.pages().len(), 1);
+}
+
+#[test]
+fn anchors() {
+    let doc = Document::parse_html(
+        br#"
+        <p id=intro>One <span id=word>two</span></p>
+        <p id=hidden style="display: none">Three</p>
+        <div id=last style="padding: 10px">Four</div>
+        "#,
+    );
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    let ids: Vec<_> = report.anchors.iter().map(|a| &*a.id).collect();
+    assert_eq!(ids, ["intro", "word", "last"]);
+    let intro = report.anchor("intro").unwrap();
+    assert_eq!(intro.page_number, 1);
+    assert_eq!(intro.position, point(8., 16.));
+    let word = report.anchor("word").unwrap();
+    assert_eq!(word.position.y, 16.);
+    assert!(word.position.x > 8.);
+    // Below the paragraph’s line and bottom margin
+    assert_eq!(report.anchor("last").unwrap().position, point(8., 51.2));
+    assert!(report.anchor("hidden").is_none());
+}
+
+#[test]
+fn overflows() {
+    let doc = Document::parse_html(b"<p>Content</p>");
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert_eq!(report.overflows, []);
+
+    let doc = Document::parse_html(
+        b"<div style='height: 900px'>
+            <p>One</p><p style='width: 700px'>Two</p>
+        </div>
+        <p>Three</p>",
+    );
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    // The wide paragraph, rather than the tall `div` that contains it
+    let element = ElementLocation {
+        name: "p".to_owned(),
+        line: 2,
+        column: 23,
+    };
+    assert_eq!(
+        report.overflows,
+        [OverflowReport {
+            page_number: 1,
+            element
+        }]
+    );
+
+    // Content that overflows a box that fits
+    let doc = Document::parse_html(
+        b"<div style='height: 10px'><div style='height: 2000px'></div></div><p>After</p>",
+    );
+    let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+    assert_eq!(report.overflows[0].element.column, 27);
+
+    // A box that fills a page area that is not a whole number of pixels,
+    // then one that is one app unit (1/60 px) wider.
+    let overflows = |html: &[u8]| {
+        let mut doc = Document::parse_html(html);
+        doc.set_page_size(Size::new(600.3, 100.));
+        doc.set_page_margins(SideOffsets::new_all_same(0.7));
+        let (_, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+        report.overflows.len()
+    };
+    assert_eq!(
+        overflows(b"<body style='margin: 0'><div style='height: 10px'>"),
+        0
+    );
+    assert_eq!(
+        overflows(b"<body style='margin: 0'><div style='height: 10px; margin-right: -0.02px'>"),
+        1
+    );
+}
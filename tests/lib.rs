@@ -6,4 +6,14 @@ extern crate lester;
 extern crate victor;
 
 mod alice;
+mod borders;
+mod cancellation;
+mod computed_style;
+mod css_diagnostics;
+mod image_sources;
+mod layout_report;
+mod limits;
+mod page_overlay;
+mod parallel_layout;
 mod pdf;
+mod pixel_snapping;
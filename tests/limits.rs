@@ -0,0 +1,93 @@
+use victor::dom::{Document, LimitExceeded, Limits, RenderError};
+use victor::fonts::FontContext;
+
+#[test]
+fn max_depth() {
+    let html = format!("<!doctype html>\n{}", "<div>\n".repeat(10));
+    let parse = |max_depth| {
+        let limits = Limits {
+            max_depth: Some(max_depth),
+            ..Limits::default()
+        };
+        Document::parse_html_with_limits(html.as_bytes(), limits).map(|_| ())
+    };
+    // `html` and `body`, then one `div` per line from line 2
+    assert_eq!(parse(12), Ok(()));
+    assert_eq!(parse(11), Err(LimitExceeded::Depth { line: 11 }));
+    assert_eq!(parse(4), Err(LimitExceeded::Depth { line: 4 }));
+}
+
+#[test]
+fn max_boxes_and_pages() {
+    let html = "<p>a</p>".repeat(10);
+    let render = |limits| {
+        Document::parse_html_with_limits(html.as_bytes(), limits)
+            .unwrap()
+            .try_to_pdf_bytes_and_layout_report(FontContext::shared())
+            .map(|(_, report)| report.page_count())
+    };
+    let limits = |max_boxes, max_pages| Limits {
+        max_boxes,
+        max_pages,
+        ..Limits::default()
+    };
+    assert_eq!(render(limits(None, None)), Ok(1));
+    // Root, `body`, and 10 `p` with a text node each
+    assert_eq!(render(limits(Some(22), Some(1))), Ok(1));
+    assert_eq!(
+        render(limits(Some(21), None)),
+        Err(RenderError::LimitExceeded(LimitExceeded::Boxes))
+    );
+    assert_eq!(
+        render(limits(None, Some(0))),
+        Err(RenderError::LimitExceeded(LimitExceeded::Pages))
+    );
+}
+
+#[test]
+fn max_depth_with_encoding_sniffing_and_xml() {
+    let limits = Limits {
+        max_depth: Some(2),
+        ..Limits::default()
+    };
+    let html = b"<!doctype html>\n<html>\n<body>\n<p>";
+    assert!(Document::parse_html_with_encoding_sniffing_and_limits(&html[..30], limits).is_ok());
+    assert_eq!(
+        Document::parse_html_with_encoding_sniffing_and_limits(html, limits).map(|_| ()),
+        Err(LimitExceeded::Depth { line: 4 })
+    );
+
+    let xml = b"<a>\n<b>\n<c/>\n</b>\n</a>";
+    let error = Document::parse_xml_with_limits(xml, limits)
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(
+        error.limit_exceeded(),
+        Some(&LimitExceeded::Depth { line: 3 })
+    );
+    assert!(Document::parse_xml(xml).is_ok());
+    let error = Document::parse_xml(b"<a>").map(|_| ()).unwrap_err();
+    assert_eq!(error.limit_exceeded(), None);
+}
+
+#[test]
+fn limits_are_checked_without_try() {
+    let render = |max_boxes, max_pages| {
+        let limits = Limits {
+            max_boxes,
+            max_pages,
+            ..Limits::default()
+        };
+        let doc = Document::parse_html_with_limits(b"<p id=a>a</p>", limits).unwrap();
+        let font_context = FontContext::shared();
+        let (_, report) = doc.to_pdf_bytes_and_layout_report(font_context);
+        (
+            report.anchors.len(),
+            doc.dump_fragment_tree(font_context).contains("<p>"),
+        )
+    };
+    assert_eq!(render(None, None), (1, true));
+    // Content is left out, like when rendering is cancelled.
+    assert_eq!(render(Some(1), None), (0, false));
+    assert_eq!(render(None, Some(0)), (0, false));
+}
@@ -0,0 +1,23 @@
+use lester::PdfDocument;
+use victor::dom::Document;
+use victor::fonts::BITSTREAM_VERA_SANS;
+use victor::primitives::{point, Length, TextRun};
+use victor::text::ShapedSegment;
+
+#[test]
+fn page_overlay() {
+    let mut doc = Document::parse_html(b"<p>Content</p>");
+    doc.set_page_overlay(|page, size| {
+        let segment = ShapedSegment::naive_shape("DRAFT", BITSTREAM_VERA_SANS.clone()).unwrap();
+        page.show_text(&TextRun {
+            segment: &segment,
+            font_size: Length::new(30.),
+            origin: point(10., size.height - 10.),
+        })
+        .unwrap();
+    });
+    let pdf_bytes = doc.to_pdf_bytes();
+    let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let page = pdf.pages().next().unwrap();
+    assert_eq!(page.text().to_str().unwrap(), "Content\nDRAFT");
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::Path;
+use victor::dom::Document;
+use victor::fonts::FontContext;
+
+/// Box construction and block layout split their work differently
+/// depending on how many threads are in rayon’s pool,
+/// but the resulting PDF should not change.
+#[test]
+fn same_output_with_any_number_of_threads() {
+    let reftests = Path::new(env!("CARGO_MANIFEST_DIR")).join("reftests");
+    let mut paths = Vec::new();
+    for dir in &[reftests.clone(), reftests.join("abspos")] {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "html") {
+                paths.push(path)
+            }
+        }
+    }
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in &paths {
+        let doc = Document::parse_html(&fs::read(path).unwrap());
+        let render = |num_threads| {
+            victor::rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+                .install(|| doc.to_pdf_bytes_and_layout_report(FontContext::shared()))
+        };
+        let (expected_pdf, expected_report) = render(1);
+        for &num_threads in &[2, 3, 8] {
+            let (pdf, report) = render(num_threads);
+            assert!(
+                pdf == expected_pdf && report == expected_report,
+                "{} differs with {} threads",
+                path.display(),
+                num_threads
+            );
+        }
+    }
+}
+
+#[test]
+fn thread_pool() {
+    let html: &[u8] =
+        b"<p style='float: left; width: 50%'>a<div style='position: absolute'>b</div>";
+    let expected = Document::parse_html(html).to_pdf_bytes();
+    for &num_threads in &[1, 4] {
+        let pool = victor::rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut doc = Document::parse_html(html);
+        doc.set_thread_pool(Some(std::sync::Arc::new(pool)));
+        assert!(doc.to_pdf_bytes() == expected);
+    }
+}
@@ -2,14 +2,19 @@ use lester::{Backdrop, PdfDocument, RenderOptions};
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use victor::fonts::{FontError, BITSTREAM_VERA_SANS};
-use victor::pdf::Document;
-use victor::primitives::{point, rect, Length, Size, TextRun, RGBA};
+use std::sync::Arc;
+use victor::dom;
+use victor::fonts::{FontContext, FontError, GenericFontFamily, BITSTREAM_VERA_SANS};
+use victor::pdf::{AttachedFile, Document, FileRelationship, FormField, FormFieldKind};
+use victor::pdf::{Image, ImageData, InvalidImage, PageLabelRange, PageNumberStyle};
+use victor::primitives::{point, rect, FillRule, Length, LineCap, LineJoin, Size, TextRun};
+use victor::primitives::{ColorMatrix, Transform, RGBA};
 use victor::text::ShapedSegment;
 
 include_fonts! {
     AHEM: "fonts/ahem/ahem.ttf",
     NOTO: "fonts/noto/NotoSansLinearB-Regular.ttf",
+    STEMS: "fonts/stems/Stems.ttf",
 }
 
 fn doc() -> Result<Vec<u8>, FontError> {
@@ -60,6 +65,8 @@ fn pdf() {
         doc.producer().unwrap().to_str().unwrap(),
         "Victor <https://github.com/SimonSapin/victor>"
     );
+    // Opening the document runs no action
+    assert_eq!(doc.open_action().unwrap(), None);
 
     let pages: Vec<_> = doc.pages().collect();
     assert_eq!(pages[0].size_in_css_px(), (140., 50.));
@@ -130,3 +137,800 @@ fn pdf() {
 
     assert!(pdf_bytes == include_bytes!("expected.pdf").as_ref());
 }
+
+#[test]
+fn attached_files() {
+    let mut doc = Document::new();
+    doc.add_page(Size::new(10., 10.));
+    doc.attach_file(&AttachedFile {
+        name: "invoice.xml",
+        contents: b"<invoice/>",
+        mime_type: Some("text/xml"),
+        description: Some("Invoice data"),
+        relationship: FileRelationship::Alternative,
+    });
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/Type /EmbeddedFile /Params << /Size 10 >> /Subtype /text#2Fxml"));
+    assert!(pdf.contains("stream\n<invoice/>\nendstream"));
+    assert!(pdf.contains("/AFRelationship /Alternative"));
+    // File name as UTF-16BE
+    let key = "<FEFF0069006E0076006F006900630065002E0078006D006C>";
+    assert!(pdf.contains(&format!("/EmbeddedFiles << /Names [{} 7 0 R] >>", key)));
+    assert!(pdf.contains("/AF [7 0 R]"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(doc.pages().count(), 1);
+}
+
+#[test]
+fn form_fields() {
+    let mut doc = Document::new();
+    doc.add_page(Size::new(100., 100.))
+        .add_form_field(&FormField {
+            name: "name",
+            rect: rect(10., 20., 40., 8.),
+            kind: FormFieldKind::Text {
+                value: "Ann",
+                multiline: false,
+            },
+        })
+        .add_form_field(&FormField {
+            name: "agree",
+            rect: rect(10., 40., 8., 8.),
+            kind: FormFieldKind::Checkbox { checked: true },
+        })
+        .add_form_field(&FormField {
+            name: "size",
+            rect: rect(10., 60., 40., 8.),
+            kind: FormFieldKind::Choice {
+                options: &["S", "M"],
+                selected: Some(1),
+            },
+        });
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/AcroForm << /Fields [4 0 R 5 0 R 6 0 R] /NeedAppearances true"));
+    assert!(pdf.contains("/Annots [4 0 R 5 0 R 6 0 R]"));
+    // In PDF units, with the Y axis pointing up from the top edge of the page
+    assert!(pdf.contains("/Rect [7.5 -21.0 37.5 -15.0]"));
+    assert!(pdf.contains("/FT /Btn /V /Yes /AS /Yes"));
+    assert!(pdf.contains("/Opt [<FEFF0053> <FEFF004D>] /DA (/Helv 0 Tf 0 g) /V <FEFF004D>"));
+    // Only documents with signature fields need incremental updates
+    assert!(!pdf.contains("/SigFlags"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(doc.pages().count(), 1);
+}
+
+#[test]
+fn html_form_fields() {
+    let html = "
+        <input name=name value=Ann>
+        <input type=password name=pin value=1234>
+        <input type=checkbox name=tags checked> <input type=checkbox name=tags>
+        <input type=radio name=choice> <button>Send</button>
+        <select name=size><option>S<optgroup><option selected>M</optgroup></select>
+        <textarea name=notes.text>Hi</textarea>
+    ";
+    let pdf_bytes = victor::dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    let text_string = |s: &str| {
+        let hex: String = s.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+        format!("<FEFF{}>", hex)
+    };
+    // Password inputs, radio buttons and buttons are not fields.
+    assert_eq!(pdf.matches("/Subtype /Widget").count(), 5);
+    for name in &["name", "tags", "tags_2", "size", "notes_text"] {
+        assert!(
+            pdf.contains(&format!("/T {}", text_string(name))),
+            "{}",
+            name
+        );
+    }
+    assert!(pdf.contains(&format!("/FT /Tx /Ff 0 /V {}", text_string("Ann"))));
+    assert!(pdf.contains("/FT /Btn /V /Yes /AS /Yes"));
+    assert!(pdf.contains("/FT /Btn /V /Off /AS /Off"));
+    assert!(pdf.contains("/Opt [<FEFF0053> <FEFF004D>] /DA (/Helv 0 Tf 0 g) /V <FEFF004D>"));
+    // Multiline
+    assert!(pdf.contains(&format!("/FT /Tx /Ff 4096 /V {}", text_string("Hi"))));
+    assert!(pdf.contains("/AcroForm << /Fields ["));
+}
+
+#[test]
+fn signature_placeholder() {
+    let mut doc = Document::new();
+    doc.add_page(Size::new(100., 100.))
+        .add_form_field(&FormField {
+            name: "signature",
+            rect: rect(10., 20., 40., 8.),
+            kind: FormFieldKind::Signature {
+                reserved_bytes: Some(4),
+            },
+        });
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/SigFlags 3"));
+    assert!(pdf.contains("/ByteRange [0 9999999999 9999999999 9999999999] /Contents <00000000>"));
+    assert!(pdf.contains("/FT /Sig /V 4 0 R"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(doc.pages().count(), 1);
+}
+
+#[test]
+fn page_labels() {
+    let mut doc = Document::new();
+    for _ in 0..4 {
+        doc.add_page(Size::new(10., 10.));
+    }
+    doc.add_page_label_range(&PageLabelRange {
+        first_page: 2,
+        style: Some(PageNumberStyle::Decimal),
+        prefix: "",
+        start: 1,
+    });
+    doc.add_page_label_range(&PageLabelRange {
+        first_page: 0,
+        style: Some(PageNumberStyle::LowerRoman),
+        prefix: "",
+        start: 1,
+    });
+    doc.add_page_label_range(&PageLabelRange {
+        first_page: 3,
+        style: None,
+        prefix: "Index",
+        start: 1,
+    });
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/PageLabels << /Nums [0 14 0 R 2 13 0 R 3 15 0 R] >>"));
+    assert!(pdf.contains("14 0 obj\n<< /Type /PageLabel /St 1 /S /r >>"));
+    assert!(pdf.contains("15 0 obj\n<< /Type /PageLabel /St 1 /P <FEFF0049006E006400650078> >>"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(doc.pages().count(), 4);
+}
+
+#[test]
+fn printer_marks() {
+    let trim = rect(48., 48., 96., 96.);
+    let bleed = trim.inflate(6., 6.);
+    let mut doc = Document::new();
+    doc.add_page(Size::new(192., 192.))
+        .set_trim_and_bleed_boxes(&trim, &bleed)
+        .paint_crop_marks(&trim, &bleed)
+        .paint_cross_marks(&trim, &bleed);
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/TrimBox [36.0 -108.0 108.0 -36.0] /BleedBox [31.5 -112.5 112.5 -31.5]"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().next().unwrap().render().unwrap();
+    let pixels = surface.pixels();
+    let opaque = |x: usize, y: usize| pixels.buffer[y * pixels.width + x] >> 24 > 0;
+    // Crop mark above the top left corner, outside of the bleed
+    assert!(opaque(48, 30));
+    assert!(!opaque(48, 45));
+    // Cross mark left of the trim box
+    assert!(opaque(30, 96));
+    // Nothing inside the bleed box
+    assert!(!opaque(96, 96));
+}
+
+#[test]
+fn page_rule_printer_marks() {
+    let mut html = dom::Document::parse_html(
+        b"<style>
+            @page { bleed: 6px; marks: crop cross }
+            body { margin: 0; background: black }
+            div { height: 96px }
+        </style>
+        <div></div>",
+    );
+    html.set_page_size(Size::new(96., 96.));
+    let pdf_bytes = html.to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // The page is on a sheet with a quarter inch for marks outside of the bleed
+    assert!(pdf.contains("/MediaBox [0 0 117.0 -117.0]"));
+    assert!(pdf.contains("/TrimBox [22.5 -94.5 94.5 -22.5] /BleedBox [18.0 -99.0 99.0 -18.0]"));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().next().unwrap().render().unwrap();
+    let pixels = surface.pixels();
+    let opaque = |x: usize, y: usize| pixels.buffer[y * pixels.width + x] >> 24 > 0;
+    // Crop mark above the top left corner of the page
+    assert!(opaque(30, 10));
+    // Content is painted in the trim box, not moved by the sheet’s margins
+    assert!(opaque(32, 32));
+    assert!(!opaque(20, 20));
+}
+
+#[test]
+fn tagged_pdf() {
+    let mut html = dom::Document::parse_html(
+        b"<h1>Title</h1>
+        <p>One</p>
+        <div><p>Two <b>and</b> three</p></div>
+        <span>Untagged</span>",
+    );
+    html.set_tagged_pdf(true);
+    let pdf_bytes = html.to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/StructTreeRoot"));
+    assert!(pdf.contains("/MarkInfo << /Marked true >>"));
+    assert!(pdf.contains("/Type /StructElem /S /H1"));
+    assert!(pdf.contains("/Type /StructElem /S /P"));
+    assert!(pdf.contains("/StructParents 0"));
+    // One sequence of marked content for each element
+    assert_eq!(pdf.matches("BDC").count(), 3);
+    assert_eq!(pdf.matches("EMC").count(), 3);
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let tree = doc.structure_tree();
+    let kinds: Vec<_> = tree.iter().map(|element| element.kind).collect();
+    assert_eq!(kinds, ["H1", "P", "P"]);
+    assert!(tree.iter().all(|element| element.page == Some(0)));
+
+    // Not tagged by default
+    let html = dom::Document::parse_html(b"<p>One</p>");
+    let pdf_bytes = html.to_pdf_bytes();
+    assert!(!String::from_utf8_lossy(&pdf_bytes).contains("/StructTreeRoot"));
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    assert_eq!(doc.structure_tree(), []);
+}
+
+#[test]
+fn generic_font_families() {
+    let mut context = FontContext::new();
+    let monospace = context.generic_family(GenericFontFamily::Monospace);
+    assert!(Arc::ptr_eq(&monospace[0], &BITSTREAM_VERA_SANS));
+    context.set_fallback_fonts(vec![NOTO.clone()]);
+    context.set_generic_family(GenericFontFamily::SansSerif, vec![AHEM.clone()]);
+    let monospace = context.generic_family(GenericFontFamily::Monospace);
+    assert!(Arc::ptr_eq(&monospace[0], &NOTO));
+
+    let html = dom::Document::parse_html(b"<p>Test");
+    let pdf_bytes = html.to_pdf_bytes_with_font_context(&context);
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("Ahem"));
+    assert!(!pdf.contains("BitstreamVeraSans"));
+
+    // Generic families are selected with `font-family`, including in the user-agent stylesheet
+    let mut context = FontContext::new();
+    context.set_generic_family(GenericFontFamily::Monospace, vec![AHEM.clone()]);
+    let fonts = |html: &str| {
+        let html = dom::Document::parse_html(html.as_bytes());
+        let pdf_bytes = html.to_pdf_bytes_with_font_context(&context);
+        let pdf = String::from_utf8_lossy(&pdf_bytes);
+        (pdf.contains("Ahem"), pdf.contains("BitstreamVeraSans"))
+    };
+    assert_eq!(fonts("<p>Test"), (false, true));
+    assert_eq!(fonts("<p>Test <code>x</code>"), (true, true));
+    let html = "<p style='font-family: \"Foo\", Bar Baz, monospace, serif'>Test";
+    assert_eq!(fonts(html), (true, false));
+    let html = "<p style='font-family: monospace'><span style='font-family: Foo'>Test";
+    assert_eq!(fonts(html), (false, true));
+}
+
+#[test]
+fn font_fallback() {
+    let mut context = FontContext::new();
+    context.set_generic_family(
+        GenericFontFamily::SansSerif,
+        vec![BITSTREAM_VERA_SANS.clone(), NOTO.clone()],
+    );
+    let render = |html: &str| {
+        let pdf_bytes =
+            dom::Document::parse_html(html.as_bytes()).to_pdf_bytes_with_font_context(&context);
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<p>Test 𐁉 𐁁𐀓𐀠𐀴𐀍");
+    assert!(pdf.contains("BitstreamVeraSans"));
+    assert!(pdf.contains("NotoSansLinearB"));
+    let pdf = render("<p>Test");
+    assert!(!pdf.contains("NotoSansLinearB"));
+}
+
+#[test]
+fn synthetic_bold_and_oblique() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<p style='font-size: 48px'><b>Bold</b> <i>Italic</i>");
+    assert!(pdf.contains("\n2.0 w\n2 Tr\n"));
+    assert!(pdf.contains("\nq\n1.0 0.0 -0.249328 1.0 "));
+    let pdf = render("<p style='font-size: 48px; font-synthesis: none'><b>Bold</b> <i>Italic</i>");
+    assert!(!pdf.contains(" Tr\n"));
+    assert!(!pdf.contains("-0.249328"));
+}
+
+#[test]
+fn variable_fonts() {
+    let mut context = FontContext::new();
+    context.set_generic_family(GenericFontFamily::SansSerif, vec![STEMS.clone()]);
+    let render = |html: &str| {
+        let pdf_bytes =
+            dom::Document::parse_html(html.as_bytes()).to_pdf_bytes_with_font_context(&context);
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<p>HI <b>HI</b> <span style='font-weight: 1000'>HI</span>");
+    // Each instance is embedded as its own font, with the advance widths of its glyphs
+    assert!(pdf.contains("/BaseFont /Stems-Regular /"));
+    assert!(pdf.contains("/W [0 [500 250 300 600 250]]"));
+    assert!(pdf.contains("/BaseFont /Stems-Regular_700wght /"));
+    assert!(pdf.contains("/W [0 [500 250 460 920 250]]"));
+    // 1000 is clamped to the range of the `wght` axis
+    assert!(pdf.contains("/BaseFont /Stems-Regular_900wght /"));
+    assert!(pdf.contains("/W [0 [500 250 500 1000 250]]"));
+    // Bold instances are not emboldened again
+    assert!(!pdf.contains(" Tr\n"));
+
+    let pdf = render("<p style='font-stretch: 200%; font-variation-settings: \"wght\" 100'>HI");
+    assert!(pdf.contains("/BaseFont /Stems-Regular_100wght_200wdth /"));
+    assert!(!pdf.contains("/BaseFont /Stems-Regular /"));
+}
+
+#[test]
+fn ellipsis_without_ellipsis_character() {
+    let mut context = FontContext::new();
+    context.set_generic_family(GenericFontFamily::SansSerif, vec![STEMS.clone()]);
+    let html = "<p style='width: 150px; font-size: 100px; white-space: nowrap; \
+                overflow: hidden; text-overflow: ellipsis'>HIHI";
+    let pdf_bytes =
+        dom::Document::parse_html(html.as_bytes()).to_pdf_bytes_with_font_context(&context);
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // The font has no U+2026, so the line ends with three periods: "H..."
+    assert!(pdf.contains("<0003000400040004> Tj"));
+}
+
+#[test]
+fn synthetic_small_caps() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    // The font has no `smcp` feature, so lowercase letters become smaller capitals.
+    let pdf = render("<p style='font-size: 40px; font-variant-caps: small-caps'>Small caps");
+    assert_eq!(pdf.matches("\n40.0 0 0 -40.0 ").count(), 2);
+    assert_eq!(pdf.matches("\n28.0 0 0 -28.0 ").count(), 2);
+    let pdf = render(
+        "<p style='font-size: 40px; font-variant-caps: small-caps; font-synthesis: none'>Small caps",
+    );
+    assert_eq!(pdf.matches("\n40.0 0 0 -40.0 ").count(), 1);
+    assert!(!pdf.contains("\n28.0 0 0 -28.0 "));
+}
+
+#[test]
+fn text_shadow() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<p style='text-shadow: 2px 4px red'>Shadow");
+    let shadow = pdf.find("\n1.0 0.0 0.0 rg\nBT\n/f0 1 Tf\n16.0 0 0 -16.0 10.0 34.85 Tm\n");
+    let text = pdf.find("\n0.0 0.0 0.0 rg\nBT\n/f0 1 Tf\n16.0 0 0 -16.0 8.0 30.85 Tm\n");
+    assert!(shadow.unwrap() < text.unwrap());
+    // A blurred shadow is nine translucent copies, below an unblurred one.
+    let pdf = render("<p style='text-shadow: blue 1px 1px 4px, 2px 2px'>Shadow");
+    assert_eq!(pdf.matches(" Tj\n").count(), 11);
+    assert_eq!(pdf.matches(" gs\n").count(), 2);
+    let pdf = render("<p style='text-shadow: 1px 1px -4px'>Shadow");
+    assert_eq!(pdf.matches(" Tj\n").count(), 1);
+}
+
+#[test]
+fn text_decorations() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let lines = |html: &str| render(html).matches(" re\n").count();
+    // The underline is interrupted around the descender of "g", but not under "x".
+    assert_eq!(lines("<p style='text-decoration: underline'>xx"), 1);
+    assert_eq!(lines("<p style='text-decoration: underline'>xgx"), 2);
+    assert_eq!(
+        lines("<p style='text-decoration: underline; text-decoration-skip-ink: none'>xgx"),
+        1
+    );
+    // Decorations of a box are painted in its color on the text of its in-flow descendants.
+    let pdf = render(
+        "<p style='color: blue; text-decoration: underline'>\
+         x<span style='color: red'>x</span><span style='float: left'>x</span>",
+    );
+    assert_eq!(pdf.matches(" re\n").count(), 2);
+    assert!(pdf.rfind(" re\n").unwrap() < pdf.find("\n1.0 0.0 0.0 rg\n").unwrap());
+    // Lines through text are painted after it, other lines before
+    let pdf = render("<s>x");
+    assert!(pdf.find(" Tj\n").unwrap() < pdf.find(" re\n").unwrap());
+    let pdf = render("<u>x");
+    assert!(pdf.find(" re\n").unwrap() < pdf.find(" Tj\n").unwrap());
+}
+
+#[test]
+fn filters() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render(
+        "<div style='height: 10px; background-color: red; filter: grayscale(100%) opacity(.5)'>",
+    );
+    assert!(pdf.contains("\n0.2126 0.2126 0.2126 rg\n"));
+    assert!(pdf.contains("/CA 1.0 /ca 0.5"));
+    // The shadow is the box painted again in its color, below the box.
+    let pdf = render(
+        "<div style='height: 10px; background-color: red; filter: drop-shadow(blue 2px 2px)'>",
+    );
+    let shadow = pdf.find("\n0.0 0.0 1.0 rg\n");
+    let background = pdf.find("\n1.0 0.0 0.0 rg\n");
+    assert!(shadow.unwrap() < background.unwrap());
+    assert_eq!(pdf.matches(" re\n").count(), 2);
+    assert_eq!(pdf.matches(" Do\n").count(), 1);
+    // A blurred shadow is nine translucent copies, like for text,
+    // of a form with the box painted once.
+    let pdf = render(
+        "<div style='height: 10px; background-color: red; filter: drop-shadow(2px 2px 4px)'>",
+    );
+    assert_eq!(pdf.matches(" re\n").count(), 2);
+    assert_eq!(pdf.matches(" Do\n").count(), 9);
+    // Nested drop shadows are only painted up to a limit, and the others are reported.
+    let nested = |depth| {
+        let shadows = "<div style='filter: drop-shadow(1px 1px)'>\n".repeat(depth);
+        let red = "<div style='height: 10px; background-color: red'>";
+        let doc = dom::Document::parse_html(format!("{}{}", shadows, red).as_bytes());
+        let (pdf_bytes, report) = doc.to_pdf_bytes_and_layout_report(FontContext::shared());
+        let skipped_lines: Vec<_> = report
+            .skipped_drop_shadows
+            .iter()
+            .map(|element| element.line)
+            .collect();
+        let pdf = String::from_utf8_lossy(&pdf_bytes).into_owned();
+        (pdf.matches(" re\n").count(), skipped_lines)
+    };
+    assert_eq!(nested(8), (9, vec![]));
+    assert_eq!(nested(12), (9, vec![9, 10, 11, 12]));
+    let pdf = render("<div style='height: 10px; background-color: red; filter: blur(2px)'>");
+    assert_eq!(pdf.matches(" re\n").count(), 1);
+    assert!(pdf.contains("\n1.0 0.0 0.0 rg\n"));
+}
+
+#[test]
+fn transforms() {
+    let render = |style: &str| {
+        let html = format!(
+            "<body style='margin: 0'><div style='width: 100px; height: 50px; {}'>",
+            style
+        );
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    // Scaled around the center of the border box by default
+    let pdf = render("transform: scale(2)");
+    assert!(pdf.contains("\n2.0 0.0 0.0 2.0 -50.0 -25.0 cm\n"));
+    let pdf = render("transform: scale(2); transform-origin: 0 0");
+    assert!(pdf.contains("\n2.0 0.0 0.0 2.0 0.0 0.0 cm\n"));
+    let pdf = render("transform: scale(2); transform-origin: right bottom");
+    assert!(pdf.contains("\n2.0 0.0 0.0 2.0 -100.0 -50.0 cm\n"));
+    // A single length is horizontal, with the vertical origin centered.
+    let pdf = render("transform: scale(2); transform-origin: 10px");
+    assert!(pdf.contains("\n2.0 0.0 0.0 2.0 -10.0 -25.0 cm\n"));
+    let pdf = render("transform: translate(10px, 50%) rotate(90deg); transform-origin: top left");
+    assert!(pdf.contains("\n0.0 1.0 -1.0 0.0 10.0 25.0 cm\n"));
+}
+
+#[test]
+fn blend_modes() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<div style='height: 10px; background-color: red; mix-blend-mode: multiply'>");
+    assert!(pdf.contains("/Group << /S /Transparency /I true >>"));
+    assert!(pdf.contains("/BM /Multiply"));
+    assert_eq!(pdf.matches(" Do\n").count(), 1);
+    let pdf = render("<div style='isolation: isolate'><p>Text");
+    assert_eq!(pdf.matches(" Do\n").count(), 1);
+    assert!(!pdf.contains("/BM"));
+    // Other stacking contexts are only painted as groups when something in them blends.
+    let pdf = render("<div style='transform: rotate(1deg)'><p>Text");
+    assert!(!pdf.contains("/Group"));
+    let pdf = render("<div style='transform: rotate(1deg)'><p style='mix-blend-mode: screen'>Text");
+    assert_eq!(pdf.matches(" Do\n").count(), 2);
+    let pdf =
+        render("<div style='position: relative; z-index: 0'><p style='mix-blend-mode: screen'>");
+    assert_eq!(pdf.matches(" Do\n").count(), 2);
+    let pdf = render("<div style='position: relative'><p style='mix-blend-mode: screen'>Text");
+    assert_eq!(pdf.matches(" Do\n").count(), 1);
+    // The group’s bounding box is the ink overflow of the box, with a pixel to spare.
+    let pdf = render(
+        "<body style='margin: 0'><div style='width: 20px; height: 10px; background-color: red; \
+         filter: drop-shadow(4px 6px); mix-blend-mode: multiply'>",
+    );
+    assert!(pdf.contains("/BBox [-1.0 -1.0 25.0 17.0]"));
+
+    // Blue multiplied by yellow is black, unless an isolated group is between them.
+    let pixels = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+        let mut surface = pdf.pages().next().unwrap().render().unwrap();
+        let pixels = surface.pixels();
+        [pixels.buffer[0], pixels.buffer[1]]
+    };
+    const YELL: u32 = 0xFFFF_FF00;
+    const BLCK: u32 = 0xFF00_0000;
+    const BLUE: u32 = 0xFF00_00FF;
+    let yellow = "<body style='margin: 0'><div style='width: 2px; background-color: yellow'>";
+    let blended =
+        "<div style='width: 1px; height: 1px; background-color: blue; mix-blend-mode: multiply'>";
+    assert_eq!(pixels(&format!("{}{}", yellow, blended)), [BLCK, YELL]);
+    let isolated = format!("{}<div style='isolation: isolate'>{}", yellow, blended);
+    assert_eq!(pixels(&isolated), [BLUE, YELL]);
+}
+
+#[test]
+fn clip_path() {
+    let render = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        String::from_utf8_lossy(&pdf_bytes).into_owned()
+    };
+    let pdf = render("<div style='height: 10px; clip-path: circle(5px)'>Text");
+    assert_eq!(pdf.matches("\nW\nn\n").count(), 1);
+    assert_eq!(pdf.matches(" c\n").count(), 4);
+    let pdf = render("<div style='clip-path: polygon(evenodd, 0 0, 100% 0, 0 100%)'>Text");
+    assert_eq!(pdf.matches("\nW*\nn\n").count(), 1);
+    let pdf = render("<div style='clip-path: none'>Text");
+    assert!(!pdf.contains("\nW\nn\n"));
+
+    let pixels = |html: &str| {
+        let pdf_bytes = dom::Document::parse_html(html.as_bytes()).to_pdf_bytes();
+        let pdf = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+        let mut surface = pdf.pages().next().unwrap().render().unwrap();
+        let pixels = surface.pixels();
+        [pixels.buffer[0], pixels.buffer[1], pixels.buffer[2]]
+    };
+    const RED_: u32 = 0xFFFF_0000;
+    const ____: u32 = 0x0000_0000;
+    // A 1px border on the left of a 2px wide content box
+    let clipped = |clip_path: &str| {
+        pixels(&format!(
+            "<body style='margin: 0'><div style='width: 2px; height: 1px; background-color: red; \
+             border-left-style: solid; border-left-width: 1px; border-left-color: red; \
+             clip-path: {}'>",
+            clip_path
+        ))
+    };
+    assert_eq!(clipped("none"), [RED_, RED_, RED_]);
+    assert_eq!(clipped("inset(0 1px 0 0)"), [RED_, RED_, ____]);
+    assert_eq!(clipped("padding-box"), [____, RED_, RED_]);
+    assert_eq!(clipped("inset(0 1px) content-box"), [____, ____, ____]);
+}
+
+#[test]
+fn color_matrix() {
+    let swap_red_and_blue = ColorMatrix([
+        [0., 0., 1., 0., 0.],
+        [0., 1., 0., 0., 0.],
+        [1., 0., 0., 0., 0.],
+        [0., 0., 0., 1., 0.],
+    ]);
+    let translucent_blue = ColorMatrix::opacity(0.5)
+        .then(&swap_red_and_blue)
+        .apply(RGBA(1., 0., 0., 1.));
+    assert!(translucent_blue == RGBA(0., 0., 1., 0.5));
+
+    let image = Image {
+        width: 2,
+        height: 1,
+        data: ImageData::Rgb8(&[255, 0, 0, 0, 0, 255]),
+    };
+    let mut doc = Document::new();
+    doc.add_page(Size::new(4., 1.))
+        .save_state()
+        .apply_color_matrix(&swap_red_and_blue)
+        .paint_image(&image, &rect(0., 0., 2., 1.))
+        .unwrap()
+        .set_color(&RGBA(1., 0., 0., 1.))
+        .paint_rectangle(&rect(2., 0., 1., 1.))
+        .restore_state()
+        .set_color(&RGBA(1., 0., 0., 1.))
+        .paint_rectangle(&rect(3., 0., 1., 1.));
+    let pdf_bytes = doc.write_to_pdf_bytes();
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().next().unwrap().render().unwrap();
+    const RED_: u32 = 0xFFFF_0000;
+    const BLUE: u32 = 0xFF00_00FF;
+    assert_pixels_eq!(surface.pixels().buffer, &[BLUE, RED_, BLUE, RED_]);
+}
+
+#[test]
+fn stroke_style() {
+    let mut doc = Document::new();
+    doc.add_page(Size::new(10., 10.))
+        .set_line_cap(LineCap::Round)
+        .set_line_join(LineJoin::Bevel)
+        .set_miter_limit(4.)
+        .set_dash_pattern(&[Length::new(3.), Length::new(1.)], Length::new(2.))
+        .set_line_join(LineJoin::Bevel)
+        .set_line_cap(LineCap::Butt);
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("\n1 J\n2 j\n4.0 M\n[3.0 1.0] 2.0 d\n0 J\n"));
+    assert_eq!(pdf.matches(" j\n").count(), 1);
+}
+
+#[test]
+fn fill_and_stroke_alpha() {
+    let mut doc = Document::new();
+    doc.add_page(Size::new(10., 10.))
+        .set_stroke_color(&RGBA(0., 0., 1., 0.5))
+        .set_color(&RGBA(1., 0., 0., 1.))
+        .paint_rectangle(&rect(0., 0., 5., 5.));
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // Setting the fill color after the stroke color keeps the stroking alpha.
+    assert!(pdf.contains("/CA 0.5 /ca 1.0"));
+    assert!(!pdf.contains("/CA 1.0"));
+}
+
+#[test]
+fn paths_and_images() {
+    let image = Image {
+        width: 2,
+        height: 2,
+        data: ImageData::Rgb8(&[0, 0, 255, 0, 255, 0, 0, 255, 0, 0, 0, 255]),
+    };
+    let mut doc = Document::new();
+    doc.add_page(Size::new(4., 4.))
+        .save_state()
+        .move_to(&point(0., 0.))
+        .line_to(&point(2., 0.))
+        .curve_to(&point(2., 1.), &point(2., 1.), &point(2., 2.))
+        .line_to(&point(0., 2.))
+        .close_path()
+        .clip(FillRule::NonZero)
+        .set_color(&RGBA(1., 0., 0., 1.))
+        .paint_rectangle(&rect(0., 0., 4., 4.))
+        .restore_state()
+        .transform(&Transform::create_translation(2., 2.))
+        .paint_image(&image, &rect(0., 0., 2., 2.))
+        .unwrap();
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(
+        pdf.contains("\nq\n0.0 0.0 m\n2.0 0.0 l\n2.0 1.0 2.0 1.0 2.0 2.0 c\n0.0 2.0 l\nh\nW\nn\n")
+    );
+    assert!(pdf.contains("/XObject << /i0 "));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().next().unwrap().render().unwrap();
+    const RED_: u32 = 0xFFFF_0000;
+    const GREE: u32 = 0xFF00_FF00;
+    const BLUE: u32 = 0xFF00_00FF;
+    const ____: u32 = 0x0000_0000;
+    #[rustfmt::skip]
+    assert_pixels_eq!(
+        surface.pixels().buffer,
+        &[
+            RED_, RED_, ____, ____,
+            RED_, RED_, ____, ____,
+            ____, ____, BLUE, GREE,
+            ____, ____, GREE, BLUE,
+        ]
+    );
+}
+
+#[test]
+fn invalid_image() {
+    let image = Image {
+        width: 2,
+        height: 2,
+        data: ImageData::Rgb8(&[0, 0, 255]),
+    };
+    let mut doc = Document::new();
+    let mut page = doc.add_page(Size::new(4., 4.));
+    assert_eq!(
+        page.paint_image(&image, &rect(0., 0., 2., 2.)).map(|_| ()),
+        Err(InvalidImage)
+    );
+}
+
+#[test]
+fn repeated_images() {
+    let samples = [0, 0, 255, 0, 255, 0, 0, 255, 0, 0, 0, 255];
+    let copy = samples.to_vec();
+    let image = |samples| Image {
+        width: 2,
+        height: 2,
+        data: ImageData::Rgb8(samples),
+    };
+    let (image, same_image, other_image) = (image(&samples), image(&copy), image(&[0; 12]));
+    let mut doc = Document::new();
+    doc.add_page(Size::new(4., 4.))
+        .paint_image(&image, &rect(0., 0., 2., 2.))
+        .unwrap()
+        .paint_image_tiles(&image, &rect(0., 0., 1., 1.), &rect(0., 0., 4., 4.))
+        .unwrap();
+    doc.add_page(Size::new(4., 4.))
+        .paint_image(&same_image, &rect(0., 0., 2., 2.))
+        .unwrap()
+        .paint_image(&other_image, &rect(0., 0., 2., 2.))
+        .unwrap();
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    // Images with the same samples are embedded once.
+    assert_eq!(pdf.matches("/Subtype /Image").count(), 2);
+    assert_eq!(pdf.matches("/i1 Do").count(), 1);
+}
+
+#[test]
+fn max_image_resolution() {
+    let r = [255, 0, 0];
+    let g = [0, 255, 0];
+    let b = [0, 0, 255];
+    let w = [255, 255, 255];
+    let rows = [[r, r, g, g], [r, r, g, g], [b, b, w, w], [b, b, w, w]];
+    let samples: Vec<u8> = rows.iter().flatten().flatten().cloned().collect();
+    let image = Image {
+        width: 4,
+        height: 4,
+        data: ImageData::Rgb8(&samples),
+    };
+    let mut doc = Document::new();
+    doc.set_max_image_resolution(Some(96.));
+    doc.add_page(Size::new(2., 2.))
+        .paint_image(&image, &rect(0., 0., 2., 2.))
+        .unwrap();
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert!(pdf.contains("/Width 2 /Height 2 "));
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().next().unwrap().render().unwrap();
+    assert_pixels_eq!(
+        surface.pixels().buffer,
+        &[0xFFFF_0000, 0xFF00_FF00, 0xFF00_00FF, 0xFFFF_FFFF]
+    );
+}
+
+#[test]
+fn repeated_content() {
+    let mut doc = Document::new();
+    for i in 0..4 {
+        let mut page = doc.add_page(Size::new(4., 4.));
+        if i < 2 {
+            page.paint_repeated(|page| {
+                page.set_color(&RGBA(1., 0., 0., 1.))
+                    .paint_rectangle(&rect(0., 0., 2., 4.));
+            });
+        }
+        if i == 1 {
+            page.set_color(&RGBA(0., 0., 1., 1.))
+                .paint_rectangle(&rect(2., 0., 2., 4.));
+        }
+    }
+    let pdf_bytes = doc.write_to_pdf_bytes();
+    let pdf = String::from_utf8_lossy(&pdf_bytes);
+    assert_eq!(pdf.matches("/Subtype /Form").count(), 1);
+    assert_eq!(pdf.matches("/x0 Do").count(), 2);
+    // The two blank pages share their content stream
+    assert_eq!(pdf.matches("/Type /Page ").count(), 4);
+    assert_eq!(pdf.matches("/Length").count(), 4);
+
+    let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = doc.pages().nth(1).unwrap().render().unwrap();
+    const RED_: u32 = 0xFFFF_0000;
+    const BLUE: u32 = 0xFF00_00FF;
+    #[rustfmt::skip]
+    assert_pixels_eq!(
+        surface.pixels().buffer,
+        &[
+            RED_, RED_, BLUE, BLUE,
+            RED_, RED_, BLUE, BLUE,
+            RED_, RED_, BLUE, BLUE,
+            RED_, RED_, BLUE, BLUE,
+        ]
+    );
+}
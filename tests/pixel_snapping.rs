@@ -0,0 +1,24 @@
+use victor::dom::Document;
+
+#[test]
+fn pixel_snapping() {
+    let mut doc = Document::parse_html(
+        br#"
+        <body style="margin: 0">
+            <div style="margin-left: 0.5px; width: 1px; height: 0.25px; background: #00f"></div>
+        </body>
+        "#,
+    );
+    doc.set_pixel_snapping(Some(1.));
+    let pdf_bytes = doc.to_pdf_bytes();
+    let pdf = lester::PdfDocument::from_bytes(&pdf_bytes).unwrap();
+    let mut surface = pdf.pages().next().unwrap().render().unwrap();
+    let pixels = surface.pixels();
+    let row = |y: usize| &pixels.buffer[y * pixels.width..][..3];
+
+    const BLUE: u32 = 0xFF00_00FF;
+    const ____: u32 = 0x0000_0000;
+    // The left edge moves from 0.5 to 1, and the hairline grows to one device pixel
+    assert_eq!(row(0), [____, BLUE, ____]);
+    assert_eq!(row(1), [____, ____, ____]);
+}
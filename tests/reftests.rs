@@ -1,20 +1,103 @@
+use lester::reftest::{images_match, MatchOptions};
 use std::env;
 use std::fs;
 use std::io::Write;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 
 fn main() {
     let args: Vec<_> = env::args().skip(1).collect();
     let base = reftests_dir();
+    let expectations = Expectations::load(&base);
+    // Only keep the renderings of tests that fail this time
+    let failures = target_dir().join("reftests");
+    if failures.exists() {
+        fs::remove_dir_all(failures).unwrap()
+    }
+    let mut unexpected = Vec::new();
     for_each_file_in(base.clone(), &mut |path| {
-        if !args.is_empty() {
-            let relative = path.strip_prefix(&base).unwrap().to_str().unwrap();
-            if !args.iter().any(|arg| relative.contains(arg)) {
-                return;
+        let relative = path.strip_prefix(&base).unwrap().to_str().unwrap();
+        if !args.is_empty() && !args.iter().any(|arg| relative.contains(arg)) {
+            return;
+        }
+        let expectation = expectations.get(relative);
+        if expectation == Expectation::Skip {
+            return;
+        }
+        let relative = relative.to_owned();
+        match (TestFile::load(path).test(), expectation) {
+            (Ok(()), Expectation::Pass) => {}
+            (Err(message), Expectation::Fail) => println!("Expected failure: {}", message),
+            (Ok(()), _) => unexpected.push(format!("Unexpected pass: {}", relative)),
+            (Err(message), _) => unexpected.push(message),
+        }
+    });
+    if !unexpected.is_empty() {
+        panic!(
+            "{}\n\nUpdate {} if these are expected.",
+            unexpected.join("\n"),
+            show(&Expectations::path(&base))
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Expectation {
+    Pass,
+    Fail,
+    Skip,
+}
+
+/// Tests that are not expected to pass, for example for work-in-progress features.
+///
+/// Each non-empty line of `reftest-expectations.txt` that does not start with `#`
+/// is a path relative to `reftests`, then `FAIL` or `SKIP`, then optionally
+/// the platforms (as in `std::env::consts::OS`) that this applies to, separated by spaces.
+/// Without platforms, it applies to all of them.
+struct Expectations(Vec<(String, Expectation)>);
+
+impl Expectations {
+    fn path(reftests_dir: &Path) -> PathBuf {
+        reftests_dir.with_file_name("reftest-expectations.txt")
+    }
+
+    fn load(reftests_dir: &Path) -> Self {
+        let path = Self::path(reftests_dir);
+        let text = fs::read_to_string(&path).unwrap();
+        let mut expectations = Vec::new();
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let test = match words.next() {
+                Some(word) if !word.starts_with('#') => word,
+                _ => continue,
+            };
+            assert!(
+                reftests_dir.join(test).is_file(),
+                "{} names a file that does not exist: {}",
+                show(&path),
+                test
+            );
+            let expectation = match words.next() {
+                Some("FAIL") => Expectation::Fail,
+                Some("SKIP") => Expectation::Skip,
+                other => panic!("{}: expected FAIL or SKIP, got {:?}", show(&path), other),
+            };
+            let platforms: Vec<_> = words.collect();
+            if platforms.is_empty() || platforms.contains(&env::consts::OS) {
+                expectations.push((test.to_owned(), expectation))
             }
         }
-        TestFile::load(path).test()
-    })
+        Expectations(expectations)
+    }
+
+    fn get(&self, relative_path: &str) -> Expectation {
+        // Expectations use `/` separators on all platforms
+        let relative_path = relative_path.replace(std::path::MAIN_SEPARATOR, "/");
+        self.0
+            .iter()
+            .find(|(test, _)| *test == relative_path)
+            .map_or(Expectation::Pass, |&(_, expectation)| expectation)
+    }
 }
 
 fn target_dir() -> PathBuf {
@@ -26,6 +109,14 @@ fn target_dir() -> PathBuf {
     target.to_owned()
 }
 
+/// Where the renderings of a failing test are written, so that each failure keeps its own:
+/// for example `target/reftests/abspos/absolute-non-replaced-height-002.html/` with `test.png`,
+/// `reference.png`, and `test.pdf`.
+fn failure_dir(test_path: &Path) -> PathBuf {
+    let relative = test_path.strip_prefix(reftests_dir()).unwrap();
+    target_dir().join("reftests").join(relative)
+}
+
 fn reftests_dir() -> PathBuf {
     let target = target_dir();
     let repo = target.parent().unwrap();
@@ -62,7 +153,21 @@ impl TestFile {
             Some("html") => doc = Some(victor::dom::Document::parse_html(&bytes)),
             Some("pdf") => pdf = Some(bytes),
             Some("png") => {
-                pages_pixels = Some(vec![lester::ImageSurface::read_from_png(&*bytes).unwrap()])
+                let mut pages = vec![lester::ImageSurface::read_from_png(&*bytes).unwrap()];
+                // A `-page1.png` reference is the first page of a multi-page reference,
+                // followed by `-page2.png` and so on.
+                let path_str = path.to_str().unwrap();
+                if let Some(prefix) = path_str.strip_suffix("-page1.png") {
+                    for number in 2.. {
+                        match fs::read(format!("{}-page{}.png", prefix, number)) {
+                            Ok(bytes) => {
+                                pages.push(lester::ImageSurface::read_from_png(&*bytes).unwrap())
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                }
+                pages_pixels = Some(pages)
             }
             ext => panic!("Unsupported file extension: {:?}", ext),
         }
@@ -93,17 +198,21 @@ impl TestFile {
         self.pages_pixels.as_mut().unwrap()
     }
 
-    fn expect_single_page(&mut self) -> lester::Argb32Pixels {
-        let pages = self.pages_pixels();
-        assert_eq!(pages.len(), 1);
-        pages[0].pixels()
+    fn pages(&mut self) -> Vec<lester::Argb32Pixels> {
+        self.pages_pixels()
+            .iter_mut()
+            .map(|page| page.pixels())
+            .collect()
     }
 
-    fn write_png(&mut self, path: &Path) {
-        self.pages_pixels()[0].write_to_png_file(path).unwrap()
+    /// Does nothing if there is no such page.
+    fn write_png(&mut self, page_index: usize, path: &Path) {
+        if let Some(page) = self.pages_pixels().get(page_index) {
+            page.write_to_png_file(path).unwrap()
+        }
     }
 
-    fn test(&mut self) {
+    fn test(&mut self) -> Result<(), String> {
         let base = self.path.parent().unwrap();
         let references = self.doc.as_ref().map_or(Vec::new(), |doc| {
             doc.html_link_elements()
@@ -114,26 +223,66 @@ impl TestFile {
                 })
                 .collect()
         });
-        let page = self.expect_single_page();
+        let fuzzy = match &self.doc {
+            Some(doc) => doc
+                .html_meta_elements()
+                .filter(|&(name, _)| name == "fuzzy")
+                .map(|(_, content)| {
+                    parse_fuzzy(base, content).map_err(|message| {
+                        format!(
+                            "{}: invalid fuzzy annotation: {}",
+                            show(&self.path),
+                            message
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let pages = self.pages();
         for (expect_equal, reference_path) in references {
+            // An annotation for this reference, or else one for all references
+            let fuzzy = fuzzy
+                .iter()
+                .find(|(path, _)| path.as_ref() == Some(&reference_path))
+                .or_else(|| fuzzy.iter().find(|(path, _)| path.is_none()))
+                .map_or_else(Fuzzy::default, |(_, fuzzy)| fuzzy.clone());
             let mut reference = Self::load(reference_path);
-            let reference_page = reference.expect_single_page();
-            if (page == reference_page) != expect_equal {
-                let test_png = target_dir().join("test.png");
-                let reference_png = target_dir().join("reference.png");
-                self.write_png(&test_png);
-                reference.write_png(&reference_png);
-                std::fs::write(target_dir().join("test.pdf"), self.pdf_bytes()).unwrap();
-                panic!(
-                    "Failed {} {} ↔ {}\n{}\n{}",
+            let reference_pages = reference.pages();
+            let page_matches = |(page, reference_page): (&lester::Argb32Pixels, _)| {
+                fuzzy.matches(page, reference_page)
+            };
+            let matches = pages.len() == reference_pages.len()
+                && pages.iter().zip(&reference_pages).all(page_matches);
+            if matches != expect_equal {
+                // The first page that differs, or the first extra page
+                let page_index = pages
+                    .iter()
+                    .zip(&reference_pages)
+                    .position(|pair| !page_matches(pair))
+                    .unwrap_or_else(|| pages.len().min(reference_pages.len()));
+                let (page_count, reference_page_count) = (pages.len(), reference_pages.len());
+                let dir = failure_dir(&self.path);
+                fs::create_dir_all(&dir).unwrap();
+                let test_png = dir.join("test.png");
+                let reference_png = dir.join("reference.png");
+                self.write_png(page_index, &test_png);
+                reference.write_png(page_index, &reference_png);
+                fs::write(dir.join("test.pdf"), self.pdf_bytes()).unwrap();
+                return Err(format!(
+                    "Failed {} {} ↔ {} at page {} of {} ↔ {}\n{}\n{}",
                     if expect_equal { "match" } else { "mismatch" },
                     show(&self.path),
                     show(&reference.path),
+                    page_index + 1,
+                    page_count,
+                    reference_page_count,
                     show(&test_png),
                     show(&reference_png),
-                )
+                ));
             }
         }
+        Ok(())
     }
 }
 
@@ -143,6 +292,119 @@ fn show(path: &Path) -> std::path::Display {
         .display()
 }
 
+/// How much a page may differ from its reference:
+/// https://web-platform-tests.org/writing-tests/reftests.html#fuzzy-matching
+#[derive(Clone)]
+struct Fuzzy {
+    /// The largest difference in any channel of any pixel, from 0 to 255
+    max_difference: RangeInclusive<u8>,
+    /// The number of pixels that differ at all
+    total_pixels: RangeInclusive<usize>,
+}
+
+impl Default for Fuzzy {
+    fn default() -> Self {
+        Fuzzy {
+            max_difference: 0..=0,
+            total_pixels: 0..=0,
+        }
+    }
+}
+
+impl Fuzzy {
+    /// Like WPT, images that are the same match if either range starts at zero,
+    /// and other images match if both the largest difference and the number of pixels that differ
+    /// are in their ranges.
+    /// So a range with a minimum above zero also fails a test that no longer differs as much,
+    /// for example after a fix.
+    fn matches(&self, a: &lester::Argb32Pixels, b: &lester::Argb32Pixels) -> bool {
+        if (a.width, a.height) != (b.width, b.height) {
+            return false;
+        }
+        // Whether at most `max_differing_pixels` differ by more than `per_channel_tolerance`
+        let within = |per_channel_tolerance, max_differing_pixels| {
+            let options = MatchOptions {
+                per_channel_tolerance,
+                max_differing_pixels,
+                ..MatchOptions::default()
+            };
+            images_match(a, b, &options)
+        };
+        let (min_difference, max_difference) = self.max_difference.clone().into_inner();
+        let (min_pixels, max_pixels) = self.total_pixels.clone().into_inner();
+        if within(0, 0) {
+            return min_difference == 0 || min_pixels == 0;
+        }
+        within(max_difference, 0)
+            && within(0, max_pixels)
+            && (min_difference == 0 || !within(min_difference - 1, 0))
+            && (min_pixels == 0 || !within(0, min_pixels - 1))
+    }
+}
+
+/// The `content` of a `<meta name=fuzzy>` element: for example `maxDifference=3;totalPixels=120`,
+/// `maxDifference=1-3;totalPixels=100-120`, or `3;100-120`,
+/// optionally prefixed with the `href` of the one reference it applies to and a colon.
+/// A range with a single number, like `3`, is the same as `0-3`.
+fn parse_fuzzy(base: &Path, content: &str) -> Result<(Option<PathBuf>, Fuzzy), String> {
+    let (reference, values) = match content.rfind(':') {
+        Some(colon) => {
+            let href = content[..colon].trim();
+            if href.is_empty() || href.starts_with('/') {
+                return Err(format!("unsupported reference {:?}", href));
+            }
+            (Some(resolve_href(base, href)), &content[colon + 1..])
+        }
+        None => (None, content),
+    };
+    let mut max_difference = None;
+    let mut total_pixels = None;
+    let values: Vec<_> = values.split(';').collect();
+    if values.len() != 2 {
+        return Err(format!("expected two values, got {:?}", content));
+    }
+    for (index, value) in values.into_iter().enumerate() {
+        let (name, range) = match value.find('=') {
+            Some(equals) => (value[..equals].trim(), &value[equals + 1..]),
+            // Values without a name are in this order
+            None => (["maxDifference", "totalPixels"][index], value),
+        };
+        match name {
+            "maxDifference" if max_difference.is_none() => {
+                max_difference = Some(parse_range(range)?)
+            }
+            "totalPixels" if total_pixels.is_none() => total_pixels = Some(parse_range(range)?),
+            _ => return Err(format!("unexpected {:?} in {:?}", name, content)),
+        }
+    }
+    let fuzzy = Fuzzy {
+        max_difference: max_difference
+            .ok_or_else(|| format!("no maxDifference in {:?}", content))?,
+        total_pixels: total_pixels.ok_or_else(|| format!("no totalPixels in {:?}", content))?,
+    };
+    Ok((reference, fuzzy))
+}
+
+/// `3` or `1-3`
+fn parse_range<T: std::str::FromStr + Default + PartialOrd>(
+    range: &str,
+) -> Result<RangeInclusive<T>, String> {
+    let number = |number: &str| {
+        number
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid number {:?}", number))
+    };
+    let (min, max) = match range.find('-') {
+        Some(dash) => (number(&range[..dash])?, number(&range[dash + 1..])?),
+        None => (T::default(), number(range)?),
+    };
+    if min > max {
+        return Err(format!("empty range {:?}", range));
+    }
+    Ok(min..=max)
+}
+
 fn resolve_href(base: &Path, href: &str) -> PathBuf {
     assert!(!href.starts_with('/'));
     assert!(!href.is_empty());
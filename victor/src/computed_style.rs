@@ -0,0 +1,163 @@
+//! Inspecting the result of the cascade, for example in tests.
+
+use crate::dom::Document;
+use crate::primitives::RGBA;
+use crate::style::values::{
+    self, DisplayGeneratingBox, DisplayInside, DisplayLayoutInternal, DisplayOutside,
+};
+use crate::style::{matches_any, parse_selector_list, style_for_element, ComputedValues};
+use std::sync::Arc;
+
+impl Document {
+    /// The computed style of the first element in tree order that matches `selectors`.
+    ///
+    /// Returns `None` if no element matches, or if `selectors` is not a valid selector list.
+    pub fn computed_style_of(&self, selectors: &str) -> Option<ComputedStyleView> {
+        let selectors = parse_selector_list(selectors)?;
+        let element = self.nodes().find(|&node| {
+            self[node].as_element().is_some() && matches_any(&selectors, self, node)
+        })?;
+        let author_styles = self.parse_stylesheets();
+        let mut ancestors = self
+            .node_and_ancestors(element)
+            .filter(|&node| self[node].as_element().is_some())
+            .collect::<Vec<_>>();
+        ancestors.reverse();
+        let mut style: Option<Arc<ComputedValues>> = None;
+        for node in ancestors {
+            style = Some(style_for_element(
+                &author_styles,
+                self,
+                node,
+                style.as_deref(),
+            ))
+        }
+        style.map(|style| ComputedStyleView { style })
+    }
+}
+
+/// The computed values of some properties for one element.
+pub struct ComputedStyleView {
+    style: Arc<ComputedValues>,
+}
+
+/// https://drafts.csswg.org/css-display-3/#the-display-properties
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Display {
+    None,
+    Contents,
+    Block,
+    ListItem,
+    FlowRoot,
+    Inline,
+    /// `inline flow-root`
+    InlineBlock,
+    Ruby,
+    BlockRuby,
+    RubyBase,
+    RubyText,
+    Math,
+    BlockMath,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LengthOrPercentageOrAuto {
+    /// In CSS `px`
+    Length(f32),
+    /// For example `50.` for `50%`
+    Percentage(f32),
+    Auto,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicalSides<T> {
+    pub top: T,
+    pub right: T,
+    pub bottom: T,
+    pub left: T,
+}
+
+impl ComputedStyleView {
+    /// After blockification of floats and absolutely positioned boxes.
+    pub fn display(&self) -> Display {
+        match self.style.box_.display {
+            values::Display::None => Display::None,
+            values::Display::Contents => Display::Contents,
+            values::Display::GeneratingBox(DisplayGeneratingBox::OutsideInside {
+                outside,
+                inside,
+                list_item,
+            }) => match (outside, inside) {
+                // FIXME: list items other than `block flow` once `display` parses them
+                (DisplayOutside::Block, DisplayInside::Flow) if list_item => Display::ListItem,
+                (DisplayOutside::Block, DisplayInside::Flow) => Display::Block,
+                (DisplayOutside::Block, DisplayInside::FlowRoot) => Display::FlowRoot,
+                (DisplayOutside::Inline, DisplayInside::Flow) => Display::Inline,
+                (DisplayOutside::Inline, DisplayInside::FlowRoot) => Display::InlineBlock,
+                (DisplayOutside::Block, DisplayInside::Ruby) => Display::BlockRuby,
+                (DisplayOutside::Inline, DisplayInside::Ruby) => Display::Ruby,
+                (DisplayOutside::Block, DisplayInside::Math) => Display::BlockMath,
+                (DisplayOutside::Inline, DisplayInside::Math) => Display::Math,
+            },
+            values::Display::GeneratingBox(DisplayGeneratingBox::LayoutInternal(internal)) => {
+                match internal {
+                    DisplayLayoutInternal::RubyBase => Display::RubyBase,
+                    DisplayLayoutInternal::RubyText => Display::RubyText,
+                }
+            }
+        }
+    }
+
+    pub fn margin(&self) -> PhysicalSides<LengthOrPercentageOrAuto> {
+        let m = &self.style.margin;
+        PhysicalSides {
+            top: m.margin_top.into(),
+            right: m.margin_right.into(),
+            bottom: m.margin_bottom.into(),
+            left: m.margin_left.into(),
+        }
+    }
+
+    /// In CSS `px`
+    pub fn font_size(&self) -> f32 {
+        self.style.font.font_size.0.px()
+    }
+
+    /// OpenType feature tags and their values, sorted by tag.
+    ///
+    /// This combines `font-variant-ligatures`, `font-variant-numeric`,
+    /// and `font-feature-settings` with the features enabled by default.
+    pub fn font_features(&self) -> Vec<([u8; 4], u32)> {
+        self.style.font_features()
+    }
+
+    /// OpenType variation axis tags and their values, sorted by tag.
+    ///
+    /// This combines `font-weight`, `font-stretch`, `font-style` and `font-size`
+    /// with `font-variation-settings`.
+    pub fn font_variations(&self) -> Vec<([u8; 4], f32)> {
+        self.style.font_variations()
+    }
+
+    pub fn color(&self) -> RGBA {
+        self.style.color.color.into()
+    }
+
+    pub fn background_color(&self) -> RGBA {
+        self.style
+            .to_rgba(self.style.background.background_color)
+            .into()
+    }
+}
+
+impl From<values::LengthOrPercentageOrAuto> for LengthOrPercentageOrAuto {
+    fn from(value: values::LengthOrPercentageOrAuto) -> Self {
+        match value {
+            values::LengthOrPercentageOrAuto::Length(l) => LengthOrPercentageOrAuto::Length(l.px()),
+            values::LengthOrPercentageOrAuto::Percentage(p) => {
+                LengthOrPercentageOrAuto::Percentage(p.unit_value() * 100.)
+            }
+            values::LengthOrPercentageOrAuto::Auto => LengthOrPercentageOrAuto::Auto,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! `data:` URLs, for resources inlined in a document.
+//!
+//! https://fetch.spec.whatwg.org/#data-urls
+
+/// The result of parsing a `data:` URL.
+#[derive(Debug, PartialEq)]
+pub struct DataUrl {
+    /// The MIME type essence, ASCII-lowercased, such as `text/css` or `image/png`.
+    pub mime_type: String,
+    /// The MIME type parameters as written, such as `charset=utf-8`
+    pub parameters: String,
+    pub body: Vec<u8>,
+}
+
+impl DataUrl {
+    /// https://fetch.spec.whatwg.org/#data-url-processor
+    ///
+    /// Returns `None` if `url` is not a `data:` URL, or a malformed one.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim_matches(|c: char| c <= ' ');
+        let scheme_len = "data:".len();
+        if !url.get(..scheme_len)?.eq_ignore_ascii_case("data:") {
+            return None;
+        }
+        let after_scheme = &url[scheme_len..];
+        let without_fragment = match after_scheme.find('#') {
+            Some(position) => &after_scheme[..position],
+            None => after_scheme,
+        };
+        let comma = without_fragment.find(',')?;
+        let mut mime_type = without_fragment[..comma].trim_matches(is_ascii_whitespace);
+        let mut body = percent_decode(&without_fragment.as_bytes()[comma + 1..]);
+
+        if let Some(before) = strip_base64_suffix(mime_type) {
+            body = forgiving_base64_decode(&body)?;
+            mime_type = before;
+        }
+        let (essence, parameters) = match mime_type.find(';') {
+            Some(0) => ("text/plain", &mime_type[1..]),
+            Some(position) => (&mime_type[..position], &mime_type[position + 1..]),
+            None => (mime_type, ""),
+        };
+        let essence = essence.trim_matches(is_ascii_whitespace);
+        // FIXME: parse and normalize parameters, https://mimesniff.spec.whatwg.org/#parse-a-mime-type
+        let (mime_type, parameters) = if is_valid_essence(essence) {
+            (
+                essence.to_ascii_lowercase(),
+                parameters.trim_matches(is_ascii_whitespace),
+            )
+        } else {
+            ("text/plain".to_owned(), "charset=US-ASCII")
+        };
+        Some(DataUrl {
+            mime_type,
+            parameters: parameters.to_owned(),
+            body,
+        })
+    }
+}
+
+fn is_ascii_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
+/// `type/subtype`, where both are non-empty HTTP tokens
+fn is_valid_essence(essence: &str) -> bool {
+    let is_token = |s: &str| {
+        !s.is_empty()
+            && s.bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+    };
+    match essence.find('/') {
+        Some(slash) => is_token(&essence[..slash]) && is_token(&essence[slash + 1..]),
+        None => false,
+    }
+}
+
+/// If `mime_type` ends with `;base64`, with optional spaces and ASCII case-insensitive,
+/// return what is before it.
+fn strip_base64_suffix(mime_type: &str) -> Option<&str> {
+    let trimmed = mime_type.trim_end_matches(' ');
+    let suffix_start = trimmed.len().checked_sub("base64".len())?;
+    let suffix = trimmed.get(suffix_start..)?;
+    if !suffix.eq_ignore_ascii_case("base64") {
+        return None;
+    }
+    let before = trimmed[..suffix_start].trim_end_matches(' ');
+    if !before.ends_with(';') {
+        return None;
+    }
+    Some(&before[..before.len() - 1])
+}
+
+/// https://url.spec.whatwg.org/#percent-decode
+fn percent_decode(input: &[u8]) -> Vec<u8> {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' && i + 2 < input.len() {
+            if let (Some(high), Some(low)) = (hex(input[i + 1]), hex(input[i + 2])) {
+                output.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(input[i]);
+        i += 1
+    }
+    output
+}
+
+/// https://infra.spec.whatwg.org/#forgiving-base64-decode
+fn forgiving_base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut data: Vec<u8> = input
+        .iter()
+        .cloned()
+        .filter(|&b| !is_ascii_whitespace(b as char))
+        .collect();
+    if data.len() % 4 == 0 {
+        for _ in 0..2 {
+            if data.last() == Some(&b'=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut output = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    for b in data {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(output)
+}
+
+#[test]
+fn parse() {
+    let parse = |url| {
+        DataUrl::parse(url).map(|u| {
+            (
+                u.mime_type,
+                u.parameters,
+                String::from_utf8(u.body).unwrap(),
+            )
+        })
+    };
+    let expected = |mime_type: &str, parameters: &str, body: &str| {
+        Some((mime_type.to_owned(), parameters.to_owned(), body.to_owned()))
+    };
+    assert_eq!(parse("https://example.net/"), None);
+    assert_eq!(parse("data:text/html"), None);
+    assert_eq!(
+        parse("data:,a%20b%2"),
+        expected("text/plain", "charset=US-ASCII", "a b%2")
+    );
+    assert_eq!(
+        parse(" DATA:Text/CSS;charset=utf-8,p{}#frag"),
+        expected("text/css", "charset=utf-8", "p{}")
+    );
+    assert_eq!(
+        parse("data:text/css;base64,cHt9IGE=#x"),
+        expected("text/css", "", "p{} a")
+    );
+    assert_eq!(
+        parse("data:;BASE64 , cH t9"),
+        expected("text/plain", "charset=US-ASCII", "p{}")
+    );
+    assert_eq!(parse("data:;base64,cHt9I"), None);
+    assert_eq!(parse("data:;base64,p!"), None);
+}
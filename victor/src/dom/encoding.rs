@@ -0,0 +1,253 @@
+//! https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding
+//!
+//! Labels and decoders are those of the Encoding Standard, from `encoding_rs`.
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+/// How many bytes the prescan looks at, as recommended by the HTML spec
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Find the encoding of an HTML document.
+/// `Encoding::decode` also removes its byte order mark, if any.
+pub(super) fn sniff(bytes: &[u8]) -> &'static Encoding {
+    // https://encoding.spec.whatwg.org/#bom-sniff
+    if let Some((encoding, _bom_length)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LENGTH)]) {
+        return encoding;
+    }
+    // The spec allows autodetection here. Text in another encoding
+    // is very unlikely to also be valid UTF-8, unless it is all ASCII.
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    WINDOWS_1252
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+///
+/// FIXME: a `<meta>` found later by the parser should restart parsing in its encoding.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let starts_with_ignoring_case = |position: usize, prefix: &[u8]| {
+        bytes.len() >= position + prefix.len()
+            && bytes[position..position + prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    // The position of the last byte of the next `needle`
+    let find_end = |position: usize, needle: &[u8]| {
+        bytes[position..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|found| position + found + needle.len() - 1)
+    };
+    let is_tag_start = |position: usize| {
+        let name_start = if bytes.get(position + 1) == Some(&b'/') {
+            position + 2
+        } else {
+            position + 1
+        };
+        bytes[position] == b'<' && bytes.get(name_start).is_some_and(u8::is_ascii_alphabetic)
+    };
+    let mut position = 0;
+    while position < bytes.len() {
+        if bytes[position..].starts_with(b"<!--") {
+            // The comment’s `-->` can share dashes with `<!--`
+            position = find_end(position + 2, b"-->")?
+        } else if starts_with_ignoring_case(position, b"<meta")
+            && bytes
+                .get(position + 5)
+                .is_some_and(|&b| is_space(b) || b == b'/')
+        {
+            position += 5;
+            if let Some(encoding) = meta_encoding(bytes, &mut position)? {
+                return Some(encoding);
+            }
+        } else if is_tag_start(position) {
+            // A start or end tag, whose attributes are skipped
+            // so that `<` in their values is not taken as the start of another tag
+            while position < bytes.len() && !is_space(bytes[position]) && bytes[position] != b'>' {
+                position += 1
+            }
+            while attribute(bytes, &mut position)?.is_some() {}
+        } else if bytes[position..].starts_with(b"<!")
+            || bytes[position..].starts_with(b"</")
+            || bytes[position..].starts_with(b"<?")
+        {
+            position = find_end(position, b">")?
+        }
+        position += 1
+    }
+    None
+}
+
+/// The encoding declared by the attributes of a `<meta>` element, if any.
+///
+/// Returns `None` if the end of the input is reached first.
+fn meta_encoding(bytes: &[u8], position: &mut usize) -> Option<Option<&'static Encoding>> {
+    let mut names = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    // `Some(None)` for a declaration with an unsupported label
+    let mut charset = None;
+    while let Some((name, value)) = attribute(bytes, position)? {
+        if names.contains(&name) {
+            continue;
+        }
+        match &*name {
+            b"http-equiv" => got_pragma |= value == b"content-type",
+            b"content" if charset.is_none() => {
+                if let Some(label) = charset_from_content(&value) {
+                    charset = Some(Encoding::for_label(label));
+                    need_pragma = Some(true)
+                }
+            }
+            b"charset" => {
+                charset = Some(Encoding::for_label(&value));
+                need_pragma = Some(false)
+            }
+            _ => {}
+        }
+        names.push(name)
+    }
+    Some(match (need_pragma, charset) {
+        (Some(true), _) if !got_pragma => None,
+        // A document that the prescan can read is not in UTF-16.
+        (Some(_), Some(Some(encoding))) if encoding.output_encoding() == UTF_8 => Some(UTF_8),
+        (Some(_), Some(Some(encoding))) if encoding == encoding_rs::X_USER_DEFINED => {
+            Some(WINDOWS_1252)
+        }
+        (Some(_), Some(encoding)) => encoding,
+        _ => None,
+    })
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn charset_from_content(content: &[u8]) -> Option<&[u8]> {
+    let mut rest = content;
+    loop {
+        let found = rest.windows(7).position(|w| w == b"charset")?;
+        rest = &rest[found + 7..];
+        let after_spaces = rest.iter().position(|&b| !is_space(b))?;
+        if rest[after_spaces] == b'=' {
+            rest = &rest[after_spaces + 1..];
+            break;
+        }
+    }
+    let start = rest.iter().position(|&b| !is_space(b))?;
+    let rest = &rest[start..];
+    match rest[0] {
+        quote @ b'"' | quote @ b'\'' => {
+            let end = rest[1..].iter().position(|&b| b == quote)?;
+            Some(&rest[1..1 + end])
+        }
+        _ => {
+            let end = rest
+                .iter()
+                .position(|&b| is_space(b) || b == b';')
+                .unwrap_or(rest.len());
+            Some(&rest[..end])
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+///
+/// Returns the ASCII-lowercased name and value of the next attribute,
+/// `Some(None)` at the end of the tag, or `None` at the end of the input.
+fn attribute(bytes: &[u8], position: &mut usize) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut next = || {
+        let b = *bytes.get(*position)?;
+        *position += 1;
+        Some(b)
+    };
+    let mut b = next()?;
+    while is_space(b) || b == b'/' {
+        b = next()?
+    }
+    if b == b'>' {
+        *position -= 1;
+        return Some(None);
+    }
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        match b {
+            b'=' if !name.is_empty() => break,
+            b'/' | b'>' => {
+                *position -= 1;
+                return Some(Some((name, value)));
+            }
+            _ if is_space(b) => {
+                while is_space(b) {
+                    b = next()?
+                }
+                if b != b'=' {
+                    *position -= 1;
+                    return Some(Some((name, value)));
+                }
+                break;
+            }
+            _ => name.push(b.to_ascii_lowercase()),
+        }
+        b = next()?
+    }
+    // After `=`
+    b = next()?;
+    while is_space(b) {
+        b = next()?
+    }
+    match b {
+        b'"' | b'\'' => {
+            let quote = b;
+            loop {
+                b = next()?;
+                if b == quote {
+                    return Some(Some((name, value)));
+                }
+                value.push(b.to_ascii_lowercase())
+            }
+        }
+        b'>' => {
+            *position -= 1;
+            Some(Some((name, value)))
+        }
+        _ => loop {
+            value.push(b.to_ascii_lowercase());
+            b = next()?;
+            if is_space(b) || b == b'>' {
+                *position -= 1;
+                return Some(Some((name, value)));
+            }
+        },
+    }
+}
+
+#[test]
+fn sniff_encodings() {
+    assert_eq!(sniff(b"\xEF\xBB\xBF<meta charset=latin1>"), UTF_8);
+    assert_eq!(sniff(b"\xFF\xFE<\0p\0>\0"), encoding_rs::UTF_16LE);
+    assert_eq!(sniff(b"<p>caf\xC3\xA9"), UTF_8);
+    assert_eq!(sniff(b"<p>caf\xE9"), WINDOWS_1252);
+    assert_eq!(sniff(b"<META CHARSET='ISO-8859-1'>"), WINDOWS_1252);
+    assert_eq!(sniff(b"<meta charset=utf-16><p>\xE9"), UTF_8);
+    assert_eq!(sniff(b"<meta charset=x-user-defined>"), WINDOWS_1252);
+    assert_eq!(
+        sniff(b"<meta http-equiv=Content-Type content='text/html; charset=\"us-ascii\"'>\xC3\xA9"),
+        WINDOWS_1252
+    );
+    // Missing `http-equiv`, a commented-out declaration, and one in an attribute value
+    assert_eq!(sniff(b"<meta content='charset=latin1'>\xC3\xA9"), UTF_8);
+    assert_eq!(sniff(b"<!-- <meta charset=latin1> -->\xC3\xA9"), UTF_8);
+    assert_eq!(sniff(b"<a title='<meta charset=latin1>'>\xC3\xA9"), UTF_8);
+
+    let html = b"<meta charset=Shift_JIS><p>\x82\xA0";
+    let (text, _, _) = sniff(html).decode(html);
+    assert_eq!(text, "<meta charset=Shift_JIS><p>\u{3042}");
+    let html = b"\xFE\xFF\0<\0p\0>\xD8\x00";
+    let (text, _, _) = sniff(html).decode(html);
+    assert_eq!(text, "<p>\u{FFFD}");
+}
@@ -1,3 +1,4 @@
+use super::encoding;
 use super::*;
 use html5ever::interface::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
 use html5ever::tendril::{StrTendril, TendrilSink};
@@ -7,22 +8,173 @@ use std::collections::HashSet;
 
 impl Document {
     pub fn parse_html(utf8_bytes: &[u8]) -> Self {
-        let sink = Sink {
-            document: Document::new(),
-            quirks_mode: QuirksMode::NoQuirks,
-        };
-        parse_document(sink, Default::default())
-            .from_utf8()
-            .one(utf8_bytes)
+        let (document, _depth_exceeded) = parse_utf8(utf8_bytes, Limits::default());
+        document
+    }
+
+    /// Same as `parse_html`, but return an error for documents that exceed `limits`.
+    ///
+    /// `max_depth` is checked while parsing, the others when rendering:
+    /// see `Document::try_to_pdf_bytes_and_layout_report`.
+    pub fn parse_html_with_limits(
+        utf8_bytes: &[u8],
+        limits: Limits,
+    ) -> Result<Self, LimitExceeded> {
+        let (document, depth_exceeded) = parse_utf8(utf8_bytes, limits);
+        depth_exceeded.map_or(Ok(document), Err)
+    }
+
+    /// Parse HTML from bytes in an encoding that is not known in advance,
+    /// such as from a file.
+    ///
+    /// The encoding is found from a byte order mark or a `<meta charset>` declaration.
+    /// Without either, it is UTF-8 if the bytes are valid UTF-8, or else windows-1252.
+    pub fn parse_html_with_encoding_sniffing(bytes: &[u8]) -> Self {
+        let (document, _depth_exceeded) = parse_with_encoding_sniffing(bytes, Limits::default());
+        document
+    }
+
+    /// Same as `parse_html_with_encoding_sniffing`,
+    /// but return an error for documents that exceed `limits`,
+    /// like `parse_html_with_limits`.
+    pub fn parse_html_with_encoding_sniffing_and_limits(
+        bytes: &[u8],
+        limits: Limits,
+    ) -> Result<Self, LimitExceeded> {
+        let (document, depth_exceeded) = parse_with_encoding_sniffing(bytes, limits);
+        depth_exceeded.map_or(Ok(document), Err)
     }
 }
 
-struct Sink {
+fn parse_utf8(utf8_bytes: &[u8], limits: Limits) -> (Document, Option<LimitExceeded>) {
+    // The same replacement characters as the parser’s own decoding
+    let text = String::from_utf8_lossy(utf8_bytes);
+    parse(&text, limits)
+}
+
+fn parse_with_encoding_sniffing(bytes: &[u8], limits: Limits) -> (Document, Option<LimitExceeded>) {
+    let (text, _encoding, _had_errors) = encoding::sniff(bytes).decode(bytes);
+    parse(&text, limits)
+}
+
+fn parse(text: &str, limits: Limits) -> (Document, Option<LimitExceeded>) {
+    // The tokenizer skips a byte order mark
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let mut sink = Sink::new(text);
+    sink.document.limits = limits;
+    parse_document(sink, Default::default()).one(StrTendril::from(text))
+}
+
+struct Sink<'src> {
     document: Document,
     quirks_mode: QuirksMode,
+    current_line: u64,
+    /// The text being parsed, to find the column of start tags
+    source: &'src str,
+    /// The byte offset in `source` where `current_line` starts
+    line_start: usize,
+    /// The byte offset in `source` after the last start tag that was found
+    tag_search_start: usize,
+    /// The first element that was not appended because it would exceed `Limits::max_depth`
+    depth_exceeded: Option<LimitExceeded>,
 }
 
-impl Sink {
+impl<'src> Sink<'src> {
+    fn new(source: &'src str) -> Self {
+        Sink {
+            document: Document::new(),
+            quirks_mode: QuirksMode::NoQuirks,
+            current_line: 1,
+            source,
+            line_start: 0,
+            tag_search_start: 0,
+            depth_exceeded: None,
+        }
+    }
+
+    /// The line and column of the start tag of an element named `name`.
+    ///
+    /// The tree builder only knows the line where a tag ends,
+    /// so the tag is the first one with this name after the last tag found on that line,
+    /// or else the last one on an earlier line for a tag that spans several lines.
+    /// Elements that the parser inserts without a tag, like `<tbody>` in a table without one,
+    /// are on the current line with column 0.
+    fn find_start_tag(&mut self, name: &str) -> (u32, u32) {
+        let source = self.source;
+        let line_end = line_breaks(&source[self.line_start..])
+            .next()
+            .map_or(source.len(), |(start, _)| self.line_start + start);
+        let is_tag = |position: &usize| {
+            let rest = &source.as_bytes()[position + 1..];
+            let (tag_name, after) = match rest.get(..name.len()) {
+                Some(tag_name) => (tag_name, rest.get(name.len())),
+                None => return false,
+            };
+            // The name ends at whitespace, `/`, `>`, or the end of the input
+            tag_name.eq_ignore_ascii_case(name.as_bytes())
+                && match after {
+                    Some(byte) => b"\t\n\x0C\r />".contains(byte),
+                    None => true,
+                }
+        };
+        let on_this_line = self.tag_search_start.max(self.line_start);
+        let position = source[on_this_line..line_end]
+            .match_indices('<')
+            .map(|(index, _)| on_this_line + index)
+            .find(is_tag)
+            .or_else(|| {
+                let earlier = self.tag_search_start.min(self.line_start);
+                source[earlier..self.line_start]
+                    .match_indices('<')
+                    .map(|(index, _)| earlier + index)
+                    .rev()
+                    .find(is_tag)
+            });
+        let position = match position {
+            Some(position) => position,
+            None => return (self.current_line as u32, 0),
+        };
+        self.tag_search_start = position + 1;
+        // For tags that start on an earlier line
+        let mut line = self.current_line as u32;
+        let mut line_start = self.line_start;
+        if position < self.line_start {
+            line -= line_breaks(&source[position..self.line_start]).count() as u32;
+            line_start = source[..position]
+                .rfind(['\n', '\r'])
+                .map_or(0, |index| index + 1);
+        }
+        let column = source[line_start..position].encode_utf16().count() as u32 + 1;
+        (line, column)
+    }
+
+    /// Whether appending `child` to `parent` would nest it deeper than `Limits::max_depth`.
+    /// If so, record the error.
+    fn too_deep(&mut self, parent: NodeId, child: &NodeOrText<NodeId>) -> bool {
+        let (max_depth, element) = match (self.document.limits.max_depth, child) {
+            (Some(max_depth), NodeOrText::AppendNode(node)) => {
+                match self.document[*node].as_element() {
+                    Some(element) => (max_depth, element),
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        // Ancestors include the document node, which makes up for not counting `child` itself.
+        if self
+            .document
+            .node_and_ancestors(parent)
+            .nth(max_depth)
+            .is_none()
+        {
+            return false;
+        }
+        let line = element.source_line;
+        self.depth_exceeded
+            .get_or_insert(LimitExceeded::Depth { line });
+        true
+    }
+
     fn new_node(&mut self, data: NodeData) -> NodeId {
         self.document.push_node(Node::new(data))
     }
@@ -56,12 +208,13 @@ impl Sink {
     }
 }
 
-impl TreeSink for Sink {
+impl TreeSink for Sink<'_> {
     type Handle = NodeId;
-    type Output = Document;
+    /// The document, and whether it exceeded `Limits::max_depth`
+    type Output = (Document, Option<LimitExceeded>);
 
-    fn finish(self) -> Document {
-        self.document
+    fn finish(self) -> Self::Output {
+        (self.document, self.depth_exceeded)
     }
 
     fn parse_error(&mut self, _: Cow<'static, str>) {}
@@ -106,19 +259,35 @@ impl TreeSink for Sink {
             ..
         }: ElementFlags,
     ) -> NodeId {
-        let is_style = name.expanded() == expanded_name!(html "style");
-        let element = self.new_node(NodeData::Element(ElementData {
+        let is_stylesheet = name.expanded() == expanded_name!(html "style")
+            || name.expanded() == expanded_name!(html "link");
+        let mut data = ElementData::new(
             name,
-            attrs: attrs.into_iter().map(Attribute::from).collect(),
+            attrs.into_iter().map(Attribute::from).collect(),
             mathml_annotation_xml_integration_point,
-            layout_data: Default::default(),
-        }));
-        if is_style {
-            self.document.style_elements.push(element)
+        );
+        let (line, column) = self.find_start_tag(&data.name.local);
+        data.source_line = line;
+        data.source_column = column;
+        let element = self.new_node(NodeData::Element(data));
+        if is_stylesheet {
+            self.document.stylesheet_elements.push(element)
         }
         element
     }
 
+    fn set_current_line(&mut self, line_number: u64) {
+        if line_number > self.current_line {
+            let lines = (line_number - self.current_line) as usize;
+            if let Some((_, next_line_start)) =
+                line_breaks(&self.source[self.line_start..]).nth(lines - 1)
+            {
+                self.line_start += next_line_start
+            }
+        }
+        self.current_line = line_number
+    }
+
     fn create_comment(&mut self, text: StrTendril) -> NodeId {
         self.new_node(NodeData::Comment {
             _contents: text.into(),
@@ -133,6 +302,9 @@ impl TreeSink for Sink {
     }
 
     fn append(&mut self, &parent: &NodeId, child: NodeOrText<NodeId>) {
+        if self.too_deep(parent, &child) {
+            return;
+        }
         self.append_common(
             child,
             |document| document[parent].last_child,
@@ -141,6 +313,11 @@ impl TreeSink for Sink {
     }
 
     fn append_before_sibling(&mut self, &sibling: &NodeId, child: NodeOrText<NodeId>) {
+        if let Some(parent) = self.document[sibling].parent {
+            if self.too_deep(parent, &child) {
+                return;
+            }
+        }
         self.append_common(
             child,
             |document| document[sibling].previous_sibling,
@@ -192,6 +369,7 @@ impl TreeSink for Sink {
                 .map(Attribute::from)
                 .filter(|attr| !existing_names.contains(&attr.name)),
         );
+        element.intern_id_and_classes()
     }
 
     fn remove_from_parent(&mut self, &target: &NodeId) {
@@ -216,3 +394,53 @@ impl From<html5ever::Attribute> for Attribute {
         }
     }
 }
+
+/// The byte ranges of line breaks in `text`, as counted by the tokenizer:
+/// CR LF, or LF or CR on its own.
+fn line_breaks(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let bytes = text.as_bytes();
+    bytes
+        .iter()
+        .enumerate()
+        .filter_map(move |(index, &byte)| match byte {
+            b'\n' if index > 0 && bytes[index - 1] == b'\r' => None,
+            b'\r' if bytes.get(index + 1) == Some(&b'\n') => Some((index, index + 2)),
+            b'\n' | b'\r' => Some((index, index + 1)),
+            _ => None,
+        })
+}
+
+#[test]
+fn start_tag_locations() {
+    let html =
+        "\u{FEFF}<!doctype html>\r\n<P>é</P><p\n  class=a>\r<table><tr><td>x</table><section>";
+    let document = Document::parse_html(html.as_bytes());
+    let locations: Vec<_> = document
+        .nodes()
+        .filter_map(|node| {
+            let element = document[node].as_element()?;
+            Some((
+                &*element.name.local,
+                element.source_line,
+                element.source_column,
+            ))
+        })
+        .collect();
+    assert_eq!(
+        locations,
+        [
+            // Inserted by the parser
+            ("html", 2, 0),
+            ("head", 2, 0),
+            ("body", 2, 0),
+            ("p", 2, 1),
+            // A tag on two lines, after a character with two bytes in UTF-8
+            ("p", 2, 9),
+            ("table", 4, 1),
+            ("tbody", 4, 0),
+            ("tr", 4, 8),
+            ("td", 4, 12),
+            ("section", 4, 25),
+        ]
+    );
+}
@@ -1,19 +1,272 @@
 //! This is *a* Document Object Model, but is not necessarily compatible with *the* DOM.
 
+mod encoding;
 mod html;
+mod srcset;
 mod xml;
 
+use crate::data_url::DataUrl;
+use crate::primitives::{CssPx, SideOffsets, Size};
+use crate::style::{matches_any, style_attribute_errors, stylesheet_errors};
 use crate::style::{StyleSet, StyleSetBuilder};
 use html5ever::{LocalName, QualName};
 use std::borrow::Cow;
 use std::fmt;
 use std::iter::successors;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub use self::xml::XmlError;
 
 pub struct Document {
     nodes: Vec<Node>,
-    style_elements: Vec<NodeId>,
+    /// `<style>` and `<link>` elements, in tree order
+    stylesheet_elements: Vec<NodeId>,
+    /// See `set_page_size`
+    pub(crate) page_size: Size<CssPx>,
+    /// See `set_page_margins`
+    pub(crate) page_margins: SideOffsets<CssPx>,
+    /// Device pixels per CSS `px`, see `set_pixel_snapping`
+    pub(crate) pixel_snapping: Option<f32>,
+    /// Dots per inch, see `set_max_image_resolution`
+    pub(crate) max_image_resolution: Option<f32>,
+    /// In bytes, see `set_image_memory_budget`
+    pub(crate) image_memory_budget: Option<usize>,
+    /// See `set_page_overlay`
+    pub(crate) page_overlay: Option<Box<PageOverlay>>,
+    /// See `set_page_range`
+    pub(crate) page_range: Option<RangeInclusive<usize>>,
+    /// See `set_dashed_borders`
+    pub(crate) dashed_borders: DashedBorders,
+    /// See `set_tagged_pdf`
+    pub(crate) tagged_pdf: bool,
+    /// The element that matches `:target`, see `set_target`
+    pub(crate) target: Option<NodeId>,
+    /// See `set_thread_pool`
+    pub(crate) thread_pool: Option<Arc<rayon::ThreadPool>>,
+    /// See `set_cancellation_token`
+    pub(crate) cancellation_token: Option<CancellationToken>,
+    /// See `parse_html_with_limits`
+    pub(crate) limits: Limits,
+    /// False for documents from `parse_xml`.
+    /// Selectors match names of HTML elements ASCII case-insensitively only in HTML documents:
+    /// https://html.spec.whatwg.org/multipage/#selectors
+    pub(crate) is_html: bool,
+}
+
+pub(crate) type PageOverlay =
+    dyn Fn(&mut crate::pdf::Page, crate::primitives::Size<crate::primitives::CssPx>) + Send + Sync;
+
+/// How `dashed` and `dotted` borders are painted, see `Document::set_dashed_borders`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DashedBorders {
+    /// One rectangle per dash or dot.
+    /// Renderers rasterize them like any other rectangle,
+    /// so they match reference renderings to the pixel.
+    Segments,
+    /// One stroked line per side, with a PDF dash pattern.
+    /// This is more compact, and left to the resolution of the printer or viewer.
+    DashPattern,
+}
+
+/// Bounds on the size of a document, see `Document::parse_html_with_limits`.
+///
+/// Pathological documents can otherwise overflow the stack or run out of memory.
+/// `None` means no limit, which is the default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// How deeply elements can be nested when parsing,
+    /// counting the root element as depth 1.
+    pub max_depth: Option<usize>,
+    /// How many boxes box construction can generate,
+    /// counting elements and pseudo-elements that generate a box and text nodes.
+    pub max_boxes: Option<usize>,
+    /// How many pages layout can generate.
+    pub max_pages: Option<usize>,
+}
+
+/// Which of the `Limits` a document exceeded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// An element is nested too deeply.
+    /// `line` is where it starts in the HTML source, starting at 1.
+    Depth {
+        line: u32,
+    },
+    Boxes,
+    Pages,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Depth { line } => {
+                write!(f, "element nested too deeply at line {}", line)
+            }
+            LimitExceeded::Boxes => f.write_str("too many boxes"),
+            LimitExceeded::Pages => f.write_str("too many pages"),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Why `Document::try_to_pdf_bytes_and_layout_report` did not produce a PDF file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    LimitExceeded(LimitExceeded),
+    /// The token given to `Document::set_cancellation_token` was cancelled.
+    Cancelled,
+}
+
+impl From<LimitExceeded> for RenderError {
+    fn from(error: LimitExceeded) -> Self {
+        RenderError::LimitExceeded(error)
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::LimitExceeded(error) => error.fmt(f),
+            RenderError::Cancelled => f.write_str("rendering was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::LimitExceeded(error) => Some(error),
+            RenderError::Cancelled => None,
+        }
+    }
+}
+
+/// Stops rendering a document from another thread, see `Document::set_cancellation_token`.
+///
+/// Clones share the same state: cancelling one cancels all of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What layout produced, see `Document::to_pdf_bytes_and_layout_report`.
+///
+/// For example, embedders can refuse documents with too many pages,
+/// or log pagination statistics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutReport {
+    pub pages: Vec<PageReport>,
+    /// Elements with an `id` attribute that generated a box, in tree order.
+    pub anchors: Vec<AnchorReport>,
+    /// Pages whose content does not fit in their page area, in page order.
+    pub overflows: Vec<OverflowReport>,
+    /// Elements whose `drop-shadow()` filters were not painted, in tree order.
+    /// Each drop shadow paints its box a second time, including boxes with drop shadows in it,
+    /// so only the 8 outermost nested drop shadows are painted.
+    pub skipped_drop_shadows: Vec<ElementLocation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageReport {
+    pub size: crate::primitives::Size<crate::primitives::CssPx>,
+    /// The named page (https://drafts.csswg.org/css-page-3/#using-named-pages)
+    /// used for this page, if any.
+    pub name: Option<String>,
+}
+
+/// Where an element with an `id` was laid out,
+/// for example to make it the destination of links to `#id` or of a document outline entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnchorReport {
+    pub id: String,
+    /// Starting at 1, like `Document::set_page_range`
+    pub page_number: usize,
+    /// The top-left corner of the border box of the element’s first box,
+    /// from the top-left corner of the page.
+    pub position: crate::primitives::Point<crate::primitives::CssPx>,
+}
+
+/// A page whose content extends past its page area, into the page margins or off the page,
+/// to help find why a document lays out unexpectedly.
+///
+/// FIXME: once layout is fragmented into pages, content that does not fit in the block direction
+/// continues on the next page instead, and only boxes that cannot be split overflow.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverflowReport {
+    /// Starting at 1, like `Document::set_page_range`
+    pub page_number: usize,
+    /// The element of the innermost box that extends past the page area, first in tree order:
+    /// usually the one to make smaller, or to move, for the content to fit.
+    pub element: ElementLocation,
+}
+
+impl LayoutReport {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn anchor(&self, id: &str) -> Option<&AnchorReport> {
+        self.anchors.iter().find(|anchor| anchor.id == id)
+    }
+}
+
+/// A CSS rule or declaration that was skipped or approximated, see `Document::css_diagnostics`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CssDiagnostic {
+    pub kind: CssDiagnosticKind,
+    pub message: String,
+    /// The line of the HTML source, starting at 1.
+    /// In a `<style>` element this is the line of the error,
+    /// otherwise the line of the `<link>` element or of the element with a `style` attribute.
+    pub line: u32,
+    /// The column of the error in its line of CSS, starting at 1, in UTF-16 code units.
+    pub column: u32,
+    /// For a declaration, the elements it applies to:
+    /// the element with the `style` attribute, or those matching the selectors of its rule.
+    pub elements: Vec<ElementLocation>,
+}
+
+/// An element that a `CssDiagnostic` applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementLocation {
+    /// The local name of the element, like `div`
+    pub name: String,
+    /// The line of its start tag in the HTML source, starting at 1, or 0 if unknown.
+    pub line: u32,
+    /// The column of the `<` of its start tag, starting at 1, in UTF-16 code units
+    /// (in characters for XML documents), or 0 if unknown, for example for a `<tbody>` that the parser inserted without a tag.
+    pub column: u32,
+}
+
+/// Why a CSS rule or declaration was skipped or approximated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CssDiagnosticKind {
+    /// Invalid CSS, or valid CSS that Victor does not implement.
+    Unsupported,
+    /// A property like `backdrop-filter`, `animation` or `transition`
+    /// that has no equivalent in a static PDF document, whether or not its value is valid,
+    /// or a function like `blur()` in `filter`.
+    NotRenderable,
+    /// A declaration that is applied, but only approximately:
+    /// `position: sticky` is rendered like `position: relative`,
+    /// and sticky boxes are not repeated on each page.
+    /// `z-index` makes stacking contexts, but positioned boxes are painted in tree order.
+    Approximated,
 }
 
 pub struct Node {
@@ -25,7 +278,7 @@ pub struct Node {
     pub(crate) data: NodeData,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct NodeId(std::num::NonZeroUsize);
 
 impl Document {
@@ -36,27 +289,141 @@ impl Document {
         let document_node = Node::new(NodeData::Document);
         Document {
             nodes: vec![dummy, document_node],
-            style_elements: Vec::new(),
+            stylesheet_elements: Vec::new(),
+            page_size: Size::new(600., 800.),
+            page_margins: SideOffsets::zero(),
+            pixel_snapping: None,
+            max_image_resolution: None,
+            image_memory_budget: None,
+            page_overlay: None,
+            page_range: None,
+            dashed_borders: DashedBorders::Segments,
+            tagged_pdf: false,
+            target: None,
+            thread_pool: None,
+            cancellation_token: None,
+            limits: Limits::default(),
+            is_html: true,
         }
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
     pub(crate) fn document_node_id() -> NodeId {
         NodeId(std::num::NonZeroUsize::new(1).unwrap())
     }
 
     pub(crate) fn parse_stylesheets(&self) -> StyleSet {
         let mut style_set = StyleSetBuilder::new();
-        for &id in &self.style_elements {
-            let element = &self[id];
+        for (_, css) in self.stylesheets() {
+            style_set.add_stylesheet(&css)
+        }
+        style_set.finish()
+    }
+
+    /// The author stylesheets, in tree order, with the `<style>` or `<link>` element of each
+    fn stylesheets(&self) -> impl Iterator<Item = (&ElementData, Cow<'_, String>)> {
+        self.stylesheet_elements.iter().filter_map(move |&id| {
+            let element = self[id].as_element().unwrap();
+            if element.name.expanded() == expanded_name!(html "link") {
+                return Some((element, Cow::Owned(linked_stylesheet(element)?)));
+            }
             // https://html.spec.whatwg.org/multipage/semantics.html#update-a-style-block
-            if let Some(type_attr) = element.as_element().unwrap().get_attr(&local_name!("type")) {
+            if let Some(type_attr) = element.get_attr(&local_name!("type")) {
                 if !type_attr.eq_ignore_ascii_case("text/css") {
-                    continue;
+                    return None;
                 }
             }
-            style_set.add_stylesheet(&self.child_text_content(id))
+            Some((element, self.child_text_content(id)))
+        })
+    }
+
+    /// Find the CSS rules and declarations that were skipped because they are invalid,
+    /// or use something that Victor does not support, sorted by line.
+    /// Declarations that are only rendered approximately are included too.
+    ///
+    /// This parses all stylesheets and `style` attributes again,
+    /// so it is best called only when debugging a document.
+    pub fn css_diagnostics(&self) -> Vec<CssDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for (element, css) in self.stylesheets() {
+            let is_style = element.name.expanded() == expanded_name!(html "style");
+            // The text of a `<style>` element usually starts on the line of its start tag
+            let first_line = if is_style { element.source_line } else { 0 };
+            for error in stylesheet_errors(&css, first_line) {
+                let elements = match &error.selectors {
+                    Some(selectors) => self
+                        .nodes()
+                        .filter(|&node| {
+                            self[node].as_element().is_some() && matches_any(selectors, self, node)
+                        })
+                        .map(|node| self[node].as_element().unwrap().location())
+                        .collect(),
+                    None => Vec::new(),
+                };
+                diagnostics.push(CssDiagnostic {
+                    kind: error.kind,
+                    message: error.message,
+                    line: if is_style {
+                        error.location.line
+                    } else {
+                        element.source_line
+                    },
+                    column: error.location.column,
+                    elements,
+                })
+            }
         }
-        style_set.finish()
+        for node in self.nodes() {
+            if let Some(element) = self[node].as_element() {
+                if let Some(style_attr) = element.get_attr(&local_name!("style")) {
+                    for error in style_attribute_errors(style_attr) {
+                        diagnostics.push(CssDiagnostic {
+                            kind: error.kind,
+                            message: error.message,
+                            line: element.source_line,
+                            column: error.location.column,
+                            elements: vec![element.location()],
+                        })
+                    }
+                }
+            }
+        }
+        // Stable sort: errors on the same line stay in source order
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        diagnostics
+    }
+
+    /// Set the fragment identifier of the document’s URL, without `#`,
+    /// so that the element it indicates matches the `:target` pseudo-class.
+    ///
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#find-a-potential-indicated-element
+    /// The default is `None`: no element matches `:target`.
+    ///
+    /// FIXME: percent-decode the fragment.
+    pub fn set_target(&mut self, fragment: Option<&str>) {
+        let document = &*self;
+        let target = fragment.and_then(|fragment| {
+            let elements = || {
+                document
+                    .nodes()
+                    .filter_map(move |node| Some((node, document[node].as_element()?)))
+            };
+            elements()
+                .find(|(_, e)| e.id.as_deref() == Some(fragment))
+                .or_else(|| {
+                    elements().find(|(_, e)| {
+                        e.name.expanded() == expanded_name!(html "a")
+                            && e.get_attr(&local_name!("name")) == Some(fragment)
+                    })
+                })
+                .map(|(node, _)| node)
+        });
+        self.target = target
     }
 
     /// (rel_attribute, href_attribute)
@@ -75,6 +442,34 @@ impl Document {
             })
     }
 
+    /// (name_attribute, content_attribute)
+    pub fn html_meta_elements(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.nodes()
+            .filter_map(move |node| self[node].as_element())
+            .filter(|e| e.name.expanded() == expanded_name!(html "meta"))
+            .filter_map(|e| {
+                match (
+                    e.get_attr(&local_name!("name")),
+                    e.get_attr(&local_name!("content")),
+                ) {
+                    (Some(name), Some(content)) => Some((name, content)),
+                    _ => None,
+                }
+            })
+    }
+
+    /// The URL of the image of each `<img>` element, chosen from its `srcset`
+    /// or the `<source>` elements of its `<picture>` for `set_max_image_resolution`.
+    pub fn html_image_sources(&self) -> impl Iterator<Item = &str> {
+        self.nodes()
+            .filter(move |&node| {
+                self[node]
+                    .as_element()
+                    .is_some_and(|e| e.name.expanded() == expanded_name!(html "img"))
+            })
+            .filter_map(move |node| Some(self.image_source(node)?.0))
+    }
+
     pub(crate) fn root_element(&self) -> NodeId {
         let document_node = &self[Document::document_node_id()];
         assert!(matches!(document_node.data, NodeData::Document));
@@ -158,7 +553,7 @@ impl Document {
     }
 
     /// <https://dom.spec.whatwg.org/#concept-child-text-content>
-    fn child_text_content(&self, node: NodeId) -> Cow<String> {
+    pub(crate) fn child_text_content(&self, node: NodeId) -> Cow<String> {
         let mut link = self[node].first_child;
         let mut text = None;
         while let Some(child) = link {
@@ -200,6 +595,30 @@ impl Document {
     }
 }
 
+/// The stylesheet that a `<link rel=stylesheet>` element refers to.
+///
+/// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+///
+/// Only `data:` URLs are supported, since Victor does not fetch resources.
+/// FIXME: `@import` rules, and `url()` in `@font-face` rules once they are supported,
+/// could be resolved the same way.
+fn linked_stylesheet(element: &ElementData) -> Option<String> {
+    let rel = element.get_attr(&local_name!("rel"))?;
+    let has_keyword = |keyword| {
+        rel.split_ascii_whitespace()
+            .any(|k| k.eq_ignore_ascii_case(keyword))
+    };
+    if !has_keyword("stylesheet") || has_keyword("alternate") {
+        return None;
+    }
+    let url = DataUrl::parse(element.get_attr(&local_name!("href"))?)?;
+    if url.mime_type != "text/css" {
+        return None;
+    }
+    // FIXME: decode with the `charset` parameter, or `@charset`, if not UTF-8
+    Some(String::from_utf8_lossy(&url.body).into_owned())
+}
+
 impl std::ops::Index<NodeId> for Document {
     type Output = Node;
 
@@ -239,8 +658,17 @@ pub(crate) enum NodeData {
 pub(crate) struct ElementData {
     pub(crate) name: QualName,
     pub(crate) attrs: Vec<Attribute>,
+    /// The value of the `id` attribute, interned for selector matching
+    pub(crate) id: Option<Atom>,
+    /// The tokens of the `class` attribute, interned for selector matching
+    pub(crate) classes: Box<[Atom]>,
     pub(crate) mathml_annotation_xml_integration_point: bool,
-    pub(crate) layout_data: atomic_refcell::AtomicRefCell<crate::layout::LayoutDataForElement>,
+    /// The line of the start tag in the source, starting at 1, or 0 if unknown
+    pub(crate) source_line: u32,
+    /// The column of the `<` of the start tag in its line, starting at 1, or 0 if unknown.
+    /// In UTF-16 code units for HTML, in characters for XML.
+    pub(crate) source_column: u32,
+    pub(crate) layout_data: crate::layout::LayoutDataForElement,
 }
 
 pub(crate) struct Attribute {
@@ -250,7 +678,48 @@ pub(crate) struct Attribute {
     pub value: String,
 }
 
+/// An interned string, cheap to compare for equality
+pub(crate) type Atom = string_cache::DefaultAtom;
+
 impl ElementData {
+    pub(crate) fn new(
+        name: QualName,
+        attrs: Vec<Attribute>,
+        mathml_annotation_xml_integration_point: bool,
+    ) -> Self {
+        let mut element = ElementData {
+            name,
+            attrs,
+            id: None,
+            classes: Box::new([]),
+            mathml_annotation_xml_integration_point,
+            source_line: 0,
+            source_column: 0,
+            layout_data: Default::default(),
+        };
+        element.intern_id_and_classes();
+        element
+    }
+
+    pub(crate) fn location(&self) -> ElementLocation {
+        ElementLocation {
+            name: self.name.local.to_string(),
+            line: self.source_line,
+            column: self.source_column,
+        }
+    }
+
+    /// Update `id` and `classes` after a change to `attrs`.
+    pub(crate) fn intern_id_and_classes(&mut self) {
+        self.id = self.get_attr(&local_name!("id")).map(Atom::from);
+        // https://dom.spec.whatwg.org/#concept-ordered-set-parser
+        self.classes = self
+            .get_attr(&local_name!("class"))
+            .map_or_else(Box::default, |classes| {
+                classes.split_ascii_whitespace().map(Atom::from).collect()
+            });
+    }
+
     pub(crate) fn get_attr(&self, name: &LocalName) -> Option<&str> {
         self.attrs
             .iter()
@@ -263,17 +732,12 @@ impl ElementData {
 #[cfg(target_pointer_width = "64")]
 fn size_of() {
     use std::mem::size_of;
-    assert_eq!(size_of::<Node>(), 120);
-    assert_eq!(size_of::<NodeData>(), 80);
-    assert_eq!(size_of::<ElementData>(), 64);
+    assert_eq!(size_of::<Node>(), 240);
+    assert_eq!(size_of::<NodeData>(), 200);
+    assert_eq!(size_of::<ElementData>(), 200);
 }
 
 impl Node {
-    pub(crate) fn in_html_document(&self) -> bool {
-        // FIXME: track something when we add XML parsing
-        true
-    }
-
     pub(crate) fn as_element(&self) -> Option<&ElementData> {
         match self.data {
             NodeData::Element(ref data) => Some(data),
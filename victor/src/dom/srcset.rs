@@ -0,0 +1,244 @@
+//! Choosing among the images of `<img srcset>` and `<picture>`.
+//!
+//! https://html.spec.whatwg.org/multipage/images.html#selecting-an-image-source
+
+use super::{Document, NodeId};
+use crate::primitives::PX_PER_INCH;
+
+struct ImageCandidate<'a> {
+    url: &'a str,
+    descriptor: Descriptor,
+}
+
+enum Descriptor {
+    /// `2x`: device pixels per CSS `px` that the image is meant for
+    Density(f32),
+    /// `800w`: the width of the image in image pixels
+    Width(f32),
+}
+
+impl Document {
+    /// The URL of the image selected for an `<img>` element,
+    /// from the `srcset` of a `<source>` of its `<picture>` parent, its own `srcset`, or `src`,
+    /// and its density in image pixels per CSS `px`.
+    ///
+    /// Higher densities are preferred until one covers the resolution set with
+    /// `set_max_image_resolution`, so that images are not upscaled when printed.
+    pub(crate) fn image_source(&self, img: NodeId) -> Option<(&str, f32)> {
+        let element = self[img].as_element()?;
+        // https://html.spec.whatwg.org/multipage/images.html#update-the-source-set
+        let parent = self[img].parent.and_then(|parent| {
+            let data = self[parent].as_element()?;
+            Some(parent).filter(|_| data.name.expanded() == expanded_name!(html "picture"))
+        });
+        if let Some(picture) = parent {
+            let mut child = self[picture].first_child;
+            while let Some(node) = child {
+                if node == img {
+                    break;
+                }
+                child = self[node].next_sibling;
+                let source = match self[node].as_element() {
+                    Some(data) if data.name.expanded() == expanded_name!(html "source") => data,
+                    _ => continue,
+                };
+                let srcset = match source.get_attr(&local_name!("srcset")) {
+                    Some(srcset) => srcset,
+                    None => continue,
+                };
+                if !media_matches(source.get_attr(&local_name!("media"))) {
+                    continue;
+                }
+                if !is_supported_type(source.get_attr(&local_name!("type"))) {
+                    continue;
+                }
+                let candidates = parse_srcset(srcset);
+                if candidates.is_empty() {
+                    continue;
+                }
+                return self.select(candidates, source.get_attr(&local_name!("sizes")));
+            }
+        }
+
+        let mut candidates = element
+            .get_attr(&local_name!("srcset"))
+            .map(parse_srcset)
+            .unwrap_or_default();
+        if let Some(src) = element
+            .get_attr(&local_name!("src"))
+            .filter(|src| !src.is_empty())
+        {
+            let has_1x_or_width = candidates
+                .iter()
+                .any(|candidate| match candidate.descriptor {
+                    Descriptor::Density(density) => density == 1.,
+                    Descriptor::Width(_) => true,
+                });
+            if !has_1x_or_width {
+                candidates.push(ImageCandidate {
+                    url: src,
+                    descriptor: Descriptor::Density(1.),
+                })
+            }
+        }
+        self.select(candidates, element.get_attr(&local_name!("sizes")))
+    }
+
+    /// The candidate with the lowest density at least the device pixel ratio,
+    /// or the highest density if none is.
+    fn select<'a>(
+        &self,
+        candidates: Vec<ImageCandidate<'a>>,
+        sizes: Option<&str>,
+    ) -> Option<(&'a str, f32)> {
+        let device_pixel_ratio = match (self.max_image_resolution, self.pixel_snapping) {
+            (Some(dots_per_inch), _) => dots_per_inch / PX_PER_INCH,
+            (None, Some(device_pixels_per_px)) => device_pixels_per_px,
+            (None, None) => 1.,
+        };
+        let source_size = self.source_size(sizes);
+        let mut best: Option<(f32, &str)> = None;
+        for candidate in candidates {
+            let density = match candidate.descriptor {
+                Descriptor::Density(density) => density,
+                Descriptor::Width(width) => width / source_size,
+            };
+            let better = match best {
+                None => true,
+                Some((best_density, _)) if best_density < device_pixel_ratio => {
+                    density > best_density
+                }
+                Some((best_density, _)) => density >= device_pixel_ratio && density < best_density,
+            };
+            if better {
+                best = Some((density, candidate.url))
+            }
+        }
+        best.map(|(density, url)| (url, density))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/images.html#parse-a-sizes-attribute
+    ///
+    /// In CSS `px`. The viewport is the page area.
+    ///
+    /// FIXME: entries with a media condition are skipped, since media queries are not supported,
+    /// and so are lengths other than `px`, `em`, `rem` and `vw`.
+    fn source_size(&self, sizes: Option<&str>) -> f32 {
+        let viewport_width =
+            self.page_size.width - self.page_margins.left - self.page_margins.right;
+        let size = sizes
+            .into_iter()
+            .flat_map(|sizes| sizes.split(','))
+            .find_map(|entry| {
+                let entry = entry.trim_matches(|c: char| c.is_ascii_whitespace());
+                let unit_start = entry.find(|c: char| c.is_ascii_alphabetic())?;
+                let value: f32 = entry[..unit_start].parse().ok()?;
+                let unit = &entry[unit_start..];
+                let px = if unit.eq_ignore_ascii_case("px") {
+                    value
+                } else if unit.eq_ignore_ascii_case("em") || unit.eq_ignore_ascii_case("rem") {
+                    // The initial `font-size`
+                    value * 16.
+                } else if unit.eq_ignore_ascii_case("vw") {
+                    value * viewport_width / 100.
+                } else {
+                    return None;
+                };
+                Some(px).filter(|&px| px > 0.)
+            });
+        size.unwrap_or(viewport_width)
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/images.html#parse-a-srcset-attribute
+///
+/// Candidates with invalid descriptors are dropped.
+fn parse_srcset(srcset: &str) -> Vec<ImageCandidate<'_>> {
+    let is_whitespace = |c: char| c.is_ascii_whitespace();
+    let mut candidates = Vec::new();
+    let mut remaining = srcset;
+    loop {
+        remaining = remaining.trim_start_matches(|c: char| is_whitespace(c) || c == ',');
+        if remaining.is_empty() {
+            return candidates;
+        }
+        let url_end = remaining.find(is_whitespace).unwrap_or(remaining.len());
+        let url = &remaining[..url_end];
+        remaining = &remaining[url_end..];
+        let (url, descriptors) = if url.ends_with(',') {
+            (url.trim_end_matches(','), "")
+        } else {
+            // FIXME: commas within parentheses in descriptors
+            let descriptors_end = remaining.find(',').unwrap_or(remaining.len());
+            let descriptors = &remaining[..descriptors_end];
+            remaining = &remaining[descriptors_end..];
+            (url, descriptors)
+        };
+        if let Some(descriptor) = parse_descriptors(descriptors) {
+            candidates.push(ImageCandidate { url, descriptor })
+        }
+    }
+}
+
+/// `None` for an error. No descriptor at all means `1x`.
+fn parse_descriptors(descriptors: &str) -> Option<Descriptor> {
+    let mut result = None;
+    let mut height = false;
+    for descriptor in descriptors.split_ascii_whitespace() {
+        let kind = descriptor.chars().last()?;
+        let value = &descriptor[..descriptor.len() - kind.len_utf8()];
+        match kind {
+            'w' if result.is_none() && !value.starts_with('+') => {
+                let width: u32 = value.parse().ok()?;
+                result = Some(Descriptor::Width(width as f32).filter_positive()?)
+            }
+            'x' if result.is_none() => {
+                let density: f32 = value.parse().ok()?;
+                result = Some(Descriptor::Density(density).filter_positive()?)
+            }
+            // The height is only a hint, and only valid together with a width.
+            'h' if !height && !value.starts_with('+') => {
+                let _: u32 = value.parse().ok().filter(|&h| h > 0)?;
+                height = true
+            }
+            _ => return None,
+        }
+    }
+    match (result, height) {
+        (Some(Descriptor::Density(_)), true) | (None, true) => None,
+        (Some(descriptor), _) => Some(descriptor),
+        (None, false) => Some(Descriptor::Density(1.)),
+    }
+}
+
+impl Descriptor {
+    fn filter_positive(self) -> Option<Self> {
+        let value = match self {
+            Descriptor::Density(value) | Descriptor::Width(value) => value,
+        };
+        Some(self).filter(|_| value > 0. && value.is_finite())
+    }
+}
+
+/// FIXME: evaluate media queries. Until then only those that match any paged output are known
+/// to match, and other sources are skipped.
+fn media_matches(media: Option<&str>) -> bool {
+    match media.map(|media| media.trim_matches(|c: char| c.is_ascii_whitespace())) {
+        None | Some("") => true,
+        Some(media) => media.eq_ignore_ascii_case("all") || media.eq_ignore_ascii_case("print"),
+    }
+}
+
+/// Image formats that can be embedded in PDF, either as-is or after decoding to RGB.
+fn is_supported_type(type_: Option<&str>) -> bool {
+    match type_ {
+        None => true,
+        Some(type_) => {
+            let essence = type_.split(';').next().unwrap_or("");
+            let essence = essence.trim_matches(|c: char| c.is_ascii_whitespace());
+            ["image/jpeg", "image/png"]
+                .iter()
+                .any(|supported| essence.eq_ignore_ascii_case(supported))
+        }
+    }
+}
@@ -1,19 +1,43 @@
 use super::*;
+use xml_rs::common::Position;
 use xml_rs::reader::XmlEvent;
 
 impl Document {
     pub fn parse_xml(utf8_bytes: &[u8]) -> Result<Self, XmlError> {
+        Self::parse_xml_with_limits(utf8_bytes, Limits::default())
+    }
+
+    /// Same as `parse_xml`, but return an error for documents that exceed `limits`,
+    /// like `Document::parse_html_with_limits`.
+    /// `XmlError::limit_exceeded` tells this error apart from a syntax error.
+    pub fn parse_xml_with_limits(utf8_bytes: &[u8], limits: Limits) -> Result<Self, XmlError> {
         let mut document = Document::new();
+        document.is_html = false;
+        document.limits = limits;
         let mut current = Document::document_node_id();
         let mut ancestors = Vec::new();
-        for event in xml_rs::EventReader::new(utf8_bytes) {
-            match event.map_err(XmlError)? {
+        let mut reader = xml_rs::EventReader::new(utf8_bytes);
+        loop {
+            let event = reader
+                .next()
+                .map_err(|error| XmlError(XmlErrorKind::Syntax(error)))?;
+            match event {
                 XmlEvent::StartElement {
                     name, attributes, ..
                 } => {
-                    let id = document.push_node(Node::new(NodeData::Element(ElementData {
-                        name: convert_name(name),
-                        attrs: attributes
+                    // The new element is nested `ancestors.len() + 1` deep
+                    if limits
+                        .max_depth
+                        .is_some_and(|max_depth| ancestors.len() >= max_depth)
+                    {
+                        let line = reader.position().row as u32 + 1;
+                        let error = LimitExceeded::Depth { line };
+                        return Err(XmlError(XmlErrorKind::LimitExceeded(error)));
+                    }
+                    let position = reader.position();
+                    let mut element = ElementData::new(
+                        convert_name(name),
+                        attributes
                             .into_iter()
                             .map(
                                 |xml_rs::attribute::OwnedAttribute { name, value }| Attribute {
@@ -22,9 +46,11 @@ impl Document {
                                 },
                             )
                             .collect(),
-                        mathml_annotation_xml_integration_point: false,
-                        layout_data: Default::default(),
-                    })));
+                        /* mathml_annotation_xml_integration_point = */ false,
+                    );
+                    element.source_line = position.row as u32 + 1;
+                    element.source_column = position.column as u32 + 1;
+                    let id = document.push_node(Node::new(NodeData::Element(element)));
                     document.append(current, id);
                     ancestors.push(current);
                     current = id;
@@ -51,10 +77,10 @@ impl Document {
                     }));
                     document.append(current, id);
                 }
-                XmlEvent::StartDocument { .. } | XmlEvent::EndDocument | XmlEvent::Comment(_) => {}
+                XmlEvent::StartDocument { .. } | XmlEvent::Comment(_) => {}
+                XmlEvent::EndDocument => return Ok(document),
             }
         }
-        Ok(document)
     }
 }
 
@@ -67,20 +93,39 @@ fn convert_name(name: xml_rs::name::OwnedName) -> QualName {
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
-pub struct XmlError(xml_rs::reader::Error);
+pub struct XmlError(XmlErrorKind);
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum XmlErrorKind {
+    Syntax(xml_rs::reader::Error),
+    LimitExceeded(LimitExceeded),
+}
+
+impl XmlError {
+    /// Which of the limits given to `Document::parse_xml_with_limits` the document exceeded,
+    /// if that is why it was not parsed.
+    pub fn limit_exceeded(&self) -> Option<&LimitExceeded> {
+        match &self.0 {
+            XmlErrorKind::Syntax(_) => None,
+            XmlErrorKind::LimitExceeded(error) => Some(error),
+        }
+    }
+}
 
 impl std::fmt::Display for XmlError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        self.0.fmt(f)
+        match &self.0 {
+            XmlErrorKind::Syntax(error) => error.fmt(f),
+            XmlErrorKind::LimitExceeded(error) => error.fmt(f),
+        }
     }
 }
 
 impl std::error::Error for XmlError {
-    fn description(&self) -> &str {
-        self.0.description()
-    }
-
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.0.source()
+        match &self.0 {
+            XmlErrorKind::Syntax(error) => error.source(),
+            XmlErrorKind::LimitExceeded(error) => error.source(),
+        }
     }
 }
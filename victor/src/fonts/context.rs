@@ -0,0 +1,269 @@
+#[cfg(feature = "html")]
+use crate::fonts::gsub::{ParsedLookups, Substitutions};
+#[cfg(feature = "html")]
+use crate::fonts::variations::{self, VariationAxes};
+use crate::fonts::{Em, Font, FontError, GlyphId, BITSTREAM_VERA_SANS};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Fonts and glyph lookups that can be reused across the layout of many documents.
+///
+/// Mapping a character to a glyph and finding that glyph’s advance width
+/// decodes parts of the font file every time.
+/// A `FontContext` remembers the results, and can be shared between threads.
+///
+/// It also maps generic font families to concrete fonts.
+/// Server environments often have few fonts installed,
+/// so embedders configure this with `set_generic_family` and `set_fallback_fonts`
+/// on their own context, and pass it to `Document::to_pdf_bytes_with_font_context`.
+pub struct FontContext {
+    generic_families: HashMap<GenericFontFamily, Vec<Arc<Font>>>,
+    fallback_fonts: Vec<Arc<Font>>,
+    /// Keyed by the address of the `Font`, which is kept alive in the value.
+    glyphs: RwLock<HashMap<usize, FontGlyphs>>,
+    /// Keyed by the address of the `Font`, like `glyphs`.
+    #[cfg(feature = "html")]
+    substitutions: RwLock<HashMap<usize, FontSubstitutions>>,
+    /// Keyed by the address of the `Font`, like `glyphs`.
+    #[cfg(feature = "html")]
+    instances: RwLock<HashMap<usize, FontInstances>>,
+}
+
+/// https://drafts.csswg.org/css-fonts/#generic-font-families
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GenericFontFamily {
+    Serif,
+    SansSerif,
+    Monospace,
+    Cursive,
+    Fantasy,
+}
+
+struct FontGlyphs {
+    _font: Arc<Font>,
+    by_char: HashMap<char, (GlyphId, euclid::Length<f32, Em>)>,
+}
+
+#[cfg(feature = "html")]
+struct FontSubstitutions {
+    _font: Arc<Font>,
+    by_features: HashMap<Vec<[u8; 4]>, Arc<Substitutions>>,
+    /// Shared by the substitutions of feature lists that have lookups in common,
+    /// such as the default ligatures with or without `smcp`.
+    lookups: ParsedLookups,
+}
+
+#[cfg(feature = "html")]
+struct FontInstances {
+    _font: Arc<Font>,
+    /// None for a font that is not variable, or whose `fvar` or `avar` table is malformed.
+    axes: VariationAxes,
+    /// Keyed by the bits of the value of each axis
+    by_values: HashMap<Vec<u32>, Arc<Font>>,
+}
+
+lazy_static::lazy_static! {
+    static ref SHARED: FontContext = FontContext::new();
+}
+
+impl FontContext {
+    pub fn new() -> Self {
+        FontContext {
+            generic_families: HashMap::new(),
+            fallback_fonts: vec![BITSTREAM_VERA_SANS.clone()],
+            glyphs: RwLock::new(HashMap::new()),
+            #[cfg(feature = "html")]
+            substitutions: RwLock::new(HashMap::new()),
+            #[cfg(feature = "html")]
+            instances: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The context used by `Document::to_pdf_bytes`, shared by the whole process.
+    ///
+    /// It uses the default configuration: Bitstream Vera Sans for everything.
+    pub fn shared() -> &'static Self {
+        &SHARED
+    }
+
+    /// Set the fonts that a generic family resolves to, in order of preference.
+    ///
+    /// An empty vector resets the family to the fallback fonts.
+    pub fn set_generic_family(&mut self, family: GenericFontFamily, fonts: Vec<Arc<Font>>) {
+        if fonts.is_empty() {
+            self.generic_families.remove(&family);
+        } else {
+            self.generic_families.insert(family, fonts);
+        }
+    }
+
+    /// Set the fonts used for generic families that were not configured, in order of preference.
+    ///
+    /// An empty vector resets to the default: Bitstream Vera Sans.
+    pub fn set_fallback_fonts(&mut self, fonts: Vec<Arc<Font>>) {
+        self.fallback_fonts = if fonts.is_empty() {
+            vec![BITSTREAM_VERA_SANS.clone()]
+        } else {
+            fonts
+        }
+    }
+
+    /// The fonts that a generic family resolves to, in order of preference.
+    /// This is never empty.
+    ///
+    /// Inline layout falls back to the next fonts for characters missing in the first.
+    pub fn generic_family(&self, family: GenericFontFamily) -> &[Arc<Font>] {
+        match self.generic_families.get(&family) {
+            Some(fonts) => fonts,
+            None => &self.fallback_fonts,
+        }
+    }
+
+    /// The fonts that a generic family resolves to, like `generic_family`,
+    /// with variable fonts instanced at `variations`.
+    /// These are the values of `ComputedValues::font_variations`.
+    #[cfg(feature = "html")]
+    pub(crate) fn generic_family_instances(
+        &self,
+        family: GenericFontFamily,
+        variations: &[([u8; 4], f32)],
+    ) -> Vec<Arc<Font>> {
+        self.generic_family(family)
+            .iter()
+            .map(|font| self.instance(font, variations))
+            .collect()
+    }
+
+    /// The instance of a variable font for some values of its variation axes, cached.
+    ///
+    /// Other fonts are used as they are, as are variable fonts at their default values.
+    /// So is a font with malformed variation tables or outlines,
+    /// so that its text is still laid out with the default instance.
+    #[cfg(feature = "html")]
+    pub(crate) fn instance(&self, font: &Arc<Font>, variations: &[([u8; 4], f32)]) -> Arc<Font> {
+        let key = &**font as *const Font as usize;
+        let bits = |values: &[f32]| {
+            values
+                .iter()
+                .map(|value| value.to_bits())
+                .collect::<Vec<_>>()
+        };
+        if let Some(instances) = self.instances.read().get(&key) {
+            let values = match instances.axes.values(variations) {
+                Some(values) => values,
+                None => return font.clone(),
+            };
+            if let Some(instance) = instances.by_values.get(&bits(&values)) {
+                return instance.clone();
+            }
+        }
+        let mut all_instances = self.instances.write();
+        let instances = all_instances.entry(key).or_insert_with(|| FontInstances {
+            _font: font.clone(),
+            axes: VariationAxes::parse(font).unwrap_or_default(),
+            by_values: HashMap::new(),
+        });
+        let axes = &instances.axes;
+        let values = match axes.values(variations) {
+            Some(values) => values,
+            None => return font.clone(),
+        };
+        // Another thread may have written this instance since the read lock was released
+        instances
+            .by_values
+            .entry(bits(&values))
+            .or_insert_with(|| match variations::instance(font, axes, &values) {
+                Ok(instance) => Arc::new(instance),
+                Err(_) => font.clone(),
+            })
+            .clone()
+    }
+
+    /// The first font of a generic family, whose metrics are used without any text:
+    /// https://drafts.csswg.org/css-fonts/#first-available-font
+    #[cfg(feature = "html")]
+    pub(crate) fn first_available_font(&self, family: GenericFontFamily) -> &Arc<Font> {
+        &self.generic_family(family)[0]
+    }
+
+    /// Same as `font.glyph_id(ch)` followed by `font.glyph_width(id)`, but cached.
+    pub(crate) fn glyph(
+        &self,
+        font: &Arc<Font>,
+        ch: char,
+    ) -> Result<(GlyphId, euclid::Length<f32, Em>), FontError> {
+        let key = &**font as *const Font as usize;
+        if let Some(glyph) = self
+            .glyphs
+            .read()
+            .get(&key)
+            .and_then(|glyphs| glyphs.by_char.get(&ch))
+        {
+            return Ok(*glyph);
+        }
+        let id = font.glyph_id(ch)?;
+        let glyph = (id, font.glyph_width(id)?);
+        self.glyphs
+            .write()
+            .entry(key)
+            .or_insert_with(|| FontGlyphs {
+                _font: font.clone(),
+                by_char: HashMap::new(),
+            })
+            .by_char
+            .insert(ch, glyph);
+        Ok(glyph)
+    }
+
+    /// The substitutions of `features` in `font`, cached.
+    /// `features` are the tags of enabled OpenType features, sorted.
+    /// Lookups of the font’s GSUB table are only read once,
+    /// even when they are used by several lists of features.
+    ///
+    /// A font with a malformed GSUB table gets no substitutions,
+    /// so that its text is still laid out with the glyphs of its character map.
+    #[cfg(feature = "html")]
+    pub(crate) fn substitutions(
+        &self,
+        font: &Arc<Font>,
+        features: &[[u8; 4]],
+    ) -> Arc<Substitutions> {
+        let key = &**font as *const Font as usize;
+        if let Some(substitutions) = self
+            .substitutions
+            .read()
+            .get(&key)
+            .and_then(|substitutions| substitutions.by_features.get(features))
+        {
+            return substitutions.clone();
+        }
+        let mut all_substitutions = self.substitutions.write();
+        let font_substitutions =
+            all_substitutions
+                .entry(key)
+                .or_insert_with(|| FontSubstitutions {
+                    _font: font.clone(),
+                    by_features: HashMap::new(),
+                    lookups: ParsedLookups::default(),
+                });
+        // Another thread may have parsed them since the read lock was released
+        if let Some(substitutions) = font_substitutions.by_features.get(features) {
+            return substitutions.clone();
+        }
+        let substitutions = Arc::new(
+            Substitutions::parse(font, features, &mut font_substitutions.lookups)
+                .unwrap_or_default(),
+        );
+        font_substitutions
+            .by_features
+            .insert(features.to_vec(), substitutions.clone());
+        substitutions
+    }
+}
+
+impl Default for FontContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,179 @@
+//! Positions of text decoration lines, and the ink of glyphs that underlines skip.
+//!
+//! https://drafts.csswg.org/css-text-decor-3/#line-decoration
+
+use crate::fonts::tables::*;
+use crate::fonts::types::FWord;
+use crate::fonts::variations::glyph_contours;
+use crate::fonts::{read_table_directory, Em, Font, FontError, GlyphId};
+
+type EmLength = euclid::Length<f32, Em>;
+
+/// Lines from the baseline, in ems with positive values upwards like in the font.
+pub(crate) struct DecorationMetrics {
+    /// The top edge of underlines
+    pub underline_position: EmLength,
+    pub underline_thickness: EmLength,
+    /// The top edge of lines through text
+    pub strikeout_position: EmLength,
+    pub strikeout_thickness: EmLength,
+}
+
+/// Segments that approximate each quadratic curve when finding the ink of a glyph
+const CURVE_SEGMENTS: u8 = 8;
+
+impl Font {
+    /// From the `post` and `OS/2` tables, or approximations for a font that does not have them.
+    pub(crate) fn decoration_metrics(&self) -> DecorationMetrics {
+        let bytes = self.bytes();
+        let post = || {
+            let post = read_table_directory(bytes)?.find_table::<PostScriptHeader>(bytes)?;
+            Ok((
+                post.underline_position().read_from(bytes)?,
+                post.underline_thickness().read_from(bytes)?,
+            ))
+        };
+        let os2 = || {
+            let os2 = read_table_directory(bytes)?.find_table::<Os2Header>(bytes)?;
+            Ok((
+                os2.strikeout_position().read_from(bytes)?,
+                os2.strikeout_size().read_from(bytes)?,
+            ))
+        };
+        let to_ems = |line: Result<(FWord, FWord), FontError>| {
+            // A line without thickness would not be visible
+            line.ok()
+                .filter(|(_, thickness)| thickness.get() > 0)
+                .map(|(position, thickness)| (self.to_ems(position), self.to_ems(thickness)))
+        };
+        let (underline_position, underline_thickness) =
+            to_ems(post()).unwrap_or((euclid::Length::new(-0.1), euclid::Length::new(0.05)));
+        let (strikeout_position, strikeout_thickness) =
+            to_ems(os2()).unwrap_or((self.ascender() * 0.3, underline_thickness));
+        DecorationMetrics {
+            underline_position,
+            underline_thickness,
+            strikeout_position,
+            strikeout_thickness,
+        }
+    }
+
+    /// The horizontal extent of the outline of `glyph` between two heights from the baseline,
+    /// from the start of its advance. `None` if it has no ink at these heights.
+    ///
+    /// Curves are approximated with line segments, so this can be off by a fraction
+    /// of a font unit where a curve is tangent to one of the heights.
+    pub(crate) fn glyph_ink_between(
+        &self,
+        glyph: GlyphId,
+        bottom: EmLength,
+        top: EmLength,
+    ) -> Result<Option<(EmLength, EmLength)>, FontError> {
+        let units_per_em = self.font_design_units_per_em.get();
+        let (bottom, top) = (bottom.get() * units_per_em, top.get() * units_per_em);
+        let mut extent: Option<(f32, f32)> = None;
+        let mut add_line = |from: (f32, f32), to: (f32, f32)| {
+            if let Some((min, max)) = line_between(from, to, bottom, top) {
+                extent = Some(match extent {
+                    Some((start, end)) => (start.min(min), end.max(max)),
+                    None => (min, max),
+                })
+            }
+        };
+        for contour in glyph_contours(self, glyph)? {
+            each_line(&contour, &mut add_line)
+        }
+        Ok(extent.map(|(start, end)| {
+            (
+                euclid::Length::new(start / units_per_em),
+                euclid::Length::new(end / units_per_em),
+            )
+        }))
+    }
+}
+
+/// Call `f` with the line segments of a closed TrueType contour, approximating curves.
+///
+/// Between two consecutive off-curve points, there is an implied on-curve point in the middle.
+fn each_line(contour: &[(f32, f32, bool)], f: &mut impl FnMut((f32, f32), (f32, f32))) {
+    let midpoint = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2., (a.1 + b.1) / 2.);
+    let point = |index: usize| contour[index % contour.len()];
+    // Start at an on-curve point, or between the first two points if they are all off the curve
+    let (first, start) = match contour.iter().position(|&(_, _, on_curve)| on_curve) {
+        Some(index) => (index, (contour[index].0, contour[index].1)),
+        None if contour.is_empty() => return,
+        None => {
+            let (x0, y0, _) = point(0);
+            let (x1, y1, _) = point(1);
+            (0, midpoint((x0, y0), (x1, y1)))
+        }
+    };
+    let mut current = start;
+    let mut control = None;
+    for index in first + 1..=first + contour.len() {
+        let (x, y, on_curve) = point(index);
+        let next = (x, y);
+        match (control, on_curve) {
+            (None, true) => {
+                f(current, next);
+                current = next
+            }
+            (Some(control_point), true) => {
+                each_curve_line(current, control_point, next, f);
+                control = None;
+                current = next
+            }
+            (None, false) => control = Some(next),
+            (Some(control_point), false) => {
+                let middle = midpoint(control_point, next);
+                each_curve_line(current, control_point, middle, f);
+                control = Some(next);
+                current = middle
+            }
+        }
+    }
+    if let Some(control_point) = control {
+        each_curve_line(current, control_point, start, f)
+    }
+}
+
+/// Call `f` with line segments along a quadratic curve
+fn each_curve_line(
+    from: (f32, f32),
+    control: (f32, f32),
+    to: (f32, f32),
+    f: &mut impl FnMut((f32, f32), (f32, f32)),
+) {
+    let mut previous = from;
+    for step in 1..=CURVE_SEGMENTS {
+        let t = f32::from(step) / f32::from(CURVE_SEGMENTS);
+        let along =
+            |a: f32, b: f32, c: f32| (1. - t) * (1. - t) * a + 2. * (1. - t) * t * b + t * t * c;
+        let next = (
+            along(from.0, control.0, to.0),
+            along(from.1, control.1, to.1),
+        );
+        f(previous, next);
+        previous = next
+    }
+}
+
+/// The horizontal extent of the part of a line segment between two heights
+fn line_between(from: (f32, f32), to: (f32, f32), bottom: f32, top: f32) -> Option<(f32, f32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (t_start, t_end) = if dy == 0. {
+        if from.1 < bottom || from.1 > top {
+            return None;
+        }
+        (0., 1.)
+    } else {
+        let t_bottom = (bottom - from.1) / dy;
+        let t_top = (top - from.1) / dy;
+        (t_bottom.min(t_top).max(0.), t_bottom.max(t_top).min(1.))
+    };
+    if t_start > t_end {
+        return None;
+    }
+    let (x_start, x_end) = (from.0 + dx * t_start, from.0 + dx * t_end);
+    Some((x_start.min(x_end), x_start.max(x_end)))
+}
@@ -0,0 +1,372 @@
+//! Glyph substitutions for OpenType features, such as ligatures or old-style numerals.
+//!
+//! https://docs.microsoft.com/en-us/typography/opentype/spec/gsub
+
+use crate::fonts::parsing::{Position, Slice};
+use crate::fonts::tables::*;
+use crate::fonts::{read_table_directory, Font, FontError, GlyphId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The substitutions of some features in one font, in the order they apply.
+#[derive(Default)]
+pub(crate) struct Substitutions {
+    lookups: Vec<Arc<Lookup>>,
+}
+
+/// Lookups already read from one font, keyed by their index in its lookup list,
+/// so that they are shared by the `Substitutions` of different features.
+#[derive(Default)]
+pub(crate) struct ParsedLookups {
+    by_index: HashMap<u16, Vec<Arc<Lookup>>>,
+}
+
+enum Lookup {
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#lookuptype-1-single-substitution-subtable
+    Single(HashMap<GlyphId, GlyphId>),
+
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#lookuptype-4-ligature-substitution-subtable
+    ///
+    /// Keyed by the first component.
+    /// The values are the other components and the ligature glyph, in order of preference.
+    Ligature(HashMap<GlyphId, Vec<(Vec<GlyphId>, GlyphId)>>),
+}
+
+const SINGLE: u16 = 1;
+const LIGATURE: u16 = 4;
+const EXTENSION: u16 = 7;
+
+impl Substitutions {
+    /// Read the lookups of `features`, which are OpenType feature tags such as `*b"liga"`.
+    /// Lookups that `parsed` already has for the same font are reused, the others are added to it.
+    ///
+    /// FIXME: only use the features of the script and language system of the text,
+    /// instead of every feature with a given tag.
+    /// Also support lookup flags (such as ignoring marks between the components of a ligature),
+    /// and the other lookup types: multiple, alternate, contextual, and chained contextual.
+    pub(crate) fn parse(
+        font: &Font,
+        features: &[[u8; 4]],
+        parsed: &mut ParsedLookups,
+    ) -> Result<Self, FontError> {
+        Self::parse_filtered(font, |tag| features.contains(&tag), parsed)
+    }
+
+    /// Read the lookups of every feature in the font,
+    /// for finding what text the glyphs they substitute stand for.
+    pub(crate) fn parse_all(font: &Font) -> Result<Self, FontError> {
+        Self::parse_filtered(font, |_| true, &mut ParsedLookups::default())
+    }
+
+    fn parse_filtered(
+        font: &Font,
+        include: impl Fn([u8; 4]) -> bool,
+        parsed: &mut ParsedLookups,
+    ) -> Result<Self, FontError> {
+        let bytes = font.bytes();
+        let header = match read_table_directory(bytes)?.find_table::<GlyphSubstitutionHeader>(bytes)
+        {
+            Ok(header) => header,
+            Err(FontError::MissingTable) => {
+                return Ok(Substitutions {
+                    lookups: Vec::new(),
+                })
+            }
+            Err(error) => return Err(error),
+        };
+
+        let feature_list: Position<u16> =
+            header.offset_bytes(header.feature_list_offset().read_from(bytes)?);
+        let feature_records = Slice::new(
+            feature_list.followed_by::<FeatureRecord>(),
+            feature_list.read_from(bytes)?,
+        );
+        let mut lookup_indices = Vec::new();
+        for record in feature_records {
+            if !include(record.tag().read_from(bytes)?.0) {
+                continue;
+            }
+            let feature: Position<FeatureTable> =
+                feature_list.offset_bytes(record.feature_offset().read_from(bytes)?);
+            let indices = Slice::new(
+                feature.followed_by::<u16>(),
+                feature.lookup_index_count().read_from(bytes)?,
+            );
+            for index in indices {
+                lookup_indices.push(index.read_from(bytes)?)
+            }
+        }
+        // Lookups apply in the order of the lookup list, not of features.
+        lookup_indices.sort();
+        lookup_indices.dedup();
+
+        let lookup_list: Position<u16> =
+            header.offset_bytes(header.lookup_list_offset().read_from(bytes)?);
+        let lookup_offsets = Slice::new(
+            lookup_list.followed_by::<u16>(),
+            lookup_list.read_from(bytes)?,
+        );
+        let mut lookups = Vec::new();
+        for index in lookup_indices {
+            if u32::from(index) >= lookup_offsets.count() {
+                continue;
+            }
+            if let Some(lookup) = parsed.by_index.get(&index) {
+                lookups.extend(lookup.iter().cloned());
+                continue;
+            }
+            let lookup: Position<LookupTable> = lookup_list.offset_bytes(
+                lookup_offsets
+                    .get_unchecked(index.into())
+                    .read_from(bytes)?,
+            );
+            let lookup_type = lookup.lookup_type().read_from(bytes)?;
+            let subtable_offsets = Slice::new(
+                lookup.followed_by::<u16>(),
+                lookup.subtable_count().read_from(bytes)?,
+            );
+            let mut single = HashMap::new();
+            let mut ligatures = HashMap::new();
+            for offset in subtable_offsets {
+                let mut subtable: Position<u16> = lookup.offset_bytes(offset.read_from(bytes)?);
+                let mut subtable_type = lookup_type;
+                if subtable_type == EXTENSION {
+                    let extension = subtable.cast::<ExtensionSubstitution>();
+                    subtable_type = extension.extension_lookup_type().read_from(bytes)?;
+                    subtable = subtable.offset_bytes(extension.extension_offset().read_from(bytes)?)
+                }
+                match subtable_type {
+                    SINGLE => read_single_substitution(bytes, subtable, &mut single)?,
+                    LIGATURE => read_ligature_substitution(bytes, subtable, &mut ligatures)?,
+                    _ => {}
+                }
+            }
+            let mut parsed_lookup = Vec::new();
+            if !single.is_empty() {
+                parsed_lookup.push(Arc::new(Lookup::Single(single)))
+            }
+            if !ligatures.is_empty() {
+                parsed_lookup.push(Arc::new(Lookup::Ligature(ligatures)))
+            }
+            lookups.extend(parsed_lookup.iter().cloned());
+            parsed.by_index.insert(index, parsed_lookup);
+        }
+        Ok(Substitutions { lookups })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lookups.is_empty()
+    }
+
+    /// The text of the glyphs that these lookups substitute and that are not in `text` already,
+    /// from the text of the glyphs they replace, sorted by glyph ID.
+    /// A ligature stands for the text of all its components.
+    ///
+    /// `text` maps glyphs to their characters, typically from the font’s character map.
+    pub(crate) fn substitute_text(
+        &self,
+        text: &HashMap<GlyphId, String>,
+    ) -> Vec<(GlyphId, String)> {
+        let mut substitutes = HashMap::new();
+        for lookup in &self.lookups {
+            let text_of = |glyph: &GlyphId| text.get(glyph).or_else(|| substitutes.get(glyph));
+            let mut found = Vec::new();
+            match &**lookup {
+                Lookup::Single(single) => {
+                    for (glyph, &substitute) in single {
+                        if let Some(glyph_text) = text_of(glyph) {
+                            found.push((substitute, glyph_text.clone()))
+                        }
+                    }
+                }
+                Lookup::Ligature(ligatures) => {
+                    for (first, candidates) in ligatures {
+                        for (components, ligature) in candidates {
+                            let ligature_text = std::iter::once(first)
+                                .chain(components)
+                                .map(|glyph| text_of(glyph).map(|s| s.as_str()))
+                                .collect::<Option<String>>();
+                            if let Some(ligature_text) = ligature_text {
+                                found.push((*ligature, ligature_text))
+                            }
+                        }
+                    }
+                }
+            }
+            // Deterministic when several glyphs have the same substitute
+            found.sort_by(|(a, a_text), (b, b_text)| (a.0, a_text).cmp(&(b.0, b_text)));
+            for (glyph, glyph_text) in found {
+                if !text.contains_key(&glyph) {
+                    substitutes.entry(glyph).or_insert(glyph_text);
+                }
+            }
+        }
+        let mut substitutes: Vec<_> = substitutes.into_iter().collect();
+        substitutes.sort_by_key(|(GlyphId(glyph), _)| *glyph);
+        substitutes
+    }
+
+    /// Replace glyphs in `glyphs`, which are in logical order.
+    pub(crate) fn apply(&self, glyphs: &mut Vec<GlyphId>) {
+        for lookup in &self.lookups {
+            match &**lookup {
+                Lookup::Single(substitutes) => {
+                    for glyph in glyphs.iter_mut() {
+                        if let Some(&substitute) = substitutes.get(glyph) {
+                            *glyph = substitute
+                        }
+                    }
+                }
+                Lookup::Ligature(ligatures) => {
+                    let mut i = 0;
+                    while i < glyphs.len() {
+                        let rest = &glyphs[i + 1..];
+                        let found = ligatures.get(&glyphs[i]).and_then(|candidates| {
+                            candidates
+                                .iter()
+                                .find(|(components, _)| rest.starts_with(components))
+                        });
+                        if let Some((components, ligature)) = found {
+                            glyphs.splice(i..i + 1 + components.len(), Some(*ligature));
+                        }
+                        i += 1
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#coverage-table
+///
+/// Returns the covered glyphs in order of their coverage index.
+fn read_coverage(bytes: &[u8], coverage: Position<u16>) -> Result<Vec<GlyphId>, FontError> {
+    let header = coverage.cast::<CoverageHeader>();
+    let count = header.count().read_from(bytes)?;
+    let mut glyphs = Vec::new();
+    match header.format().read_from(bytes)? {
+        1 => {
+            for glyph in Slice::new(header.followed_by::<u16>(), count) {
+                glyphs.push(GlyphId(glyph.read_from(bytes)?))
+            }
+        }
+        2 => {
+            for range in Slice::new(header.followed_by::<CoverageRangeRecord>(), count) {
+                let start = range.start_glyph_id().read_from(bytes)?;
+                let end = range.end_glyph_id().read_from(bytes)?;
+                glyphs.extend((start..=end).map(GlyphId))
+            }
+        }
+        _ => {}
+    }
+    Ok(glyphs)
+}
+
+fn read_single_substitution(
+    bytes: &[u8],
+    subtable: Position<u16>,
+    substitutes: &mut HashMap<GlyphId, GlyphId>,
+) -> Result<(), FontError> {
+    match subtable.read_from(bytes)? {
+        1 => {
+            let subtable = subtable.cast::<SingleSubstitutionFormat1>();
+            let coverage = subtable.offset_bytes(subtable.coverage_offset().read_from(bytes)?);
+            let delta = subtable.delta_glyph_id().read_from(bytes)?;
+            for GlyphId(glyph) in read_coverage(bytes, coverage)? {
+                // Earlier subtables take precedence
+                substitutes
+                    .entry(GlyphId(glyph))
+                    .or_insert(GlyphId(glyph.wrapping_add(delta as u16)));
+            }
+        }
+        2 => {
+            let subtable = subtable.cast::<SingleSubstitutionFormat2>();
+            let coverage = subtable.offset_bytes(subtable.coverage_offset().read_from(bytes)?);
+            let substitute_glyphs = Slice::new(
+                subtable.followed_by::<u16>(),
+                subtable.glyph_count().read_from(bytes)?,
+            );
+            for (glyph, substitute) in read_coverage(bytes, coverage)?
+                .into_iter()
+                .zip(substitute_glyphs)
+            {
+                let substitute = GlyphId(substitute.read_from(bytes)?);
+                substitutes.entry(glyph).or_insert(substitute);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_ligature_substitution(
+    bytes: &[u8],
+    subtable: Position<u16>,
+    ligatures: &mut HashMap<GlyphId, Vec<(Vec<GlyphId>, GlyphId)>>,
+) -> Result<(), FontError> {
+    let subtable = subtable.cast::<LigatureSubstitution>();
+    let coverage = subtable.offset_bytes(subtable.coverage_offset().read_from(bytes)?);
+    let ligature_sets = Slice::new(
+        subtable.followed_by::<u16>(),
+        subtable.ligature_set_count().read_from(bytes)?,
+    );
+    for (first, set_offset) in read_coverage(bytes, coverage)?
+        .into_iter()
+        .zip(ligature_sets)
+    {
+        let set: Position<u16> = subtable.offset_bytes(set_offset.read_from(bytes)?);
+        let ligature_offsets = Slice::new(set.followed_by::<u16>(), set.read_from(bytes)?);
+        let candidates = ligatures.entry(first).or_default();
+        for ligature_offset in ligature_offsets {
+            let ligature: Position<Ligature> = set.offset_bytes(ligature_offset.read_from(bytes)?);
+            // The first component is the covered glyph
+            let other_components = Slice::new(
+                ligature.followed_by::<u16>(),
+                ligature
+                    .component_count()
+                    .read_from(bytes)?
+                    .saturating_sub(1),
+            );
+            let mut components = Vec::new();
+            for component in other_components {
+                components.push(GlyphId(component.read_from(bytes)?))
+            }
+            candidates.push((
+                components,
+                GlyphId(ligature.ligature_glyph().read_from(bytes)?),
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn substitute_text() {
+    let glyphs = |ids: &[u16]| ids.iter().map(|&id| GlyphId(id)).collect::<Vec<_>>();
+    let mut ligatures = HashMap::new();
+    // f + f + i => ffi, f + i => fi
+    ligatures.insert(
+        GlyphId(1),
+        vec![(glyphs(&[1, 2]), GlyphId(10)), (glyphs(&[2]), GlyphId(11))],
+    );
+    let mut single = HashMap::new();
+    // Small caps of the ffi ligature
+    single.insert(GlyphId(10), GlyphId(20));
+    let substitutions = Substitutions {
+        lookups: vec![
+            Arc::new(Lookup::Ligature(ligatures)),
+            Arc::new(Lookup::Single(single)),
+        ],
+    };
+    let text = vec![(GlyphId(1), String::from("f")), (GlyphId(2), "i".into())]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        substitutions.substitute_text(&text),
+        [
+            (GlyphId(10), String::from("ffi")),
+            (GlyphId(11), String::from("fi")),
+            (GlyphId(20), String::from("ffi")),
+        ]
+    );
+}
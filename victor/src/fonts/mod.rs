@@ -1,7 +1,18 @@
 mod cmap;
+mod context;
+#[cfg(feature = "html")]
+mod decorations;
+#[cfg(feature = "html")]
+mod gsub;
 mod parsing;
 mod tables;
 mod types;
+#[cfg(feature = "html")]
+mod variations;
+
+pub use crate::fonts::context::{FontContext, GenericFontFamily};
+#[cfg(feature = "html")]
+pub(crate) use crate::fonts::gsub::Substitutions;
 
 use crate::fonts::cmap::Cmap;
 use crate::fonts::parsing::*;
@@ -59,6 +70,10 @@ pub struct Font {
     /// Distance from baseline of lowest descender
     descender: euclid::Length<i16, FontDesignUnit>,
 
+    /// Bit 0 is set for bold faces, bit 1 for italic faces.
+    #[cfg(feature = "html")]
+    mac_style: u16,
+
     /// The bounding box of the union of all glyphs
     min_x: euclid::Length<i16, FontDesignUnit>,
     min_y: euclid::Length<i16, FontDesignUnit>,
@@ -86,16 +101,7 @@ impl Font {
     #[inline]
     fn parse_without_cow_bytes_field(bytes: &[u8]) -> Result<Self, FontError> {
         let bytes: &[u8] = &*bytes;
-        let offset_table = Position::<OffsetSubtable>::initial();
-        let scaler_type = offset_table.scaler_type().read_from(bytes)?;
-        const TRUETYPE: u32 = 0x74727565; // "true" in big-endian
-        if scaler_type != TRUETYPE && scaler_type != 0x_0001_0000 {
-            Err(FontError::UnsupportedFormat)?
-        }
-        let table_directory = Slice::new(
-            offset_table.followed_by::<TableDirectoryEntry>(),
-            offset_table.table_count().read_from(bytes)?,
-        );
+        let table_directory = read_table_directory(bytes)?;
 
         let maxp = table_directory.find_table::<MaximumProfile>(bytes)?;
         let header = table_directory.find_table::<FontHeader>(bytes)?;
@@ -116,6 +122,8 @@ impl Font {
             font_design_units_per_em: header.units_per_em().read_from(bytes)?.cast(),
             ascender: horizontal_header.ascender().read_from(bytes)?,
             descender: horizontal_header.descender().read_from(bytes)?,
+            #[cfg(feature = "html")]
+            mac_style: header.mac_style().read_from(bytes)?,
             min_x: header.min_x().read_from(bytes)?,
             min_y: header.min_y().read_from(bytes)?,
             max_x: header.max_x().read_from(bytes)?,
@@ -193,6 +201,31 @@ impl Font {
     pub(crate) fn max_y(&self) -> euclid::Length<f32, Em> {
         self.to_ems(self.max_y)
     }
+
+    /// Whether this is a bold face, rather than one that bold text should be synthesized from
+    #[cfg(feature = "html")]
+    pub(crate) fn is_bold(&self) -> bool {
+        self.mac_style & 1 != 0
+    }
+
+    /// Whether this is an italic or oblique face
+    #[cfg(feature = "html")]
+    pub(crate) fn is_italic(&self) -> bool {
+        self.mac_style & 2 != 0
+    }
+}
+
+fn read_table_directory(bytes: &[u8]) -> Result<Slice<TableDirectoryEntry>, FontError> {
+    let offset_table = Position::<OffsetSubtable>::initial();
+    let scaler_type = offset_table.scaler_type().read_from(bytes)?;
+    const TRUETYPE: u32 = 0x74727565; // "true" in big-endian
+    if scaler_type != TRUETYPE && scaler_type != 0x_0001_0000 {
+        Err(FontError::UnsupportedFormat)?
+    }
+    Ok(Slice::new(
+        offset_table.followed_by::<TableDirectoryEntry>(),
+        offset_table.table_count().read_from(bytes)?,
+    ))
 }
 
 fn read_postscript_name(
@@ -17,7 +17,7 @@ pub(in crate::fonts) struct TableDirectoryEntry {
     tag: Tag,
     _checksum: u32,
     table_offset: u32,
-    _length: u32,
+    length: u32,
 }
 
 #[derive(SfntTable)]
@@ -103,10 +103,10 @@ pub(in crate::fonts) struct FontHeader {
     min_y: FWord,
     max_x: FWord,
     max_y: FWord,
-    _mac_style: u16,
+    mac_style: u16,
     _smallest_readable_size_in_px_per_em: u16,
     _font_direction_hint: i16,
-    _index_to_loc_format: i16,
+    index_to_loc_format: i16,
     _glyph_data_format: i16,
     __padding: u16,
 }
@@ -133,9 +133,204 @@ pub(in crate::fonts) struct HorizontalHeader {
     number_of_long_horizontal_metrics: u16,
 }
 
+#[derive(SfntTable)]
+#[tag = "post"]
+pub(in crate::fonts) struct PostScriptHeader {
+    _version: FixedPoint,
+    _italic_angle: FixedPoint,
+    underline_position: FWord,
+    underline_thickness: FWord,
+    // More fields that we don’t use, and glyph names in some versions
+}
+
+#[derive(SfntTable)]
+#[tag = "OS/2"]
+pub(in crate::fonts) struct Os2Header {
+    _version: u16,
+    _x_avg_char_width: FWord,
+    _weight_class: u16,
+    _width_class: u16,
+    _type: u16,
+    _subscript_x_size: FWord,
+    _subscript_y_size: FWord,
+    _subscript_x_offset: FWord,
+    _subscript_y_offset: FWord,
+    _superscript_x_size: FWord,
+    _superscript_y_size: FWord,
+    _superscript_x_offset: FWord,
+    _superscript_y_offset: FWord,
+    strikeout_size: FWord,
+    strikeout_position: FWord,
+    // More fields that we don’t use
+}
+
 #[derive(SfntTable)]
 #[tag = "hmtx"]
 pub(in crate::fonts) struct LongHorizontalMetricsRecord {
     advance_width: UFWord,
     _left_side_bearing: i16,
 }
+
+#[derive(SfntTable)]
+#[tag = "GSUB"]
+pub(in crate::fonts) struct GlyphSubstitutionHeader {
+    _major_version: u16,
+    _minor_version: u16,
+    _script_list_offset: u16,
+    feature_list_offset: u16,
+    lookup_list_offset: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct FeatureRecord {
+    tag: Tag,
+    feature_offset: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct FeatureTable {
+    _feature_params_offset: u16,
+    lookup_index_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct LookupTable {
+    lookup_type: u16,
+    _lookup_flag: u16,
+    subtable_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct CoverageHeader {
+    format: u16,
+    /// The number of glyphs in format 1, or of ranges in format 2
+    count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct CoverageRangeRecord {
+    start_glyph_id: u16,
+    end_glyph_id: u16,
+    _start_coverage_index: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct SingleSubstitutionFormat1 {
+    _format: u16,
+    coverage_offset: u16,
+    delta_glyph_id: i16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct SingleSubstitutionFormat2 {
+    _format: u16,
+    coverage_offset: u16,
+    glyph_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct LigatureSubstitution {
+    _format: u16,
+    coverage_offset: u16,
+    ligature_set_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct Ligature {
+    ligature_glyph: u16,
+    component_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct ExtensionSubstitution {
+    _format: u16,
+    extension_lookup_type: u16,
+    extension_offset: u32,
+}
+
+#[derive(SfntTable)]
+#[tag = "fvar"]
+pub(in crate::fonts) struct FontVariationsHeader {
+    _major_version: u16,
+    _minor_version: u16,
+    axes_array_offset: u16,
+    __reserved: u16,
+    axis_count: u16,
+    axis_size: u16,
+    _instance_count: u16,
+    _instance_size: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct VariationAxisRecord {
+    tag: Tag,
+    min_value: FixedPoint,
+    default_value: FixedPoint,
+    max_value: FixedPoint,
+    _flags: u16,
+    _axis_name_id: u16,
+}
+
+#[derive(SfntTable)]
+#[tag = "avar"]
+pub(in crate::fonts) struct AxisVariationsHeader {
+    _major_version: u16,
+    _minor_version: u16,
+    __reserved: u16,
+    axis_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct AxisValueMap {
+    from_coordinate: i16,
+    to_coordinate: i16,
+}
+
+#[derive(SfntTable)]
+#[tag = "gvar"]
+pub(in crate::fonts) struct GlyphVariationsHeader {
+    _major_version: u16,
+    _minor_version: u16,
+    axis_count: u16,
+    shared_tuple_count: u16,
+    shared_tuples_offset: u32,
+    _glyph_count: u16,
+    flags: u16,
+    glyph_variation_data_array_offset: u32,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct GlyphVariationDataHeader {
+    tuple_variation_count: u16,
+    data_offset: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct TupleVariationHeader {
+    variation_data_size: u16,
+    tuple_index: u16,
+}
+
+#[derive(SfntTable)]
+#[tag = "HVAR"]
+pub(in crate::fonts) struct HorizontalMetricsVariationsHeader {
+    _major_version: u16,
+    _minor_version: u16,
+    item_variation_store_offset: u32,
+    advance_width_mapping_offset: u32,
+    _left_side_bearing_mapping_offset: u32,
+    _right_side_bearing_mapping_offset: u32,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct VariationRegionListHeader {
+    axis_count: u16,
+    region_count: u16,
+}
+
+#[derive(SfntTable)]
+pub(in crate::fonts) struct ItemVariationDataHeader {
+    item_count: u16,
+    word_delta_count: u16,
+    region_index_count: u16,
+}
@@ -0,0 +1,1452 @@
+//! Instances of variable fonts, with their outlines and advance widths
+//! varied for given values of the variation axes.
+//!
+//! https://docs.microsoft.com/en-us/typography/opentype/spec/otvaroverview
+//!
+//! PDF can only embed static fonts, so each instance is written as a new TrueType font:
+//! the `glyf`, `loca` and `hmtx` tables are rewritten, and variation tables are left out.
+
+use crate::fonts::parsing::{Position, Slice};
+use crate::fonts::tables::*;
+use crate::fonts::types::Tag;
+use crate::fonts::{read_table_directory, Font, FontError, GlyphId};
+use std::cmp;
+use std::fmt::Write;
+
+/// The variation axes of a font, from its `fvar` and `avar` tables.
+#[derive(Default)]
+pub(crate) struct VariationAxes {
+    axes: Vec<Axis>,
+}
+
+struct Axis {
+    tag: [u8; 4],
+    min: f32,
+    default: f32,
+    max: f32,
+    /// Pairs of normalized coordinates from `avar`, empty for the identity mapping
+    segment_map: Vec<(f32, f32)>,
+}
+
+impl VariationAxes {
+    /// The axes of `font`, none for a font that is not variable.
+    pub(crate) fn parse(font: &Font) -> Result<Self, FontError> {
+        let bytes = font.bytes();
+        let table_directory = read_table_directory(bytes)?;
+        let header = match table_directory.find_table::<FontVariationsHeader>(bytes) {
+            Ok(header) => header,
+            Err(FontError::MissingTable) => return Ok(VariationAxes { axes: Vec::new() }),
+            Err(error) => return Err(error),
+        };
+        let axis_size = u32::from(header.axis_size().read_from(bytes)?);
+        let first_axis: Position<VariationAxisRecord> =
+            header.offset_bytes(header.axes_array_offset().read_from(bytes)?);
+        let mut axes = Vec::new();
+        for i in 0..header.axis_count().read_from(bytes)? {
+            let record: Position<VariationAxisRecord> =
+                first_axis.offset_bytes(u32::from(i) * axis_size);
+            let min: f32 = record.min_value().read_from(bytes)?.into();
+            let default: f32 = record.default_value().read_from(bytes)?.into();
+            let max: f32 = record.max_value().read_from(bytes)?.into();
+            axes.push(Axis {
+                tag: record.tag().read_from(bytes)?.0,
+                // Ranges that do not contain the default value are invalid
+                min: min.min(default),
+                default,
+                max: max.max(default),
+                segment_map: Vec::new(),
+            })
+        }
+
+        match table_directory.find_table::<AxisVariationsHeader>(bytes) {
+            Ok(header) => {
+                let count = header.axis_count().read_from(bytes)?;
+                let mut segment_map: Position<u16> = header.followed_by();
+                for axis in axes.iter_mut().take(count as usize) {
+                    let maps = Slice::new(
+                        segment_map.followed_by::<AxisValueMap>(),
+                        segment_map.read_from(bytes)?,
+                    );
+                    for map in maps {
+                        axis.segment_map.push((
+                            f2dot14(map.from_coordinate().read_from(bytes)?),
+                            f2dot14(map.to_coordinate().read_from(bytes)?),
+                        ))
+                    }
+                    segment_map = maps.get_unchecked(maps.count()).cast();
+                }
+            }
+            Err(FontError::MissingTable) => {}
+            Err(error) => return Err(error),
+        }
+        Ok(VariationAxes { axes })
+    }
+
+    /// The value of each axis for `variations`, clamped to the range of the axis,
+    /// or `None` if these are the default values.
+    /// Variations of axes that the font does not have are ignored.
+    pub(crate) fn values(&self, variations: &[([u8; 4], f32)]) -> Option<Vec<f32>> {
+        let values: Vec<f32> = self
+            .axes
+            .iter()
+            .map(|axis| {
+                variations
+                    .iter()
+                    .find(|&&(tag, _)| tag == axis.tag)
+                    .map_or(axis.default, |&(_, value)| {
+                        value.max(axis.min).min(axis.max)
+                    })
+            })
+            .collect();
+        if self
+            .axes
+            .iter()
+            .zip(&values)
+            .all(|(axis, &value)| value == axis.default)
+        {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/otvaroverview#coordinate-scales-and-normalization
+    fn normalized_coordinates(&self, values: &[f32]) -> Vec<f32> {
+        self.axes
+            .iter()
+            .zip(values)
+            .map(|(axis, &value)| {
+                let normalized = if value < axis.default {
+                    (value - axis.default) / (axis.default - axis.min)
+                } else if value > axis.default {
+                    (value - axis.default) / (axis.max - axis.default)
+                } else {
+                    0.
+                };
+                quantize(axis.map_segments(quantize(normalized)))
+            })
+            .collect()
+    }
+}
+
+impl Axis {
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/avar
+    fn map_segments(&self, coordinate: f32) -> f32 {
+        let mut previous = match self.segment_map.first() {
+            Some(&(from, to)) if coordinate <= from => return to,
+            Some(&first) => first,
+            None => return coordinate,
+        };
+        for &(from, to) in &self.segment_map[1..] {
+            if coordinate <= from {
+                if from == previous.0 {
+                    return to;
+                }
+                return previous.1
+                    + (to - previous.1) * (coordinate - previous.0) / (from - previous.0);
+            }
+            previous = (from, to)
+        }
+        previous.1
+    }
+}
+
+/// Write the instance of `font` at `values`, as returned by `VariationAxes::values`.
+///
+/// Its PostScript name is that of the default instance followed by the axis values,
+/// like Adobe Technical Note #5902 suggests, so that each instance embedded in a PDF
+/// has a different name.
+pub(crate) fn instance(
+    font: &Font,
+    axes: &VariationAxes,
+    values: &[f32],
+) -> Result<Font, FontError> {
+    let bytes = font.bytes();
+    let table_directory = read_table_directory(bytes)?;
+    let mut tables = Vec::new();
+    for entry in table_directory {
+        let data = Slice::<u8>::new(
+            Position::<OffsetSubtable>::initial()
+                .offset_bytes(entry.table_offset().read_from(bytes)?),
+            entry.length().read_from(bytes)?,
+        )
+        .read_from(bytes)?;
+        tables.push((entry.tag().read_from(bytes)?.0, data))
+    }
+    let table = |tag: &[u8; 4]| {
+        tables
+            .iter()
+            .find(|&&(t, _)| t == *tag)
+            .map(|&(_, data)| data)
+            .ok_or(FontError::MissingTable)
+    };
+
+    let header = table_directory.find_table::<FontHeader>(bytes)?;
+    let long_offsets = header.index_to_loc_format().read_from(bytes)? != 0;
+    let (glyf, loca) = (table(b"glyf")?, table(b"loca")?);
+    let coordinates = axes.normalized_coordinates(values);
+    let glyph_variations = GlyphVariations::parse(bytes, table_directory)?;
+    let advance_variations = AdvanceVariations::parse(bytes, table_directory)?;
+    let last_metrics = font
+        .horizontal_metrics
+        .count()
+        .checked_sub(1)
+        .ok_or(FontError::NoHorizontalGlyphMetrics)?;
+
+    let mut outlines = Vec::with_capacity(font.glyph_count as usize);
+    let mut advance_widths = Vec::with_capacity(font.glyph_count as usize);
+    for glyph in 0..font.glyph_count {
+        let mut outline = Outline::parse(glyph_data(glyf, loca, long_offsets, glyph)?)?;
+        let advance_width = font
+            .horizontal_metrics
+            .get_unchecked(cmp::min(u32::from(glyph), last_metrics))
+            .advance_width()
+            .read_from(bytes)?
+            .get();
+        let mut advance_delta = 0.;
+        if let Some(variations) = &glyph_variations {
+            let deltas = variations.deltas(bytes, glyph, &coordinates, &outline)?;
+            // The first two phantom points are at the origin and at the advance width.
+            let origin = deltas[deltas.len() - 4].0;
+            advance_delta = deltas[deltas.len() - 3].0 - origin;
+            outline.apply_deltas(&deltas, origin);
+        }
+        if let Some(variations) = &advance_variations {
+            advance_delta = variations.delta(bytes, glyph, &coordinates)?
+        }
+        let advance_width = (f32::from(advance_width) + advance_delta).round();
+        advance_widths.push(advance_width.max(0.).min(f32::from(u16::MAX)) as u16);
+        outlines.push(outline)
+    }
+
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::new();
+    let mut new_hmtx = Vec::new();
+    let mut font_bounds: Option<Bounds> = None;
+    let mut extents = (i16::MAX, i16::MAX, i16::MIN);
+    for (glyph, outline) in outlines.iter().enumerate() {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        let bounds = Outline::bounds(&outlines, glyph, 0).map(Bounds::round);
+        outline.write(bounds, &mut new_glyf);
+        let advance_width = advance_widths[glyph];
+        let left_side_bearing = bounds.map_or(0, |b| b.min_x as i16);
+        new_hmtx.extend_from_slice(&advance_width.to_be_bytes());
+        new_hmtx.extend_from_slice(&left_side_bearing.to_be_bytes());
+        if let Some(bounds) = bounds {
+            font_bounds = Some(font_bounds.map_or(bounds, |b| b.union(bounds)));
+            let right_side_bearing = i32::from(advance_width) - bounds.max_x as i32;
+            extents.0 = cmp::min(extents.0, left_side_bearing);
+            extents.1 = cmp::min(extents.1, clamp_i16(right_side_bearing as f32));
+            extents.2 = cmp::max(extents.2, bounds.max_x as i16);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let font_bounds = font_bounds.unwrap_or(Bounds {
+        min_x: 0.,
+        min_y: 0.,
+        max_x: 0.,
+        max_y: 0.,
+    });
+    let mut new_head = table(b"head")?.to_vec();
+    let mut new_hhea = table(b"hhea")?.to_vec();
+    if new_head.len() < 54 || new_hhea.len() < 36 {
+        return Err(FontError::OffsetPlusLengthBeyondEof);
+    }
+    // Zero `checkSumAdjustment` until the whole font is written
+    set_u16(&mut new_head, 8, 0);
+    set_u16(&mut new_head, 10, 0);
+    set_u16(&mut new_head, 36, font_bounds.min_x as i16 as u16);
+    set_u16(&mut new_head, 38, font_bounds.min_y as i16 as u16);
+    set_u16(&mut new_head, 40, font_bounds.max_x as i16 as u16);
+    set_u16(&mut new_head, 42, font_bounds.max_y as i16 as u16);
+    set_u16(
+        &mut new_head,
+        44,
+        instance_mac_style(font.mac_style, axes, values),
+    );
+    // Long offsets in `loca`
+    set_u16(&mut new_head, 50, 1);
+    let max_advance_width = advance_widths.iter().cloned().max().unwrap_or(0);
+    set_u16(&mut new_hhea, 10, max_advance_width);
+    set_u16(&mut new_hhea, 12, extents.0 as u16);
+    set_u16(&mut new_hhea, 14, extents.1 as u16);
+    set_u16(&mut new_hhea, 16, extents.2 as u16);
+    set_u16(&mut new_hhea, 34, font.glyph_count);
+
+    let mut new_tables = vec![
+        (*b"glyf", new_glyf),
+        (*b"head", new_head),
+        (*b"hhea", new_hhea),
+        (*b"hmtx", new_hmtx),
+        (*b"loca", new_loca),
+    ];
+    for &(tag, data) in &tables {
+        let replaced = new_tables.iter().any(|&(t, _)| t == tag);
+        // Glyphs are written without their hinting instructions, which would not match
+        // the varied outlines. Device metrics would not match the new advance widths either.
+        const LEFT_OUT: &[&[u8; 4]] = &[
+            b"HVAR", b"MVAR", b"STAT", b"VVAR", b"avar", b"cvar", b"fvar", b"gvar", b"hdmx",
+            b"LTSH", b"VDMX",
+        ];
+        if !replaced && !LEFT_OUT.contains(&&tag) {
+            new_tables.push((tag, data.to_vec()))
+        }
+    }
+
+    let mut postscript_name = font.postscript_name.clone();
+    for (axis, &value) in axes.axes.iter().zip(values) {
+        if value != axis.default {
+            let tag = String::from_utf8_lossy(&axis.tag);
+            write!(postscript_name, "_{}{}", value, tag.trim_end()).unwrap()
+        }
+    }
+    let new_bytes = write_font(new_tables);
+    let mut instance = Font::parse_without_cow_bytes_field(&new_bytes)?;
+    instance.bytes = new_bytes.into();
+    instance.postscript_name = postscript_name;
+    Ok(instance)
+}
+
+/// Bold if `wght` is at least 600, italic if `ital` is at least 0.5 or `slnt` is not zero.
+/// Styles are kept for axes that the font does not have.
+fn instance_mac_style(mac_style: u16, axes: &VariationAxes, values: &[f32]) -> u16 {
+    const BOLD: u16 = 1;
+    const ITALIC: u16 = 2;
+    let value = |tag: &[u8; 4]| {
+        axes.axes
+            .iter()
+            .zip(values)
+            .find(|(axis, _)| axis.tag == *tag)
+            .map(|(_, &value)| value)
+    };
+    let mut mac_style = mac_style;
+    if let Some(weight) = value(b"wght") {
+        mac_style &= !BOLD;
+        if weight >= 600. {
+            mac_style |= BOLD
+        }
+    }
+    let (italic, slant) = (value(b"ital"), value(b"slnt"));
+    if italic.is_some() || slant.is_some() {
+        mac_style &= !ITALIC;
+        if italic.filter(|&v| v >= 0.5).is_some() || slant.filter(|&v| v != 0.).is_some() {
+            mac_style |= ITALIC
+        }
+    }
+    mac_style
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/loca
+fn glyph_data<'a>(
+    glyf: &'a [u8],
+    loca: &[u8],
+    long_offsets: bool,
+    glyph: u16,
+) -> Result<&'a [u8], FontError> {
+    let mut stream = Stream { bytes: loca };
+    let (start, end) = if long_offsets {
+        stream.skip(4 * glyph as usize)?;
+        (stream.u32()?, stream.u32()?)
+    } else {
+        stream.skip(2 * glyph as usize)?;
+        (2 * u32::from(stream.u16()?), 2 * u32::from(stream.u16()?))
+    };
+    glyf.get(start as usize..)
+        .ok_or(FontError::OffsetBeyondEof)?
+        .get(..end.saturating_sub(start) as usize)
+        .ok_or(FontError::OffsetPlusLengthBeyondEof)
+}
+
+/// Points in font design units, with whether they are on the curve
+pub(super) type Contour = Vec<(f32, f32, bool)>;
+
+/// The contours of a glyph. The points of components are transformed like `Outline::bounds` does.
+///
+/// This reads the outlines of any TrueType font, not only of variable ones.
+pub(super) fn glyph_contours(font: &Font, glyph: GlyphId) -> Result<Vec<Contour>, FontError> {
+    let bytes = font.bytes();
+    let table_directory = read_table_directory(bytes)?;
+    let table = |tag: &[u8; 4]| {
+        let entry = table_directory
+            .binary_search_by_key(&Tag(*tag), |entry| entry.tag().read_from(bytes))?
+            .ok_or(FontError::MissingTable)?;
+        Slice::<u8>::new(
+            Position::<OffsetSubtable>::initial()
+                .offset_bytes(entry.table_offset().read_from(bytes)?),
+            entry.length().read_from(bytes)?,
+        )
+        .read_from(bytes)
+    };
+    let header = table_directory.find_table::<FontHeader>(bytes)?;
+    let long_offsets = header.index_to_loc_format().read_from(bytes)? != 0;
+    let glyphs = (table(b"glyf")?, table(b"loca")?, long_offsets);
+    let mut contours = Vec::new();
+    let identity = [1., 0., 0., 1., 0., 0.];
+    Outline::add_contours(glyphs, glyph.0, identity, 0, &mut contours)?;
+    Ok(contours)
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf
+enum Outline {
+    Empty,
+    Simple(SimpleOutline),
+    Composite(Vec<Component>),
+}
+
+struct SimpleOutline {
+    contour_ends: Vec<u16>,
+    /// Only `ON_CURVE_POINT` and `OVERLAP_SIMPLE` are kept.
+    flags: Vec<u8>,
+    points: Vec<(f32, f32)>,
+}
+
+struct Component {
+    flags: u16,
+    glyph: u16,
+    /// An offset with `ARGS_ARE_XY_VALUES`, otherwise point numbers to match
+    arguments: (f32, f32),
+    /// A scale, a scale for each direction, or a 2×2 transformation, in F2Dot14
+    transformation: Vec<i16>,
+}
+
+#[derive(Copy, Clone)]
+struct Bounds {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+}
+
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// Like `maxComponentDepth` in `maxp`, but without trusting the font
+const MAX_COMPONENT_DEPTH: u8 = 8;
+
+impl Outline {
+    fn parse(data: &[u8]) -> Result<Self, FontError> {
+        if data.is_empty() {
+            return Ok(Outline::Empty);
+        }
+        let mut stream = Stream { bytes: data };
+        let contour_count = stream.i16()?;
+        // The bounding box is computed again for the instance
+        stream.skip(8)?;
+        if contour_count < 0 {
+            let mut components = Vec::new();
+            loop {
+                let flags = stream.u16()?;
+                let glyph = stream.u16()?;
+                let xy = flags & ARGS_ARE_XY_VALUES != 0;
+                let arguments = match (flags & ARG_1_AND_2_ARE_WORDS != 0, xy) {
+                    (true, true) => (f32::from(stream.i16()?), f32::from(stream.i16()?)),
+                    (true, false) => (f32::from(stream.u16()?), f32::from(stream.u16()?)),
+                    (false, true) => (f32::from(stream.i8()?), f32::from(stream.i8()?)),
+                    (false, false) => (f32::from(stream.u8()?), f32::from(stream.u8()?)),
+                };
+                let transformation_count = if flags & WE_HAVE_A_SCALE != 0 {
+                    1
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    2
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    4
+                } else {
+                    0
+                };
+                let transformation = (0..transformation_count)
+                    .map(|_| stream.i16())
+                    .collect::<Result<_, _>>()?;
+                components.push(Component {
+                    flags,
+                    glyph,
+                    arguments,
+                    transformation,
+                });
+                if flags & MORE_COMPONENTS == 0 {
+                    return Ok(Outline::Composite(components));
+                }
+            }
+        }
+
+        let contour_ends = (0..contour_count)
+            .map(|_| stream.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let point_count = contour_ends.last().map_or(0, |&end| end as usize + 1);
+        let instructions_length = stream.u16()?;
+        stream.skip(instructions_length as usize)?;
+        let mut flags = Vec::with_capacity(point_count);
+        while flags.len() < point_count {
+            let flag = stream.u8()?;
+            let repeat = if flag & REPEAT_FLAG != 0 {
+                stream.u8()?
+            } else {
+                0
+            };
+            for _ in 0..=repeat {
+                flags.push(flag)
+            }
+        }
+        flags.truncate(point_count);
+        let mut coordinates = |short, same_or_positive| {
+            let mut value = 0_i16;
+            flags
+                .iter()
+                .map(|&flag| {
+                    let delta = if flag & short != 0 {
+                        let delta = i16::from(stream.u8()?);
+                        if flag & same_or_positive != 0 {
+                            delta
+                        } else {
+                            -delta
+                        }
+                    } else if flag & same_or_positive != 0 {
+                        0
+                    } else {
+                        stream.i16()?
+                    };
+                    value = value.wrapping_add(delta);
+                    Ok(f32::from(value))
+                })
+                .collect::<Result<Vec<_>, FontError>>()
+        };
+        let xs = coordinates(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR)?;
+        let ys = coordinates(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR)?;
+        Ok(Outline::Simple(SimpleOutline {
+            contour_ends,
+            flags: flags
+                .iter()
+                .map(|&flag| flag & (ON_CURVE_POINT | OVERLAP_SIMPLE))
+                .collect(),
+            points: xs.into_iter().zip(ys).collect(),
+        }))
+    }
+
+    /// The number of points that variations apply to, not counting phantom points:
+    /// for composite glyphs, one for the offset of each component.
+    fn point_count(&self) -> usize {
+        match self {
+            Outline::Empty => 0,
+            Outline::Simple(simple) => simple.points.len(),
+            Outline::Composite(components) => components.len(),
+        }
+    }
+
+    /// Add `deltas`, and move the outline by `-origin`
+    /// so that the origin of the instance is still at zero.
+    fn apply_deltas(&mut self, deltas: &[(f32, f32)], origin: f32) {
+        match self {
+            Outline::Empty => {}
+            Outline::Simple(simple) => {
+                for (point, &(dx, dy)) in simple.points.iter_mut().zip(deltas) {
+                    point.0 += dx - origin;
+                    point.1 += dy;
+                }
+            }
+            Outline::Composite(components) => {
+                for (component, &(dx, dy)) in components.iter_mut().zip(deltas) {
+                    if component.flags & ARGS_ARE_XY_VALUES != 0 {
+                        component.arguments.0 += dx - origin;
+                        component.arguments.1 += dy;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The bounding box of a glyph, including its components.
+    ///
+    /// FIXME: components positioned by matching points are placed at the origin,
+    /// and a transformed component contributes its transformed bounding box
+    /// rather than that of its points.
+    fn bounds(outlines: &[Outline], glyph: usize, depth: u8) -> Option<Bounds> {
+        match outlines.get(glyph)? {
+            Outline::Empty => None,
+            Outline::Simple(simple) => simple.points.iter().fold(None, |bounds, &(x, y)| {
+                let (x, y) = (x.round(), y.round());
+                let point = Bounds {
+                    min_x: x,
+                    min_y: y,
+                    max_x: x,
+                    max_y: y,
+                };
+                Some(bounds.map_or(point, |b: Bounds| b.union(point)))
+            }),
+            Outline::Composite(_) if depth >= MAX_COMPONENT_DEPTH => None,
+            Outline::Composite(components) => components.iter().fold(None, |bounds, component| {
+                let b = match Self::bounds(outlines, component.glyph as usize, depth + 1) {
+                    Some(b) => b.round(),
+                    None => return bounds,
+                };
+                let (xx, yx, xy, yy) = match component.transformation[..] {
+                    [scale] => (f2dot14(scale), 0., 0., f2dot14(scale)),
+                    [x_scale, y_scale] => (f2dot14(x_scale), 0., 0., f2dot14(y_scale)),
+                    [xx, yx, xy, yy] => (f2dot14(xx), f2dot14(yx), f2dot14(xy), f2dot14(yy)),
+                    _ => (1., 0., 0., 1.),
+                };
+                let (dx, dy) = if component.flags & ARGS_ARE_XY_VALUES != 0 {
+                    (component.arguments.0.round(), component.arguments.1.round())
+                } else {
+                    (0., 0.)
+                };
+                let corners = [
+                    (b.min_x, b.min_y),
+                    (b.min_x, b.max_y),
+                    (b.max_x, b.min_y),
+                    (b.max_x, b.max_y),
+                ];
+                corners.iter().fold(bounds, |bounds, &(x, y)| {
+                    let (x, y) = (xx * x + xy * y + dx, yx * x + yy * y + dy);
+                    let corner = Bounds {
+                        min_x: x,
+                        min_y: y,
+                        max_x: x,
+                        max_y: y,
+                    };
+                    Some(bounds.map_or(corner, |b: Bounds| b.union(corner)))
+                })
+            }),
+        }
+    }
+
+    /// Append the contours of `glyph` with `transform` (`[xx, yx, xy, yy, dx, dy]`) applied,
+    /// parsing the outlines of its components from `glyf` and `loca` as needed.
+    fn add_contours(
+        glyphs: (&[u8], &[u8], bool),
+        glyph: u16,
+        transform: [f32; 6],
+        depth: u8,
+        contours: &mut Vec<Contour>,
+    ) -> Result<(), FontError> {
+        let (glyf, loca, long_offsets) = glyphs;
+        let [xx, yx, xy, yy, dx, dy] = transform;
+        match Self::parse(glyph_data(glyf, loca, long_offsets, glyph)?)? {
+            Outline::Empty => {}
+            Outline::Simple(simple) => {
+                let mut start = 0;
+                for &end in &simple.contour_ends {
+                    let end = end as usize + 1;
+                    let points = simple
+                        .points
+                        .get(start..end)
+                        .ok_or(FontError::OffsetPlusLengthBeyondEof)?;
+                    let flags = &simple.flags[start..end];
+                    contours.push(
+                        points
+                            .iter()
+                            .zip(flags)
+                            .map(|(&(x, y), &flag)| {
+                                let on_curve = flag & ON_CURVE_POINT != 0;
+                                (xx * x + xy * y + dx, yx * x + yy * y + dy, on_curve)
+                            })
+                            .collect(),
+                    );
+                    start = end;
+                }
+            }
+            Outline::Composite(_) if depth >= MAX_COMPONENT_DEPTH => {}
+            Outline::Composite(components) => {
+                for component in components {
+                    let (cxx, cyx, cxy, cyy) = match component.transformation[..] {
+                        [scale] => (f2dot14(scale), 0., 0., f2dot14(scale)),
+                        [x_scale, y_scale] => (f2dot14(x_scale), 0., 0., f2dot14(y_scale)),
+                        [a, b, c, d] => (f2dot14(a), f2dot14(b), f2dot14(c), f2dot14(d)),
+                        _ => (1., 0., 0., 1.),
+                    };
+                    let (cdx, cdy) = if component.flags & ARGS_ARE_XY_VALUES != 0 {
+                        component.arguments
+                    } else {
+                        (0., 0.)
+                    };
+                    let transform = [
+                        xx * cxx + xy * cyx,
+                        yx * cxx + yy * cyx,
+                        xx * cxy + xy * cyy,
+                        yx * cxy + yy * cyy,
+                        xx * cdx + xy * cdy + dx,
+                        yx * cdx + yy * cdy + dy,
+                    ];
+                    Self::add_contours(glyphs, component.glyph, transform, depth + 1, contours)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the glyph without hinting instructions.
+    /// An empty glyph and a glyph without points both have no data.
+    fn write(&self, bounds: Option<Bounds>, glyf: &mut Vec<u8>) {
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let contour_count = match self {
+            Outline::Empty => return,
+            Outline::Simple(simple) => simple.contour_ends.len() as i16,
+            Outline::Composite(_) => -1,
+        };
+        glyf.extend_from_slice(&contour_count.to_be_bytes());
+        for &value in &[bounds.min_x, bounds.min_y, bounds.max_x, bounds.max_y] {
+            glyf.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+        match self {
+            Outline::Empty => {}
+            Outline::Simple(simple) => {
+                for &end in &simple.contour_ends {
+                    glyf.extend_from_slice(&end.to_be_bytes());
+                }
+                // No instructions
+                glyf.extend_from_slice(&0_u16.to_be_bytes());
+                let mut flags = Vec::with_capacity(simple.points.len());
+                let mut xs = Vec::new();
+                let mut ys = Vec::new();
+                let mut previous = (0, 0);
+                for (&flag, &(x, y)) in simple.flags.iter().zip(&simple.points) {
+                    let point = (clamp_i16(x), clamp_i16(y));
+                    let x_flags = write_coordinate(
+                        point.0.wrapping_sub(previous.0),
+                        X_SHORT_VECTOR,
+                        X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR,
+                        &mut xs,
+                    );
+                    let y_flags = write_coordinate(
+                        point.1.wrapping_sub(previous.1),
+                        Y_SHORT_VECTOR,
+                        Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR,
+                        &mut ys,
+                    );
+                    flags.push(flag | x_flags | y_flags);
+                    previous = point;
+                }
+                glyf.extend(flags);
+                glyf.extend(xs);
+                glyf.extend(ys);
+            }
+            Outline::Composite(components) => {
+                for (i, component) in components.iter().enumerate() {
+                    let mut flags = (component.flags | ARG_1_AND_2_ARE_WORDS)
+                        & !(MORE_COMPONENTS | WE_HAVE_INSTRUCTIONS);
+                    if i + 1 < components.len() {
+                        flags |= MORE_COMPONENTS
+                    }
+                    glyf.extend_from_slice(&flags.to_be_bytes());
+                    glyf.extend_from_slice(&component.glyph.to_be_bytes());
+                    let (a, b) = component.arguments;
+                    if flags & ARGS_ARE_XY_VALUES != 0 {
+                        glyf.extend_from_slice(&clamp_i16(a).to_be_bytes());
+                        glyf.extend_from_slice(&clamp_i16(b).to_be_bytes());
+                    } else {
+                        glyf.extend_from_slice(&(a as u16).to_be_bytes());
+                        glyf.extend_from_slice(&(b as u16).to_be_bytes());
+                    }
+                    for value in &component.transformation {
+                        glyf.extend_from_slice(&value.to_be_bytes());
+                    }
+                }
+            }
+        }
+        glyf.resize((glyf.len() + 3) & !3, 0)
+    }
+}
+
+/// Write a coordinate relative to the previous point, and return its flags.
+fn write_coordinate(delta: i16, short: u8, same_or_positive: u8, data: &mut Vec<u8>) -> u8 {
+    if delta == 0 {
+        same_or_positive
+    } else if (-255..=255).contains(&delta) {
+        data.push(delta.unsigned_abs() as u8);
+        if delta > 0 {
+            short | same_or_positive
+        } else {
+            short
+        }
+    } else {
+        data.extend_from_slice(&delta.to_be_bytes());
+        0
+    }
+}
+
+impl SimpleOutline {
+    /// Infer the deltas of points that a variation with explicit point numbers leaves out,
+    /// from the nearest points before and after them in their contour.
+    ///
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/gvar#inferred-deltas-for-un-referenced-point-numbers
+    fn interpolate_untouched(&self, touched: &[bool], deltas: &mut [(f32, f32)]) {
+        let mut contour_start = 0;
+        for &end in &self.contour_ends {
+            let end = end as usize;
+            if end < contour_start || end >= self.points.len() {
+                break;
+            }
+            let next_in_contour = |i| if i == end { contour_start } else { i + 1 };
+            let touched_points: Vec<usize> =
+                (contour_start..=end).filter(|&i| touched[i]).collect();
+            if let (Some(&first), Some(&last)) = (touched_points.first(), touched_points.last()) {
+                // Consecutive touched points, then the last one with the first one.
+                // With a single touched point, the others all get its delta.
+                let pairs = touched_points
+                    .windows(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .chain(Some((last, first)));
+                for (previous, next) in pairs {
+                    let (previous_point, previous_delta) =
+                        (self.points[previous], deltas[previous]);
+                    let (next_point, next_delta) = (self.points[next], deltas[next]);
+                    let mut i = next_in_contour(previous);
+                    while i != next {
+                        deltas[i] = (
+                            interpolate(
+                                self.points[i].0,
+                                (previous_point.0, previous_delta.0),
+                                (next_point.0, next_delta.0),
+                            ),
+                            interpolate(
+                                self.points[i].1,
+                                (previous_point.1, previous_delta.1),
+                                (next_point.1, next_delta.1),
+                            ),
+                        );
+                        i = next_in_contour(i)
+                    }
+                }
+            }
+            contour_start = end + 1;
+        }
+    }
+}
+
+/// The delta of a coordinate between two reference points, given as coordinate and delta:
+/// interpolated between them, or the delta of the nearest one outside of them.
+fn interpolate(coordinate: f32, reference_1: (f32, f32), reference_2: (f32, f32)) -> f32 {
+    let (low, high) = if reference_1.0 <= reference_2.0 {
+        (reference_1, reference_2)
+    } else {
+        (reference_2, reference_1)
+    };
+    if low.0 == high.0 {
+        if low.1 == high.1 {
+            low.1
+        } else {
+            0.
+        }
+    } else if coordinate <= low.0 {
+        low.1
+    } else if coordinate >= high.0 {
+        high.1
+    } else {
+        low.1 + (high.1 - low.1) * (coordinate - low.0) / (high.0 - low.0)
+    }
+}
+
+impl Bounds {
+    fn union(self, other: Self) -> Self {
+        Bounds {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    fn round(self) -> Self {
+        Bounds {
+            min_x: self.min_x.floor(),
+            min_y: self.min_y.floor(),
+            max_x: self.max_x.ceil(),
+            max_y: self.max_y.ceil(),
+        }
+    }
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gvar
+struct GlyphVariations {
+    header: Position<GlyphVariationsHeader>,
+    axis_count: u16,
+    shared_tuples: Vec<Vec<f32>>,
+    long_offsets: bool,
+}
+
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+const COUNT_MASK: u16 = 0x0FFF;
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+const INTERMEDIATE_REGION: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+
+impl GlyphVariations {
+    fn parse(
+        bytes: &[u8],
+        table_directory: Slice<TableDirectoryEntry>,
+    ) -> Result<Option<Self>, FontError> {
+        let header = match table_directory.find_table::<GlyphVariationsHeader>(bytes) {
+            Ok(header) => header,
+            Err(FontError::MissingTable) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let axis_count = header.axis_count().read_from(bytes)?;
+        let mut shared_tuple: Position<i16> =
+            header.offset_bytes(header.shared_tuples_offset().read_from(bytes)?);
+        let mut shared_tuples = Vec::new();
+        for _ in 0..header.shared_tuple_count().read_from(bytes)? {
+            shared_tuples.push(read_tuple(bytes, shared_tuple, axis_count)?);
+            shared_tuple = shared_tuple.offset(axis_count);
+        }
+        Ok(Some(GlyphVariations {
+            header,
+            axis_count,
+            shared_tuples,
+            long_offsets: header.flags().read_from(bytes)? & 1 != 0,
+        }))
+    }
+
+    /// The deltas of each point of `outline` at `coordinates`, followed by four phantom points.
+    fn deltas(
+        &self,
+        bytes: &[u8],
+        glyph: u16,
+        coordinates: &[f32],
+        outline: &Outline,
+    ) -> Result<Vec<(f32, f32)>, FontError> {
+        let point_count = outline.point_count() + 4;
+        let mut deltas = vec![(0., 0.); point_count];
+
+        let (start, end) = if self.long_offsets {
+            let offset: Position<u32> = self.header.followed_by::<u32>().offset(glyph);
+            (
+                offset.read_from(bytes)?,
+                offset.offset(1_u16).read_from(bytes)?,
+            )
+        } else {
+            let offset: Position<u16> = self.header.followed_by::<u16>().offset(glyph);
+            (
+                2 * u32::from(offset.read_from(bytes)?),
+                2 * u32::from(offset.offset(1_u16).read_from(bytes)?),
+            )
+        };
+        if end <= start {
+            return Ok(deltas);
+        }
+        let array_offset = self
+            .header
+            .glyph_variation_data_array_offset()
+            .read_from(bytes)?;
+        let data: Position<GlyphVariationDataHeader> =
+            self.header.offset_bytes(array_offset + start);
+        let tuple_count = data.tuple_variation_count().read_from(bytes)?;
+        let data_offset = data.data_offset().read_from(bytes)?;
+        let serialized_length = (end - start)
+            .checked_sub(u32::from(data_offset))
+            .ok_or(FontError::OffsetPlusLengthBeyondEof)?;
+        let mut serialized = Stream {
+            bytes: Slice::<u8>::new(data.offset_bytes(data_offset), serialized_length)
+                .read_from(bytes)?,
+        };
+        let shared_points = if tuple_count & SHARED_POINT_NUMBERS != 0 {
+            packed_point_numbers(&mut serialized)?
+        } else {
+            None
+        };
+
+        let mut tuple: Position<TupleVariationHeader> = data.followed_by();
+        for _ in 0..tuple_count & COUNT_MASK {
+            let data_size = tuple.variation_data_size().read_from(bytes)?;
+            let tuple_index = tuple.tuple_index().read_from(bytes)?;
+            let mut next: Position<i16> = tuple.followed_by();
+            let peak = if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+                let peak = read_tuple(bytes, next, self.axis_count)?;
+                next = next.offset(self.axis_count);
+                peak
+            } else {
+                self.shared_tuples
+                    .get((tuple_index & TUPLE_INDEX_MASK) as usize)
+                    .ok_or(FontError::OffsetBeyondEof)?
+                    .clone()
+            };
+            let region = if tuple_index & INTERMEDIATE_REGION != 0 {
+                let start = read_tuple(bytes, next, self.axis_count)?;
+                next = next.offset(self.axis_count);
+                let end = read_tuple(bytes, next, self.axis_count)?;
+                next = next.offset(self.axis_count);
+                Some((start, end))
+            } else {
+                None
+            };
+            tuple = next.cast();
+            let mut tuple_data = serialized.split(data_size as usize)?;
+
+            let scalar = coordinates
+                .iter()
+                .zip(&peak)
+                .enumerate()
+                .map(|(i, (&coordinate, &peak))| {
+                    let (start, end) = match &region {
+                        Some((start, end)) => (start[i], end[i]),
+                        None => (peak.min(0.), peak.max(0.)),
+                    };
+                    axis_scalar(coordinate, start, peak, end)
+                })
+                .product::<f32>();
+            if scalar == 0. {
+                continue;
+            }
+
+            let points = if tuple_index & PRIVATE_POINT_NUMBERS != 0 {
+                packed_point_numbers(&mut tuple_data)?
+            } else {
+                shared_points.clone()
+            };
+            let count = points.as_ref().map_or(point_count, |numbers| numbers.len());
+            let x_deltas = packed_deltas(&mut tuple_data, count)?;
+            let y_deltas = packed_deltas(&mut tuple_data, count)?;
+            let numbers = match points {
+                Some(numbers) => numbers,
+                None => {
+                    for (delta, (dx, dy)) in
+                        deltas.iter_mut().zip(x_deltas.into_iter().zip(y_deltas))
+                    {
+                        delta.0 += scalar * dx;
+                        delta.1 += scalar * dy;
+                    }
+                    continue;
+                }
+            };
+            let mut tuple_deltas = vec![(0., 0.); point_count];
+            let mut touched = vec![false; point_count];
+            for ((number, dx), dy) in numbers.into_iter().zip(x_deltas).zip(y_deltas) {
+                if let Some(delta) = tuple_deltas.get_mut(number as usize) {
+                    *delta = (dx, dy);
+                    touched[number as usize] = true;
+                }
+            }
+            // Components of composite glyphs are not interpolated.
+            if let Outline::Simple(simple) = outline {
+                simple.interpolate_untouched(&touched, &mut tuple_deltas)
+            }
+            for (delta, (dx, dy)) in deltas.iter_mut().zip(tuple_deltas) {
+                delta.0 += scalar * dx;
+                delta.1 += scalar * dy;
+            }
+        }
+        Ok(deltas)
+    }
+}
+
+fn read_tuple(bytes: &[u8], start: Position<i16>, axis_count: u16) -> Result<Vec<f32>, FontError> {
+    Slice::new(start, axis_count)
+        .into_iter()
+        .map(|coordinate| coordinate.read_from(bytes).map(f2dot14))
+        .collect()
+}
+
+/// The factor of a variation along one axis, for a region from `start` to `end` through `peak`.
+///
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/otvaroverview#algorithm-for-interpolation-of-instance-values
+fn axis_scalar(coordinate: f32, start: f32, peak: f32, end: f32) -> f32 {
+    // Invalid regions do not restrict the variation, like axes without a peak
+    if peak == 0. || start > peak || peak > end || (start < 0. && end > 0.) || coordinate == peak {
+        1.
+    } else if coordinate <= start || coordinate >= end {
+        0.
+    } else if coordinate < peak {
+        (coordinate - start) / (peak - start)
+    } else {
+        (end - coordinate) / (end - peak)
+    }
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#packed-point-numbers
+///
+/// `None` stands for all points of the glyph.
+fn packed_point_numbers(stream: &mut Stream) -> Result<Option<Vec<u16>>, FontError> {
+    const POINTS_ARE_WORDS: u8 = 0x80;
+    const POINT_RUN_COUNT_MASK: u8 = 0x7F;
+    let first = stream.u8()?;
+    if first == 0 {
+        return Ok(None);
+    }
+    let count = if first & POINTS_ARE_WORDS != 0 {
+        usize::from(first & POINT_RUN_COUNT_MASK) << 8 | usize::from(stream.u8()?)
+    } else {
+        usize::from(first)
+    };
+    let mut numbers = Vec::with_capacity(count);
+    let mut number = 0_u16;
+    while numbers.len() < count {
+        let control = stream.u8()?;
+        for _ in 0..=control & POINT_RUN_COUNT_MASK {
+            let difference = if control & POINTS_ARE_WORDS != 0 {
+                stream.u16()?
+            } else {
+                u16::from(stream.u8()?)
+            };
+            number = number.wrapping_add(difference);
+            numbers.push(number)
+        }
+    }
+    numbers.truncate(count);
+    Ok(Some(numbers))
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#packed-deltas
+fn packed_deltas(stream: &mut Stream, count: usize) -> Result<Vec<f32>, FontError> {
+    const DELTAS_ARE_ZERO: u8 = 0x80;
+    const DELTAS_ARE_WORDS: u8 = 0x40;
+    const DELTA_RUN_COUNT_MASK: u8 = 0x3F;
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = stream.u8()?;
+        for _ in 0..=control & DELTA_RUN_COUNT_MASK {
+            deltas.push(if control & DELTAS_ARE_ZERO != 0 {
+                0.
+            } else if control & DELTAS_ARE_WORDS != 0 {
+                f32::from(stream.i16()?)
+            } else {
+                f32::from(stream.i8()?)
+            })
+        }
+    }
+    deltas.truncate(count);
+    Ok(deltas)
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/hvar
+struct AdvanceVariations {
+    /// The item variation store
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#item-variation-store
+    store: Position<()>,
+    /// From glyph IDs to outer and inner indices in the store, absent for the identity mapping
+    mapping: Option<Position<()>>,
+    /// For each region, the start, peak and end coordinates of each axis
+    regions: Vec<Vec<(f32, f32, f32)>>,
+}
+
+impl AdvanceVariations {
+    fn parse(
+        bytes: &[u8],
+        table_directory: Slice<TableDirectoryEntry>,
+    ) -> Result<Option<Self>, FontError> {
+        let header = match table_directory.find_table::<HorizontalMetricsVariationsHeader>(bytes) {
+            Ok(header) => header,
+            Err(FontError::MissingTable) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let store: Position<()> =
+            header.offset_bytes(header.item_variation_store_offset().read_from(bytes)?);
+        let mapping = match header.advance_width_mapping_offset().read_from(bytes)? {
+            0 => None,
+            offset => Some(header.offset_bytes(offset)),
+        };
+        // The region list offset is not aligned, so not part of a struct
+        let region_list: Position<VariationRegionListHeader> =
+            store.offset_bytes(store.offset_bytes::<u32, _>(2_u32).read_from(bytes)?);
+        let axis_count = region_list.axis_count().read_from(bytes)?;
+        let mut coordinates: Position<i16> = region_list.followed_by();
+        let mut regions = Vec::new();
+        for _ in 0..region_list.region_count().read_from(bytes)? {
+            let mut region = Vec::with_capacity(axis_count as usize);
+            for _ in 0..axis_count {
+                let tuple = read_tuple(bytes, coordinates, 3)?;
+                region.push((tuple[0], tuple[1], tuple[2]));
+                coordinates = coordinates.offset(3_u16);
+            }
+            regions.push(region)
+        }
+        Ok(Some(AdvanceVariations {
+            store,
+            mapping,
+            regions,
+        }))
+    }
+
+    fn delta(&self, bytes: &[u8], glyph: u16, coordinates: &[f32]) -> Result<f32, FontError> {
+        let (outer, inner) = match self.mapping {
+            Some(mapping) => delta_set_index(bytes, mapping, glyph)?,
+            None => (0, glyph),
+        };
+        let data_count = self.store.offset_bytes::<u16, _>(6_u32).read_from(bytes)?;
+        if outer >= data_count {
+            return Ok(0.);
+        }
+        let data_offset: Position<u32> = self.store.offset_bytes::<u32, _>(8_u32).offset(outer);
+        let data: Position<ItemVariationDataHeader> =
+            self.store.offset_bytes(data_offset.read_from(bytes)?);
+        let item_count = data.item_count().read_from(bytes)?;
+        if inner >= item_count {
+            return Ok(0.);
+        }
+        const LONG_WORDS: u16 = 0x8000;
+        const WORD_DELTA_COUNT_MASK: u16 = 0x7FFF;
+        let word_delta_count = data.word_delta_count().read_from(bytes)?;
+        let long_words = word_delta_count & LONG_WORDS != 0;
+        let word_count = u32::from(word_delta_count & WORD_DELTA_COUNT_MASK);
+        let region_indices = Slice::new(
+            data.followed_by::<u16>(),
+            data.region_index_count().read_from(bytes)?,
+        );
+        let (word_size, short_size) = if long_words { (4, 2) } else { (2, 1) };
+        let row_size =
+            word_count * word_size + region_indices.count().saturating_sub(word_count) * short_size;
+        let row: Position<u8> = region_indices
+            .get_unchecked(region_indices.count())
+            .offset_bytes(row_size * u32::from(inner));
+        let mut row = Stream {
+            bytes: Slice::new(row, row_size).read_from(bytes)?,
+        };
+        let mut total = 0.;
+        for (i, region_index) in region_indices.into_iter().enumerate() {
+            let delta = match (i < word_count as usize, long_words) {
+                (true, true) => row.u32()? as i32 as f32,
+                (true, false) | (false, true) => f32::from(row.i16()?),
+                (false, false) => f32::from(row.i8()?),
+            };
+            let region = match self.regions.get(region_index.read_from(bytes)? as usize) {
+                Some(region) => region,
+                None => continue,
+            };
+            let scalar = coordinates
+                .iter()
+                .zip(region)
+                .map(|(&coordinate, &(start, peak, end))| axis_scalar(coordinate, start, peak, end))
+                .product::<f32>();
+            total += scalar * delta
+        }
+        Ok(total)
+    }
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#associating-target-items-to-variation-data
+fn delta_set_index(
+    bytes: &[u8],
+    mapping: Position<()>,
+    glyph: u16,
+) -> Result<(u16, u16), FontError> {
+    const INNER_INDEX_BIT_COUNT_MASK: u8 = 0x0F;
+    const MAP_ENTRY_SIZE_MASK: u8 = 0x30;
+    let mut header = Stream {
+        bytes: Slice::new(mapping.cast(), 6_u32).read_from(bytes)?,
+    };
+    let format = header.u8()?;
+    let entry_format = header.u8()?;
+    let (count, entries): (u32, Position<u8>) = if format == 0 {
+        (u32::from(header.u16()?), mapping.offset_bytes(4_u32))
+    } else {
+        (header.u32()?, mapping.offset_bytes(6_u32))
+    };
+    if count == 0 {
+        return Ok((0, glyph));
+    }
+    // Glyphs after the last entry use the last entry
+    let index = cmp::min(u32::from(glyph), count - 1);
+    let entry_size = u32::from((entry_format & MAP_ENTRY_SIZE_MASK) >> 4) + 1;
+    let entry = Slice::<u8>::new(entries.offset_bytes(index * entry_size), entry_size)
+        .read_from(bytes)?
+        .iter()
+        .fold(0_u32, |entry, &byte| entry << 8 | u32::from(byte));
+    let inner_bits = (entry_format & INNER_INDEX_BIT_COUNT_MASK) + 1;
+    Ok((
+        (entry >> inner_bits) as u16,
+        (entry & ((1 << inner_bits) - 1)) as u16,
+    ))
+}
+
+/// Data with fields of variable size, such as outlines and packed deltas, read in order.
+struct Stream<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Stream<'a> {
+    /// The next `length` bytes, as their own stream
+    fn split(&mut self, length: usize) -> Result<Stream<'a>, FontError> {
+        if length > self.bytes.len() {
+            return Err(FontError::OffsetPlusLengthBeyondEof);
+        }
+        let (first, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(Stream { bytes: first })
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), FontError> {
+        self.split(length).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, FontError> {
+        Ok(self.split(1)?.bytes[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, FontError> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> Result<u16, FontError> {
+        Ok(u16::from(self.u8()?) << 8 | u16::from(self.u8()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, FontError> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, FontError> {
+        Ok(u32::from(self.u16()?) << 16 | u32::from(self.u16()?))
+    }
+}
+
+fn f2dot14(value: i16) -> f32 {
+    f32::from(value) / 16384.
+}
+
+/// Round a normalized coordinate to a multiple of 1/16384, like F2Dot14 numbers
+fn quantize(coordinate: f32) -> f32 {
+    (coordinate * 16384.).round() / 16384.
+}
+
+fn clamp_i16(value: f32) -> i16 {
+    value
+        .round()
+        .max(f32::from(i16::MIN))
+        .min(f32::from(i16::MAX)) as i16
+}
+
+fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_be_bytes())
+}
+
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/otff#organization-of-an-opentype-font
+fn write_font(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    fn checksum(data: &[u8]) -> u32 {
+        data.chunks(4).fold(0_u32, |sum, chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            sum.wrapping_add(u32::from_be_bytes(word))
+        })
+    }
+
+    tables.sort_by_key(|&(tag, _)| Tag(tag));
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x_0001_0000_u32.to_be_bytes());
+    for &value in &[
+        count,
+        search_range,
+        entry_selector,
+        count * 16 - search_range,
+    ] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head_offset = Some(offset)
+        }
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in &tables {
+        font.extend_from_slice(data);
+        font.resize((font.len() + 3) & !3, 0)
+    }
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0x_B1B0_AFBA_u32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes())
+    }
+    font
+}
+
+#[test]
+fn instance_stems() {
+    let stems = Font::parse(&include_bytes!("../../../tests/fonts/stems/Stems.ttf")[..]).unwrap();
+    let axes = VariationAxes::parse(&stems).unwrap();
+    assert!(axes.values(&[(*b"opsz", 12.), (*b"wght", 400.)]).is_none());
+    let instance = |variations: &[([u8; 4], f32)]| {
+        let values = axes.values(variations).unwrap();
+        instance(&stems, &axes, &values).unwrap()
+    };
+    // For a simple glyph its points, for a composite glyph the offsets of its components
+    let points = |font: &Font, glyph: u16| {
+        let bytes = font.bytes();
+        let table_directory = read_table_directory(bytes).unwrap();
+        let table = |tag: &[u8; 4]| {
+            let entry = table_directory
+                .binary_search_by_key(&Tag(*tag), |entry| entry.tag().read_from(bytes))
+                .unwrap()
+                .unwrap();
+            let start = entry.table_offset().read_from(bytes).unwrap() as usize;
+            &bytes[start..][..entry.length().read_from(bytes).unwrap() as usize]
+        };
+        let data = glyph_data(table(b"glyf"), table(b"loca"), true, glyph).unwrap();
+        match Outline::parse(data).unwrap() {
+            Outline::Empty => Vec::new(),
+            Outline::Simple(simple) => simple.points,
+            Outline::Composite(components) => components.iter().map(|c| c.arguments).collect(),
+        }
+    };
+    let advance = |font: &Font, glyph: u16| {
+        font.glyph_width(crate::fonts::GlyphId(glyph))
+            .unwrap()
+            .get()
+    };
+    const I: u16 = 2;
+    const H: u16 = 3;
+
+    let bold = instance(&[(*b"wght", 900.)]);
+    assert_eq!(bold.postscript_name, "Stems-Regular_900wght");
+    assert!(bold.is_bold());
+    // Two points moved with explicit point numbers, the two others interpolated
+    assert_eq!(
+        points(&bold, I),
+        [(100., 0.), (400., 0.), (400., 700.), (100., 700.)]
+    );
+    assert_eq!(points(&bold, H), [(0., 0.), (500., 0.)]);
+    assert_eq!((advance(&bold, I), advance(&bold, H)), (0.5, 1.));
+    assert_eq!((bold.min_x.get(), bold.max_x.get()), (50, 900));
+
+    // Clamped to the range of the axis
+    let clamped = instance(&[(*b"wght", 1000.)]);
+    assert_eq!(clamped.postscript_name, "Stems-Regular_900wght");
+
+    // `avar` maps 650 to 0.75 instead of 0.5
+    let semibold = instance(&[(*b"wght", 650.)]);
+    assert_eq!(
+        points(&semibold, I),
+        [(100., 0.), (350., 0.), (350., 700.), (100., 700.)]
+    );
+    assert_eq!(points(&semibold, H), [(0., 0.), (450., 0.)]);
+    assert_eq!((advance(&semibold, I), advance(&semibold, H)), (0.45, 0.9));
+
+    let light = instance(&[(*b"wght", 100.)]);
+    assert!(!light.is_bold());
+    assert_eq!(
+        points(&light, I),
+        [(100., 0.), (150., 0.), (150., 700.), (100., 700.)]
+    );
+    assert_eq!(advance(&light, I), 0.25);
+
+    let wide_bold = instance(&[(*b"wdth", 200.), (*b"wght", 900.)]);
+    assert_eq!(wide_bold.postscript_name, "Stems-Regular_900wght_200wdth");
+    assert_eq!(
+        points(&wide_bold, I),
+        [(150., 0.), (500., 0.), (500., 700.), (150., 700.)]
+    );
+    assert_eq!(
+        (advance(&wide_bold, 1), advance(&wide_bold, I)),
+        (0.375, 0.65)
+    );
+}
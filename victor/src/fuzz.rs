@@ -0,0 +1,25 @@
+//! Entry points for fuzzing, see the `fuzz` directory next to this crate.
+//!
+//! These take arbitrary bytes and should never panic.
+
+use crate::dom::Document;
+use crate::fonts::FontContext;
+use crate::primitives::Size;
+use crate::style::StyleSetBuilder;
+
+pub fn fuzz_parse_css(bytes: &[u8]) {
+    let mut builder = StyleSetBuilder::new();
+    builder.add_stylesheet(&String::from_utf8_lossy(bytes));
+    builder.finish();
+}
+
+pub fn fuzz_parse_html(bytes: &[u8]) {
+    Document::parse_html(bytes);
+}
+
+/// Parse as HTML, including any `<style>` element, then lay out the result.
+pub fn fuzz_layout(bytes: &[u8]) {
+    let document = Document::parse_html(bytes);
+    let author_styles = document.parse_stylesheets();
+    let _ = document.layout(Size::new(600., 800.), &author_styles, &FontContext::new());
+}
@@ -284,25 +284,31 @@ impl<T> flow_relative::Rect<T> {
     pub fn to_physical(
         &self,
         mode: (WritingMode, Direction),
-        // Will be needed for other writing modes
         // FIXME: what if the containing block has a different mode?
         // https://drafts.csswg.org/css-writing-modes/#orthogonal-flows
-        _containing_block: &physical::Rect<T>,
+        containing_block: &physical::Rect<T>,
     ) -> physical::Rect<T>
     where
-        T: Clone,
+        T: Add<Output = T> + Sub<Output = T> + Copy,
     {
+        use Direction::*;
+        use WritingMode::*;
+
+        // https://drafts.csswg.org/css-writing-modes/#logical-to-physical
+        let cb = &containing_block.size;
+        let start = &self.start_corner;
+        let end = &self.start_corner + &self.size;
         // Top-left corner
-        let (tl_x, tl_y) = if let (WritingMode::HorizontalTb, Direction::Ltr) = mode {
-            (&self.start_corner.inline, &self.start_corner.block)
-        } else {
-            unimplemented!()
+        let (tl_x, tl_y) = match mode {
+            (HorizontalTb, Ltr) => (start.inline, start.block),
+            (HorizontalTb, Rtl) => (cb.x - end.inline, start.block),
+            (VerticalRl, Ltr) | (SidewaysRl, Ltr) => (cb.x - end.block, start.inline),
+            (VerticalRl, Rtl) | (SidewaysRl, Rtl) => (cb.x - end.block, cb.y - end.inline),
+            (VerticalLr, Ltr) | (SidewaysLr, Rtl) => (start.block, start.inline),
+            (VerticalLr, Rtl) | (SidewaysLr, Ltr) => (start.block, cb.y - end.inline),
         };
         physical::Rect {
-            top_left: physical::Vec2 {
-                x: tl_x.clone(),
-                y: tl_y.clone(),
-            },
+            top_left: physical::Vec2 { x: tl_x, y: tl_y },
             size: self.size.size_to_physical(mode),
         }
     }
@@ -320,11 +326,26 @@ impl<T> physical::Rect<T> {
     }
 }
 
+impl From<crate::primitives::Rect<crate::primitives::CssPx>> for physical::Rect<Length> {
+    fn from(r: crate::primitives::Rect<crate::primitives::CssPx>) -> Self {
+        physical::Rect {
+            top_left: physical::Vec2 {
+                x: Length::from_px(r.origin.x),
+                y: Length::from_px(r.origin.y),
+            },
+            size: physical::Vec2 {
+                x: Length::from_px(r.size.width),
+                y: Length::from_px(r.size.height),
+            },
+        }
+    }
+}
+
 impl From<physical::Rect<Length>> for crate::primitives::Rect<crate::primitives::CssPx> {
     fn from(r: physical::Rect<Length>) -> Self {
         crate::primitives::Rect {
-            origin: crate::primitives::Point::new(r.top_left.x.px, r.top_left.y.px),
-            size: crate::primitives::Size::new(r.size.x.px, r.size.y.px),
+            origin: crate::primitives::Point::new(r.top_left.x.px(), r.top_left.y.px()),
+            size: crate::primitives::Size::new(r.size.x.px(), r.size.y.px()),
         }
     }
 }
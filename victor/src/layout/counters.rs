@@ -0,0 +1,219 @@
+//! https://drafts.csswg.org/css-lists-3/#auto-numbering
+//!
+//! Box construction is parallel, but the value of a counter depends on every element before it
+//! in tree order. So counters are computed first, in one sequential traversal of the document,
+//! which also computes element styles for construction to reuse.
+
+use super::*;
+use crate::dom::{Document, NodeId};
+use crate::style::StyleSet;
+use std::collections::HashMap;
+
+/// The counters in scope for each element, from the innermost counter-creating element.
+#[derive(Default)]
+pub(super) struct Counters {
+    by_element: HashMap<NodeId, ElementCounters>,
+}
+
+struct ElementCounters {
+    /// After the element’s own `counter-*` properties,
+    /// for its `::marker` and `::before` pseudo-elements
+    element: CounterStack,
+    /// After its children, for its `::after` pseudo-element
+    after_children: CounterStack,
+}
+
+/// Outermost first. Shared between elements until one changes a counter.
+type CounterStack = Arc<Vec<Counter>>;
+
+#[derive(Clone)]
+struct Counter {
+    name: String,
+    value: i32,
+    /// The parent of the element that created the counter:
+    /// the counter is in scope for the rest of its children and their descendants.
+    scope: NodeId,
+}
+
+impl Counters {
+    /// The value of the innermost counter with this name, or zero if there is none.
+    pub fn value(&self, element: NodeId, which: WhichPseudoElement, name: &str) -> i32 {
+        self.stack(element, which)
+            .iter()
+            .rev()
+            .find(|counter| counter.name == name)
+            .map_or(0, |counter| counter.value)
+    }
+
+    /// The values of all counters with this name, outermost first, or zero if there are none.
+    pub fn values(&self, element: NodeId, which: WhichPseudoElement, name: &str) -> Vec<i32> {
+        let values: Vec<_> = self
+            .stack(element, which)
+            .iter()
+            .filter(|counter| counter.name == name)
+            .map(|counter| counter.value)
+            .collect();
+        if values.is_empty() {
+            vec![0]
+        } else {
+            values
+        }
+    }
+
+    fn stack(&self, element: NodeId, which: WhichPseudoElement) -> &[Counter] {
+        match self.by_element.get(&element) {
+            Some(counters) => match which {
+                WhichPseudoElement::Before | WhichPseudoElement::Marker => &counters.element,
+                WhichPseudoElement::After => &counters.after_children,
+            },
+            None => &[],
+        }
+    }
+}
+
+/// How deeply the traversal recurses. Elements nested deeper keep the counters of
+/// their ancestor at this depth, and their styles are computed during box construction.
+const MAX_DEPTH: usize = 512;
+
+/// Compute the style of every element that generates boxes, and the counters they see.
+///
+/// FIXME: `counter-*` properties of `::before` and `::after` pseudo-elements are ignored,
+/// and so are `reversed` lists.
+pub(super) fn compute_styles_and_counters(
+    document: &Document,
+    author_styles: &StyleSet,
+) -> (HashMap<NodeId, Arc<ComputedValues>>, Counters) {
+    let mut traversal = Traversal {
+        document,
+        author_styles,
+        styles: HashMap::new(),
+        counters: Counters::default(),
+        stack: Arc::new(Vec::new()),
+        containment_boundary: 0,
+        depth: 0,
+    };
+    traversal.element(document.root_element(), None);
+    (traversal.styles, traversal.counters)
+}
+
+struct Traversal<'a> {
+    document: &'a Document,
+    author_styles: &'a StyleSet,
+    styles: HashMap<NodeId, Arc<ComputedValues>>,
+    counters: Counters,
+    stack: CounterStack,
+    /// Counters before this index are outside of the innermost element with `contain: style`:
+    /// https://drafts.csswg.org/css-contain-2/#containment-style
+    containment_boundary: usize,
+    /// How many elements, from the root element, contain the children being traversed
+    depth: usize,
+}
+
+impl Traversal<'_> {
+    fn element(&mut self, element: NodeId, parent_style: Option<&ComputedValues>) {
+        if self.document.is_cancelled() {
+            return;
+        }
+        let style = style_for_element(self.author_styles, self.document, element, parent_style);
+        self.styles.insert(element, style.clone());
+        if style.box_.display == Display::None {
+            return;
+        }
+        let scope = self.document[element]
+            .parent
+            .expect("an element has a parent");
+        self.apply_changes(&style, scope);
+        let element_stack = self.stack.clone();
+
+        let outer_boundary = self.containment_boundary;
+        if style.box_.contain.style {
+            self.containment_boundary = self.stack.len()
+        }
+        self.depth += 1;
+        let mut next = self.document[element].first_child;
+        while let Some(child) = next {
+            if self.document[child].as_element().is_some() && self.depth < MAX_DEPTH {
+                self.element(child, Some(&style))
+            }
+            next = self.document[child].next_sibling
+        }
+        self.depth -= 1;
+        self.containment_boundary = outer_boundary;
+
+        if !(element_stack.is_empty() && self.stack.is_empty()) {
+            self.counters.by_element.insert(
+                element,
+                ElementCounters {
+                    element: element_stack,
+                    after_children: self.stack.clone(),
+                },
+            );
+        }
+        // Counters created by children go out of scope
+        if self.stack.iter().any(|counter| counter.scope == element) {
+            Arc::make_mut(&mut self.stack).retain(|counter| counter.scope != element)
+        }
+    }
+
+    /// https://drafts.csswg.org/css-lists-3/#counters-without-boxes
+    ///
+    /// `counter-reset`, then `counter-increment`, then `counter-set`.
+    fn apply_changes(&mut self, style: &ComputedValues, scope: NodeId) {
+        let generated = &style.generated;
+        for (name, value) in generated.counter_reset.0.iter() {
+            let value = value.unwrap_or(0);
+            let stack = Arc::make_mut(&mut self.stack);
+            // A counter created by a previous sibling is replaced
+            match stack.iter_mut().rev().find(|counter| counter.name == *name) {
+                Some(counter) if counter.scope == scope => counter.value = value,
+                _ => stack.push(Counter {
+                    name: name.clone(),
+                    value,
+                    scope,
+                }),
+            }
+        }
+        for (name, value) in generated.counter_increment.0.iter() {
+            let by = value.unwrap_or(1);
+            let counter = self.innermost(name, scope);
+            counter.value = counter.value.saturating_add(by)
+        }
+        // https://drafts.csswg.org/css-lists-3/#list-item-counter
+        let increments_list_item = generated
+            .counter_increment
+            .0
+            .iter()
+            .any(|(name, _)| name == LIST_ITEM);
+        if style.box_.display.is_list_item() && !increments_list_item {
+            let counter = self.innermost(LIST_ITEM, scope);
+            counter.value = counter.value.saturating_add(1)
+        }
+        for (name, value) in generated.counter_set.0.iter() {
+            self.innermost(name, scope).value = value.unwrap_or(0)
+        }
+    }
+
+    /// The innermost counter with this name that an element can change,
+    /// after creating it with a value of zero if there is none.
+    fn innermost(&mut self, name: &str, scope: NodeId) -> &mut Counter {
+        let stack = Arc::make_mut(&mut self.stack);
+        let index = match stack[self.containment_boundary..]
+            .iter()
+            .rposition(|counter| counter.name == name)
+        {
+            Some(index) => self.containment_boundary + index,
+            None => {
+                stack.push(Counter {
+                    name: name.to_owned(),
+                    value: 0,
+                    scope,
+                });
+                stack.len() - 1
+            }
+        };
+        &mut stack[index]
+    }
+}
+
+/// The counter that numbers list items
+pub(super) const LIST_ITEM: &str = "list-item";
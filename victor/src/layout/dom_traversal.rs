@@ -1,15 +1,26 @@
 use super::*;
 use crate::dom::{Document, NodeData, NodeId};
-use crate::style::StyleSet;
-use atomic_refcell::AtomicRefMut;
+use crate::style::{style_for_pseudo_element, PseudoElement, StyleSet};
+use atomic_refcell::{AtomicRefCell, AtomicRefMut};
+use html5ever::LocalName;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub(super) struct Context<'a> {
     pub document: &'a Document,
     pub author_styles: &'a StyleSet,
+    /// Element styles computed with counters, see `compute_styles_and_counters`
+    pub styles: HashMap<NodeId, Arc<ComputedValues>>,
+    pub counters: Counters,
+    /// Boxes constructed so far, see `Limits::max_boxes`
+    pub box_count: AtomicUsize,
+    /// See `Limits::max_boxes`
+    pub max_boxes: Option<usize>,
 }
 
 #[derive(Copy, Clone)]
 pub(super) enum WhichPseudoElement {
+    Marker,
     Before,
     After,
 }
@@ -22,7 +33,7 @@ pub(super) enum Contents {
     /// <https://drafts.csswg.org/css2/conform.html#replaced-element>
     Replaced(ReplacedContent),
 
-    /// Content of a `::before` or `::after` pseudo-element this is being generated.
+    /// Content of a `::before`, `::after` or `::marker` pseudo-element this is being generated.
     /// <https://drafts.csswg.org/css2/generate.html#content>
     OfPseudoElement(Vec<PseudoElementContentItem>),
 }
@@ -35,19 +46,37 @@ pub(super) enum NonReplacedContents {
 pub(super) enum PseudoElementContentItem {
     Text(String),
     Replaced(ReplacedContent),
+    /// From `leader()`, a pattern string repeated to fill the line, see `Leader`
+    Leader(String),
 }
 
 pub(super) trait TraversalHandler<'dom> {
     fn handle_text(&mut self, text: &str, parent_style: &Arc<ComputedValues>);
 
-    /// Or pseudo-element
+    /// For `<br>` elements
+    fn handle_forced_line_break(&mut self, style: &Arc<ComputedValues>);
+
+    /// For `leader()` in generated content
+    fn handle_leader(&mut self, pattern: &str, parent_style: &Arc<ComputedValues>);
+
+    /// Or pseudo-element, with no `tag`
     fn handle_element(
         &mut self,
+        tag: Option<NodeId>,
         style: &Arc<ComputedValues>,
         display: DisplayGeneratingBox,
         contents: Contents,
         box_slot: BoxSlot<'dom>,
     );
+
+    /// The `::marker` of a list item with `list-style-position: outside`,
+    /// which is outside of the list item’s first line box.
+    fn handle_outside_marker(
+        &mut self,
+        style: &Arc<ComputedValues>,
+        contents: Vec<PseudoElementContentItem>,
+        box_slot: BoxSlot<'dom>,
+    );
 }
 
 fn traverse_children_of<'dom>(
@@ -56,6 +85,9 @@ fn traverse_children_of<'dom>(
     context: &'dom Context,
     handler: &mut impl TraversalHandler<'dom>,
 ) {
+    if parent_element_style.box_.display.is_list_item() {
+        traverse_marker(parent_element, parent_element_style, context, handler)
+    }
     traverse_pseudo_element(
         WhichPseudoElement::Before,
         parent_element,
@@ -64,13 +96,46 @@ fn traverse_children_of<'dom>(
         handler,
     );
 
+    // https://html.spec.whatwg.org/multipage/rendering.html#the-details-and-summary-elements
+    // The first `summary` child is rendered first,
+    // and other children only if the `details` element is open.
+    let mut summary = None;
+    let mut closed_details = false;
+    if is_html_element(parent_element, local_name!("details"), context) {
+        summary = context.document[parent_element]
+            .first_child
+            .and_then(|first| {
+                context
+                    .document
+                    .node_and_following_siblings(first)
+                    .find(|&child| is_html_element(child, local_name!("summary"), context))
+            });
+        if let Some(summary) = summary {
+            traverse_element(summary, parent_element_style, context, handler)
+        }
+        closed_details = context.document[parent_element]
+            .as_element()
+            .unwrap()
+            .get_attr(&local_name!("open"))
+            .is_none();
+        // FIXME: without a `summary` child, browsers render a “Details” label instead
+    }
+
+    // Construct no more boxes when cancelled or over the limit,
+    // like for descendants of a closed `details`.
+    let cancelled = context.document.is_cancelled() || context.too_many_boxes();
     let mut next = context.document[parent_element].first_child;
     while let Some(child) = next {
         match &context.document[child].data {
+            _ if Some(child) == summary => {}
+            NodeData::Element(_) if closed_details || cancelled => {
+                context.unset_boxes_in_subtree(child)
+            }
             NodeData::Document
             | NodeData::Doctype { .. }
             | NodeData::Comment { .. }
             | NodeData::ProcessingInstruction { .. } => {}
+            NodeData::Text { .. } if closed_details || cancelled => {}
             NodeData::Text { contents } => {
                 handler.handle_text(contents, parent_element_style);
             }
@@ -94,12 +159,7 @@ fn traverse_element<'dom>(
     context: &'dom Context,
     handler: &mut impl TraversalHandler<'dom>,
 ) {
-    let style = style_for_element(
-        context.author_styles,
-        context.document,
-        element_id,
-        Some(parent_element_style),
-    );
+    let style = context.element_style(element_id, parent_element_style);
     match style.box_.display {
         Display::None => context.unset_boxes_in_subtree(element_id),
         Display::Contents => {
@@ -108,22 +168,67 @@ fn traverse_element<'dom>(
                 // <https://drafts.csswg.org/css-display-3/#valdef-display-contents>
                 context.unset_boxes_in_subtree(element_id)
             } else {
-                context.layout_data_mut(element_id).self_box = Some(LayoutBox::DisplayContents);
+                *context.layout_data(element_id).self_box.borrow_mut() =
+                    Some(LayoutBox::DisplayContents);
                 traverse_children_of(element_id, &style, context, handler)
             }
         }
-        Display::GeneratingBox(display) => handler.handle_element(
-            &style,
-            display,
-            match ReplacedContent::for_element(element_id, context) {
+        // https://html.spec.whatwg.org/multipage/rendering.html#phrasing-content-3
+        // `br { display-outside: newline; }`
+        // `wbr { display-outside: break-opportunity; }`
+        // FIXME: these are not real `display` values, so a `<br>` or `<wbr>` element
+        // with any `display` other than `none` or `contents` is treated this way.
+        Display::GeneratingBox(_) if is_html_element(element_id, local_name!("br"), context) => {
+            context.unset_boxes_in_subtree(element_id);
+            handler.handle_forced_line_break(&style)
+        }
+        Display::GeneratingBox(_) if is_html_element(element_id, local_name!("wbr"), context) => {
+            context.unset_boxes_in_subtree(element_id);
+            handler.handle_text(ZERO_WIDTH_SPACE.encode_utf8(&mut [0; 3]), &style)
+        }
+        Display::GeneratingBox(display) => {
+            let contents = match ReplacedContent::for_element(element_id, context) {
                 Some(replaced) => Contents::Replaced(replaced),
+                None if skips_contents(&style, display) => {
+                    context.unset_boxes_in_subtree(element_id);
+                    Contents::OfPseudoElement(Vec::new())
+                }
                 None => Contents::OfElement(element_id),
-            },
-            context.element_box_slot(element_id),
-        ),
+            };
+            handler.handle_element(
+                Some(element_id),
+                &style,
+                display,
+                contents,
+                context.element_box_slot(element_id),
+            )
+        }
+    }
+}
+
+/// A break opportunity that takes no space, like `<wbr>`
+pub(super) const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+pub(super) fn is_html_element(node: NodeId, local_name: LocalName, context: &Context) -> bool {
+    match context.document[node].as_element() {
+        Some(element) => element.name.ns == ns!(html) && element.name.local == local_name,
+        None => false,
     }
 }
 
+/// https://drafts.csswg.org/css-contain-2/#valdef-content-visibility-hidden
+/// “The element skips its contents.”
+///
+/// This only applies to boxes where size containment can apply,
+/// which excludes non-atomic inline-level boxes.
+fn skips_contents(style: &ComputedValues, display: DisplayGeneratingBox) -> bool {
+    let block_level = match display {
+        DisplayGeneratingBox::OutsideInside { outside, .. } => outside == DisplayOutside::Block,
+        DisplayGeneratingBox::LayoutInternal(_) => false,
+    };
+    style.box_.content_visibility == ContentVisibility::Hidden && block_level
+}
+
 fn traverse_pseudo_element<'dom>(
     which: WhichPseudoElement,
     element: NodeId,
@@ -136,19 +241,72 @@ fn traverse_pseudo_element<'dom>(
             Display::None => context.unset_pseudo_element_box(element, which),
             Display::Contents => {
                 context.unset_pseudo_element_box(element, which);
-                let items = generate_pseudo_element_content(&style, element, context);
+                let items = generate_pseudo_element_content(&style, element, which, context);
                 traverse_pseudo_element_contents(&style, items, handler);
             }
             Display::GeneratingBox(display) => {
-                let items = generate_pseudo_element_content(&style, element, context);
+                let items = generate_pseudo_element_content(&style, element, which, context);
                 let contents = Contents::OfPseudoElement(items);
                 let box_slot = context.pseudo_element_box_slot(element, which);
-                handler.handle_element(&style, display, contents, box_slot);
+                handler.handle_element(None, &style, display, contents, box_slot);
             }
         }
     }
 }
 
+/// https://drafts.csswg.org/css-lists-3/#marker-pseudo
+///
+/// FIXME: `list-style-image`
+fn traverse_marker<'dom>(
+    element: NodeId,
+    element_style: &ComputedValues,
+    context: &'dom Context,
+    handler: &mut impl TraversalHandler<'dom>,
+) {
+    let which = WhichPseudoElement::Marker;
+    let style = style_for_pseudo_element(
+        context.author_styles,
+        context.document,
+        element,
+        &PseudoElement::Marker,
+        element_style,
+    );
+    // https://drafts.csswg.org/css-lists-3/#content-property
+    let items = match &style.generated.content {
+        Content::None => None,
+        Content::Items(_) => Some(generate_pseudo_element_content(
+            &style, element, which, context,
+        )),
+        Content::Normal => match &style.list.list_style_type {
+            ListStyleType::None => None,
+            ListStyleType::String(string) => Some(string.clone()),
+            ListStyleType::Name(counter_style) => {
+                let value = context.counters.value(element, which, LIST_ITEM);
+                Some(context.author_styles.marker_text(counter_style, value))
+            }
+        }
+        .map(|text| vec![PseudoElementContentItem::Text(text)]),
+    };
+    let items = match items {
+        Some(items) => items,
+        None => return context.unset_pseudo_element_box(element, which),
+    };
+    let box_slot = context.pseudo_element_box_slot(element, which);
+    match style.list.list_style_position {
+        ListStylePosition::Inside => {
+            // `display` does not apply to markers, they are inline boxes
+            let display_inline = DisplayGeneratingBox::OutsideInside {
+                outside: DisplayOutside::Inline,
+                inside: DisplayInside::Flow,
+                list_item: false,
+            };
+            let contents = Contents::OfPseudoElement(items);
+            handler.handle_element(None, &style, display_inline, contents, box_slot)
+        }
+        ListStylePosition::Outside => handler.handle_outside_marker(&style, items, box_slot),
+    }
+}
+
 fn traverse_pseudo_element_contents<'dom>(
     pseudo_element_style: &Arc<ComputedValues>,
     items: Vec<PseudoElementContentItem>,
@@ -160,6 +318,9 @@ fn traverse_pseudo_element_contents<'dom>(
             PseudoElementContentItem::Text(text) => {
                 handler.handle_text(&text, pseudo_element_style)
             }
+            PseudoElementContentItem::Leader(pattern) => {
+                handler.handle_leader(&pattern, pseudo_element_style)
+            }
             PseudoElementContentItem::Replaced(contents) => {
                 let item_style = anonymous_style.get_or_insert_with(|| {
                     ComputedValues::anonymous_inheriting_from(Some(pseudo_element_style))
@@ -167,10 +328,12 @@ fn traverse_pseudo_element_contents<'dom>(
                 let display_inline = DisplayGeneratingBox::OutsideInside {
                     outside: DisplayOutside::Inline,
                     inside: DisplayInside::Flow,
+                    list_item: false,
                 };
                 // `display` is not inherited, so we get the initial value
                 debug_assert!(item_style.box_.display == Display::GeneratingBox(display_inline));
                 handler.handle_element(
+                    None,
                     item_style,
                     display_inline,
                     Contents::Replaced(contents),
@@ -222,24 +385,82 @@ impl NonReplacedContents {
 }
 
 fn pseudo_element_style(
-    _which: WhichPseudoElement,
-    _element: NodeId,
-    _element_style: &ComputedValues,
-    _context: &Context,
+    which: WhichPseudoElement,
+    element: NodeId,
+    element_style: &ComputedValues,
+    context: &Context,
 ) -> Option<Arc<ComputedValues>> {
-    // FIXME: run the cascade, then return None for `content: normal` or `content: none`
+    let pseudo_element = match which {
+        WhichPseudoElement::Marker => PseudoElement::Marker,
+        WhichPseudoElement::Before => PseudoElement::Before,
+        WhichPseudoElement::After => PseudoElement::After,
+    };
+    let style = style_for_pseudo_element(
+        context.author_styles,
+        context.document,
+        element,
+        &pseudo_element,
+        element_style,
+    );
     // https://drafts.csswg.org/css2/generate.html#content
-    None
+    match style.generated.content {
+        Content::Normal | Content::None => None,
+        Content::Items(_) => Some(style),
+    }
 }
 
 fn generate_pseudo_element_content(
-    _pseudo_element_style: &ComputedValues,
-    _element: NodeId,
-    _context: &Context,
+    pseudo_element_style: &ComputedValues,
+    element: NodeId,
+    which: WhichPseudoElement,
+    context: &Context,
 ) -> Vec<PseudoElementContentItem> {
-    let _ = PseudoElementContentItem::Text;
+    let items = match &pseudo_element_style.generated.content {
+        Content::Items(items) => items,
+        Content::Normal | Content::None => unreachable!("checked by callers"),
+    };
+    let element_id = element;
+    let element = context.document[element].as_element().unwrap();
+    let mut contents = Vec::new();
+    let mut text = String::new();
+    for item in items.iter() {
+        match item {
+            ContentItem::String(string) => text.push_str(string),
+            ContentItem::Attr { name, fallback } => {
+                // A missing attribute is the empty string, unless there is a fallback
+                let value = element.get_attr(name).or(fallback.as_deref());
+                text.push_str(value.unwrap_or(""))
+            }
+            ContentItem::Counter { name, style } => {
+                if let Some(style) = style {
+                    let value = context.counters.value(element_id, which, name);
+                    text.push_str(&context.author_styles.counter_representation(style, value))
+                }
+            }
+            ContentItem::Counters {
+                name,
+                separator,
+                style,
+            } => {
+                if let Some(style) = style {
+                    let values = context.counters.values(element_id, which, name);
+                    let representations: Vec<_> = values
+                        .into_iter()
+                        .map(|value| context.author_styles.counter_representation(style, value))
+                        .collect();
+                    text.push_str(&representations.join(separator))
+                }
+            }
+            ContentItem::Leader(pattern) => {
+                contents.push(PseudoElementContentItem::Text(std::mem::take(&mut text)));
+                contents.push(PseudoElementContentItem::Leader(pattern.clone()))
+            }
+        }
+    }
+    // FIXME: `PseudoElementContentItem::Replaced` for `url()` images
     let _ = PseudoElementContentItem::Replaced;
-    unimplemented!()
+    contents.push(PseudoElementContentItem::Text(text));
+    contents
 }
 
 pub(super) struct BoxSlot<'dom> {
@@ -272,41 +493,59 @@ impl Drop for BoxSlot<'_> {
 }
 
 impl Context<'_> {
-    fn layout_data_mut(&self, element_id: NodeId) -> AtomicRefMut<LayoutDataForElement> {
-        self.document[element_id]
-            .as_element()
-            .unwrap()
-            .layout_data
-            .borrow_mut()
+    pub fn count_box(&self) {
+        self.box_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn too_many_boxes(&self) -> bool {
+        self.max_boxes
+            .is_some_and(|max| self.box_count.load(Ordering::Relaxed) > max)
+    }
+
+    /// The style of an element, computed again if `compute_styles_and_counters` skipped it
+    pub fn element_style(
+        &self,
+        element_id: NodeId,
+        parent_element_style: &ComputedValues,
+    ) -> Arc<ComputedValues> {
+        match self.styles.get(&element_id) {
+            Some(style) => style.clone(),
+            None => style_for_element(
+                self.author_styles,
+                self.document,
+                element_id,
+                Some(parent_element_style),
+            ),
+        }
+    }
+
+    fn layout_data(&self, element_id: NodeId) -> &LayoutDataForElement {
+        &self.document[element_id].as_element().unwrap().layout_data
     }
 
     fn element_box_slot(&self, element_id: NodeId) -> BoxSlot {
-        BoxSlot::new(AtomicRefMut::map(
-            self.layout_data_mut(element_id),
-            |data| &mut data.self_box,
-        ))
+        BoxSlot::new(self.layout_data(element_id).self_box.borrow_mut())
+    }
+
+    fn pseudo_element_box(
+        &self,
+        element_id: NodeId,
+        which: WhichPseudoElement,
+    ) -> &AtomicRefCell<Option<LayoutBox>> {
+        let data = self.layout_data(element_id);
+        match which {
+            WhichPseudoElement::Marker => &data.marker_box,
+            WhichPseudoElement::Before => &data.before_box,
+            WhichPseudoElement::After => &data.after_box,
+        }
     }
 
     fn pseudo_element_box_slot(&self, element_id: NodeId, which: WhichPseudoElement) -> BoxSlot {
-        BoxSlot::new(AtomicRefMut::map(
-            self.layout_data_mut(element_id),
-            |data| {
-                let pseudos = data.pseudo_elements.get_or_insert_with(Default::default);
-                match which {
-                    WhichPseudoElement::Before => &mut pseudos.before,
-                    WhichPseudoElement::After => &mut pseudos.after,
-                }
-            },
-        ))
+        BoxSlot::new(self.pseudo_element_box(element_id, which).borrow_mut())
     }
 
     fn unset_pseudo_element_box(&self, element_id: NodeId, which: WhichPseudoElement) {
-        if let Some(pseudos) = &mut self.layout_data_mut(element_id).pseudo_elements {
-            match which {
-                WhichPseudoElement::Before => pseudos.before = None,
-                WhichPseudoElement::After => pseudos.after = None,
-            }
-        }
+        *self.pseudo_element_box(element_id, which).borrow_mut() = None
     }
 
     fn unset_boxes_in_subtree(&self, base_element: NodeId) {
@@ -314,9 +553,11 @@ impl Context<'_> {
         loop {
             let node = &self.document[node_id];
             if let Some(element_data) = node.as_element() {
-                let mut layout_data = element_data.layout_data.borrow_mut();
-                layout_data.pseudo_elements = None;
-                if layout_data.self_box.take().is_some() {
+                let layout_data = &element_data.layout_data;
+                *layout_data.marker_box.borrow_mut() = None;
+                *layout_data.before_box.borrow_mut() = None;
+                *layout_data.after_box.borrow_mut() = None;
+                if layout_data.self_box.borrow_mut().take().is_some() {
                     // Only descend into children if we removed a box.
                     // If there wasn’t one, then descendants don’t have boxes either.
                     if let Some(child) = node.first_child {
@@ -327,18 +568,19 @@ impl Context<'_> {
             }
             let mut next_is_a_sibling_of = node_id;
             node_id = loop {
-                if let Some(sibling) = self.document[next_is_a_sibling_of].next_sibling {
+                if next_is_a_sibling_of == base_element {
+                    // Don’t go outside the subtree
+                    return;
+                }
+                let next = &self.document[next_is_a_sibling_of];
+                if let Some(sibling) = next.next_sibling {
                     break sibling;
                 } else {
-                    next_is_a_sibling_of = node
+                    next_is_a_sibling_of = next
                         .parent
                         .expect("reached the root while traversing only a subtree");
                 }
             };
-            if next_is_a_sibling_of == base_element {
-                // Don’t go outside the subtree
-                return;
-            }
         }
     }
 }
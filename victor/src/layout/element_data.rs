@@ -1,15 +1,14 @@
 use super::*;
+use atomic_refcell::AtomicRefCell;
 
+/// Each box has its own cell: an element’s box slot is still borrowed
+/// while boxes for its `::marker`, `::before` and `::after` pseudo-elements are built.
 #[derive(Default)]
 pub(crate) struct LayoutDataForElement {
-    pub(super) self_box: Option<LayoutBox>,
-    pub(super) pseudo_elements: Option<Box<PseudoElementBoxes>>,
-}
-
-#[derive(Default)]
-pub(super) struct PseudoElementBoxes {
-    pub before: Option<LayoutBox>,
-    pub after: Option<LayoutBox>,
+    pub(super) self_box: AtomicRefCell<Option<LayoutBox>>,
+    pub(super) marker_box: AtomicRefCell<Option<LayoutBox>>,
+    pub(super) before_box: AtomicRefCell<Option<LayoutBox>>,
+    pub(super) after_box: AtomicRefCell<Option<LayoutBox>>,
 }
 
 pub(super) enum LayoutBox {
@@ -16,20 +16,24 @@ impl BlockFormattingContext {
 
 enum IntermediateBlockLevelBox {
     SameFormattingContextBlock {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         contents: IntermediateBlockContainer,
     },
     Independent {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
     },
     OutOfFlowAbsolutelyPositionedBox {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
     },
     OutOfFlowFloatBox {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
@@ -129,9 +133,10 @@ impl BlockContainer {
             .is_empty()
         {
             if builder.block_level_boxes.is_empty() {
-                let container = BlockContainer::InlineFormattingContext(
-                    builder.ongoing_inline_formatting_context,
-                );
+                let mut ifc = builder.ongoing_inline_formatting_context;
+                ifc.text_overflow_ellipsis = text_overflow_ellipsis(block_container_style);
+                ifc.strut_block_size = strut_block_size(block_container_style);
+                let container = BlockContainer::InlineFormattingContext(ifc);
                 return (container, builder.contains_floats);
             }
             builder.end_ongoing_inline_formatting_context();
@@ -161,71 +166,70 @@ impl BlockContainer {
 impl<'a> TraversalHandler<'a> for BlockContainerBuilder<'a> {
     fn handle_element(
         &mut self,
+        tag: Option<dom::NodeId>,
         style: &Arc<ComputedValues>,
         display: DisplayGeneratingBox,
         contents: Contents,
         box_slot: BoxSlot<'a>,
     ) {
+        self.context.count_box();
         match display {
-            DisplayGeneratingBox::OutsideInside { outside, inside } => match outside {
+            DisplayGeneratingBox::OutsideInside {
+                outside, inside, ..
+            } => match outside {
                 DisplayOutside::Inline => box_slot.set(LayoutBox::InlineLevel(
-                    self.handle_inline_level_element(style, inside, contents),
+                    self.handle_inline_level_element(tag, style, inside, contents),
                 )),
                 DisplayOutside::Block => {
                     // Floats and abspos cause blockification, so they only happen in this case.
                     // https://drafts.csswg.org/css2/visuren.html#dis-pos-flo
                     if style.box_.position.is_absolutely_positioned() {
                         self.handle_absolutely_positioned_element(
+                            tag,
                             style.clone(),
                             inside,
                             contents,
                             box_slot,
                         )
                     } else if style.box_.float.is_floating() {
-                        self.handle_float_element(style.clone(), inside, contents, box_slot)
+                        self.handle_float_element(tag, style.clone(), inside, contents, box_slot)
                     } else {
-                        self.handle_block_level_element(style.clone(), inside, contents, box_slot)
+                        self.handle_block_level_element(
+                            tag,
+                            style.clone(),
+                            inside,
+                            contents,
+                            box_slot,
+                        )
                     }
                 }
             },
+            DisplayGeneratingBox::LayoutInternal(internal) => {
+                let box_ = match internal {
+                    // FIXME: pair bases with annotations in ruby containers,
+                    // creating anonymous ones as needed:
+                    // https://drafts.csswg.org/css-ruby-1/#box-fixup
+                    DisplayLayoutInternal::RubyBase => {
+                        self.handle_inline_level_element(tag, style, DisplayInside::Flow, contents)
+                    }
+                    DisplayLayoutInternal::RubyText => {
+                        self.handle_ruby_text_element(tag, style, contents)
+                    }
+                };
+                box_slot.set(LayoutBox::InlineLevel(box_))
+            }
         }
     }
 
     fn handle_text(&mut self, input: &str, parent_style: &Arc<ComputedValues>) {
+        self.context.count_box();
         let (leading_whitespace, mut input) = self.handle_leading_whitespace(input);
         if leading_whitespace || !input.is_empty() {
             // This text node should be pushed either to the next ongoing
             // inline level box with the parent style of that inline level box
             // that will be ended, or directly to the ongoing inline formatting
             // context with the parent style of that builder.
-            let inlines = self.current_inline_level_boxes();
-
-            fn last_text(inlines: &mut [Arc<InlineLevelBox>]) -> Option<&mut String> {
-                let last = inlines.last_mut()?;
-                if let InlineLevelBox::TextRun(_) = &**last {
-                    // We never clone text run boxes, so the refcount is 1 and unwrap succeeds:
-                    let last = Arc::get_mut(last).unwrap();
-                    if let InlineLevelBox::TextRun(TextRun { text, .. }) = last {
-                        Some(text)
-                    } else {
-                        unreachable!()
-                    }
-                } else {
-                    None
-                }
-            }
-
-            let mut new_text_run_contents;
-            let output;
-            if let Some(text) = last_text(inlines) {
-                // Append to the existing text run
-                new_text_run_contents = None;
-                output = text;
-            } else {
-                new_text_run_contents = Some(String::new());
-                output = new_text_run_contents.as_mut().unwrap();
-            }
-
+            let output = self.last_text_run(parent_style);
             if leading_whitespace {
                 output.push(' ')
             }
@@ -244,16 +248,43 @@ impl<'a> TraversalHandler<'a> for BlockContainerBuilder<'a> {
                     break;
                 }
             }
+        }
+    }
 
-            if let Some(text) = new_text_run_contents {
-                let parent_style = parent_style.clone();
-                inlines.push(Arc::new(InlineLevelBox::TextRun(TextRun {
-                    parent_style,
-                    text,
-                })))
-            }
+    fn handle_forced_line_break(&mut self, style: &Arc<ComputedValues>) {
+        // Other newlines were collapsed to spaces by `handle_text`,
+        // so inline layout can tell that this one is a forced line break.
+        self.last_text_run(style).push('\n');
+        if style.box_.clear != Clear::None {
+            self.current_inline_level_boxes()
+                .push(Arc::new(InlineLevelBox::Clearance(style.box_.clear)))
         }
     }
+
+    fn handle_leader(&mut self, pattern: &str, parent_style: &Arc<ComputedValues>) {
+        self.context.count_box();
+        self.current_inline_level_boxes()
+            .push(Arc::new(InlineLevelBox::Leader(Leader {
+                parent_style: parent_style.clone(),
+                pattern: pattern.to_owned(),
+            })))
+    }
+
+    fn handle_outside_marker(
+        &mut self,
+        style: &Arc<ComputedValues>,
+        contents: Vec<PseudoElementContentItem>,
+        box_slot: BoxSlot<'a>,
+    ) {
+        self.context.count_box();
+        // FIXME: when the list item starts with a block-level box, the marker should be
+        // on the first line of that box instead of on an empty line of its own.
+        let marker =
+            self.handle_inline_box(None, style, NonReplacedContents::OfPseudoElement(contents));
+        let box_ = Arc::new(InlineLevelBox::OutsideMarker(marker));
+        self.current_inline_level_boxes().push(box_.clone());
+        box_slot.set(LayoutBox::InlineLevel(box_))
+    }
 }
 
 impl<'a> BlockContainerBuilder<'a> {
@@ -271,10 +302,16 @@ impl<'a> BlockContainerBuilder<'a> {
         let mut stack = Vec::new();
         let preserved = loop {
             match inline_level_boxes.next().map(|b| &**b) {
-                Some(InlineLevelBox::TextRun(r)) => break !r.text.ends_with(' '),
-                Some(InlineLevelBox::Atomic { .. }) => break false,
+                Some(InlineLevelBox::TextRun(r)) => break !r.text.ends_with(&[' ', '\n'][..]),
+                // A space after an atomic inline or a leader separates it from the next word.
+                Some(InlineLevelBox::Atomic { .. })
+                | Some(InlineLevelBox::Math { .. })
+                | Some(InlineLevelBox::Leader(_)) => break true,
                 Some(InlineLevelBox::OutOfFlowAbsolutelyPositionedBox(_))
-                | Some(InlineLevelBox::OutOfFlowFloatBox(_)) => {}
+                | Some(InlineLevelBox::OutOfFlowFloatBox(_))
+                | Some(InlineLevelBox::RubyAnnotation(_))
+                | Some(InlineLevelBox::OutsideMarker(_))
+                | Some(InlineLevelBox::Clearance(_)) => {}
                 Some(InlineLevelBox::InlineBox(b)) => {
                     stack.push(inline_level_boxes);
                     inline_level_boxes = b.children.iter().rev()
@@ -294,47 +331,94 @@ impl<'a> BlockContainerBuilder<'a> {
 
     fn handle_inline_level_element(
         &mut self,
+        tag: Option<dom::NodeId>,
         style: &Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
     ) -> Arc<InlineLevelBox> {
         let box_ = match contents.try_into() {
             Err(replaced) => Arc::new(InlineLevelBox::Atomic {
+                tag,
                 style: style.clone(),
                 contents: replaced,
             }),
             Ok(non_replaced) => match display_inside {
-                DisplayInside::Flow => {
-                    // Whatever happened before, we just found an inline level element, so
-                    // all we need to do is to remember this ongoing inline level box.
-                    self.ongoing_inline_boxes_stack.push(InlineBox {
-                        style: style.clone(),
-                        first_fragment: true,
-                        last_fragment: false,
-                        children: vec![],
-                    });
-
-                    NonReplacedContents::traverse(non_replaced, &style, self.context, self);
-
-                    let mut inline_box = self
-                        .ongoing_inline_boxes_stack
-                        .pop()
-                        .expect("no ongoing inline level box found");
-                    inline_box.last_fragment = true;
-                    Arc::new(InlineLevelBox::InlineBox(inline_box))
-                }
+                // Ruby containers are inline boxes,
+                // inline layout places annotations above the content before them.
+                DisplayInside::Flow | DisplayInside::Ruby => Arc::new(InlineLevelBox::InlineBox(
+                    self.handle_inline_box(tag, style, non_replaced),
+                )),
                 DisplayInside::FlowRoot => {
                     // a.k.a. `inline-block`
                     unimplemented!()
                 }
+                DisplayInside::Math => Arc::new(InlineLevelBox::Math {
+                    tag,
+                    style: style.clone(),
+                    contents: MathContent::construct(
+                        self.context,
+                        style,
+                        non_replaced,
+                        /* compact = */ true,
+                    ),
+                }),
             },
         };
         self.current_inline_level_boxes().push(box_.clone());
         box_
     }
 
+    fn handle_inline_box(
+        &mut self,
+        tag: Option<dom::NodeId>,
+        style: &Arc<ComputedValues>,
+        contents: NonReplacedContents,
+    ) -> InlineBox {
+        // Whatever happened before, we just found an inline level element, so
+        // all we need to do is to remember this ongoing inline level box.
+        self.ongoing_inline_boxes_stack.push(InlineBox {
+            tag,
+            style: style.clone(),
+            first_fragment: true,
+            last_fragment: false,
+            children: vec![],
+        });
+
+        NonReplacedContents::traverse(contents, &style, self.context, self);
+
+        let mut inline_box = self
+            .ongoing_inline_boxes_stack
+            .pop()
+            .expect("no ongoing inline level box found");
+        inline_box.last_fragment = true;
+        inline_box
+    }
+
+    /// https://drafts.csswg.org/css-ruby-1/#ruby-layout
+    fn handle_ruby_text_element(
+        &mut self,
+        tag: Option<dom::NodeId>,
+        style: &Arc<ComputedValues>,
+        contents: Contents,
+    ) -> Arc<InlineLevelBox> {
+        let box_ =
+            match contents.try_into() {
+                Err(replaced) => Arc::new(InlineLevelBox::Atomic {
+                    tag,
+                    style: style.clone(),
+                    contents: replaced,
+                }),
+                Ok(non_replaced) => Arc::new(InlineLevelBox::RubyAnnotation(
+                    self.handle_inline_box(tag, style, non_replaced),
+                )),
+            };
+        self.current_inline_level_boxes().push(box_.clone());
+        box_
+    }
+
     fn handle_block_level_element(
         &mut self,
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
@@ -351,6 +435,7 @@ impl<'a> BlockContainerBuilder<'a> {
                 .rev()
                 .map(|ongoing| {
                     let fragmented = InlineBox {
+                        tag: ongoing.tag,
                         style: ongoing.style.clone(),
                         first_fragment: ongoing.first_fragment,
                         // The fragmented boxes before the block level element
@@ -385,11 +470,15 @@ impl<'a> BlockContainerBuilder<'a> {
 
         let intermediate_box = match contents.try_into() {
             Ok(contents) => match display_inside {
-                DisplayInside::Flow => IntermediateBlockLevelBox::SameFormattingContextBlock {
-                    style,
-                    contents: IntermediateBlockContainer::Deferred { contents },
-                },
+                DisplayInside::Flow if !style.has_layout_or_paint_containment() => {
+                    IntermediateBlockLevelBox::SameFormattingContextBlock {
+                        tag,
+                        style,
+                        contents: IntermediateBlockContainer::Deferred { contents },
+                    }
+                }
                 _ => IntermediateBlockLevelBox::Independent {
+                    tag,
                     style,
                     display_inside,
                     contents: contents.into(),
@@ -398,6 +487,7 @@ impl<'a> BlockContainerBuilder<'a> {
             Err(contents) => {
                 let contents = Contents::Replaced(contents);
                 IntermediateBlockLevelBox::Independent {
+                    tag,
                     style,
                     display_inside,
                     contents,
@@ -409,6 +499,7 @@ impl<'a> BlockContainerBuilder<'a> {
 
     fn handle_absolutely_positioned_element(
         &mut self,
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
@@ -416,6 +507,7 @@ impl<'a> BlockContainerBuilder<'a> {
     ) {
         if !self.has_ongoing_inline_formatting_context() {
             let box_ = IntermediateBlockLevelBox::OutOfFlowAbsolutelyPositionedBox {
+                tag,
                 style,
                 contents,
                 display_inside,
@@ -424,6 +516,7 @@ impl<'a> BlockContainerBuilder<'a> {
         } else {
             let box_ = Arc::new(InlineLevelBox::OutOfFlowAbsolutelyPositionedBox(
                 AbsolutelyPositionedBox {
+                    tag,
                     contents: IndependentFormattingContext::construct(
                         self.context,
                         &style,
@@ -440,6 +533,7 @@ impl<'a> BlockContainerBuilder<'a> {
 
     fn handle_float_element(
         &mut self,
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         display_inside: DisplayInside,
         contents: Contents,
@@ -449,6 +543,7 @@ impl<'a> BlockContainerBuilder<'a> {
 
         if !self.has_ongoing_inline_formatting_context() {
             let box_ = IntermediateBlockLevelBox::OutOfFlowFloatBox {
+                tag,
                 style,
                 contents,
                 display_inside,
@@ -456,6 +551,7 @@ impl<'a> BlockContainerBuilder<'a> {
             self.block_level_boxes.push((box_, box_slot));
         } else {
             let box_ = Arc::new(InlineLevelBox::OutOfFlowFloatBox(FloatBox {
+                tag,
                 contents: IndependentFormattingContext::construct(
                     self.context,
                     &style,
@@ -492,15 +588,37 @@ impl<'a> BlockContainerBuilder<'a> {
             ComputedValues::anonymous_inheriting_from(Some(block_container_style))
         });
 
+        let mut ifc = take(&mut self.ongoing_inline_formatting_context);
+        ifc.text_overflow_ellipsis = text_overflow_ellipsis(block_container_style);
+        ifc.strut_block_size = strut_block_size(block_container_style);
         let box_ = IntermediateBlockLevelBox::SameFormattingContextBlock {
+            tag: None,
             style: anonymous_style.clone(),
-            contents: IntermediateBlockContainer::InlineFormattingContext(take(
-                &mut self.ongoing_inline_formatting_context,
-            )),
+            contents: IntermediateBlockContainer::InlineFormattingContext(ifc),
         };
         self.block_level_boxes.push((box_, BoxSlot::dummy()))
     }
 
+    /// The text of the last text run of the current inline level boxes,
+    /// after pushing a new empty one if the last box is not a text run.
+    fn last_text_run(&mut self, parent_style: &Arc<ComputedValues>) -> &mut String {
+        let inlines = self.current_inline_level_boxes();
+        if !matches!(
+            inlines.last().map(|b| &**b),
+            Some(InlineLevelBox::TextRun(_))
+        ) {
+            inlines.push(Arc::new(InlineLevelBox::TextRun(TextRun {
+                parent_style: parent_style.clone(),
+                text: String::new(),
+            })))
+        }
+        // We never clone text run boxes, so the refcount is 1 and unwrap succeeds:
+        match Arc::get_mut(inlines.last_mut().unwrap()).unwrap() {
+            InlineLevelBox::TextRun(TextRun { text, .. }) => text,
+            _ => unreachable!(),
+        }
+    }
+
     fn current_inline_level_boxes(&mut self) -> &mut Vec<Arc<InlineLevelBox>> {
         match self.ongoing_inline_boxes_stack.last_mut() {
             Some(last) => &mut last.children,
@@ -520,13 +638,21 @@ impl<'a> BlockContainerBuilder<'a> {
 impl IntermediateBlockLevelBox {
     fn finish(self, context: &Context) -> (Arc<BlockLevelBox>, ContainsFloats) {
         match self {
-            IntermediateBlockLevelBox::SameFormattingContextBlock { style, contents } => {
+            IntermediateBlockLevelBox::SameFormattingContextBlock {
+                tag,
+                style,
+                contents,
+            } => {
                 let (contents, contains_floats) = contents.finish(context, &style);
-                let block_level_box =
-                    Arc::new(BlockLevelBox::SameFormattingContextBlock { contents, style });
+                let block_level_box = Arc::new(BlockLevelBox::SameFormattingContextBlock {
+                    tag,
+                    contents,
+                    style,
+                });
                 (block_level_box, contains_floats)
             }
             IntermediateBlockLevelBox::Independent {
+                tag,
                 style,
                 display_inside,
                 contents,
@@ -538,17 +664,23 @@ impl IntermediateBlockLevelBox {
                     contents,
                 );
                 (
-                    Arc::new(BlockLevelBox::Independent { style, contents }),
+                    Arc::new(BlockLevelBox::Independent {
+                        tag,
+                        style,
+                        contents,
+                    }),
                     ContainsFloats::No,
                 )
             }
             IntermediateBlockLevelBox::OutOfFlowAbsolutelyPositionedBox {
+                tag,
                 style,
                 display_inside,
                 contents,
             } => {
                 let block_level_box = Arc::new(BlockLevelBox::OutOfFlowAbsolutelyPositionedBox(
                     AbsolutelyPositionedBox {
+                        tag,
                         contents: IndependentFormattingContext::construct(
                             context,
                             &style,
@@ -561,6 +693,7 @@ impl IntermediateBlockLevelBox {
                 (block_level_box, ContainsFloats::No)
             }
             IntermediateBlockLevelBox::OutOfFlowFloatBox {
+                tag,
                 style,
                 display_inside,
                 contents,
@@ -572,6 +705,7 @@ impl IntermediateBlockLevelBox {
                     contents,
                 );
                 let block_level_box = Arc::new(BlockLevelBox::OutOfFlowFloatBox(FloatBox {
+                    tag,
                     contents,
                     style,
                 }));
@@ -581,6 +715,24 @@ impl IntermediateBlockLevelBox {
     }
 }
 
+/// Whether lines of a block container with this style that overflow it end with “…”.
+/// This also applies to lines in its anonymous block boxes.
+///
+/// https://drafts.csswg.org/css-overflow-3/#text-overflow
+fn text_overflow_ellipsis(block_container_style: &ComputedValues) -> bool {
+    block_container_style.box_.overflow != Overflow::Visible
+        && block_container_style.box_.text_overflow == TextOverflow::Ellipsis
+}
+
+/// The block size of the strut that each line of a block container with this style starts with,
+/// for `line-height: normal`. This also applies to lines in its anonymous block boxes,
+/// which inherit its font.
+///
+/// https://drafts.csswg.org/css2/visudet.html#strut
+fn strut_block_size(block_container_style: &ComputedValues) -> Length {
+    block_container_style.font.font_size.0 * 1.2
+}
+
 impl IntermediateBlockContainer {
     fn finish(
         self,
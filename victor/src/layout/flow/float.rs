@@ -2,17 +2,448 @@ use super::*;
 
 #[derive(Debug)]
 pub(in crate::layout) struct FloatBox {
+    pub tag: Option<dom::NodeId>,
     pub style: Arc<ComputedValues>,
     pub contents: IndependentFormattingContext,
 }
 
 /// Data kept during layout about the floats in a given block formatting context.
+///
+/// Unless otherwise noted, positions are relative to the content box
+/// of the block formatting context root.
 pub(in crate::layout) struct FloatContext {
-    // TODO
+    placed: Vec<PlacedFloat>,
+
+    /// Start corner of the content box of the block container being laid out.
+    ///
+    /// The block position is an estimate made before margin collapsing is resolved.
+    pub containing_block_start_corner: Vec2<Length>,
+
+    /// “The outer top of a floating box may not be higher than
+    ///  the outer top of any block or floated box generated by an element earlier in the source document.”
+    /// https://drafts.csswg.org/css2/visuren.html#float-rules
+    ceiling: Length,
+}
+
+struct PlacedFloat {
+    float: Float,
+    side: FloatSide,
+    margin_rect: Rect<Length>,
+    /// From `shape-outside`, relative to the start corner of the margin box
+    shape: Option<ResolvedShape>,
+}
+
+impl PlacedFloat {
+    fn block_end(&self) -> Length {
+        self.margin_rect.start_corner.block + self.margin_rect.size.block
+    }
+
+    /// The inline range that line boxes avoid in the given band, if any:
+    /// the float area, which is the margin box unless `shape-outside` clips it.
+    ///
+    /// https://drafts.csswg.org/css-shapes/#relation-to-box-model-and-float-behavior
+    fn float_area_inline_range(
+        &self,
+        block_start: Length,
+        block_size: Length,
+    ) -> Option<(Length, Length)> {
+        let rect = &self.margin_rect;
+        let margin_box_range = (
+            rect.start_corner.inline,
+            rect.start_corner.inline + rect.size.inline,
+        );
+        let shape = match &self.shape {
+            Some(shape) => shape,
+            None => return Some(margin_box_range),
+        };
+        let band_start = (block_start - rect.start_corner.block).max(Length::zero());
+        let band_end = (block_start + block_size - rect.start_corner.block).min(rect.size.block);
+        let (start, end) = shape.inline_range(band_start, band_end)?;
+        let start = start.max(Length::zero());
+        let end = end.min(rect.size.inline);
+        if start > end {
+            return None;
+        }
+        Some((
+            rect.start_corner.inline + start,
+            rect.start_corner.inline + end,
+        ))
+    }
+}
+
+/// The float area from `shape-outside` of a float with these margins, borders and padding,
+/// relative to the start corner of its margin box. It is clipped to the margin box when used.
+fn float_shape(
+    shape_outside: &ShapeOutside,
+    margin_box_size: &Vec2<Length>,
+    margin: &Sides<Length>,
+    border: &Sides<Length>,
+    padding: &Sides<Length>,
+) -> Option<ResolvedShape> {
+    let (shape, reference_box) = match shape_outside {
+        ShapeOutside::None => return None,
+        ShapeOutside::Shape(shape, reference_box) => (shape, reference_box),
+    };
+    let zero = Sides::zero();
+    let insets = match reference_box {
+        ShapeBox::Margin => zero,
+        ShapeBox::Border => margin.clone(),
+        ShapeBox::Padding => margin + border,
+        ShapeBox::Content => &(margin + border) + padding,
+    };
+    let reference_box = Rect {
+        start_corner: insets.start_corner(),
+        size: Vec2 {
+            inline: (margin_box_size.inline - insets.inline_sum()).max(Length::zero()),
+            block: (margin_box_size.block - insets.block_sum()).max(Length::zero()),
+        },
+    };
+    Some(ResolvedShape::resolve(shape.as_ref(), &reference_box))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FloatSide {
+    InlineStart,
+    InlineEnd,
 }
 
 impl FloatContext {
     pub fn new() -> Self {
-        FloatContext {}
+        FloatContext {
+            placed: Vec::new(),
+            containing_block_start_corner: Vec2::zero(),
+            ceiling: Length::zero(),
+        }
+    }
+
+    /// The block-end margin edge of the lowest float placed so far, if any.
+    pub fn lowest_float_edge(&self) -> Option<Length> {
+        self.clearance_edge(Clear::Both)
+    }
+
+    /// The block-end margin edge of the lowest float placed so far
+    /// that an element with the given `clear` value must be placed below, if any.
+    ///
+    /// https://drafts.csswg.org/css2/visuren.html#flow-control
+    pub fn clearance_edge(&self, clear: Clear) -> Option<Length> {
+        self.placed
+            .iter()
+            .filter(|float| {
+                matches!(
+                    (clear, float.float),
+                    (Clear::Both, _) | (Clear::Left, Float::Left) | (Clear::Right, Float::Right)
+                )
+            })
+            .map(PlacedFloat::block_end)
+            .fold(None, |lowest, edge| {
+                Some(lowest.map_or(edge, |l: Length| l.max(edge)))
+            })
+    }
+
+    /// The inline offset and size available to a line box at the given block position
+    /// and with (at least) the given block size, relative to the current containing block.
+    pub fn line_box_range(
+        &self,
+        block_position: Length,
+        block_size: Length,
+        containing_block_inline_size: Length,
+    ) -> (Length, Length) {
+        let cb_start = &self.containing_block_start_corner;
+        // FIXME: a line box taller than `block_size` does not avoid floats
+        // (or float areas from `shape-outside`) below that band,
+        // since the block size of a line box is only known after its layout.
+        let (start, end) = self.available_inline_range(
+            cb_start.block + block_position,
+            block_size,
+            cb_start.inline,
+            cb_start.inline + containing_block_inline_size,
+            true,
+        );
+        (start - cb_start.inline, (end - start).max(Length::zero()))
+    }
+
+    /// Find a position for a float whose margin box has the given size,
+    /// and record it so that later content can avoid it.
+    ///
+    /// `block_position` and the return value are relative to the current containing block.
+    fn place(
+        &mut self,
+        style: &ComputedValues,
+        side: FloatSide,
+        margin_box_size: &Vec2<Length>,
+        shape: Option<ResolvedShape>,
+        block_position: Length,
+        containing_block_inline_size: Length,
+    ) -> Vec2<Length> {
+        let cb_start = self.containing_block_start_corner.clone();
+        let min_inline = cb_start.inline;
+        let max_inline = cb_start.inline + containing_block_inline_size;
+        let mut block_start = (cb_start.block + block_position).max(self.ceiling);
+        if let Some(edge) = self.clearance_edge(style.box_.clear) {
+            block_start.max_assign(edge)
+        }
+        let (inline_start, inline_end) = loop {
+            let (start, end) = self.available_inline_range(
+                block_start,
+                margin_box_size.block,
+                min_inline,
+                max_inline,
+                false,
+            );
+            let fits = end - start >= margin_box_size.inline;
+            let nothing_to_avoid = start == min_inline && end == max_inline;
+            if fits || nothing_to_avoid {
+                break (start, end);
+            }
+            match self.next_float_block_end(block_start, margin_box_size.block) {
+                Some(next) => block_start = next,
+                None => break (start, end),
+            }
+        };
+        let start_corner = Vec2 {
+            inline: match side {
+                FloatSide::InlineStart => inline_start,
+                FloatSide::InlineEnd => inline_end - margin_box_size.inline,
+            },
+            block: block_start,
+        };
+        self.ceiling = block_start;
+        let position = Vec2 {
+            inline: start_corner.inline - cb_start.inline,
+            block: start_corner.block - cb_start.block,
+        };
+        self.placed.push(PlacedFloat {
+            float: style.box_.float,
+            side,
+            margin_rect: Rect {
+                start_corner,
+                size: margin_box_size.clone(),
+            },
+            shape,
+        });
+        position
+    }
+
+    /// Place a block-level box that establishes an independent formatting context
+    /// so that its border box does not overlap the margin box of any float:
+    /// https://drafts.csswg.org/css2/visuren.html#floats
+    ///
+    /// `layout` lays out the box in a containing block with the given inline size,
+    /// narrowed next to floats, and returns the inline size of its margin box
+    /// and the block size of its border box. The box moves down past floats until it fits.
+    /// Returns the result of its last layout and its offset from where it would be
+    /// without floats, relative to the current containing block.
+    pub fn place_independent<T>(
+        &self,
+        containing_block_inline_size: Length,
+        mut layout: impl FnMut(Length) -> (T, Vec2<Length>),
+    ) -> (T, Vec2<Length>) {
+        let cb_start = &self.containing_block_start_corner;
+        let min_inline = cb_start.inline;
+        let max_inline = cb_start.inline + containing_block_inline_size;
+        let mut block_start = cb_start.block;
+        loop {
+            let range = |block_size| {
+                self.available_inline_range(block_start, block_size, min_inline, max_inline, false)
+            };
+            let (start, end) = range(Length::zero());
+            let (result, size) = layout((end - start).max(Length::zero()));
+            let fits = size.inline <= end - start && range(size.block) == (start, end);
+            match self.next_float_block_end(block_start, size.block) {
+                Some(next) if !fits => block_start = next,
+                _ => {
+                    let offset = Vec2 {
+                        inline: start - min_inline,
+                        block: block_start - cb_start.block,
+                    };
+                    return (result, offset);
+                }
+            }
+        }
+    }
+
+    /// The part of `min_inline..max_inline` not covered by floats
+    /// in the band of the given block position and size.
+    ///
+    /// Line boxes avoid float areas, but floats are placed next to the margin boxes
+    /// of other floats.
+    fn available_inline_range(
+        &self,
+        block_start: Length,
+        block_size: Length,
+        min_inline: Length,
+        max_inline: Length,
+        float_areas: bool,
+    ) -> (Length, Length) {
+        let mut start = min_inline;
+        let mut end = max_inline;
+        for float in self.floats_in_band(block_start, block_size) {
+            let rect = &float.margin_rect;
+            let (float_start, float_end) = if float_areas {
+                match float.float_area_inline_range(block_start, block_size) {
+                    Some(range) => range,
+                    None => continue,
+                }
+            } else {
+                (
+                    rect.start_corner.inline,
+                    rect.start_corner.inline + rect.size.inline,
+                )
+            };
+            match float.side {
+                FloatSide::InlineStart => start.max_assign(float_end),
+                FloatSide::InlineEnd => end = end.min(float_start),
+            }
+        }
+        (start, end)
+    }
+
+    /// The closest block-end margin edge below `block_start` among floats in the given band.
+    fn next_float_block_end(&self, block_start: Length, block_size: Length) -> Option<Length> {
+        self.floats_in_band(block_start, block_size)
+            .map(PlacedFloat::block_end)
+            .fold(None, |next, edge| {
+                Some(next.map_or(edge, |n: Length| n.min(edge)))
+            })
+    }
+
+    fn floats_in_band(
+        &self,
+        block_start: Length,
+        block_size: Length,
+    ) -> impl Iterator<Item = &PlacedFloat> {
+        let block_end = block_start + block_size;
+        self.placed.iter().filter(move |float| {
+            let rect = &float.margin_rect;
+            // An empty band still intersects floats that contain its position.
+            let starts_before_band_end =
+                rect.start_corner.block < block_end || rect.start_corner.block <= block_start;
+            starts_before_band_end && float.block_end() > block_start
+        })
+    }
+}
+
+impl FloatBox {
+    pub(super) fn outer_inline_content_sizes(&self, font_context: &FontContext) -> ContentSizes {
+        outer_inline_content_sizes(&self.style, || {
+            self.contents
+                .inline_content_sizes(font_context, &self.style)
+        })
+    }
+
+    /// Lay out this float and place it in the float context.
+    ///
+    /// `block_position` is the position in the containing block
+    /// where the next in-flow content would go.
+    pub(super) fn layout<'a>(
+        &'a self,
+        layout_context: &LayoutContext,
+        containing_block: &ContainingBlock,
+        tree_rank: usize,
+        absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
+        float_context: &mut FloatContext,
+        block_position: Length,
+    ) -> Fragment {
+        let style = &self.style;
+        let cbis = containing_block.inline_size;
+        let padding = style
+            .padding(containing_block.mode)
+            .percentages_relative_to(cbis);
+        let border = style
+            .border_width(containing_block.mode)
+            .percentages_relative_to(cbis);
+        // https://drafts.csswg.org/css2/visudet.html#float-width
+        // “If 'margin-left', or 'margin-right' are computed as 'auto', their used value is '0'.”
+        let margin = style
+            .margin(containing_block.mode)
+            .percentages_relative_to(cbis)
+            .auto_is(Length::zero);
+        let pb = &padding + &border;
+        let box_size = style.box_size();
+        let inline_size = box_size.inline.percentage_relative_to(cbis).auto_is(|| {
+            let available_size = cbis - pb.inline_sum() - margin.inline_sum();
+            self.contents
+                .inline_content_sizes(layout_context.font_context, style)
+                .shrink_to_fit(available_size)
+        });
+        let block_size = match box_size.block {
+            LengthOrPercentageOrAuto::Length(l) => LengthOrAuto::Length(l),
+            LengthOrPercentageOrAuto::Percentage(p) => {
+                containing_block.block_size.map(|cbbs| cbbs * p)
+            }
+            LengthOrPercentageOrAuto::Auto => LengthOrAuto::Auto,
+        };
+        let containing_block_for_children = ContainingBlock {
+            inline_size,
+            block_size,
+            mode: style.writing_mode_in(containing_block.mode),
+        };
+        let mut nested_abspos = vec![];
+        let mut flow_children = self.contents.layout(
+            layout_context,
+            style,
+            &containing_block_for_children,
+            tree_rank,
+            if style.establishes_containing_block_for_absolute_descendants() {
+                &mut nested_abspos
+            } else {
+                absolutely_positioned_fragments
+            },
+        );
+        let block_size = block_size.auto_is(|| flow_children.block_size);
+
+        // FIXME: this assumes the same direction throughout the block formatting context.
+        let side = match (style.box_.float, containing_block.mode.1) {
+            (Float::Left, Direction::Ltr) | (Float::Right, Direction::Rtl) => {
+                FloatSide::InlineStart
+            }
+            _ => FloatSide::InlineEnd,
+        };
+        let margin_box_size = Vec2 {
+            inline: inline_size + pb.inline_sum() + margin.inline_sum(),
+            block: block_size + pb.block_sum() + margin.block_sum(),
+        };
+        let shape = float_shape(
+            &style.box_.shape_outside,
+            &margin_box_size,
+            &margin,
+            &border,
+            &padding,
+        );
+        let margin_box_start_corner =
+            float_context.place(style, side, &margin_box_size, shape, block_position, cbis);
+
+        let relative_adjustement = relative_adjustement(style, containing_block);
+        let content_rect = Rect {
+            start_corner: &(&margin_box_start_corner + &relative_adjustement)
+                + &(&margin + &pb).start_corner(),
+            size: Vec2 {
+                inline: inline_size,
+                block: block_size,
+            },
+        };
+        if style.establishes_containing_block_for_absolute_descendants() {
+            flow_children.fragments.extend(
+                AbsolutelyPositionedFragment::in_positioned_containing_block(
+                    layout_context,
+                    style,
+                    nested_abspos,
+                    absolutely_positioned_fragments,
+                    &content_rect.size,
+                    &padding,
+                    containing_block_for_children.mode,
+                ),
+            )
+        }
+        Fragment::Box(BoxFragment {
+            tag: self.tag,
+            style: style.clone(),
+            children: flow_children.fragments,
+            content_rect,
+            padding,
+            border,
+            margin,
+            block_margins_collapsed_with_children: CollapsedBlockMargins::zero(),
+        })
     }
 }
@@ -1,10 +1,16 @@
 use super::*;
-use crate::fonts::BITSTREAM_VERA_SANS;
+use crate::fonts::{Em, Font, FontError, GlyphId, Substitutions};
+use crate::layout::dom_traversal::ZERO_WIDTH_SPACE;
+use crate::text::itemize::{itemize, Item};
 use crate::text::ShapedSegment;
 
 #[derive(Debug, Default)]
 pub(in crate::layout) struct InlineFormattingContext {
     pub(super) inline_level_boxes: Vec<Arc<InlineLevelBox>>,
+    /// From `text-overflow` of the block container, see `text_overflow_ellipsis`
+    pub(super) text_overflow_ellipsis: bool,
+    /// From `font-size` of the block container, see `strut_block_size`
+    pub(super) strut_block_size: Length,
 }
 
 #[derive(Debug)]
@@ -13,15 +19,32 @@ pub(in crate::layout) enum InlineLevelBox {
     TextRun(TextRun),
     OutOfFlowAbsolutelyPositionedBox(AbsolutelyPositionedBox),
     OutOfFlowFloatBox(FloatBox),
+    /// A `display: ruby-text` box, placed above the line instead of in it.
+    RubyAnnotation(InlineBox),
+    /// The `::marker` of a list item with `list-style-position: outside`,
+    /// placed before the start of the line without taking space in it.
+    OutsideMarker(InlineBox),
+    /// After the forced line break of a `<br>` with `clear` (such as from `<br clear=left>`):
+    /// the next line starts below the floats on that side.
+    Clearance(Clear),
     Atomic {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         // FIXME: this should be IndependentFormattingContext:
         contents: ReplacedContent,
     },
+    /// A `display: math` formula, laid out as an atomic inline
+    Math {
+        tag: Option<dom::NodeId>,
+        style: Arc<ComputedValues>,
+        contents: MathContent,
+    },
+    Leader(Leader),
 }
 
 #[derive(Debug)]
 pub(in crate::layout) struct InlineBox {
+    pub tag: Option<dom::NodeId>,
     pub style: Arc<ComputedValues>,
     pub first_fragment: bool,
     pub last_fragment: bool,
@@ -35,6 +58,16 @@ pub(in crate::layout) struct TextRun {
     pub text: String,
 }
 
+/// https://drafts.csswg.org/css-content-3/#leaders
+///
+/// Fills the space left on its line after the content that follows it
+/// up to the next forced line break, such as a page number in a table of contents.
+#[derive(Debug)]
+pub(in crate::layout) struct Leader {
+    pub parent_style: Arc<ComputedValues>,
+    pub pattern: String,
+}
+
 struct InlineNestingLevelState<'box_tree> {
     remaining_boxes: std::slice::Iter<'box_tree, Arc<InlineLevelBox>>,
     fragments_so_far: Vec<Fragment>,
@@ -43,6 +76,7 @@ struct InlineNestingLevelState<'box_tree> {
 }
 
 struct PartialInlineBoxFragment<'box_tree> {
+    tag: Option<dom::NodeId>,
     style: Arc<ComputedValues>,
     start_corner: Vec2<Length>,
     padding: Sides<Length>,
@@ -53,9 +87,27 @@ struct PartialInlineBoxFragment<'box_tree> {
 }
 
 struct InlineFormattingContextState<'box_tree, 'cb> {
+    layout_context: &'cb LayoutContext<'cb>,
     containing_block: &'cb ContainingBlock,
+    tree_rank: usize,
+    absolutely_positioned_fragments: &'cb mut Vec<AbsolutelyPositionedFragment<'box_tree>>,
+    float_context: Option<&'cb mut FloatContext>,
+    /// Floats encountered in the middle of a line, placed once that line is finished.
+    pending_floats: Vec<&'box_tree FloatBox>,
     line_boxes: LinesBoxes,
     inline_position: Length,
+    text_overflow_ellipsis: bool,
+    strut_block_size: Length,
+    /// Whether the current line was already truncated with an ellipsis:
+    /// its remaining content is hidden.
+    line_truncated: bool,
+    /// Whether the current line has text, preserved break opportunities, in-flow content,
+    /// or inline boxes with non-zero margins, padding or borders.
+    /// Other lines are zero-height line boxes:
+    /// https://drafts.csswg.org/css2/visuren.html#inline-formatting
+    line_has_content: bool,
+    /// In a ruby container, where the base of the next annotation starts on the current line.
+    ruby_base_start: Option<Length>,
     partial_inline_boxes_stack: Vec<PartialInlineBoxFragment<'box_tree>>,
     current_nesting_level: InlineNestingLevelState<'box_tree>,
 }
@@ -63,47 +115,102 @@ struct InlineFormattingContextState<'box_tree, 'cb> {
 struct LinesBoxes {
     boxes: Vec<Fragment>,
     next_line_block_position: Length,
+    /// Inline offset of the current line in the containing block, shifted by floats.
+    current_line_inline_start: Length,
+    /// Inline size of the current line, shortened by floats.
+    current_line_inline_size: Length,
+    /// Ruby annotations above the current line
+    ruby_annotations: Vec<Fragment>,
+    ruby_annotations_block_size: Length,
 }
 
 impl InlineFormattingContext {
     pub(super) fn layout<'a>(
         &'a self,
+        layout_context: &LayoutContext,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
+        float_context: Option<&mut FloatContext>,
     ) -> FlowChildren {
+        // The result of a cancelled layout is not used, so leave the lines empty.
+        let inline_level_boxes: &[_] = if layout_context.is_cancelled() {
+            &[]
+        } else {
+            &self.inline_level_boxes
+        };
         let mut ifc = InlineFormattingContextState {
+            layout_context,
             containing_block,
+            tree_rank,
+            absolutely_positioned_fragments,
+            float_context,
+            pending_floats: Vec::new(),
             partial_inline_boxes_stack: Vec::new(),
             line_boxes: LinesBoxes {
                 boxes: Vec::new(),
                 next_line_block_position: Length::zero(),
+                current_line_inline_start: Length::zero(),
+                current_line_inline_size: containing_block.inline_size,
+                ruby_annotations: Vec::new(),
+                ruby_annotations_block_size: Length::zero(),
             },
             inline_position: Length::zero(),
+            text_overflow_ellipsis: self.text_overflow_ellipsis,
+            strut_block_size: self.strut_block_size,
+            line_truncated: false,
+            line_has_content: false,
+            ruby_base_start: None,
             current_nesting_level: InlineNestingLevelState {
-                remaining_boxes: self.inline_level_boxes.iter(),
-                fragments_so_far: Vec::with_capacity(self.inline_level_boxes.len()),
+                remaining_boxes: inline_level_boxes.iter(),
+                fragments_so_far: Vec::with_capacity(inline_level_boxes.len()),
                 inline_start: Length::zero(),
                 max_block_size_of_fragments_so_far: Length::zero(),
             },
         };
+        ifc.start_line();
         loop {
             if let Some(child) = ifc.current_nesting_level.remaining_boxes.next() {
                 match &**child {
                     InlineLevelBox::InlineBox(inline) => {
                         let partial = inline.start_layout(&mut ifc);
+                        if is_ruby_container(&partial.style) {
+                            ifc.ruby_base_start = Some(ifc.inline_position)
+                        }
                         ifc.partial_inline_boxes_stack.push(partial)
                     }
                     InlineLevelBox::TextRun(run) => run.layout(&mut ifc),
-                    InlineLevelBox::Atomic { style: _, contents } => {
-                        // FIXME
-                        match *contents {}
+                    InlineLevelBox::Leader(leader) => leader.layout(&mut ifc),
+                    InlineLevelBox::RubyAnnotation(annotation) => {
+                        annotation.layout_ruby_annotation(&mut ifc)
+                    }
+                    InlineLevelBox::OutsideMarker(marker) => marker.layout_outside_marker(&mut ifc),
+                    InlineLevelBox::Clearance(clear) => ifc.clear_floats(*clear),
+                    InlineLevelBox::Atomic {
+                        tag,
+                        style,
+                        contents,
+                    } => {
+                        let font_context = ifc.layout_context.font_context;
+                        let size = contents.used_size(style, ifc.containing_block, font_context);
+                        let (children, ascent) =
+                            contents.make_fragments(style, &size, font_context);
+                        ifc.place_atomic(*tag, style, children, size, ascent)
+                    }
+                    InlineLevelBox::Math {
+                        tag,
+                        style,
+                        contents,
+                    } => {
+                        let (children, size, ascent) =
+                            contents.layout(ifc.layout_context.font_context, style.writing_mode());
+                        ifc.place_atomic(*tag, style, children, size, ascent)
                     }
                     InlineLevelBox::OutOfFlowAbsolutelyPositionedBox(box_) => {
                         let initial_start_corner = match box_.style.specified_display {
                             Display::GeneratingBox(DisplayGeneratingBox::OutsideInside {
                                 outside,
-                                inside: _,
+                                ..
                             }) => Vec2 {
                                 inline: match outside {
                                     DisplayOutside::Inline => ifc.inline_position,
@@ -111,31 +218,54 @@ impl InlineFormattingContext {
                                 },
                                 block: ifc.line_boxes.next_line_block_position,
                             },
+                            // Ruby bases and annotations are inline-level
+                            Display::GeneratingBox(DisplayGeneratingBox::LayoutInternal(_)) => {
+                                Vec2 {
+                                    inline: ifc.inline_position,
+                                    block: ifc.line_boxes.next_line_block_position,
+                                }
+                            }
                             Display::Contents => {
                                 panic!("display:contents does not generate an abspos box")
                             }
                             Display::None => panic!("display:none does not generate an abspos box"),
                         };
-                        absolutely_positioned_fragments
-                            .push(box_.layout(initial_start_corner, tree_rank));
+                        ifc.absolutely_positioned_fragments.push(box_.layout(
+                            initial_start_corner,
+                            ifc.containing_block.mode,
+                            tree_rank,
+                        ));
                     }
-                    InlineLevelBox::OutOfFlowFloatBox(_box_) => {
-                        // TODO
-                        continue;
+                    InlineLevelBox::OutOfFlowFloatBox(box_) => {
+                        if ifc.line_is_empty() {
+                            ifc.place_float(box_);
+                            ifc.start_line();
+                        } else {
+                            // Keep the current line as it is,
+                            // the float only shortens the following lines.
+                            ifc.pending_floats.push(box_);
+                        }
                     }
                 }
             } else
             // Reached the end of ifc.remaining_boxes
             if let Some(mut partial) = ifc.partial_inline_boxes_stack.pop() {
-                partial.finish_layout(
+                if is_ruby_container(&partial.style) {
+                    ifc.ruby_base_start = None
+                }
+                ifc.line_has_content |= partial.finish_layout(
                     &mut ifc.current_nesting_level,
                     &mut ifc.inline_position,
                     false,
                 );
                 ifc.current_nesting_level = partial.parent_nesting_level
             } else {
-                ifc.line_boxes
-                    .finish_line(&mut ifc.current_nesting_level, containing_block);
+                ifc.line_boxes.finish_line(
+                    &mut ifc.current_nesting_level,
+                    containing_block,
+                    ifc.line_has_content,
+                );
+                ifc.place_pending_floats();
                 return FlowChildren {
                     fragments: ifc.line_boxes.boxes,
                     block_size: ifc.line_boxes.next_line_block_position,
@@ -144,34 +274,495 @@ impl InlineFormattingContext {
             }
         }
     }
+
+    pub(super) fn inline_content_sizes(&self, font_context: &FontContext) -> ContentSizes {
+        let mut state = ContentSizesState::new(font_context);
+        state.add_boxes(&self.inline_level_boxes);
+        state.forced_line_break();
+        state.sizes
+    }
+}
+
+/// Measures the words and lines of an inline formatting context, see `inline_content_sizes`.
+///
+/// Like line breaking, this uses the advance widths of glyphs before substitutions.
+struct ContentSizesState<'a> {
+    font_context: &'a FontContext,
+    sizes: ContentSizes,
+    /// Since the last soft wrap opportunity
+    current_word: Length,
+    /// Since the last forced line break
+    current_line: Length,
+    /// Collapsible spaces at the end of `current_line`, which hang when the line ends there
+    trailing_spaces: Length,
+    /// Up to the first forced line break, see `Leader::layout`
+    first_line: Option<Length>,
+}
+
+impl<'a> ContentSizesState<'a> {
+    fn new(font_context: &'a FontContext) -> Self {
+        ContentSizesState {
+            font_context,
+            sizes: ContentSizes::default(),
+            current_word: Length::zero(),
+            current_line: Length::zero(),
+            trailing_spaces: Length::zero(),
+            first_line: None,
+        }
+    }
+
+    fn add(&mut self, inline_size: Length) {
+        self.current_word += inline_size;
+        self.current_line += inline_size;
+        self.trailing_spaces = Length::zero();
+    }
+
+    fn soft_wrap_opportunity(&mut self) {
+        self.sizes.min_content.max_assign(self.current_word);
+        self.current_word = Length::zero();
+    }
+
+    fn forced_line_break(&mut self) {
+        self.soft_wrap_opportunity();
+        let line = self.current_line - self.trailing_spaces;
+        self.sizes.max_content.max_assign(line);
+        self.first_line.get_or_insert(line);
+        self.current_line = Length::zero();
+        self.trailing_spaces = Length::zero();
+    }
+
+    fn add_boxes(&mut self, boxes: &[Arc<InlineLevelBox>]) {
+        let font_context = self.font_context;
+        for box_ in boxes {
+            match &**box_ {
+                InlineLevelBox::InlineBox(inline) => {
+                    // Percentages of the containing block count as zero,
+                    // see `outer_inline_content_sizes`.
+                    let mode = inline.style.writing_mode();
+                    let zero = Length::zero();
+                    let padding = inline.style.padding(mode).percentages_relative_to(zero);
+                    let border = inline
+                        .style
+                        .border_width(mode)
+                        .percentages_relative_to(zero);
+                    let margin = inline
+                        .style
+                        .margin(mode)
+                        .percentages_relative_to(zero)
+                        .auto_is(Length::zero);
+                    let pbm = &(&padding + &border) + &margin;
+                    if inline.first_fragment {
+                        self.add(pbm.inline_start)
+                    }
+                    self.add_boxes(&inline.children);
+                    if inline.last_fragment {
+                        self.add(pbm.inline_end)
+                    }
+                }
+                InlineLevelBox::TextRun(run) => self.add_text(run),
+                InlineLevelBox::Atomic {
+                    style, contents, ..
+                } => {
+                    let sizes = outer_inline_content_sizes(style, || {
+                        contents.inline_content_sizes(style, font_context)
+                    });
+                    self.soft_wrap_opportunity();
+                    self.add(sizes.max_content);
+                    self.soft_wrap_opportunity();
+                }
+                InlineLevelBox::Math {
+                    style, contents, ..
+                } => {
+                    let sizes = outer_inline_content_sizes(style, || {
+                        contents.inline_content_sizes(style, font_context)
+                    });
+                    self.soft_wrap_opportunity();
+                    self.add(sizes.max_content);
+                    self.soft_wrap_opportunity();
+                }
+                // FIXME: a float adds to the max-content size of the line it is on.
+                InlineLevelBox::OutOfFlowFloatBox(float_box) => self
+                    .sizes
+                    .max_assign(&float_box.outer_inline_content_sizes(font_context)),
+                // A leader only fills space that is left over, it does not need any.
+                InlineLevelBox::Leader(_) => self.soft_wrap_opportunity(),
+                InlineLevelBox::OutOfFlowAbsolutelyPositionedBox(_)
+                | InlineLevelBox::RubyAnnotation(_)
+                | InlineLevelBox::OutsideMarker(_)
+                | InlineLevelBox::Clearance(_) => {}
+            }
+        }
+    }
+
+    fn add_text(&mut self, run: &TextRun) {
+        let font_context = self.font_context;
+        let style = &run.parent_style;
+        let font_size = style.font.font_size;
+        let wrap = style.text.white_space != WhiteSpace::Nowrap;
+        let fonts = font_context
+            .generic_family_instances(style.font.font_family.0, &style.font_variations());
+        for item in itemize(&run.text, false, &fonts, font_context) {
+            let advance = |ch| glyph_width(item.font, ch, font_context);
+            // Advances are summed in ems and scaled once per item, like line breaking does,
+            // so that rounding does not make text wrap in its own max-content size.
+            let mut word = (self.current_word, euclid::Length::new(0.));
+            let mut line = (self.current_line, euclid::Length::new(0.));
+            for ch in item.text.chars() {
+                let line_before = self.current_line;
+                match ch {
+                    ' ' if wrap => {
+                        self.soft_wrap_opportunity();
+                        word = (Length::zero(), euclid::Length::new(0.));
+                        line.1 += advance(' ');
+                    }
+                    '\n' => {
+                        self.forced_line_break();
+                        word = (Length::zero(), euclid::Length::new(0.));
+                        line = (Length::zero(), euclid::Length::new(0.));
+                    }
+                    ZERO_WIDTH_SPACE | SOFT_HYPHEN => {
+                        self.soft_wrap_opportunity();
+                        word = (Length::zero(), euclid::Length::new(0.));
+                    }
+                    _ => {
+                        word.1 += advance(ch);
+                        line.1 += advance(ch);
+                        self.trailing_spaces = Length::zero();
+                    }
+                }
+                self.current_word = word.0 + font_size * word.1;
+                self.current_line = line.0 + font_size * line.1;
+                if ch == ' ' && wrap {
+                    self.trailing_spaces += self.current_line - line_before
+                }
+            }
+        }
+    }
+}
+
+impl<'box_tree> InlineFormattingContextState<'box_tree, '_> {
+    fn line_is_empty(&self) -> bool {
+        self.inline_position == Length::zero()
+            && self.current_nesting_level.fragments_so_far.is_empty()
+            && self.partial_inline_boxes_stack.is_empty()
+    }
+
+    /// The inline size left for content on the current line. With `box-decoration-break: clone`,
+    /// the inline-end edges of the boxes that are still open are painted
+    /// on this line even if it breaks inside them, so room is kept for them.
+    fn available_inline_size(&self) -> Length {
+        let reserved = self
+            .partial_inline_boxes_stack
+            .iter()
+            .filter(|partial| partial.clones_decorations())
+            .fold(Length::zero(), |sum, partial| {
+                sum + partial.padding.inline_end
+                    + partial.border.inline_end
+                    + partial.margin.inline_end
+            });
+        self.line_boxes.current_line_inline_size - reserved - self.inline_position
+    }
+
+    /// The max-content inline size of the rest of the current line after the box
+    /// being laid out, up to the next forced line break. This includes the inline-end edges
+    /// of the inline boxes that are still open.
+    fn rest_of_line_inline_size(&self) -> Length {
+        let mut state = ContentSizesState::new(self.layout_context.font_context);
+        state.add_boxes(self.current_nesting_level.remaining_boxes.as_slice());
+        for partial in self.partial_inline_boxes_stack.iter().rev() {
+            if partial.last_box_tree_fragment {
+                state.add(
+                    partial.padding.inline_end
+                        + partial.border.inline_end
+                        + partial.margin.inline_end,
+                )
+            }
+            state.add_boxes(partial.parent_nesting_level.remaining_boxes.as_slice());
+        }
+        state.forced_line_break();
+        state.first_line.unwrap_or_else(Length::zero)
+    }
+
+    /// Find the inline range available to the next line, between floats.
+    /// Every line is at least as tall as the strut, so float areas are avoided
+    /// in that band even before the line’s own block size is known.
+    fn start_line(&mut self) {
+        let cbis = self.containing_block.inline_size;
+        let (start, size) = match &self.float_context {
+            Some(float_context) => float_context.line_box_range(
+                self.line_boxes.next_line_block_position,
+                self.strut_block_size,
+                cbis,
+            ),
+            None => (Length::zero(), cbis),
+        };
+        self.line_boxes.current_line_inline_start = start;
+        self.line_boxes.current_line_inline_size = size;
+        self.line_truncated = false;
+        self.line_has_content = false;
+        if self.ruby_base_start.is_some() {
+            self.ruby_base_start = Some(Length::zero())
+        }
+    }
+
+    /// Place an atomic inline with the given content size on the current line,
+    /// or at the start of the next line if it does not fit.
+    fn place_atomic(
+        &mut self,
+        tag: Option<dom::NodeId>,
+        style: &Arc<ComputedValues>,
+        children: Vec<Fragment>,
+        size: Vec2<Length>,
+        ascent: Length,
+    ) {
+        let cbis = self.containing_block.inline_size;
+        let mode = self.containing_block.mode;
+        let padding = style.padding(mode).percentages_relative_to(cbis);
+        let border = style.border_width(mode).percentages_relative_to(cbis);
+        let margin = style
+            .margin(mode)
+            .percentages_relative_to(cbis)
+            .auto_is(Length::zero);
+        let pbm = &(&padding + &border) + &margin;
+        let outer_inline_size = size.inline + pbm.inline_sum();
+        let available = self.available_inline_size();
+        if self.inline_position > Length::zero() && outer_inline_size > available {
+            self.break_line()
+        }
+        // FIXME: lines do not align baselines (there is no `vertical-align` yet),
+        // text is at the top of its line. Align the baseline of the atomic inline with
+        // the baseline that text of its parent would have, unless that would push it
+        // above the top of the line.
+        let font_context = self.layout_context.font_context;
+        let font = font_context.first_available_font(style.font.font_family.0);
+        let text_ascent = style.font.font_size * font.ascender();
+        let block_start = (text_ascent - ascent - pbm.block_start).max(Length::zero());
+        let content_rect = Rect {
+            start_corner: Vec2 {
+                inline: self.inline_position - self.current_nesting_level.inline_start
+                    + pbm.inline_start,
+                block: block_start + pbm.block_start,
+            },
+            size,
+        };
+        self.current_nesting_level
+            .max_block_size_of_fragments_so_far
+            .max_assign(content_rect.start_corner.block + content_rect.size.block + pbm.block_end);
+        self.inline_position += outer_inline_size;
+        self.line_has_content = true;
+        self.current_nesting_level
+            .fragments_so_far
+            .push(Fragment::Box(BoxFragment {
+                tag,
+                style: style.clone(),
+                children,
+                content_rect,
+                padding,
+                border,
+                margin,
+                block_margins_collapsed_with_children: CollapsedBlockMargins::zero(),
+            }))
+    }
+
+    /// Finish the current line, with the inline boxes it is in, and start a new one.
+    fn break_line(&mut self) {
+        let mut nesting_level = &mut self.current_nesting_level;
+        for partial in self.partial_inline_boxes_stack.iter_mut().rev() {
+            self.line_has_content |=
+                partial.finish_layout(nesting_level, &mut self.inline_position, true);
+            nesting_level = &mut partial.parent_nesting_level;
+        }
+        self.line_boxes
+            .finish_line(nesting_level, self.containing_block, self.line_has_content);
+        self.inline_position = Length::zero();
+        self.place_pending_floats();
+        self.start_line();
+        // Continue the inline boxes on the new line, outermost first
+        for partial in &mut self.partial_inline_boxes_stack {
+            if !partial.clones_decorations() {
+                partial.padding.inline_start = Length::zero();
+                partial.border.inline_start = Length::zero();
+                partial.margin.inline_start = Length::zero();
+            }
+            let start_edges = partial.padding.inline_start
+                + partial.border.inline_start
+                + partial.margin.inline_start;
+            partial.parent_nesting_level.inline_start = self.inline_position;
+            partial.start_corner.inline = start_edges;
+            self.inline_position += start_edges;
+        }
+        self.current_nesting_level.inline_start = self.inline_position;
+    }
+
+    fn place_float(&mut self, box_: &'box_tree FloatBox) {
+        if let Some(float_context) = &mut self.float_context {
+            let fragment = box_.layout(
+                self.layout_context,
+                self.containing_block,
+                self.tree_rank,
+                self.absolutely_positioned_fragments,
+                float_context,
+                self.line_boxes.next_line_block_position,
+            );
+            self.line_boxes.boxes.push(fragment)
+        }
+    }
+
+    /// Move the current line, which starts after a forced line break,
+    /// below the floats that `clear` applies to.
+    fn clear_floats(&mut self, clear: Clear) {
+        self.place_pending_floats();
+        if let Some(float_context) = &self.float_context {
+            if let Some(edge) = float_context.clearance_edge(clear) {
+                let edge = edge - float_context.containing_block_start_corner.block;
+                if edge > self.line_boxes.next_line_block_position {
+                    self.line_boxes.next_line_block_position = edge;
+                    self.start_line()
+                }
+            }
+        }
+    }
+
+    fn place_pending_floats(&mut self) {
+        for box_ in take(&mut self.pending_floats) {
+            self.place_float(box_)
+        }
+    }
 }
 
 impl LinesBoxes {
+    /// Without content, the line is a zero-height line box, see `line_has_content`.
     fn finish_line(
         &mut self,
         top_nesting_level: &mut InlineNestingLevelState,
         containing_block: &ContainingBlock,
+        has_content: bool,
     ) {
         let start_corner = Vec2 {
-            inline: Length::zero(),
+            inline: self.current_line_inline_start,
             block: self.next_line_block_position,
         };
-        let size = Vec2 {
-            inline: containing_block.inline_size,
-            block: std::mem::replace(
-                &mut top_nesting_level.max_block_size_of_fragments_so_far,
-                Length::zero(),
-            ),
+        let max_block_size = std::mem::replace(
+            &mut top_nesting_level.max_block_size_of_fragments_so_far,
+            Length::zero(),
+        );
+        let mut size = Vec2 {
+            inline: self.current_line_inline_size,
+            block: if has_content {
+                max_block_size
+            } else {
+                Length::zero()
+            },
         };
+        let mut children = take(&mut top_nesting_level.fragments_so_far);
+        reorder_line(&mut children, paragraph_level(containing_block));
+        if !self.ruby_annotations.is_empty() {
+            // Make room for annotations above the rest of the line
+            // https://drafts.csswg.org/css-ruby-1/#line-height
+            let annotations_block_size =
+                std::mem::replace(&mut self.ruby_annotations_block_size, Length::zero());
+            let base = Fragment::Anonymous(AnonymousFragment {
+                children,
+                rect: Rect {
+                    start_corner: Vec2 {
+                        inline: Length::zero(),
+                        block: annotations_block_size,
+                    },
+                    size: size.clone(),
+                },
+                mode: containing_block.mode,
+            });
+            children = take(&mut self.ruby_annotations);
+            children.push(base);
+            size.block += annotations_block_size;
+        }
         self.next_line_block_position += size.block;
         self.boxes.push(Fragment::Anonymous(AnonymousFragment {
-            children: take(&mut top_nesting_level.fragments_so_far),
+            children,
             rect: Rect { start_corner, size },
             mode: containing_block.mode,
         }))
     }
 }
 
+/// The embedding level of the paragraph that an inline formatting context lays out,
+/// from the direction of its block container: https://unicode.org/reports/tr9/#BD4
+fn paragraph_level(containing_block: &ContainingBlock) -> u8 {
+    (containing_block.mode.1 == Direction::Rtl) as u8
+}
+
+/// Reorder the fragments of a line from the logical order they were laid out in
+/// to their visual order, by reversing runs at each embedding level higher than
+/// the paragraph’s: https://unicode.org/reports/tr9/#L2
+///
+/// Positions are flow-relative, so a right-to-left paragraph is already reversed as a whole.
+/// The glyphs of right-to-left text fragments are reversed when they are shaped.
+///
+/// FIXME: levels are resolved in each text run on its own rather than in the whole paragraph,
+/// so neutral characters at the edges of a run take the paragraph’s direction.
+/// An inline box stays in one piece at the lowest level of its contents,
+/// even if that splits a run at a higher level. Atomic inlines are at the paragraph level
+/// instead of that of U+FFFC around them, and brackets are not mirrored
+/// (https://unicode.org/reports/tr9/#L4).
+fn reorder_line(fragments: &mut [Fragment], paragraph_level: u8) -> u8 {
+    let mut levels: Vec<u8> = fragments
+        .iter_mut()
+        .map(|fragment| match fragment {
+            Fragment::Text(t) => t.bidi_level,
+            Fragment::Box(b) if b.is_inline_box() => reorder_line(&mut b.children, paragraph_level),
+            _ => paragraph_level,
+        })
+        .collect();
+    let max_level = levels.iter().copied().max().unwrap_or(paragraph_level);
+    for level in (paragraph_level + 1..=max_level).rev() {
+        let mut start = 0;
+        while start < fragments.len() {
+            if levels[start] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..fragments.len())
+                .find(|&i| levels[i] < level)
+                .unwrap_or(fragments.len());
+            reverse_run(&mut fragments[start..end]);
+            levels[start..end].reverse();
+            start = end;
+        }
+    }
+    levels.into_iter().min().unwrap_or(paragraph_level)
+}
+
+/// Mirror the positions of fragments within the inline extent that they span together,
+/// and reverse their order.
+fn reverse_run(run: &mut [Fragment]) {
+    let mut positions: Vec<_> = run.iter_mut().filter_map(inline_extent).collect();
+    let start = positions.iter().map(|p| p.1).min().unwrap_or_default();
+    let end = positions.iter().map(|p| p.2).max().unwrap_or_default();
+    for (position, item_start, item_end) in &mut positions {
+        **position += start + end - *item_end - *item_start
+    }
+    run.reverse()
+}
+
+/// The inline position of a fragment on its line,
+/// and where the inline extent of its margin box starts and ends.
+fn inline_extent(fragment: &mut Fragment) -> Option<(&mut Length, Length, Length)> {
+    let (rect, before, after) = match fragment {
+        Fragment::Box(b) => {
+            let pbm = &(&b.padding + &b.border) + &b.margin;
+            (&mut b.content_rect, pbm.inline_start, pbm.inline_end)
+        }
+        Fragment::Anonymous(a) => (&mut a.rect, Length::zero(), Length::zero()),
+        Fragment::Text(t) => (&mut t.content_rect, Length::zero(), Length::zero()),
+        Fragment::Checkbox(c) => (&mut c.content_rect, Length::zero(), Length::zero()),
+        Fragment::Image(i) => (&mut i.content_rect, Length::zero(), Length::zero()),
+        Fragment::FormField(_) => return None,
+    };
+    let start = rect.start_corner.inline - before;
+    let end = rect.start_corner.inline + rect.size.inline + after;
+    Some((&mut rect.start_corner.inline, start, end))
+}
+
 impl InlineBox {
     fn start_layout<'box_tree>(
         &'box_tree self,
@@ -179,13 +770,14 @@ impl InlineBox {
     ) -> PartialInlineBoxFragment<'box_tree> {
         let style = self.style.clone();
         let cbis = ifc.containing_block.inline_size;
-        let mut padding = style.padding().percentages_relative_to(cbis);
-        let mut border = style.border_width().percentages_relative_to(cbis);
+        let mode = ifc.containing_block.mode;
+        let mut padding = style.padding(mode).percentages_relative_to(cbis);
+        let mut border = style.border_width(mode).percentages_relative_to(cbis);
         let mut margin = style
-            .margin()
+            .margin(mode)
             .percentages_relative_to(cbis)
             .auto_is(Length::zero);
-        if self.first_fragment {
+        if self.first_fragment || style.border.box_decoration_break == BoxDecorationBreak::Clone {
             ifc.inline_position += padding.inline_start + border.inline_start + margin.inline_start;
         } else {
             padding.inline_start = Length::zero();
@@ -196,12 +788,9 @@ impl InlineBox {
             block: padding.block_start + border.block_start + margin.block_start,
             inline: ifc.inline_position - ifc.current_nesting_level.inline_start,
         };
-        start_corner += &relative_adjustement(
-            &style,
-            ifc.containing_block.inline_size,
-            ifc.containing_block.block_size,
-        );
+        start_corner += &relative_adjustement(&style, ifc.containing_block);
         PartialInlineBoxFragment {
+            tag: self.tag,
             style,
             start_corner,
             padding,
@@ -221,20 +810,186 @@ impl InlineBox {
     }
 }
 
+impl InlineBox {
+    /// Lay out an outside list marker on one line,
+    /// ending where the first line of the list item starts.
+    ///
+    /// FIXME: like ruby annotations, only text directly in the marker is supported.
+    /// A marker in the way of a float should also move with the start of the line.
+    /// Unlike other naively shaped boxes, a marker is shaped with font fallback
+    /// and with its font features, such as the `tabular-nums` of the user-agent stylesheet.
+    fn layout_outside_marker(&self, ifc: &mut InlineFormattingContextState) {
+        let font_context = ifc.layout_context.font_context;
+        let right_to_left = self.style.inherited_box.direction == Direction::Rtl;
+        let fonts = font_context
+            .generic_family_instances(self.style.font.font_family.0, &self.style.font_variations());
+        let features = enabled_features(&self.style);
+        let font_size = self.style.font.font_size;
+        let text = self.direct_text();
+        // Items whose glyphs cannot be read from a malformed font are left out.
+        let segments: Vec<_> = itemize(&text, right_to_left, &fonts, font_context)
+            .filter_map(|item| {
+                let substitutions = font_context.substitutions(item.font, &features);
+                shape_item(&item, &substitutions, font_context).ok()
+            })
+            .collect();
+        let total_inline_size = segments.iter().fold(Length::zero(), |size, shaped| {
+            size + font_size * shaped.advance_width
+        });
+        let line_height = font_size.0 * 1.2;
+        if !text.is_empty() {
+            // Like a browser, give an empty list item the height of its marker
+            ifc.line_has_content = true;
+            ifc.current_nesting_level
+                .max_block_size_of_fragments_so_far
+                .max_assign(line_height);
+        }
+        let mut inline_start =
+            ifc.inline_position - ifc.current_nesting_level.inline_start - total_inline_size;
+        for shaped in segments {
+            let inline_size = font_size * shaped.advance_width;
+            let content_rect = Rect {
+                start_corner: Vec2 {
+                    block: Length::zero(),
+                    inline: inline_start,
+                },
+                size: Vec2 {
+                    block: line_height,
+                    inline: inline_size,
+                },
+            };
+            inline_start += inline_size;
+            ifc.current_nesting_level
+                .fragments_so_far
+                .push(Fragment::Text(TextFragment {
+                    parent_style: self.style.clone(),
+                    content_rect,
+                    font_size: font_size.0,
+                    text: shaped,
+                    bidi_level: paragraph_level(ifc.containing_block),
+                }));
+        }
+    }
+
+    /// The text directly in this box, shaped in its first font without substitutions.
+    fn shape_text_naively(&self, font_context: &FontContext) -> ShapedSegment {
+        let font = font_context.first_available_font(self.style.font.font_family.0);
+        let text = self.direct_text();
+        let item = Item {
+            text: &text,
+            bidi_level: 0,
+            font,
+        };
+        shape_item(&item, &Substitutions::default(), font_context)
+            .unwrap_or_else(|_| ShapedSegment::new_with_naive_shaping(font.clone()))
+    }
+
+    /// The text of the text runs directly in this box, without forced line breaks
+    /// or break opportunities.
+    fn direct_text(&self) -> String {
+        let mut text = String::new();
+        for child in &self.children {
+            if let InlineLevelBox::TextRun(run) = &**child {
+                text.extend(
+                    run.text
+                        .chars()
+                        .filter(|&ch| !matches!(ch, '\n' | ZERO_WIDTH_SPACE | SOFT_HYPHEN)),
+                )
+            }
+        }
+        text
+    }
+
+    /// Lay out a ruby annotation on one line, centered above its base:
+    /// the content of the line since the start of the ruby container or the previous annotation.
+    ///
+    /// FIXME: only text directly in the annotation is supported,
+    /// and a base narrower than its annotation should be spaced out to its width.
+    fn layout_ruby_annotation(&self, ifc: &mut InlineFormattingContextState) {
+        let base_start = ifc.ruby_base_start.unwrap_or(ifc.inline_position);
+        let base_end = ifc.inline_position;
+        let shaped = self.shape_text_naively(ifc.layout_context.font_context);
+        let font_size = self.style.font.font_size;
+        let inline_size = font_size * shaped.advance_width;
+        let line_height = font_size.0 * 1.2;
+        let content_rect = Rect {
+            start_corner: Vec2 {
+                block: Length::zero(),
+                inline: base_start + (base_end - base_start - inline_size) / 2.,
+            },
+            size: Vec2 {
+                block: line_height,
+                inline: inline_size,
+            },
+        };
+        ifc.line_boxes
+            .ruby_annotations_block_size
+            .max_assign(line_height);
+        ifc.line_boxes
+            .ruby_annotations
+            .push(Fragment::Text(TextFragment {
+                parent_style: self.style.clone(),
+                content_rect,
+                font_size: font_size.0,
+                text: shaped,
+                bidi_level: paragraph_level(ifc.containing_block),
+            }));
+        ifc.ruby_base_start = Some(base_end);
+    }
+}
+
+/// Split `text` into runs of characters that small-caps synthesis makes small or not:
+/// lowercase letters, and also uppercase letters if `all`.
+fn small_caps_runs(text: &str, all: bool) -> impl Iterator<Item = (&str, bool)> {
+    let is_small = move |ch: char| ch.is_lowercase() || (all && ch.is_uppercase());
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let small = is_small(rest.chars().next()?);
+        let end = rest.find(|ch| is_small(ch) != small).unwrap_or(rest.len());
+        let (run, after) = rest.split_at(end);
+        rest = after;
+        Some((run, small))
+    })
+}
+
+fn is_ruby_container(style: &ComputedValues) -> bool {
+    match style.box_.display {
+        Display::GeneratingBox(DisplayGeneratingBox::OutsideInside { inside, .. }) => {
+            inside == DisplayInside::Ruby
+        }
+        _ => false,
+    }
+}
+
 impl<'box_tree> PartialInlineBoxFragment<'box_tree> {
+    /// With `box-decoration-break: clone`, each fragment of the box
+    /// has its own inline-start and inline-end margins, padding and borders,
+    /// instead of only the first and last fragments.
+    fn clones_decorations(&self) -> bool {
+        self.style.border.box_decoration_break == BoxDecorationBreak::Clone
+    }
+
+    /// Returns whether the fragment has non-zero margins, padding or borders,
+    /// which give its line a height even when the box is empty.
     fn finish_layout(
         &mut self,
         nesting_level: &mut InlineNestingLevelState,
         inline_position: &mut Length,
         at_line_break: bool,
-    ) {
+    ) -> bool {
+        // https://drafts.csswg.org/css2/visudet.html#inline-non-replaced
+        // The content area is based on the font, like that of text.
+        let line_height = self.style.font.font_size.0 * 1.2;
         let mut fragment = BoxFragment {
+            tag: self.tag,
             style: self.style.clone(),
             children: take(&mut nesting_level.fragments_so_far),
             content_rect: Rect {
                 size: Vec2 {
-                    inline: *inline_position - self.start_corner.inline,
-                    block: nesting_level.max_block_size_of_fragments_so_far,
+                    inline: *inline_position - nesting_level.inline_start,
+                    block: nesting_level
+                        .max_block_size_of_fragments_so_far
+                        .max(line_height),
                 },
                 start_corner: self.start_corner.clone(),
             },
@@ -244,7 +999,7 @@ impl<'box_tree> PartialInlineBoxFragment<'box_tree> {
             block_margins_collapsed_with_children: CollapsedBlockMargins::zero(),
         };
         let last_fragment = self.last_box_tree_fragment && !at_line_break;
-        if last_fragment {
+        if last_fragment || self.clones_decorations() {
             *inline_position += fragment.padding.inline_end
                 + fragment.border.inline_end
                 + fragment.margin.inline_end;
@@ -253,6 +1008,17 @@ impl<'box_tree> PartialInlineBoxFragment<'box_tree> {
             fragment.border.inline_end = Length::zero();
             fragment.margin.inline_end = Length::zero();
         }
+        let has_edges = [&fragment.padding, &fragment.border, &fragment.margin]
+            .iter()
+            .any(|sides| {
+                let edges = [
+                    sides.inline_start,
+                    sides.inline_end,
+                    sides.block_start,
+                    sides.block_end,
+                ];
+                edges.iter().any(|&edge| edge != Length::zero())
+            });
         self.parent_nesting_level
             .max_block_size_of_fragments_so_far
             .max_assign(
@@ -264,82 +1030,356 @@ impl<'box_tree> PartialInlineBoxFragment<'box_tree> {
         self.parent_nesting_level
             .fragments_so_far
             .push(Fragment::Box(fragment));
+        has_edges
+    }
+}
+
+/// The OpenType features to apply for the `font-*` properties of this style.
+fn enabled_features(style: &ComputedValues) -> Vec<[u8; 4]> {
+    style
+        .font_features()
+        .into_iter()
+        .filter(|&(_, value)| value != 0)
+        .map(|(tag, _)| tag)
+        .collect()
+}
+
+/// Shape all of `item` on one line, with glyph lookups cached in `font_context`,
+/// for boxes that are not broken into lines like `TextRun::layout` does.
+///
+/// Returns an error for a font whose glyphs cannot be read.
+pub(in crate::layout) fn shape_item(
+    item: &Item,
+    substitutions: &Substitutions,
+    font_context: &FontContext,
+) -> Result<ShapedSegment, FontError> {
+    let mut shaped = ShapedSegment::new_with_naive_shaping(item.font.clone());
+    for ch in item.text.chars() {
+        shaped.append_char_in(ch, font_context)?
+    }
+    shaped.substitute(substitutions);
+    Ok(shaped)
+}
+
+/// Append the glyph of `ch`, leaving it out like `shape_item` leaves out items
+/// if it cannot be read from a malformed font.
+fn append_char(shaped: &mut ShapedSegment, ch: char, font_context: &FontContext) {
+    let _ = shaped.append_char_in(ch, font_context);
+}
+
+/// The advance width of the glyph of `ch`, zero for a glyph left out by `append_char`.
+fn glyph_width(font: &Arc<Font>, ch: char, font_context: &FontContext) -> euclid::Length<f32, Em> {
+    font_context
+        .glyph(font, ch)
+        .map_or(euclid::Length::new(0.), |(_, advance_width)| advance_width)
+}
+
+/// The text that `text-overflow: ellipsis` ends a line with:
+/// three periods in a font that does not have the ellipsis character.
+///
+/// https://drafts.csswg.org/css-overflow-3/#text-overflow
+fn ellipsis(font: &Arc<Font>, font_context: &FontContext) -> &'static str {
+    match font_context.glyph(font, ELLIPSIS) {
+        Ok((GlyphId(0), _)) | Err(_) => "...",
+        Ok(_) => "…",
     }
 }
 
 impl TextRun {
     fn layout(&self, ifc: &mut InlineFormattingContextState) {
-        let available = ifc.containing_block.inline_size - ifc.inline_position;
-        let mut chars = self.text.chars();
+        let font_context = ifc.layout_context.font_context;
+        // Without `unicode-bidi`, the `direction` of inline boxes does not change the levels.
+        let right_to_left = ifc.containing_block.mode.1 == Direction::Rtl;
+        // FIXME: select faces of the family with `font-weight`, `font-stretch` and `font-style`.
+        let fonts = font_context.generic_family_instances(
+            self.parent_style.font.font_family.0,
+            &self.parent_style.font_variations(),
+        );
+        let features = enabled_features(&self.parent_style);
+        // FIXME: when an item overflows the line, break at the last opportunity
+        // of a previous item (such as a space before a change of script) instead.
+        let font_size = self.parent_style.font.font_size;
+        let synthesized_caps = self
+            .parent_style
+            .font
+            .font_variant_caps
+            .synthesized_feature()
+            .filter(|_| self.parent_style.font.font_synthesis.small_caps);
+        for item in itemize(&self.text, right_to_left, &fonts, font_context) {
+            let substitutions = font_context.substitutions(item.font, &features);
+            match synthesized_caps {
+                // https://drafts.csswg.org/css-fonts-4/#small-caps-synthesis
+                Some((feature, all))
+                    if font_context.substitutions(item.font, &[feature]).is_empty() =>
+                {
+                    // Scaled-down uppercase glyphs, about as small as browsers make them
+                    let small_size = FontSize(font_size.0 * 0.7);
+                    for (text, small) in small_caps_runs(item.text, all) {
+                        if small {
+                            let uppercase = text.to_uppercase();
+                            let item = Item {
+                                text: &uppercase,
+                                bidi_level: item.bidi_level,
+                                font: item.font,
+                            };
+                            self.layout_item(ifc, &item, &substitutions, small_size)
+                        } else {
+                            let item = Item { text, ..item };
+                            self.layout_item(ifc, &item, &substitutions, font_size)
+                        }
+                    }
+                }
+                _ => self.layout_item(ifc, &item, &substitutions, font_size),
+            }
+        }
+    }
+
+    fn layout_item(
+        &self,
+        ifc: &mut InlineFormattingContextState,
+        item: &Item,
+        substitutions: &Substitutions,
+        font_size: FontSize,
+    ) {
+        let font_context = ifc.layout_context.font_context;
+        let mut chars = item.text.chars();
+        let wrap = self.parent_style.text.white_space != WhiteSpace::Nowrap;
         loop {
-            let mut shaped = ShapedSegment::new_with_naive_shaping(BITSTREAM_VERA_SANS.clone());
+            // FIXME: when even the first word does not fit a line shortened by floats,
+            // the line should move down until it fits or there are no more floats.
+            // https://drafts.csswg.org/css2/visuren.html#floats
+            let available = ifc.available_inline_size();
+            let mut shaped = ShapedSegment::new_with_naive_shaping(item.font.clone());
+            let ellipsis = ellipsis(&shaped.font, font_context);
+            let ellipsis_width = if ifc.text_overflow_ellipsis {
+                ellipsis
+                    .chars()
+                    .map(|ch| glyph_width(&shaped.font, ch, font_context))
+                    .fold(euclid::Length::new(0.), |total, width| total + width)
+            } else {
+                euclid::Length::new(0.)
+            };
             let mut last_break_opportunity = None;
+            // Where to truncate this text if the line overflows: up to there, an ellipsis fits.
+            let mut last_ellipsis_opportunity = shaped.save();
+            let mut forced_break = false;
+            // Break opportunities that are not rendered, but still give the line its height
+            let mut has_invisible_content = false;
             loop {
+                if ifc.line_truncated {
+                    // Hide the rest of the line, up to the next forced line break.
+                    forced_break = chars.by_ref().any(|ch| ch == '\n');
+                    break;
+                }
                 let next = chars.next();
-                if matches!(next, Some(' ') | None) {
-                    let inline_size = self.parent_style.font.font_size * shaped.advance_width;
+                // https://drafts.csswg.org/css-text-3/#white-space-phase-2
+                // Collapsible spaces at the start of a line are removed.
+                if next == Some(' ') && shaped.glyphs.is_empty() && !ifc.line_has_content {
+                    continue;
+                }
+                if matches!(
+                    next,
+                    Some(' ') | Some('\n') | Some(ZERO_WIDTH_SPACE) | Some(SOFT_HYPHEN) | None
+                ) && wrap
+                {
+                    let mut advance_width = shaped.advance_width;
+                    if next == Some(SOFT_HYPHEN) {
+                        advance_width += glyph_width(&shaped.font, '-', font_context)
+                    }
+                    let inline_size = font_size * advance_width;
                     if inline_size > available {
-                        if let Some((state, iter)) = last_break_opportunity.take() {
-                            shaped.restore(&state);
-                            chars = iter;
+                        match last_break_opportunity.take() {
+                            Some((state, iter, hyphenate)) => {
+                                shaped.restore(&state);
+                                if hyphenate {
+                                    append_char(&mut shaped, '-', font_context)
+                                }
+                                chars = iter;
+                                break;
+                            }
+                            // Not breaking early, but still breaking at this newline below
+                            None if next == Some('\n') => {}
+                            None => {
+                                if next == Some(SOFT_HYPHEN) {
+                                    append_char(&mut shaped, '-', font_context)
+                                }
+                                break;
+                            }
                         }
-                        break;
                     }
                 }
-                if let Some(ch) = next {
-                    if ch == ' ' {
-                        last_break_opportunity = Some((shaped.save(), chars.clone()))
+                // FIXME: also hide inline boxes and atomic inlines after the ellipsis.
+                if ifc.text_overflow_ellipsis && last_break_opportunity.is_none() {
+                    if font_size * (shaped.advance_width + ellipsis_width) <= available {
+                        last_ellipsis_opportunity = shaped.save()
                     }
-                    shaped.append_char(ch).unwrap()
-                } else {
-                    break;
+                    let rendered = match next {
+                        Some(' ') if wrap => None,
+                        Some('\n') | Some(ZERO_WIDTH_SPACE) | Some(SOFT_HYPHEN) | None => None,
+                        Some(ch) => Some(ch),
+                    };
+                    if let Some(ch) = rendered {
+                        let glyph_width = glyph_width(&shaped.font, ch, font_context);
+                        if font_size * (shaped.advance_width + glyph_width) > available {
+                            shaped.restore(&last_ellipsis_opportunity);
+                            for ch in ellipsis.chars() {
+                                append_char(&mut shaped, ch, font_context)
+                            }
+                            ifc.line_truncated = true;
+                            forced_break = chars.by_ref().any(|ch| ch == '\n');
+                            break;
+                        }
+                    }
+                }
+                match next {
+                    // From `<br>`, see `handle_forced_line_break`
+                    Some('\n') => {
+                        forced_break = true;
+                        break;
+                    }
+                    Some(' ') if wrap => {
+                        last_break_opportunity = Some((shaped.save(), chars.clone(), false));
+                        append_char(&mut shaped, ' ', font_context)
+                    }
+                    // Break opportunities that are not rendered unless broken
+                    Some(ch @ ZERO_WIDTH_SPACE) | Some(ch @ SOFT_HYPHEN) => {
+                        has_invisible_content = true;
+                        if wrap {
+                            last_break_opportunity =
+                                Some((shaped.save(), chars.clone(), ch == SOFT_HYPHEN))
+                        }
+                    }
+                    Some(ch) => append_char(&mut shaped, ch, font_context),
+                    None => break,
                 }
             }
-            let inline_size = self.parent_style.font.font_size * shaped.advance_width;
-            // https://www.w3.org/TR/CSS2/visudet.html#propdef-line-height
-            // 'normal':
-            // “set the used value to a "reasonable" value based on the font of the element.”
-            let line_height = self.parent_style.font.font_size.0 * 1.2;
-            let content_rect = Rect {
-                start_corner: Vec2 {
-                    block: Length::zero(),
-                    inline: ifc.inline_position - ifc.current_nesting_level.inline_start,
-                },
-                size: Vec2 {
-                    block: line_height,
-                    inline: inline_size,
-                },
-            };
-            ifc.inline_position += inline_size;
-            ifc.current_nesting_level
-                .max_block_size_of_fragments_so_far
-                .max_assign(line_height);
-            ifc.current_nesting_level
-                .fragments_so_far
-                .push(Fragment::Text(TextFragment {
-                    parent_style: self.parent_style.clone(),
-                    content_rect,
-                    text: shaped,
-                }));
-            if chars.as_str().is_empty() {
+            // FIXME: line breaking measures glyphs before substitution,
+            // so a line can overflow when substitutes are wider.
+            shaped.substitute(substitutions);
+            if item.bidi_level % 2 == 1 {
+                // The fragments themselves are reordered in `reorder_line`.
+                shaped.glyphs.reverse()
+            }
+            // A line with nothing but collapsed spaces gets no fragment for them,
+            // so that it can be a zero-height line box. A forced line break is preserved.
+            if !shaped.glyphs.is_empty() || has_invisible_content || forced_break {
+                let inline_size = font_size * shaped.advance_width;
+                // https://www.w3.org/TR/CSS2/visudet.html#propdef-line-height
+                // 'normal':
+                // “set the used value to a "reasonable" value based on the font of the element.”
+                let line_height = self.parent_style.font.font_size.0 * 1.2;
+                let content_rect = Rect {
+                    start_corner: Vec2 {
+                        block: Length::zero(),
+                        inline: ifc.inline_position - ifc.current_nesting_level.inline_start,
+                    },
+                    size: Vec2 {
+                        block: line_height,
+                        inline: inline_size,
+                    },
+                };
+                ifc.inline_position += inline_size;
+                ifc.line_has_content = true;
+                ifc.current_nesting_level
+                    .max_block_size_of_fragments_so_far
+                    .max_assign(line_height);
+                ifc.current_nesting_level
+                    .fragments_so_far
+                    .push(Fragment::Text(TextFragment {
+                        parent_style: self.parent_style.clone(),
+                        content_rect,
+                        font_size: font_size.0,
+                        text: shaped,
+                        bidi_level: item.bidi_level,
+                    }));
+            }
+            if chars.as_str().is_empty() && !forced_break {
                 break;
             } else {
-                // New line
-                ifc.current_nesting_level.inline_start = Length::zero();
-                let mut nesting_level = &mut ifc.current_nesting_level;
-                for partial in ifc.partial_inline_boxes_stack.iter_mut().rev() {
-                    partial.finish_layout(nesting_level, &mut ifc.inline_position, true);
-                    partial.start_corner.inline = Length::zero();
-                    partial.padding.inline_start = Length::zero();
-                    partial.border.inline_start = Length::zero();
-                    partial.margin.inline_start = Length::zero();
-                    partial.parent_nesting_level.inline_start = Length::zero();
-                    nesting_level = &mut partial.parent_nesting_level;
+                ifc.break_line();
+                if chars.as_str().is_empty() {
+                    break;
                 }
-                ifc.line_boxes
-                    .finish_line(nesting_level, ifc.containing_block);
-                ifc.inline_position = Length::zero();
             }
         }
     }
 }
+
+impl Leader {
+    fn layout(&self, ifc: &mut InlineFormattingContextState) {
+        if ifc.line_truncated {
+            return;
+        }
+        let font_context = ifc.layout_context.font_context;
+        let right_to_left = ifc.containing_block.mode.1 == Direction::Rtl;
+        let fonts = font_context.generic_family_instances(
+            self.parent_style.font.font_family.0,
+            &self.parent_style.font_variations(),
+        );
+        // FIXME: a pattern with characters in more than one font uses the first one.
+        let item = match itemize(&self.pattern, right_to_left, &fonts, font_context).next() {
+            Some(item) => item,
+            None => return,
+        };
+        let font_size = self.parent_style.font.font_size;
+        let pattern_width = font_size
+            * self
+                .pattern
+                .chars()
+                .map(|ch| glyph_width(item.font, ch, font_context))
+                .fold(euclid::Length::new(0.), |total, width| total + width);
+        // FIXME: when the content after the leader does not fit on this line either,
+        // the leader should end this line and that content start the next one.
+        let inline_size =
+            (ifc.available_inline_size() - ifc.rest_of_line_inline_size()).max(Length::zero());
+        if pattern_width <= Length::zero() || inline_size == Length::zero() {
+            return;
+        }
+        // https://drafts.csswg.org/css-content-3/#leader-function
+        // Copies of the pattern are aligned to multiples of its width from the line start,
+        // so that leaders on consecutive lines line up.
+        let start = pattern_width * (ifc.inline_position.px() / pattern_width.px()).ceil();
+        let end = ifc.inline_position + inline_size;
+        let count = ((end - start).px() / pattern_width.px()).floor().max(0.) as usize;
+        let mut shaped = ShapedSegment::new_with_naive_shaping(item.font.clone());
+        for _ in 0..count {
+            for ch in self.pattern.chars() {
+                append_char(&mut shaped, ch, font_context)
+            }
+        }
+        if item.bidi_level % 2 == 1 {
+            shaped.glyphs.reverse()
+        }
+        let line_height = font_size.0 * 1.2;
+        let content_rect = Rect {
+            start_corner: Vec2 {
+                block: Length::zero(),
+                inline: start - ifc.current_nesting_level.inline_start,
+            },
+            size: Vec2 {
+                block: line_height,
+                inline: font_size * shaped.advance_width,
+            },
+        };
+        ifc.inline_position += inline_size;
+        ifc.line_has_content = true;
+        ifc.current_nesting_level
+            .max_block_size_of_fragments_so_far
+            .max_assign(line_height);
+        ifc.current_nesting_level
+            .fragments_so_far
+            .push(Fragment::Text(TextFragment {
+                parent_style: self.parent_style.clone(),
+                content_rect,
+                font_size: font_size.0,
+                text: shaped,
+                bidi_level: item.bidi_level,
+            }));
+    }
+}
+
+/// Appended to lines truncated by `text-overflow: ellipsis`
+const ELLIPSIS: char = '…';
+
+/// `&shy;`, a break opportunity that renders as a hyphen if the line breaks there
+const SOFT_HYPHEN: char = '\u{AD}';
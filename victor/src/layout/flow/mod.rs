@@ -28,12 +28,14 @@ pub(super) enum BlockContainer {
 #[derive(Debug)]
 pub(super) enum BlockLevelBox {
     SameFormattingContextBlock {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         contents: BlockContainer,
     },
     OutOfFlowAbsolutelyPositionedBox(AbsolutelyPositionedBox),
     OutOfFlowFloatBox(FloatBox),
     Independent {
+        tag: Option<dom::NodeId>,
         style: Arc<ComputedValues>,
         contents: IndependentFormattingContext,
     },
@@ -51,22 +53,22 @@ struct CollapsibleWithParentStartMargin(bool);
 impl BlockFormattingContext {
     pub(super) fn layout<'a>(
         &'a self,
+        layout_context: &LayoutContext,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
     ) -> FlowChildren {
-        let mut float_context;
-        let float_context = if self.contains_floats {
-            float_context = FloatContext::new();
-            Some(&mut float_context)
+        let mut float_context = if self.contains_floats {
+            Some(FloatContext::new())
         } else {
             None
         };
         let mut flow_children = self.contents.layout(
+            layout_context,
             containing_block,
             tree_rank,
             absolutely_positioned_fragments,
-            float_context,
+            float_context.as_mut(),
             CollapsibleWithParentStartMargin(false),
         );
         flow_children.block_size += flow_children.collapsible_margins_in_children.end.solve();
@@ -74,6 +76,13 @@ impl BlockFormattingContext {
             .collapsible_margins_in_children
             .collapsed_through = false;
         flow_children.collapsible_margins_in_children.end = CollapsedMargin::zero();
+        // https://drafts.csswg.org/css2/visudet.html#root-height
+        // “In addition, if the element has any floating descendants whose bottom margin edge
+        //  is below the element's bottom content edge, then the height is increased
+        //  to include those edges.”
+        if let Some(lowest) = float_context.and_then(|c| c.lowest_float_edge()) {
+            flow_children.block_size.max_assign(lowest)
+        }
         flow_children
     }
 }
@@ -81,6 +90,7 @@ impl BlockFormattingContext {
 impl BlockContainer {
     fn layout<'a>(
         &'a self,
+        layout_context: &LayoutContext,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
@@ -89,6 +99,7 @@ impl BlockContainer {
     ) -> FlowChildren {
         match self {
             BlockContainer::BlockLevelBoxes(child_boxes) => layout_block_level_children(
+                layout_context,
                 child_boxes,
                 containing_block,
                 tree_rank,
@@ -96,14 +107,51 @@ impl BlockContainer {
                 float_context,
                 collapsible_with_parent_start_margin,
             ),
-            BlockContainer::InlineFormattingContext(ifc) => {
-                ifc.layout(containing_block, tree_rank, absolutely_positioned_fragments)
+            BlockContainer::InlineFormattingContext(ifc) => ifc.layout(
+                layout_context,
+                containing_block,
+                tree_rank,
+                absolutely_positioned_fragments,
+                float_context,
+            ),
+        }
+    }
+}
+
+impl BlockContainer {
+    pub(super) fn inline_content_sizes(&self, font_context: &FontContext) -> ContentSizes {
+        match self {
+            BlockContainer::BlockLevelBoxes(child_boxes) => {
+                let mut sizes = ContentSizes::default();
+                for box_ in child_boxes {
+                    let child_sizes = match &**box_ {
+                        BlockLevelBox::SameFormattingContextBlock {
+                            style, contents, ..
+                        } => outer_inline_content_sizes(style, || {
+                            contents.inline_content_sizes(font_context)
+                        }),
+                        BlockLevelBox::Independent {
+                            style, contents, ..
+                        } => outer_inline_content_sizes(style, || {
+                            contents.inline_content_sizes(font_context, style)
+                        }),
+                        // FIXME: floats next to each other add up in the max-content size.
+                        BlockLevelBox::OutOfFlowFloatBox(float_box) => {
+                            float_box.outer_inline_content_sizes(font_context)
+                        }
+                        BlockLevelBox::OutOfFlowAbsolutelyPositionedBox(_) => continue,
+                    };
+                    sizes.max_assign(&child_sizes)
+                }
+                sizes
             }
+            BlockContainer::InlineFormattingContext(ifc) => ifc.inline_content_sizes(font_context),
         }
     }
 }
 
 fn layout_block_level_children<'a>(
+    layout_context: &LayoutContext,
     child_boxes: &'a [Arc<BlockLevelBox>],
     containing_block: &ContainingBlock,
     tree_rank: usize,
@@ -167,6 +215,12 @@ fn layout_block_level_children<'a>(
         current_block_direction_position: Length,
     }
 
+    // Stop at the next level of the tree when cancelled, see `Document::set_cancellation_token`.
+    let child_boxes = if layout_context.is_cancelled() {
+        &[]
+    } else {
+        child_boxes
+    };
     let abspos_so_far = absolutely_positioned_fragments.len();
     let mut placement_state = PlacementState {
         next_in_flow_margin_collapses_with_parent_start_margin:
@@ -184,17 +238,130 @@ fn layout_block_level_children<'a>(
             .iter()
             .enumerate()
             .map(|(tree_rank, box_)| {
-                let mut fragment = box_.layout(
-                    containing_block,
-                    tree_rank,
-                    absolutely_positioned_fragments,
-                    Some(float_context),
-                );
+                if let BlockLevelBox::OutOfFlowFloatBox(float_box) = &**box_ {
+                    // Floats are not in flow, so they don’t go through `place_block_level_fragment`.
+                    let block_position = placement_state.current_block_direction_position
+                        + placement_state.current_margin.solve();
+                    return float_box.layout(
+                        layout_context,
+                        containing_block,
+                        tree_rank,
+                        absolutely_positioned_fragments,
+                        float_context,
+                        block_position,
+                    );
+                }
+                let start_margin = box_.in_flow_style().map_or(Length::zero(), |style| {
+                    style
+                        .margin(containing_block.mode)
+                        .block_start
+                        .percentage_relative_to(containing_block.inline_size)
+                        .auto_is(Length::zero)
+                });
+                let clearance_edge = box_
+                    .in_flow_style()
+                    .and_then(|style| float_context.clearance_edge(style.box_.clear));
+                if let Some(edge) = clearance_edge {
+                    // https://drafts.csswg.org/css2/visuren.html#clearance
+                    let edge = edge - float_context.containing_block_start_corner.block;
+                    let hypothetical_position = placement_state.current_block_direction_position
+                        + placement_state
+                            .current_margin
+                            .adjoin(&CollapsedMargin::new(start_margin))
+                            .solve();
+                    if hypothetical_position < edge {
+                        // Clearance stops this child’s start margin from collapsing
+                        // with preceding margins or with its parent’s.
+                        // FIXME: margins of descendants that collapse with this child’s
+                        // are still included in `place_block_level_fragment`.
+                        placement_state.current_margin = CollapsedMargin::zero();
+                        placement_state.next_in_flow_margin_collapses_with_parent_start_margin =
+                            false;
+                        placement_state.current_block_direction_position = edge - start_margin;
+                    }
+                }
+                // Descendant floats are placed relative to the block formatting context root,
+                // but where this child ends up is only known after margin collapsing.
+                // Estimate it with this child’s own start margin.
+                let saved_start_corner = float_context.containing_block_start_corner.clone();
+                float_context.containing_block_start_corner.block +=
+                    placement_state.current_block_direction_position;
+                if !placement_state.next_in_flow_margin_collapses_with_parent_start_margin {
+                    float_context.containing_block_start_corner.block += placement_state
+                        .current_margin
+                        .adjoin(&CollapsedMargin::new(start_margin))
+                        .solve();
+                }
+                let mut fragment = if let BlockLevelBox::Independent { .. } = &**box_ {
+                    let abspos_before = absolutely_positioned_fragments.len();
+                    let (mut fragment, offset) = float_context.place_independent(
+                        containing_block.inline_size,
+                        |inline_size| {
+                            // Laid out again if it does not fit next to floats
+                            absolutely_positioned_fragments.truncate(abspos_before);
+                            let containing_block = ContainingBlock {
+                                inline_size,
+                                block_size: containing_block.block_size,
+                                mode: containing_block.mode,
+                            };
+                            let fragment = box_.layout(
+                                layout_context,
+                                &containing_block,
+                                tree_rank,
+                                absolutely_positioned_fragments,
+                                None,
+                            );
+                            // Over-constrained margins are negative to fill the containing block,
+                            // but a box wider than the space next to floats does not fit.
+                            let size = match &fragment {
+                                Fragment::Box(b) => Vec2 {
+                                    inline: b.border_rect().size.inline
+                                        + b.margin.inline_start.max(Length::zero())
+                                        + b.margin.inline_end.max(Length::zero()),
+                                    block: b.border_rect().size.block,
+                                },
+                                _ => Vec2::zero(),
+                            };
+                            (fragment, size)
+                        },
+                    );
+                    if let Fragment::Box(b) = &mut fragment {
+                        b.content_rect.start_corner.inline += offset.inline
+                    }
+                    if offset.block > Length::zero() {
+                        // Like clearance, moving below floats stops margins from collapsing.
+                        let hypothetical_position = placement_state
+                            .current_block_direction_position
+                            + placement_state
+                                .current_margin
+                                .adjoin(&CollapsedMargin::new(start_margin))
+                                .solve();
+                        placement_state.current_margin = CollapsedMargin::zero();
+                        placement_state.next_in_flow_margin_collapses_with_parent_start_margin =
+                            false;
+                        placement_state.current_block_direction_position =
+                            hypothetical_position + offset.block - start_margin;
+                    }
+                    fragment
+                } else {
+                    box_.layout(
+                        layout_context,
+                        containing_block,
+                        tree_rank,
+                        absolutely_positioned_fragments,
+                        Some(float_context),
+                    )
+                };
+                float_context.containing_block_start_corner = saved_start_corner;
                 place_block_level_fragment(&mut fragment, &mut placement_state);
                 fragment
             })
             .collect()
     } else {
+        // Both `collect` and the reduction of `mapfold_reduce_into` keep the order of children
+        // (the left half of a split is always appended before the right half),
+        // so fragments and absolutely positioned fragments come out in tree order
+        // regardless of how rayon splits the work between threads.
         fragments = child_boxes
             .par_iter()
             .enumerate()
@@ -202,6 +369,7 @@ fn layout_block_level_children<'a>(
                 absolutely_positioned_fragments,
                 |abspos_fragments, (tree_rank, box_)| {
                     box_.layout(
+                        layout_context,
                         containing_block,
                         tree_rank,
                         abspos_fragments,
@@ -239,20 +407,32 @@ fn layout_block_level_children<'a>(
 impl BlockLevelBox {
     fn layout<'a>(
         &'a self,
+        layout_context: &LayoutContext,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
         float_context: Option<&mut FloatContext>,
     ) -> Fragment {
         match self {
-            BlockLevelBox::SameFormattingContextBlock { style, contents } => {
-                Fragment::Box(layout_in_flow_non_replaced_block_level(
+            BlockLevelBox::SameFormattingContextBlock {
+                tag,
+                style,
+                contents,
+            } => Fragment::Box(BoxFragment {
+                tag: *tag,
+                ..layout_in_flow_non_replaced_block_level(
+                    layout_context,
                     containing_block,
                     absolutely_positioned_fragments,
+                    float_context,
                     style,
                     BlockLevelKind::SameFormattingContextBlock,
-                    |containing_block, nested_abspos, collapsible_with_parent_start_margin| {
+                    |containing_block,
+                     nested_abspos,
+                     float_context,
+                     collapsible_with_parent_start_margin| {
                         contents.layout(
+                            layout_context,
                             containing_block,
                             tree_rank,
                             nested_abspos,
@@ -260,33 +440,68 @@ impl BlockLevelBox {
                             collapsible_with_parent_start_margin,
                         )
                     },
-                ))
-            }
-            BlockLevelBox::Independent { style, contents } => match contents.as_replaced() {
-                Ok(replaced) => {
-                    // FIXME
-                    match *replaced {}
-                }
-                Err(contents) => Fragment::Box(layout_in_flow_non_replaced_block_level(
-                    containing_block,
-                    absolutely_positioned_fragments,
-                    style,
-                    BlockLevelKind::EstablishesAnIndependentFormattingContext,
-                    |containing_block, nested_abspos, _| {
-                        contents.layout(containing_block, tree_rank, nested_abspos)
-                    },
-                )),
+                )
+            }),
+            BlockLevelBox::Independent {
+                tag,
+                style,
+                contents,
+            } => match contents.as_replaced() {
+                Ok(replaced) => Fragment::Box(BoxFragment {
+                    tag: *tag,
+                    ..layout_in_flow_replaced_block_level(
+                        layout_context,
+                        containing_block,
+                        style,
+                        replaced,
+                    )
+                }),
+                // Placed next to floats in the parent formatting context
+                // by `FloatContext::place_independent`.
+                Err(contents) => Fragment::Box(BoxFragment {
+                    tag: *tag,
+                    ..layout_in_flow_non_replaced_block_level(
+                        layout_context,
+                        containing_block,
+                        absolutely_positioned_fragments,
+                        /* float_context = */ None,
+                        style,
+                        BlockLevelKind::EstablishesAnIndependentFormattingContext,
+                        |containing_block, nested_abspos, _, _| {
+                            contents.layout(
+                                layout_context,
+                                containing_block,
+                                tree_rank,
+                                nested_abspos,
+                            )
+                        },
+                    )
+                }),
             },
             BlockLevelBox::OutOfFlowAbsolutelyPositionedBox(box_) => {
-                absolutely_positioned_fragments.push(box_.layout(Vec2::zero(), tree_rank));
+                absolutely_positioned_fragments.push(box_.layout(
+                    Vec2::zero(),
+                    containing_block.mode,
+                    tree_rank,
+                ));
                 Fragment::Anonymous(AnonymousFragment::no_op(containing_block.mode))
             }
             BlockLevelBox::OutOfFlowFloatBox(_box_) => {
-                // TODO
+                // Floats are laid out in `layout_block_level_children`,
+                // this is only reached when there is no float context.
                 Fragment::Anonymous(AnonymousFragment::no_op(containing_block.mode))
             }
         }
     }
+
+    fn in_flow_style(&self) -> Option<&Arc<ComputedValues>> {
+        match self {
+            BlockLevelBox::SameFormattingContextBlock { style, .. }
+            | BlockLevelBox::Independent { style, .. } => Some(style),
+            BlockLevelBox::OutOfFlowAbsolutelyPositionedBox(_)
+            | BlockLevelBox::OutOfFlowFloatBox(_) => None,
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -295,47 +510,115 @@ enum BlockLevelKind {
     EstablishesAnIndependentFormattingContext,
 }
 
+/// https://drafts.csswg.org/css2/visudet.html#block-replaced-width
+/// https://drafts.csswg.org/css2/visudet.html#inline-replaced-height
+fn layout_in_flow_replaced_block_level(
+    layout_context: &LayoutContext,
+    containing_block: &ContainingBlock,
+    style: &Arc<ComputedValues>,
+    replaced: &ReplacedContent,
+) -> BoxFragment {
+    let cbis = containing_block.inline_size;
+    let padding = style
+        .padding(containing_block.mode)
+        .percentages_relative_to(cbis);
+    let border = style
+        .border_width(containing_block.mode)
+        .percentages_relative_to(cbis);
+    let computed_margin = style
+        .margin(containing_block.mode)
+        .percentages_relative_to(cbis);
+    let pb = &padding + &border;
+    let font_context = layout_context.font_context;
+    let size = replaced.used_size(style, containing_block, font_context);
+    let inline_margins = cbis - size.inline - pb.inline_sum();
+    let (margin_inline_start, margin_inline_end) =
+        match (computed_margin.inline_start, computed_margin.inline_end) {
+            (LengthOrAuto::Auto, LengthOrAuto::Auto) => {
+                let start = inline_margins / 2.;
+                (start, inline_margins - start)
+            }
+            (LengthOrAuto::Auto, LengthOrAuto::Length(end)) => (inline_margins - end, end),
+            (start, end) => (start.auto_is(Length::zero), end.auto_is(Length::zero)),
+        };
+    let margin = Sides {
+        inline_start: margin_inline_start,
+        inline_end: margin_inline_end,
+        block_start: computed_margin.block_start.auto_is(Length::zero),
+        block_end: computed_margin.block_end.auto_is(Length::zero),
+    };
+    let (children, _ascent) = replaced.make_fragments(style, &size, font_context);
+    let relative_adjustement = relative_adjustement(style, containing_block);
+    let content_rect = Rect {
+        start_corner: Vec2 {
+            block: pb.block_start + relative_adjustement.block,
+            inline: pb.inline_start + relative_adjustement.inline + margin.inline_start,
+        },
+        size,
+    };
+    BoxFragment {
+        // Set by the caller
+        tag: None,
+        style: style.clone(),
+        children,
+        content_rect,
+        padding,
+        border,
+        block_margins_collapsed_with_children: CollapsedBlockMargins::from_margin(&margin),
+        margin,
+    }
+}
+
 /// https://drafts.csswg.org/css2/visudet.html#blockwidth
 /// https://drafts.csswg.org/css2/visudet.html#normal-block
 fn layout_in_flow_non_replaced_block_level<'a>(
+    layout_context: &LayoutContext,
     containing_block: &ContainingBlock,
     absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
+    mut float_context: Option<&mut FloatContext>,
     style: &Arc<ComputedValues>,
     block_level_kind: BlockLevelKind,
     layout_contents: impl FnOnce(
         &ContainingBlock,
         &mut Vec<AbsolutelyPositionedFragment<'a>>,
+        Option<&mut FloatContext>,
         CollapsibleWithParentStartMargin,
     ) -> FlowChildren,
 ) -> BoxFragment {
     let cbis = containing_block.inline_size;
-    let padding = style.padding().percentages_relative_to(cbis);
-    let border = style.border_width().percentages_relative_to(cbis);
-    let mut computed_margin = style.margin().percentages_relative_to(cbis);
+    let padding = style
+        .padding(containing_block.mode)
+        .percentages_relative_to(cbis);
+    let border = style
+        .border_width(containing_block.mode)
+        .percentages_relative_to(cbis);
+    let mut computed_margin = style
+        .margin(containing_block.mode)
+        .percentages_relative_to(cbis);
     let pb = &padding + &border;
     let box_size = style.box_size();
     let inline_size = box_size.inline.percentage_relative_to(cbis);
     if let LengthOrAuto::Length(is) = inline_size {
         let inline_margins = cbis - is - pb.inline_sum();
         use LengthOrAuto::*;
-        match (
-            &mut computed_margin.inline_start,
-            &mut computed_margin.inline_end,
-        ) {
-            (s @ &mut Auto, e @ &mut Auto) => {
-                *s = Length(inline_margins / 2.);
-                *e = Length(inline_margins / 2.);
+        match (computed_margin.inline_start, computed_margin.inline_end) {
+            (Auto, Auto) => {
+                // Rounding to app units might make halves unequal, but they add up.
+                let start = inline_margins / 2.;
+                computed_margin.inline_start = Length(start);
+                computed_margin.inline_end = Length(inline_margins - start);
             }
-            (s @ &mut Auto, _) => {
-                *s = Length(inline_margins);
+            (Auto, Length(e)) => {
+                computed_margin.inline_start = Length(inline_margins - e);
             }
-            (_, e @ &mut Auto) => {
-                *e = Length(inline_margins);
+            (Length(s), Auto) => {
+                computed_margin.inline_end = Length(inline_margins - s);
             }
-            (_, e @ _) => {
-                // Either the inline-end margin is auto,
-                // or we’re over-constrained and we do as if it were.
-                *e = Length(inline_margins);
+            (Length(s), Length(_)) => {
+                // We’re over-constrained. The margin to ignore is the one
+                // at the end of the containing block’s inline direction:
+                // https://drafts.csswg.org/css2/visudet.html#blockwidth
+                computed_margin.inline_end = Length(inline_margins - s)
             }
         }
     }
@@ -350,13 +633,8 @@ fn layout_in_flow_non_replaced_block_level<'a>(
     let containing_block_for_children = ContainingBlock {
         inline_size,
         block_size,
-        mode: style.writing_mode(),
+        mode: style.writing_mode_in(containing_block.mode),
     };
-    // https://drafts.csswg.org/css-writing-modes/#orthogonal-flows
-    assert_eq!(
-        containing_block.mode, containing_block_for_children.mode,
-        "Mixed writing modes are not supported yet"
-    );
     let this_start_margin_can_collapse_with_children = CollapsibleWithParentStartMargin(
         block_level_kind == BlockLevelKind::SameFormattingContextBlock
             && pb.block_start == Length::zero(),
@@ -368,13 +646,20 @@ fn layout_in_flow_non_replaced_block_level<'a>(
             LengthOrAuto::Auto,
         );
     let mut nested_abspos = vec![];
+    // The caller takes care of the block position and restores this afterwards.
+    if let Some(float_context) = &mut float_context {
+        let start_corner = &mut float_context.containing_block_start_corner;
+        start_corner.inline += margin.inline_start + pb.inline_start;
+        start_corner.block += pb.block_start;
+    }
     let mut flow_children = layout_contents(
         &containing_block_for_children,
-        if style.box_.position.is_relatively_positioned() {
+        if style.establishes_containing_block_for_absolute_descendants() {
             &mut nested_abspos
         } else {
             absolutely_positioned_fragments
         },
+        float_context,
         this_start_margin_can_collapse_with_children,
     );
     if this_start_margin_can_collapse_with_children.0 {
@@ -406,7 +691,7 @@ fn layout_in_flow_non_replaced_block_level<'a>(
             && flow_children
                 .collapsible_margins_in_children
                 .collapsed_through;
-    let relative_adjustement = relative_adjustement(style, inline_size, block_size);
+    let relative_adjustement = relative_adjustement(style, containing_block);
     let block_size = block_size.auto_is(|| flow_children.block_size);
     let content_rect = Rect {
         start_corner: Vec2 {
@@ -418,16 +703,22 @@ fn layout_in_flow_non_replaced_block_level<'a>(
             inline: inline_size,
         },
     };
-    if style.box_.position.is_relatively_positioned() {
-        AbsolutelyPositionedFragment::in_positioned_containing_block(
-            &nested_abspos,
-            &mut flow_children.fragments,
-            &content_rect.size,
-            &padding,
-            containing_block_for_children.mode,
+    if style.establishes_containing_block_for_absolute_descendants() {
+        flow_children.fragments.extend(
+            AbsolutelyPositionedFragment::in_positioned_containing_block(
+                layout_context,
+                style,
+                nested_abspos,
+                absolutely_positioned_fragments,
+                &content_rect.size,
+                &padding,
+                containing_block_for_children.mode,
+            ),
         )
     }
     BoxFragment {
+        // Set by the caller
+        tag: None,
         style: style.clone(),
         children: flow_children.fragments,
         content_rect,
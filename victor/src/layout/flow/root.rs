@@ -1,30 +1,68 @@
 use super::*;
+use crate::dom::LimitExceeded;
+use crate::style::StyleSet;
+use std::sync::atomic::AtomicUsize;
 
 impl crate::dom::Document {
+    /// Stop with an error after `Limits::max_boxes` boxes.
     pub(crate) fn layout(
         &self,
         viewport: crate::primitives::Size<crate::primitives::CssPx>,
-    ) -> Vec<Fragment> {
-        BoxTreeRoot::construct(self).layout(viewport)
+        author_styles: &StyleSet,
+        font_context: &FontContext,
+    ) -> Result<Vec<Fragment>, LimitExceeded> {
+        let max_boxes = self.limits.max_boxes;
+        let layout_context = &LayoutContext {
+            font_context,
+            cancellation_token: self.cancellation_token.as_ref(),
+        };
+        let layout = || {
+            Ok(BoxTreeRoot::construct(self, author_styles, max_boxes)?
+                .layout(viewport, layout_context))
+        };
+        match &self.thread_pool {
+            Some(pool) => pool.install(layout),
+            None => layout(),
+        }
     }
 }
 
 struct BoxTreeRoot(BlockFormattingContext);
 
 impl BoxTreeRoot {
-    pub fn construct(document: &dom::Document) -> Self {
-        let author_styles = &document.parse_stylesheets();
+    pub fn construct(
+        document: &dom::Document,
+        author_styles: &StyleSet,
+        max_boxes: Option<usize>,
+    ) -> Result<Self, LimitExceeded> {
+        // Without counters, styles are computed during parallel box construction instead.
+        let (styles, counters) = if document_may_use_counters(author_styles, document) {
+            compute_styles_and_counters(document, author_styles)
+        } else {
+            Default::default()
+        };
+        let root_element = document.root_element();
+        let style = match styles.get(&root_element) {
+            Some(style) => style.clone(),
+            None => style_for_element(author_styles, document, root_element, None),
+        };
         let context = Context {
             document,
             author_styles,
+            styles,
+            counters,
+            // Counting the root element’s box
+            box_count: AtomicUsize::new(1),
+            max_boxes,
         };
-        let root_element = document.root_element();
-        let style = style_for_element(context.author_styles, context.document, root_element, None);
         let (contains_floats, boxes) = construct_for_root_element(&context, root_element, style);
-        Self(BlockFormattingContext {
+        if context.too_many_boxes() {
+            return Err(LimitExceeded::Boxes);
+        }
+        Ok(Self(BlockFormattingContext {
             contains_floats: contains_floats == ContainsFloats::Yes,
             contents: BlockContainer::BlockLevelBoxes(boxes),
-        })
+        }))
     }
 }
 
@@ -45,33 +83,31 @@ fn construct_for_root_element(
         Display::Contents => DisplayInside::Flow,
         // The root element is blockified, ignore DisplayOutside
         Display::GeneratingBox(DisplayGeneratingBox::OutsideInside { inside, .. }) => inside,
+        Display::GeneratingBox(DisplayGeneratingBox::LayoutInternal(_)) => DisplayInside::Flow,
     };
 
-    if let Some(replaced) = replaced {
-        let _box = match replaced {};
-        #[allow(unreachable_code)]
-        {
-            return (ContainsFloats::No, vec![Arc::new(_box)]);
-        }
-    }
-
-    let contents = IndependentFormattingContext::construct(
-        context,
-        &style,
-        display_inside,
-        Contents::OfElement(root_element),
-    );
+    let contents = match replaced {
+        Some(replaced) => Contents::Replaced(replaced),
+        None => Contents::OfElement(root_element),
+    };
+    let contents =
+        IndependentFormattingContext::construct(context, &style, display_inside, contents);
     if style.box_.position.is_absolutely_positioned() {
         (
             ContainsFloats::No,
             vec![Arc::new(BlockLevelBox::OutOfFlowAbsolutelyPositionedBox(
-                AbsolutelyPositionedBox { style, contents },
+                AbsolutelyPositionedBox {
+                    tag: Some(root_element),
+                    style,
+                    contents,
+                },
             ))],
         )
     } else if style.box_.float.is_floating() {
         (
             ContainsFloats::Yes,
             vec![Arc::new(BlockLevelBox::OutOfFlowFloatBox(FloatBox {
+                tag: Some(root_element),
                 contents,
                 style,
             }))],
@@ -79,18 +115,24 @@ fn construct_for_root_element(
     } else {
         (
             ContainsFloats::No,
-            vec![Arc::new(BlockLevelBox::Independent { style, contents })],
+            vec![Arc::new(BlockLevelBox::Independent {
+                tag: Some(root_element),
+                style,
+                contents,
+            })],
         )
     }
 }
 
 impl BoxTreeRoot {
-    fn layout(&self, viewport: crate::primitives::Size<crate::primitives::CssPx>) -> Vec<Fragment> {
+    fn layout(
+        &self,
+        viewport: crate::primitives::Size<crate::primitives::CssPx>,
+        layout_context: &LayoutContext,
+    ) -> Vec<Fragment> {
         let initial_containing_block_size = Vec2 {
-            inline: Length { px: viewport.width },
-            block: Length {
-                px: viewport.height,
-            },
+            inline: Length::from_px(viewport.width),
+            block: Length::from_px(viewport.height),
         };
 
         let initial_containing_block = ContainingBlock {
@@ -103,6 +145,7 @@ impl BoxTreeRoot {
         let dummy_tree_rank = 0;
         let mut absolutely_positioned_fragments = vec![];
         let mut flow_children = self.0.layout(
+            layout_context,
             &initial_containing_block,
             dummy_tree_rank,
             &mut absolutely_positioned_fragments,
@@ -112,11 +155,15 @@ impl BoxTreeRoot {
             size: initial_containing_block_size,
             mode: initial_containing_block.mode,
         };
-        flow_children.fragments.par_extend(
-            absolutely_positioned_fragments
-                .par_iter()
-                .map(|a| a.layout(&initial_containing_block)),
-        );
+        // Fixed descendants of absolutely positioned boxes come out one level at a time.
+        while !absolutely_positioned_fragments.is_empty() {
+            absolutely_positioned_fragments = AbsolutelyPositionedFragment::layout_all(
+                layout_context,
+                &absolutely_positioned_fragments,
+                &initial_containing_block,
+                &mut flow_children.fragments,
+            );
+        }
         flow_children.fragments
     }
 }
@@ -5,9 +5,15 @@ pub(crate) enum Fragment {
     Box(BoxFragment),
     Anonymous(AnonymousFragment),
     Text(TextFragment),
+    Checkbox(CheckboxFragment),
+    Image(ImageFragment),
+    FormField(FormFieldFragment),
 }
 
 pub(crate) struct BoxFragment {
+    /// The element that generated this box,
+    /// `None` for anonymous boxes and pseudo-elements.
+    pub tag: Option<crate::dom::NodeId>,
     pub style: Arc<ComputedValues>,
     pub children: Vec<Fragment>,
 
@@ -45,7 +51,57 @@ pub(crate) struct AnonymousFragment {
 pub(crate) struct TextFragment {
     pub parent_style: Arc<ComputedValues>,
     pub content_rect: Rect<Length>,
+    /// Usually the `font-size` of `parent_style`, but smaller for synthesized small caps
+    pub font_size: Length,
     pub text: ShapedSegment,
+    /// The embedding level of the text, which is right-to-left if odd:
+    /// https://unicode.org/reports/tr9/#BD2
+    pub bidi_level: u8,
+}
+
+/// A checkbox or radio button, drawn to fill its content rect
+pub(crate) struct CheckboxFragment {
+    pub style: Arc<ComputedValues>,
+    pub content_rect: Rect<Length>,
+    pub radio: bool,
+    pub checked: bool,
+}
+
+/// The image of an `<img>` element, clipped to its content rect.
+/// It is decoded when painted, with `ImageCache`.
+pub(crate) struct ImageFragment {
+    pub content_rect: Rect<Length>,
+    /// Where the image is painted, relative to the top-left corner of the content rect:
+    /// see `object-fit` and `object-position`
+    pub object_rect: crate::geom::physical::Rect<Length>,
+    pub element: crate::dom::NodeId,
+    /// In image pixels, as stored
+    pub width: u32,
+    pub height: u32,
+    /// How the image is flipped and rotated in the content rect
+    pub orientation: Orientation,
+}
+
+/// Makes the border box of its parent box a field of the PDF’s interactive form,
+/// that users can fill in. It is not painted itself.
+pub(crate) struct FormFieldFragment {
+    pub name: String,
+    pub value: FormFieldValue,
+}
+
+pub(crate) enum FormFieldValue {
+    Text {
+        value: String,
+        multiline: bool,
+    },
+    Checkbox {
+        checked: bool,
+    },
+    Choice {
+        options: Vec<String>,
+        /// An index in `options`
+        selected: Option<usize>,
+    },
 }
 
 impl AnonymousFragment {
@@ -59,10 +115,39 @@ impl AnonymousFragment {
 }
 
 impl BoxFragment {
+    pub fn padding_rect(&self) -> Rect<Length> {
+        self.content_rect.inflate(&self.padding)
+    }
+
     pub fn border_rect(&self) -> Rect<Length> {
-        self.content_rect
-            .inflate(&self.padding)
-            .inflate(&self.border)
+        self.padding_rect().inflate(&self.border)
+    }
+
+    /// Whether this is a fragment of an inline box, whose children are positioned
+    /// in the lines of its parent’s inline formatting context rather than in their own.
+    /// (A replaced element with `display: inline` also counts, but its content fills it.)
+    pub fn is_inline_box(&self) -> bool {
+        match self.style.box_.display {
+            Display::GeneratingBox(DisplayGeneratingBox::OutsideInside {
+                outside: DisplayOutside::Inline,
+                inside,
+                ..
+            }) => matches!(inside, DisplayInside::Flow | DisplayInside::Ruby),
+            _ => false,
+        }
+    }
+
+    /// The writing mode that the children of this box are positioned in,
+    /// when the box itself is in a containing block with `containing_block_mode`.
+    pub fn children_mode(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> (WritingMode, Direction) {
+        if self.is_inline_box() {
+            containing_block_mode
+        } else {
+            self.style.writing_mode_in(containing_block_mode)
+        }
     }
 }
 
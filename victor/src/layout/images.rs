@@ -0,0 +1,363 @@
+//! Raster images of `<img>` elements and backgrounds.
+//!
+//! FIXME: only JPEG files in `data:` URLs are supported so far, since PDF can embed them as-is.
+//! Other URLs need a way to fetch resources, and PNG images need decoding.
+
+use crate::data_url::DataUrl;
+use crate::dom::{Document, NodeId};
+use crate::pdf::{Image, ImageData};
+use crate::primitives::{CssPx, Rect, Transform};
+use std::fmt;
+use std::sync::Arc;
+
+/// A JPEG file with RGB color, and its size in image pixels from its header
+pub(crate) struct JpegImage {
+    file: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub orientation: Orientation,
+}
+
+impl JpegImage {
+    /// `None` for URLs other than `data:` URLs of JPEG files with RGB color.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let file = DataUrl::parse(url)?.body;
+        // The MIME type is ignored, like in browsers:
+        // https://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+        if !file.starts_with(b"\xFF\xD8\xFF") {
+            return None;
+        }
+        let (width, height) = jpeg_size(&file)?;
+        let orientation = exif_orientation(&file);
+        Some(JpegImage {
+            file,
+            width,
+            height,
+            orientation,
+        })
+    }
+
+    pub fn as_pdf_image(&self) -> Image<'_> {
+        Image {
+            width: self.width,
+            height: self.height,
+            data: ImageData::Jpeg(&self.file),
+        }
+    }
+}
+
+impl fmt::Debug for JpegImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JpegImage({}x{})", self.width, self.height)
+    }
+}
+
+/// Images decoded when painting, see `Document::set_image_memory_budget`.
+pub(crate) struct ImageCache<'a> {
+    document: &'a Document,
+    /// In bytes
+    budget: Option<usize>,
+    /// Least recently painted first
+    images: Vec<(ImageKey, Arc<JpegImage>)>,
+    /// In bytes, the sum for `images`
+    size: usize,
+}
+
+impl<'a> ImageCache<'a> {
+    pub fn new(document: &'a Document, budget: Option<usize>) -> Self {
+        ImageCache {
+            document,
+            budget,
+            images: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// The image of an `<img>` element, decoded again if it is not in the cache.
+    pub fn get(&mut self, element: NodeId) -> Option<Arc<JpegImage>> {
+        let document = self.document;
+        self.get_or_decode(ImageKey::Element(element), || {
+            let (url, _density) = document.image_source(element)?;
+            JpegImage::from_url(url)
+        })
+    }
+
+    /// A `background-image`, decoded again if it is not in the cache.
+    /// `url` is shared by the styles that have this value.
+    pub fn get_url(&mut self, url: &Arc<str>) -> Option<Arc<JpegImage>> {
+        self.get_or_decode(ImageKey::Url(url.clone()), || JpegImage::from_url(url))
+    }
+
+    fn get_or_decode(
+        &mut self,
+        key: ImageKey,
+        decode: impl FnOnce() -> Option<JpegImage>,
+    ) -> Option<Arc<JpegImage>> {
+        if let Some(index) = self.images.iter().position(|(k, _)| *k == key) {
+            let entry = self.images.remove(index);
+            let image = entry.1.clone();
+            self.images.push(entry);
+            return Some(image);
+        }
+        let image = Arc::new(decode()?);
+        self.size += image.file.len();
+        self.images.push((key, image.clone()));
+        // The image being painted is kept even if it alone exceeds the budget.
+        while self.budget.is_some_and(|budget| self.size > budget) && self.images.len() > 1 {
+            let (_, evicted) = self.images.remove(0);
+            self.size -= evicted.file.len();
+        }
+        Some(image)
+    }
+}
+
+/// How the pixels of an image are flipped and rotated from how they are stored,
+/// from the EXIF orientation tag of JPEG files: 1 for as stored, up to 8.
+///
+/// CIPA DC-008 (Exif 2.3), section 4.6.4 A, Orientation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Orientation(u8);
+
+impl Orientation {
+    pub const AS_STORED: Self = Orientation(1);
+
+    /// Whether rows of stored pixels are displayed as columns,
+    /// so that the width and height of the image are swapped.
+    pub fn is_transposed(self) -> bool {
+        self.0 >= 5
+    }
+
+    /// The transform that paints the stored image in `rect`,
+    /// from a rect at the origin of the same size, or of the swapped size if transposed.
+    pub fn transform(self, rect: &Rect<CssPx>) -> Transform<CssPx, CssPx> {
+        let (w, h) = (rect.size.width, rect.size.height);
+        // Where each orientation puts the first row and the first column of stored pixels:
+        let (m11, m12, m21, m22, m31, m32) = match self.0 {
+            // Top and left
+            1 => (1., 0., 0., 1., 0., 0.),
+            // Top and right
+            2 => (-1., 0., 0., 1., w, 0.),
+            // Bottom and right
+            3 => (-1., 0., 0., -1., w, h),
+            // Bottom and left
+            4 => (1., 0., 0., -1., 0., h),
+            // Left and top
+            5 => (0., 1., 1., 0., 0., 0.),
+            // Right and top
+            6 => (0., 1., -1., 0., w, 0.),
+            // Right and bottom
+            7 => (0., -1., -1., 0., w, h),
+            // Left and bottom
+            _ => (0., -1., 1., 0., 0., h),
+        };
+        Transform::row_major(m11, m12, m21, m22, m31, m32).post_translate(rect.origin.to_vector())
+    }
+}
+
+enum ImageKey {
+    Element(NodeId),
+    /// Compared by address, which is stable while painting since styles keep their values
+    Url(Arc<str>),
+}
+
+impl PartialEq for ImageKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ImageKey::Element(a), ImageKey::Element(b)) => a == b,
+            (ImageKey::Url(a), ImageKey::Url(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// The marker code and content of each segment before the first scan of a JPEG file,
+/// up to the first malformed one.
+///
+/// ITU-T T.81, section B.1.1.2 (markers)
+fn jpeg_segments(file: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    // After the start of image marker
+    let mut rest = file.get(2..).unwrap_or(&[]);
+    std::iter::from_fn(move || loop {
+        // A marker is 0xFF and a code, with any number of 0xFF fill bytes before it.
+        let code_position = rest.iter().position(|&byte| byte != 0xFF)?;
+        if code_position == 0 {
+            return None;
+        }
+        let code = rest[code_position];
+        rest = &rest[code_position + 1..];
+        match code {
+            // TEM and RST0 to RST7 are not followed by a segment
+            0x01 | 0xD0..=0xD7 => continue,
+            // End of image, or start of scan
+            0xD9 | 0xDA => return None,
+            _ => {}
+        }
+        // The length of the segment includes its own two bytes.
+        let length = usize::from(u16::from_be_bytes([*rest.first()?, *rest.get(1)?]));
+        let segment = rest.get(2..length)?;
+        rest = &rest[length..];
+        return Some((code, segment));
+    })
+    .fuse()
+}
+
+/// The size from the frame header, if samples have 8 bits and there are 3 components
+/// (YCbCr or RGB) which PDF’s `DCTDecode` filter decodes to RGB.
+///
+/// ITU-T T.81, section B.2.2 (frame header)
+fn jpeg_size(file: &[u8]) -> Option<(u32, u32)> {
+    // Start of frame, in the range of codes that also has DHT, JPG and DAC
+    let is_frame = |code| (0xC0..=0xCF).contains(&code) && ![0xC4, 0xC8, 0xCC].contains(&code);
+    let (_, segment) = jpeg_segments(file).find(|&(code, _)| is_frame(code))?;
+    let (precision, height, width, components) = match *segment {
+        [p, y1, y2, x1, x2, nf, ..] => (
+            p,
+            u16::from_be_bytes([y1, y2]),
+            u16::from_be_bytes([x1, x2]),
+            nf,
+        ),
+        _ => return None,
+    };
+    // A zero height is defined later by a DNL segment, which is rarely used.
+    if precision != 8 || components != 3 || width == 0 || height == 0 {
+        return None;
+    }
+    Some((width.into(), height.into()))
+}
+
+/// The orientation tag of the EXIF metadata in an APP1 segment, if there is one.
+///
+/// CIPA DC-008 (Exif 2.3), sections 4.5.2 (TIFF header) and 4.6.2 (IFD structure)
+fn exif_orientation(file: &[u8]) -> Orientation {
+    let orientation = || {
+        let (_, segment) = jpeg_segments(file)
+            .find(|&(code, segment)| code == 0xE1 && segment.starts_with(b"Exif\0\0"))?;
+        let tiff = &segment[6..];
+        let big_endian = match tiff.get(..4)? {
+            b"MM\0*" => true,
+            b"II*\0" => false,
+            _ => return None,
+        };
+        let u16_at = |offset: usize| {
+            let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+            Some(if big_endian {
+                u16::from_be_bytes(bytes)
+            } else {
+                u16::from_le_bytes(bytes)
+            })
+        };
+        let u32_at = |offset: usize| {
+            let (high, low) = (u32::from(u16_at(offset)?), u32::from(u16_at(offset + 2)?));
+            Some(if big_endian {
+                high << 16 | low
+            } else {
+                low << 16 | high
+            })
+        };
+        // The 0th IFD, for the primary image: a count of 12-byte entries
+        let ifd = u32_at(4)? as usize;
+        let entries = (0..usize::from(u16_at(ifd)?)).map(|i| ifd + 2 + 12 * i);
+        // Tag 0x0112 has a single SHORT value, in the first bytes of the value field.
+        let entry = entries
+            .take_while(|&entry| u16_at(entry).is_some())
+            .find(|&entry| u16_at(entry) == Some(0x0112))?;
+        match u16_at(entry + 8)? {
+            value @ 1..=8 => Some(Orientation(value as u8)),
+            _ => None,
+        }
+    };
+    orientation().unwrap_or(Orientation::AS_STORED)
+}
+
+#[cfg(test)]
+fn jpeg_header(components: &[u8], precision: u8) -> Vec<u8> {
+    let mut file = vec![0xFF, 0xD8];
+    // An APP0 segment, then a fill byte before a baseline frame header
+    file.extend(&[0xFF, 0xE0, 0, 4, 0, 0, 0xFF]);
+    let length = 8 + components.len() as u8;
+    file.extend(&[0xFF, 0xC0, 0, length, precision, 0, 2, 1, 0x2C]);
+    file.push((components.len() / 3) as u8);
+    file.extend(components);
+    file
+}
+
+#[test]
+fn size_from_frame_header() {
+    let component = |id| vec![id, 0x11, 0];
+    let rgb: Vec<u8> = (1..=3).flat_map(component).collect();
+    assert_eq!(jpeg_size(&jpeg_header(&rgb, 8)), Some((300, 2)));
+    assert_eq!(jpeg_size(&jpeg_header(&rgb, 12)), None);
+    assert_eq!(jpeg_size(&jpeg_header(&component(1), 8)), None);
+    assert_eq!(jpeg_size(&jpeg_header(&rgb, 8)[..12]), None);
+    assert_eq!(jpeg_size(&[0xFF, 0xD8, 0xFF, 0xD9]), None);
+}
+
+#[test]
+fn orientation_from_exif() {
+    let rgb = [1, 0x11, 0, 2, 0x11, 0, 3, 0x11, 0];
+    let header = jpeg_header(&rgb, 8);
+    assert_eq!(exif_orientation(&header), Orientation::AS_STORED);
+    let with_exif = |tiff: &[u8]| {
+        let mut file = header[..2].to_vec();
+        file.extend(&[0xFF, 0xE1, 0, 8 + tiff.len() as u8]);
+        file.extend(b"Exif\0\0");
+        file.extend(tiff);
+        file.extend(&header[2..]);
+        file
+    };
+    // Two entries, the second of which is the orientation
+    let big_endian = [
+        b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 2, //
+        0x01, 0x0F, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, //
+        0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0,
+    ];
+    let file = with_exif(&big_endian);
+    assert_eq!(jpeg_size(&file), Some((300, 2)));
+    assert_eq!(exif_orientation(&file), Orientation(6));
+    let little_endian = [
+        b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, //
+        0x12, 0x01, 3, 0, 1, 0, 0, 0, 8, 0, 0, 0,
+    ];
+    assert_eq!(exif_orientation(&with_exif(&little_endian)), Orientation(8));
+    // Out of range, and truncated
+    let mut invalid = little_endian;
+    invalid[18] = 9;
+    assert_eq!(
+        exif_orientation(&with_exif(&invalid)),
+        Orientation::AS_STORED
+    );
+    assert_eq!(
+        exif_orientation(&with_exif(&little_endian[..19])),
+        Orientation::AS_STORED
+    );
+}
+
+#[test]
+fn image_cache() {
+    let rgb = [1, 0x11, 0, 2, 0x11, 0, 3, 0x11, 0];
+    let header = jpeg_header(&rgb, 8);
+    let url: String = header.iter().map(|byte| format!("%{:02X}", byte)).collect();
+    let html = format!(
+        "<img src='data:,{0}'><img src='data:,{0}'><img src=a.jpg>",
+        url
+    );
+    let document = Document::parse_html(html.as_bytes());
+    let imgs: Vec<NodeId> = document
+        .nodes()
+        .filter(|&node| {
+            document[node]
+                .as_element()
+                .is_some_and(|e| &*e.name.local == "img")
+        })
+        .collect();
+    let mut cache = ImageCache::new(&document, Some(header.len() * 3 / 2));
+    let first = cache.get(imgs[0]).unwrap();
+    assert_eq!((first.width, first.height), (300, 2));
+    assert!(Arc::ptr_eq(&first, &cache.get(imgs[0]).unwrap()));
+    // Only one image fits in the budget
+    let second = cache.get(imgs[1]).unwrap();
+    assert_eq!(cache.images.len(), 1);
+    assert!(Arc::ptr_eq(&second, &cache.get(imgs[1]).unwrap()));
+    assert!(!Arc::ptr_eq(&first, &cache.get(imgs[0]).unwrap()));
+    assert!(cache.get(imgs[2]).is_none());
+}
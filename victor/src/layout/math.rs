@@ -0,0 +1,626 @@
+//! https://w3c.github.io/mathml-core/
+//!
+//! A subset of MathML: `mrow`, token elements (`mi`, `mn`, `mo`, `mtext`),
+//! `mfrac`, `msub`, `msup`, `msubsup` and `msqrt`.
+//! Other elements are laid out like `mrow`.
+//!
+//! A `display: math` element is laid out from its DOM subtree by this module,
+//! its descendants don’t generate CSS boxes.
+//!
+//! FIXME: parameters like the fraction rule thickness and script shifts
+//! should come from the OpenType `MATH` table of the font.
+//! Without one, they are approximations in ems of the values of common math fonts.
+
+use super::*;
+use crate::dom::NodeId;
+use crate::fonts::{FontContext, Substitutions};
+use crate::text::itemize::itemize;
+use cssparser::Color;
+
+#[derive(Debug)]
+pub(super) struct MathContent {
+    root: MathBox,
+}
+
+#[derive(Debug)]
+enum MathBox {
+    /// `mrow`, and elements without a layout of their own
+    Row(Vec<MathBox>),
+    /// `mi`, `mn`, `mo` and `mtext`
+    Token {
+        style: Arc<ComputedValues>,
+        text: String,
+        /// Space before and after an operator, in ems
+        spacing: (f32, f32),
+    },
+    Fraction {
+        style: Arc<ComputedValues>,
+        /// https://w3c.github.io/mathml-core/#the-math-style-property
+        compact: bool,
+        numerator: Box<MathBox>,
+        denominator: Box<MathBox>,
+    },
+    Scripts {
+        style: Arc<ComputedValues>,
+        base: Box<MathBox>,
+        subscript: Option<Box<MathBox>>,
+        superscript: Option<Box<MathBox>>,
+    },
+    Sqrt {
+        style: Arc<ComputedValues>,
+        compact: bool,
+        radicand: Box<MathBox>,
+    },
+}
+
+/// Fragments positioned from the top-left corner of a box, with its metrics
+pub(super) struct MathFragments {
+    pub fragments: Vec<Fragment>,
+    pub inline_size: Length,
+    /// Above the baseline
+    pub ascent: Length,
+    /// Below the baseline
+    pub descent: Length,
+}
+
+/// The size of scripts, and of fractions in compact math, relative to their parent’s:
+/// https://w3c.github.io/mathml-core/#the-math-script-level-property
+///
+/// FIXME: `scriptscriptlevel` sizes for nested scripts are smaller.
+const SCRIPT_SCALE: f32 = 0.71;
+
+const RULE_THICKNESS: f32 = 0.066;
+
+/// The height of the math axis, on which fraction bars and operators like `+` are centered
+const AXIS_HEIGHT: f32 = 0.25;
+
+impl MathContent {
+    /// `display: block math` lays out the formula in display style,
+    /// other values in compact style.
+    pub fn construct(
+        context: &Context,
+        style: &Arc<ComputedValues>,
+        contents: NonReplacedContents,
+        compact: bool,
+    ) -> Self {
+        let root = match contents {
+            NonReplacedContents::OfElement(element) => Builder { context }.children(
+                element,
+                style,
+                compact,
+                element_name(context, element),
+            ),
+            NonReplacedContents::OfPseudoElement(items) => MathBox::Row(
+                items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        PseudoElementContentItem::Text(text) => Some(MathBox::Token {
+                            style: style.clone(),
+                            text,
+                            spacing: (0., 0.),
+                        }),
+                        PseudoElementContentItem::Replaced(_)
+                        | PseudoElementContentItem::Leader(_) => None,
+                    })
+                    .collect(),
+            ),
+        };
+        MathContent { root }
+    }
+
+    /// Returns the fragments of the formula, with its inline size and its ascent above the baseline.
+    pub fn layout(
+        &self,
+        font_context: &FontContext,
+        mode: (WritingMode, Direction),
+    ) -> (Vec<Fragment>, Vec2<Length>, Length) {
+        let laid_out = self.root.layout(font_context, mode);
+        let size = Vec2 {
+            inline: laid_out.inline_size,
+            block: laid_out.ascent + laid_out.descent,
+        };
+        (laid_out.fragments, size, laid_out.ascent)
+    }
+}
+
+struct Builder<'a> {
+    context: &'a Context<'a>,
+}
+
+fn element_name<'a>(context: &'a Context, element: NodeId) -> &'a str {
+    let name = &context.document[element].as_element().unwrap().name;
+    if name.ns == ns!(mathml) {
+        &name.local
+    } else {
+        ""
+    }
+}
+
+impl Builder<'_> {
+    fn element(
+        &self,
+        element: NodeId,
+        parent_style: &Arc<ComputedValues>,
+        compact: bool,
+    ) -> Option<MathBox> {
+        let mut style = style_for_element(
+            self.context.author_styles,
+            self.context.document,
+            element,
+            Some(parent_style),
+        );
+        if style.box_.display == Display::None {
+            return None;
+        }
+        let name = element_name(self.context, element);
+        if let "mi" | "mn" | "mo" | "mtext" = name {
+            let text = self.context.document.child_text_content(element);
+            // https://w3c.github.io/mathml-core/#token-elements
+            // Whitespace is trimmed and collapsed.
+            let text = text.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+            let mathvariant = self.context.document[element]
+                .as_element()
+                .unwrap()
+                .get_attr(&local_name!("mathvariant"));
+            // https://w3c.github.io/mathml-core/#new-text-transform-values
+            // FIXME: map to Mathematical Alphanumeric Symbols when the font has them,
+            // instead of synthesizing italics.
+            if name == "mi" && text.chars().count() == 1 && mathvariant != Some("normal") {
+                Arc::make_mut(&mut Arc::make_mut(&mut style).font).font_style = FontStyle::Italic
+            }
+            let spacing = if name == "mo" {
+                operator_spacing(&text)
+            } else {
+                (0., 0.)
+            };
+            return Some(MathBox::Token {
+                style,
+                text,
+                spacing,
+            });
+        }
+        let mut children = self.children_elements(element);
+        let mut next = |style| Box::new(self.element_or_empty(children.next(), style));
+        // Elements with the wrong number of children are laid out like `mrow`.
+        match (name, self.children_elements(element).count()) {
+            ("mfrac", 2) => {
+                let children_style = if compact {
+                    scaled(&style)
+                } else {
+                    style.clone()
+                };
+                Some(MathBox::Fraction {
+                    numerator: next(&children_style),
+                    denominator: next(&children_style),
+                    compact,
+                    style,
+                })
+            }
+            ("msub", 2) | ("msup", 2) | ("msubsup", 3) => {
+                let base = next(&style);
+                let script_style = scaled(&style);
+                let subscript = if name == "msup" {
+                    None
+                } else {
+                    Some(next(&script_style))
+                };
+                let superscript = if name == "msub" {
+                    None
+                } else {
+                    Some(next(&script_style))
+                };
+                Some(MathBox::Scripts {
+                    style,
+                    base,
+                    subscript,
+                    superscript,
+                })
+            }
+            ("msqrt", _) => {
+                let radicand = self.children(element, &style, compact, "mrow");
+                Some(MathBox::Sqrt {
+                    style,
+                    compact,
+                    radicand: Box::new(radicand),
+                })
+            }
+            _ => Some(self.children(element, &style, compact, name)),
+        }
+    }
+
+    fn element_or_empty(&self, element: Option<NodeId>, style: &Arc<ComputedValues>) -> MathBox {
+        // Children of fractions and scripts are always laid out in compact style
+        element
+            .and_then(|element| self.element(element, style, /* compact = */ true))
+            .unwrap_or(MathBox::Row(Vec::new()))
+    }
+
+    /// Children of an element laid out like `mrow`
+    fn children(
+        &self,
+        element: NodeId,
+        style: &Arc<ComputedValues>,
+        compact: bool,
+        name: &str,
+    ) -> MathBox {
+        let mut children = self.children_elements(element);
+        // https://w3c.github.io/mathml-core/#semantics-and-presentation
+        // Only the first child of `semantics` is rendered, annotations are not.
+        if name == "semantics" {
+            return children
+                .next()
+                .and_then(|child| self.element(child, style, compact))
+                .unwrap_or(MathBox::Row(Vec::new()));
+        }
+        MathBox::Row(
+            children
+                .filter_map(|child| self.element(child, style, compact))
+                .collect(),
+        )
+    }
+
+    /// Text directly in elements other than tokens is not rendered.
+    fn children_elements(&self, element: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let document = self.context.document;
+        document[element]
+            .first_child
+            .into_iter()
+            .flat_map(move |first| document.node_and_following_siblings(first))
+            .filter(move |&child| document[child].as_element().is_some())
+    }
+}
+
+fn scaled(style: &Arc<ComputedValues>) -> Arc<ComputedValues> {
+    let mut style = style.clone();
+    let font = &mut Arc::make_mut(&mut Arc::make_mut(&mut style).font);
+    font.font_size = FontSize(font.font_size.0 * SCRIPT_SCALE);
+    style
+}
+
+/// The space before and after an infix operator, in ems.
+///
+/// https://w3c.github.io/mathml-core/#operator-dictionary
+/// FIXME: the rest of the dictionary, and prefix and postfix forms
+/// for operators at the start and end of a row, which have no space.
+fn operator_spacing(text: &str) -> (f32, f32) {
+    match text {
+        "=" | "<" | ">" | "≤" | "≥" | "≠" | "≈" | "≡" | "→" | "←" | "↔" => {
+            (5. / 18., 5. / 18.)
+        }
+        "+" | "-" | "−" | "±" | "∓" | "×" | "÷" | "·" => (4. / 18., 4. / 18.),
+        "," | ";" => (0., 3. / 18.),
+        _ => (0., 0.),
+    }
+}
+
+impl MathBox {
+    fn layout(&self, font_context: &FontContext, mode: (WritingMode, Direction)) -> MathFragments {
+        match self {
+            MathBox::Row(children) => {
+                let children = children
+                    .iter()
+                    .map(|child| child.layout(font_context, mode))
+                    .collect::<Vec<_>>();
+                let ascent = children
+                    .iter()
+                    .fold(Length::zero(), |max, child| max.max(child.ascent));
+                let descent = children
+                    .iter()
+                    .fold(Length::zero(), |max, child| max.max(child.descent));
+                let mut inline_size = Length::zero();
+                let mut fragments = Vec::with_capacity(children.len());
+                for child in children {
+                    let start_corner = Vec2 {
+                        inline: inline_size,
+                        block: ascent - child.ascent,
+                    };
+                    inline_size += child.inline_size;
+                    fragments.push(child.into_fragment(start_corner, mode))
+                }
+                MathFragments {
+                    fragments,
+                    inline_size,
+                    ascent,
+                    descent,
+                }
+            }
+            MathBox::Token {
+                style,
+                text,
+                spacing,
+            } => {
+                let em = style.font.font_size.0;
+                let mut laid_out = text_fragments(style, text, font_context);
+                let (before, after) = (em * spacing.0, em * spacing.1);
+                for fragment in &mut laid_out.fragments {
+                    if let Fragment::Text(text) = fragment {
+                        text.content_rect.start_corner.inline += before
+                    }
+                }
+                laid_out.inline_size += before + after;
+                laid_out
+            }
+            MathBox::Fraction {
+                style,
+                compact,
+                numerator,
+                denominator,
+            } => {
+                // https://w3c.github.io/mathml-core/#fraction-with-nonzero-line-thickness
+                let em = style.font.font_size.0;
+                let thickness = em * RULE_THICKNESS;
+                let axis = em * AXIS_HEIGHT;
+                let (gap, min_shift_up, min_shift_down) = if *compact {
+                    (thickness, em * 0.394, em * 0.345)
+                } else {
+                    (thickness * 3., em * 0.677, em * 0.686)
+                };
+                // `mfrac { padding-inline: 1px }` in the user-agent stylesheet
+                let padding = Length::from_px(1.);
+                let numerator = numerator.layout(font_context, mode);
+                let denominator = denominator.layout(font_context, mode);
+                let shift_up = min_shift_up.max(axis + thickness / 2. + gap + numerator.descent);
+                let shift_down =
+                    min_shift_down.max(denominator.ascent + gap + thickness / 2. - axis);
+                let ascent = shift_up + numerator.ascent;
+                let descent = shift_down + denominator.descent;
+                let width = numerator.inline_size.max(denominator.inline_size);
+                let bar = rule(
+                    style,
+                    Rect {
+                        start_corner: Vec2 {
+                            inline: padding,
+                            block: ascent - axis - thickness / 2.,
+                        },
+                        size: Vec2 {
+                            inline: width,
+                            block: thickness,
+                        },
+                    },
+                );
+                let centered = |child: &MathFragments, baseline| Vec2 {
+                    inline: padding + (width - child.inline_size) / 2.,
+                    block: baseline - child.ascent,
+                };
+                let numerator_start = centered(&numerator, ascent - shift_up);
+                let denominator_start = centered(&denominator, ascent + shift_down);
+                MathFragments {
+                    fragments: vec![
+                        numerator.into_fragment(numerator_start, mode),
+                        bar,
+                        denominator.into_fragment(denominator_start, mode),
+                    ],
+                    inline_size: width + padding * 2.,
+                    ascent,
+                    descent,
+                }
+            }
+            MathBox::Scripts {
+                style,
+                base,
+                subscript,
+                superscript,
+            } => {
+                // https://w3c.github.io/mathml-core/#base-with-subscript
+                // https://w3c.github.io/mathml-core/#base-with-superscript
+                // FIXME: italic correction of the base.
+                let em = style.font.font_size.0;
+                let base = base.layout(font_context, mode);
+                let subscript = subscript
+                    .as_ref()
+                    .map(|script| script.layout(font_context, mode));
+                let superscript = superscript
+                    .as_ref()
+                    .map(|script| script.layout(font_context, mode));
+                let sub_shift = subscript.as_ref().map(|sub| {
+                    (em * 0.25)
+                        .max(base.descent + em * 0.05)
+                        .max(sub.ascent - em * 0.35)
+                });
+                let sup_shift = superscript.as_ref().map(|sup| {
+                    (em * 0.4)
+                        .max(base.ascent - em * 0.25)
+                        .max(sup.descent + em * 0.11)
+                });
+                let sub_shift = match (&subscript, &superscript, sub_shift, sup_shift) {
+                    (Some(sub), Some(sup), Some(sub_shift), Some(sup_shift)) => {
+                        // Keep a gap between the two scripts
+                        let gap = (sup_shift - sup.descent) - (sub.ascent - sub_shift);
+                        let min_gap = em * (RULE_THICKNESS * 4.);
+                        Some(sub_shift + (min_gap - gap).max(Length::zero()))
+                    }
+                    _ => sub_shift,
+                };
+                let mut ascent = base.ascent;
+                let mut descent = base.descent;
+                if let (Some(sup), Some(shift)) = (&superscript, sup_shift) {
+                    ascent.max_assign(shift + sup.ascent)
+                }
+                if let (Some(sub), Some(shift)) = (&subscript, sub_shift) {
+                    descent.max_assign(shift + sub.descent)
+                }
+                let scripts_start = base.inline_size;
+                let mut scripts_size = Length::zero();
+                let base_start = Vec2 {
+                    inline: Length::zero(),
+                    block: ascent - base.ascent,
+                };
+                let mut fragments = vec![base.into_fragment(base_start, mode)];
+                for (script, baseline) in [
+                    (superscript, ascent - sup_shift.unwrap_or(Length::zero())),
+                    (subscript, ascent + sub_shift.unwrap_or(Length::zero())),
+                ] {
+                    if let Some(script) = script {
+                        scripts_size.max_assign(script.inline_size);
+                        let start_corner = Vec2 {
+                            inline: scripts_start,
+                            block: baseline - script.ascent,
+                        };
+                        fragments.push(script.into_fragment(start_corner, mode))
+                    }
+                }
+                // https://w3c.github.io/mathml-core/#dfn-spaceafterscript
+                let space_after_script = em * 0.056;
+                MathFragments {
+                    fragments,
+                    inline_size: scripts_start + scripts_size + space_after_script,
+                    ascent,
+                    descent,
+                }
+            }
+            MathBox::Sqrt {
+                style,
+                compact,
+                radicand,
+            } => {
+                // https://w3c.github.io/mathml-core/#radicals-msqrt-mroot
+                let em = style.font.font_size.0;
+                let thickness = em * RULE_THICKNESS;
+                let gap = if *compact {
+                    thickness * 1.25
+                } else {
+                    thickness + em * 0.125
+                };
+                let radicand = radicand.layout(font_context, mode);
+                let height = radicand.ascent + radicand.descent + gap + thickness;
+                // FIXME: use stretched glyph variants from the `MATH` table,
+                // instead of scaling the radical sign to the height of its radicand.
+                // The bar is at the top of the font’s ascent, not of the glyph itself.
+                let natural = text_fragments(style, "√", font_context);
+                let scale = (height.px() / (natural.ascent + natural.descent).px()).max(1.);
+                let mut radical_style = style.clone();
+                let font = &mut Arc::make_mut(&mut Arc::make_mut(&mut radical_style).font);
+                font.font_size = FontSize(font.font_size.0 * scale);
+                let radical = text_fragments(&radical_style, "√", font_context);
+                let ascent = radical.ascent.max(radicand.ascent + gap + thickness);
+                let radical_top = ascent - radical.ascent;
+                let bar = rule(
+                    style,
+                    Rect {
+                        start_corner: Vec2 {
+                            inline: radical.inline_size,
+                            block: radical_top,
+                        },
+                        size: Vec2 {
+                            inline: radicand.inline_size,
+                            block: thickness,
+                        },
+                    },
+                );
+                let descent = radical.descent.max(radicand.descent);
+                let inline_size = radical.inline_size + radicand.inline_size;
+                let radicand_start = Vec2 {
+                    inline: radical.inline_size,
+                    block: ascent - radicand.ascent,
+                };
+                let radical_start = Vec2 {
+                    inline: Length::zero(),
+                    block: radical_top,
+                };
+                MathFragments {
+                    fragments: vec![
+                        radical.into_fragment(radical_start, mode),
+                        bar,
+                        radicand.into_fragment(radicand_start, mode),
+                    ],
+                    inline_size,
+                    ascent,
+                    descent,
+                }
+            }
+        }
+    }
+}
+
+impl MathFragments {
+    fn into_fragment(self, start_corner: Vec2<Length>, mode: (WritingMode, Direction)) -> Fragment {
+        Fragment::Anonymous(AnonymousFragment {
+            rect: Rect {
+                start_corner,
+                size: Vec2 {
+                    inline: self.inline_size,
+                    block: self.ascent + self.descent,
+                },
+            },
+            children: self.fragments,
+            mode,
+        })
+    }
+}
+
+/// Text on a single line, falling back to other fonts for missing characters.
+/// Also used for the labels of form controls.
+pub(super) fn text_fragments(
+    style: &Arc<ComputedValues>,
+    text: &str,
+    font_context: &FontContext,
+) -> MathFragments {
+    let font_size = style.font.font_size;
+    let fonts =
+        font_context.generic_family_instances(style.font.font_family.0, &style.font_variations());
+    let mut laid_out = MathFragments {
+        fragments: Vec::new(),
+        inline_size: Length::zero(),
+        ascent: Length::zero(),
+        descent: Length::zero(),
+    };
+    let mut ascents = Vec::new();
+    for item in itemize(text, false, &fonts, font_context) {
+        // Items whose glyphs cannot be read from a malformed font are left out.
+        let shaped = match shape_item(&item, &Substitutions::default(), font_context) {
+            Ok(shaped) => shaped,
+            Err(_) => continue,
+        };
+        let ascent = font_size * item.font.ascender();
+        let descent = -(font_size * item.font.descender());
+        laid_out.ascent.max_assign(ascent);
+        laid_out.descent.max_assign(descent);
+        let inline_size = font_size * shaped.advance_width;
+        ascents.push(ascent);
+        laid_out.fragments.push(Fragment::Text(TextFragment {
+            parent_style: style.clone(),
+            content_rect: Rect {
+                start_corner: Vec2 {
+                    inline: laid_out.inline_size,
+                    block: Length::zero(),
+                },
+                size: Vec2 {
+                    inline: inline_size,
+                    block: ascent + descent,
+                },
+            },
+            font_size: font_size.0,
+            text: shaped,
+            bidi_level: 0,
+        }));
+        laid_out.inline_size += inline_size;
+    }
+    // Align the baselines of fonts with different ascents
+    for (fragment, ascent) in laid_out.fragments.iter_mut().zip(ascents) {
+        if let Fragment::Text(text) = fragment {
+            text.content_rect.start_corner.block = laid_out.ascent - ascent
+        }
+    }
+    if laid_out.fragments.is_empty() {
+        let font = font_context.first_available_font(style.font.font_family.0);
+        laid_out.ascent = font_size * font.ascender();
+        laid_out.descent = -(font_size * font.descender());
+    }
+    laid_out
+}
+
+/// A fraction bar or the top of a radical, painted in the text color
+fn rule(style: &Arc<ComputedValues>, rect: Rect<Length>) -> Fragment {
+    let mut style = ComputedValues::anonymous_inheriting_from(Some(style));
+    Arc::make_mut(&mut Arc::make_mut(&mut style).background).background_color = Color::CurrentColor;
+    Fragment::Box(BoxFragment {
+        tag: None,
+        style,
+        children: Vec::new(),
+        content_rect: rect,
+        padding: Sides::zero(),
+        border: Sides::zero(),
+        margin: Sides::zero(),
+        block_margins_collapsed_with_children: CollapsedBlockMargins::zero(),
+    })
+}
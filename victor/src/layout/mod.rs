@@ -1,25 +1,36 @@
 use crate::dom;
+use crate::fonts::FontContext;
 use crate::geom::flow_relative::{Rect, Sides, Vec2};
 use crate::geom::Length;
 use crate::style::values::*;
-use crate::style::{style_for_element, ComputedValues};
+use crate::style::{document_may_use_counters, style_for_element, ComputedValues};
 use std::convert::TryInto;
 use std::sync::Arc;
 
+mod counters;
 mod dom_traversal;
 mod element_data;
 mod flow;
 mod fragments;
+mod images;
+mod math;
 mod positioned;
 mod replaced;
+mod shapes;
+mod sizing;
 
+use counters::*;
 use dom_traversal::*;
 use flow::*;
+use math::*;
 use positioned::*;
 use replaced::*;
+use sizing::*;
 
 pub(crate) use element_data::*;
 pub(crate) use fragments::*;
+pub(crate) use images::*;
+pub(crate) use shapes::*;
 
 /// https://drafts.csswg.org/css-display/#independent-formatting-context
 #[derive(Debug)]
@@ -28,11 +39,15 @@ enum IndependentFormattingContext {
 
     // Not called FC in specs, but behaves close enough
     Replaced(ReplacedContent),
+
+    /// https://w3c.github.io/mathml-core/#new-display-math-value
+    Math(MathContent),
     // Other layout modes go here
 }
 
 enum NonReplacedIFC<'a> {
     Flow(&'a BlockFormattingContext),
+    Math(&'a MathContent),
 }
 
 impl IndependentFormattingContext {
@@ -44,13 +59,21 @@ impl IndependentFormattingContext {
     ) -> Self {
         match contents.try_into() {
             Ok(non_replaced) => match display_inside {
-                DisplayInside::Flow | DisplayInside::FlowRoot => {
+                // FIXME: a block-level ruby container is a block box
+                // around an inline-level one, not a block container.
+                DisplayInside::Flow | DisplayInside::FlowRoot | DisplayInside::Ruby => {
                     IndependentFormattingContext::Flow(BlockFormattingContext::construct(
                         context,
                         style,
                         non_replaced,
                     ))
                 }
+                DisplayInside::Math => IndependentFormattingContext::Math(MathContent::construct(
+                    context,
+                    style,
+                    non_replaced,
+                    /* compact = */ false,
+                )),
             },
             Err(replaced) => IndependentFormattingContext::Replaced(replaced),
         }
@@ -60,18 +83,47 @@ impl IndependentFormattingContext {
         match self {
             IndependentFormattingContext::Replaced(r) => Ok(r),
             IndependentFormattingContext::Flow(f) => Err(NonReplacedIFC::Flow(f)),
+            IndependentFormattingContext::Math(m) => Err(NonReplacedIFC::Math(m)),
         }
     }
 
+    /// `style` is that of the box that establishes this formatting context.
     fn layout<'a>(
         &'a self,
+        layout_context: &LayoutContext,
+        style: &Arc<ComputedValues>,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
     ) -> FlowChildren {
         match self.as_replaced() {
-            Ok(replaced) => match *replaced {},
-            Err(ifc) => ifc.layout(containing_block, tree_rank, absolutely_positioned_fragments),
+            // The box around replaced content has already resolved `width` and `height`,
+            // its content box is the containing block.
+            // FIXME: floats and absolutely positioned boxes with `width: auto`
+            // should use the intrinsic width of replaced content.
+            Ok(replaced) => {
+                let font_context = layout_context.font_context;
+                let size = Vec2 {
+                    inline: containing_block.inline_size,
+                    block: containing_block.block_size.auto_is(|| {
+                        replaced
+                            .used_size(style, containing_block, font_context)
+                            .block
+                    }),
+                };
+                let (fragments, _ascent) = replaced.make_fragments(style, &size, font_context);
+                FlowChildren {
+                    fragments,
+                    block_size: size.block,
+                    collapsible_margins_in_children: CollapsedBlockMargins::zero(),
+                }
+            }
+            Err(ifc) => ifc.layout(
+                layout_context,
+                containing_block,
+                tree_rank,
+                absolutely_positioned_fragments,
+            ),
         }
     }
 }
@@ -79,18 +131,58 @@ impl IndependentFormattingContext {
 impl<'a> NonReplacedIFC<'a> {
     fn layout(
         &self,
+        layout_context: &LayoutContext,
         containing_block: &ContainingBlock,
         tree_rank: usize,
         absolutely_positioned_fragments: &mut Vec<AbsolutelyPositionedFragment<'a>>,
     ) -> FlowChildren {
         match self {
-            NonReplacedIFC::Flow(bfc) => {
-                bfc.layout(containing_block, tree_rank, absolutely_positioned_fragments)
+            NonReplacedIFC::Flow(bfc) => bfc.layout(
+                layout_context,
+                containing_block,
+                tree_rank,
+                absolutely_positioned_fragments,
+            ),
+            // https://w3c.github.io/mathml-core/#layout-of-the-top-level-math-element
+            // Block math is centered.
+            NonReplacedIFC::Math(math) => {
+                let (children, size, _ascent) =
+                    math.layout(layout_context.font_context, containing_block.mode);
+                let rect = Rect {
+                    start_corner: Vec2 {
+                        inline: (containing_block.inline_size - size.inline) / 2.,
+                        block: Length::zero(),
+                    },
+                    size,
+                };
+                FlowChildren {
+                    block_size: rect.size.block,
+                    fragments: vec![Fragment::Anonymous(AnonymousFragment {
+                        rect,
+                        children,
+                        mode: containing_block.mode,
+                    })],
+                    collapsible_margins_in_children: CollapsedBlockMargins::zero(),
+                }
             }
         }
     }
 }
 
+/// What layout needs besides the box tree.
+struct LayoutContext<'a> {
+    font_context: &'a FontContext,
+    /// See `Document::set_cancellation_token`
+    cancellation_token: Option<&'a dom::CancellationToken>,
+}
+
+impl LayoutContext<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .is_some_and(dom::CancellationToken::is_cancelled)
+    }
+}
+
 struct ContainingBlock {
     inline_size: Length,
     block_size: LengthOrAuto,
@@ -105,10 +197,12 @@ struct DefiniteContainingBlock {
 /// https://drafts.csswg.org/css2/visuren.html#relative-positioning
 fn relative_adjustement(
     style: &ComputedValues,
-    inline_size: Length,
-    block_size: LengthOrAuto,
+    containing_block: &ContainingBlock,
 ) -> Vec2<Length> {
-    if !style.box_.position.is_relatively_positioned() {
+    // There is no scrolling in paged output, so a sticky box is never shifted
+    // as long as it fits in its page.
+    // Sticky boxes are not repeated on each page: `Document::css_diagnostics` reports them.
+    if style.box_.position != Position::Relative {
         return Vec2::zero();
     }
     fn adjust(start: LengthOrAuto, end: LengthOrAuto) -> Length {
@@ -118,11 +212,13 @@ fn relative_adjustement(
             (LengthOrAuto::Length(start), _) => start,
         }
     }
-    let block_size = block_size.auto_is(Length::zero);
-    let box_offsets = style.box_offsets().map_inline_and_block_axes(
-        |v| v.percentage_relative_to(inline_size),
-        |v| v.percentage_relative_to(block_size),
-    );
+    let block_size = containing_block.block_size.auto_is(Length::zero);
+    let box_offsets = style
+        .box_offsets(containing_block.mode)
+        .map_inline_and_block_axes(
+            |v| v.percentage_relative_to(containing_block.inline_size),
+            |v| v.percentage_relative_to(block_size),
+        );
     Vec2 {
         inline: adjust(box_offsets.inline_start, box_offsets.inline_end),
         block: adjust(box_offsets.block_start, box_offsets.block_end),
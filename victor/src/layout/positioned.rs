@@ -3,6 +3,7 @@ use rayon::prelude::*;
 
 #[derive(Debug)]
 pub(super) struct AbsolutelyPositionedBox {
+    pub tag: Option<dom::NodeId>,
     pub style: Arc<ComputedValues>,
     pub contents: IndependentFormattingContext,
 }
@@ -35,10 +36,13 @@ impl AbsolutelyPositionedBox {
     pub(super) fn layout<'a>(
         &'a self,
         initial_start_corner: Vec2<Length>,
+        static_containing_block_mode: (WritingMode, Direction),
         tree_rank: usize,
     ) -> AbsolutelyPositionedFragment {
         let style = &self.style;
-        let box_offsets = style.box_offsets();
+        // FIXME: the containing block of this box, found later while going up the tree,
+        // can have another direction than the one that its static position is in.
+        let box_offsets = style.box_offsets(static_containing_block_mode);
         let box_size = style.box_size();
 
         let inline_size = box_size.inline;
@@ -82,15 +86,36 @@ impl AbsolutelyPositionedBox {
 }
 
 impl<'a> AbsolutelyPositionedFragment<'a> {
+    /// Move the `position: fixed` fragments of `nested` to `ancestors`
+    /// when `style` does not establish their containing block,
+    /// so that the nearest ancestor that does (or else the page area) positions them.
+    fn pass_fixed_up(style: &ComputedValues, nested: &mut Vec<Self>, ancestors: &mut Vec<Self>) {
+        if style.establishes_containing_block_for_fixed_descendants() {
+            return;
+        }
+        let (fixed, absolute) = std::mem::take(nested)
+            .into_iter()
+            .partition(|a| a.absolutely_positioned_box.style.box_.position == Position::Fixed);
+        *nested = absolute;
+        ancestors.extend(fixed);
+    }
+
+    /// Lay out the fragments of `nested` in the padding box of a box with `style`
+    /// that establishes their containing block.
+    /// Those that `style` does not position are moved to `ancestors`,
+    /// with static positions relative to the box’s content box.
     pub(super) fn in_positioned_containing_block(
-        absolute: &[Self],
-        fragments: &mut Vec<Fragment>,
+        layout_context: &LayoutContext,
+        style: &ComputedValues,
+        mut nested: Vec<Self>,
+        ancestors: &mut Vec<Self>,
         content_rect_size: &Vec2<Length>,
         padding: &Sides<Length>,
         mode: (WritingMode, Direction),
-    ) {
-        if absolute.is_empty() {
-            return;
+    ) -> Option<Fragment> {
+        Self::pass_fixed_up(style, &mut nested, ancestors);
+        if nested.is_empty() {
+            return None;
         }
         let padding_rect = Rect {
             size: content_rect_size.clone(),
@@ -102,24 +127,68 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
             size: padding_rect.size.clone(),
             mode,
         };
-        fragments.push(Fragment::Anonymous(AnonymousFragment {
-            children: absolute
-                .par_iter()
-                .map(|a| a.layout(&containing_block))
-                .collect(),
+        let mut children = vec![];
+        while !nested.is_empty() {
+            nested = Self::layout_all(layout_context, &nested, &containing_block, &mut children);
+            for fixed in &mut nested {
+                fixed.translate_static_position(&padding_rect.start_corner)
+            }
+            Self::pass_fixed_up(style, &mut nested, ancestors);
+        }
+        Some(Fragment::Anonymous(AnonymousFragment {
+            children,
             rect: padding_rect,
             mode,
         }))
     }
 
-    pub(super) fn layout(&self, containing_block: &DefiniteContainingBlock) -> Fragment {
+    /// Lay out `absolute` in `containing_block`, appending their fragments to `children`.
+    ///
+    /// Returns the `position: fixed` descendants that their boxes pass up,
+    /// with static positions relative to `containing_block`.
+    pub(super) fn layout_all(
+        layout_context: &LayoutContext,
+        absolute: &[Self],
+        containing_block: &DefiniteContainingBlock,
+        children: &mut Vec<Fragment>,
+    ) -> Vec<Self> {
+        let (fragments, fixed): (Vec<_>, Vec<_>) = absolute
+            .par_iter()
+            .map(|a| a.layout(layout_context, containing_block))
+            .unzip();
+        children.extend(fragments);
+        fixed.into_iter().flatten().collect()
+    }
+
+    fn translate_static_position(&mut self, by: &Vec2<Length>) {
+        if let AbsoluteBoxOffsets::StaticStart { start } = &mut self.inline_start {
+            *start += by.inline;
+        }
+        if let AbsoluteBoxOffsets::StaticStart { start } = &mut self.block_start {
+            *start += by.block;
+        }
+    }
+
+    /// Also returns the `position: fixed` descendants that this box does not position,
+    /// with static positions relative to `containing_block`.
+    fn layout(
+        &self,
+        layout_context: &LayoutContext,
+        containing_block: &DefiniteContainingBlock,
+    ) -> (Fragment, Vec<Self>) {
         let style = &self.absolutely_positioned_box.style;
         let cbis = containing_block.size.inline;
         let cbbs = containing_block.size.block;
 
-        let padding = style.padding().percentages_relative_to(cbis);
-        let border = style.border_width().percentages_relative_to(cbis);
-        let computed_margin = style.margin().percentages_relative_to(cbis);
+        let padding = style
+            .padding(containing_block.mode)
+            .percentages_relative_to(cbis);
+        let border = style
+            .border_width(containing_block.mode)
+            .percentages_relative_to(cbis);
+        let computed_margin = style
+            .margin(containing_block.mode)
+            .percentages_relative_to(cbis);
         let pb = &padding + &border;
 
         enum Anchor {
@@ -206,8 +275,8 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
             computed_margin.inline_start,
             computed_margin.inline_end,
             |margins| {
-                if margins.px >= 0. {
-                    (margins / 2., margins / 2.)
+                if margins >= Length::zero() {
+                    (margins / 2., margins - margins / 2.)
                 } else {
                     (Length::zero(), margins)
                 }
@@ -221,7 +290,7 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
             pb.block_sum(),
             computed_margin.block_start,
             computed_margin.block_end,
-            |margins| (margins / 2., margins / 2.),
+            |margins| (margins / 2., margins - margins / 2.),
             self.block_start,
             self.block_size,
         );
@@ -246,16 +315,13 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
         let containing_block_for_children = ContainingBlock {
             inline_size,
             block_size,
-            mode: style.writing_mode(),
+            mode: style.writing_mode_in(containing_block.mode),
         };
-        // https://drafts.csswg.org/css-writing-modes/#orthogonal-flows
-        assert_eq!(
-            containing_block.mode, containing_block_for_children.mode,
-            "Mixed writing modes are not supported yet"
-        );
         let dummy_tree_rank = 0;
         let mut absolutely_positioned_fragments = vec![];
         let mut flow_children = self.absolutely_positioned_box.contents.layout(
+            layout_context,
+            style,
             &containing_block_for_children,
             dummy_tree_rank,
             &mut absolutely_positioned_fragments,
@@ -283,15 +349,25 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
             },
         };
 
-        AbsolutelyPositionedFragment::in_positioned_containing_block(
-            &absolutely_positioned_fragments,
-            &mut flow_children.fragments,
-            &content_rect.size,
-            &padding,
-            containing_block_for_children.mode,
+        let mut fixed = vec![];
+        flow_children.fragments.extend(
+            AbsolutelyPositionedFragment::in_positioned_containing_block(
+                layout_context,
+                style,
+                absolutely_positioned_fragments,
+                &mut fixed,
+                &content_rect.size,
+                &padding,
+                containing_block_for_children.mode,
+            ),
         );
+        for a in &mut fixed {
+            a.tree_rank = self.tree_rank;
+            a.translate_static_position(&content_rect.start_corner);
+        }
 
-        Fragment::Box(BoxFragment {
+        let fragment = Fragment::Box(BoxFragment {
+            tag: self.absolutely_positioned_box.tag,
             style: style.clone(),
             children: flow_children.fragments,
             content_rect,
@@ -299,7 +375,8 @@ impl<'a> AbsolutelyPositionedFragment<'a> {
             border,
             margin,
             block_margins_collapsed_with_children: CollapsedBlockMargins::zero(),
-        })
+        });
+        (fragment, fixed)
     }
 }
 
@@ -316,13 +393,6 @@ pub(super) fn adjust_static_positions(
         };
 
         abspos_fragment.tree_rank = tree_rank_in_parent;
-
-        if let AbsoluteBoxOffsets::StaticStart { start } = &mut abspos_fragment.inline_start {
-            *start += child_fragment_rect.start_corner.inline;
-        }
-
-        if let AbsoluteBoxOffsets::StaticStart { start } = &mut abspos_fragment.block_start {
-            *start += child_fragment_rect.start_corner.block;
-        }
+        abspos_fragment.translate_static_position(&child_fragment_rect.start_corner);
     }
 }
@@ -1,14 +1,635 @@
 use super::*;
-use crate::dom::NodeId;
+use crate::dom::{NodeData, NodeId};
+use crate::fonts::FontContext;
+use crate::geom::physical;
+use crate::style::values::{ImageOrientation, ObjectFit};
 
 #[derive(Debug)]
 pub(super) enum ReplacedContent {
-    // Not implemented yet
+    FormControl {
+        control: FormControl,
+        /// The name of the PDF form field, from the `name` or `id` attribute
+        field_name: String,
+    },
+
+    /// An `<img>` element, with the size of its image in image pixels as stored,
+    /// its density in image pixels per CSS `px`, and its EXIF orientation.
+    /// Only the size is kept for layout, painting decodes the image again.
+    Image {
+        element: NodeId,
+        width: u32,
+        height: u32,
+        density: f32,
+        orientation: Orientation,
+    },
+
+    /// Embedded content that is not rendered, or not rendered yet: an empty box
+    /// sized by its `width` and `height` attributes (through presentational hints).
+    ///
+    /// https://html.spec.whatwg.org/multipage/rendering.html#embedded-content-rendering-rules
+    Placeholder,
+}
+
+/// Form controls are drawn as they look before any interaction, so that printed forms
+/// look complete. Text entries, checkboxes, `<select>` and `<textarea>` are also
+/// fields of the PDF’s interactive form at their border box, that users can fill in
+/// a PDF reader.
+///
+/// https://html.spec.whatwg.org/multipage/rendering.html#form-controls
+///
+/// FIXME: radio buttons as fields, which in PDF are grouped by name,
+/// and `file`, `image`, `color` and `range` inputs.
+#[derive(Debug)]
+pub(super) enum FormControl {
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-input-element-as-a-text-entry-widget
+    TextEntry {
+        value: String,
+        /// The `size` attribute, a number of characters
+        size: u32,
+        /// Drawn with bullets, and not a form field since its value would be readable
+        password: bool,
+    },
+
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-textarea-element-2
+    TextArea {
+        value: String,
+        /// The `cols` and `rows` attributes
+        cols: u32,
+        rows: u32,
+    },
+
+    /// A drop-down box showing the selected option:
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-select-element-2
+    ///
+    /// FIXME: list boxes for `multiple` or a `size` greater than 1.
+    Select {
+        /// The label of each `<option>`
+        options: Vec<String>,
+        /// An index in `options`
+        selected: Option<usize>,
+    },
+
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-input-element-as-a-checkbox-and-radio-button-widgets
+    Checkbox { radio: bool, checked: bool },
+
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-button-element
+    /// https://html.spec.whatwg.org/multipage/rendering.html#the-input-element-as-a-button
+    Button { label: String },
 }
 
 impl ReplacedContent {
-    pub fn for_element(_element: NodeId, _context: &Context) -> Option<Self> {
-        // FIXME: implement <img> etc.
+    pub fn for_element(element: NodeId, context: &Context) -> Option<Self> {
+        if let Some(control) = FormControl::for_element(element, context) {
+            let field_name = form_field_name(element, context);
+            return Some(ReplacedContent::FormControl {
+                control,
+                field_name,
+            });
+        }
+        if let Some(image) = image_for_element(element, context) {
+            return Some(image);
+        }
+        if is_placeholder(element, context) {
+            return Some(ReplacedContent::Placeholder);
+        }
+        // FIXME: `<img>` elements with images that are not supported (see `JpegImage`)
+        // or not found are rendered as their `alt` text, inline:
+        // https://html.spec.whatwg.org/multipage/rendering.html#images-3
         None
     }
+
+    /// https://drafts.csswg.org/css2/visudet.html#inline-replaced-width
+    /// https://drafts.csswg.org/css2/visudet.html#inline-replaced-height
+    pub fn used_size(
+        &self,
+        style: &Arc<ComputedValues>,
+        containing_block: &ContainingBlock,
+        font_context: &FontContext,
+    ) -> Vec2<Length> {
+        let box_size = style.box_size();
+        let intrinsic = self.intrinsic_size(style, font_context);
+        let inline = box_size
+            .inline
+            .percentage_relative_to(containing_block.inline_size);
+        // FIXME: percentages of a definite containing block height
+        let block = match box_size.block {
+            LengthOrPercentageOrAuto::Length(length) => LengthOrAuto::Length(length),
+            _ => LengthOrAuto::Auto,
+        };
+        // Images keep their aspect ratio when only one of `width` and `height` is set.
+        let ratio = match self {
+            ReplacedContent::Image { .. } => Some(intrinsic.inline.px() / intrinsic.block.px()),
+            _ => None,
+        };
+        match (inline, block, ratio) {
+            (LengthOrAuto::Length(inline), LengthOrAuto::Auto, Some(ratio)) => Vec2 {
+                inline,
+                block: inline / ratio,
+            },
+            (LengthOrAuto::Auto, LengthOrAuto::Length(block), Some(ratio)) => Vec2 {
+                inline: block * ratio,
+                block,
+            },
+            (inline, block, _) => Vec2 {
+                inline: inline.auto_is(|| intrinsic.inline),
+                block: block.auto_is(|| intrinsic.block),
+            },
+        }
+    }
+
+    fn intrinsic_size(
+        &self,
+        style: &Arc<ComputedValues>,
+        font_context: &FontContext,
+    ) -> Vec2<Length> {
+        match self {
+            ReplacedContent::FormControl { control, .. } => {
+                control.intrinsic_size(style, font_context)
+            }
+            ReplacedContent::Image {
+                width,
+                height,
+                density,
+                orientation,
+                ..
+            } => {
+                let (width, height) = if used_orientation(style, *orientation).is_transposed() {
+                    (height, width)
+                } else {
+                    (width, height)
+                };
+                Vec2 {
+                    inline: Length::from_px(*width as f32 / density),
+                    block: Length::from_px(*height as f32 / density),
+                }
+            }
+            // The default object size of HTML:
+            // https://drafts.csswg.org/css-images-3/#default-object-size
+            ReplacedContent::Placeholder => Vec2 {
+                inline: Length::from_px(300.),
+                block: Length::from_px(150.),
+            },
+        }
+    }
+
+    /// The fragments that fill a content box of this size,
+    /// and the distance from its top to its baseline
+    pub fn make_fragments(
+        &self,
+        style: &Arc<ComputedValues>,
+        size: &Vec2<Length>,
+        font_context: &FontContext,
+    ) -> (Vec<Fragment>, Length) {
+        match self {
+            ReplacedContent::FormControl {
+                control,
+                field_name,
+            } => {
+                let (mut fragments, ascent) = control.make_fragments(style, size, font_context);
+                if let Some(value) = control.form_field_value() {
+                    fragments.push(Fragment::FormField(FormFieldFragment {
+                        name: field_name.clone(),
+                        value,
+                    }))
+                }
+                (fragments, ascent)
+            }
+            &ReplacedContent::Image {
+                element,
+                width,
+                height,
+                orientation,
+                ..
+            } => {
+                let intrinsic = self.intrinsic_size(style, font_context);
+                let fragment = Fragment::Image(ImageFragment {
+                    content_rect: Rect {
+                        start_corner: Vec2::zero(),
+                        size: size.clone(),
+                    },
+                    object_rect: object_rect(style, &intrinsic, size),
+                    element,
+                    width,
+                    height,
+                    orientation: used_orientation(style, orientation),
+                });
+                // The baseline is the bottom of the box, like for checkboxes
+                (vec![fragment], size.block)
+            }
+            ReplacedContent::Placeholder => (Vec::new(), size.block),
+        }
+    }
+}
+
+/// The image selected for an `<img>` element, if it is supported
+fn image_for_element(element: NodeId, context: &Context) -> Option<ReplacedContent> {
+    let document = context.document;
+    let data = document[element].as_element()?;
+    if data.name.expanded() != expanded_name!(html "img") {
+        return None;
+    }
+    let (url, density) = document.image_source(element)?;
+    let JpegImage {
+        width,
+        height,
+        orientation,
+        ..
+    } = JpegImage::from_url(url)?;
+    Some(ReplacedContent::Image {
+        element,
+        width,
+        height,
+        density,
+        orientation,
+    })
+}
+
+/// Where an image is painted, relative to the top-left corner of its content box of `size`:
+/// scaled by `object-fit` from its `intrinsic` size, and placed by `object-position`.
+///
+/// https://drafts.csswg.org/css-images-3/#the-object-fit
+fn object_rect(
+    style: &ComputedValues,
+    intrinsic: &Vec2<Length>,
+    size: &Vec2<Length>,
+) -> physical::Rect<Length> {
+    let mode = style.writing_mode();
+    let box_size = size.size_to_physical(mode);
+    let intrinsic = intrinsic.size_to_physical(mode);
+    let scaled = |scale: f32| physical::Vec2 {
+        x: intrinsic.x * scale,
+        y: intrinsic.y * scale,
+    };
+    // The scale at which the image fits the box in both axes
+    let contain = (box_size.x.px() / intrinsic.x.px()).min(box_size.y.px() / intrinsic.y.px());
+    let object_size = match style.box_.object_fit {
+        ObjectFit::Fill => box_size.clone(),
+        ObjectFit::Contain => scaled(contain),
+        ObjectFit::Cover => {
+            scaled((box_size.x.px() / intrinsic.x.px()).max(box_size.y.px() / intrinsic.y.px()))
+        }
+        ObjectFit::None => intrinsic.clone(),
+        ObjectFit::ScaleDown => scaled(contain.min(1.)),
+    };
+    // Percentages of the space left in the box
+    let position = &style.box_.object_position;
+    let (free_x, free_y) = (box_size.x - object_size.x, box_size.y - object_size.y);
+    let x = position.x.percentage_relative_to(free_x);
+    let y = position.y.percentage_relative_to(free_y);
+    physical::Rect {
+        top_left: physical::Vec2 { x, y },
+        size: object_size,
+    }
+}
+
+/// The EXIF orientation of an image, unless `image-orientation` ignores it
+fn used_orientation(style: &ComputedValues, orientation: Orientation) -> Orientation {
+    match style.inherited_box.image_orientation {
+        ImageOrientation::FromImage => orientation,
+        ImageOrientation::None => Orientation::AS_STORED,
+    }
+}
+
+/// The `name` attribute of a form control, or its `id`, or its tag name.
+/// `pdf::Page::add_form_field` makes names unique in the document.
+fn form_field_name(element: NodeId, context: &Context) -> String {
+    let data = context.document[element].as_element().unwrap();
+    let name = data
+        .get_attr(&local_name!("name"))
+        .filter(|name| !name.is_empty());
+    match (name, &data.id) {
+        (Some(name), _) => name.to_owned(),
+        (None, Some(id)) => id.to_string(),
+        (None, None) => data.name.local.to_string(),
+    }
+}
+
+/// Whether this element is embedded content drawn as an empty box,
+/// rather than through its fallback content (its children).
+fn is_placeholder(element: NodeId, context: &Context) -> bool {
+    let document = context.document;
+    let data = match document[element].as_element() {
+        Some(data) if data.name.ns == ns!(html) => data,
+        _ => return false,
+    };
+    match data.name.local {
+        // Neither has fallback content: the children of `<iframe>` are not even parsed,
+        // and `<embed>` is a void element.
+        //
+        // FIXME: the document of `<iframe srcdoc>` could be laid out in the box.
+        local_name!("iframe") | local_name!("embed") => true,
+        // The content of `<video>` is for browsers that do not support it:
+        // https://html.spec.whatwg.org/multipage/media.html#the-video-element
+        local_name!("video") => true,
+        // Without scripting, `<canvas>` represents its fallback content:
+        // https://html.spec.whatwg.org/multipage/canvas.html#the-canvas-element
+        //
+        // FIXME: `<object>` with `data` that is an image could be drawn like `<img>`.
+        local_name!("canvas") | local_name!("object") => !has_fallback_content(element, context),
+        _ => false,
+    }
+}
+
+/// Whether the children of this element include something to render,
+/// other than whitespace and `<param>` elements.
+fn has_fallback_content(element: NodeId, context: &Context) -> bool {
+    let document = context.document;
+    let mut child = document[element].first_child;
+    while let Some(node) = child {
+        match &document[node].data {
+            NodeData::Text { contents } if contents.chars().any(|c| !c.is_ascii_whitespace()) => {
+                return true
+            }
+            NodeData::Element(child_data)
+                if child_data.name.expanded() != expanded_name!(html "param") =>
+            {
+                return true
+            }
+            _ => {}
+        }
+        child = document[node].next_sibling;
+    }
+    false
+}
+
+impl FormControl {
+    fn for_element(element: NodeId, context: &Context) -> Option<Self> {
+        let document = context.document;
+        let data = document[element].as_element()?;
+        if data.name.ns != ns!(html) {
+            return None;
+        }
+        let attr = |name| data.get_attr(&name);
+        let positive_integer = |name, default| {
+            attr(name)
+                .and_then(|value| value.trim().parse().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or(default)
+        };
+        match data.name.local {
+            local_name!("button") => {
+                // FIXME: the text of descendants, not only of children
+                let text = document.child_text_content(element);
+                let label = text.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+                Some(FormControl::Button { label })
+            }
+            local_name!("input") => {
+                let type_ = attr(local_name!("type")).unwrap_or("").to_ascii_lowercase();
+                let value = attr(local_name!("value"));
+                match &*type_ {
+                    "checkbox" | "radio" => Some(FormControl::Checkbox {
+                        radio: type_ == "radio",
+                        checked: attr(local_name!("checked")).is_some(),
+                    }),
+                    "submit" | "reset" | "button" => {
+                        // https://html.spec.whatwg.org/multipage/input.html#submit-button-state-(type=submit)
+                        let default_label = match &*type_ {
+                            "submit" => "Submit",
+                            "reset" => "Reset",
+                            _ => "",
+                        };
+                        let label = value.unwrap_or(default_label).to_owned();
+                        Some(FormControl::Button { label })
+                    }
+                    "hidden" | "file" | "image" | "color" | "range" => None,
+                    // Text, and the other types (including invalid ones) that have a value
+                    _ => {
+                        let value = value.unwrap_or("");
+                        let password = type_ == "password";
+                        let value = if password {
+                            "\u{2022}".repeat(value.chars().count())
+                        } else {
+                            value.to_owned()
+                        };
+                        // https://html.spec.whatwg.org/multipage/input.html#attr-input-size
+                        let size = positive_integer(local_name!("size"), 20);
+                        Some(FormControl::TextEntry {
+                            value,
+                            size,
+                            password,
+                        })
+                    }
+                }
+            }
+            local_name!("textarea") => {
+                // https://html.spec.whatwg.org/multipage/form-elements.html#attr-textarea-cols
+                let cols = positive_integer(local_name!("cols"), 20);
+                let rows = positive_integer(local_name!("rows"), 2);
+                let value = document.child_text_content(element).into_owned();
+                Some(FormControl::TextArea { value, cols, rows })
+            }
+            local_name!("select") => {
+                // https://html.spec.whatwg.org/multipage/form-elements.html#concept-select-option-list
+                let mut options = Vec::new();
+                let mut selected = None;
+                let mut add_option = |option: NodeId| {
+                    let data = document[option].as_element().unwrap();
+                    if data.get_attr(&local_name!("selected")).is_some() {
+                        selected = Some(options.len())
+                    }
+                    // https://html.spec.whatwg.org/multipage/form-elements.html#concept-option-label
+                    let label = match data.get_attr(&local_name!("label")) {
+                        Some(label) if !label.is_empty() => label.to_owned(),
+                        _ => {
+                            let text = document.child_text_content(option);
+                            text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+                        }
+                    };
+                    options.push(label)
+                };
+                let mut child = document[element].first_child;
+                while let Some(node) = child {
+                    if is_html_element(node, local_name!("option"), context) {
+                        add_option(node)
+                    } else if is_html_element(node, local_name!("optgroup"), context) {
+                        let mut grandchild = document[node].first_child;
+                        while let Some(node) = grandchild {
+                            if is_html_element(node, local_name!("option"), context) {
+                                add_option(node)
+                            }
+                            grandchild = document[node].next_sibling;
+                        }
+                    }
+                    child = document[node].next_sibling;
+                }
+                // https://html.spec.whatwg.org/multipage/form-elements.html#selectedness-setting-algorithm
+                if options.is_empty() {
+                    selected = None
+                } else if selected.is_none() {
+                    selected = Some(0)
+                }
+                Some(FormControl::Select { options, selected })
+            }
+            _ => None,
+        }
+    }
+
+    /// The field of the PDF form for this control, if it is fillable
+    fn form_field_value(&self) -> Option<FormFieldValue> {
+        match self {
+            FormControl::TextEntry {
+                value,
+                password: false,
+                ..
+            } => Some(FormFieldValue::Text {
+                value: value.clone(),
+                multiline: false,
+            }),
+            FormControl::TextArea { value, .. } => Some(FormFieldValue::Text {
+                value: value.clone(),
+                multiline: true,
+            }),
+            FormControl::Select { options, selected } => Some(FormFieldValue::Choice {
+                options: options.clone(),
+                selected: *selected,
+            }),
+            FormControl::Checkbox {
+                radio: false,
+                checked,
+            } => Some(FormFieldValue::Checkbox { checked: *checked }),
+            FormControl::TextEntry { password: true, .. }
+            | FormControl::Checkbox { radio: true, .. }
+            | FormControl::Button { .. } => None,
+        }
+    }
+
+    fn intrinsic_size(
+        &self,
+        style: &Arc<ComputedValues>,
+        font_context: &FontContext,
+    ) -> Vec2<Length> {
+        let text_size = |text: &str| {
+            let text = text_fragments(style, text, font_context);
+            (text.inline_size, text.ascent + text.descent)
+        };
+        // FIXME: use the average character width of the font
+        let columns = |count: u32| style.font.font_size.0 * (0.5 * count as f32);
+        match self {
+            FormControl::TextEntry { value, size, .. } => Vec2 {
+                inline: columns(*size),
+                block: text_size(value).1,
+            },
+            FormControl::TextArea { cols, rows, .. } => Vec2 {
+                inline: columns(*cols),
+                block: text_size("").1 * *rows as f32,
+            },
+            FormControl::Select { options, .. } => {
+                let (mut inline, block) = text_size("");
+                for option in options {
+                    inline.max_assign(text_size(option).0)
+                }
+                Vec2 {
+                    // Room for the drop-down arrow of browsers
+                    inline: inline + style.font.font_size.0,
+                    block,
+                }
+            }
+            FormControl::Button { label } => {
+                let (inline, block) = text_size(label);
+                Vec2 { inline, block }
+            }
+            // The size of checkboxes in browsers, independent of `font-size`
+            FormControl::Checkbox { .. } => Vec2 {
+                inline: Length::from_px(13.),
+                block: Length::from_px(13.),
+            },
+        }
+    }
+
+    fn make_fragments(
+        &self,
+        style: &Arc<ComputedValues>,
+        size: &Vec2<Length>,
+        font_context: &FontContext,
+    ) -> (Vec<Fragment>, Length) {
+        let (text, centered) = match self {
+            FormControl::TextEntry { value, .. } => (value, false),
+            FormControl::Button { label } => (label, true),
+            FormControl::Select { options, selected } => {
+                match selected.and_then(|index| options.get(index)) {
+                    Some(label) => (label, false),
+                    None => return (Vec::new(), size.block),
+                }
+            }
+            FormControl::TextArea { value, .. } => {
+                return text_area_fragments(style, value, size, font_context)
+            }
+            &FormControl::Checkbox { radio, checked } => {
+                let fragment = Fragment::Checkbox(CheckboxFragment {
+                    style: style.clone(),
+                    content_rect: Rect {
+                        start_corner: Vec2::zero(),
+                        size: size.clone(),
+                    },
+                    radio,
+                    checked,
+                });
+                // The baseline is the bottom of the box, like for other atomic inlines
+                // without an in-flow line box:
+                // https://drafts.csswg.org/css2/visudet.html#propdef-vertical-align
+                return (vec![fragment], size.block);
+            }
+        };
+        let text = text_fragments(style, text, font_context);
+        let text_size = Vec2 {
+            inline: text.inline_size,
+            block: text.ascent + text.descent,
+        };
+        // The text is centered vertically. Buttons also center their label horizontally.
+        // FIXME: clip text that overflows the content box
+        let start_corner = Vec2 {
+            inline: if centered {
+                (size.inline - text_size.inline) / 2.
+            } else {
+                Length::zero()
+            },
+            block: (size.block - text_size.block) / 2.,
+        };
+        let ascent = start_corner.block + text.ascent;
+        let fragment = Fragment::Anonymous(AnonymousFragment {
+            rect: Rect {
+                start_corner,
+                size: text_size,
+            },
+            children: text.fragments,
+            mode: style.writing_mode(),
+        });
+        (vec![fragment], ascent)
+    }
+}
+
+/// The lines of a `<textarea>` value from the top of its content box,
+/// and the baseline of the first one.
+///
+/// FIXME: wrap lines that are wider than the content box, and clip the overflow.
+fn text_area_fragments(
+    style: &Arc<ComputedValues>,
+    value: &str,
+    size: &Vec2<Length>,
+    font_context: &FontContext,
+) -> (Vec<Fragment>, Length) {
+    let mut fragments = Vec::new();
+    let mut block_position = Length::zero();
+    let mut first_ascent = None;
+    for line in value.lines() {
+        let text = text_fragments(style, line, font_context);
+        let line_size = Vec2 {
+            inline: text.inline_size,
+            block: text.ascent + text.descent,
+        };
+        first_ascent.get_or_insert(text.ascent);
+        fragments.push(Fragment::Anonymous(AnonymousFragment {
+            rect: Rect {
+                start_corner: Vec2 {
+                    inline: Length::zero(),
+                    block: block_position,
+                },
+                size: line_size.clone(),
+            },
+            children: text.fragments,
+            mode: style.writing_mode(),
+        }));
+        block_position += line_size.block;
+    }
+    (fragments, first_ascent.unwrap_or(size.block))
 }
@@ -0,0 +1,172 @@
+use super::*;
+
+/// A `<basic-shape>` resolved against its reference box,
+/// for the float area of `shape-outside` and the clipping region of `clip-path`.
+///
+/// FIXME: shapes are defined with physical coordinates, but are resolved here
+/// with x along the inline axis and y along the block axis,
+/// which is only correct for `horizontal-tb` left-to-right content.
+pub(crate) enum ResolvedShape {
+    Rect(Rect<Length>),
+    Ellipse {
+        center: Vec2<Length>,
+        radii: Vec2<Length>,
+    },
+    Polygon(Vec<Vec2<Length>>),
+}
+
+impl ResolvedShape {
+    /// Resolve `shape` in `reference_box`, or that box itself if there is no shape.
+    pub fn resolve(shape: Option<&BasicShape>, reference_box: &Rect<Length>) -> Self {
+        let start = reference_box.start_corner.clone();
+        let size = reference_box.size.clone();
+        let point = |[x, y]: &[LengthOrPercentage; 2]| Vec2 {
+            inline: start.inline + x.percentage_relative_to(size.inline),
+            block: start.block + y.percentage_relative_to(size.block),
+        };
+        // Distances from a point to the sides of the reference box
+        let to_sides = |center: &Vec2<Length>| {
+            let to_inline_sides = [
+                center.inline - start.inline,
+                start.inline + size.inline - center.inline,
+            ];
+            let to_block_sides = [
+                center.block - start.block,
+                start.block + size.block - center.block,
+            ];
+            let abs = |length: Length| length.max(-length);
+            (to_inline_sides.map(abs), to_block_sides.map(abs))
+        };
+        let radius = |radius: &ShapeRadius<LengthOrPercentage>, reference, sides: [Length; 2]| {
+            match radius {
+                ShapeRadius::Length(length) => length.percentage_relative_to(reference),
+                ShapeRadius::ClosestSide => sides[0].min(sides[1]),
+                ShapeRadius::FarthestSide => sides[0].max(sides[1]),
+            }
+        };
+        match shape {
+            None => ResolvedShape::Rect(Rect {
+                start_corner: start,
+                size,
+            }),
+            Some(BasicShape::Inset([top, right, bottom, left])) => {
+                let top = top.percentage_relative_to(size.block);
+                let right = right.percentage_relative_to(size.inline);
+                let bottom = bottom.percentage_relative_to(size.block);
+                let left = left.percentage_relative_to(size.inline);
+                ResolvedShape::Rect(Rect {
+                    start_corner: Vec2 {
+                        inline: start.inline + left,
+                        block: start.block + top,
+                    },
+                    size: Vec2 {
+                        inline: (size.inline - left - right).max(Length::zero()),
+                        block: (size.block - top - bottom).max(Length::zero()),
+                    },
+                })
+            }
+            Some(BasicShape::Circle { radius: r, center }) => {
+                let center = point(center);
+                let (inline_sides, block_sides) = to_sides(&center);
+                // https://drafts.csswg.org/css-shapes/#funcdef-basic-shape-circle
+                let diagonal = Length::from_px(
+                    ((size.inline.px().powi(2) + size.block.px().powi(2)) / 2.).sqrt(),
+                );
+                let closest_or_farthest = match r {
+                    ShapeRadius::ClosestSide => [
+                        inline_sides[0].min(inline_sides[1]),
+                        block_sides[0].min(block_sides[1]),
+                    ],
+                    _ => [
+                        inline_sides[0].max(inline_sides[1]),
+                        block_sides[0].max(block_sides[1]),
+                    ],
+                };
+                let r = radius(r, diagonal, closest_or_farthest);
+                ResolvedShape::Ellipse {
+                    center,
+                    radii: Vec2 {
+                        inline: r,
+                        block: r,
+                    },
+                }
+            }
+            Some(BasicShape::Ellipse {
+                radii: [rx, ry],
+                center,
+            }) => {
+                let center = point(center);
+                let (inline_sides, block_sides) = to_sides(&center);
+                ResolvedShape::Ellipse {
+                    radii: Vec2 {
+                        inline: radius(rx, size.inline, inline_sides),
+                        block: radius(ry, size.block, block_sides),
+                    },
+                    center,
+                }
+            }
+            Some(BasicShape::Polygon { vertices, .. }) => {
+                ResolvedShape::Polygon(vertices.iter().map(point).collect())
+            }
+        }
+    }
+
+    /// The inline range of this shape between the given block positions, if it is there.
+    pub fn inline_range(&self, band_start: Length, band_end: Length) -> Option<(Length, Length)> {
+        match self {
+            ResolvedShape::Rect(rect) => {
+                let block_end = rect.start_corner.block + rect.size.block;
+                if rect.start_corner.block > band_end || block_end < band_start {
+                    return None;
+                }
+                Some((
+                    rect.start_corner.inline,
+                    rect.start_corner.inline + rect.size.inline,
+                ))
+            }
+            ResolvedShape::Ellipse { center, radii } => {
+                if radii.block <= Length::zero()
+                    || center.block - radii.block > band_end
+                    || center.block + radii.block < band_start
+                {
+                    return None;
+                }
+                // The ellipse is widest in the band where it is closest to its center
+                let closest = center.block.max(band_start).min(band_end);
+                let dy = (closest - center.block).px() / radii.block.px();
+                let half_width = radii.inline * (1. - dy * dy).max(0.).sqrt();
+                Some((center.inline - half_width, center.inline + half_width))
+            }
+            ResolvedShape::Polygon(vertices) => {
+                let mut range: Option<(Length, Length)> = None;
+                let mut include = |inline: Length| {
+                    range = Some(match range {
+                        Some((start, end)) => (start.min(inline), end.max(inline)),
+                        None => (inline, inline),
+                    })
+                };
+                let edges = vertices.iter().zip(vertices.iter().cycle().skip(1));
+                for (a, b) in edges {
+                    // The part of this edge within the band
+                    let low = a.block.min(b.block).max(band_start);
+                    let high = a.block.max(b.block).min(band_end);
+                    if low > high {
+                        continue;
+                    }
+                    if a.block == b.block {
+                        include(a.inline);
+                        include(b.inline);
+                        continue;
+                    }
+                    let inline_at = |block: Length| {
+                        let t = (block - a.block).px() / (b.block - a.block).px();
+                        a.inline + (b.inline - a.inline) * t
+                    };
+                    include(inline_at(low));
+                    include(inline_at(high));
+                }
+                range
+            }
+        }
+    }
+}
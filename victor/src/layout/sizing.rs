@@ -0,0 +1,115 @@
+//! Intrinsic inline sizes of boxes, for widths that depend on their contents:
+//! https://drafts.csswg.org/css-sizing/#intrinsic-sizes
+
+use super::*;
+
+/// The min-content and max-content inline sizes of some contents
+#[derive(Clone, Debug, Default)]
+pub(super) struct ContentSizes {
+    /// The narrowest the contents can be without overflowing,
+    /// taking every soft wrap opportunity
+    pub min_content: Length,
+    /// The size of the contents without any soft line break
+    pub max_content: Length,
+}
+
+impl ContentSizes {
+    pub fn max_assign(&mut self, other: &Self) {
+        self.min_content.max_assign(other.min_content);
+        self.max_content.max_assign(other.max_content);
+    }
+
+    /// https://drafts.csswg.org/css2/visudet.html#shrink-to-fit-float
+    pub fn shrink_to_fit(&self, available_size: Length) -> Length {
+        available_size.max(self.min_content).min(self.max_content)
+    }
+}
+
+/// The content sizes of the margin box of a box with `style`,
+/// from those of its content box unless it has a fixed `width`.
+///
+/// Percentages of the containing block, whose size is not known yet, count as zero.
+pub(super) fn outer_inline_content_sizes(
+    style: &ComputedValues,
+    contents: impl FnOnce() -> ContentSizes,
+) -> ContentSizes {
+    // Only the sums of both sides are used, which do not depend on the direction.
+    let mode = style.writing_mode();
+    let zero = Length::zero();
+    let padding = style.padding(mode).percentages_relative_to(zero);
+    let border = style.border_width(mode).percentages_relative_to(zero);
+    let margin = style
+        .margin(mode)
+        .percentages_relative_to(zero)
+        .auto_is(Length::zero);
+    let pbm = (&(&padding + &border) + &margin).inline_sum();
+    let inner = match style.box_size().inline {
+        LengthOrPercentageOrAuto::Length(length) => ContentSizes {
+            min_content: length,
+            max_content: length,
+        },
+        _ => contents(),
+    };
+    ContentSizes {
+        min_content: inner.min_content + pbm,
+        max_content: inner.max_content + pbm,
+    }
+}
+
+impl IndependentFormattingContext {
+    /// `style` is that of the box that establishes this formatting context.
+    pub(super) fn inline_content_sizes(
+        &self,
+        font_context: &FontContext,
+        style: &Arc<ComputedValues>,
+    ) -> ContentSizes {
+        match self {
+            IndependentFormattingContext::Flow(bfc) => {
+                bfc.contents.inline_content_sizes(font_context)
+            }
+            IndependentFormattingContext::Replaced(replaced) => {
+                replaced.inline_content_sizes(style, font_context)
+            }
+            IndependentFormattingContext::Math(math) => {
+                math.inline_content_sizes(style, font_context)
+            }
+        }
+    }
+}
+
+impl ReplacedContent {
+    /// Replaced content has a single size, with percentages of the containing block as zero.
+    pub(super) fn inline_content_sizes(
+        &self,
+        style: &Arc<ComputedValues>,
+        font_context: &FontContext,
+    ) -> ContentSizes {
+        let containing_block = ContainingBlock {
+            inline_size: Length::zero(),
+            block_size: LengthOrAuto::Auto,
+            mode: style.writing_mode(),
+        };
+        let size = self
+            .used_size(style, &containing_block, font_context)
+            .inline;
+        ContentSizes {
+            min_content: size,
+            max_content: size,
+        }
+    }
+}
+
+impl MathContent {
+    /// A formula is laid out without line breaks, so it has a single size.
+    pub(super) fn inline_content_sizes(
+        &self,
+        style: &Arc<ComputedValues>,
+        font_context: &FontContext,
+    ) -> ContentSizes {
+        let (_, size, _) = self.layout(font_context, style.writing_mode());
+        ContentSizes {
+            min_content: size.inline,
+            max_content: size.inline,
+        }
+    }
+}
@@ -1,5 +1,18 @@
+//! With default features disabled, Victor is a standalone PDF generation library:
+//! `pdf::Document` writes pages with text and shapes,
+//! `fonts` parses TrueType fonts, and `text` shapes text with them.
+//!
+//! The `html` feature (enabled by default) adds parsing HTML and CSS
+//! and laying out documents into PDF pages, with `dom::Document`.
+
 pub use euclid;
+#[cfg(feature = "html")]
+pub use rayon;
 
+#[cfg(feature = "html")]
+pub mod computed_style;
+pub mod data_url;
+#[cfg(feature = "html")]
 pub mod dom;
 pub mod fonts;
 pub mod pdf;
@@ -7,19 +20,33 @@ pub mod primitives;
 pub mod text;
 pub mod text_plain;
 
+#[cfg(feature = "html")]
+#[doc(hidden)]
+pub use fuzz::{fuzz_layout, fuzz_parse_css, fuzz_parse_html};
+
+#[cfg(feature = "html")]
 #[macro_use]
 mod tagged_union_with_jump_tables;
+#[cfg(feature = "html")]
+mod fuzz;
+#[cfg(feature = "html")]
 mod geom;
+#[cfg(feature = "html")]
 mod layout;
+#[cfg(feature = "html")]
 mod paint;
+#[cfg(feature = "html")]
 mod style;
 
+#[cfg(feature = "html")]
 #[macro_use]
 extern crate cssparser;
 
+#[cfg(feature = "html")]
 #[macro_use]
 extern crate html5ever;
 
+#[cfg(feature = "html")]
 #[macro_use]
 extern crate matches;
 
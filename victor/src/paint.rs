@@ -1,90 +1,1521 @@
+use crate::dom::{
+    AnchorReport, CancellationToken, DashedBorders, LayoutReport, LimitExceeded, NodeId,
+    OverflowReport, PageReport, RenderError,
+};
+use crate::fonts::{Em, FontContext};
+use crate::geom::flow_relative;
 use crate::geom::physical::{Rect, Vec2};
 use crate::geom::Length;
-use crate::layout::{BoxFragment, Fragment};
-use crate::pdf::Page;
-use crate::primitives::{CssPx, Size, TextRun};
+use crate::layout::{AnonymousFragment, BoxFragment, CheckboxFragment, Fragment, TextFragment};
+use crate::layout::{FormFieldFragment, FormFieldValue};
+use crate::layout::{ImageCache, Orientation, ResolvedShape};
+use crate::pdf::{FormField, FormFieldKind, Page};
+use crate::primitives::{
+    self, BlendMode, ColorMatrix, CssPx, FillRule, SideOffsets, Size, TextRenderingMode, TextRun,
+    Transform, RGBA,
+};
+use crate::style::values::{BackgroundBox, BackgroundImage, FilterFunction, FontStyle};
+use crate::style::values::{BasicShape, ClipPath, ShapeBox};
+use crate::style::values::{Direction, WritingMode};
+use crate::style::values::{Isolation, MixBlendMode, TransformFunction, TransformOrigin};
+use crate::style::values::{LineStyle, LineWidth, TextDecorationSkipInk};
+use crate::style::{ComputedValues, StyleSet};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
 
 impl crate::dom::Document {
     pub fn to_pdf_bytes(&self) -> Vec<u8> {
-        let page_size: Size<CssPx> = Size::new(600., 800.);
-        let fragments = self.layout(page_size);
+        self.to_pdf_bytes_with_font_context(FontContext::shared())
+    }
+
+    /// Set the size of every page. The default is 600 × 800 CSS `px`.
+    pub fn set_page_size(&mut self, size: Size<CssPx>) {
+        self.page_size = size
+    }
+
+    /// Set the margins between the edges of every page and its page area.
+    /// The default is no margins.
+    ///
+    /// The page area is the initial containing block that the document is laid out in.
+    /// Positions in `LayoutReport` are still from the top-left corner of the page.
+    pub fn set_page_margins(&mut self, margins: SideOffsets<CssPx>) {
+        self.page_margins = margins
+    }
+
+    /// Paint box edges on whole device pixels,
+    /// for output rendered at `device_pixels_per_px` (`1.` for 96dpi, `3.125` for 300dpi).
+    ///
+    /// Otherwise, an edge that falls between two device pixels can be rasterized
+    /// on either side of it, so that boxes of the same size look slightly different.
+    /// The default is `None`: edges are painted exactly where layout puts them.
+    pub fn set_pixel_snapping(&mut self, device_pixels_per_px: Option<f32>) {
+        self.pixel_snapping = device_pixels_per_px
+    }
+
+    /// Downsample images with more than `dots_per_inch` at their painted size,
+    /// see `pdf::Document::set_max_image_resolution`,
+    /// and choose images from `srcset` attributes for that resolution,
+    /// see `html_image_sources`.
+    ///
+    /// `x` descriptors are relative to 96dpi, one device pixel per CSS `px`:
+    /// at 300dpi a `3x` or `4x` image is preferred over a `2x` one,
+    /// which would be upscaled when printed.
+    /// Without a maximum, images are chosen for the resolution of `set_pixel_snapping`,
+    /// or for 96dpi if it is not set either.
+    /// The default is `None`.
+    pub fn set_max_image_resolution(&mut self, dots_per_inch: Option<f32>) {
+        self.max_image_resolution = dots_per_inch
+    }
+
+    /// Keep at most this many bytes of images decoded from their URL while painting.
+    ///
+    /// Layout only reads the size of images, and painting decodes them again.
+    /// Decoded images are kept in case they are painted again, for example on another page,
+    /// until this budget is exceeded: the least recently painted ones are then dropped,
+    /// and decoded again if they are needed again.
+    /// The default is `None`: images are kept until painting is done.
+    pub fn set_image_memory_budget(&mut self, bytes: Option<usize>) {
+        self.image_memory_budget = bytes
+    }
+
+    /// Set how `dashed` and `dotted` borders are painted.
+    /// The default is `DashedBorders::Segments`.
+    pub fn set_dashed_borders(&mut self, dashed_borders: DashedBorders) {
+        self.dashed_borders = dashed_borders
+    }
+
+    /// Write a tagged PDF, whose structure tree has an element for each paragraph and heading
+    /// (`P` for `<p>`, `H1` to `H6` for `<h1>` to `<h6>`) with the content painted for it,
+    /// for assistive technology and for extracting text in reading order.
+    ///
+    /// FIXME: other structure types, such as lists, tables and figures.
+    /// The default is `false`.
+    pub fn set_tagged_pdf(&mut self, tagged: bool) {
+        self.tagged_pdf = tagged
+    }
+
+    /// Paint on every page after the document’s content, for example a watermark,
+    /// a “draft” banner, or crop marks.
+    ///
+    /// `overlay` is called with each page and its size.
+    /// It is painted with `Page::paint_repeated`, so pages with the same overlay share it.
+    ///
+    /// FIXME: also paint `position: fixed` boxes on every page, once there is more than one.
+    pub fn set_page_overlay(
+        &mut self,
+        overlay: impl Fn(&mut Page, Size<CssPx>) + Send + Sync + 'static,
+    ) {
+        self.page_overlay = Some(Box::new(overlay))
+    }
+
+    /// Only write the pages with these numbers (starting at 1) to PDF,
+    /// for example `Some(3..=10)` for a preview of a large document.
+    ///
+    /// The whole document is still laid out, so that its pages are the same
+    /// as without a range, and the layout report still lists all of them.
+    /// The range is clamped to the pages of the document, so that at least one is written:
+    /// a range past the end writes the last page.
+    /// The default is `None`: all pages are written.
+    pub fn set_page_range(&mut self, page_numbers: Option<RangeInclusive<usize>>) {
+        self.page_range = page_numbers
+    }
+
+    /// Run box construction and layout in this thread pool.
+    ///
+    /// For example, a server can give each request a pool with a few threads
+    /// to bound how much CPU time one document uses,
+    /// or a pool built with `num_threads(1)` to lay out without parallelism.
+    /// Layout produces the same result with any number of threads.
+    /// The default is `None`: rayon’s global thread pool is used.
+    pub fn set_thread_pool(&mut self, pool: Option<Arc<rayon::ThreadPool>>) {
+        self.thread_pool = pool
+    }
+
+    /// Check this token while constructing boxes, laying them out, and painting them,
+    /// and skip the rest of the work once it is cancelled.
+    ///
+    /// For example, a server can cancel rendering a pathological document after a timeout
+    /// from another thread. Rendering then returns soon after:
+    /// `try_to_pdf_bytes_and_layout_report` with `RenderError::Cancelled`,
+    /// other methods with incomplete output that should be discarded.
+    /// The default is `None`: rendering cannot be cancelled.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation_token = token
+    }
+
+    /// Same as `to_pdf_bytes`, with fonts and glyph lookups from the given context.
+    pub fn to_pdf_bytes_with_font_context(&self, font_context: &FontContext) -> Vec<u8> {
+        self.to_pdf_bytes_and_layout_report(font_context).0
+    }
+
+    /// Same as `to_pdf_bytes_with_font_context`,
+    /// also returning a summary of the pages that layout produced.
+    ///
+    /// The `Limits` the document was parsed with are enforced, like cancellation:
+    /// content is left out of a document that exceeds them,
+    /// so the output should be discarded. `try_to_pdf_bytes_and_layout_report`
+    /// returns an error instead.
+    pub fn to_pdf_bytes_and_layout_report(
+        &self,
+        font_context: &FontContext,
+    ) -> (Vec<u8>, LayoutReport) {
+        let page_area = self.page_area();
+        let author_styles = self.parse_stylesheets();
+        let (fragments, report) = self
+            .layout_pages(page_area, &author_styles, font_context)
+            .unwrap_or_else(|_| (Vec::new(), self.layout_report(&[], page_area)));
+        let pdf_bytes = self.paint_pages(fragments, &report, page_area, &author_styles);
+        (pdf_bytes, report)
+    }
+
+    /// A text dump of the fragment tree: one line per fragment, indented by nesting depth,
+    /// with the position and size of its border box (of its content box for text)
+    /// in CSS `px` from the top-left corner of the page.
+    ///
+    /// Tests can compare this with an expected dump, to check layout
+    /// without rendering the PDF file to pixels.
+    ///
+    /// A document that exceeds the `Limits` it was parsed with has an empty dump.
+    pub fn dump_fragment_tree(&self, font_context: &FontContext) -> String {
+        let page_area = self.page_area();
+        let fragments = self
+            .layout_pages(page_area, &self.parse_stylesheets(), font_context)
+            .map(|(fragments, _)| fragments)
+            .unwrap_or_default();
+        let containing_block = ContainingBlock::page_area(page_area);
+        let mut dump = String::new();
+        for fragment in &fragments {
+            fragment.dump(self, &containing_block, 0, &mut dump)
+        }
+        dump
+    }
+
+    /// The page minus its margins, where content is laid out
+    fn page_area(&self) -> primitives::Rect<CssPx> {
+        let mut page_area =
+            primitives::Rect::from_size(self.page_size).inner_rect(self.page_margins);
+        // Margins larger than the page leave an empty page area.
+        page_area.size = page_area.size.max(Size::zero());
+        page_area
+    }
+
+    /// Same as `to_pdf_bytes_and_layout_report`,
+    /// but return an error if the document exceeds `Limits::max_boxes` or `Limits::max_pages`
+    /// given to `Document::parse_html_with_limits` or another parser with limits,
+    /// or if rendering was cancelled with `set_cancellation_token`
+    /// instead of returning incomplete output.
+    pub fn try_to_pdf_bytes_and_layout_report(
+        &self,
+        font_context: &FontContext,
+    ) -> Result<(Vec<u8>, LayoutReport), RenderError> {
+        let page_area = self.page_area();
+        let author_styles = self.parse_stylesheets();
+        let (fragments, report) = self.layout_pages(page_area, &author_styles, font_context)?;
+        let pdf_bytes = self.paint_pages(fragments, &report, page_area, &author_styles);
+        // Checked last: cancelling at any point before this truncates the output.
+        if self.is_cancelled() {
+            return Err(RenderError::Cancelled);
+        }
+        Ok((pdf_bytes, report))
+    }
+
+    /// Lay out the document within its `Limits`, before painting anything.
+    ///
+    /// FIXME: count pages while fragmenting once layout makes more than one,
+    /// and stop as soon as there are too many.
+    fn layout_pages(
+        &self,
+        page_area: primitives::Rect<CssPx>,
+        author_styles: &StyleSet,
+        font_context: &FontContext,
+    ) -> Result<(Vec<Fragment>, LayoutReport), LimitExceeded> {
+        let fragments = self.layout(page_area.size, author_styles, font_context)?;
+        let report = self.layout_report(&fragments, page_area);
+        let max_pages = self.limits.max_pages;
+        if max_pages.is_some_and(|max| report.page_count() > max) {
+            return Err(LimitExceeded::Pages);
+        }
+        Ok((fragments, report))
+    }
+
+    fn layout_report(
+        &self,
+        fragments: &[Fragment],
+        page_area: primitives::Rect<CssPx>,
+    ) -> LayoutReport {
+        // Positions in the report are relative to the page, not to the sheet.
+        let containing_block = ContainingBlock::page_area(page_area);
+        // FIXME: one page per fragmentainer once there is fragmentation,
+        // with named pages from the `page` property.
+        let page_number = 1;
+        let mut positions = HashMap::new();
+        let mut skipped_drop_shadows = HashSet::new();
+        for fragment in fragments {
+            fragment.collect_positions(&containing_block, &mut positions);
+            fragment.collect_skipped_drop_shadows(0, &mut skipped_drop_shadows)
+        }
+        LayoutReport {
+            pages: vec![PageReport {
+                size: self.page_size,
+                name: None,
+            }],
+            anchors: self
+                .nodes()
+                .filter_map(|node| {
+                    Some(AnchorReport {
+                        id: self[node].as_element()?.id.as_ref()?.to_string(),
+                        page_number,
+                        position: *positions.get(&node)?,
+                    })
+                })
+                .collect(),
+            overflows: fragments
+                .iter()
+                .find_map(|fragment| fragment.first_overflow(&containing_block, &page_area))
+                .flatten()
+                .map(|node| OverflowReport {
+                    page_number,
+                    element: self[node].as_element().unwrap().location(),
+                })
+                .into_iter()
+                .collect(),
+            skipped_drop_shadows: self
+                .nodes()
+                .filter(|node| skipped_drop_shadows.contains(node))
+                .map(|node| self[node].as_element().unwrap().location())
+                .collect(),
+        }
+    }
+
+    fn paint_pages(
+        &self,
+        fragments: Vec<Fragment>,
+        report: &LayoutReport,
+        page_area: primitives::Rect<CssPx>,
+        author_styles: &StyleSet,
+    ) -> Vec<u8> {
+        // FIXME: page size and margins from `@page` rules, overriding `set_page_size`
+        // and `set_page_margins`.
+        let page_size = self.page_size;
+        // https://drafts.csswg.org/css-page-3/#bleed
+        // Pages are written on larger sheets, with room for the bleed area and printer marks.
+        let marks = author_styles.page_marks();
+        let bleed = author_styles.page_bleed().max(Length::zero()).px();
+        let marks_length = if marks.crop || marks.cross {
+            crate::pdf::PRINTER_MARK_LENGTH
+        } else {
+            0.
+        };
+        let sheet_margin = bleed + marks_length;
+        let trim_box =
+            primitives::Rect::new(primitives::point(sheet_margin, sheet_margin), page_size);
+        let sheet_size = trim_box.inflate(sheet_margin, sheet_margin).size;
+        let images = RefCell::new(ImageCache::new(self, self.image_memory_budget));
+        let options = PaintOptions {
+            pixel_snapping: self.pixel_snapping,
+            dashed_borders: self.dashed_borders,
+            cancellation_token: self.cancellation_token.as_ref(),
+            images: &images,
+            nested_drop_shadows: 0,
+            structure: if self.tagged_pdf { Some(self) } else { None },
+        };
         let mut doc = crate::pdf::Document::new();
-        let containing_block = Rect {
-            top_left: Vec2 {
-                x: Length::zero(),
-                y: Length::zero(),
-            },
-            size: Vec2 {
-                x: Length {
-                    px: page_size.width,
-                },
-                y: Length {
-                    px: page_size.height,
-                },
-            },
+        doc.set_max_image_resolution(self.max_image_resolution);
+        // FIXME: one page per fragmentainer, as in `layout_report`.
+        let page_number = 1;
+        // Once layout is fragmented into pages, hashing the parts of their display lists
+        // would also find content repeated on many pages, such as running headers,
+        // to paint with `Page::paint_repeated`.
+        let page_count = report.page_count();
+        let write_page = match &self.page_range {
+            Some(range) => {
+                let first = (*range.start()).max(1).min(page_count);
+                let last = (*range.end()).max(first).min(page_count);
+                (first..=last).contains(&page_number)
+            }
+            None => true,
         };
-        {
-            let mut page = doc.add_page(page_size);
+        if write_page {
+            let mut page = doc.add_page(sheet_size);
+            if sheet_margin > 0. {
+                let bleed_box = trim_box.inflate(bleed, bleed);
+                page.set_trim_and_bleed_boxes(&trim_box, &bleed_box);
+                if marks.crop {
+                    page.paint_crop_marks(&trim_box, &bleed_box);
+                }
+                if marks.cross {
+                    page.paint_cross_marks(&trim_box, &bleed_box);
+                }
+            }
+            // Offset the page area rather than transforming, since form fields
+            // and link annotations are not affected by the transformation matrix.
+            let containing_block =
+                ContainingBlock::page_area(page_area.translate(&trim_box.origin.to_vector()));
             for fragment in fragments {
-                fragment.paint_onto(&mut page, &containing_block)
+                fragment.paint_onto(&mut page, &containing_block, options)
+            }
+            if let Some(overlay) = &self.page_overlay {
+                page.paint_repeated(|page| {
+                    page.save_state()
+                        .transform(&Transform::create_translation(sheet_margin, sheet_margin));
+                    overlay(page, page_size);
+                    page.restore_state();
+                });
             }
         }
         doc.write_to_pdf_bytes()
     }
 }
 
+fn dump_line(dump: &mut String, indent: &str, name: &str, rect: primitives::Rect<CssPx>) {
+    use std::fmt::Write;
+    writeln!(
+        dump,
+        "{}{} {},{} {}x{}",
+        indent, name, rect.origin.x, rect.origin.y, rect.size.width, rect.size.height
+    )
+    .unwrap()
+}
+
+/// Where fragments are on the page: their flow-relative rects are relative
+/// to the physical rect of their containing block, in its writing mode.
+struct ContainingBlock {
+    rect: Rect<Length>,
+    mode: (WritingMode, Direction),
+}
+
+impl ContainingBlock {
+    /// The initial containing block, see `BoxTreeRoot::layout`
+    fn page_area(page_area: primitives::Rect<CssPx>) -> Self {
+        ContainingBlock {
+            rect: page_area.into(),
+            mode: (WritingMode::HorizontalTb, Direction::Ltr),
+        }
+    }
+
+    fn to_page(&self, rect: &flow_relative::Rect<Length>) -> Rect<Length> {
+        rect.to_physical(self.mode, &self.rect)
+            .translate(&self.rect.top_left)
+    }
+
+    /// The containing block of the children of `box_`, which is in this one
+    fn for_children_of(&self, box_: &BoxFragment) -> Self {
+        ContainingBlock {
+            rect: self.to_page(&box_.content_rect),
+            mode: box_.children_mode(self.mode),
+        }
+    }
+
+    /// The containing block of the children of an anonymous fragment in this one
+    fn for_children_of_anonymous(&self, anonymous: &AnonymousFragment) -> Self {
+        ContainingBlock {
+            rect: self.to_page(&anonymous.rect),
+            mode: anonymous.mode,
+        }
+    }
+}
+
+/// Settings of the document that apply to painting each fragment.
+#[derive(Copy, Clone)]
+struct PaintOptions<'a> {
+    pixel_snapping: Option<f32>,
+    dashed_borders: DashedBorders,
+    cancellation_token: Option<&'a CancellationToken>,
+    images: &'a RefCell<ImageCache<'a>>,
+    /// Drop shadows of the box being painted and its ancestors, see `MAX_NESTED_DROP_SHADOWS`
+    nested_drop_shadows: usize,
+    /// The document whose elements are tagged, see `set_tagged_pdf`
+    structure: Option<&'a crate::dom::Document>,
+}
+
+/// Painting the innermost of nested boxes with drop shadows up to 2⁸ = 256 times
+const MAX_NESTED_DROP_SHADOWS: usize = 8;
+
 impl Fragment {
-    fn paint_onto(&self, page: &mut Page, containing_block: &Rect<Length>) {
+    fn dump(
+        &self,
+        document: &crate::dom::Document,
+        containing_block: &ContainingBlock,
+        depth: usize,
+        dump: &mut String,
+    ) {
+        let indent = "  ".repeat(depth);
+        let (containing_block_for_children, children) = match self {
+            Fragment::Box(b) => {
+                let name = match b.tag.and_then(|tag| document[tag].as_element()) {
+                    Some(element) => format!("<{}>", &*element.name.local),
+                    None => "(anonymous box)".to_owned(),
+                };
+                let border_rect = containing_block.to_page(&b.border_rect());
+                dump_line(dump, &indent, &name, border_rect.into());
+                (containing_block.for_children_of(b), &b.children)
+            }
+            Fragment::Anonymous(a) => {
+                let rect = containing_block.to_page(&a.rect);
+                dump_line(dump, &indent, "(anonymous)", rect.into());
+                (containing_block.for_children_of_anonymous(a), &a.children)
+            }
+            Fragment::Text(t) => {
+                let name = format!("text ({} glyphs)", t.text.glyphs.len());
+                let rect = containing_block.to_page(&t.content_rect);
+                dump_line(dump, &indent, &name, rect.into());
+                return;
+            }
+            Fragment::Checkbox(c) => {
+                let name = if c.radio { "radio" } else { "checkbox" };
+                let name = format!("{}{}", name, if c.checked { " (checked)" } else { "" });
+                let rect = containing_block.to_page(&c.content_rect);
+                dump_line(dump, &indent, &name, rect.into());
+                return;
+            }
+            Fragment::Image(i) => {
+                let name = format!("image ({}x{})", i.width, i.height);
+                let rect = containing_block.to_page(&i.content_rect);
+                dump_line(dump, &indent, &name, rect.into());
+                return;
+            }
+            Fragment::FormField(_) => return,
+        };
+        for child in children {
+            child.dump(document, &containing_block_for_children, depth + 1, dump)
+        }
+    }
+
+    /// Record the top-left corner of the border box of each element’s first box.
+    fn collect_positions(
+        &self,
+        containing_block: &ContainingBlock,
+        positions: &mut HashMap<NodeId, primitives::Point<CssPx>>,
+    ) {
+        let (containing_block_for_children, children) = match self {
+            Fragment::Box(b) => {
+                if let Some(tag) = b.tag {
+                    let border_rect: primitives::Rect<CssPx> =
+                        containing_block.to_page(&b.border_rect()).into();
+                    positions.entry(tag).or_insert(border_rect.origin);
+                }
+                (containing_block.for_children_of(b), &b.children)
+            }
+            Fragment::Anonymous(a) => (containing_block.for_children_of_anonymous(a), &a.children),
+            Fragment::Text(_)
+            | Fragment::Checkbox(_)
+            | Fragment::Image(_)
+            | Fragment::FormField(_) => return,
+        };
+        for child in children {
+            child.collect_positions(&containing_block_for_children, positions)
+        }
+    }
+
+    /// The elements of boxes whose drop shadows `BoxFragment::paint_onto` skips,
+    /// inside `nested_drop_shadows` drop shadows of ancestors.
+    fn collect_skipped_drop_shadows(
+        &self,
+        nested_drop_shadows: usize,
+        found: &mut HashSet<NodeId>,
+    ) {
+        let (nested_drop_shadows, children) = match self {
+            Fragment::Box(b) => {
+                let drop_shadows = b.drop_shadow_count();
+                let nested_drop_shadows = nested_drop_shadows + drop_shadows;
+                if drop_shadows > 0 && nested_drop_shadows > MAX_NESTED_DROP_SHADOWS {
+                    found.extend(b.tag)
+                }
+                (nested_drop_shadows, &b.children)
+            }
+            Fragment::Anonymous(a) => (nested_drop_shadows, &a.children),
+            Fragment::Text(_)
+            | Fragment::Checkbox(_)
+            | Fragment::Image(_)
+            | Fragment::FormField(_) => return,
+        };
+        for child in children {
+            child.collect_skipped_drop_shadows(nested_drop_shadows, found)
+        }
+    }
+
+    /// The innermost fragment that extends past `page_area`, first in tree order, if any:
+    /// `Some(Some(element))` for an element’s box, or a fragment in it,
+    /// or `Some(None)` for an anonymous or text fragment not in an element’s box.
+    fn first_overflow(
+        &self,
+        containing_block: &ContainingBlock,
+        page_area: &primitives::Rect<CssPx>,
+    ) -> Option<Option<NodeId>> {
+        // In app units like layout, rather than in `f32` pixels where a box that ends
+        // on an edge of the page area could be past it by a rounding error.
+        let area: Rect<Length> = (*page_area).into();
+        let (area_start, area_end) = (&area.top_left, &area.top_left + &area.size);
+        let overflows = |rect: &flow_relative::Rect<Length>| {
+            let rect = containing_block.to_page(rect);
+            let (start, end) = (&rect.top_left, &rect.top_left + &rect.size);
+            start.x < area_start.x
+                || start.y < area_start.y
+                || end.x > area_end.x
+                || end.y > area_end.y
+        };
+        let first_in_children = |children: &[Fragment], containing_block: &ContainingBlock| {
+            children
+                .iter()
+                .find_map(|child| child.first_overflow(containing_block, page_area))
+        };
+        match self {
+            Fragment::Box(b) => {
+                let containing_block_for_children = containing_block.for_children_of(b);
+                match first_in_children(&b.children, &containing_block_for_children) {
+                    Some(found) => Some(found.or(b.tag)),
+                    None if overflows(&b.border_rect()) => Some(b.tag),
+                    None => None,
+                }
+            }
+            Fragment::Anonymous(a) => {
+                let containing_block_for_children = containing_block.for_children_of_anonymous(a);
+                match first_in_children(&a.children, &containing_block_for_children) {
+                    Some(found) => Some(found),
+                    None if overflows(&a.rect) => Some(None),
+                    None => None,
+                }
+            }
+            Fragment::Text(t) if overflows(&t.content_rect) => Some(None),
+            Fragment::Text(_)
+            | Fragment::Checkbox(_)
+            | Fragment::Image(_)
+            | Fragment::FormField(_) => None,
+        }
+    }
+
+    /// The area that this fragment and its descendants can paint, see `BoxFragment::ink_overflow`.
+    fn ink_overflow(&self, containing_block: &ContainingBlock) -> Option<primitives::Rect<CssPx>> {
+        let physical = |rect: &flow_relative::Rect<Length>| -> primitives::Rect<CssPx> {
+            containing_block.to_page(rect).into()
+        };
         match self {
-            Fragment::Box(b) => b.paint_onto(page, containing_block),
+            Fragment::Box(b) => Some(b.ink_overflow(containing_block)),
             Fragment::Anonymous(a) => {
-                let rect = a
-                    .rect
-                    .to_physical(a.mode, containing_block)
-                    .translate(&containing_block.top_left);
+                let containing_block = containing_block.for_children_of_anonymous(a);
+                a.children
+                    .iter()
+                    .filter_map(|child| child.ink_overflow(&containing_block))
+                    .fold(None, |ink, child| {
+                        Some(ink.map_or(child, |ink: primitives::Rect<CssPx>| ink.union(&child)))
+                    })
+            }
+            Fragment::Text(t) => {
+                let style = &t.parent_style;
+                // Glyphs, their outlines and synthetic italics can extend out of the line,
+                // by less than an em for most fonts.
+                let em = style.font.font_size.0.px();
+                let mut ink = physical(&t.content_rect).inflate(em, em);
+                for shadow in style.text.text_shadow.0.iter() {
+                    let spread = (shadow.blur_radius / 2.).px();
+                    let offset = euclid::vec2(shadow.offset_x.px(), shadow.offset_y.px());
+                    ink = ink.union(&ink.translate(&offset).inflate(spread, spread))
+                }
+                Some(ink)
+            }
+            Fragment::Checkbox(c) => Some(physical(&c.content_rect)),
+            Fragment::Image(i) => Some(physical(&i.content_rect)),
+            // Annotations are not part of the page’s content
+            Fragment::FormField(_) => None,
+        }
+    }
+
+    /// Whether this fragment or one of its descendants has a `mix-blend-mode`.
+    fn has_blending(&self) -> bool {
+        match self {
+            Fragment::Box(b) => {
+                b.style.box_.mix_blend_mode != MixBlendMode::Normal
+                    || b.children.iter().any(Fragment::has_blending)
+            }
+            Fragment::Anonymous(a) => a.children.iter().any(Fragment::has_blending),
+            Fragment::Text(_)
+            | Fragment::Checkbox(_)
+            | Fragment::Image(_)
+            | Fragment::FormField(_) => false,
+        }
+    }
+
+    fn paint_onto(
+        &self,
+        page: &mut Page,
+        containing_block: &ContainingBlock,
+        options: PaintOptions,
+    ) {
+        match self {
+            Fragment::Box(b) => b.paint_onto(page, containing_block, options),
+            Fragment::Anonymous(a) => {
+                let containing_block = containing_block.for_children_of_anonymous(a);
                 for child in &a.children {
-                    child.paint_onto(page, &rect)
+                    child.paint_onto(page, &containing_block, options)
                 }
             }
             Fragment::Text(t) => {
-                let mut origin = t
-                    .content_rect
-                    .to_physical(t.parent_style.writing_mode(), containing_block)
-                    .translate(&containing_block.top_left)
-                    .top_left;
+                let mut origin = containing_block.to_page(&t.content_rect).top_left;
                 // Distance from top edge to baseline
                 let ascender = t.parent_style.font.font_size * t.text.font.ascender();
                 origin.y += ascender;
-                page.set_color(&t.parent_style.color.color.into());
-                page.show_text(&TextRun {
-                    segment: &t.text,
-                    font_size: t.parent_style.font.font_size.0.into(),
-                    origin: origin.into(),
-                })
-                .unwrap();
+                let style = &t.parent_style;
+                let fill_color: RGBA = style.to_rgba(style.text.webkit_text_fill_color).into();
+                let stroke_width = style
+                    .text
+                    .webkit_text_stroke_width
+                    .0
+                    .percentage_relative_to(Length::zero());
+                // https://drafts.csswg.org/css-fonts-4/#font-synthesis-intro
+                // FIXME: only when no face of the family matches, once faces are selected.
+                let synthesis = style.font.font_synthesis;
+                let synthetic_bold =
+                    synthesis.weight && style.font.font_weight.0 >= 600. && !t.text.font.is_bold();
+                let synthetic_oblique_degrees = match style.font.font_style {
+                    _ if !synthesis.style || t.text.font.is_italic() => 0.,
+                    FontStyle::Normal => 0.,
+                    // About the slant of browsers’ synthetic italics
+                    FontStyle::Italic => 14.,
+                    FontStyle::Oblique(degrees) => degrees,
+                };
+                // Width and color of glyph outlines, if they are stroked
+                let stroke = if stroke_width > Length::zero() {
+                    let color = style.to_rgba(style.text.webkit_text_stroke_color).into();
+                    Some((stroke_width, color))
+                } else if synthetic_bold {
+                    // Thicken glyphs by also stroking their outlines in the fill color,
+                    // by about as much as browsers embolden small text.
+                    Some((style.font.font_size.0 * (1. / 24.), fill_color))
+                } else {
+                    None
+                };
+                let show_text = |page: &mut Page, origin: &Vec2<Length>| {
+                    let text_run = TextRun {
+                        segment: &t.text,
+                        font_size: t.font_size.into(),
+                        origin: origin.clone().into(),
+                    };
+                    // Text in a font that cannot be embedded, because some of its glyphs
+                    // cannot be read, is left out like glyphs that layout cannot read.
+                    if synthetic_oblique_degrees != 0. {
+                        // Shear along the baseline,
+                        // so that positive angles lean glyphs to the right.
+                        let tan = synthetic_oblique_degrees.to_radians().tan();
+                        let baseline = origin.y.px();
+                        page.save_state().transform(&Transform::row_major(
+                            1.,
+                            0.,
+                            -tan,
+                            1.,
+                            tan * baseline,
+                            0.,
+                        ));
+                        let _ = page.show_text(&text_run);
+                        page.restore_state();
+                    } else {
+                        let _ = page.show_text(&text_run);
+                    }
+                };
+                // https://drafts.csswg.org/css-text-decor-3/#text-shadow-property
+                // The first shadow is on top of the others, and all are below the text.
+                for shadow in style.text.text_shadow.0.iter().rev() {
+                    let color: RGBA = style.to_rgba(shadow.color).into();
+                    let origin = Vec2 {
+                        x: origin.x + shadow.offset_x,
+                        y: origin.y + shadow.offset_y,
+                    };
+                    let (offsets, alpha) = shadow_copies(shadow.blur_radius, color.3);
+                    let color = RGBA(color.0, color.1, color.2, alpha);
+                    for offset in offsets {
+                        page.set_color(&color);
+                        set_text_stroke(page, stroke.map(|(width, _)| (width, color)));
+                        show_text(page, &(&origin + &offset))
+                    }
+                }
+                // https://drafts.csswg.org/css-text-decor-3/#painting-order
+                // Underlines and overlines are below the text, lines through it above.
+                // FIXME: text shadows should also have copies of decoration lines.
+                let decorations = style.text_decorations_in_effect;
+                let metrics = t.text.font.decoration_metrics();
+                let skip_ink = style.text.text_decoration_skip_ink == TextDecorationSkipInk::Auto;
+                let underline = (metrics.underline_position, metrics.underline_thickness);
+                let overline = (t.text.font.ascender(), metrics.underline_thickness);
+                let line_through = (metrics.strikeout_position, metrics.strikeout_thickness);
+                for &(color, line) in &[
+                    (decorations.underline, underline),
+                    (decorations.overline, overline),
+                ] {
+                    if let Some(color) = color {
+                        paint_decoration_line(page, t, &origin, color.into(), line, skip_ink)
+                    }
+                }
+                page.set_color(&fill_color);
+                set_text_stroke(page, stroke);
+                show_text(page, &origin);
+                if let Some(color) = decorations.line_through {
+                    paint_decoration_line(page, t, &origin, color.into(), line_through, false)
+                }
             }
+            Fragment::Checkbox(c) => {
+                let rect = containing_block.to_page(&c.content_rect);
+                c.paint_onto(page, &rect.into())
+            }
+            Fragment::Image(i) => {
+                let content_rect = containing_block.to_page(&i.content_rect);
+                let mut rect: primitives::Rect<CssPx> =
+                    i.object_rect.translate(&content_rect.top_left).into();
+                let content_rect: primitives::Rect<CssPx> = content_rect.into();
+                if let Some(image) = options.images.borrow_mut().get(i.element) {
+                    // With `object-fit: cover` or `none`, the image can extend past its box.
+                    let clipped = !content_rect.contains_rect(&rect);
+                    let oriented = i.orientation != Orientation::AS_STORED;
+                    if clipped || oriented {
+                        page.save_state();
+                    }
+                    if clipped {
+                        page.move_to(&content_rect.origin)
+                            .line_to(&content_rect.top_right())
+                            .line_to(&content_rect.bottom_right())
+                            .line_to(&content_rect.bottom_left())
+                            .close_path()
+                            .clip(FillRule::NonZero);
+                    }
+                    if oriented {
+                        page.transform(&i.orientation.transform(&rect));
+                        let mut size = rect.size;
+                        if i.orientation.is_transposed() {
+                            size = Size::new(size.height, size.width)
+                        }
+                        rect = primitives::Rect::from_size(size)
+                    }
+                    // `JpegImage` has no samples that could have the wrong length
+                    let _ = page.paint_image(&image.as_pdf_image(), &rect);
+                    if clipped || oriented {
+                        page.restore_state();
+                    }
+                }
+            }
+            // Added by the parent box, at its border box
+            Fragment::FormField(_) => {}
         }
     }
 }
 
+/// Paint a line along a text fragment, with its top edge at the position of `line`
+/// above the `baseline`, and its thickness.
+///
+/// With `skip_ink`, the line is interrupted where glyphs cross it, leaving a gap
+/// as wide as the line is thick around them:
+/// https://drafts.csswg.org/css-text-decor-4/#text-decoration-skip-ink-property
+///
+/// FIXME: this finds the ink of glyphs without the slant of synthetic oblique.
+fn paint_decoration_line(
+    page: &mut Page,
+    t: &TextFragment,
+    baseline: &Vec2<Length>,
+    color: RGBA,
+    line: (euclid::Length<f32, Em>, euclid::Length<f32, Em>),
+    skip_ink: bool,
+) {
+    let (position, thickness) = line;
+    let font = &t.text.font;
+    let ems_to_px = |length: euclid::Length<f32, Em>| (t.font_size * length.get()).px();
+    let start = baseline.x.px();
+    let end = start + t.content_rect.size.inline.px();
+    let mut gaps = Vec::new();
+    if skip_ink {
+        let (bottom, top) = (position - thickness * 2., position + thickness);
+        let mut advance = euclid::Length::new(0.);
+        for &glyph in &t.text.glyphs {
+            // Glyphs that cannot be read are not painted either, see `show_text`.
+            if let Ok(Some((ink_start, ink_end))) = font.glyph_ink_between(glyph, bottom, top) {
+                gaps.push((
+                    start + ems_to_px(advance + ink_start - thickness),
+                    start + ems_to_px(advance + ink_end + thickness),
+                ))
+            }
+            advance += font.glyph_width(glyph).unwrap_or(euclid::Length::new(0.));
+        }
+        gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    page.set_color(&color);
+    let top = baseline.y.px() - ems_to_px(position);
+    let height = ems_to_px(thickness);
+    let mut segment_start = start;
+    for (gap_start, gap_end) in gaps.into_iter().chain(Some((end, end))) {
+        let segment_end = gap_start.min(end);
+        if segment_end > segment_start {
+            let width = segment_end - segment_start;
+            page.paint_rectangle(&primitives::rect(segment_start, top, width, height));
+        }
+        segment_start = segment_start.max(gap_end)
+    }
+}
+
+fn set_text_stroke(page: &mut Page, stroke: Option<(Length, RGBA)>) {
+    match stroke {
+        Some((width, color)) => page
+            .set_stroke_color(&color)
+            .set_line_width(width.into())
+            .set_text_rendering_mode(TextRenderingMode::FillThenStroke),
+        None => page.set_text_rendering_mode(TextRenderingMode::Fill),
+    };
+}
+
+/// The matrix of the `transform` property of a box with this border box, in page coordinates:
+/// https://drafts.csswg.org/css-transforms-1/#transformation-matrix-computation
+fn transform_matrix(
+    functions: &[TransformFunction],
+    origin: &TransformOrigin,
+    border_rect: &primitives::Rect<CssPx>,
+) -> Transform<CssPx, CssPx> {
+    let width = Length::from_px(border_rect.size.width);
+    let height = Length::from_px(border_rect.size.height);
+    let origin = border_rect.origin
+        + euclid::vec2(
+            origin.x.percentage_relative_to(width).px(),
+            origin.y.percentage_relative_to(height).px(),
+        );
+    // With row vectors, the last function is the first to apply to a point.
+    let mut matrix = Transform::create_translation(-origin.x, -origin.y);
+    for function in functions.iter().rev() {
+        matrix = matrix.post_mul(&match *function {
+            TransformFunction::Translate(x, y) => Transform::create_translation(
+                x.percentage_relative_to(width).px(),
+                y.percentage_relative_to(height).px(),
+            ),
+            TransformFunction::Scale(x, y) => Transform::create_scale(x, y),
+            TransformFunction::Rotate(degrees) => {
+                // Clockwise, since the y axis points down
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                Transform::row_major(cos, sin, -sin, cos, 0., 0.)
+            }
+            TransformFunction::Matrix([a, b, c, d, e, f]) => Transform::row_major(a, b, c, d, e, f),
+        })
+    }
+    matrix.post_translate(origin.to_vector())
+}
+
+/// Where to paint copies of text for a shadow with `blur_radius`, relative to its offset,
+/// and the alpha of each copy.
+///
+/// FIXME: this approximates a Gaussian blur with nine copies spread over the blur radius,
+/// each a third as opaque as the shadow color: the shadow is nearly as opaque as its color
+/// where most copies overlap, and fades towards its edges where few do.
+/// A soft mask of the text’s blurred rendering would look smoother.
+fn shadow_copies(blur_radius: Length, alpha: f32) -> (Vec<Vec2<Length>>, f32) {
+    if blur_radius <= Length::zero() {
+        let zero = Length::zero();
+        return (vec![Vec2 { x: zero, y: zero }], alpha);
+    }
+    let spread = blur_radius / 2.;
+    let steps = [-spread, Length::zero(), spread];
+    let offsets = steps
+        .iter()
+        .flat_map(|&x| steps.iter().map(move |&y| Vec2 { x, y }))
+        .collect();
+    (offsets, alpha / 3.)
+}
+
+/// Paint with `filters` applied in order, each to the result of the ones before it:
+/// https://drafts.fxtf.org/filter-effects/#FilterProperty
+///
+/// FIXME: color matrices apply to each painted color, not to the result of painting
+/// the whole box like CSS does, so that with `opacity()` overlapping descendants show
+/// through each other. A transparency group form XObject would composite them first.
+fn paint_filtered(
+    page: &mut Page,
+    style: &ComputedValues,
+    filters: &[FilterFunction],
+    paint: &mut dyn FnMut(&mut Page),
+) {
+    // The last filter is applied to the result of the others, so it is set up first.
+    let (last, others) = match filters.split_last() {
+        Some(split) => split,
+        None => return paint(page),
+    };
+    let matrix = match last {
+        FilterFunction::Opacity(amount) => ColorMatrix::opacity(*amount),
+        // https://drafts.fxtf.org/filter-effects/#grayscaleEquivalent
+        FilterFunction::Grayscale(amount) => mix_with_identity(
+            [
+                [0.2126, 0.7152, 0.0722],
+                [0.2126, 0.7152, 0.0722],
+                [0.2126, 0.7152, 0.0722],
+            ],
+            *amount,
+        ),
+        // https://drafts.fxtf.org/filter-effects/#sepiaEquivalent
+        FilterFunction::Sepia(amount) => mix_with_identity(
+            [
+                [0.393, 0.769, 0.189],
+                [0.349, 0.686, 0.168],
+                [0.272, 0.534, 0.131],
+            ],
+            *amount,
+        ),
+        // https://drafts.fxtf.org/filter-effects/#funcdef-filter-drop-shadow
+        // The box is painted again in the shadow color below itself,
+        // with the same copies as a text shadow for the blur.
+        // It is recorded once as a form, so that each copy only references it.
+        FilterFunction::DropShadow(shadow) => {
+            let color: RGBA = style.to_rgba(shadow.color).into();
+            let (offsets, alpha) = shadow_copies(shadow.blur_radius, color.3);
+            let flood = ColorMatrix::flood(RGBA(color.0, color.1, color.2, alpha));
+            page.save_state().apply_color_matrix(&flood);
+            let form = page.record_form(|page| paint_filtered(page, style, others, paint));
+            for offset in offsets {
+                let x = shadow.offset_x + offset.x;
+                let y = shadow.offset_y + offset.y;
+                page.save_state()
+                    .transform(&Transform::create_translation(x.px(), y.px()))
+                    .paint_form(&form)
+                    .restore_state();
+            }
+            page.restore_state();
+            return paint_filtered(page, style, others, paint);
+        }
+    };
+    page.save_state().apply_color_matrix(&matrix);
+    paint_filtered(page, style, others, paint);
+    page.restore_state();
+}
+
+fn blend_mode(mode: MixBlendMode) -> BlendMode {
+    match mode {
+        MixBlendMode::Normal => BlendMode::Normal,
+        MixBlendMode::Multiply => BlendMode::Multiply,
+        MixBlendMode::Screen => BlendMode::Screen,
+        MixBlendMode::Overlay => BlendMode::Overlay,
+        MixBlendMode::Darken => BlendMode::Darken,
+        MixBlendMode::Lighten => BlendMode::Lighten,
+        MixBlendMode::ColorDodge => BlendMode::ColorDodge,
+        MixBlendMode::ColorBurn => BlendMode::ColorBurn,
+        MixBlendMode::HardLight => BlendMode::HardLight,
+        MixBlendMode::SoftLight => BlendMode::SoftLight,
+        MixBlendMode::Difference => BlendMode::Difference,
+        MixBlendMode::Exclusion => BlendMode::Exclusion,
+        MixBlendMode::Hue => BlendMode::Hue,
+        MixBlendMode::Saturation => BlendMode::Saturation,
+        MixBlendMode::Color => BlendMode::Color,
+        MixBlendMode::Luminosity => BlendMode::Luminosity,
+    }
+}
+
+/// A filter that changes red, green and blue with `rgb` at `amount` 1,
+/// and does nothing at `amount` 0.
+fn mix_with_identity(rgb: [[f32; 3]; 3], amount: f32) -> ColorMatrix {
+    let mut matrix = ColorMatrix::IDENTITY;
+    for (row, rgb_row) in matrix.0.iter_mut().zip(&rgb) {
+        for (value, rgb_value) in row.iter_mut().zip(rgb_row) {
+            *value = *value * (1. - amount) + rgb_value * amount
+        }
+    }
+    matrix
+}
+
+/// FIXME: form fields are annotations, which are not affected by `transform`:
+/// the field of a transformed box is at its untransformed border box.
+fn add_form_field(page: &mut Page, field: &FormFieldFragment, rect: &primitives::Rect<CssPx>) {
+    let options: Vec<&str>;
+    let kind = match &field.value {
+        FormFieldValue::Text { value, multiline } => FormFieldKind::Text {
+            value,
+            multiline: *multiline,
+        },
+        FormFieldValue::Checkbox { checked } => FormFieldKind::Checkbox { checked: *checked },
+        FormFieldValue::Choice {
+            options: labels,
+            selected,
+        } => {
+            options = labels.iter().map(|label| &**label).collect();
+            FormFieldKind::Choice {
+                options: &options,
+                selected: *selected,
+            }
+        }
+    };
+    page.add_form_field(&FormField {
+        name: &field.name,
+        rect: *rect,
+        kind,
+    });
+}
+
+impl CheckboxFragment {
+    /// Stroke a square, or a circle for radio buttons, in the text color,
+    /// with a check mark or a dot inside when checked.
+    fn paint_onto(&self, page: &mut Page, rect: &primitives::Rect<CssPx>) {
+        let color: RGBA = self.style.color.color.into();
+        let side = rect.size.width.min(rect.size.height);
+        let center = rect.center();
+        let point = |x: f32, y: f32| primitives::Point::new(center.x + x, center.y + y);
+        // 1px at the default size of 13px
+        let line_width = side / 13.;
+        // The outline is stroked inside the content box.
+        let outline_radius = (side - line_width) / 2.;
+        page.set_color(&color)
+            .set_stroke_color(&color)
+            .set_line_width(primitives::Length::new(line_width));
+        if self.radio {
+            circle(page, &center, outline_radius).stroke();
+            if self.checked {
+                circle(page, &center, side / 4.).fill(FillRule::NonZero);
+            }
+            return;
+        }
+        let r = outline_radius;
+        page.move_to(&point(-r, -r))
+            .line_to(&point(r, -r))
+            .line_to(&point(r, r))
+            .line_to(&point(-r, r))
+            .close_path()
+            .stroke();
+        if self.checked {
+            let s = side / 2.;
+            page.set_line_width(primitives::Length::new(side * 0.15))
+                .move_to(&point(-s * 0.55, 0.))
+                .line_to(&point(-s * 0.15, s * 0.45))
+                .line_to(&point(s * 0.55, -s * 0.45))
+                .stroke();
+        }
+    }
+}
+
+/// Add a circle to the current path, approximated with four Bézier curves
+fn circle<'p, 'a>(
+    page: &'p mut Page<'a>,
+    center: &primitives::Point<CssPx>,
+    radius: f32,
+) -> &'p mut Page<'a> {
+    ellipse(page, center, radius, radius)
+}
+
+/// Add an ellipse with horizontal radius `rx` and vertical radius `ry` to the current path,
+/// approximated with four Bézier curves
+fn ellipse<'p, 'a>(
+    page: &'p mut Page<'a>,
+    center: &primitives::Point<CssPx>,
+    rx: f32,
+    ry: f32,
+) -> &'p mut Page<'a> {
+    // Distance from each end of a quarter ellipse to the control point next to it
+    let (kx, ky) = (rx * 0.552_284_8, ry * 0.552_284_8);
+    let point = |x: f32, y: f32| primitives::Point::new(center.x + x, center.y + y);
+    page.move_to(&point(rx, 0.))
+        .curve_to(&point(rx, ky), &point(kx, ry), &point(0., ry))
+        .curve_to(&point(-kx, ry), &point(-rx, ky), &point(-rx, 0.))
+        .curve_to(&point(-rx, -ky), &point(-kx, -ry), &point(0., -ry))
+        .curve_to(&point(kx, -ry), &point(rx, -ky), &point(rx, 0.))
+        .close_path()
+}
+
 impl BoxFragment {
-    fn paint_onto(&self, page: &mut Page, containing_block: &Rect<Length>) {
-        let background_color = self.style.to_rgba(self.style.background.background_color);
+    fn paint_onto(
+        &self,
+        page: &mut Page,
+        containing_block: &ContainingBlock,
+        options: PaintOptions,
+    ) {
+        // Each drop shadow paints the box and its descendants a second time,
+        // so the number of times nested boxes are painted doubles with each one.
+        // Beyond a limit, drop shadows are not painted: the layout report lists them.
+        let mut options = options;
+        let filters = &self.style.box_.filter.0;
+        options.nested_drop_shadows += self.drop_shadow_count();
+        let filters: Cow<[FilterFunction]> =
+            if options.nested_drop_shadows > MAX_NESTED_DROP_SHADOWS {
+                filters
+                    .iter()
+                    .filter(|f| !matches!(f, FilterFunction::DropShadow(_)))
+                    .cloned()
+                    .collect()
+            } else {
+                Cow::Borrowed(filters)
+            };
+        // Filters apply in the local coordinate system of the box, before its transform.
+        let paint_filtered_box = |page: &mut Page| {
+            paint_filtered(page, &self.style, &filters, &mut |page| {
+                self.paint_untransformed_onto(page, containing_block, options)
+            })
+        };
+        // Clipping applies after filters, so that it also clips their drop shadows.
+        let paint_clipped_box = |page: &mut Page| match &self.style.box_.clip_path {
+            ClipPath::None => paint_filtered_box(page),
+            ClipPath::Shape(shape, reference_box) => {
+                page.save_state();
+                self.clip_to_shape(page, shape.as_ref(), *reference_box, containing_block);
+                paint_filtered_box(page);
+                page.restore_state();
+            }
+        };
+        // The group includes the transform, so that the page bounds its content.
+        let paint = |page: &mut Page| {
+            let transform = &self.style.box_.transform;
+            if transform.is_none() {
+                return paint_clipped_box(page);
+            }
+            let border_rect = containing_block.to_page(&self.border_rect()).into();
+            page.save_state().transform(&transform_matrix(
+                &transform.0,
+                &self.style.box_.transform_origin,
+                &border_rect,
+            ));
+            paint_clipped_box(page);
+            page.restore_state();
+        };
+        let structure_type = options
+            .structure
+            .and_then(|document| self.structure_type(document));
+        if let Some(kind) = structure_type {
+            page.begin_structure_element(kind);
+        }
+        if self.is_isolated_group() {
+            // Borders and backgrounds snapped to device pixels can move by up to a pixel.
+            let bbox = self.ink_overflow(containing_block).inflate(1., 1.);
+            let blend_mode = blend_mode(self.style.box_.mix_blend_mode);
+            page.paint_group(blend_mode, &bbox, paint);
+        } else {
+            paint(page)
+        }
+        if structure_type.is_some() {
+            page.end_structure_element();
+        }
+    }
+
+    fn drop_shadow_count(&self) -> usize {
+        let filters = &self.style.box_.filter.0;
+        filters
+            .iter()
+            .filter(|f| matches!(f, FilterFunction::DropShadow(_)))
+            .count()
+    }
+
+    /// The structure type of this box in a tagged PDF, if it is a paragraph or a heading.
+    fn structure_type(&self, document: &crate::dom::Document) -> Option<&'static str> {
+        if self.is_inline_box() {
+            return None;
+        }
+        let element = document[self.tag?].as_element()?;
+        if element.name.ns != ns!(html) {
+            return None;
+        }
+        Some(match &*element.name.local {
+            "p" => "P",
+            "h1" => "H1",
+            "h2" => "H2",
+            "h3" => "H3",
+            "h4" => "H4",
+            "h5" => "H5",
+            "h6" => "H6",
+            _ => return None,
+        })
+    }
+
+    /// Whether this box is painted as an isolated group, that its descendants’
+    /// `mix-blend-mode` blends with instead of with what is painted below it:
+    /// https://drafts.fxtf.org/compositing-1/#isolation
+    ///
+    /// Every stacking context is an isolated group,
+    /// but this only makes a difference when a descendant blends.
+    fn is_isolated_group(&self) -> bool {
+        let box_ = &self.style.box_;
+        if box_.mix_blend_mode != MixBlendMode::Normal || box_.isolation == Isolation::Isolate {
+            return true;
+        }
+        self.style.establishes_stacking_context()
+            && self.children.iter().any(Fragment::has_blending)
+    }
+
+    /// The area that this box and its descendants can paint,
+    /// including drop shadows and the box’s own transform:
+    /// https://drafts.csswg.org/css-overflow-3/#ink
+    fn ink_overflow(&self, containing_block: &ContainingBlock) -> primitives::Rect<CssPx> {
+        let border_rect: primitives::Rect<CssPx> =
+            containing_block.to_page(&self.border_rect()).into();
+        let containing_block_for_children = containing_block.for_children_of(self);
+        let mut ink = self
+            .children
+            .iter()
+            .filter_map(|child| child.ink_overflow(&containing_block_for_children))
+            .fold(border_rect, |ink, child| ink.union(&child));
+        for filter in self.style.box_.filter.0.iter() {
+            if let FilterFunction::DropShadow(shadow) = filter {
+                // Copies of the box are spread over half the blur radius, see `shadow_copies`.
+                let spread = (shadow.blur_radius / 2.).px();
+                let offset = euclid::vec2(shadow.offset_x.px(), shadow.offset_y.px());
+                ink = ink.union(&ink.translate(&offset).inflate(spread, spread))
+            }
+        }
+        let transform = &self.style.box_.transform;
+        if transform.is_none() {
+            return ink;
+        }
+        let origin = &self.style.box_.transform_origin;
+        transform_matrix(&transform.0, origin, &border_rect).transform_rect(&ink)
+    }
+
+    /// Intersect the clipping region with `shape` in the given box of this fragment,
+    /// or with that box if there is no shape:
+    /// https://drafts.fxtf.org/css-masking/#the-clip-path
+    fn clip_to_shape(
+        &self,
+        page: &mut Page,
+        shape: Option<&BasicShape>,
+        reference_box: ShapeBox,
+        containing_block: &ContainingBlock,
+    ) {
+        let reference_box = match reference_box {
+            ShapeBox::Margin => self.border_rect().inflate(&self.margin),
+            ShapeBox::Border => self.border_rect(),
+            ShapeBox::Padding => self.padding_rect(),
+            ShapeBox::Content => self.content_rect.clone(),
+        };
+        let point = |point: &flow_relative::Vec2<Length>| {
+            let rect = flow_relative::Rect {
+                start_corner: point.clone(),
+                size: flow_relative::Vec2::zero(),
+            };
+            primitives::Point::from(containing_block.to_page(&rect).top_left)
+        };
+        let mut rule = FillRule::NonZero;
+        match ResolvedShape::resolve(shape, &reference_box) {
+            ResolvedShape::Rect(rect) => {
+                let rect: primitives::Rect<CssPx> = containing_block.to_page(&rect).into();
+                page.move_to(&rect.origin)
+                    .line_to(&rect.top_right())
+                    .line_to(&rect.bottom_right())
+                    .line_to(&rect.bottom_left())
+                    .close_path();
+            }
+            ResolvedShape::Ellipse { center, radii } => {
+                let radii = radii.size_to_physical(containing_block.mode);
+                ellipse(page, &point(&center), radii.x.px(), radii.y.px());
+            }
+            ResolvedShape::Polygon(vertices) => {
+                let mut vertices = vertices.iter().map(point);
+                if let Some(first) = vertices.next() {
+                    page.move_to(&first);
+                }
+                for vertex in vertices {
+                    page.line_to(&vertex);
+                }
+                page.close_path();
+                if let Some(BasicShape::Polygon { even_odd: true, .. }) = shape {
+                    rule = FillRule::EvenOdd
+                }
+            }
+        }
+        page.clip(rule);
+    }
+
+    fn paint_untransformed_onto(
+        &self,
+        page: &mut Page,
+        containing_block: &ContainingBlock,
+        options: PaintOptions,
+    ) {
+        let border_rect = containing_block.to_page(&self.border_rect()).into();
+        let background = &self.style.background;
+        let background_box = |box_| match box_ {
+            BackgroundBox::Border => border_rect,
+            BackgroundBox::Padding => containing_block.to_page(&self.padding_rect()).into(),
+            BackgroundBox::Content => containing_block.to_page(&self.content_rect).into(),
+        };
+        // https://drafts.csswg.org/css-backgrounds/#background-clip
+        let mut clip_rect = background_box(background.background_clip);
+        if let Some(device_pixels_per_px) = options.pixel_snapping {
+            clip_rect = snap_to_device_pixels(&clip_rect, device_pixels_per_px)
+        }
+        let background_color = self.style.to_rgba(background.background_color);
         if background_color.alpha > 0 {
             page.set_color(&background_color.into());
-            let rect = self
-                .border_rect()
-                .to_physical(self.style.writing_mode(), containing_block)
-                .translate(&containing_block.top_left)
-                .into();
-            page.paint_rectangle(&rect);
-        }
-        let content_rect = self
-            .content_rect
-            .to_physical(self.style.writing_mode(), containing_block)
-            .translate(&containing_block.top_left);
+            page.paint_rectangle(&clip_rect);
+        }
+        if let BackgroundImage::Url(url) = &background.background_image {
+            if let Some(image) = options.images.borrow_mut().get_url(url) {
+                // https://drafts.csswg.org/css-backgrounds/#background-origin
+                // The first tile is at the top-left corner of the origin box,
+                // with one image pixel per CSS `px`.
+                let tile = primitives::Rect::new(
+                    background_box(background.background_origin).origin,
+                    primitives::Size::new(image.width as f32, image.height as f32),
+                );
+                // `JpegImage` has no samples that could have the wrong length
+                let _ = page.paint_image_tiles(&image.as_pdf_image(), &tile, &clip_rect);
+            }
+        }
+        self.paint_border(page, &border_rect, options);
+        if options
+            .cancellation_token
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return;
+        }
+        let containing_block_for_children = containing_block.for_children_of(self);
         for child in &self.children {
-            child.paint_onto(page, &content_rect)
+            if let Fragment::FormField(field) = child {
+                add_form_field(page, field, &border_rect)
+            }
+            child.paint_onto(page, &containing_block_for_children, options)
+        }
+    }
+
+    /// Paint each side of the border in its color and style.
+    /// `double`, `groove`, `ridge`, `inset` and `outset` are not supported yet.
+    ///
+    /// FIXME: corners between sides of different colors or styles should be split diagonally.
+    fn paint_border(
+        &self,
+        page: &mut Page,
+        border_rect: &primitives::Rect<CssPx>,
+        options: PaintOptions,
+    ) {
+        let b = &self.style.border;
+        let width = |w: LineWidth| w.0.percentage_relative_to(Length::zero()).px();
+        let top = width(b.border_top_width);
+        let right = width(b.border_right_width);
+        let bottom = width(b.border_bottom_width);
+        let left = width(b.border_left_width);
+        let (x, y) = (border_rect.origin.x, border_rect.origin.y);
+        let (w, h) = (border_rect.size.width, border_rect.size.height);
+        let sides = [
+            (
+                b.border_top_style,
+                b.border_top_color,
+                Axis::Horizontal,
+                primitives::rect(x, y, w, top),
+            ),
+            (
+                b.border_bottom_style,
+                b.border_bottom_color,
+                Axis::Horizontal,
+                primitives::rect(x, y + h - bottom, w, bottom),
+            ),
+            (
+                b.border_left_style,
+                b.border_left_color,
+                Axis::Vertical,
+                primitives::rect(x, y + top, left, h - top - bottom),
+            ),
+            (
+                b.border_right_style,
+                b.border_right_color,
+                Axis::Vertical,
+                primitives::rect(x + w - right, y + top, right, h - top - bottom),
+            ),
+        ];
+        for &(style, color, axis, mut rect) in &sides {
+            let color = self.style.to_rgba(color);
+            if color.alpha > 0 && rect.size.width > 0. && rect.size.height > 0. {
+                if let Some(device_pixels_per_px) = options.pixel_snapping {
+                    rect = snap_to_device_pixels(&rect, device_pixels_per_px)
+                }
+                paint_border_side(
+                    page,
+                    &rect,
+                    axis,
+                    style,
+                    &color.into(),
+                    options.dashed_borders,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Paint one side of a border, that runs along `axis` for the length of `rect`.
+///
+/// Dots are squares and dashes are twice as long as the border is wide,
+/// with gaps as long as the border is wide.
+fn paint_border_side(
+    page: &mut Page,
+    rect: &primitives::Rect<CssPx>,
+    axis: Axis,
+    style: LineStyle,
+    color: &RGBA,
+    dashed_borders: DashedBorders,
+) {
+    let (length, thickness) = match axis {
+        Axis::Horizontal => (rect.size.width, rect.size.height),
+        Axis::Vertical => (rect.size.height, rect.size.width),
+    };
+    let dash = match style {
+        LineStyle::None | LineStyle::Solid => {
+            page.set_color(color).paint_rectangle(rect);
+            return;
+        }
+        LineStyle::Dotted => thickness,
+        LineStyle::Dashed => thickness * 2.,
+    };
+    let gap = thickness;
+    match dashed_borders {
+        DashedBorders::Segments => {
+            page.set_color(color);
+            let mut start = 0.;
+            while start < length {
+                let dash = dash.min(length - start);
+                let segment = match axis {
+                    Axis::Horizontal => {
+                        primitives::rect(rect.min_x() + start, rect.min_y(), dash, thickness)
+                    }
+                    Axis::Vertical => {
+                        primitives::rect(rect.min_x(), rect.min_y() + start, thickness, dash)
+                    }
+                };
+                page.paint_rectangle(&segment);
+                start += dash + gap;
+            }
+        }
+        DashedBorders::DashPattern => {
+            let center = rect.center();
+            let (from, to) = match axis {
+                Axis::Horizontal => (
+                    primitives::point(rect.min_x(), center.y),
+                    primitives::point(rect.max_x(), center.y),
+                ),
+                Axis::Vertical => (
+                    primitives::point(center.x, rect.min_y()),
+                    primitives::point(center.x, rect.max_y()),
+                ),
+            };
+            let no_phase = primitives::Length::new(0.);
+            page.set_stroke_color(color)
+                .set_line_width(primitives::Length::new(thickness))
+                .set_dash_pattern(
+                    &[primitives::Length::new(dash), primitives::Length::new(gap)],
+                    no_phase,
+                )
+                .stroke_line(&from, &to)
+                // Text is stroked with solid lines
+                .set_dash_pattern(&[], no_phase);
         }
     }
 }
+
+/// Round each edge to the nearest device pixel boundary.
+///
+/// A non-empty side stays at least one device pixel long, so that hairlines do not disappear.
+fn snap_to_device_pixels(
+    rect: &primitives::Rect<CssPx>,
+    device_pixels_per_px: f32,
+) -> primitives::Rect<CssPx> {
+    let snap = |start: f32, length: f32| {
+        let start_device = (start * device_pixels_per_px).round();
+        let mut end_device = ((start + length) * device_pixels_per_px).round();
+        if length > 0. && end_device == start_device {
+            end_device += 1.
+        }
+        (
+            start_device / device_pixels_per_px,
+            (end_device - start_device) / device_pixels_per_px,
+        )
+    };
+    let (x, width) = snap(rect.origin.x, rect.size.width);
+    let (y, height) = snap(rect.origin.y, rect.size.height);
+    primitives::rect(x, y, width, height)
+}
@@ -1,27 +1,67 @@
+#[cfg(feature = "html")]
+use crate::fonts::Substitutions;
 use crate::fonts::{Font, FontError, GlyphId};
 use crate::pdf::object::{Dictionary, Object};
-use crate::pdf::syntax::{BasicObjects, PdfFile, PAGE_TREE_ID};
+use crate::pdf::syntax::{BasicObjects, IndirectObjectId, PdfFile, PAGE_TREE_ID};
+use crate::pdf::{AttachedFile, FileRelationship, FormField, FormFieldKind, Image, ImageData};
+use crate::pdf::{PageLabelRange, PageNumberStyle};
 use crate::primitives::*;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::hash_map::{Entry, RandomState};
+use std::collections::{HashMap, HashSet};
 use std::hash;
 use std::io::{self, Write};
 use std::ops::Deref;
 use std::sync::Arc;
 
 const PT_PER_INCH: f32 = 72.;
-const PX_PER_INCH: f32 = 96.;
 const PT_PER_PX: f32 = PT_PER_INCH / PX_PER_INCH;
-const CSS_TO_PDF_SCALE_X: f32 = PT_PER_PX;
-const CSS_TO_PDF_SCALE_Y: f32 = -PT_PER_PX; // Flip the Y axis direction, it defaults to upwards in PDF.
+
+/// The default user space of a page: origin at bottom-left corner, unit `1pt`
+struct PdfPt;
+
+/// Set up with the `cm` operator at the start of each page,
+/// so that content streams use CSS coordinates.
+fn css_to_pdf() -> Transform<CssPx, PdfPt> {
+    // Flip the Y axis direction, it defaults to upwards in PDF.
+    Transform::create_scale(PT_PER_PX, -PT_PER_PX)
+}
 
 pub(crate) struct InProgressDoc {
     pdf: PdfFile,
     page_ids: Vec<Object<'static>>,
     extended_graphics_states: Vec<(Vec<u8>, Object<'static>)>,
     font_resources: Vec<(Vec<u8>, Object<'static>)>,
-    alpha_states: HashMap<u16, String>,
+    xobject_resources: Vec<(Vec<u8>, Object<'static>)>,
+    /// Page content streams keyed by their bytes, so that identical pages share one stream
+    content_streams: HashMap<Vec<u8>, IndirectObjectId>,
+    /// Form XObjects keyed by their content, see `InProgressPage::end_repeated`
+    repeated_contents: HashMap<Vec<u8>, Vec<(GraphicsState, String)>>,
+    alpha_states: HashMap<(u16, u16), String>,
+    blend_mode_states: HashMap<BlendMode, String>,
     fonts: HashMap<ByAddress<Arc<Font>>, String>,
+    /// File specifications keyed by file name (as a text string), sorted by key
+    embedded_files: Vec<(Vec<u8>, IndirectObjectId)>,
+    form_fields: Vec<Object<'static>>,
+    /// Partial names of the fields in `form_fields`
+    form_field_names: HashSet<String>,
+    has_signature_fields: bool,
+    /// Page label dictionaries keyed by page index, sorted by key
+    page_labels: Vec<(usize, IndirectObjectId)>,
+    /// Reserved when the first structure element begins,
+    /// see `InProgressPage::begin_structure_element`
+    structure_tree_root: Option<IndirectObjectId>,
+    /// References to the structure elements that are children of the root
+    structure_tree_kids: Vec<Object<'static>>,
+    /// Indexed by the `StructParents` key of each page with marked content:
+    /// an array of the structure elements that its marked-content identifiers belong to
+    parent_tree: Vec<IndirectObjectId>,
+    /// Raster images with more pixels per inch at their painted size are downsampled
+    pub(crate) max_image_resolution: Option<f32>,
+    /// Image XObjects keyed by a hash of their size, format and data,
+    /// so that an image painted many times is embedded once.
+    /// The hash is randomly keyed, so that documents cannot make images collide.
+    images: HashMap<u64, String>,
+    image_hasher: RandomState,
 }
 
 impl InProgressDoc {
@@ -31,33 +71,392 @@ impl InProgressDoc {
             page_ids: Vec::new(),
             extended_graphics_states: Vec::new(),
             font_resources: Vec::new(),
+            xobject_resources: Vec::new(),
+            content_streams: HashMap::new(),
+            repeated_contents: HashMap::new(),
             alpha_states: HashMap::new(),
+            blend_mode_states: HashMap::new(),
             fonts: HashMap::new(),
+            embedded_files: Vec::new(),
+            form_fields: Vec::new(),
+            form_field_names: HashSet::new(),
+            has_signature_fields: false,
+            page_labels: Vec::new(),
+            structure_tree_root: None,
+            structure_tree_kids: Vec::new(),
+            parent_tree: Vec::new(),
+            max_image_resolution: None,
+            images: HashMap::new(),
+            image_hasher: RandomState::new(),
+        }
+    }
+
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G11.2384826
+    pub(crate) fn add_page_label_range(&mut self, range: &PageLabelRange) {
+        // The number tree must have an entry for the first page
+        if self.page_labels.is_empty() && range.first_page != 0 {
+            self.add_page_label_range(&PageLabelRange {
+                first_page: 0,
+                style: Some(PageNumberStyle::Decimal),
+                prefix: "",
+                start: 1,
+            })
+        }
+        let label = dictionary! {
+            "Type" => "PageLabel",
+            "St" => range.start,
+        };
+        let style = range.style.map(|style| match style {
+            PageNumberStyle::Decimal => "D",
+            PageNumberStyle::UpperRoman => "R",
+            PageNumberStyle::LowerRoman => "r",
+            PageNumberStyle::UpperAlpha => "A",
+            PageNumberStyle::LowerAlpha => "a",
+        });
+        let style_pairs = key_value_pairs! {
+            "S" => style.unwrap_or_default(),
+        };
+        let label = Dictionary {
+            prev: Some(&label),
+            pairs: if style.is_some() { style_pairs } else { &[] },
+        };
+        let prefix = text_string(range.prefix);
+        let prefix_pairs = key_value_pairs! {
+            "P" => Object::HexString(&prefix),
+        };
+        let id = self.pdf.add_dictionary(Dictionary {
+            prev: Some(&label),
+            pairs: if range.prefix.is_empty() {
+                &[]
+            } else {
+                prefix_pairs
+            },
+        });
+
+        // Keys in a number tree are unique and sorted
+        match self
+            .page_labels
+            .binary_search_by_key(&range.first_page, |&(page, _)| page)
+        {
+            Ok(index) => self.page_labels[index].1 = id,
+            Err(index) => self.page_labels.insert(index, (range.first_page, id)),
+        }
+    }
+
+    /// Embedded File Streams
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1944624
+    pub(crate) fn attach_file(&mut self, file: &AttachedFile) {
+        let params = dictionary! {
+            "Size" => file.contents.len(),
+        };
+        let stream_meta = dictionary! {
+            "Type" => "EmbeddedFile",
+            "Params" => params,
+        };
+        let stream_id = match file.mime_type {
+            Some(mime_type) => self.pdf.add_stream(
+                linked_dictionary! {
+                    &stream_meta,
+                    "Subtype" => mime_type,
+                },
+                file.contents.into(),
+            ),
+            None => self.pdf.add_stream(stream_meta, file.contents.into()),
+        };
+
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1640832
+        let name = text_string(file.name);
+        let relationship = match file.relationship {
+            FileRelationship::Source => "Source",
+            FileRelationship::Data => "Data",
+            FileRelationship::Alternative => "Alternative",
+            FileRelationship::Supplement => "Supplement",
+            FileRelationship::Unspecified => "Unspecified",
+        };
+        let embedded_file = dictionary! {
+            "F" => stream_id,
+            "UF" => stream_id,
+        };
+        let file_spec = dictionary! {
+            "Type" => "Filespec",
+            // The `F` entry is a byte string. Readers that support `UF` prefer it.
+            "F" => Object::LiteralString(file.name.as_bytes()),
+            "UF" => Object::HexString(&name),
+            "EF" => embedded_file,
+            // Associated files, from PDF 2.0 and PDF/A-3
+            "AFRelationship" => relationship,
+        };
+        let file_spec_id = match file.description {
+            Some(description) => self.pdf.add_dictionary(linked_dictionary! {
+                &file_spec,
+                "Desc" => Object::HexString(&text_string(description)),
+            }),
+            None => self.pdf.add_dictionary(file_spec),
+        };
+
+        // Keys in a name tree are unique and sorted
+        match self
+            .embedded_files
+            .binary_search_by(|(key, _)| key.as_slice().cmp(&name))
+        {
+            Ok(index) => self.embedded_files[index].1 = file_spec_id,
+            Err(index) => self.embedded_files.insert(index, (name, file_spec_id)),
         }
     }
 
     pub(crate) fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => PAGE_TREE_ID,
+        };
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1945290
+        let embedded_files_names: Vec<_> = self
+            .embedded_files
+            .iter()
+            .flat_map(|(key, id)| vec![Object::HexString(key), Object::Reference(*id)])
+            .collect();
+        let associated_files: Vec<_> = self
+            .embedded_files
+            .iter()
+            .map(|&(_, id)| Object::Reference(id))
+            .collect();
+        let embedded_files = dictionary! {
+            "Names" => &*embedded_files_names,
+        };
+        let names = dictionary! {
+            "EmbeddedFiles" => embedded_files,
+        };
+        let attachments = key_value_pairs! {
+            "Names" => names,
+            "AF" => &*associated_files,
+        };
+        let catalog_with_attachments = Dictionary {
+            prev: Some(&catalog),
+            pairs: if self.embedded_files.is_empty() {
+                &[]
+            } else {
+                attachments
+            },
+        };
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1945290
+        let page_labels_nums: Vec<_> = self
+            .page_labels
+            .iter()
+            .flat_map(|&(page, id)| vec![Object::Usize(page), Object::Reference(id)])
+            .collect();
+        let page_labels_tree = dictionary! {
+            "Nums" => &*page_labels_nums,
+        };
+        let page_labels = key_value_pairs! {
+            "PageLabels" => page_labels_tree,
+        };
+        let catalog_with_page_labels = Dictionary {
+            prev: Some(&catalog_with_attachments),
+            pairs: if self.page_labels.is_empty() {
+                &[]
+            } else {
+                page_labels
+            },
+        };
+        // Interactive Forms
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G11.1951345
+        let helvetica = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        };
+        let fonts = dictionary! {
+            "Helv" => helvetica,
+        };
+        let default_resources = dictionary! {
+            "Font" => fonts,
+        };
+        let acro_form_dictionary = dictionary! {
+            "Fields" => &*self.form_fields,
+            // Readers draw fields, since we don’t provide appearance streams
+            "NeedAppearances" => true,
+            "DA" => Object::LiteralString(DEFAULT_APPEARANCE),
+            "DR" => default_resources,
+        };
+        let signature_flags = key_value_pairs! {
+            // SignaturesExist | AppendOnly: later changes should be incremental updates
+            "SigFlags" => 3,
+        };
+        let acro_form = key_value_pairs! {
+            "AcroForm" => Dictionary {
+                prev: Some(&acro_form_dictionary),
+                pairs: if self.has_signature_fields {
+                    signature_flags
+                } else {
+                    &[]
+                },
+            },
+        };
+        let resources = dictionary! {
+            "Font" => Object::DictionaryWithOwnedKeys(&self.font_resources),
+            "ExtGState" => Object::DictionaryWithOwnedKeys(&self.extended_graphics_states),
+        };
+        let images = key_value_pairs! {
+            "XObject" => Object::DictionaryWithOwnedKeys(&self.xobject_resources),
+        };
+        let catalog_with_acro_form = Dictionary {
+            prev: Some(&catalog_with_page_labels),
+            pairs: if self.form_fields.is_empty() {
+                &[]
+            } else {
+                acro_form
+            },
+        };
+        // Tagged PDF
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G13.2296397
+        let parent_tree_nums: Vec<_> = self
+            .parent_tree
+            .iter()
+            .enumerate()
+            .flat_map(|(key, &id)| vec![Object::Usize(key), Object::Reference(id)])
+            .collect();
+        let parent_tree = dictionary! {
+            "Nums" => &*parent_tree_nums,
+        };
+        let mark_info = dictionary! {
+            "Marked" => true,
+        };
+        let structure_tree_root = dictionary! {
+            "Type" => "StructTreeRoot",
+            "K" => &*self.structure_tree_kids,
+            "ParentTree" => parent_tree,
+            "ParentTreeNextKey" => self.parent_tree.len(),
+        };
+        let reserved = self.structure_tree_root.map(|id| (id, structure_tree_root));
+        let tagged = self.structure_tree_root.map(|id| {
+            [
+                (&b"StructTreeRoot"[..], Object::Reference(id)),
+                (&b"MarkInfo"[..], Object::Dictionary(mark_info)),
+            ]
+        });
         self.pdf.write(w, &BasicObjects {
             page_tree: dictionary! {
                 "Type" => "Pages",
                 "Count" => self.page_ids.len(),
                 "Kids" => &*self.page_ids,
-                "Resources" => dictionary! {
-                    "Font" => Object::DictionaryWithOwnedKeys(&self.font_resources),
-                    "ExtGState" => Object::DictionaryWithOwnedKeys(&self.extended_graphics_states),
+                "Resources" => Dictionary {
+                    prev: Some(&resources),
+                    pairs: if self.xobject_resources.is_empty() {
+                        &[]
+                    } else {
+                        images
+                    },
                 },
             },
-            catalog: dictionary! {
-                "Type" => "Catalog",
-                "Pages" => PAGE_TREE_ID,
+            catalog: Dictionary {
+                prev: Some(&catalog_with_acro_form),
+                pairs: tagged.as_ref().map_or(&[], |pairs| &pairs[..]),
             },
             info: dictionary! {
                 "Producer" => Object::LiteralString(b"Victor <https://github.com/SimonSapin/victor>"),
             },
+            reserved: reserved.as_slice(),
         })
     }
 }
 
+/// Printer marks are a quarter inch long, and a quarter point wide.
+pub(crate) const MARK_LENGTH: f32 = PX_PER_INCH / 4.;
+const MARK_WIDTH: f32 = 0.25 / PT_PER_PX;
+
+/// Unlike content streams, annotations and page boundaries
+/// are not affected by the transformation matrix set up in `InProgressPage::new`.
+fn in_default_user_space(rect: &Rect<CssPx>) -> [Object<'static>; 4] {
+    let rect = css_to_pdf().transform_rect(rect);
+    [
+        Object::Float(rect.min_x()),
+        Object::Float(rect.min_y()),
+        Object::Float(rect.max_x()),
+        Object::Float(rect.max_y()),
+    ]
+}
+
+/// Resize an image to fewer pixels, with each new pixel
+/// the average of the pixels whose center is in its area.
+fn downsample_rgb8(
+    samples: &[u8],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    debug_assert!(new_width <= width && new_height <= height);
+    let mut result = Vec::with_capacity(new_width * new_height * 3);
+    for new_y in 0..new_height {
+        let y_range = (new_y * height / new_height)..((new_y + 1) * height / new_height);
+        for new_x in 0..new_width {
+            let x_range = (new_x * width / new_width)..((new_x + 1) * width / new_width);
+            let mut sums = [0_u32; 3];
+            for y in y_range.clone() {
+                for x in x_range.clone() {
+                    let pixel = &samples[(y * width + x) * 3..][..3];
+                    for (sum, &sample) in sums.iter_mut().zip(pixel) {
+                        *sum += u32::from(sample)
+                    }
+                }
+            }
+            let count = (y_range.len() * x_range.len()) as u32;
+            result.extend(sums.iter().map(|&sum| ((sum + count / 2) / count) as u8))
+        }
+    }
+    result
+}
+
+/// The name of the blend mode in an `ExtGState` dictionary’s `BM` entry
+fn blend_mode_name(blend_mode: BlendMode) -> &'static str {
+    match blend_mode {
+        BlendMode::Normal => "Normal",
+        BlendMode::Multiply => "Multiply",
+        BlendMode::Screen => "Screen",
+        BlendMode::Overlay => "Overlay",
+        BlendMode::Darken => "Darken",
+        BlendMode::Lighten => "Lighten",
+        BlendMode::ColorDodge => "ColorDodge",
+        BlendMode::ColorBurn => "ColorBurn",
+        BlendMode::HardLight => "HardLight",
+        BlendMode::SoftLight => "SoftLight",
+        BlendMode::Difference => "Difference",
+        BlendMode::Exclusion => "Exclusion",
+        BlendMode::Hue => "Hue",
+        BlendMode::Saturation => "Saturation",
+        BlendMode::Color => "Color",
+        BlendMode::Luminosity => "Luminosity",
+    }
+}
+
+/// Apply `matrix` to each pixel of an opaque image.
+fn filter_rgb8(samples: &[u8], matrix: &ColorMatrix) -> Vec<u8> {
+    let to_unit = |sample: u8| f32::from(sample) / 255.;
+    let to_sample = |value: f32| (value * 255.).round() as u8;
+    let mut result = Vec::with_capacity(samples.len());
+    for pixel in samples.chunks_exact(3) {
+        let color = RGBA(to_unit(pixel[0]), to_unit(pixel[1]), to_unit(pixel[2]), 1.);
+        let RGBA(r, g, b, _) = matrix.apply(color);
+        result.extend_from_slice(&[to_sample(r), to_sample(g), to_sample(b)])
+    }
+    result
+}
+
+/// Text of form fields in automatically-sized Helvetica, in black
+const DEFAULT_APPEARANCE: &[u8] = b"/Helv 0 Tf 0 g";
+
+/// Encode as UTF-16BE with a byte order mark, so that any Unicode text can be represented.
+/// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1957385
+fn text_string(s: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFE, 0xFF];
+    for code_unit in s.encode_utf16() {
+        bytes.extend_from_slice(&code_unit.to_be_bytes())
+    }
+    bytes
+}
+
 struct ByAddress<T>(T);
 
 impl<T> hash::Hash for ByAddress<T>
@@ -96,32 +495,145 @@ pub(crate) struct InProgressPage<'a> {
     size: Size<CssPx>,
     operations: Vec<u8>,
     graphics_state: GraphicsState,
+    /// Pushed by `save_state`, popped by `restore_state`
+    saved_graphics_states: Vec<GraphicsState>,
+    annotations: Vec<Object<'static>>,
+    trim_and_bleed_boxes: Option<(Rect<CssPx>, Rect<CssPx>)>,
+    /// Pushed by `begin_structure_element`, popped by `end_structure_element`
+    structure_elements: Vec<StructureElement>,
+    /// Ended on this page, written with the page since they refer to it
+    ended_structure_elements: Vec<StructureElement>,
+    /// Structure elements begun inside forms, which are not tagged
+    untagged_structure_elements: usize,
+    /// The structure element of each marked-content identifier on this page
+    marked_content_parents: Vec<Object<'static>>,
+    /// Whether a marked-content sequence of the innermost structure element is open
+    in_marked_content: bool,
+    /// Nesting depth of `begin_repeated`
+    forms: usize,
+}
+
+/// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G13.2296397
+struct StructureElement {
+    kind: &'static str,
+    id: IndirectObjectId,
+    /// Another structure element, or the structure tree root
+    parent: IndirectObjectId,
+    /// Marked-content identifiers and references to child structure elements, in order
+    kids: Vec<Object<'static>>,
+}
+
+/// The state of the page outside of content recorded by `begin_repeated`
+/// The resource name of a Form XObject, see `InProgressPage::end_form`
+pub(crate) struct Form(String);
+
+pub(crate) struct RepeatedContent {
+    operations: Vec<u8>,
+    saved_graphics_states: Vec<GraphicsState>,
+    graphics_state: GraphicsState,
 }
 
 impl<'a> Drop for InProgressPage<'a> {
     fn drop(&mut self) {
-        let content_id = self
+        while !self.structure_elements.is_empty() {
+            self.end_structure_element()
+        }
+        self.restore_all_saved_states();
+        // Blank pages, or pages that only have repeated content, are often identical
+        let operations = std::mem::take(&mut self.operations);
+        let pdf = &mut self.doc.pdf;
+        let content_id = *self
             .doc
-            .pdf
-            .add_stream(dictionary! {}, self.operations.as_slice().into());
-        let page_id = self.doc.pdf.add_dictionary(dictionary! {
+            .content_streams
+            .entry(operations)
+            .or_insert_with_key(|operations| {
+                pdf.add_stream(dictionary! {}, operations.as_slice().into())
+            });
+        let corner = css_to_pdf().transform_point(&self.size.to_vector().to_point());
+        let media_box = array![0, 0, corner.x, corner.y];
+        let page = dictionary! {
             "Type" => "Page",
             "Parent" => PAGE_TREE_ID,
             "Contents" => content_id,
-            "MediaBox" => array![
-                0,
-                0,
-                self.size.width * CSS_TO_PDF_SCALE_X,
-                self.size.height * CSS_TO_PDF_SCALE_Y,
-            ],
+            "MediaBox" => media_box,
+        };
+        let annotations = key_value_pairs! {
+            "Annots" => &*self.annotations,
+        };
+        let page_with_annotations = Dictionary {
+            prev: Some(&page),
+            pairs: if self.annotations.is_empty() {
+                &[]
+            } else {
+                annotations
+            },
+        };
+        let struct_parents = key_value_pairs! {
+            "StructParents" => self.doc.parent_tree.len(),
+        };
+        let page_with_struct_parents = Dictionary {
+            prev: Some(&page_with_annotations),
+            pairs: if self.marked_content_parents.is_empty() {
+                &[]
+            } else {
+                struct_parents
+            },
+        };
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G15.1942818
+        let (trim, bleed) = self
+            .trim_and_bleed_boxes
+            .unwrap_or_else(|| (Rect::zero(), Rect::zero()));
+        let trim_box = in_default_user_space(&trim);
+        let bleed_box = in_default_user_space(&bleed);
+        let boxes = key_value_pairs! {
+            "TrimBox" => &trim_box[..],
+            "BleedBox" => &bleed_box[..],
+        };
+        if !self.marked_content_parents.is_empty() {
+            let mut parents = Vec::new();
+            Object::Array(&self.marked_content_parents)
+                .write(&mut parents)
+                .unwrap();
+            let id = self.doc.pdf.add_indirect_object(parents);
+            self.doc.parent_tree.push(id);
+        }
+        let page_id = self.doc.pdf.add_dictionary(Dictionary {
+            prev: Some(&page_with_struct_parents),
+            pairs: if self.trim_and_bleed_boxes.is_some() {
+                boxes
+            } else {
+                &[]
+            },
         });
         self.doc.page_ids.push(page_id.into());
+        for element in &self.ended_structure_elements {
+            let dictionary = dictionary! {
+                "Type" => "StructElem",
+                "S" => element.kind,
+                "P" => element.parent,
+                "Pg" => page_id,
+                "K" => &*element.kids,
+            };
+            self.doc.pdf.set_dictionary(element.id, dictionary)
+        }
     }
 }
 
+#[derive(Clone, PartialEq)]
 struct GraphicsState {
     non_stroking_color_rgb: (f32, f32, f32),
+    stroking_color_rgb: (f32, f32, f32),
     alpha: f32,
+    stroking_alpha: f32,
+    line_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    dash_pattern: (Vec<f32>, f32),
+    text_rendering_mode: TextRenderingMode,
+    /// Applied to colors as they are set, and to the pixels of images
+    color_matrix: ColorMatrix,
+    blend_mode: BlendMode,
 }
 
 macro_rules! op {
@@ -146,31 +658,126 @@ impl<'a> InProgressPage<'a> {
             doc,
             size,
             operations: Vec::new(),
+            annotations: Vec::new(),
+            trim_and_bleed_boxes: None,
+            structure_elements: Vec::new(),
+            ended_structure_elements: Vec::new(),
+            untagged_structure_elements: 0,
+            marked_content_parents: Vec::new(),
+            in_marked_content: false,
+            forms: 0,
             // Initial state:
+            saved_graphics_states: Vec::new(),
             graphics_state: GraphicsState {
                 non_stroking_color_rgb: (0., 0., 0.), // Black
+                stroking_color_rgb: (0., 0., 0.),     // Black
                 alpha: 1.,                            // Fully opaque
+                stroking_alpha: 1.,                   // Fully opaque
+                line_width: 1.,
+                line_cap: LineCap::Butt,
+                line_join: LineJoin::Miter,
+                miter_limit: 10.,
+                dash_pattern: (Vec::new(), 0.), // Solid
+                text_rendering_mode: TextRenderingMode::Fill,
+                color_matrix: ColorMatrix::IDENTITY,
+                blend_mode: BlendMode::Normal,
             },
         };
+        let t = css_to_pdf();
         op!(
             page,
             CURRENT_TRANSFORMATION_MATRIX,
-            CSS_TO_PDF_SCALE_X,
-            0,
-            0,
-            CSS_TO_PDF_SCALE_Y,
-            0,
-            0
+            t.m11,
+            t.m12,
+            t.m21,
+            t.m22,
+            t.m31,
+            t.m32
         );
         page
     }
 
-    pub(crate) fn set_color(&mut self, &RGBA(r, g, b, a): &RGBA) {
+    pub(crate) fn set_color(&mut self, rgba: &RGBA) {
+        let RGBA(r, g, b, a) = self.graphics_state.color_matrix.apply(*rgba);
         if self.graphics_state.non_stroking_color_rgb != (r, g, b) {
             self.graphics_state.non_stroking_color_rgb = (r, g, b);
             op!(self, NON_STROKING_RGB_COLOR, r, g, b);
         }
-        self.set_alpha(a)
+        let stroking_alpha = self.graphics_state.stroking_alpha;
+        self.set_alpha(a, stroking_alpha)
+    }
+
+    pub(crate) fn set_stroke_color(&mut self, rgba: &RGBA) {
+        let RGBA(r, g, b, a) = self.graphics_state.color_matrix.apply(*rgba);
+        if self.graphics_state.stroking_color_rgb != (r, g, b) {
+            self.graphics_state.stroking_color_rgb = (r, g, b);
+            op!(self, STROKING_RGB_COLOR, r, g, b);
+        }
+        let fill_alpha = self.graphics_state.alpha;
+        self.set_alpha(fill_alpha, a)
+    }
+
+    pub(crate) fn set_line_width(&mut self, width: Length<CssPx>) {
+        if self.graphics_state.line_width != width.0 {
+            self.graphics_state.line_width = width.0;
+            op!(self, LINE_WIDTH, width.0);
+        }
+    }
+
+    pub(crate) fn set_line_cap(&mut self, cap: LineCap) {
+        if self.graphics_state.line_cap != cap {
+            self.graphics_state.line_cap = cap;
+            // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.3793795
+            let operand = match cap {
+                LineCap::Butt => 0,
+                LineCap::Round => 1,
+                LineCap::Square => 2,
+            };
+            op!(self, LINE_CAP, operand);
+        }
+    }
+
+    pub(crate) fn set_line_join(&mut self, join: LineJoin) {
+        if self.graphics_state.line_join != join {
+            self.graphics_state.line_join = join;
+            let operand = match join {
+                LineJoin::Miter => 0,
+                LineJoin::Round => 1,
+                LineJoin::Bevel => 2,
+            };
+            op!(self, LINE_JOIN, operand);
+        }
+    }
+
+    pub(crate) fn set_miter_limit(&mut self, limit: f32) {
+        if self.graphics_state.miter_limit != limit {
+            self.graphics_state.miter_limit = limit;
+            op!(self, MITER_LIMIT, limit);
+        }
+    }
+
+    pub(crate) fn set_dash_pattern(&mut self, dashes: &[Length<CssPx>], phase: Length<CssPx>) {
+        let dashes: Vec<f32> = dashes.iter().map(|dash| dash.0).collect();
+        if self.graphics_state.dash_pattern.0 != dashes
+            || self.graphics_state.dash_pattern.1 != phase.0
+        {
+            let array: Vec<Object> = dashes.iter().map(|&dash| dash.into()).collect();
+            op!(self, DASH_PATTERN, &*array, phase.0);
+            self.graphics_state.dash_pattern = (dashes, phase.0);
+        }
+    }
+
+    pub(crate) fn set_text_rendering_mode(&mut self, mode: TextRenderingMode) {
+        if self.graphics_state.text_rendering_mode != mode {
+            self.graphics_state.text_rendering_mode = mode;
+            // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G8.1694883
+            let operand = match mode {
+                TextRenderingMode::Fill => 0,
+                TextRenderingMode::Stroke => 1,
+                TextRenderingMode::FillThenStroke => 2,
+            };
+            op!(self, TEXT_RENDERING_MODE, operand);
+        }
     }
 
     pub(crate) fn paint_rectangle(&mut self, rect: &Rect<CssPx>) {
@@ -185,6 +792,361 @@ impl<'a> InProgressPage<'a> {
         op!(self, FILL);
     }
 
+    pub(crate) fn stroke_line(&mut self, from: &Point<CssPx>, to: &Point<CssPx>) {
+        self.move_to(from);
+        self.line_to(to);
+        self.stroke();
+    }
+
+    pub(crate) fn move_to(&mut self, point: &Point<CssPx>) {
+        op!(self, MOVE_TO, point.x, point.y);
+    }
+
+    pub(crate) fn line_to(&mut self, point: &Point<CssPx>) {
+        op!(self, LINE_TO, point.x, point.y);
+    }
+
+    pub(crate) fn curve_to(&mut self, c1: &Point<CssPx>, c2: &Point<CssPx>, to: &Point<CssPx>) {
+        op!(self, CURVE_TO, c1.x, c1.y, c2.x, c2.y, to.x, to.y);
+    }
+
+    pub(crate) fn close_path(&mut self) {
+        op!(self, CLOSE_PATH);
+    }
+
+    pub(crate) fn fill(&mut self, rule: FillRule) {
+        match rule {
+            FillRule::NonZero => op!(self, FILL),
+            FillRule::EvenOdd => op!(self, FILL_EVEN_ODD),
+        }
+    }
+
+    pub(crate) fn stroke(&mut self) {
+        op!(self, STROKE);
+    }
+
+    pub(crate) fn clip(&mut self, rule: FillRule) {
+        // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.3987145
+        match rule {
+            FillRule::NonZero => op!(self, CLIP),
+            FillRule::EvenOdd => op!(self, CLIP_EVEN_ODD),
+        }
+        op!(self, END_PATH);
+    }
+
+    /// Content streams must have balanced `q` and `Q` operators
+    fn restore_all_saved_states(&mut self) {
+        for _ in self.saved_graphics_states.drain(..) {
+            self.operations.extend(RESTORE_STATE.as_bytes());
+            self.operations.push(b'\n');
+        }
+    }
+
+    /// Start recording content to be painted with `end_repeated`.
+    pub(crate) fn begin_repeated(&mut self) -> RepeatedContent {
+        // Marked content in forms would need a `StructParents` entry for each form,
+        // which could then not be shared.
+        self.end_marked_content();
+        self.forms += 1;
+        RepeatedContent {
+            operations: std::mem::take(&mut self.operations),
+            saved_graphics_states: std::mem::take(&mut self.saved_graphics_states),
+            graphics_state: self.graphics_state.clone(),
+        }
+    }
+
+    /// Paint the content recorded since `begin_repeated` as a Form XObject,
+    /// reusing an earlier one with the same content.
+    pub(crate) fn end_repeated(&mut self, outer: RepeatedContent) {
+        let form = self.end_form(outer);
+        self.paint_form(&form)
+    }
+
+    /// Paint a form returned by `end_form`, from the graphics state it was recorded in.
+    pub(crate) fn paint_form(&mut self, form: &Form) {
+        op!(self, PAINT_XOBJECT, &*form.0);
+    }
+
+    /// Make a Form XObject of the content recorded since `begin_repeated`,
+    /// reusing an earlier one with the same content.
+    ///
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.3794210
+    ///
+    /// The form inherits the graphics state of the page where it is painted,
+    /// which the recorded operators rely on by skipping redundant changes.
+    /// So forms are only shared when painted from the same (tracked) graphics state.
+    /// Painting one restores the graphics state afterwards, as `Q` would.
+    pub(crate) fn end_form(&mut self, outer: RepeatedContent) -> Form {
+        self.restore_all_saved_states();
+        let content = std::mem::replace(&mut self.operations, outer.operations);
+        self.saved_graphics_states = outer.saved_graphics_states;
+        self.graphics_state = outer.graphics_state;
+        self.forms -= 1;
+        self.begin_marked_content();
+        let doc = &mut *self.doc;
+        let state = &self.graphics_state;
+        let existing = doc
+            .repeated_contents
+            .get(&content)
+            .and_then(|forms| forms.iter().find(|(s, _)| s == state))
+            .map(|(_, pdf_key)| pdf_key.clone());
+        let pdf_key = match existing {
+            Some(pdf_key) => pdf_key,
+            None => {
+                // FIXME: this relies on the deprecated lookup of the page’s resources
+                // by forms that don’t have a `Resources` entry.
+                // Resources are only known at the end, when they are written to the page tree.
+                let bbox = array![0, 0, self.size.width, self.size.height];
+                let form_dict = dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => bbox,
+                };
+                let id = doc.pdf.add_stream(form_dict, content.as_slice().into());
+                let pdf_key = format!("x{}", doc.xobject_resources.len());
+                doc.xobject_resources
+                    .push((pdf_key.clone().into_bytes(), id.into()));
+                doc.repeated_contents
+                    .entry(content)
+                    .or_default()
+                    .push((state.clone(), pdf_key.clone()));
+                pdf_key
+            }
+        };
+        Form(pdf_key)
+    }
+
+    /// Start recording content to be painted with `end_group`.
+    pub(crate) fn begin_group(&mut self) -> RepeatedContent {
+        let outer = self.begin_repeated();
+        // A transparency group starts with the default blend mode and alpha constants
+        // (PDF 32000-1:2008, section 11.6.6).
+        let state = &mut self.graphics_state;
+        state.alpha = 1.;
+        state.stroking_alpha = 1.;
+        state.blend_mode = BlendMode::Normal;
+        outer
+    }
+
+    /// Paint the content recorded since `begin_group` as an isolated transparency group
+    /// (PDF 32000-1:2008, section 11.6.6), a Form XObject with a `Group` entry.
+    /// Its bounding box `bbox` is in the current coordinate system.
+    pub(crate) fn end_group(
+        &mut self,
+        outer: RepeatedContent,
+        blend_mode: BlendMode,
+        bbox: &Rect<CssPx>,
+    ) {
+        self.restore_all_saved_states();
+        let content = std::mem::replace(&mut self.operations, outer.operations);
+        self.saved_graphics_states = outer.saved_graphics_states;
+        self.graphics_state = outer.graphics_state;
+        self.forms -= 1;
+        self.begin_marked_content();
+        let bbox = array![bbox.min_x(), bbox.min_y(), bbox.max_x(), bbox.max_y()];
+        let group = dictionary! {
+            "S" => "Transparency",
+            "I" => true,
+        };
+        let form_dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => bbox,
+            "Group" => group,
+        };
+        let id = self
+            .doc
+            .pdf
+            .add_stream(form_dict, content.as_slice().into());
+        let pdf_key = format!("x{}", self.doc.xobject_resources.len());
+        self.doc
+            .xobject_resources
+            .push((pdf_key.clone().into_bytes(), id.into()));
+        // The group is composited with the alpha constant, as well as the blend mode,
+        // of the graphics state it is painted in.
+        self.save_state();
+        self.set_alpha(1., 1.);
+        self.set_blend_mode(blend_mode);
+        op!(self, PAINT_XOBJECT, &*pdf_key);
+        self.restore_state();
+    }
+
+    pub(crate) fn save_state(&mut self) {
+        self.saved_graphics_states.push(self.graphics_state.clone());
+        op!(self, SAVE_STATE);
+    }
+
+    pub(crate) fn restore_state(&mut self) {
+        debug_assert!(
+            !self.saved_graphics_states.is_empty(),
+            "restore_state without a matching save_state"
+        );
+        if let Some(state) = self.saved_graphics_states.pop() {
+            self.graphics_state = state;
+            op!(self, RESTORE_STATE);
+        }
+    }
+
+    pub(crate) fn apply_color_matrix(&mut self, matrix: &ColorMatrix) {
+        let state = &mut self.graphics_state;
+        state.color_matrix = matrix.then(&state.color_matrix)
+    }
+
+    pub(crate) fn transform(&mut self, t: &Transform<CssPx, CssPx>) {
+        op!(
+            self,
+            CURRENT_TRANSFORMATION_MATRIX,
+            t.m11,
+            t.m12,
+            t.m21,
+            t.m22,
+            t.m31,
+            t.m32
+        );
+    }
+
+    pub(crate) fn paint_image(&mut self, image: &Image, rect: &Rect<CssPx>) {
+        let pdf_key = self.add_image(image, &rect.size);
+        self.paint_image_xobject(&pdf_key, rect);
+    }
+
+    pub(crate) fn paint_image_tiles(
+        &mut self,
+        image: &Image,
+        tile: &Rect<CssPx>,
+        area: &Rect<CssPx>,
+    ) {
+        let tile_size = tile.size;
+        // Also true for NaN
+        let is_empty = |size: Size<CssPx>| !(size.width > 0. && size.height > 0.);
+        if is_empty(tile_size) || is_empty(area.size) {
+            return;
+        }
+        let pdf_key = self.add_image(image, &tile_size);
+        self.save_state();
+        op!(
+            self,
+            RECTANGLE,
+            area.origin.x,
+            area.origin.y,
+            area.size.width,
+            area.size.height
+        );
+        self.clip(FillRule::NonZero);
+        // The first tile in each direction that reaches into `area`,
+        // and how many are needed from there to cover it
+        let first =
+            |origin: f32, start: f32, step: f32| origin + ((start - origin) / step).floor() * step;
+        let x = first(tile.origin.x, area.min_x(), tile_size.width);
+        let y = first(tile.origin.y, area.min_y(), tile_size.height);
+        let columns = ((area.max_x() - x) / tile_size.width).ceil() as usize;
+        let rows = ((area.max_y() - y) / tile_size.height).ceil() as usize;
+        for row in 0..rows {
+            for column in 0..columns {
+                let origin = point(
+                    x + column as f32 * tile_size.width,
+                    y + row as f32 * tile_size.height,
+                );
+                self.paint_image_xobject(&pdf_key, &Rect::new(origin, tile_size))
+            }
+        }
+        self.restore_state();
+    }
+
+    /// Add an image XObject to the document, for painting at `size`.
+    /// This also sets the alpha of the graphics state for painting it.
+    ///
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.3845384
+    fn add_image(&mut self, image: &Image, size: &Size<CssPx>) -> String {
+        let mut width = image.width;
+        let mut height = image.height;
+        let matrix = self.graphics_state.color_matrix;
+        let downsampled;
+        let filtered;
+        let (mut data, is_jpeg) = match image.data {
+            ImageData::Rgb8(samples) => {
+                // Checked in `Image::check`
+                debug_assert_eq!(samples.len(), width as usize * height as usize * 3);
+                // FIXME: this ignores `transform`, which could also change the painted size.
+                if let Some(dpi) = self.doc.max_image_resolution {
+                    let max_pixels =
+                        |length: f32| (length / PX_PER_INCH * dpi).ceil().max(1.) as u32;
+                    width = width.min(max_pixels(size.width));
+                    height = height.min(max_pixels(size.height));
+                }
+                if (width, height) == (image.width, image.height) {
+                    (samples, false)
+                } else {
+                    downsampled =
+                        downsample_rgb8(samples, image.width, image.height, width, height);
+                    (&downsampled[..], false)
+                }
+            }
+            // FIXME: downsample JPEG images too. This needs decoding them, and encoding again.
+            ImageData::Jpeg(file) => (file, true),
+        };
+        // FIXME: JPEG images keep their colors, since changing them needs decoding.
+        if !is_jpeg && !matrix.preserves_rgb() {
+            filtered = filter_rgb8(data, &matrix);
+            data = &filtered[..]
+        }
+        // Images are opaque, and have a single alpha in the graphics state.
+        let alpha = matrix.apply(RGBA(0., 0., 0., 1.)).3;
+        let stroking_alpha = self.graphics_state.stroking_alpha;
+        self.set_alpha(alpha, stroking_alpha);
+        let hash = {
+            use std::hash::{BuildHasher, Hash, Hasher};
+            let mut hasher = self.doc.image_hasher.build_hasher();
+            (width, height, is_jpeg, data).hash(&mut hasher);
+            hasher.finish()
+        };
+        if let Some(pdf_key) = self.doc.images.get(&hash) {
+            return pdf_key.clone();
+        }
+        let image_dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width as usize,
+            "Height" => height as usize,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        };
+        let dct_decode = key_value_pairs! {
+            "Filter" => "DCTDecode",
+        };
+        let id = self.doc.pdf.add_stream(
+            Dictionary {
+                prev: Some(&image_dict),
+                pairs: if is_jpeg { dct_decode } else { &[] },
+            },
+            data.into(),
+        );
+        let pdf_key = format!("i{}", self.doc.xobject_resources.len());
+        self.doc
+            .xobject_resources
+            .push((pdf_key.clone().into_bytes(), id.into()));
+        self.doc.images.insert(hash, pdf_key.clone());
+        pdf_key
+    }
+
+    fn paint_image_xobject(&mut self, pdf_key: &str, rect: &Rect<CssPx>) {
+        // Images are painted in a unit square, whose top edge is at y = 1
+        // while the page’s y axis points downwards.
+        self.save_state();
+        self.transform(&Transform::row_major(
+            rect.size.width,
+            0.,
+            0.,
+            -rect.size.height,
+            rect.origin.x,
+            rect.max_y(),
+        ));
+        op!(self, PAINT_XOBJECT, pdf_key);
+        self.restore_state();
+    }
+
+    /// FIXME: each run is its own text object that selects its font.
+    /// Consecutive runs in the same font could share one, with `Td` to move between them.
     pub(crate) fn show_text(&mut self, text: &TextRun) -> Result<(), FontError> {
         let TextRun {
             ref segment,
@@ -239,17 +1201,227 @@ impl<'a> InProgressPage<'a> {
         Ok(())
     }
 
-    fn set_alpha(&mut self, alpha: f32) {
+    /// Content painted until the matching `end_structure_element` belongs to a new element,
+    /// a child of the innermost open one or of the structure tree root.
+    pub(crate) fn begin_structure_element(&mut self, kind: &'static str) {
+        if self.forms > 0 {
+            self.untagged_structure_elements += 1;
+            return;
+        }
+        self.end_marked_content();
+        let doc = &mut *self.doc;
+        let id = doc.pdf.reserve_id();
+        let parent = match self.structure_elements.last_mut() {
+            Some(parent) => {
+                parent.kids.push(id.into());
+                parent.id
+            }
+            None => {
+                doc.structure_tree_kids.push(id.into());
+                match doc.structure_tree_root {
+                    Some(root) => root,
+                    None => {
+                        let root = doc.pdf.reserve_id();
+                        doc.structure_tree_root = Some(root);
+                        root
+                    }
+                }
+            }
+        };
+        self.structure_elements.push(StructureElement {
+            kind,
+            id,
+            parent,
+            kids: Vec::new(),
+        });
+        self.begin_marked_content()
+    }
+
+    pub(crate) fn end_structure_element(&mut self) {
+        if self.untagged_structure_elements > 0 {
+            self.untagged_structure_elements -= 1;
+            return;
+        }
+        self.end_marked_content();
+        let element = self
+            .structure_elements
+            .pop()
+            .expect("end_structure_element without begin_structure_element");
+        self.ended_structure_elements.push(element);
+        // The rest of the parent’s content
+        self.begin_marked_content()
+    }
+
+    /// Start a marked-content sequence of the innermost structure element, if any
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G13.2296006
+    fn begin_marked_content(&mut self) {
+        if self.forms > 0 {
+            return;
+        }
+        if let Some(element) = self.structure_elements.last_mut() {
+            let mcid = self.marked_content_parents.len();
+            self.marked_content_parents.push(element.id.into());
+            element.kids.push(mcid.into());
+            let kind = element.kind;
+            let properties = dictionary! {
+                "MCID" => mcid,
+            };
+            op!(self, BEGIN_MARKED_CONTENT_WITH_PROPERTIES, kind, properties);
+            self.in_marked_content = true
+        }
+    }
+
+    fn end_marked_content(&mut self) {
+        if self.in_marked_content {
+            op!(self, END_MARKED_CONTENT);
+            self.in_marked_content = false
+        }
+    }
+
+    pub(crate) fn set_trim_and_bleed_boxes(&mut self, trim: &Rect<CssPx>, bleed: &Rect<CssPx>) {
+        self.trim_and_bleed_boxes = Some((*trim, *bleed))
+    }
+
+    /// Horizontal and vertical lines aligned with the edges of `trim`
+    /// near each of its corners, outside of `bleed`.
+    pub(crate) fn paint_crop_marks(&mut self, trim: &Rect<CssPx>, bleed: &Rect<CssPx>) {
+        for &x in &[trim.min_x(), trim.max_x()] {
+            self.paint_vertical_mark(x, bleed.min_y() - MARK_LENGTH, MARK_LENGTH);
+            self.paint_vertical_mark(x, bleed.max_y(), MARK_LENGTH);
+        }
+        for &y in &[trim.min_y(), trim.max_y()] {
+            self.paint_horizontal_mark(bleed.min_x() - MARK_LENGTH, y, MARK_LENGTH);
+            self.paint_horizontal_mark(bleed.max_x(), y, MARK_LENGTH);
+        }
+    }
+
+    /// Crosses centered on each edge of `trim`, outside of `bleed`, for aligning separations.
+    pub(crate) fn paint_cross_marks(&mut self, trim: &Rect<CssPx>, bleed: &Rect<CssPx>) {
+        let half = MARK_LENGTH / 2.;
+        let center = trim.center();
+        for &y in &[bleed.min_y() - half, bleed.max_y() + half] {
+            self.paint_horizontal_mark(center.x - half, y, MARK_LENGTH);
+            self.paint_vertical_mark(center.x, y - half, MARK_LENGTH);
+        }
+        for &x in &[bleed.min_x() - half, bleed.max_x() + half] {
+            self.paint_horizontal_mark(x - half, center.y, MARK_LENGTH);
+            self.paint_vertical_mark(x, center.y - half, MARK_LENGTH);
+        }
+    }
+
+    fn paint_horizontal_mark(&mut self, x: f32, y: f32, length: f32) {
+        self.paint_rectangle(&rect(x, y - MARK_WIDTH / 2., length, MARK_WIDTH))
+    }
+
+    fn paint_vertical_mark(&mut self, x: f32, y: f32, length: f32) {
+        self.paint_rectangle(&rect(x - MARK_WIDTH / 2., y, MARK_WIDTH, length))
+    }
+
+    /// A field dictionary merged with its widget annotation
+    /// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G11.1951345
+    pub(crate) fn add_form_field(&mut self, field: &FormField) {
+        // PDF 32000-1:2008, section 12.7.3.2: periods separate the partial names
+        // of nested fields, and fields with the same fully qualified name are the same field.
+        let base_name = field.name.replace('.', "_");
+        let mut unique_name = base_name.clone();
+        let mut suffix = 1;
+        while self.doc.form_field_names.contains(&unique_name) {
+            suffix += 1;
+            unique_name = format!("{}_{}", base_name, suffix);
+        }
+        let name = text_string(&unique_name);
+        self.doc.form_field_names.insert(unique_name);
+        let rect = in_default_user_space(&field.rect);
+        let widget = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Rect" => &rect[..],
+            // Print
+            "F" => 4,
+            "T" => Object::HexString(&name),
+        };
+        let pdf = &mut self.doc.pdf;
+        let id = match field.kind {
+            FormFieldKind::Text { value, multiline } => pdf.add_dictionary(linked_dictionary! {
+                &widget,
+                "FT" => "Tx",
+                "Ff" => if multiline { 1 << 12 } else { 0 },
+                "V" => Object::HexString(&text_string(value)),
+                "DA" => Object::LiteralString(DEFAULT_APPEARANCE),
+            }),
+            FormFieldKind::Checkbox { checked } => {
+                let state = if checked { "Yes" } else { "Off" };
+                pdf.add_dictionary(linked_dictionary! {
+                    &widget,
+                    "FT" => "Btn",
+                    "V" => state,
+                    "AS" => state,
+                })
+            }
+            FormFieldKind::Choice { options, selected } => {
+                let options: Vec<_> = options.iter().map(|option| text_string(option)).collect();
+                let option_objects: Vec<_> = options.iter().map(|o| Object::HexString(o)).collect();
+                let choice = linked_dictionary! {
+                    &widget,
+                    "FT" => "Ch",
+                    // Combo box
+                    "Ff" => 1 << 17,
+                    "Opt" => &*option_objects,
+                    "DA" => Object::LiteralString(DEFAULT_APPEARANCE),
+                };
+                match selected.and_then(|index| options.get(index)) {
+                    Some(value) => pdf.add_dictionary(linked_dictionary! {
+                        &choice,
+                        "V" => Object::HexString(value),
+                    }),
+                    None => pdf.add_dictionary(choice),
+                }
+            }
+            FormFieldKind::Signature { reserved_bytes } => {
+                self.doc.has_signature_fields = true;
+                let signature = linked_dictionary! {
+                    &widget,
+                    "FT" => "Sig",
+                };
+                match reserved_bytes {
+                    // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G11.2107384
+                    Some(reserved_bytes) => {
+                        let contents = vec![0; reserved_bytes];
+                        let value = pdf.add_dictionary(dictionary! {
+                            "Type" => "Sig",
+                            "Filter" => "Adobe.PPKLite",
+                            "SubFilter" => "adbe.pkcs7.detached",
+                            "ByteRange" => Object::SignatureByteRangePlaceholder,
+                            "Contents" => Object::HexString(&contents),
+                        });
+                        pdf.add_dictionary(linked_dictionary! {
+                            &signature,
+                            "V" => value,
+                        })
+                    }
+                    None => pdf.add_dictionary(signature),
+                }
+            }
+        };
+        self.annotations.push(id.into());
+        self.doc.form_fields.push(id.into());
+    }
+
+    fn set_alpha(&mut self, alpha: f32, stroking_alpha: f32) {
         let alpha = alpha.max(0.).min(1.);
-        if alpha != self.graphics_state.alpha {
-            self.graphics_state.alpha = alpha;
+        let stroking_alpha = stroking_alpha.max(0.).min(1.);
+        let state = &mut self.graphics_state;
+        if (alpha, stroking_alpha) != (state.alpha, state.stroking_alpha) {
+            state.alpha = alpha;
+            state.stroking_alpha = stroking_alpha;
 
             // Use u16 instead of f32 as a hash key because f32 does not implement Eq,
             // and to do some rounding in case float computation
             // produces very close but different values.
             //
             // Map 0.0 to 0, 1.0 to max
-            let hash_key = (alpha * (u16::max_value() as f32)) as u16;
+            let to_key = |alpha: f32| (alpha * (u16::max_value() as f32)) as u16;
+            let hash_key = (to_key(alpha), to_key(stroking_alpha));
 
             let next_id = self.doc.alpha_states.len();
             let states = &mut self.doc.extended_graphics_states;
@@ -257,7 +1429,10 @@ impl<'a> InProgressPage<'a> {
                 let pdf_key = format!("a{}", next_id);
                 states.push((
                     pdf_key.clone().into_bytes(),
-                    Object::GraphicsStateDictionaryAlpha(alpha),
+                    Object::GraphicsStateDictionaryAlpha {
+                        non_stroking: alpha,
+                        stroking: stroking_alpha,
+                    },
                 ));
                 pdf_key
             });
@@ -265,6 +1440,28 @@ impl<'a> InProgressPage<'a> {
         }
     }
 
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        if self.graphics_state.blend_mode == blend_mode {
+            return;
+        }
+        self.graphics_state.blend_mode = blend_mode;
+        let next_id = self.doc.blend_mode_states.len();
+        let states = &mut self.doc.extended_graphics_states;
+        let pdf_key = self
+            .doc
+            .blend_mode_states
+            .entry(blend_mode)
+            .or_insert_with(|| {
+                let pdf_key = format!("b{}", next_id);
+                states.push((
+                    pdf_key.clone().into_bytes(),
+                    Object::GraphicsStateDictionaryBlendMode(blend_mode_name(blend_mode)),
+                ));
+                pdf_key
+            });
+        op!(self, EXTENDED_GRAPHICS_STATE, &*pdf_key);
+    }
+
     fn add_font(&mut self, font: &Arc<Font>) -> Result<String, FontError> {
         let next_id = self.doc.fonts.len();
         let vacant_entry = match self.doc.fonts.entry(ByAddress(font.clone())) {
@@ -312,34 +1509,40 @@ impl<'a> InProgressPage<'a> {
             endcodespacerange\n\
         "
         .to_vec();
+        // The text of each glyph: characters from the font’s character map,
+        // then the substitutes of OpenType features such as ligatures,
+        // which are not in the character map but appear in text with these features.
+        let mut glyph_text = Vec::new();
+        font.each_code_point(|ch, GlyphId(glyph_id)| glyph_text.push((glyph_id, ch.to_string())))?;
+        #[cfg(feature = "html")]
         {
-            let mut write_bfchar = |chars: &[char], glyph_ids: &[u16]| {
-                write!(to_unicode_cmap, "{} beginbfchar\n", chars.len()).unwrap();
-                for (ch, glyph_id) in chars.iter().zip(glyph_ids) {
-                    write!(to_unicode_cmap, "<{:04x}> <", glyph_id).unwrap();
-                    for code_unit in ch.encode_utf16(&mut [0, 0]) {
-                        write!(to_unicode_cmap, "{:04x}", code_unit).unwrap()
-                    }
-                    to_unicode_cmap.extend(b">\n");
+            // A malformed GSUB table only leaves substitutes without text.
+            if let Ok(substitutions) = Substitutions::parse_all(font) {
+                let mut by_glyph = HashMap::new();
+                for (glyph_id, text) in &glyph_text {
+                    by_glyph
+                        .entry(GlyphId(*glyph_id))
+                        .or_insert_with(|| text.clone());
                 }
-                to_unicode_cmap.extend(b"endbfchar\n");
-            };
-            // Max 100 entries per beginbfchar operator
-            let mut chars = ['\0'; 100];
-            let mut glyph_ids = [0_u16; 100];
-            let mut i = 0;
-            font.each_code_point(|ch, GlyphId(glyph_id)| {
-                if i >= 100 {
-                    write_bfchar(&chars, &glyph_ids);
-                    i = 0
+                glyph_text.extend(
+                    substitutions
+                        .substitute_text(&by_glyph)
+                        .into_iter()
+                        .map(|(GlyphId(glyph_id), text)| (glyph_id, text)),
+                )
+            }
+        }
+        // Max 100 entries per beginbfchar operator
+        for entries in glyph_text.chunks(100) {
+            write!(to_unicode_cmap, "{} beginbfchar\n", entries.len()).unwrap();
+            for (glyph_id, text) in entries {
+                write!(to_unicode_cmap, "<{:04x}> <", glyph_id).unwrap();
+                for code_unit in text.encode_utf16() {
+                    write!(to_unicode_cmap, "{:04x}", code_unit).unwrap()
                 }
-                chars[i] = ch;
-                glyph_ids[i] = glyph_id;
-                i += 1;
-            })?;
-            if i > 0 {
-                write_bfchar(&chars[..i], &glyph_ids[..i])
+                to_unicode_cmap.extend(b">\n");
             }
+            to_unicode_cmap.extend(b"endbfchar\n");
         }
         to_unicode_cmap.extend(
             b"\
@@ -412,14 +1615,40 @@ operators! {
     // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.3793795
     CURRENT_TRANSFORMATION_MATRIX = "cm",
     EXTENDED_GRAPHICS_STATE = "gs",
+    LINE_WIDTH = "w",
+    LINE_CAP = "J",
+    LINE_JOIN = "j",
+    MITER_LIMIT = "M",
+    DASH_PATTERN = "d",
+    SAVE_STATE = "q",
+    RESTORE_STATE = "Q",
 
     // Path Construction and Painting
     // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.1849957
+    MOVE_TO = "m",
+    LINE_TO = "l",
+    CURVE_TO = "c",
+    CLOSE_PATH = "h",
     RECTANGLE = "re",
+    STROKE = "S",
     FILL = "f",
+    FILL_EVEN_ODD = "f*",
+    END_PATH = "n",
+    CLIP = "W",
+    CLIP_EVEN_ODD = "W*",
+
+    // XObjects
+    // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.1852235
+    PAINT_XOBJECT = "Do",
+
+    // Marked Content
+    // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G13.2296006
+    BEGIN_MARKED_CONTENT_WITH_PROPERTIES = "BDC",
+    END_MARKED_CONTENT = "EMC",
 
     // Colour Spaces
     // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G7.1850197
+    STROKING_RGB_COLOR = "RG",
     NON_STROKING_RGB_COLOR = "rg",
 
     // Text
@@ -427,6 +1656,14 @@ operators! {
     BEGIN_TEXT = "BT",
     END_TEXT = "ET",
     TEXT_FONT_AND_SIZE = "Tf",
+    TEXT_RENDERING_MODE = "Tr",
     TEXT_MATRIX = "Tm",
     SHOW_TEXT = "Tj",
 }
+
+#[test]
+fn css_px_to_pdf_pt() {
+    // 96 CSS `px` and 72 PDF `pt` are one inch, with the Y axis flipped
+    let corner = css_to_pdf().transform_point(&point(96., 48.));
+    assert_eq!((corner.x, corner.y), (72., -36.));
+}
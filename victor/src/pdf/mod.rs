@@ -1,6 +1,7 @@
-use self::convert::{InProgressDoc, InProgressPage};
+use self::convert::{Form, InProgressDoc, InProgressPage};
 use crate::fonts::FontError;
-use crate::primitives::{CssPx, Rect, Size, TextRun, RGBA};
+use crate::primitives::{BlendMode, ColorMatrix, TextRenderingMode, TextRun, Transform, RGBA};
+use crate::primitives::{CssPx, FillRule, Length, LineCap, LineJoin, Point, Rect, Size};
 use std::fs;
 use std::io::{self, Write};
 use std::path;
@@ -10,14 +11,156 @@ mod object;
 mod convert;
 mod syntax;
 
+/// How far printer marks extend outside of the bleed box, in CSS `px`,
+/// see `Page::paint_crop_marks` and `Page::paint_cross_marks`.
+pub const PRINTER_MARK_LENGTH: f32 = convert::MARK_LENGTH;
+
 pub struct Document {
     in_progress: InProgressDoc,
 }
 
+/// A page being written, with its graphics state.
 pub struct Page<'doc> {
     in_progress: InProgressPage<'doc>,
 }
 
+/// A raster image, see `Page::paint_image`.
+pub struct Image<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub data: ImageData<'a>,
+}
+
+pub enum ImageData<'a> {
+    /// 8-bit red, green, and blue samples for each pixel, row by row from the top
+    Rgb8(&'a [u8]),
+    /// A JPEG file with RGB color, embedded as-is
+    Jpeg(&'a [u8]),
+}
+
+impl Image<'_> {
+    fn check(&self) -> Result<(), InvalidImage> {
+        if let ImageData::Rgb8(samples) = self.data {
+            let expected_len = (self.width as usize)
+                .checked_mul(self.height as usize)
+                .and_then(|pixels| pixels.checked_mul(3));
+            if expected_len != Some(samples.len()) {
+                return Err(InvalidImage);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error of `Page::paint_image` for `ImageData::Rgb8` samples
+/// that are not 3 bytes for each of `width` × `height` pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidImage;
+
+impl std::fmt::Display for InvalidImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("expected 3 bytes per pixel")
+    }
+}
+
+impl std::error::Error for InvalidImage {}
+
+/// An interactive form field, see `Page::add_form_field`.
+pub struct FormField<'a> {
+    /// Identifies the field in the form’s data.
+    ///
+    /// A name already used in the document gets a `_2`, `_3`, … suffix,
+    /// and periods are replaced with underscores:
+    /// in PDF they separate the parts of the names of nested fields.
+    pub name: &'a str,
+    pub rect: Rect<CssPx>,
+    pub kind: FormFieldKind<'a>,
+}
+
+pub enum FormFieldKind<'a> {
+    Text {
+        value: &'a str,
+        multiline: bool,
+    },
+    Checkbox {
+        checked: bool,
+    },
+    /// A drop-down list
+    Choice {
+        options: &'a [&'a str],
+        /// An index in `options`
+        selected: Option<usize>,
+    },
+    /// A field for an external tool to sign the document.
+    ///
+    /// With `reserved_bytes: None` the field is empty,
+    /// and signing tools add a signature dictionary in an incremental update.
+    /// With `Some(n)`, the field already has a signature dictionary whose `/Contents`
+    /// is `n` zero bytes and whose `/ByteRange` is `[0 9999999999 9999999999 9999999999]`:
+    /// tools that sign in place overwrite both without changing the length of the file.
+    Signature {
+        reserved_bytes: Option<usize>,
+    },
+}
+
+/// How PDF readers number a range of pages, see `Document::add_page_label_range`.
+///
+/// For example, front matter numbered i, ii, iii, … followed by a body numbered 1, 2, 3, …
+/// is two ranges starting at page index 0 and at the index of the first body page.
+pub struct PageLabelRange<'a> {
+    /// The index of the first page of the range, starting at 0.
+    /// The range extends to the start of the next range, or to the end of the document.
+    pub first_page: usize,
+    /// `None` for labels that are only `prefix`
+    pub style: Option<PageNumberStyle>,
+    pub prefix: &'a str,
+    /// The number of the first page of the range, at least 1.
+    pub start: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PageNumberStyle {
+    /// 1, 2, 3, …
+    Decimal,
+    /// I, II, III, …
+    UpperRoman,
+    /// i, ii, iii, …
+    LowerRoman,
+    /// A, B, C, … Z, AA, BB, …
+    UpperAlpha,
+    /// a, b, c, … z, aa, bb, …
+    LowerAlpha,
+}
+
+/// A file to embed in a PDF document, see `Document::attach_file`.
+pub struct AttachedFile<'a> {
+    /// The file name shown to users, for example `factur-x.xml`
+    pub name: &'a str,
+    pub contents: &'a [u8],
+    /// For example `text/xml`
+    pub mime_type: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub relationship: FileRelationship,
+}
+
+/// How an attached file relates to the content of the document.
+///
+/// This is the `AFRelationship` key of PDF 2.0 and PDF/A-3,
+/// which some formats require. For example, ZUGFeRD invoices use `Alternative`
+/// for the XML version of the invoice.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileRelationship {
+    /// The original source of the document, such as its HTML
+    Source,
+    /// Data used to derive a visual presentation in the document, such as a table
+    Data,
+    /// An alternative representation of the content
+    Alternative,
+    /// Supplemental information that is not an alternative representation
+    Supplement,
+    Unspecified,
+}
+
 impl Document {
     pub fn new() -> Self {
         Document {
@@ -31,6 +174,33 @@ impl Document {
         }
     }
 
+    /// Set how PDF readers label pages, starting at `range.first_page`.
+    ///
+    /// Pages before the first range are labeled with their number, starting at 1.
+    /// Adding another range with the same first page replaces it.
+    ///
+    /// FIXME: generate this from CSS when `@page` rules and page counters are supported.
+    pub fn add_page_label_range(&mut self, range: &PageLabelRange) {
+        self.in_progress.add_page_label_range(range)
+    }
+
+    /// Embed a file in this document, listed in the attachments of PDF readers.
+    ///
+    /// Attaching another file with the same name replaces it in that list.
+    pub fn attach_file(&mut self, file: &AttachedFile) {
+        self.in_progress.attach_file(file)
+    }
+
+    /// Downsample raster images painted after this, so that they have at most
+    /// `dots_per_inch` pixels per inch at the size they are painted at.
+    /// For example, 300 is often enough for print.
+    ///
+    /// The default is `None`: images are embedded with all of their pixels.
+    /// JPEG images are always embedded as-is.
+    pub fn set_max_image_resolution(&mut self, dots_per_inch: Option<f32>) {
+        self.in_progress.max_image_resolution = dots_per_inch
+    }
+
     /// Encode this document to PDF and write it into the file with the given name.
     pub fn write_to_pdf_file<P: AsRef<path::Path>>(&self, filename: P) -> Result<(), io::Error> {
         self.write_to_pdf(&mut io::BufWriter::new(fs::File::create(filename)?))
@@ -57,18 +227,280 @@ impl Document {
 }
 
 impl<'doc> Page<'doc> {
+    /// Set the color used for filling paths and (depending on the text rendering mode) text.
+    ///
+    /// This does not change the stroking color or alpha, see `set_stroke_color`.
     pub fn set_color(&mut self, rgba: &RGBA) -> &mut Self {
         self.in_progress.set_color(rgba);
         self
     }
 
+    /// Set the color used for stroking paths and (depending on the text rendering mode) text.
+    ///
+    /// This does not change the fill color or alpha, so it can be called before or after `set_color`.
+    pub fn set_stroke_color(&mut self, rgba: &RGBA) -> &mut Self {
+        self.in_progress.set_stroke_color(rgba);
+        self
+    }
+
+    pub fn set_line_width(&mut self, width: Length<CssPx>) -> &mut Self {
+        self.in_progress.set_line_width(width);
+        self
+    }
+
+    pub fn set_line_cap(&mut self, cap: LineCap) -> &mut Self {
+        self.in_progress.set_line_cap(cap);
+        self
+    }
+
+    pub fn set_line_join(&mut self, join: LineJoin) -> &mut Self {
+        self.in_progress.set_line_join(join);
+        self
+    }
+
+    /// Set the ratio of miter length to line width
+    /// beyond which `LineJoin::Miter` joins are beveled instead. The default is 10.
+    pub fn set_miter_limit(&mut self, limit: f32) -> &mut Self {
+        self.in_progress.set_miter_limit(limit);
+        self
+    }
+
+    /// Set the lengths of alternating dashes and gaps for stroking,
+    /// starting `phase` into that pattern. An empty `dashes` strokes solid lines.
+    pub fn set_dash_pattern(
+        &mut self,
+        dashes: &[Length<CssPx>],
+        phase: Length<CssPx>,
+    ) -> &mut Self {
+        self.in_progress.set_dash_pattern(dashes, phase);
+        self
+    }
+
+    pub fn set_text_rendering_mode(&mut self, mode: TextRenderingMode) -> &mut Self {
+        self.in_progress.set_text_rendering_mode(mode);
+        self
+    }
+
     pub fn paint_rectangle(&mut self, rect: &Rect<CssPx>) -> &mut Self {
         self.in_progress.paint_rectangle(rect);
         self
     }
 
+    /// Start a new subpath of the current path.
+    pub fn move_to(&mut self, point: &Point<CssPx>) -> &mut Self {
+        self.in_progress.move_to(point);
+        self
+    }
+
+    /// Add a straight line segment to the current subpath.
+    pub fn line_to(&mut self, point: &Point<CssPx>) -> &mut Self {
+        self.in_progress.line_to(point);
+        self
+    }
+
+    /// Add a cubic Bézier curve segment to the current subpath.
+    pub fn curve_to(
+        &mut self,
+        control_1: &Point<CssPx>,
+        control_2: &Point<CssPx>,
+        to: &Point<CssPx>,
+    ) -> &mut Self {
+        self.in_progress.curve_to(control_1, control_2, to);
+        self
+    }
+
+    /// Close the current subpath with a straight line to its start.
+    pub fn close_path(&mut self) -> &mut Self {
+        self.in_progress.close_path();
+        self
+    }
+
+    /// Fill the current path with the current color, and end it.
+    pub fn fill(&mut self, rule: FillRule) -> &mut Self {
+        self.in_progress.fill(rule);
+        self
+    }
+
+    /// Stroke the current path with the current stroke color, line width, and dash pattern,
+    /// and end it.
+    pub fn stroke(&mut self) -> &mut Self {
+        self.in_progress.stroke();
+        self
+    }
+
+    /// Intersect the clipping region with the current path, and end it without painting.
+    /// Use `save_state` and `restore_state` to undo this.
+    pub fn clip(&mut self, rule: FillRule) -> &mut Self {
+        self.in_progress.clip(rule);
+        self
+    }
+
+    /// Push a copy of the graphics state (colors, line style, transform, clipping region)
+    /// that the matching `restore_state` goes back to.
+    pub fn save_state(&mut self) -> &mut Self {
+        self.in_progress.save_state();
+        self
+    }
+
+    /// Does nothing without a matching `save_state`, but panics in debug builds.
+    pub fn restore_state(&mut self) -> &mut Self {
+        self.in_progress.restore_state();
+        self
+    }
+
+    /// Transform the colors set after this, and the pixels of images painted after this,
+    /// until `restore_state`.
+    ///
+    /// Matrices that were already applied transform the result of this one,
+    /// so that applying the matrix of an outer group first and that of an inner group next
+    /// composes them as nested groups would.
+    pub fn apply_color_matrix(&mut self, matrix: &ColorMatrix) -> &mut Self {
+        self.in_progress.apply_color_matrix(matrix);
+        self
+    }
+
+    /// Apply a transformation to everything painted after this, until `restore_state`.
+    pub fn transform(&mut self, transform: &Transform<CssPx, CssPx>) -> &mut Self {
+        self.in_progress.transform(transform);
+        self
+    }
+
+    /// Paint an image scaled to fill `rect`.
+    pub fn paint_image(
+        &mut self,
+        image: &Image,
+        rect: &Rect<CssPx>,
+    ) -> Result<&mut Self, InvalidImage> {
+        image.check()?;
+        self.in_progress.paint_image(image, rect);
+        Ok(self)
+    }
+
+    /// Paint an image repeatedly, scaled to the size of `tile`,
+    /// in rows and columns aligned with `tile` that cover `area`.
+    ///
+    /// Content outside of `area` is clipped. The image is written once in the PDF file.
+    pub fn paint_image_tiles(
+        &mut self,
+        image: &Image,
+        tile: &Rect<CssPx>,
+        area: &Rect<CssPx>,
+    ) -> Result<&mut Self, InvalidImage> {
+        image.check()?;
+        self.in_progress.paint_image_tiles(image, tile, area);
+        Ok(self)
+    }
+
+    /// Paint content that is likely to be repeated on other pages,
+    /// such as a running header, a footer, or a watermark.
+    ///
+    /// Content that is identical to earlier repeated content,
+    /// and painted from the same graphics state, is written only once in the PDF file
+    /// and referenced from each page.
+    /// Changes to the graphics state made by `paint` are undone afterwards.
+    /// Content outside of the page’s rectangle, in the current coordinate system, is clipped.
+    pub fn paint_repeated(&mut self, paint: impl FnOnce(&mut Self)) -> &mut Self {
+        let outer = self.in_progress.begin_repeated();
+        paint(self);
+        self.in_progress.end_repeated(outer);
+        self
+    }
+
+    /// Record content painted by `paint` once, to be painted with `paint_form`
+    /// in the same graphics state, but possibly with other transforms.
+    /// Changes to the graphics state made by `paint` are undone afterwards.
+    pub(crate) fn record_form(&mut self, paint: impl FnOnce(&mut Self)) -> Form {
+        let outer = self.in_progress.begin_repeated();
+        paint(self);
+        self.in_progress.end_form(outer)
+    }
+
+    pub(crate) fn paint_form(&mut self, form: &Form) -> &mut Self {
+        self.in_progress.paint_form(form);
+        self
+    }
+
+    /// Paint content as an isolated transparency group: it is composited on its own,
+    /// over a transparent backdrop, then the result is composited with what is painted
+    /// below it with `blend_mode`.
+    ///
+    /// Changes to the graphics state made by `paint` are undone afterwards.
+    /// Content outside of `bbox`, in the current coordinate system, is clipped.
+    pub fn paint_group(
+        &mut self,
+        blend_mode: BlendMode,
+        bbox: &Rect<CssPx>,
+        paint: impl FnOnce(&mut Self),
+    ) -> &mut Self {
+        let outer = self.in_progress.begin_group();
+        paint(self);
+        self.in_progress.end_group(outer, blend_mode, bbox);
+        self
+    }
+
+    /// Stroke a straight line with the current stroke color, line width, and dash pattern.
+    pub fn stroke_line(&mut self, from: &Point<CssPx>, to: &Point<CssPx>) -> &mut Self {
+        self.in_progress.stroke_line(from, to);
+        self
+    }
+
+    /// Paint a run of glyphs in its font, with the current color and text rendering mode.
     pub fn show_text(&mut self, text: &TextRun) -> Result<&mut Self, FontError> {
         self.in_progress.show_text(text)?;
         Ok(self)
     }
+
+    /// Start a structure element of a tagged PDF, such as `P` or `H1`
+    /// (see § 14.8.4 “Standard Structure Types” of PDF 32000-1:2008).
+    /// Content painted until the matching `end_structure_element` belongs to it,
+    /// or to structure elements nested in it.
+    ///
+    /// Structure elements end on the page where they begin.
+    /// Those begun in `paint_repeated` or `paint_group`, and their content, are not tagged.
+    pub fn begin_structure_element(&mut self, kind: &'static str) -> &mut Self {
+        self.in_progress.begin_structure_element(kind);
+        self
+    }
+
+    pub fn end_structure_element(&mut self) -> &mut Self {
+        self.in_progress.end_structure_element();
+        self
+    }
+
+    /// Set the trim box (the finished page after cutting)
+    /// and bleed box (how far content may extend past it, to be cut off)
+    /// for printers to impose this page.
+    ///
+    /// Both are relative to the page, whose size should leave room outside of `bleed`
+    /// for printer marks such as those of `paint_crop_marks` and `paint_cross_marks`.
+    pub fn set_trim_and_bleed_boxes(
+        &mut self,
+        trim: &Rect<CssPx>,
+        bleed: &Rect<CssPx>,
+    ) -> &mut Self {
+        self.in_progress.set_trim_and_bleed_boxes(trim, bleed);
+        self
+    }
+
+    /// Paint lines that show where to cut the page to `trim`, outside of `bleed`,
+    /// with the current color.
+    pub fn paint_crop_marks(&mut self, trim: &Rect<CssPx>, bleed: &Rect<CssPx>) -> &mut Self {
+        self.in_progress.paint_crop_marks(trim, bleed);
+        self
+    }
+
+    /// Paint registration crosses on each side of `trim`, outside of `bleed`,
+    /// with the current color.
+    pub fn paint_cross_marks(&mut self, trim: &Rect<CssPx>, bleed: &Rect<CssPx>) -> &mut Self {
+        self.in_progress.paint_cross_marks(trim, bleed);
+        self
+    }
+
+    /// Add a field to the document’s interactive form, that users can fill in a PDF reader.
+    ///
+    /// Readers draw the field themselves, over what is painted on the page.
+    pub fn add_form_field(&mut self, field: &FormField) -> &mut Self {
+        self.in_progress.add_form_field(field);
+        self
+    }
 }
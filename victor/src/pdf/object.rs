@@ -4,6 +4,7 @@ use std::io::{self, Write};
 
 #[derive(Debug)]
 pub(crate) enum Object<'a> {
+    Bool(bool),
     Usize(usize),
     I32(i32),
     Float(f32),
@@ -14,8 +15,10 @@ pub(crate) enum Object<'a> {
     Dictionary(Dictionary<'a>),
     Reference(IndirectObjectId),
 
-    GraphicsStateDictionaryAlpha(f32),
+    GraphicsStateDictionaryAlpha { non_stroking: f32, stroking: f32 },
+    GraphicsStateDictionaryBlendMode(&'static str),
     DictionaryWithOwnedKeys(&'a [(Vec<u8>, Object<'a>)]),
+    SignatureByteRangePlaceholder,
 }
 
 fn _static_assert_size() {
@@ -79,6 +82,12 @@ where
     }
 }
 
+impl<'a> From<bool> for Object<'a> {
+    fn from(value: bool) -> Self {
+        Object::Bool(value)
+    }
+}
+
 impl<'a> From<i32> for Object<'a> {
     fn from(value: i32) -> Self {
         Object::I32(value)
@@ -150,10 +159,11 @@ impl<'a> From<IndirectObjectId> for Object<'a> {
 impl<'a> Object<'a> {
     pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
         match *self {
+            Object::Bool(value) => w.write_all(if value { b"true" } else { b"false" }),
             // https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1965566
             Object::I32(value) => itoa::write(w, value).map(|_| ()),
             Object::Usize(value) => itoa::write(w, value).map(|_| ()),
-            Object::Float(value) => dtoa::write(w, value).map(|_| ()),
+            Object::Float(value) => write_real(value, w),
             Object::Name(value) => write_name(value, w),
             Object::Dictionary(ref value) => value.write(w),
             Object::LiteralString(value) => {
@@ -161,6 +171,8 @@ impl<'a> Object<'a> {
                 for &byte in value {
                     match byte {
                         b'\\' | b'(' | b')' => w.write_all(&[b'\\', byte])?,
+                        // Readers would read an unescaped CR or CR LF as LF
+                        b'\r' => w.write_all(b"\\r")?,
                         _ => w.write_all(&[byte])?,
                     }
                 }
@@ -189,13 +201,26 @@ impl<'a> Object<'a> {
                 itoa::write(&mut *w, id)?;
                 w.write_all(b" 0 R")
             }
-            Object::GraphicsStateDictionaryAlpha(value) => {
+            Object::GraphicsStateDictionaryAlpha {
+                non_stroking,
+                stroking,
+            } => {
+                let dict = dictionary! {
+                    "CA" => stroking,
+                    "ca" => non_stroking,
+                };
+                dict.write(w)
+            }
+            Object::GraphicsStateDictionaryBlendMode(name) => {
                 let dict = dictionary! {
-                    "CA" => value,
-                    "ca" => value,
+                    "BM" => name,
                 };
                 dict.write(w)
             }
+            Object::SignatureByteRangePlaceholder => {
+                // Fixed-width, to be overwritten when signing
+                w.write_all(b"[0 9999999999 9999999999 9999999999]")
+            }
             Object::DictionaryWithOwnedKeys(value) => {
                 w.write_all(b"<<")?;
                 for &(ref key, ref value) in value {
@@ -239,12 +264,40 @@ fn write_hex<W: Write>(byte: u8, w: &mut W) -> io::Result<()> {
     ])
 }
 
+/// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1965566
+///
+/// PDF has no exponent notation, infinity, or NaN.
+fn write_real<W: Write>(value: f32, w: &mut W) -> io::Result<()> {
+    if value.is_nan() {
+        w.write_all(b"0.0")
+    } else if value.abs() < 1e-6 {
+        // Below the precision of PDF readers, and dtoa would use exponent notation
+        w.write_all(b"0.0")
+    } else if value.abs() >= 1e21 {
+        // `Display` does not use exponent notation
+        let finite = if value.is_infinite() {
+            f32::MAX.copysign(value)
+        } else {
+            value
+        };
+        write!(w, "{:.1}", finite)
+    } else {
+        dtoa::write(w, value).map(|_| ())
+    }
+}
+
+/// Names can come from outside data, such as the PostScript name of a font.
+///
+/// https://www.adobe.com/content/dam/acom/en/devnet/pdf/PDF32000_2008.pdf#G6.1840203
 fn write_name<W: Write>(name: &[u8], w: &mut W) -> io::Result<()> {
     w.write_all(b"/")?;
     for &byte in name {
         match KIND[byte as usize] {
-            CharKind::Regular => w.write_all(&[byte])?,
-            CharKind::Whitespace | CharKind::Delimiter => {
+            // `#` starts an escape, and other bytes should be in the printable ASCII range
+            CharKind::Regular if byte != b'#' && (b'!'..=b'~').contains(&byte) => {
+                w.write_all(&[byte])?
+            }
+            _ => {
                 w.write_all(b"#")?;
                 write_hex(byte, w)?
             }
@@ -316,3 +369,25 @@ static KIND: [CharKind; 256] = {
         r, r, r, r, r, r, r, r, // \xf8 … \xff
     ]
 };
+
+#[test]
+fn escaping() {
+    let to_string = |object: Object| {
+        let mut bytes = Vec::new();
+        object.write(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    };
+    assert_eq!(
+        to_string(Object::Name(b"Ab#1 (c)/\xE9")),
+        "/Ab#231#20#28c#29#2F#E9"
+    );
+    assert_eq!(
+        to_string(Object::LiteralString(b"(a\\b)\r")),
+        "(\\(a\\\\b\\)\\r)"
+    );
+    assert_eq!(to_string(Object::Float(0.75)), "0.75");
+    assert_eq!(to_string(Object::Float(1e-9)), "0.0");
+    assert_eq!(to_string(Object::Float(f32::NAN)), "0.0");
+    assert!(!to_string(Object::Float(1e30)).contains('e'));
+    assert!(!to_string(Object::Float(f32::INFINITY)).contains("inf"));
+}
@@ -20,6 +20,8 @@ pub(crate) struct BasicObjects<'a> {
     pub page_tree: Dictionary<'a>,
     pub catalog: Dictionary<'a>,
     pub info: Dictionary<'a>,
+    /// Dictionaries for IDs from `PdfFile::reserve_id` that were not set earlier
+    pub reserved: &'a [(IndirectObjectId, Dictionary<'a>)],
 }
 
 pub(crate) struct PdfFile {
@@ -55,6 +57,18 @@ impl PdfFile {
         self.add_indirect_object(obj)
     }
 
+    /// An ID for an object that other objects refer to before it can be written,
+    /// set with `set_dictionary` or given in `BasicObjects::reserved`.
+    pub fn reserve_id(&mut self) -> IndirectObjectId {
+        self.add_indirect_object(Vec::new())
+    }
+
+    pub fn set_dictionary(&mut self, id: IndirectObjectId, dict: Dictionary) {
+        let contents = &mut self.indirect_objects[(id.0 - FIRST_AVAILABLE_ID.0) as usize];
+        debug_assert!(contents.is_empty());
+        dict.write(contents).unwrap();
+    }
+
     pub fn add_indirect_object(&mut self, serialized_contents: Vec<u8>) -> IndirectObjectId {
         self.indirect_objects.push(serialized_contents);
         let id = self.next_id;
@@ -99,7 +113,16 @@ impl PdfFile {
                 indirect_object_offsets.push(w.bytes_written as u32);
                 itoa(&mut w, object_id.0)?;
                 w.write_all(b" 0 obj\n")?; // Generation number is always zero for us
-                w.write_all(contents)?;
+                if contents.is_empty() {
+                    let (_, dictionary) = basic_objects
+                        .reserved
+                        .iter()
+                        .find(|&&(id, _)| id == object_id)
+                        .expect("reserved object ID without a dictionary");
+                    dictionary.write(&mut w)?;
+                } else {
+                    w.write_all(contents)?;
+                }
                 w.write_all(b"\nendobj\n")?;
             }
 
@@ -1,26 +1,160 @@
+//! Geometry and colors, for painting PDF pages and for the `dom` API.
+//!
+//! Points, sizes, and rectangles are `euclid` types tagged with their unit,
+//! so that for example CSS `px` and millimeters are not mixed up.
+
 use crate::text;
 
 /// Origin at top-left corner, unit `1px`
 pub struct CssPx;
 
+/// https://drafts.csswg.org/css-values/#absolute-lengths
+pub(crate) const PX_PER_INCH: f32 = 96.;
+
 pub use euclid::point2 as point;
 pub use euclid::rect;
+pub use euclid::size2 as size;
 pub type Length<U> = euclid::Length<f32, U>;
 pub type Point<U> = euclid::TypedPoint2D<f32, U>;
 pub type Size<U> = euclid::TypedSize2D<f32, U>;
 pub type Rect<U> = euclid::TypedRect<f32, U>;
 pub type SideOffsets<U> = euclid::TypedSideOffsets2D<f32, U>;
 pub type Scale<Src, Dest> = euclid::TypedScale<f32, Src, Dest>;
+pub type Transform<Src, Dest> = euclid::TypedTransform2D<f32, Src, Dest>;
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct RGBA(pub f32, pub f32, pub f32, pub f32);
 
+/// A transformation of colors like SVG’s `feColorMatrix`, see `Page::apply_color_matrix`.
+///
+/// Each row gives the red, green, blue or alpha component of the result:
+/// the first four values multiply those components of the original color, from 0 to 1,
+/// and the fifth is added.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorMatrix(pub [[f32; 5]; 4]);
+
+impl ColorMatrix {
+    pub const IDENTITY: Self = ColorMatrix([
+        [1., 0., 0., 0., 0.],
+        [0., 1., 0., 0., 0.],
+        [0., 0., 1., 0., 0.],
+        [0., 0., 0., 1., 0.],
+    ]);
+
+    /// Multiply alpha by `opacity`.
+    pub fn opacity(opacity: f32) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix.0[3][3] = opacity;
+        matrix
+    }
+
+    /// Replace every color with `color`, multiplying its alpha by that of the original color.
+    pub fn flood(RGBA(r, g, b, a): RGBA) -> Self {
+        ColorMatrix([
+            [0., 0., 0., 0., r],
+            [0., 0., 0., 0., g],
+            [0., 0., 0., 0., b],
+            [0., 0., 0., a, 0.],
+        ])
+    }
+
+    /// The transformation that applies `self`, then `next` to the result.
+    pub fn then(&self, next: &Self) -> Self {
+        let mut result = ColorMatrix([[0.; 5]; 4]);
+        for (row, next_row) in result.0.iter_mut().zip(&next.0) {
+            for (weight, self_row) in next_row[..4].iter().zip(&self.0) {
+                for (value, self_value) in row.iter_mut().zip(self_row) {
+                    *value += weight * self_value
+                }
+            }
+            row[4] += next_row[4]
+        }
+        result
+    }
+
+    /// Transform `color`, with each component clamped to the 0 to 1 range.
+    pub fn apply(&self, RGBA(r, g, b, a): RGBA) -> RGBA {
+        let component = |row: &[f32; 5]| {
+            (row[0] * r + row[1] * g + row[2] * b + row[3] * a + row[4]).clamp(0., 1.)
+        };
+        RGBA(
+            component(&self.0[0]),
+            component(&self.0[1]),
+            component(&self.0[2]),
+            component(&self.0[3]),
+        )
+    }
+
+    /// Whether this leaves red, green and blue unchanged.
+    pub fn preserves_rgb(&self) -> bool {
+        self.0[..3] == Self::IDENTITY.0[..3]
+    }
+}
+
+/// How a transparency group is composited with what is painted below it,
+/// see `Page::paint_group`.
+///
+/// These are the blend modes of PDF 32000-1:2008, section 11.3.5,
+/// which are also those of `mix-blend-mode` in CSS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+/// How glyph outlines are painted by `Page::show_text`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextRenderingMode {
+    Fill,
+    Stroke,
+    FillThenStroke,
+}
+
+/// The shape of the ends of stroked open paths, see `Page::set_line_cap`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// The shape of the corners of stroked paths, see `Page::set_line_join`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Which points are inside a path that intersects itself,
+/// see `Page::fill` and `Page::clip`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
 pub struct TextRun<'a> {
     pub segment: &'a text::ShapedSegment,
     pub font_size: Length<CssPx>,
     pub origin: Point<CssPx>,
 }
 
+#[cfg(feature = "html")]
 impl From<cssparser::RGBA> for RGBA {
     fn from(c: cssparser::RGBA) -> Self {
         RGBA(c.red_f32(), c.green_f32(), c.blue_f32(), c.alpha_f32())
@@ -1,21 +1,32 @@
 use crate::dom;
+use crate::style::counter_styles::CounterStyleRule;
 use crate::style::declaration_block::DeclarationBlock;
+use crate::style::errors::{for_each_with_location, CssError};
+use crate::style::page::{Marks, PageRule};
 use crate::style::properties::{ComputedValues, Phase};
 use crate::style::rules::{CssRule, RulesParser};
-use crate::style::selectors::{self, Selector};
+use crate::style::selectors::{self, PseudoElement, Selector};
+use crate::style::values::Length;
 use cssparser::{Parser, ParserInput, RuleListParser};
+use html5ever::LocalName;
 use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::sync::Arc;
 
 pub struct StyleSetBuilder(StyleSet);
 
 pub struct StyleSet {
     rules: Vec<(Selector, Arc<DeclarationBlock>)>,
+    /// By name. A later rule with the same name replaces an earlier one.
+    pub(super) counter_styles: HashMap<String, Arc<CounterStyleRule>>,
+    page: PageRule,
 }
 
 lazy_static::lazy_static! {
-    static ref USER_AGENT_STYLESHEET: StyleSet = {
+    pub(super) static ref USER_AGENT_STYLESHEET: StyleSet = {
         let mut builder = StyleSetBuilder::new();
+        builder.add_stylesheet(include_str!("predefined_counter_styles.css"));
         builder.add_stylesheet(include_str!("user_agent.css"));
         builder.finish()
     };
@@ -23,23 +34,52 @@ lazy_static::lazy_static! {
 
 impl StyleSetBuilder {
     pub fn new() -> Self {
-        StyleSetBuilder(StyleSet { rules: Vec::new() })
+        StyleSetBuilder(StyleSet {
+            rules: Vec::new(),
+            counter_styles: HashMap::new(),
+            page: PageRule::default(),
+        })
     }
 
     pub fn add_stylesheet(&mut self, css: &str) {
-        let mut input = ParserInput::new(css);
+        self.add_stylesheet_with_errors(ParserInput::new(css), None)
+    }
+
+    fn add_stylesheet_with_errors(
+        &mut self,
+        mut input: ParserInput,
+        errors: Option<&mut Vec<CssError>>,
+    ) {
         let mut parser = Parser::new(&mut input);
-        for result in RuleListParser::new_for_stylesheet(&mut parser, RulesParser) {
-            match result {
-                Ok(CssRule::StyleRule { selectors, block }) => {
-                    for selector in selectors.0 {
-                        self.0.rules.push((selector, block.clone()));
-                    }
+        let mut iter = RuleListParser::new_for_stylesheet(&mut parser, RulesParser::new(errors));
+        for_each_with_location(&mut iter, |iter, result, location| match result {
+            Ok(rule) => self.add_rule(rule),
+            Err((error, source)) => {
+                if let Some(errors) = iter.parser.errors.as_deref_mut() {
+                    errors.push(CssError::in_rule(error, source, location))
                 }
-                Err(_) => {
-                    // FIXME: error reporting
+            }
+        });
+    }
+
+    fn add_rule(&mut self, rule: CssRule) {
+        match rule {
+            CssRule::StyleRule { selectors, block } => {
+                for selector in selectors.0 {
+                    self.0.rules.push((selector, block.clone()));
+                }
+            }
+            CssRule::Supports { rules } => {
+                for rule in rules {
+                    self.add_rule(rule)
                 }
             }
+            // Already applied to the selectors of the rules after it
+            CssRule::Namespace => {}
+            CssRule::CounterStyle { name, rule } => {
+                self.0.counter_styles.insert(name.0, rule);
+            }
+            CssRule::Page(rule) => self.0.page.cascade(rule),
         }
     }
 
@@ -53,18 +93,70 @@ impl StyleSetBuilder {
 }
 
 impl StyleSet {
+    /// `marks` from `@page` rules
+    pub(crate) fn page_marks(&self) -> Marks {
+        self.page.marks()
+    }
+
+    /// How far content is painted outside of each edge of the page, from `@page` rules
+    pub(crate) fn page_bleed(&self) -> Length {
+        self.page.bleed()
+    }
+
     fn push_matching<'a>(
         &'a self,
         document: &dom::Document,
         node: dom::NodeId,
+        pseudo_element: Option<&PseudoElement>,
         into: &mut SmallVec<impl smallvec::Array<Item = &'a DeclarationBlock>>,
     ) {
         for &(ref selector, ref block) in &self.rules {
-            if selectors::matches(selector, document, node) {
+            let matches = match pseudo_element {
+                None => selectors::matches(selector, document, node),
+                Some(pseudo_element) => {
+                    selectors::matches_pseudo_element(selector, document, node, pseudo_element)
+                }
+            };
+            if matches {
                 into.push(block)
             }
         }
     }
+
+    fn may_change_counters(&self, document: &dom::Document, node: dom::NodeId) -> bool {
+        self.rules.iter().any(|(selector, block)| {
+            block.may_change_counters() && selectors::matches(selector, document, node)
+        })
+    }
+}
+
+/// Whether any element can have a counter with a value other than zero:
+/// a rule or `style` attribute that makes list items or changes counters applies to one.
+/// When none does, the sequential traversal that computes counters can be skipped.
+///
+/// Presentational hints are not checked: those of lists are on elements
+/// that user-agent rules already match.
+pub(crate) fn document_may_use_counters(author: &StyleSet, document: &dom::Document) -> bool {
+    document.nodes().any(|node| {
+        let element = match document[node].as_element() {
+            Some(element) => element,
+            None => return false,
+        };
+        if USER_AGENT_STYLESHEET.may_change_counters(document, node)
+            || author.may_change_counters(document, node)
+        {
+            return true;
+        }
+        match (&element.name.ns, element.get_attr(&local_name!("style"))) {
+            (&ns!(html), Some(style_attr))
+            | (&ns!(svg), Some(style_attr))
+            | (&ns!(mathml), Some(style_attr)) => {
+                let mut input = ParserInput::new(style_attr);
+                DeclarationBlock::parse(&mut Parser::new(&mut input), None).may_change_counters()
+            }
+            _ => false,
+        }
+    })
 }
 
 pub(super) struct MatchingDeclarations<'a> {
@@ -82,6 +174,24 @@ impl MatchingDeclarations<'_> {
     }
 }
 
+/// Parse a stylesheet only to find what it skips, see `dom::Document::css_diagnostics`.
+///
+/// Lines are counted from `first_line`.
+pub(crate) fn stylesheet_errors(css: &str, first_line: u32) -> Vec<CssError> {
+    let mut errors = Vec::new();
+    let input = ParserInput::new_with_line_number_offset(css, first_line);
+    StyleSetBuilder::new().add_stylesheet_with_errors(input, Some(&mut errors));
+    errors
+}
+
+/// Like `stylesheet_errors`, for the declarations of a `style` attribute.
+pub(crate) fn style_attribute_errors(css: &str) -> Vec<CssError> {
+    let mut errors = Vec::new();
+    let mut input = ParserInput::new(css);
+    DeclarationBlock::parse(&mut Parser::new(&mut input), Some(&mut errors));
+    errors
+}
+
 pub(crate) fn style_for_element(
     author: &StyleSet,
     document: &dom::Document,
@@ -90,19 +200,216 @@ pub(crate) fn style_for_element(
 ) -> Arc<ComputedValues> {
     let element = document[node].as_element().unwrap();
     let style_attr_block;
+    let hints_block;
     let mut matching = MatchingDeclarations {
         ua: SmallVec::new(),
         author: SmallVec::new(),
     };
-    USER_AGENT_STYLESHEET.push_matching(document, node, &mut matching.ua);
-    author.push_matching(document, node, &mut matching.author);
+    USER_AGENT_STYLESHEET.push_matching(document, node, None, &mut matching.ua);
+    if element.name.ns == ns!(html) {
+        if let Some(hints) = presentational_hints(element) {
+            // Presentational hints are author-level with zero specificity,
+            // so any matching author rule comes after them and overrides them.
+            hints_block = hints;
+            matching.author.push(&hints_block);
+        }
+    }
+    author.push_matching(document, node, None, &mut matching.author);
     if let ns!(html) | ns!(svg) | ns!(mathml) = element.name.ns {
         if let Some(style_attr) = element.get_attr(&local_name!("style")) {
             let mut input = ParserInput::new(style_attr);
             let mut parser = Parser::new(&mut input);
-            style_attr_block = DeclarationBlock::parse(&mut parser);
+            style_attr_block = DeclarationBlock::parse(&mut parser, None);
             matching.author.push(&style_attr_block);
         }
     }
     ComputedValues::new(parent_style, Some(&matching))
 }
+
+/// https://html.spec.whatwg.org/multipage/rendering.html#presentational-hints
+///
+/// FIXME: only the `width` and `height` attributes of embedded content
+/// and the attributes of lists are mapped so far.
+fn presentational_hints(element: &dom::ElementData) -> Option<DeclarationBlock> {
+    let mut css = String::new();
+    match element.name.local {
+        // https://html.spec.whatwg.org/multipage/rendering.html#dimRendering
+        local_name!("canvas")
+        | local_name!("embed")
+        | local_name!("iframe")
+        | local_name!("img")
+        | local_name!("object")
+        | local_name!("video") => {
+            for &(ref attr, property) in &[
+                (local_name!("width"), "width"),
+                (local_name!("height"), "height"),
+            ] {
+                if let Some(value) = element.get_attr(attr).and_then(parse_dimension_value) {
+                    css.push_str(&format!("{}: {}; ", property, value))
+                }
+            }
+        }
+        // https://html.spec.whatwg.org/multipage/rendering.html#flow-content-3
+        local_name!("br") => {
+            if let Some(clear) = element.get_attr(&local_name!("clear")) {
+                let value = ["left", "right", "all", "both"]
+                    .iter()
+                    .find(|keyword| clear.eq_ignore_ascii_case(keyword));
+                match value {
+                    Some(&"all") | Some(&"both") => css.push_str("clear: both; "),
+                    Some(side) => css.push_str(&format!("clear: {}; ", side)),
+                    None => {}
+                }
+            }
+        }
+        // https://html.spec.whatwg.org/multipage/rendering.html#lists
+        // FIXME: `reversed` lists
+        local_name!("ol") | local_name!("ul") | local_name!("li") => {
+            let integer_attr = |name: LocalName| element.get_attr(&name).and_then(parse_integer);
+            if element.name.local == local_name!("ol") {
+                if let Some(start) = integer_attr(local_name!("start")) {
+                    let value = start.saturating_sub(1);
+                    css.push_str(&format!("counter-reset: list-item {}; ", value))
+                }
+            }
+            if element.name.local == local_name!("li") {
+                if let Some(value) = integer_attr(local_name!("value")) {
+                    css.push_str(&format!("counter-set: list-item {}; ", value))
+                }
+            }
+            if let Some(type_) = element.get_attr(&local_name!("type")) {
+                let ordered = match type_ {
+                    "1" => Some("decimal"),
+                    "a" => Some("lower-alpha"),
+                    "A" => Some("upper-alpha"),
+                    "i" => Some("lower-roman"),
+                    "I" => Some("upper-roman"),
+                    _ => None,
+                };
+                let unordered = ["none", "disc", "circle", "square"]
+                    .iter()
+                    .find(|keyword| type_.eq_ignore_ascii_case(keyword));
+                let style_type = match element.name.local {
+                    local_name!("ol") => ordered,
+                    local_name!("ul") => unordered.copied(),
+                    _ => ordered.or_else(|| unordered.copied()),
+                };
+                if let Some(style_type) = style_type {
+                    css.push_str(&format!("list-style-type: {}; ", style_type))
+                }
+            }
+        }
+        _ => return None,
+    }
+    if css.is_empty() {
+        return None;
+    }
+    let mut input = ParserInput::new(&css);
+    Some(DeclarationBlock::parse(&mut Parser::new(&mut input), None))
+}
+
+/// https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-integers
+fn parse_integer(value: &str) -> Option<i32> {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let magnitude: i64 = digits[..end].parse().ok()?;
+    let integer = if negative { -magnitude } else { magnitude };
+    i32::try_from(integer).ok()
+}
+
+/// https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-dimension-values
+///
+/// Returns the value as CSS, in `px` or `%`.
+fn parse_dimension_value(value: &str) -> Option<String> {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let integer = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    if integer == 0 {
+        return None;
+    }
+    let mut end = integer;
+    if value[end..].starts_with('.') {
+        let fraction = value[end + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len() - end - 1);
+        if fraction > 0 {
+            end += 1 + fraction
+        }
+    }
+    let number: f32 = value[..end].parse().ok()?;
+    if value[end..].starts_with('%') {
+        Some(format!("{}%", number))
+    } else {
+        Some(format!("{}px", number))
+    }
+}
+
+/// The style of a `::before`, `::after` or `::marker` pseudo-element,
+/// which inherits from that of its originating element.
+pub(crate) fn style_for_pseudo_element(
+    author: &StyleSet,
+    document: &dom::Document,
+    node: dom::NodeId,
+    pseudo_element: &PseudoElement,
+    element_style: &ComputedValues,
+) -> Arc<ComputedValues> {
+    let mut matching = MatchingDeclarations {
+        ua: SmallVec::new(),
+        author: SmallVec::new(),
+    };
+    USER_AGENT_STYLESHEET.push_matching(document, node, Some(pseudo_element), &mut matching.ua);
+    author.push_matching(document, node, Some(pseudo_element), &mut matching.author);
+    let style = ComputedValues::new(Some(element_style), Some(&matching));
+    match pseudo_element {
+        PseudoElement::Marker => marker_style(&style, element_style),
+        _ => style,
+    }
+}
+
+/// https://drafts.csswg.org/css-pseudo-4/#marker-pseudo
+///
+/// Only font properties, `color`, `white-space`, `direction` and `content` apply to `::marker`,
+/// so the other properties keep the values a box inheriting from the list item would have:
+/// a marker cannot be given a background, a border, or `display: block`.
+/// (Text fill, stroke and shadow are kept as well, like `color`.)
+fn marker_style(cascaded: &ComputedValues, element_style: &ComputedValues) -> Arc<ComputedValues> {
+    let mut style = ComputedValues::anonymous_inheriting_from(Some(element_style));
+    let marker = Arc::make_mut(&mut style);
+    marker.font = cascaded.font.clone();
+    marker.color = cascaded.color.clone();
+    marker.inherited_box = cascaded.inherited_box.clone();
+    marker.text = cascaded.text.clone();
+    Arc::make_mut(&mut marker.generated).content = cascaded.generated.content.clone();
+    style
+}
+
+#[test]
+fn documents_using_counters() {
+    let cases = [
+        ("<p>text</p><div style='color: red'></div>", false),
+        ("<style>p { counter-reset: none }</style><p>", false),
+        ("<ul><li>item</ul>", true),
+        ("<style>p { display: list-item }</style><p>", true),
+        ("<style>p { display: list-item }</style><div>", false),
+        ("<h2 style='counter-increment: section'>", true),
+        ("<h2 style='counter-set: inherit'>", true),
+    ];
+    for &(html, expected) in &cases {
+        let document = dom::Document::parse_html(html.as_bytes());
+        let styles = document.parse_stylesheets();
+        assert_eq!(
+            document_may_use_counters(&styles, &document),
+            expected,
+            "{}",
+            html
+        );
+    }
+}
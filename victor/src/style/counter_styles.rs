@@ -0,0 +1,492 @@
+//! https://drafts.csswg.org/css-counter-styles-3/
+
+use crate::style::cascade::{StyleSet, USER_AGENT_STYLESHEET};
+use crate::style::errors::{for_each_with_location, CssError};
+use crate::style::errors::{PropertyParseError, PropertyParseErrorKind};
+use crate::style::values::CounterStyleName;
+use cssparser::{AtRuleParser, CowRcStr, DeclarationListParser, DeclarationParser};
+use cssparser::{Parser, Token};
+
+/// https://drafts.csswg.org/css-counter-styles-3/#the-counter-style-rule
+///
+/// Descriptors are `None` when not specified, so that `extends` can tell
+/// which ones to take from the extended counter style.
+///
+/// FIXME: `speak-as` is parsed but ignored, and symbols cannot be images.
+#[derive(Default)]
+pub(super) struct CounterStyleRule {
+    system: Option<System>,
+    negative: Option<(String, String)>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    range: Option<Vec<(i64, i64)>>,
+    pad: Option<(u32, String)>,
+    fallback: Option<CounterStyleName>,
+    symbols: Option<Vec<String>>,
+    additive_symbols: Option<Vec<(u32, String)>>,
+}
+
+/// https://drafts.csswg.org/css-counter-styles-3/#counter-style-system
+#[derive(Clone)]
+enum System {
+    Cyclic,
+    Numeric,
+    Alphabetic,
+    Symbolic,
+    Additive,
+    /// With the value of the first symbol
+    Fixed(i64),
+    Extends(CounterStyleName),
+}
+
+/// A counter style with `extends` resolved
+struct CounterStyle<'a> {
+    system: &'a System,
+    symbols: &'a [String],
+    additive_symbols: &'a [(u32, String)],
+    negative: (&'a str, &'a str),
+    prefix: &'a str,
+    suffix: &'a str,
+    /// `None` for `auto`
+    range: Option<&'a [(i64, i64)]>,
+    pad: Option<(u32, &'a str)>,
+    fallback: &'a CounterStyleName,
+}
+
+/// How many times a symbol can be repeated in a representation.
+/// Representations that would need more use the fallback counter style instead,
+/// and `pad` is cut to that length.
+const MAX_REPETITIONS: i64 = 60;
+
+/// How many `extends` or `fallback` references are followed,
+/// which also stops reference cycles.
+const MAX_REFERENCES: usize = 20;
+
+impl StyleSet {
+    /// https://drafts.csswg.org/css-counter-styles-3/#generate-a-counter
+    ///
+    /// Without prefix and suffix, as used by `counter()` and `counters()`.
+    pub(crate) fn counter_representation(&self, style: &CounterStyleName, value: i32) -> String {
+        self.generate(style, value.into(), 0).0
+    }
+
+    /// https://drafts.csswg.org/css-lists-3/#content-property
+    ///
+    /// The text of a list marker: the representation with the prefix and suffix of its style.
+    pub(crate) fn marker_text(&self, style: &CounterStyleName, value: i32) -> String {
+        let (representation, prefix, suffix) = self.generate(style, value.into(), 0);
+        format!("{}{}{}", prefix, representation, suffix)
+    }
+
+    /// The representation, with the prefix and suffix of the counter style that produced it
+    /// (which is a fallback style if `value` is out of the range of `name`).
+    fn generate(&self, name: &CounterStyleName, value: i64, depth: usize) -> (String, &str, &str) {
+        let style = match self.counter_style(name, 0) {
+            Some(style) => style,
+            // Unknown counter styles are treated as `decimal`
+            None => return self.generate(&CounterStyleName::decimal(), value, depth),
+        };
+        let fallback = |depth| {
+            if depth < MAX_REFERENCES && style.fallback != name {
+                self.generate(style.fallback, value, depth + 1)
+            } else {
+                self.generate(&CounterStyleName::decimal(), value, MAX_REFERENCES)
+            }
+        };
+        if !style.in_range(value) {
+            return fallback(depth);
+        }
+        let uses_negative = value < 0 && style.uses_negative_sign();
+        let magnitude = if uses_negative { -value } else { value };
+        let representation = match style.initial_representation(magnitude) {
+            Some(representation) => representation,
+            None => return fallback(depth),
+        };
+        let mut result = String::new();
+        if uses_negative {
+            result.push_str(style.negative.0)
+        }
+        if let Some((length, symbol)) = style.pad {
+            let mut current = representation.chars().count();
+            if uses_negative {
+                current += style.negative.0.chars().count() + style.negative.1.chars().count()
+            }
+            let missing = (length as i64 - current as i64).min(MAX_REPETITIONS);
+            for _ in 0..missing {
+                result.push_str(symbol)
+            }
+        }
+        result.push_str(&representation);
+        if uses_negative {
+            result.push_str(style.negative.1)
+        }
+        (result, style.prefix, style.suffix)
+    }
+
+    /// Author styles take precedence over predefined ones,
+    /// except for names that cannot be redefined.
+    fn counter_style_rule(&self, name: &CounterStyleName) -> Option<&CounterStyleRule> {
+        let author = if is_redefinable(&name.0) {
+            self.counter_styles.get(&name.0)
+        } else {
+            None
+        };
+        author
+            .or_else(|| USER_AGENT_STYLESHEET.counter_styles.get(&name.0))
+            .map(|rule| &**rule)
+    }
+
+    fn counter_style(&self, name: &CounterStyleName, depth: usize) -> Option<CounterStyle<'_>> {
+        let rule = self.counter_style_rule(name)?;
+        let extended = match rule.system.as_ref().unwrap_or(&System::Symbolic) {
+            System::Extends(extended) => {
+                // Unknown counter styles and `extends` cycles are treated as extending `decimal`
+                let decimal = CounterStyleName::decimal();
+                let extended = if depth < MAX_REFERENCES {
+                    self.counter_style(extended, depth + 1)
+                } else {
+                    None
+                };
+                Some(extended.unwrap_or_else(|| {
+                    self.counter_style(&decimal, MAX_REFERENCES)
+                        .expect("decimal is predefined")
+                }))
+            }
+            _ => None,
+        };
+        Some(CounterStyle {
+            system: match &extended {
+                Some(extended) => extended.system,
+                None => rule.system.as_ref().unwrap_or(&System::Symbolic),
+            },
+            symbols: match &extended {
+                Some(extended) => extended.symbols,
+                None => rule.symbols.as_deref().unwrap_or(&[]),
+            },
+            additive_symbols: match &extended {
+                Some(extended) => extended.additive_symbols,
+                None => rule.additive_symbols.as_deref().unwrap_or(&[]),
+            },
+            negative: match (&rule.negative, &extended) {
+                (Some((before, after)), _) => (before, after),
+                (None, Some(extended)) => extended.negative,
+                (None, None) => ("-", ""),
+            },
+            prefix: match (&rule.prefix, &extended) {
+                (Some(prefix), _) => prefix,
+                (None, Some(extended)) => extended.prefix,
+                (None, None) => "",
+            },
+            suffix: match (&rule.suffix, &extended) {
+                (Some(suffix), _) => suffix,
+                (None, Some(extended)) => extended.suffix,
+                (None, None) => ". ",
+            },
+            range: match (&rule.range, &extended) {
+                (Some(range), _) => Some(range),
+                (None, Some(extended)) => extended.range,
+                (None, None) => None,
+            },
+            pad: match (&rule.pad, &extended) {
+                (Some((length, symbol)), _) => Some((*length, symbol)),
+                (None, Some(extended)) => extended.pad,
+                (None, None) => None,
+            },
+            fallback: match (&rule.fallback, &extended) {
+                (Some(fallback), _) => fallback,
+                (None, Some(extended)) => extended.fallback,
+                (None, None) => &DECIMAL,
+            },
+        })
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref DECIMAL: CounterStyleName = CounterStyleName::decimal();
+}
+
+/// https://drafts.csswg.org/css-counter-styles-3/#the-counter-style-rule
+fn is_redefinable(name: &str) -> bool {
+    !matches!(
+        name,
+        "decimal" | "disc" | "square" | "circle" | "disclosure-open" | "disclosure-closed"
+    )
+}
+
+impl CounterStyle<'_> {
+    /// https://drafts.csswg.org/css-counter-styles-3/#counter-style-range
+    fn in_range(&self, value: i64) -> bool {
+        match self.range {
+            Some(ranges) => ranges
+                .iter()
+                .any(|&(lower, upper)| lower <= value && value <= upper),
+            None => match self.system {
+                System::Alphabetic | System::Symbolic => value >= 1,
+                System::Additive => value >= 0,
+                _ => true,
+            },
+        }
+    }
+
+    /// https://drafts.csswg.org/css-counter-styles-3/#counter-style-negative
+    fn uses_negative_sign(&self) -> bool {
+        match self.system {
+            System::Symbolic | System::Alphabetic | System::Numeric | System::Additive => true,
+            System::Cyclic | System::Fixed(_) | System::Extends(_) => false,
+        }
+    }
+
+    /// The representation of `value` (an absolute value for systems that use a negative sign),
+    /// or `None` if this counter style cannot represent it.
+    fn initial_representation(&self, value: i64) -> Option<String> {
+        let symbols = self.symbols;
+        let n = symbols.len() as i64;
+        match *self.system {
+            // https://drafts.csswg.org/css-counter-styles-3/#cyclic-system
+            System::Cyclic => Some(symbols[(value - 1).rem_euclid(n) as usize].clone()),
+            // https://drafts.csswg.org/css-counter-styles-3/#fixed-system
+            System::Fixed(first) => {
+                let index = value.checked_sub(first)?;
+                if (0..n).contains(&index) {
+                    Some(symbols[index as usize].clone())
+                } else {
+                    None
+                }
+            }
+            // https://drafts.csswg.org/css-counter-styles-3/#symbolic-system
+            System::Symbolic | System::Alphabetic if value < 1 => None,
+            System::Symbolic => {
+                let repetitions = (value + n - 1) / n;
+                if repetitions > MAX_REPETITIONS {
+                    return None;
+                }
+                Some(symbols[((value - 1) % n) as usize].repeat(repetitions as usize))
+            }
+            // https://drafts.csswg.org/css-counter-styles-3/#alphabetic-system
+            System::Alphabetic => {
+                let mut digits = Vec::new();
+                let mut value = value;
+                while value != 0 {
+                    value -= 1;
+                    digits.push(&*symbols[(value % n) as usize]);
+                    value /= n
+                }
+                Some(digits.iter().rev().cloned().collect())
+            }
+            // https://drafts.csswg.org/css-counter-styles-3/#numeric-system
+            System::Numeric => {
+                if value == 0 {
+                    return Some(symbols[0].clone());
+                }
+                let mut digits = Vec::new();
+                let mut value = value;
+                while value != 0 {
+                    digits.push(&*symbols[(value % n) as usize]);
+                    value /= n
+                }
+                Some(digits.iter().rev().cloned().collect())
+            }
+            // https://drafts.csswg.org/css-counter-styles-3/#additive-system
+            System::Additive => {
+                if value == 0 {
+                    return self
+                        .additive_symbols
+                        .iter()
+                        .find(|(weight, _)| *weight == 0)
+                        .map(|(_, symbol)| symbol.clone());
+                }
+                let mut result = String::new();
+                let mut value = value;
+                for (weight, symbol) in self.additive_symbols {
+                    let weight = i64::from(*weight);
+                    if weight == 0 || weight > value {
+                        continue;
+                    }
+                    let repetitions = value / weight;
+                    if repetitions > MAX_REPETITIONS {
+                        return None;
+                    }
+                    result.push_str(&symbol.repeat(repetitions as usize));
+                    value -= repetitions * weight;
+                    if value == 0 {
+                        return Some(result);
+                    }
+                }
+                None
+            }
+            System::Extends(_) => unreachable!("resolved in StyleSet::counter_style"),
+        }
+    }
+}
+
+impl CounterStyleRule {
+    /// The block of a `@counter-style` rule.
+    /// Returns `None` if the rule is invalid, for lack of the symbols its system needs.
+    pub(super) fn parse(
+        parser: &mut Parser,
+        mut errors: Option<&mut Vec<CssError>>,
+    ) -> Option<Self> {
+        let mut iter = DeclarationListParser::new(parser, DescriptorParser(Self::default()));
+        for_each_with_location(&mut iter, |_, result, location| {
+            if let (Err((error, source)), Some(errors)) = (result, errors.as_deref_mut()) {
+                errors.push(CssError::in_declaration(error, source, location))
+            }
+        });
+        let rule = iter.parser.0;
+        let symbols = rule.symbols.as_ref().map_or(0, Vec::len);
+        let additive_symbols = rule.additive_symbols.as_ref().map_or(0, Vec::len);
+        // https://drafts.csswg.org/css-counter-styles-3/#counter-style-symbols
+        let valid = match rule.system.as_ref().unwrap_or(&System::Symbolic) {
+            System::Cyclic | System::Fixed(_) | System::Symbolic => symbols >= 1,
+            System::Alphabetic | System::Numeric => symbols >= 2,
+            System::Additive => additive_symbols >= 1,
+            System::Extends(_) => rule.symbols.is_none() && rule.additive_symbols.is_none(),
+        };
+        if valid {
+            Some(rule)
+        } else {
+            None
+        }
+    }
+}
+
+struct DescriptorParser(CounterStyleRule);
+
+impl<'i> DeclarationParser<'i> for DescriptorParser {
+    type Declaration = ();
+    type Error = PropertyParseErrorKind<'i>;
+
+    fn parse_value<'t>(
+        &mut self,
+        name: CowRcStr<'i>,
+        parser: &mut Parser<'i, 't>,
+    ) -> Result<(), PropertyParseError<'i>> {
+        let rule = &mut self.0;
+        match_ignore_ascii_case! { &name,
+            "system" => rule.system = Some(parse_system(parser)?),
+            "negative" => {
+                let before = parse_symbol(parser)?;
+                let after = parser.r#try(parse_symbol).unwrap_or_default();
+                rule.negative = Some((before, after))
+            },
+            "prefix" => rule.prefix = Some(parse_symbol(parser)?),
+            "suffix" => rule.suffix = Some(parse_symbol(parser)?),
+            "range" => {
+                rule.range = if parser.r#try(|p| p.expect_ident_matching("auto")).is_ok() {
+                    None
+                } else {
+                    Some(parser.parse_comma_separated(|parser| {
+                        let location = parser.current_source_location();
+                        let lower = parse_range_bound(parser, i64::MIN)?;
+                        let upper = parse_range_bound(parser, i64::MAX)?;
+                        if lower > upper {
+                            return Err(location.new_custom_error(
+                                PropertyParseErrorKind::NegativeValue
+                            ));
+                        }
+                        Ok((lower, upper))
+                    })?)
+                }
+            },
+            "pad" => {
+                // <integer [0,∞]> && <symbol>
+                let symbol = parser.r#try(parse_symbol).ok();
+                let length = parse_non_negative_integer(parser)?;
+                let symbol = match symbol {
+                    Some(symbol) => symbol,
+                    None => parse_symbol(parser)?,
+                };
+                rule.pad = Some((length, symbol))
+            },
+            "fallback" => rule.fallback = Some(CounterStyleName::parse(parser)?),
+            "symbols" => {
+                let mut symbols = vec![parse_symbol(parser)?];
+                while let Ok(symbol) = parser.r#try(parse_symbol) {
+                    symbols.push(symbol)
+                }
+                rule.symbols = Some(symbols)
+            },
+            "additive-symbols" => {
+                let symbols = parser.parse_comma_separated(|parser| {
+                    let symbol = parser.r#try(parse_symbol).ok();
+                    let weight = parse_non_negative_integer(parser)?;
+                    let symbol = match symbol {
+                        Some(symbol) => symbol,
+                        None => parse_symbol(parser)?,
+                    };
+                    Ok((weight, symbol))
+                })?;
+                // Weights must be in strictly descending order
+                if symbols.windows(2).any(|pair| pair[0].0 <= pair[1].0) {
+                    return Err(parser.new_error_for_next_token());
+                }
+                rule.additive_symbols = Some(symbols)
+            },
+            "speak-as" => {
+                while parser.next().is_ok() {}
+            },
+            _ => return Err(parser.new_custom_error(PropertyParseErrorKind::UnknownProperty(name))),
+        }
+        parser.expect_exhausted()?;
+        Ok(())
+    }
+}
+
+impl<'i> AtRuleParser<'i> for DescriptorParser {
+    type PreludeNoBlock = ();
+    type PreludeBlock = ();
+    type AtRule = ();
+    type Error = PropertyParseErrorKind<'i>;
+}
+
+fn parse_system<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<System, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let ident = parser.expect_ident()?.clone();
+    match_ignore_ascii_case! { &ident,
+        "cyclic" => Ok(System::Cyclic),
+        "numeric" => Ok(System::Numeric),
+        "alphabetic" => Ok(System::Alphabetic),
+        "symbolic" => Ok(System::Symbolic),
+        "additive" => Ok(System::Additive),
+        "fixed" => Ok(System::Fixed(
+            parser.r#try(|p| p.expect_integer()).map_or(1, i64::from)
+        )),
+        "extends" => Ok(System::Extends(CounterStyleName::parse(parser)?)),
+        _ => Err(location.new_unexpected_token_error(Token::Ident(ident))),
+    }
+}
+
+/// https://drafts.csswg.org/css-counter-styles-3/#typedef-symbol
+fn parse_symbol<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<String, PropertyParseError<'i>> {
+    match parser.next()? {
+        Token::QuotedString(string) | Token::Ident(string) => Ok(string.to_string()),
+        token => {
+            let token = token.clone();
+            Err(parser.new_unexpected_token_error(token))
+        }
+    }
+}
+
+fn parse_range_bound<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+    infinite: i64,
+) -> Result<i64, PropertyParseError<'i>> {
+    if parser
+        .r#try(|p| p.expect_ident_matching("infinite"))
+        .is_ok()
+    {
+        return Ok(infinite);
+    }
+    Ok(parser.expect_integer()?.into())
+}
+
+fn parse_non_negative_integer<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<u32, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let value = parser.expect_integer()?;
+    if value < 0 {
+        return Err(location.new_custom_error(PropertyParseErrorKind::NegativeValue));
+    }
+    Ok(value as u32)
+}
@@ -1,6 +1,8 @@
 use crate::style::errors::PropertyParseErrorKind;
-use crate::style::properties::{property_data_by_name, LonghandDeclaration, PerPhase, Phase};
-use crate::style::values::{CssWideKeyword, Parse};
+use crate::style::errors::{for_each_with_location, is_dynamic_property, CssError};
+use crate::style::properties::{property_data_by_name, LonghandDeclaration, LonghandId};
+use crate::style::properties::{PerPhase, Phase};
+use crate::style::values::{CssWideKeyword, Parse, Position, ZIndex};
 use cssparser::{AtRuleParser, ParseError, Parser};
 use cssparser::{CowRcStr, DeclarationListParser, DeclarationParser};
 use std::iter::repeat;
@@ -14,28 +16,47 @@ pub(super) struct DeclarationBlock {
 }
 
 impl DeclarationBlock {
-    pub fn parse(parser: &mut Parser) -> Self {
+    pub fn parse(parser: &mut Parser, mut errors: Option<&mut Vec<CssError>>) -> Self {
         let mut iter = DeclarationListParser::new(
             parser,
             LonghandDeclarationParser {
                 block: DeclarationBlock::default(),
             },
         );
-        loop {
-            let previous_len = iter.parser.block.declarations.len();
-            let result = if let Some(r) = iter.next() { r } else { break };
+        // The number of declarations before the one being parsed
+        let mut previous_len = 0;
+        for_each_with_location(&mut iter, |iter, result, location| {
             match result {
-                Ok(()) => {}
-                Err(_) => {
+                Ok(()) => {
+                    let parsed = &iter.parser.block.declarations[previous_len..];
+                    let message = parsed.iter().find_map(|declaration| match declaration {
+                        LonghandDeclaration::position(Position::Sticky) => Some(
+                            "`position: sticky` is rendered like `position: relative`".to_owned(),
+                        ),
+                        LonghandDeclaration::z_index(ZIndex::Integer(_)) => {
+                            Some("`z-index` does not change the painting order".to_owned())
+                        }
+                        LonghandDeclaration::filter(filter) => {
+                            filter.first_image_color_change().map(|function| {
+                                format!("`{}` leaves the colors of JPEG images unchanged", function)
+                            })
+                        }
+                        _ => None,
+                    });
+                    if let (Some(message), Some(errors)) = (message, errors.as_deref_mut()) {
+                        errors.push(CssError::approximated(location, message))
+                    }
+                }
+                Err((error, source)) => {
                     assert!(iter.parser.block.declarations.len() == previous_len);
-                    // FIXME error reporting
+                    if let Some(errors) = errors.as_deref_mut() {
+                        errors.push(CssError::in_declaration(error, source, location))
+                    }
                 }
             }
-            debug_assert_eq!(
-                iter.parser.block.declarations.len(),
-                iter.parser.block.important.len()
-            );
-        }
+            previous_len = iter.parser.block.declarations.len();
+            debug_assert_eq!(previous_len, iter.parser.block.important.len());
+        });
         debug_assert_eq!(
             iter.parser.block.any_normal.early || iter.parser.block.any_normal.late,
             !iter.parser.block.important.all_true()
@@ -55,6 +76,27 @@ impl DeclarationBlock {
         self.cascade(true, self.any_important, phase)
     }
 
+    /// Whether a declaration can make a list item or change the value of a counter.
+    /// CSS-wide keywords for these properties count, since they may inherit such a value.
+    pub fn may_change_counters(&self) -> bool {
+        self.declarations
+            .iter()
+            .any(|declaration| match declaration {
+                LonghandDeclaration::display(display) => display.is_list_item(),
+                LonghandDeclaration::counter_reset(changes)
+                | LonghandDeclaration::counter_increment(changes)
+                | LonghandDeclaration::counter_set(changes) => !changes.0.is_empty(),
+                LonghandDeclaration::CssWide(longhand, _) => matches!(
+                    longhand,
+                    LonghandId::display
+                        | LonghandId::counter_reset
+                        | LonghandId::counter_increment
+                        | LonghandId::counter_set
+                ),
+                _ => false,
+            })
+    }
+
     fn cascade(&self, important: bool, any: PerPhase<bool>, phase: &mut impl Phase) {
         if phase.select(any) {
             self.declarations.iter().zip(&self.important).for_each(
@@ -68,6 +110,24 @@ impl DeclarationBlock {
     }
 }
 
+/// Parse `name: value` as in a declaration block, for `@supports (name: value)`.
+///
+/// Returns whether Victor implements all the longhands of the property,
+/// rather than only parsing them.
+pub(super) fn parse_supports_declaration<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<bool, ParseError<'i, PropertyParseErrorKind<'i>>> {
+    let name = parser.expect_ident_cloned()?;
+    parser.expect_colon()?;
+    LonghandDeclarationParser {
+        block: DeclarationBlock::default(),
+    }
+    .parse_value(name.clone(), parser)?;
+    parser.expect_exhausted()?;
+    Ok(property_data_by_name(&name)
+        .map_or(false, |data| data.longhands.iter().all(|l| l.is_implemented())))
+}
+
 struct LonghandDeclarationParser {
     block: DeclarationBlock,
 }
@@ -100,6 +160,11 @@ impl<'i> DeclarationParser<'i> for LonghandDeclarationParser {
                 parsed = (data.parse)(parser, &mut self.block.declarations)?
             }
             let important = parser.r#try(cssparser::parse_important).is_ok();
+            // A valid prefix followed by other tokens makes the whole declaration invalid.
+            if let Err(error) = parser.expect_exhausted() {
+                self.block.declarations.truncate(previous_len);
+                return Err(error.into());
+            }
             let count = self.block.declarations.len() - previous_len;
             assert!(count > 0);
             self.block.important.extend(repeat(important).take(count));
@@ -111,6 +176,8 @@ impl<'i> DeclarationParser<'i> for LonghandDeclarationParser {
             any.early |= parsed.early;
             any.late |= parsed.late;
             Ok(())
+        } else if is_dynamic_property(&name) {
+            Err(parser.new_custom_error(PropertyParseErrorKind::DynamicProperty(name)))
         } else {
             Err(parser.new_custom_error(PropertyParseErrorKind::UnknownProperty(name)))
         }
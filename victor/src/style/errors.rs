@@ -1,15 +1,40 @@
-use cssparser::{CowRcStr, ParseError};
+use crate::dom::CssDiagnosticKind;
+use crate::style::selectors::SelectorList;
+use cssparser::{BasicParseErrorKind, CowRcStr, ParseError, ParseErrorKind, SourceLocation};
+use cssparser::{DeclarationListParser, Parser, RuleListParser};
 use selectors::parser::SelectorParseErrorKind;
 
 pub(super) type PropertyParseError<'i> = ParseError<'i, PropertyParseErrorKind<'i>>;
 
 pub(super) enum PropertyParseErrorKind<'i> {
     UnknownProperty(CowRcStr<'i>),
+    /// A property that cannot be rendered in a static PDF document, see `is_dynamic_property`.
+    DynamicProperty(CowRcStr<'i>),
+    /// A function of the `filter` property that needs rasterizing, like `blur()`
+    NotRenderableFunction(CowRcStr<'i>),
     UnknownUnit(CowRcStr<'i>),
+    NegativeValue,
+}
+
+/// Filtering what is behind an element, animations and transitions
+/// only make sense in a browser: Victor will never render them,
+/// and reports them separately from properties that are merely not implemented yet.
+/// (`filter` itself is supported, except for some of its functions.)
+///
+/// Vendor-prefixed names are included, since stylesheets often use them for these properties.
+pub(super) fn is_dynamic_property(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_prefix("-webkit-").unwrap_or(&name);
+    match name {
+        "backdrop-filter" | "animation" | "transition" => true,
+        _ => name.starts_with("animation-") || name.starts_with("transition-"),
+    }
 }
 
 pub(super) enum RuleParseErrorKind<'i> {
     Selector(SelectorParseErrorKind<'i>),
+    /// https://drafts.csswg.org/css-namespaces/#syntax
+    MisplacedNamespaceRule,
 }
 
 impl<'i> From<SelectorParseErrorKind<'i>> for RuleParseErrorKind<'i> {
@@ -17,3 +42,139 @@ impl<'i> From<SelectorParseErrorKind<'i>> for RuleParseErrorKind<'i> {
         RuleParseErrorKind::Selector(e)
     }
 }
+
+/// A rule list or declaration list parser, see `for_each_with_location`.
+pub(super) trait ListParser<'i: 't, 't>: Iterator {
+    fn input(&mut self) -> &mut Parser<'i, 't>;
+}
+
+impl<'i: 't, 't: 'a, 'a, P> ListParser<'i, 't> for RuleListParser<'i, 't, 'a, P>
+where
+    Self: Iterator,
+{
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+}
+
+impl<'i: 't, 't: 'a, 'a, P> ListParser<'i, 't> for DeclarationListParser<'i, 't, 'a, P>
+where
+    Self: Iterator,
+{
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+}
+
+/// Parse each rule or declaration of `list`, and call `f` with the list, the result,
+/// and where the rule or declaration starts, after any whitespace before it.
+/// That location is the one `CssError::in_rule` and `CssError::in_declaration` expect.
+pub(super) fn for_each_with_location<'i: 't, 't, L: ListParser<'i, 't>>(
+    list: &mut L,
+    mut f: impl FnMut(&mut L, L::Item, SourceLocation),
+) {
+    loop {
+        list.input().skip_whitespace();
+        let location = list.input().current_source_location();
+        let result = if let Some(r) = list.next() { r } else { break };
+        f(list, result, location)
+    }
+}
+
+/// Something in a stylesheet or `style` attribute that was skipped or approximated,
+/// see `dom::CssDiagnostic`.
+pub(crate) struct CssError {
+    pub location: SourceLocation,
+    pub message: String,
+    pub kind: CssDiagnosticKind,
+    /// For a declaration in a style rule, the selectors of that rule
+    pub selectors: Option<SelectorList>,
+}
+
+impl CssError {
+    /// `source` is the text of the rule up to where the error was found,
+    /// and `location` where the rule starts.
+    pub(super) fn in_rule(
+        error: ParseError<RuleParseErrorKind>,
+        source: &str,
+        location: SourceLocation,
+    ) -> Self {
+        let message = match &error.kind {
+            ParseErrorKind::Basic(BasicParseErrorKind::AtRuleInvalid(name)) => {
+                format!("unsupported at-rule `@{}`", name)
+            }
+            ParseErrorKind::Custom(RuleParseErrorKind::Selector(_)) => {
+                // The selector is before the block, if any
+                let selector = source.split('{').next().unwrap_or(source);
+                format!("invalid or unsupported selector `{}`", selector.trim())
+            }
+            ParseErrorKind::Custom(RuleParseErrorKind::MisplacedNamespaceRule) => {
+                "`@namespace` rules must come before other rules".to_owned()
+            }
+            _ => format!("invalid rule `{}`", source.trim()),
+        };
+        CssError {
+            location,
+            message,
+            kind: CssDiagnosticKind::Unsupported,
+            selectors: None,
+        }
+    }
+
+    /// `source` is the text of the declaration up to where the error was found,
+    /// and `location` where the declaration starts.
+    pub(super) fn in_declaration(
+        error: ParseError<PropertyParseErrorKind>,
+        source: &str,
+        location: SourceLocation,
+    ) -> Self {
+        let mut kind = CssDiagnosticKind::Unsupported;
+        let message = match &error.kind {
+            ParseErrorKind::Custom(PropertyParseErrorKind::UnknownProperty(name)) => {
+                format!("unsupported property `{}`", name)
+            }
+            ParseErrorKind::Custom(PropertyParseErrorKind::DynamicProperty(name)) => {
+                kind = CssDiagnosticKind::NotRenderable;
+                format!("`{}` cannot be rendered in a static PDF", name)
+            }
+            ParseErrorKind::Custom(PropertyParseErrorKind::NotRenderableFunction(name)) => {
+                kind = CssDiagnosticKind::NotRenderable;
+                format!("`{}()` cannot be rendered in a static PDF", name)
+            }
+            _ => {
+                // The source of a declaration that is not the last one ends with its `;`.
+                let declaration = source.trim().trim_end_matches(';').trim_end();
+                format!("invalid or unsupported declaration `{}`", declaration)
+            }
+        };
+        CssError {
+            location,
+            message,
+            kind,
+            selectors: None,
+        }
+    }
+
+    /// For a declaration at `location` that was parsed successfully,
+    /// but is only rendered approximately.
+    pub(super) fn approximated(location: SourceLocation, message: String) -> Self {
+        CssError {
+            location,
+            message,
+            kind: CssDiagnosticKind::Approximated,
+            selectors: None,
+        }
+    }
+}
+
+#[test]
+fn dynamic_properties() {
+    assert!(is_dynamic_property("backdrop-filter"));
+    assert!(is_dynamic_property("-WebKit-Backdrop-Filter"));
+    assert!(is_dynamic_property("animation-name"));
+    assert!(is_dynamic_property("transition"));
+    // `filter` is rendered, only some of its functions are not
+    assert!(!is_dynamic_property("filter"));
+    assert!(!is_dynamic_property("-webkit-filter"));
+    assert!(!is_dynamic_property("animations"));
+}
@@ -1,10 +1,16 @@
 mod cascade;
+mod counter_styles;
 mod declaration_block;
 mod errors;
+mod page;
 mod properties;
 mod rules;
 mod selectors;
+mod supports;
 pub(crate) mod values;
 
-pub(crate) use self::cascade::{style_for_element, StyleSet, StyleSetBuilder};
+pub(crate) use self::cascade::{document_may_use_counters, stylesheet_errors};
+pub(crate) use self::cascade::{style_attribute_errors, style_for_element};
+pub(crate) use self::cascade::{style_for_pseudo_element, StyleSet, StyleSetBuilder};
 pub(crate) use self::properties::ComputedValues;
+pub(crate) use self::selectors::{matches_any, parse_selector_list, PseudoElement};
@@ -0,0 +1,153 @@
+//! https://drafts.csswg.org/css-page-3/#at-page-rule
+
+use crate::style::errors::{for_each_with_location, CssError};
+use crate::style::errors::{PropertyParseError, PropertyParseErrorKind};
+use crate::style::values::{Length, Parse, SpecifiedLength};
+use cssparser::{AtRuleParser, CowRcStr, DeclarationListParser, DeclarationParser, Parser};
+
+/// The descriptors of `@page` rules that apply to every page.
+/// Those of later rules override earlier ones.
+///
+/// FIXME: page selectors, and the `size` and `margin` descriptors.
+#[derive(Clone, Default)]
+pub(super) struct PageRule {
+    /// `None` for `auto`
+    bleed: Option<Option<SpecifiedLength>>,
+    marks: Option<Marks>,
+}
+
+/// https://drafts.csswg.org/css-page-3/#marks
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Marks {
+    pub crop: bool,
+    pub cross: bool,
+}
+
+impl PageRule {
+    pub(super) fn parse(parser: &mut Parser, mut errors: Option<&mut Vec<CssError>>) -> Self {
+        let mut iter = DeclarationListParser::new(parser, DescriptorParser(Self::default()));
+        for_each_with_location(&mut iter, |_, result, location| {
+            if let (Err((error, source)), Some(errors)) = (result, errors.as_deref_mut()) {
+                errors.push(CssError::in_declaration(error, source, location))
+            }
+        });
+        iter.parser.0
+    }
+
+    pub(super) fn cascade(&mut self, later: PageRule) {
+        if later.bleed.is_some() {
+            self.bleed = later.bleed
+        }
+        if later.marks.is_some() {
+            self.marks = later.marks
+        }
+    }
+
+    pub(super) fn marks(&self) -> Marks {
+        self.marks.unwrap_or_default()
+    }
+
+    /// https://drafts.csswg.org/css-page-3/#bleed
+    pub(super) fn bleed(&self) -> Length {
+        match &self.bleed {
+            Some(Some(SpecifiedLength::Absolute(length))) => *length,
+            // The page context has the initial `font-size`.
+            Some(Some(SpecifiedLength::Em(value))) => Length::from_px(16.) * *value,
+            // `auto` is 6pt with crop marks, and zero otherwise.
+            Some(None) | None if self.marks().crop => Length::from_px(8.),
+            Some(None) | None => Length::zero(),
+        }
+    }
+}
+
+struct DescriptorParser(PageRule);
+
+impl<'i> DeclarationParser<'i> for DescriptorParser {
+    type Declaration = ();
+    type Error = PropertyParseErrorKind<'i>;
+
+    fn parse_value<'t>(
+        &mut self,
+        name: CowRcStr<'i>,
+        parser: &mut Parser<'i, 't>,
+    ) -> Result<(), PropertyParseError<'i>> {
+        let rule = &mut self.0;
+        match_ignore_ascii_case! { &name,
+            "bleed" => {
+                rule.bleed = if parser.r#try(|p| p.expect_ident_matching("auto")).is_ok() {
+                    Some(None)
+                } else {
+                    Some(Some(SpecifiedLength::parse(parser)?))
+                }
+            },
+            "marks" => {
+                // none | [ crop || cross ]
+                let mut marks = Marks::default();
+                if parser.r#try(|p| p.expect_ident_matching("none")).is_err() {
+                    loop {
+                        let location = parser.current_source_location();
+                        let ident = match parser.r#try(|p| p.expect_ident_cloned()) {
+                            Ok(ident) => ident,
+                            Err(_) if marks != Marks::default() => break,
+                            Err(_) => return Err(parser.new_error_for_next_token()),
+                        };
+                        let mark = match_ignore_ascii_case! { &ident,
+                            "crop" => &mut marks.crop,
+                            "cross" => &mut marks.cross,
+                            _ => return Err(location.new_unexpected_token_error(
+                                cssparser::Token::Ident(ident)
+                            )),
+                        };
+                        if *mark {
+                            return Err(location.new_unexpected_token_error(
+                                cssparser::Token::Ident(ident)
+                            ));
+                        }
+                        *mark = true
+                    }
+                }
+                rule.marks = Some(marks)
+            },
+            _ => return Err(parser.new_custom_error(PropertyParseErrorKind::UnknownProperty(name))),
+        }
+        parser.expect_exhausted()?;
+        Ok(())
+    }
+}
+
+impl<'i> AtRuleParser<'i> for DescriptorParser {
+    type PreludeNoBlock = ();
+    type PreludeBlock = ();
+    type AtRule = ();
+    type Error = PropertyParseErrorKind<'i>;
+}
+
+#[test]
+fn page_rule() {
+    let parse = |css| {
+        PageRule::parse(
+            &mut Parser::new(&mut cssparser::ParserInput::new(css)),
+            None,
+        )
+    };
+    let rule = parse("bleed: 2px; marks: cross crop");
+    assert_eq!(rule.bleed(), Length::from_px(2.));
+    assert_eq!(
+        rule.marks(),
+        Marks {
+            crop: true,
+            cross: true
+        }
+    );
+    // 6pt
+    assert_eq!(parse("marks: crop").bleed(), Length::from_px(8.));
+    assert_eq!(parse("marks: cross; bleed: auto").bleed(), Length::zero());
+    assert_eq!(parse("marks: crop crop").marks(), Marks::default());
+    assert_eq!(
+        parse("marks: none; bleed: 1em").bleed(),
+        Length::from_px(16.)
+    );
+    let mut cascaded = parse("marks: crop; bleed: 1px");
+    cascaded.cascade(parse("bleed: auto"));
+    assert_eq!(cascaded.bleed(), Length::from_px(8.));
+}
@@ -5,7 +5,26 @@ properties! {
     type Discriminant = u8;
 
     inherited struct font {
-        @early font_size { "font-size", FontSize, initial = Length { px: 16. } }
+        @early font_size { "font-size", FontSize, initial = Length::from_px(16.) }
+        font_family { "font-family", FontFamily, initial = FontFamily::INITIAL }
+        font_weight { "font-weight", FontWeight, initial = FontWeight::NORMAL }
+        font_stretch { "font-stretch", FontStretch, initial = FontStretch::NORMAL }
+        font_style { "font-style", FontStyle, initial = FontStyle::Normal }
+        font_synthesis { "font-synthesis", FontSynthesis, initial = FontSynthesis::INITIAL }
+        font_variation_settings {
+            "font-variation-settings", FontVariationSettings,
+            initial = FontVariationSettings::Normal
+        }
+        font_feature_settings {
+            "font-feature-settings", FontFeatureSettings, initial = FontFeatureSettings::Normal
+        }
+        font_variant_ligatures {
+            "font-variant-ligatures", FontVariantLigatures, initial = FontVariantLigatures::NORMAL
+        }
+        font_variant_caps { "font-variant-caps", FontVariantCaps, initial = FontVariantCaps::Normal }
+        font_variant_numeric {
+            "font-variant-numeric", FontVariantNumeric, initial = FontVariantNumeric::NORMAL
+        }
     }
 
     inherited struct color {
@@ -13,16 +32,71 @@ properties! {
         color { "color", RGBA, initial = BLACK }
     }
 
+    inherited struct inherited_box {
+        direction { "direction", Direction, initial = Direction::Ltr }
+        image_orientation {
+            "image-orientation", ImageOrientation, initial = ImageOrientation::FromImage
+        }
+    }
+
+    inherited struct text {
+        // https://compat.spec.whatwg.org/#text-fill-and-stroking
+        webkit_text_fill_color {
+            "-webkit-text-fill-color", Color, initial = Color::CurrentColor
+        }
+        webkit_text_stroke_color {
+            "-webkit-text-stroke-color", Color, initial = Color::CurrentColor
+        }
+        webkit_text_stroke_width {
+            "-webkit-text-stroke-width", LineWidth, initial = LineWidth::ZERO
+        }
+        text_shadow { "text-shadow", TextShadow, initial = TextShadow::none() }
+        white_space { "white-space", WhiteSpace, initial = WhiteSpace::Normal }
+        text_decoration_skip_ink {
+            "text-decoration-skip-ink", TextDecorationSkipInk, initial = TextDecorationSkipInk::Auto
+        }
+    }
+
+    reset struct text_decoration {
+        text_decoration_line {
+            "text-decoration-line", TextDecorationLine, initial = TextDecorationLine::NONE
+        }
+        text_decoration_color {
+            "text-decoration-color", Color, initial = Color::CurrentColor
+        }
+    }
+
     reset struct box_ {
         position { "position", Position, initial = Position::Static }
+        z_index { "z-index", ZIndex, initial = ZIndex::Auto }
         float { "float", Float, initial = Float::None }
+        clear { "clear", Clear, initial = Clear::None }
+        shape_outside { "shape-outside", ShapeOutside, initial = ShapeOutside::None }
+        contain { "contain", Contain, initial = Contain::NONE }
+        content_visibility {
+            "content-visibility", ContentVisibility, initial = ContentVisibility::Visible
+        }
         display { "display", Display, initial = Display::INITIAL }
+        overflow { "overflow", Overflow, initial = Overflow::Visible }
+        text_overflow { "text-overflow", TextOverflow, initial = TextOverflow::Clip }
         top { "top", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
         left { "left", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
         bottom { "bottom", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
         right { "right", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
         width { "width", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
         height { "height", LengthOrPercentageOrAuto, initial = LengthOrPercentageOrAuto::Auto }
+        transform { "transform", Transform, initial = Transform::none() }
+        transform_origin {
+            "transform-origin", TransformOrigin, initial = TransformOrigin::center()
+        }
+        filter { "filter", Filter, initial = Filter::none() }
+        mix_blend_mode { "mix-blend-mode", MixBlendMode, initial = MixBlendMode::Normal }
+        isolation { "isolation", Isolation, initial = Isolation::Auto }
+        clip_path { "clip-path", ClipPath, initial = ClipPath::None }
+        object_fit { "object-fit", ObjectFit, initial = ObjectFit::Fill }
+        object_position {
+            "object-position", ObjectPosition, initial = ObjectPosition::center()
+        }
     }
 
     reset struct margin {
@@ -54,10 +128,37 @@ properties! {
         border_left_width { "border-left-width", LineWidth, initial = LineWidth::MEDIUM }
         border_bottom_width { "border-bottom-width", LineWidth, initial = LineWidth::MEDIUM }
         border_right_width { "border-right-width", LineWidth, initial = LineWidth::MEDIUM }
+
+        box_decoration_break {
+            "box-decoration-break", BoxDecorationBreak, initial = BoxDecorationBreak::Slice
+        }
+    }
+
+    reset struct generated {
+        content { "content", Content, initial = Content::Normal }
+        counter_reset { "counter-reset", CounterChanges, initial = CounterChanges::none() }
+        counter_increment {
+            "counter-increment", CounterChanges, initial = CounterChanges::none()
+        }
+        counter_set { "counter-set", CounterChanges, initial = CounterChanges::none() }
+    }
+
+    inherited struct list {
+        list_style_type { "list-style-type", ListStyleType, initial = ListStyleType::disc() }
+        list_style_position {
+            "list-style-position", ListStylePosition, initial = ListStylePosition::Outside
+        }
     }
 
     reset struct background {
         background_color { "background-color", Color, initial = Color::RGBA(RGBA::transparent()) }
+        background_image {
+            "background-image", BackgroundImage, initial = BackgroundImage::None
+        }
+        background_origin {
+            "background-origin", BackgroundBox, initial = BackgroundBox::Padding
+        }
+        background_clip { "background-clip", BackgroundBox, initial = BackgroundBox::Border }
     }
 
     @shorthands {
@@ -96,8 +197,23 @@ properties! {
             color: border_top_color,
             width: border_top_width,
         }
+        "-webkit-text-stroke" => TextStroke {
+            width: webkit_text_stroke_width,
+            color: webkit_text_stroke_color,
+        }
+        "text-decoration" => TextDecoration {
+            line: text_decoration_line,
+            color: text_decoration_color,
+        }
+        "list-style" => ListStyle {
+            style_type: list_style_type,
+            position: list_style_position,
+        }
         "background" => Background {
             color: background_color,
+            image: background_image,
+            origin: background_origin,
+            clip: background_clip,
         }
     }
 }
@@ -8,6 +8,7 @@ macro_rules! properties {
                         $name: expr,
                         $ValueType: ty,
                         initial = $initial_value: expr
+                        $(, implemented = $implemented: expr )?
                     }
                 )+
             }
@@ -70,6 +71,11 @@ macro_rules! properties {
             () => { false };
         }
 
+        macro_rules! is_implemented {
+            ($value: expr) => { $value };
+            () => { true };
+        }
+
         impl LonghandId {
             pub(in crate::style) fn is_early(self) -> bool {
                 [
@@ -78,6 +84,16 @@ macro_rules! properties {
                     )+)+
                 ][self as usize]
             }
+
+            /// Whether layout or painting use this property,
+            /// as opposed to it only going through the cascade.
+            pub(in crate::style) fn is_implemented(self) -> bool {
+                [
+                    $($(
+                        is_implemented!($( $implemented )?),
+                    )+)+
+                ][self as usize]
+            }
         }
 
         macro_rules! if_early {
@@ -167,6 +183,7 @@ macro_rules! properties {
                 pub(crate) $struct_name: Arc<style_structs::$struct_name>,
             )+
             pub(crate) specified_display: Display,
+            pub(crate) text_decorations_in_effect: TextDecorationsInEffect,
         }
 
         impl std::fmt::Debug for ComputedValues {
@@ -205,6 +222,7 @@ macro_rules! properties {
                             ),
                         )+
                         specified_display: Display::INITIAL,
+                        text_decorations_in_effect: TextDecorationsInEffect::default(),
                     };
                 }
                 let inherited = inherited.unwrap_or(&*INITIAL_VALUES);
@@ -217,6 +235,7 @@ macro_rules! properties {
                         $struct_name: Arc::clone(&select!($inherited).$struct_name),
                     )+
                     specified_display: Display::INITIAL,
+                    text_decorations_in_effect: inherited.text_decorations_in_effect,
                 };
                 if let Some(matching) = matching {
                     matching.cascade(&mut crate::style::values::EarlyCascadeContext {
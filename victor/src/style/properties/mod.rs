@@ -1,12 +1,15 @@
 pub(crate) use self::definitions::ComputedValues;
-use self::definitions::LonghandId;
-pub(super) use self::definitions::{property_data_by_name, LonghandDeclaration};
+pub(super) use self::definitions::{property_data_by_name, LonghandDeclaration, LonghandId};
 pub(super) use self::definitions::{ComputedValuesForEarlyCascade, ComputedValuesForLateCascade};
 use crate::geom::{flow_relative, physical};
 use crate::style::errors::PropertyParseError;
-use crate::style::values::{self, CssWideKeyword, Direction, Display, WritingMode};
+use crate::style::values::{
+    self, ClipPath, ContentVisibility, CssWideKeyword, Direction, Display, Isolation, MixBlendMode,
+    Position, TextDecorationsInEffect, WritingMode, ZIndex,
+};
 use crate::style::values::{CascadeContext, EarlyCascadeContext};
 use cssparser::{Color, RGBA};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 #[macro_use]
@@ -27,21 +30,94 @@ impl ComputedValues {
         b.border_right_width.fixup(b.border_right_style);
 
         Display::fixup(self);
+        TextDecorationsInEffect::fixup(self);
     }
 
     pub(crate) fn writing_mode(&self) -> (WritingMode, Direction) {
-        // FIXME: For now, this is the only supported mode
-        (WritingMode::HorizontalTb, Direction::Ltr)
+        // FIXME: For now, horizontal-tb is the only supported writing mode.
+        (WritingMode::HorizontalTb, self.inherited_box.direction)
     }
 
-    pub(crate) fn box_offsets(&self) -> flow_relative::Sides<values::LengthOrPercentageOrAuto> {
+    /// The writing mode that the contents of this box are laid out in,
+    /// in a containing block with the writing mode `containing_block_mode`.
+    ///
+    /// FIXME: orthogonal flows are not supported:
+    /// https://drafts.csswg.org/css-writing-modes/#orthogonal-flows
+    /// A box whose block flow direction differs from that of its containing block
+    /// is laid out in the containing block’s, with its own `direction`.
+    pub(crate) fn writing_mode_in(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> (WritingMode, Direction) {
+        let (_, direction) = self.writing_mode();
+        (containing_block_mode.0, direction)
+    }
+
+    /// https://drafts.csswg.org/css-contain-1/#containment-layout
+    /// https://drafts.csswg.org/css-contain-1/#containment-paint
+    ///
+    /// Both make the box establish an independent formatting context
+    /// and a containing block for absolutely positioned descendants.
+    /// (Style containment only affects counters, see `layout::counters`, and quotes.)
+    pub(crate) fn has_layout_or_paint_containment(&self) -> bool {
+        // FIXME: paint containment should also clip contents to the padding box,
+        // and `contain: size` should size the box as if it had no contents.
+        self.box_.contain.layout
+            || self.box_.contain.paint
+            || self.box_.content_visibility == ContentVisibility::Hidden
+    }
+
+    /// Whether absolutely positioned descendants are positioned relative to this box
+    /// rather than to an ancestor.
+    ///
+    /// https://drafts.csswg.org/css-position-3/#def-cb
+    pub(crate) fn establishes_containing_block_for_absolute_descendants(&self) -> bool {
+        self.box_.position.is_relatively_positioned()
+            || self.establishes_containing_block_for_fixed_descendants()
+    }
+
+    /// Whether `position: fixed` descendants are positioned relative to this box
+    /// rather than to the page area.
+    ///
+    /// https://drafts.csswg.org/css-transforms-1/#containing-block-for-all-descendants
+    /// https://drafts.csswg.org/css-contain-2/#containment-layout
+    pub(crate) fn establishes_containing_block_for_fixed_descendants(&self) -> bool {
+        !self.box_.transform.is_none() || self.has_layout_or_paint_containment()
+    }
+
+    /// Whether this box is painted as a stacking context, with its descendants:
+    /// https://drafts.csswg.org/css2/zindex.html
+    /// https://drafts.fxtf.org/compositing-1/#csscompositingrules_CSS
+    pub(crate) fn establishes_stacking_context(&self) -> bool {
+        let box_ = &self.box_;
+        let positioned_with_z_index =
+            box_.position != Position::Static && box_.z_index != ZIndex::Auto;
+        positioned_with_z_index
+            || matches!(box_.position, Position::Fixed | Position::Sticky)
+            || !box_.transform.is_none()
+            || !box_.filter.0.is_empty()
+            || !matches!(box_.clip_path, ClipPath::None)
+            || box_.mix_blend_mode != MixBlendMode::Normal
+            || box_.isolation == Isolation::Isolate
+            || self.has_layout_or_paint_containment()
+    }
+
+    /// Like `padding`, `border_width` and `margin`, flow-relative in the writing mode
+    /// of the containing block that lays out the box, not in the box’s own:
+    /// for example, the margin ignored when a width is over-constrained
+    /// is at the end of the containing block’s direction.
+    /// https://drafts.csswg.org/css2/visudet.html#blockwidth
+    pub(crate) fn box_offsets(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> flow_relative::Sides<values::LengthOrPercentageOrAuto> {
         physical::Sides {
             top: self.box_.top,
             left: self.box_.left,
             bottom: self.box_.bottom,
             right: self.box_.right,
         }
-        .to_flow_relative(self.writing_mode())
+        .to_flow_relative(containing_block_mode)
     }
 
     pub(crate) fn box_size(&self) -> flow_relative::Vec2<values::LengthOrPercentageOrAuto> {
@@ -52,34 +128,43 @@ impl ComputedValues {
         .size_to_flow_relative(self.writing_mode())
     }
 
-    pub(crate) fn padding(&self) -> flow_relative::Sides<values::LengthOrPercentage> {
+    pub(crate) fn padding(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> flow_relative::Sides<values::LengthOrPercentage> {
         physical::Sides {
             top: self.padding.padding_top,
             left: self.padding.padding_left,
             bottom: self.padding.padding_bottom,
             right: self.padding.padding_right,
         }
-        .to_flow_relative(self.writing_mode())
+        .to_flow_relative(containing_block_mode)
     }
 
-    pub(crate) fn border_width(&self) -> flow_relative::Sides<values::LengthOrPercentage> {
+    pub(crate) fn border_width(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> flow_relative::Sides<values::LengthOrPercentage> {
         physical::Sides {
             top: self.border.border_top_width.0,
             left: self.border.border_left_width.0,
             bottom: self.border.border_bottom_width.0,
             right: self.border.border_right_width.0,
         }
-        .to_flow_relative(self.writing_mode())
+        .to_flow_relative(containing_block_mode)
     }
 
-    pub(crate) fn margin(&self) -> flow_relative::Sides<values::LengthOrPercentageOrAuto> {
+    pub(crate) fn margin(
+        &self,
+        containing_block_mode: (WritingMode, Direction),
+    ) -> flow_relative::Sides<values::LengthOrPercentageOrAuto> {
         physical::Sides {
             top: self.margin.margin_top,
             left: self.margin.margin_left,
             bottom: self.margin.margin_bottom,
             right: self.margin.margin_right,
         }
-        .to_flow_relative(self.writing_mode())
+        .to_flow_relative(containing_block_mode)
     }
 
     pub(crate) fn to_rgba(&self, color: Color) -> RGBA {
@@ -88,6 +173,57 @@ impl ComputedValues {
             Color::CurrentColor => self.color.color,
         }
     }
+
+    /// Values of OpenType variation axes, sorted by tag.
+    ///
+    /// https://drafts.csswg.org/css-fonts-4/#font-variation-resolution
+    pub(crate) fn font_variations(&self) -> Vec<([u8; 4], f32)> {
+        let font = &self.font;
+        let mut variations = BTreeMap::new();
+        variations.insert(*b"wght", font.font_weight.0);
+        variations.insert(*b"wdth", font.font_stretch.0);
+        match font.font_style {
+            values::FontStyle::Normal => {}
+            values::FontStyle::Italic => {
+                variations.insert(*b"ital", 1.);
+            }
+            // `slnt` angles are counter-clockwise
+            values::FontStyle::Oblique(degrees) => {
+                variations.insert(*b"slnt", -degrees);
+            }
+        }
+        // The initial `font-optical-sizing: auto`
+        variations.insert(*b"opsz", font.font_size.0.px());
+        if let values::FontVariationSettings::Settings(settings) = &font.font_variation_settings {
+            variations.extend(settings.iter().cloned())
+        }
+        variations.into_iter().collect()
+    }
+
+    /// OpenType features and their values, sorted by tag.
+    ///
+    /// https://drafts.csswg.org/css-fonts-4/#feature-precedence
+    ///
+    /// FIXME: `locl` and other features required by the script and language of the text.
+    pub(crate) fn font_features(&self) -> Vec<([u8; 4], u32)> {
+        let font = &self.font;
+        let default_features = [b"ccmp", b"rlig", b"liga", b"clig", b"calt"]
+            .iter()
+            .map(|&&tag| (tag, 1));
+        let settings = match &font.font_feature_settings {
+            values::FontFeatureSettings::Normal => &[],
+            values::FontFeatureSettings::Settings(settings) => &settings[..],
+        };
+        // Later values override earlier ones for the same feature.
+        default_features
+            .chain(font.font_variant_ligatures.features())
+            .chain(font.font_variant_caps.features())
+            .chain(font.font_variant_numeric.features())
+            .chain(settings.iter().cloned())
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect()
+    }
 }
 
 pub(super) trait Phase {
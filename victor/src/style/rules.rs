@@ -1,7 +1,13 @@
+use crate::style::counter_styles::CounterStyleRule;
 use crate::style::declaration_block::DeclarationBlock;
-use crate::style::errors::RuleParseErrorKind;
-use crate::style::selectors::{self, SelectorList};
-use cssparser::{AtRuleParser, ParseError, Parser, QualifiedRuleParser, SourceLocation};
+use crate::style::errors::{for_each_with_location, CssError, RuleParseErrorKind};
+use crate::style::page::PageRule;
+use crate::style::selectors::{self, Namespaces, SelectorList};
+use crate::style::supports;
+use crate::style::values::CounterStyleName;
+use cssparser::{AtRuleParser, AtRuleType, BasicParseErrorKind, CowRcStr, ParseError, Parser};
+use cssparser::{QualifiedRuleParser, RuleListParser, SourceLocation, Token};
+use html5ever::{Namespace, Prefix};
 use std::sync::Arc;
 
 pub(super) enum CssRule {
@@ -15,11 +21,54 @@ pub(super) enum CssRule {
         // Use `Arc` to enable having multiple references to the `Vec` without cloning it.
         block: Arc<DeclarationBlock>,
     },
+
+    /// https://drafts.csswg.org/css-conditional-3/#at-supports
+    ///
+    /// The condition is evaluated when parsing,
+    /// `rules` is empty if it is false.
+    Supports { rules: Vec<CssRule> },
+
+    /// https://drafts.csswg.org/css-namespaces/
+    ///
+    /// Recorded in `RulesParser::namespaces` when parsing, for the selectors of later rules.
+    Namespace,
+
+    /// https://drafts.csswg.org/css-counter-styles-3/#the-counter-style-rule
+    CounterStyle {
+        name: CounterStyleName,
+        rule: Arc<CounterStyleRule>,
+    },
+
+    /// https://drafts.csswg.org/css-page-3/#at-page-rule
+    Page(PageRule),
 }
 
-pub(super) struct RulesParser;
+pub(super) enum AtRulePrelude {
+    Supports(bool),
+    CounterStyle(CounterStyleName),
+    Page,
+}
 
-impl<'i> QualifiedRuleParser<'i> for RulesParser {
+pub(super) struct RulesParser<'a> {
+    /// Where to record skipped rules and declarations, if anywhere
+    pub errors: Option<&'a mut Vec<CssError>>,
+    pub namespaces: Namespaces,
+    /// Whether a rule other than `@namespace` was parsed,
+    /// after which `@namespace` rules are invalid.
+    pub other_rules_parsed: bool,
+}
+
+impl<'a> RulesParser<'a> {
+    pub fn new(errors: Option<&'a mut Vec<CssError>>) -> Self {
+        RulesParser {
+            errors,
+            namespaces: Namespaces::default(),
+            other_rules_parsed: false,
+        }
+    }
+}
+
+impl<'i, 'a> QualifiedRuleParser<'i> for RulesParser<'a> {
     type Prelude = SelectorList;
     type QualifiedRule = CssRule;
     type Error = RuleParseErrorKind<'i>;
@@ -28,7 +77,8 @@ impl<'i> QualifiedRuleParser<'i> for RulesParser {
         &mut self,
         parser: &mut Parser<'i, 't>,
     ) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
-        SelectorList::parse(&selectors::Parser, parser)
+        let namespaces = &self.namespaces;
+        SelectorList::parse(&selectors::Parser { namespaces }, parser)
     }
 
     fn parse_block<'t>(
@@ -37,16 +87,120 @@ impl<'i> QualifiedRuleParser<'i> for RulesParser {
         _location: SourceLocation,
         parser: &mut Parser<'i, 't>,
     ) -> Result<Self::QualifiedRule, ParseError<'i, Self::Error>> {
+        self.other_rules_parsed = true;
+        let previous_errors = self.errors.as_deref().map_or(0, Vec::len);
+        let block = Arc::new(DeclarationBlock::parse(parser, self.errors.as_deref_mut()));
+        if let Some(errors) = self.errors.as_deref_mut() {
+            for error in &mut errors[previous_errors..] {
+                error.selectors = Some(prelude.clone())
+            }
+        }
         Ok(CssRule::StyleRule {
             selectors: prelude,
-            block: Arc::new(DeclarationBlock::parse(parser)),
+            block,
         })
     }
 }
 
-impl<'i> AtRuleParser<'i> for RulesParser {
-    type PreludeNoBlock = ();
-    type PreludeBlock = ();
+impl<'i, 'a> AtRuleParser<'i> for RulesParser<'a> {
+    /// The prefix and URL of a `@namespace` rule
+    type PreludeNoBlock = (Option<Prefix>, Namespace);
+    type PreludeBlock = AtRulePrelude;
     type AtRule = CssRule;
     type Error = RuleParseErrorKind<'i>;
+
+    fn parse_prelude<'t>(
+        &mut self,
+        name: CowRcStr<'i>,
+        parser: &mut Parser<'i, 't>,
+    ) -> Result<AtRuleType<Self::PreludeNoBlock, AtRulePrelude>, ParseError<'i, Self::Error>> {
+        match_ignore_ascii_case! { &name,
+            "supports" => Ok(AtRuleType::WithBlock(
+                AtRulePrelude::Supports(supports::parse_condition(parser, &self.namespaces)?)
+            )),
+            "namespace" => {
+                if self.other_rules_parsed {
+                    return Err(parser.new_custom_error(RuleParseErrorKind::MisplacedNamespaceRule))
+                }
+                // @namespace <namespace-prefix>? [ <string> | <url> ] ;
+                let prefix = parser.r#try(|p| p.expect_ident_cloned()).ok();
+                let url = parser.expect_url_or_string()?;
+                Ok(AtRuleType::WithoutBlock((
+                    prefix.map(|prefix| Prefix::from(&*prefix)),
+                    Namespace::from(&*url),
+                )))
+            },
+            "counter-style" => {
+                let location = parser.current_source_location();
+                let ident = parser.expect_ident()?.clone();
+                match CounterStyleName::from_ident(&ident) {
+                    Some(name) => Ok(AtRuleType::WithBlock(AtRulePrelude::CounterStyle(name))),
+                    None => Err(location.new_unexpected_token_error(Token::Ident(ident))),
+                }
+            },
+            "page" => {
+                // FIXME: page selectors
+                parser.expect_exhausted()?;
+                Ok(AtRuleType::WithBlock(AtRulePrelude::Page))
+            },
+            _ => Err(parser.new_error(BasicParseErrorKind::AtRuleInvalid(name))),
+        }
+    }
+
+    fn rule_without_block(
+        &mut self,
+        (prefix, url): Self::PreludeNoBlock,
+        _location: SourceLocation,
+    ) -> CssRule {
+        match prefix {
+            Some(prefix) => {
+                self.namespaces.prefixes.insert(prefix, url);
+            }
+            None => self.namespaces.default = Some(url),
+        }
+        CssRule::Namespace
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        prelude: AtRulePrelude,
+        _location: SourceLocation,
+        parser: &mut Parser<'i, 't>,
+    ) -> Result<CssRule, ParseError<'i, Self::Error>> {
+        self.other_rules_parsed = true;
+        match prelude {
+            AtRulePrelude::Supports(true) => {
+                let nested = RulesParser {
+                    errors: self.errors.as_deref_mut(),
+                    namespaces: self.namespaces.clone(),
+                    other_rules_parsed: true,
+                };
+                let mut iter = RuleListParser::new_for_nested_rule(parser, nested);
+                let mut rules = Vec::new();
+                for_each_with_location(&mut iter, |iter, result, location| match result {
+                    Ok(rule) => rules.push(rule),
+                    Err((error, source)) => {
+                        if let Some(errors) = iter.parser.errors.as_deref_mut() {
+                            errors.push(CssError::in_rule(error, source, location))
+                        }
+                    }
+                });
+                Ok(CssRule::Supports { rules })
+            }
+            AtRulePrelude::Supports(false) => Ok(CssRule::Supports { rules: Vec::new() }),
+            AtRulePrelude::CounterStyle(name) => {
+                match CounterStyleRule::parse(parser, self.errors.as_deref_mut()) {
+                    Some(rule) => Ok(CssRule::CounterStyle {
+                        name,
+                        rule: Arc::new(rule),
+                    }),
+                    None => Err(parser.new_error(BasicParseErrorKind::AtRuleBodyInvalid)),
+                }
+            }
+            AtRulePrelude::Page => Ok(CssRule::Page(PageRule::parse(
+                parser,
+                self.errors.as_deref_mut(),
+            ))),
+        }
+    }
 }
@@ -1,16 +1,43 @@
-use crate::dom::{Document, Node, NodeId};
+use crate::dom::{Atom, Document, Node, NodeId};
 use crate::style::errors::RuleParseErrorKind;
-use cssparser::ToCss;
+use cssparser::{CowRcStr, SourceLocation, ToCss};
 use html5ever::{LocalName, Namespace, Prefix};
 use selectors::attr::{AttrSelectorOperation, CaseSensitivity, NamespaceConstraint};
 use selectors::context::{MatchingContext, MatchingMode, QuirksMode};
 use selectors::matching::{matches_selector, ElementSelectorFlags};
+use selectors::parser::SelectorParseErrorKind;
+use std::collections::HashMap;
 use std::fmt;
 
-pub(super) type SelectorList = selectors::SelectorList<Impl>;
+pub(crate) type SelectorList = selectors::SelectorList<Impl>;
 pub(super) type Selector = selectors::parser::Selector<Impl>;
 
 pub(super) fn matches(selector: &Selector, document: &Document, element: NodeId) -> bool {
+    matches_in_mode(selector, document, element, MatchingMode::Normal)
+}
+
+/// Whether `selector` matches the given pseudo-element of `element`.
+pub(super) fn matches_pseudo_element(
+    selector: &Selector,
+    document: &Document,
+    element: NodeId,
+    pseudo_element: &PseudoElement,
+) -> bool {
+    selector.pseudo_element() == Some(pseudo_element)
+        && matches_in_mode(
+            selector,
+            document,
+            element,
+            MatchingMode::ForStatelessPseudoElement,
+        )
+}
+
+fn matches_in_mode(
+    selector: &Selector,
+    document: &Document,
+    element: NodeId,
+    mode: MatchingMode,
+) -> bool {
     matches_selector(
         selector,
         0,
@@ -19,34 +46,79 @@ pub(super) fn matches(selector: &Selector, document: &Document, element: NodeId)
             document,
             node: element,
         },
-        &mut MatchingContext::new(MatchingMode::Normal, None, None, QuirksMode::NoQuirks),
+        &mut MatchingContext::new(mode, None, None, QuirksMode::NoQuirks),
         &mut |_, _| {},
     )
 }
 
+/// Parse a comma-separated list of selectors, like the argument of `querySelector()`.
+pub(crate) fn parse_selector_list(css: &str) -> Option<SelectorList> {
+    let mut input = cssparser::ParserInput::new(css);
+    let mut parser = cssparser::Parser::new(&mut input);
+    parser
+        .parse_entirely(|parser| {
+            let namespaces = Namespaces::default();
+            SelectorList::parse(
+                &Parser {
+                    namespaces: &namespaces,
+                },
+                parser,
+            )
+        })
+        .ok()
+}
+
+pub(crate) fn matches_any(list: &SelectorList, document: &Document, element: NodeId) -> bool {
+    list.0
+        .iter()
+        .any(|selector| matches(selector, document, element))
+}
+
 #[derive(Clone, Debug)]
-pub(super) struct Impl;
+pub(crate) struct Impl;
 
-pub(super) struct Parser;
+pub(super) struct Parser<'a> {
+    pub namespaces: &'a Namespaces,
+}
 
+/// Declared by `@namespace` rules, for the selectors of the rules after them.
+///
+/// https://drafts.csswg.org/css-namespaces/
+#[derive(Clone, Default)]
+pub(super) struct Namespaces {
+    pub default: Option<Namespace>,
+    pub prefixes: HashMap<Prefix, Namespace>,
+}
+
+/// https://drafts.csswg.org/css-pseudo-4/#generated-content
 #[derive(Clone, PartialEq, Eq)]
-pub(super) enum PseudoElement {}
+pub(crate) enum PseudoElement {
+    Before,
+    After,
+    /// https://drafts.csswg.org/css-pseudo-4/#marker-pseudo
+    Marker,
+}
 
 #[derive(Clone, PartialEq, Eq)]
-pub(super) enum PseudoClass {}
+pub(crate) enum PseudoClass {
+    /// https://drafts.csswg.org/selectors/#the-target-pseudo
+    Target,
+}
 
 impl selectors::parser::NonTSPseudoClass for PseudoClass {
     type Impl = Impl;
     fn is_active_or_hover(&self) -> bool {
-        match *self {}
+        match *self {
+            PseudoClass::Target => false,
+        }
     }
 }
 
 impl selectors::parser::SelectorImpl for Impl {
     type ExtraMatchingData = ();
     type AttrValue = String;
-    type Identifier = String;
-    type ClassName = String;
+    type Identifier = Atom;
+    type ClassName = Atom;
     type LocalName = LocalName;
     type NamespaceUrl = Namespace;
     type NamespacePrefix = Prefix;
@@ -56,9 +128,45 @@ impl selectors::parser::SelectorImpl for Impl {
     type PseudoElement = PseudoElement;
 }
 
-impl<'i> selectors::parser::Parser<'i> for Parser {
+impl<'i> selectors::parser::Parser<'i> for Parser<'_> {
     type Impl = Impl;
     type Error = RuleParseErrorKind<'i>;
+
+    fn default_namespace(&self) -> Option<Namespace> {
+        self.namespaces.default.clone()
+    }
+
+    fn namespace_for_prefix(&self, prefix: &Prefix) -> Option<Namespace> {
+        self.namespaces.prefixes.get(prefix).cloned()
+    }
+
+    fn parse_non_ts_pseudo_class(
+        &self,
+        location: SourceLocation,
+        name: CowRcStr<'i>,
+    ) -> Result<PseudoClass, cssparser::ParseError<'i, Self::Error>> {
+        match_ignore_ascii_case! { &name,
+            "target" => Ok(PseudoClass::Target),
+            _ => Err(location.new_custom_error(
+                SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name),
+            )),
+        }
+    }
+
+    fn parse_pseudo_element(
+        &self,
+        location: SourceLocation,
+        name: CowRcStr<'i>,
+    ) -> Result<PseudoElement, cssparser::ParseError<'i, Self::Error>> {
+        match_ignore_ascii_case! { &name,
+            "before" => Ok(PseudoElement::Before),
+            "after" => Ok(PseudoElement::After),
+            "marker" => Ok(PseudoElement::Marker),
+            _ => Err(location.new_custom_error(
+                SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name),
+            )),
+        }
+    }
 }
 
 impl selectors::parser::PseudoElement for PseudoElement {
@@ -66,20 +174,34 @@ impl selectors::parser::PseudoElement for PseudoElement {
 }
 
 impl ToCss for PseudoElement {
-    fn to_css<W>(&self, _dest: &mut W) -> fmt::Result
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result
     where
         W: fmt::Write,
     {
-        match *self {}
+        match *self {
+            PseudoElement::Before => dest.write_str("::before"),
+            PseudoElement::After => dest.write_str("::after"),
+            PseudoElement::Marker => dest.write_str("::marker"),
+        }
     }
 }
 
 impl ToCss for PseudoClass {
-    fn to_css<W>(&self, _dest: &mut W) -> fmt::Result
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result
     where
         W: fmt::Write,
     {
-        match *self {}
+        match *self {
+            PseudoClass::Target => dest.write_str(":target"),
+        }
+    }
+}
+
+fn atoms_eq(a: &Atom, b: &Atom, case_sensitivity: CaseSensitivity) -> bool {
+    match case_sensitivity {
+        // Comparing interned strings is a pointer comparison
+        CaseSensitivity::CaseSensitive => a == b,
+        CaseSensitivity::AsciiCaseInsensitive => a.eq_ignore_ascii_case(b),
     }
 }
 
@@ -147,7 +269,7 @@ impl<'a> selectors::Element for NodeRef<'a> {
     }
 
     fn is_html_element_in_html_document(&self) -> bool {
-        self.node().as_element().unwrap().name.ns == ns!(html) && self.node().in_html_document()
+        self.node().as_element().unwrap().name.ns == ns!(html) && self.document.is_html
     }
 
     fn local_name(&self) -> &LocalName {
@@ -195,15 +317,19 @@ impl<'a> selectors::Element for NodeRef<'a> {
     where
         F: FnMut(&Self, ElementSelectorFlags),
     {
-        match *pseudo_class {}
+        match *pseudo_class {
+            PseudoClass::Target => self.document.target == Some(self.node),
+        }
     }
 
     fn match_pseudo_element(
         &self,
-        pseudo_element: &PseudoElement,
+        _pseudo_element: &PseudoElement,
         _context: &mut MatchingContext<Self::Impl>,
     ) -> bool {
-        match *pseudo_element {}
+        // Pseudo-elements are matched with `matches_pseudo_element`,
+        // an element itself is never one.
+        false
     }
 
     fn is_link(&self) -> bool {
@@ -216,24 +342,24 @@ impl<'a> selectors::Element for NodeRef<'a> {
             && element.get_attr(&local_name!("href")).is_some()
     }
 
-    fn has_id(&self, id: &String, case_sensitivity: CaseSensitivity) -> bool {
+    fn has_id(&self, id: &Atom, case_sensitivity: CaseSensitivity) -> bool {
         self.node()
             .as_element()
             .unwrap()
-            .get_attr(&local_name!("id"))
-            .map_or(false, |attr| {
-                case_sensitivity.eq(id.as_bytes(), attr.as_bytes())
+            .id
+            .as_ref()
+            .map_or(false, |element_id| {
+                atoms_eq(id, element_id, case_sensitivity)
             })
     }
 
-    fn has_class(&self, class: &String, case_sensitivity: CaseSensitivity) -> bool {
+    fn has_class(&self, class: &Atom, case_sensitivity: CaseSensitivity) -> bool {
         self.node()
             .as_element()
             .unwrap()
-            .get_attr(&local_name!("class"))
-            .map_or(false, |attr| {
-                case_sensitivity.eq(class.as_bytes(), attr.as_bytes())
-            })
+            .classes
+            .iter()
+            .any(|element_class| atoms_eq(class, element_class, case_sensitivity))
     }
 
     fn is_empty(&self) -> bool {
@@ -0,0 +1,84 @@
+//! https://drafts.csswg.org/css-conditional-3/#at-supports
+
+use crate::style::declaration_block::parse_supports_declaration;
+use crate::style::errors::RuleParseErrorKind;
+use crate::style::selectors::{self, Namespaces, SelectorList};
+use cssparser::{ParseError, Parser, Token};
+
+/// Parse a `<supports-condition>`, and evaluate it against
+/// the properties, values, and selectors that Victor implements.
+///
+/// The result does not depend on the document, so there is no need to keep the condition.
+/// `selector()` conditions can use the prefixes in `namespaces`.
+pub(super) fn parse_condition<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+    namespaces: &Namespaces,
+) -> Result<bool, ParseError<'i, RuleParseErrorKind<'i>>> {
+    if parser.r#try(|p| p.expect_ident_matching("not")).is_ok() {
+        return Ok(!parse_in_parens(parser, namespaces)?);
+    }
+    let mut result = parse_in_parens(parser, namespaces)?;
+    let location = parser.current_source_location();
+    let keyword = match parser.r#try(|p| p.expect_ident_cloned()) {
+        Ok(keyword) => keyword,
+        Err(_) => return Ok(result),
+    };
+    // `and` and `or` can not be mixed without parentheses
+    let and = match_ignore_ascii_case! { &keyword,
+        "and" => true,
+        "or" => false,
+        _ => return Err(location.new_unexpected_token_error(Token::Ident(keyword))),
+    };
+    loop {
+        let next = parse_in_parens(parser, namespaces)?;
+        result = if and { result && next } else { result || next };
+        let keyword = if and { "and" } else { "or" };
+        if parser.r#try(|p| p.expect_ident_matching(keyword)).is_err() {
+            return Ok(result);
+        }
+    }
+}
+
+/// `<supports-in-parens>`
+fn parse_in_parens<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+    namespaces: &Namespaces,
+) -> Result<bool, ParseError<'i, RuleParseErrorKind<'i>>> {
+    let location = parser.current_source_location();
+    match parser.next()?.clone() {
+        Token::ParenthesisBlock => parser.parse_nested_block(|parser| {
+            if let Ok(result) = parser.r#try(|p| -> Result<_, ParseError<_>> {
+                let result = parse_condition(p, namespaces)?;
+                p.expect_exhausted()?;
+                Ok(result)
+            }) {
+                return Ok(result);
+            }
+            if let Ok(implemented) = parser.r#try(parse_supports_declaration) {
+                return Ok(implemented);
+            }
+            // `<general-enclosed>`, or a declaration that Victor does not parse
+            while parser.next().is_ok() {}
+            Ok(false)
+        }),
+        // https://drafts.csswg.org/css-conditional-4/#at-supports-ext
+        Token::Function(ref name) if name.eq_ignore_ascii_case("selector") => parser
+            .parse_nested_block(|parser| {
+                let supported = parser
+                    .r#try(|p| -> Result<_, ParseError<_>> {
+                        SelectorList::parse(&selectors::Parser { namespaces }, p)?;
+                        p.expect_exhausted()?;
+                        Ok(())
+                    })
+                    .is_ok();
+                while parser.next().is_ok() {}
+                Ok(supported)
+            }),
+        // `<general-enclosed>`, for future extensions of the syntax
+        Token::Function(_) => parser.parse_nested_block(|parser| {
+            while parser.next().is_ok() {}
+            Ok(false)
+        }),
+        token => Err(location.new_unexpected_token_error(token)),
+    }
+}
@@ -1,14 +1,105 @@
 use crate::style::errors::PropertyParseError;
 use cssparser::{Color, Parser};
+use std::sync::Arc;
 
+/// https://drafts.csswg.org/css-backgrounds/#background
+///
+/// FIXME: `<bg-position>`, `<repeat-style>` and `<attachment>`, and more than one layer.
 pub(in crate::style) struct Background {
     pub color: Option<Color>,
+    pub image: Option<BackgroundImage>,
+    pub origin: Option<BackgroundBox>,
+    pub clip: Option<BackgroundBox>,
 }
 
 impl super::Parse for Background {
     fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
-        Ok(Background {
-            color: Some(Color::parse(parser)?),
-        })
+        let mut background = Background {
+            color: None,
+            image: None,
+            origin: None,
+            clip: None,
+        };
+        loop {
+            if background.color.is_none() {
+                if let Ok(color) = parser.r#try(super::Parse::parse) {
+                    background.color = Some(color);
+                    continue;
+                }
+            }
+            if background.image.is_none() {
+                if let Ok(image) = parser.r#try(super::Parse::parse) {
+                    background.image = Some(image);
+                    continue;
+                }
+            }
+            if background.clip.is_none() {
+                if let Ok(box_) = parser.r#try(super::Parse::parse) {
+                    if background.origin.is_none() {
+                        background.origin = Some(box_)
+                    } else {
+                        background.clip = Some(box_)
+                    }
+                    continue;
+                }
+            }
+            break;
+        }
+        if background.color.is_none() && background.image.is_none() && background.origin.is_none() {
+            return Err(parser.new_error_for_next_token());
+        }
+        // A single `<box>` sets both `background-origin` and `background-clip`
+        background.clip = background.clip.or(background.origin);
+        Ok(background)
+    }
+}
+
+/// https://drafts.csswg.org/css-backgrounds/#background-image
+///
+/// Images are tiled from the top-left corner of the `background-origin` box
+/// at their natural size, as with the initial values of `background-position`,
+/// `background-size` and `background-repeat`, which are not supported yet.
+///
+/// FIXME: gradients, and more than one layer.
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) enum BackgroundImage {
+    None,
+    /// Only JPEG images in `data:` URLs are supported, see `layout::JpegImage`.
+    Url(Arc<str>),
+}
+
+impl super::Parse for BackgroundImage {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(BackgroundImage::None);
+        }
+        Ok(BackgroundImage::Url(parser.expect_url()?.as_ref().into()))
+    }
+}
+
+/// https://drafts.csswg.org/css-backgrounds/#typedef-box
+///
+/// FIXME: `background-clip: text`, which paints the background through the glyphs
+/// of descendant text, for example with a PDF text clipping path.
+/// https://drafts.csswg.org/css-backgrounds-4/#valdef-background-clip-text
+#[derive(Copy, Clone, Eq, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum BackgroundBox {
+    Border,
+    Padding,
+    Content,
+}
+
+impl super::Parse for BackgroundBox {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let ident = parser.expect_ident()?;
+        match_ignore_ascii_case!(&ident,
+            "border-box" => Ok(BackgroundBox::Border),
+            "padding-box" => Ok(BackgroundBox::Padding),
+            "content-box" => Ok(BackgroundBox::Content),
+            _ => {
+                let token = cssparser::Token::Ident(ident.clone());
+                Err(parser.new_unexpected_token_error(token))
+            }
+        )
     }
 }
@@ -7,6 +7,18 @@ use cssparser::{Color, Parser};
 pub(crate) enum LineStyle {
     None,
     Solid,
+    Dotted,
+    Dashed,
+}
+
+/// https://drafts.csswg.org/css-break/#break-decoration
+///
+/// FIXME: there is no fragmentation across pages yet, so this only applies
+/// to inline boxes split across lines.
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum BoxDecorationBreak {
+    Slice,
+    Clone,
 }
 
 #[derive(Parse)]
@@ -24,8 +36,10 @@ pub(in crate::style) struct SpecifiedLineWidth(pub SpecifiedLengthOrPercentage);
 pub(crate) struct LineWidth(pub LengthOrPercentage);
 
 impl LineWidth {
+    pub(in crate::style) const ZERO: Self = LineWidth(LengthOrPercentage::Length(Length::zero()));
+
     pub(in crate::style) const MEDIUM: Self =
-        LineWidth(LengthOrPercentage::Length(Length { px: 3. }));
+        LineWidth(LengthOrPercentage::Length(Length::from_whole_px(3)));
 
     pub(in crate::style) fn fixup(&mut self, style: LineStyle) {
         if let LineStyle::None = style {
@@ -43,41 +57,11 @@ impl super::Parse for SpecifiedLineWidth {
             ParsedLineWidth::Other(value) => return Ok(SpecifiedLineWidth(value)),
         };
         Ok(SpecifiedLineWidth(
-            SpecifiedLength::Absolute(Length { px }).into(),
+            SpecifiedLength::Absolute(Length::from_px(px)).into(),
         ))
     }
 }
 
-macro_rules! parse_one_or_more {
-    ($type: ty { $( $field: ident, )+ }) => {
-        impl crate::style::values::Parse for BorderSide {
-            fn parse<'i, 't>(parser: &mut Parser<'i, 't>)
-                -> Result<Self, PropertyParseError<'i>>
-            {
-                let mut values = Self::default();
-                let mut any = false;
-                loop {
-                    $(
-                        if values.$field.is_none() {
-                            if let Ok(value) = parser.r#try(crate::style::values::Parse::parse) {
-                                values.$field = Some(value);
-                                any = true;
-                                continue
-                            }
-                        }
-                    )+
-                    break
-                }
-                if any {
-                    Ok(values)
-                } else {
-                    Err(parser.new_error_for_next_token())
-                }
-            }
-        }
-    };
-}
-
 parse_one_or_more!(BorderSide {
     style,
     color,
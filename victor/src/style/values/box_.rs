@@ -13,7 +13,7 @@ pub(crate) enum Display {
 
 #[allow(dead_code)]
 fn _static_assert_size_of() {
-    let _ = std::mem::transmute::<Display, [u8; 2]>;
+    let _ = std::mem::transmute::<Display, [u8; 3]>;
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -21,10 +21,11 @@ pub(crate) enum DisplayGeneratingBox {
     OutsideInside {
         outside: DisplayOutside,
         inside: DisplayInside,
-        // list_item: bool,
+        /// https://drafts.csswg.org/css-display-3/#list-items
+        list_item: bool,
     },
-    // Layout-internal display types go here:
     // https://drafts.csswg.org/css-display-3/#layout-specific-display
+    LayoutInternal(DisplayLayoutInternal),
 }
 
 /// https://drafts.csswg.org/css-display-3/#outer-role
@@ -39,30 +40,64 @@ pub(crate) enum DisplayOutside {
 pub(crate) enum DisplayInside {
     Flow,
     FlowRoot,
+    /// https://drafts.csswg.org/css-ruby-1/#ruby-display
+    Ruby,
+    /// https://w3c.github.io/mathml-core/#new-display-math-value
+    Math,
+}
+
+/// https://drafts.csswg.org/css-display-3/#layout-specific-display
+///
+/// FIXME: `ruby-base-container` and `ruby-text-container`, for more than one annotation level.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub(crate) enum DisplayLayoutInternal {
+    RubyBase,
+    RubyText,
 }
 
 impl Display {
     pub const INITIAL: Self = Display::GeneratingBox(DisplayGeneratingBox::OutsideInside {
         outside: DisplayOutside::Inline,
         inside: DisplayInside::Flow,
+        list_item: false,
     });
 
     /// https://drafts.csswg.org/css-display-3/#blockify
     pub fn blockify(&self) -> Self {
         match *self {
             Display::GeneratingBox(value) => Display::GeneratingBox(match value {
-                DisplayGeneratingBox::OutsideInside { outside: _, inside } => {
-                    DisplayGeneratingBox::OutsideInside {
-                        outside: DisplayOutside::Block,
-                        inside,
-                    }
-                }
-                // other => other,
+                DisplayGeneratingBox::OutsideInside {
+                    outside: _,
+                    inside,
+                    list_item,
+                } => DisplayGeneratingBox::OutsideInside {
+                    outside: DisplayOutside::Block,
+                    inside,
+                    list_item,
+                },
+                // “If a layout-internal box is blockified,
+                // its inner display type converts to flow so that it becomes a block container.”
+                DisplayGeneratingBox::LayoutInternal(_) => DisplayGeneratingBox::OutsideInside {
+                    outside: DisplayOutside::Block,
+                    inside: DisplayInside::Flow,
+                    list_item: false,
+                },
             }),
             other => other,
         }
     }
 
+    /// https://drafts.csswg.org/css-display-3/#list-items
+    pub fn is_list_item(&self) -> bool {
+        matches!(
+            self,
+            Display::GeneratingBox(DisplayGeneratingBox::OutsideInside {
+                list_item: true,
+                ..
+            })
+        )
+    }
+
     /// https://drafts.csswg.org/css2/visuren.html#dis-pos-flo
     pub fn fixup(style: &mut ComputedValues) {
         style.specified_display = style.box_.display;
@@ -81,24 +116,62 @@ impl super::Parse for Display {
         match &**ident {
             "none" => Ok(Display::None),
             "contents" => Ok(Display::Contents),
-            "block" => Ok(Display::GeneratingBox(
+            "block" => {
+                let inside = if parser.r#try(|p| p.expect_ident_matching("math")).is_ok() {
+                    DisplayInside::Math
+                } else {
+                    DisplayInside::Flow
+                };
+                Ok(Display::GeneratingBox(
+                    DisplayGeneratingBox::OutsideInside {
+                        outside: DisplayOutside::Block,
+                        inside,
+                        list_item: false,
+                    },
+                ))
+            }
+            // FIXME: multi-keyword values, such as `inline list-item`
+            "list-item" => Ok(Display::GeneratingBox(
                 DisplayGeneratingBox::OutsideInside {
                     outside: DisplayOutside::Block,
                     inside: DisplayInside::Flow,
+                    list_item: true,
                 },
             )),
             "flow-root" => Ok(Display::GeneratingBox(
                 DisplayGeneratingBox::OutsideInside {
                     outside: DisplayOutside::Block,
                     inside: DisplayInside::FlowRoot,
+                    list_item: false,
                 },
             )),
             "inline" => Ok(Display::GeneratingBox(
                 DisplayGeneratingBox::OutsideInside {
                     outside: DisplayOutside::Inline,
                     inside: DisplayInside::Flow,
+                    list_item: false,
+                },
+            )),
+            "math" => Ok(Display::GeneratingBox(
+                DisplayGeneratingBox::OutsideInside {
+                    outside: DisplayOutside::Inline,
+                    inside: DisplayInside::Math,
+                    list_item: false,
+                },
+            )),
+            "ruby" => Ok(Display::GeneratingBox(
+                DisplayGeneratingBox::OutsideInside {
+                    outside: DisplayOutside::Inline,
+                    inside: DisplayInside::Ruby,
+                    list_item: false,
                 },
             )),
+            "ruby-base" => Ok(Display::GeneratingBox(
+                DisplayGeneratingBox::LayoutInternal(DisplayLayoutInternal::RubyBase),
+            )),
+            "ruby-text" => Ok(Display::GeneratingBox(
+                DisplayGeneratingBox::LayoutInternal(DisplayLayoutInternal::RubyText),
+            )),
             _ => {
                 let token = cssparser::Token::Ident(ident.clone());
                 Err(parser.new_unexpected_token_error(token))
@@ -124,20 +197,145 @@ impl Float {
     }
 }
 
+/// https://drafts.csswg.org/css2/visuren.html#propdef-clear
+#[derive(Copy, Clone, Debug, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+}
+
 /// https://drafts.csswg.org/css-position-3/#position-property
 #[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
 pub(crate) enum Position {
     Static,
     Relative,
     Absolute,
+    Fixed,
+    Sticky,
 }
 
 impl Position {
+    /// Sticky positioning is a kind of relative positioning:
+    /// https://drafts.csswg.org/css-position-3/#sticky-pos
     pub fn is_relatively_positioned(self) -> bool {
-        self == Position::Relative
+        matches!(self, Position::Relative | Position::Sticky)
     }
 
+    /// Fixed positioning is a kind of absolute positioning:
+    /// https://drafts.csswg.org/css-position-3/#fixed-pos
     pub fn is_absolutely_positioned(self) -> bool {
-        self == Position::Absolute
+        matches!(self, Position::Absolute | Position::Fixed)
+    }
+}
+
+/// https://drafts.csswg.org/css2/visuren.html#z-index
+///
+/// FIXME: this only makes positioned boxes stacking contexts,
+/// it does not change the order in which they are painted.
+#[derive(Copy, Clone, Eq, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum ZIndex {
+    Auto,
+    Integer(i32),
+}
+
+impl super::Parse for ZIndex {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("auto")).is_ok() {
+            return Ok(ZIndex::Auto);
+        }
+        Ok(ZIndex::Integer(parser.expect_integer()?))
     }
 }
+
+/// https://drafts.csswg.org/css-contain-1/#contain-property
+#[derive(Copy, Clone, Eq, PartialEq, SpecifiedAsComputed)]
+pub(crate) struct Contain {
+    pub size: bool,
+    pub layout: bool,
+    pub style: bool,
+    pub paint: bool,
+}
+
+impl Contain {
+    pub const NONE: Self = Contain {
+        size: false,
+        layout: false,
+        style: false,
+        paint: false,
+    };
+
+    const CONTENT: Self = Contain {
+        size: false,
+        layout: true,
+        style: true,
+        paint: true,
+    };
+}
+
+impl super::Parse for Contain {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let mut next = Some(parser.expect_ident()?.clone());
+        match &**next.as_ref().unwrap() {
+            "none" => return Ok(Contain::NONE),
+            "strict" => {
+                return Ok(Contain {
+                    size: true,
+                    ..Contain::CONTENT
+                })
+            }
+            "content" => return Ok(Contain::CONTENT),
+            _ => {}
+        }
+        // [ size || layout || style || paint ]
+        let mut contain = Contain::NONE;
+        while let Some(ident) = next {
+            let flag = match &*ident {
+                "size" => Some(&mut contain.size),
+                "layout" => Some(&mut contain.layout),
+                "style" => Some(&mut contain.style),
+                "paint" => Some(&mut contain.paint),
+                _ => None,
+            };
+            match flag {
+                // Each keyword can only appear once
+                Some(flag) if !*flag => *flag = true,
+                _ => {
+                    let token = cssparser::Token::Ident(ident);
+                    return Err(parser.new_unexpected_token_error(token));
+                }
+            }
+            next = parser.r#try(|parser| parser.expect_ident().cloned()).ok();
+        }
+        Ok(contain)
+    }
+}
+
+/// https://drafts.csswg.org/css-contain-2/#content-visibility
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum ContentVisibility {
+    Visible,
+    Auto,
+    Hidden,
+}
+
+/// https://drafts.csswg.org/css-overflow-3/#propdef-overflow
+///
+/// FIXME: clip the contents of boxes that are not `visible`.
+/// There is no scrolling in paged output, so `scroll` and `auto` are like `hidden`.
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum Overflow {
+    Visible,
+    Hidden,
+    Clip,
+    Scroll,
+    Auto,
+}
+
+/// https://drafts.csswg.org/css-overflow-3/#text-overflow
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum TextOverflow {
+    Clip,
+    Ellipsis,
+}
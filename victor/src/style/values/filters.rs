@@ -0,0 +1,142 @@
+use super::{CascadeContext, FromSpecified, Shadow, SpecifiedShadow, SpecifiedValue};
+use crate::style::errors::{PropertyParseError, PropertyParseErrorKind};
+use cssparser::{ParseErrorKind, Parser, Token};
+use std::sync::Arc;
+
+/// https://drafts.fxtf.org/filter-effects/#FilterProperty
+///
+/// Filter functions in the order they were specified, each applied to the result
+/// of the ones before it. Empty for `none`.
+#[derive(Clone)]
+pub(crate) struct Filter(pub Arc<[FilterFunction]>);
+
+/// https://drafts.fxtf.org/filter-effects/#filter-functions
+///
+/// Amounts are from 0 to 1. `blur()` is reported as not renderable.
+///
+/// FIXME: `brightness()`, `contrast()`, `hue-rotate()`, `invert()` and `saturate()`
+/// are color matrices too, like `grayscale()`. `url()` references to SVG filters.
+#[derive(Clone)]
+pub(crate) enum FilterFunction {
+    Opacity(f32),
+    Grayscale(f32),
+    Sepia(f32),
+    DropShadow(Shadow),
+}
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedFilter(Arc<[SpecifiedFilterFunction]>);
+
+#[derive(Clone)]
+pub(in crate::style) enum SpecifiedFilterFunction {
+    Opacity(f32),
+    Grayscale(f32),
+    Sepia(f32),
+    DropShadow(SpecifiedShadow),
+}
+
+impl Filter {
+    pub fn none() -> Self {
+        Filter(Arc::new([]))
+    }
+}
+
+impl SpecifiedFilter {
+    /// The first function that would change the colors of images,
+    /// which JPEG images keep since they are embedded without decoding.
+    pub fn first_image_color_change(&self) -> Option<&'static str> {
+        self.0.iter().find_map(|function| match function {
+            SpecifiedFilterFunction::Grayscale(amount) if *amount > 0. => Some("grayscale()"),
+            SpecifiedFilterFunction::Sepia(amount) if *amount > 0. => Some("sepia()"),
+            SpecifiedFilterFunction::DropShadow(_) => Some("drop-shadow()"),
+            _ => None,
+        })
+    }
+}
+
+/// `<number-percentage>?`, 1 by default. Values over 100% are clamped.
+fn parse_amount<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<f32, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let amount = match parser.r#try(|parser| parser.next().cloned()) {
+        Ok(Token::Number { value, .. }) => value,
+        Ok(Token::Percentage { unit_value, .. }) => unit_value,
+        Ok(token) => return Err(location.new_unexpected_token_error(token)),
+        Err(_) => 1.,
+    };
+    if amount < 0. {
+        return Err(parser.new_custom_error(PropertyParseErrorKind::NegativeValue));
+    }
+    Ok(amount.min(1.))
+}
+
+fn parse_function<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<SpecifiedFilterFunction, PropertyParseError<'i>> {
+    use SpecifiedFilterFunction::*;
+    let location = parser.current_source_location();
+    let name = parser.expect_function()?.clone();
+    parser.parse_nested_block(|parser| {
+        match_ignore_ascii_case!(&name,
+            "opacity" => Ok(Opacity(parse_amount(parser)?)),
+            "grayscale" => Ok(Grayscale(parse_amount(parser)?)),
+            "sepia" => Ok(Sepia(parse_amount(parser)?)),
+            "drop-shadow" => Ok(DropShadow(super::Parse::parse(parser)?)),
+            "blur" => {
+                let error = PropertyParseErrorKind::NotRenderableFunction(name.clone());
+                Err(location.new_custom_error(error))
+            },
+            _ => Err(location.new_unexpected_token_error(Token::Function(name.clone()))),
+        )
+    })
+}
+
+impl super::Parse for SpecifiedFilter {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(SpecifiedFilter(Arc::new([])));
+        }
+        // <filter-function>+
+        let mut functions = vec![parse_function(parser)?];
+        loop {
+            match parser.r#try(parse_function) {
+                Ok(function) => functions.push(function),
+                // Report the function, rather than the tokens left after the others
+                Err(error) if is_not_renderable(&error) => return Err(error),
+                Err(_) => break,
+            }
+        }
+        Ok(SpecifiedFilter(functions.into()))
+    }
+}
+
+fn is_not_renderable(error: &PropertyParseError) -> bool {
+    matches!(
+        error.kind,
+        ParseErrorKind::Custom(PropertyParseErrorKind::NotRenderableFunction(_))
+    )
+}
+
+impl SpecifiedValue for Filter {
+    type SpecifiedValue = SpecifiedFilter;
+}
+
+impl FromSpecified for Filter {
+    fn from_specified(specified: &SpecifiedFilter, context: &CascadeContext) -> Self {
+        Filter(
+            specified
+                .0
+                .iter()
+                .map(|function| match function {
+                    SpecifiedFilterFunction::Opacity(amount) => FilterFunction::Opacity(*amount),
+                    SpecifiedFilterFunction::Grayscale(amount) => {
+                        FilterFunction::Grayscale(*amount)
+                    }
+                    SpecifiedFilterFunction::Sepia(amount) => FilterFunction::Sepia(*amount),
+                    SpecifiedFilterFunction::DropShadow(shadow) => {
+                        FilterFunction::DropShadow(Shadow::from_specified(shadow, context))
+                    }
+                })
+                .collect(),
+        )
+    }
+}
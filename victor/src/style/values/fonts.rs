@@ -1,4 +1,9 @@
-use super::{EarlyCascadeContext, EarlyFromSpecified, Length, SpecifiedLength, SpecifiedValue};
+use super::{CascadeContext, EarlyCascadeContext, EarlyFromSpecified, FromSpecified};
+use super::{Length, SpecifiedLength, SpecifiedValue};
+use crate::fonts::GenericFontFamily;
+use crate::style::errors::{PropertyParseError, PropertyParseErrorKind};
+use cssparser::{Parser, Token};
+use std::sync::Arc;
 
 #[derive(Copy, Clone)]
 pub(crate) struct FontSize(pub Length);
@@ -35,6 +40,484 @@ impl std::ops::Div<FontSize> for Length {
     type Output = Em;
 
     fn div(self, other: FontSize) -> Em {
-        Em::new(self.px / other.0.px)
+        Em::new(self.px() / other.0.px())
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts/#font-family-prop
+///
+/// Fonts are only selected through generic families, configured with
+/// `FontContext::set_generic_family`: this is the first one in the list,
+/// or `sans-serif` if there is none.
+///
+/// FIXME: match family names against the names of fonts known to the `FontContext`.
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) struct FontFamily(pub GenericFontFamily);
+
+impl FontFamily {
+    pub const INITIAL: Self = FontFamily(GenericFontFamily::SansSerif);
+}
+
+impl super::Parse for FontFamily {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let families = parser.parse_comma_separated(|parser| {
+            if parser.r#try(|p| p.expect_string().map(|_| ())).is_ok() {
+                return Ok(None);
+            }
+            // A family name can be several identifiers,
+            // a generic family is a single one that is not quoted.
+            let first = parser.expect_ident()?.clone();
+            if parser.r#try(|p| p.expect_ident().map(|_| ())).is_ok() {
+                while parser.r#try(|p| p.expect_ident().map(|_| ())).is_ok() {}
+                return Ok(None);
+            }
+            Ok(match_ignore_ascii_case!(&first,
+                "serif" => Some(GenericFontFamily::Serif),
+                "sans-serif" => Some(GenericFontFamily::SansSerif),
+                "monospace" => Some(GenericFontFamily::Monospace),
+                "cursive" => Some(GenericFontFamily::Cursive),
+                "fantasy" => Some(GenericFontFamily::Fantasy),
+                _ => None,
+            ))
+        })?;
+        Ok(match families.into_iter().flatten().next() {
+            Some(generic) => FontFamily(generic),
+            None => FontFamily::INITIAL,
+        })
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-feature-settings-prop
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) enum FontFeatureSettings {
+    Normal,
+    /// OpenType feature tags and their values, in the order they were specified.
+    /// `on` is 1 and `off` is 0.
+    Settings(Arc<[([u8; 4], u32)]>),
+}
+
+impl super::Parse for FontFeatureSettings {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("normal")).is_ok() {
+            return Ok(FontFeatureSettings::Normal);
+        }
+        let settings = parser.parse_comma_separated(|parser| {
+            let tag = parse_opentype_tag(parser)?;
+            let value = if let Ok(value) = parser.r#try(|p| p.expect_integer()) {
+                if value < 0 {
+                    return Err(parser.new_custom_error(PropertyParseErrorKind::NegativeValue));
+                }
+                value as u32
+            } else if parser.r#try(|p| p.expect_ident_matching("off")).is_ok() {
+                0
+            } else {
+                parser.r#try(|p| p.expect_ident_matching("on")).ok();
+                1
+            };
+            Ok((tag, value))
+        })?;
+        Ok(FontFeatureSettings::Settings(settings.into()))
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-feature-settings-prop
+///
+/// A string of four ASCII characters in the range U+20–7E
+fn parse_opentype_tag<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<[u8; 4], PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let string = parser.expect_string()?.clone();
+    match string.as_bytes() {
+        &[a, b, c, d] if string.bytes().all(|b| (b' '..=b'~').contains(&b)) => Ok([a, b, c, d]),
+        _ => Err(location.new_unexpected_token_error(Token::QuotedString(string))),
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-variant-ligatures-prop
+///
+/// Each kind of ligature is enabled with `Some(true)`, disabled with `Some(false)`,
+/// or left to the default features with `None`.
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) struct FontVariantLigatures {
+    pub common: Option<bool>,
+    pub discretionary: Option<bool>,
+    pub historical: Option<bool>,
+    pub contextual: Option<bool>,
+}
+
+impl FontVariantLigatures {
+    pub const NORMAL: Self = FontVariantLigatures {
+        common: None,
+        discretionary: None,
+        historical: None,
+        contextual: None,
+    };
+
+    /// OpenType features for the kinds of ligatures that are not `None`
+    pub fn features(&self) -> Vec<([u8; 4], u32)> {
+        [
+            (b"liga", self.common),
+            (b"clig", self.common),
+            (b"dlig", self.discretionary),
+            (b"hlig", self.historical),
+            (b"calt", self.contextual),
+        ]
+        .iter()
+        .filter_map(|&(tag, on)| on.map(|on| (*tag, on as u32)))
+        .collect()
+    }
+}
+
+impl super::Parse for FontVariantLigatures {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let mut next = Some(parser.expect_ident()?.clone());
+        match_ignore_ascii_case!(next.as_ref().unwrap(),
+            "normal" => return Ok(FontVariantLigatures::NORMAL),
+            "none" => {
+                return Ok(FontVariantLigatures {
+                    common: Some(false),
+                    discretionary: Some(false),
+                    historical: Some(false),
+                    contextual: Some(false),
+                })
+            },
+            _ => {},
+        );
+        let mut ligatures = FontVariantLigatures::NORMAL;
+        while let Some(ident) = next {
+            let (kind, on) = match_ignore_ascii_case!(&ident,
+                "common-ligatures" => (Some(&mut ligatures.common), true),
+                "no-common-ligatures" => (Some(&mut ligatures.common), false),
+                "discretionary-ligatures" => (Some(&mut ligatures.discretionary), true),
+                "no-discretionary-ligatures" => (Some(&mut ligatures.discretionary), false),
+                "historical-ligatures" => (Some(&mut ligatures.historical), true),
+                "no-historical-ligatures" => (Some(&mut ligatures.historical), false),
+                "contextual" => (Some(&mut ligatures.contextual), true),
+                "no-contextual" => (Some(&mut ligatures.contextual), false),
+                _ => (None, false),
+            );
+            match kind {
+                // Each kind of ligature can only be specified once
+                Some(kind) if kind.is_none() => *kind = Some(on),
+                _ => return Err(parser.new_unexpected_token_error(Token::Ident(ident))),
+            }
+            next = parser.r#try(|parser| parser.expect_ident().cloned()).ok();
+        }
+        Ok(ligatures)
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-variant-caps-prop
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum FontVariantCaps {
+    Normal,
+    SmallCaps,
+    AllSmallCaps,
+    PetiteCaps,
+    AllPetiteCaps,
+    Unicase,
+    TitlingCaps,
+}
+
+impl FontVariantCaps {
+    /// OpenType features enabled by this value
+    pub fn features(self) -> Vec<([u8; 4], u32)> {
+        let tags: &[&[u8; 4]] = match self {
+            FontVariantCaps::Normal => &[],
+            FontVariantCaps::SmallCaps => &[b"smcp"],
+            FontVariantCaps::AllSmallCaps => &[b"c2sc", b"smcp"],
+            FontVariantCaps::PetiteCaps => &[b"pcap"],
+            FontVariantCaps::AllPetiteCaps => &[b"c2pc", b"pcap"],
+            FontVariantCaps::Unicase => &[b"unic"],
+            FontVariantCaps::TitlingCaps => &[b"titl"],
+        };
+        tags.iter().map(|&&tag| (tag, 1)).collect()
+    }
+
+    /// https://drafts.csswg.org/css-fonts-4/#small-caps-synthesis
+    ///
+    /// The feature that synthesis replaces when a font does not support it,
+    /// and whether uppercase letters are also made small.
+    ///
+    /// FIXME: `unicase` could also be synthesized, by making only uppercase letters small.
+    pub fn synthesized_feature(self) -> Option<([u8; 4], bool)> {
+        match self {
+            FontVariantCaps::SmallCaps => Some((*b"smcp", false)),
+            FontVariantCaps::AllSmallCaps => Some((*b"smcp", true)),
+            FontVariantCaps::PetiteCaps => Some((*b"pcap", false)),
+            FontVariantCaps::AllPetiteCaps => Some((*b"pcap", true)),
+            _ => None,
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-variant-numeric-prop
+///
+/// Each field is the OpenType feature selected from one group of keywords, if any.
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) struct FontVariantNumeric {
+    /// `lining-nums` or `oldstyle-nums`
+    pub figure: Option<[u8; 4]>,
+    /// `proportional-nums` or `tabular-nums`
+    pub spacing: Option<[u8; 4]>,
+    /// `diagonal-fractions` or `stacked-fractions`
+    pub fraction: Option<[u8; 4]>,
+    /// `ordinal`
+    pub ordinal: Option<[u8; 4]>,
+    /// `slashed-zero`
+    pub slashed_zero: Option<[u8; 4]>,
+}
+
+impl FontVariantNumeric {
+    pub const NORMAL: Self = FontVariantNumeric {
+        figure: None,
+        spacing: None,
+        fraction: None,
+        ordinal: None,
+        slashed_zero: None,
+    };
+
+    /// OpenType features enabled by this value
+    pub fn features(&self) -> Vec<([u8; 4], u32)> {
+        [
+            self.figure,
+            self.spacing,
+            self.fraction,
+            self.ordinal,
+            self.slashed_zero,
+        ]
+        .iter()
+        .flatten()
+        .map(|&tag| (tag, 1))
+        .collect()
+    }
+}
+
+impl super::Parse for FontVariantNumeric {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let mut next = Some(parser.expect_ident()?.clone());
+        if next.as_ref().unwrap().eq_ignore_ascii_case("normal") {
+            return Ok(FontVariantNumeric::NORMAL);
+        }
+        let mut numeric = FontVariantNumeric::NORMAL;
+        while let Some(ident) = next {
+            let (group, tag) = match_ignore_ascii_case!(&ident,
+                "lining-nums" => (Some(&mut numeric.figure), b"lnum"),
+                "oldstyle-nums" => (Some(&mut numeric.figure), b"onum"),
+                "proportional-nums" => (Some(&mut numeric.spacing), b"pnum"),
+                "tabular-nums" => (Some(&mut numeric.spacing), b"tnum"),
+                "diagonal-fractions" => (Some(&mut numeric.fraction), b"frac"),
+                "stacked-fractions" => (Some(&mut numeric.fraction), b"afrc"),
+                "ordinal" => (Some(&mut numeric.ordinal), b"ordn"),
+                "slashed-zero" => (Some(&mut numeric.slashed_zero), b"zero"),
+                _ => (None, b"    "),
+            );
+            match group {
+                // Only one keyword of each group
+                Some(group) if group.is_none() => *group = Some(*tag),
+                _ => return Err(parser.new_unexpected_token_error(Token::Ident(ident))),
+            }
+            next = parser.r#try(|parser| parser.expect_ident().cloned()).ok();
+        }
+        Ok(numeric)
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-weight-prop
+///
+/// A number between 1 and 1000.
+#[derive(Copy, Clone)]
+pub(crate) struct FontWeight(pub f32);
+
+impl FontWeight {
+    pub const NORMAL: Self = FontWeight(400.);
+    const BOLD: Self = FontWeight(700.);
+}
+
+#[derive(Copy, Clone)]
+pub(in crate::style) enum SpecifiedFontWeight {
+    Absolute(FontWeight),
+    Bolder,
+    Lighter,
+}
+
+impl super::Parse for SpecifiedFontWeight {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let location = parser.current_source_location();
+        match parser.next()?.clone() {
+            Token::Ident(ident) => match_ignore_ascii_case!(&ident,
+                "normal" => Ok(SpecifiedFontWeight::Absolute(FontWeight::NORMAL)),
+                "bold" => Ok(SpecifiedFontWeight::Absolute(FontWeight::BOLD)),
+                "bolder" => Ok(SpecifiedFontWeight::Bolder),
+                "lighter" => Ok(SpecifiedFontWeight::Lighter),
+                _ => Err(location.new_unexpected_token_error(Token::Ident(ident))),
+            ),
+            Token::Number { value, .. } if (1. ..=1000.).contains(&value) => {
+                Ok(SpecifiedFontWeight::Absolute(FontWeight(value)))
+            }
+            token => Err(location.new_unexpected_token_error(token)),
+        }
+    }
+}
+
+impl SpecifiedValue for FontWeight {
+    type SpecifiedValue = SpecifiedFontWeight;
+}
+
+impl FromSpecified for FontWeight {
+    /// https://drafts.csswg.org/css-fonts-4/#relative-weights
+    fn from_specified(specified: &SpecifiedFontWeight, context: &CascadeContext) -> Self {
+        let inherited = context.inherited.font.font_weight.0;
+        FontWeight(match specified {
+            SpecifiedFontWeight::Absolute(weight) => weight.0,
+            SpecifiedFontWeight::Bolder if inherited < 350. => 400.,
+            SpecifiedFontWeight::Bolder if inherited < 550. => 700.,
+            SpecifiedFontWeight::Bolder if inherited < 750. => 900.,
+            SpecifiedFontWeight::Bolder => inherited,
+            SpecifiedFontWeight::Lighter if inherited < 100. => inherited,
+            SpecifiedFontWeight::Lighter if inherited < 550. => 100.,
+            SpecifiedFontWeight::Lighter if inherited < 750. => 400.,
+            SpecifiedFontWeight::Lighter => 700.,
+        })
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-stretch-prop
+///
+/// A percentage of the normal width, such as `100.` for `normal`.
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) struct FontStretch(pub f32);
+
+impl FontStretch {
+    pub const NORMAL: Self = FontStretch(100.);
+}
+
+impl super::Parse for FontStretch {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let location = parser.current_source_location();
+        let percentage = match parser.next()?.clone() {
+            Token::Ident(ident) => match_ignore_ascii_case!(&ident,
+                "ultra-condensed" => 50.,
+                "extra-condensed" => 62.5,
+                "condensed" => 75.,
+                "semi-condensed" => 87.5,
+                "normal" => 100.,
+                "semi-expanded" => 112.5,
+                "expanded" => 125.,
+                "extra-expanded" => 150.,
+                "ultra-expanded" => 200.,
+                _ => return Err(location.new_unexpected_token_error(Token::Ident(ident))),
+            ),
+            Token::Percentage { unit_value, .. } if unit_value >= 0. => unit_value * 100.,
+            token => return Err(location.new_unexpected_token_error(token)),
+        };
+        Ok(FontStretch(percentage))
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-style-prop
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) enum FontStyle {
+    Normal,
+    Italic,
+    /// An angle in degrees, between -90 and 90. Positive angles slant clockwise.
+    Oblique(f32),
+}
+
+impl super::Parse for FontStyle {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let location = parser.current_source_location();
+        let ident = parser.expect_ident()?.clone();
+        match_ignore_ascii_case!(&ident,
+            "normal" => return Ok(FontStyle::Normal),
+            "italic" => return Ok(FontStyle::Italic),
+            "oblique" => {},
+            _ => return Err(location.new_unexpected_token_error(Token::Ident(ident))),
+        );
+        let location = parser.current_source_location();
+        let degrees = match parser.r#try(|p| p.next().cloned()) {
+            Err(_) => 14.,
+            Ok(token) => {
+                let degrees = match &token {
+                    Token::Dimension { value, unit, .. } => super::degrees(*value, unit),
+                    _ => None,
+                };
+                match degrees {
+                    Some(degrees) if (-90. ..=90.).contains(&degrees) => degrees,
+                    _ => return Err(location.new_unexpected_token_error(token)),
+                }
+            }
+        };
+        Ok(FontStyle::Oblique(degrees))
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-variation-settings-def
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) enum FontVariationSettings {
+    Normal,
+    /// OpenType variation axis tags and their values, in the order they were specified.
+    Settings(Arc<[([u8; 4], f32)]>),
+}
+
+impl super::Parse for FontVariationSettings {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("normal")).is_ok() {
+            return Ok(FontVariationSettings::Normal);
+        }
+        let settings = parser.parse_comma_separated(|parser| {
+            Ok((parse_opentype_tag(parser)?, parser.expect_number()?))
+        })?;
+        Ok(FontVariationSettings::Settings(settings.into()))
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts-4/#font-synthesis
+///
+/// Whether bold, oblique, and small-caps text can be synthesized
+/// when the font has no such face or feature.
+#[derive(Copy, Clone, SpecifiedAsComputed)]
+pub(crate) struct FontSynthesis {
+    pub weight: bool,
+    pub style: bool,
+    pub small_caps: bool,
+}
+
+impl FontSynthesis {
+    pub const INITIAL: Self = FontSynthesis {
+        weight: true,
+        style: true,
+        small_caps: true,
+    };
+}
+
+impl super::Parse for FontSynthesis {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let mut next = Some(parser.expect_ident()?.clone());
+        let mut synthesis = FontSynthesis {
+            weight: false,
+            style: false,
+            small_caps: false,
+        };
+        if next.as_ref().unwrap().eq_ignore_ascii_case("none") {
+            return Ok(synthesis);
+        }
+        // [ weight || style || small-caps ]
+        while let Some(ident) = next {
+            let flag = match_ignore_ascii_case!(&ident,
+                "weight" => Some(&mut synthesis.weight),
+                "style" => Some(&mut synthesis.style),
+                "small-caps" => Some(&mut synthesis.small_caps),
+                _ => None,
+            );
+            match flag {
+                // Each keyword can only appear once
+                Some(flag) if !*flag => *flag = true,
+                _ => return Err(parser.new_unexpected_token_error(Token::Ident(ident))),
+            }
+            next = parser.r#try(|parser| parser.expect_ident().cloned()).ok();
+        }
+        Ok(synthesis)
     }
 }
@@ -0,0 +1,137 @@
+use crate::style::errors::PropertyParseError;
+use crate::style::values::{parse_counter_name, CounterStyleName};
+use cssparser::{Parser, Token};
+use html5ever::LocalName;
+use std::sync::Arc;
+
+/// https://drafts.csswg.org/css-content-3/#content-property
+///
+/// FIXME: quotes and `url()` images.
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) enum Content {
+    Normal,
+    None,
+    Items(Arc<[ContentItem]>),
+}
+
+#[derive(Clone)]
+pub(crate) enum ContentItem {
+    String(String),
+
+    /// https://drafts.csswg.org/css-values-5/#attr-notation
+    ///
+    /// Only the `string` type is supported, which is all that `content` accepts.
+    /// FIXME: typed `attr()` in other properties, such as `width: attr(data-width px, 10px)`.
+    /// Declarations are parsed once per stylesheet, before the element is known,
+    /// so these would need to be kept as tokens like `var()` references
+    /// and parsed when computing the style of each element.
+    Attr {
+        name: LocalName,
+        fallback: Option<String>,
+    },
+
+    /// https://drafts.csswg.org/css-lists-3/#counter-functions
+    ///
+    /// `style` is `None` for `none`, which represents the counter as an empty string.
+    Counter {
+        name: String,
+        style: Option<CounterStyleName>,
+    },
+
+    /// The values of all counters of that name in scope, outermost first, joined by `separator`
+    Counters {
+        name: String,
+        separator: String,
+        style: Option<CounterStyleName>,
+    },
+
+    /// https://drafts.csswg.org/css-content-3/#leaders
+    ///
+    /// The pattern string, repeated to fill the space left on the line.
+    Leader(String),
+}
+
+impl super::Parse for Content {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("normal")).is_ok() {
+            return Ok(Content::Normal);
+        }
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(Content::None);
+        }
+        let mut items = vec![ContentItem::parse(parser)?];
+        while let Ok(item) = parser.r#try(ContentItem::parse) {
+            items.push(item)
+        }
+        Ok(Content::Items(items.into()))
+    }
+}
+
+impl ContentItem {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        match parser.next()?.clone() {
+            Token::QuotedString(string) => Ok(ContentItem::String(string.to_string())),
+            Token::Function(ref name) if name.eq_ignore_ascii_case("attr") => {
+                parser.parse_nested_block(|parser| {
+                    // FIXME: namespace prefixes
+                    // Attribute names are lower-cased when parsing HTML
+                    let name = LocalName::from(parser.expect_ident()?.to_ascii_lowercase());
+                    parser.r#try(|p| p.expect_ident_matching("string")).ok();
+                    let fallback = if parser.r#try(|p| p.expect_comma()).is_ok() {
+                        Some(parser.expect_string()?.to_string())
+                    } else {
+                        None
+                    };
+                    Ok(ContentItem::Attr { name, fallback })
+                })
+            }
+            Token::Function(ref name) if name.eq_ignore_ascii_case("counter") => parser
+                .parse_nested_block(|parser| {
+                    let name = parse_counter_name(parser)?;
+                    let style = parse_optional_counter_style(parser)?;
+                    Ok(ContentItem::Counter { name, style })
+                }),
+            Token::Function(ref name) if name.eq_ignore_ascii_case("counters") => parser
+                .parse_nested_block(|parser| {
+                    let name = parse_counter_name(parser)?;
+                    parser.expect_comma()?;
+                    let separator = parser.expect_string()?.to_string();
+                    let style = parse_optional_counter_style(parser)?;
+                    Ok(ContentItem::Counters {
+                        name,
+                        separator,
+                        style,
+                    })
+                }),
+            Token::Function(ref name) if name.eq_ignore_ascii_case("leader") => parser
+                .parse_nested_block(|parser| {
+                    if let Ok(string) = parser.r#try(|p| p.expect_string().map(|s| s.to_string())) {
+                        return Ok(ContentItem::Leader(string));
+                    }
+                    let location = parser.current_source_location();
+                    let ident = parser.expect_ident()?.clone();
+                    let pattern = match_ignore_ascii_case! { &ident,
+                        "dotted" => ". ",
+                        "solid" => "_",
+                        "space" => " ",
+                        _ => return Err(location.new_unexpected_token_error(Token::Ident(ident))),
+                    };
+                    Ok(ContentItem::Leader(pattern.to_owned()))
+                }),
+            token => Err(parser.new_unexpected_token_error(token)),
+        }
+    }
+}
+
+/// `[, <counter-style>]?`, `decimal` if omitted
+fn parse_optional_counter_style<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<Option<CounterStyleName>, PropertyParseError<'i>> {
+    if parser.r#try(|p| p.expect_comma()).is_err() {
+        return Ok(Some(CounterStyleName::decimal()));
+    }
+    if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+        return Ok(None);
+    }
+    Ok(Some(CounterStyleName::parse(parser)?))
+}
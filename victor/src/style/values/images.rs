@@ -0,0 +1,69 @@
+use super::shapes::parse_position;
+use super::{
+    CascadeContext, FromSpecified, LengthOrPercentage, Percentage, SpecifiedLengthOrPercentage,
+    SpecifiedValue,
+};
+use crate::style::errors::PropertyParseError;
+use cssparser::Parser;
+
+/// https://drafts.csswg.org/css-images-3/#the-image-orientation
+///
+/// FIXME: the deprecated `<angle>` and `flip` values,
+/// and orienting background images, which are painted as stored.
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum ImageOrientation {
+    FromImage,
+    None,
+}
+
+/// https://drafts.csswg.org/css-images-3/#the-object-fit
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum ObjectFit {
+    Fill,
+    Contain,
+    Cover,
+    None,
+    ScaleDown,
+}
+
+/// https://drafts.csswg.org/css-images-3/#the-object-position
+///
+/// Percentages are relative to the size of the content box minus the size of the image.
+#[derive(Clone)]
+pub(crate) struct ObjectPosition {
+    pub x: LengthOrPercentage,
+    pub y: LengthOrPercentage,
+}
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedObjectPosition([SpecifiedLengthOrPercentage; 2]);
+
+impl ObjectPosition {
+    pub fn center() -> Self {
+        let center = LengthOrPercentage::Percentage(Percentage::from_unit_value(0.5));
+        ObjectPosition {
+            x: center,
+            y: center,
+        }
+    }
+}
+
+impl super::Parse for SpecifiedObjectPosition {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        Ok(SpecifiedObjectPosition(parse_position(parser)?))
+    }
+}
+
+impl SpecifiedValue for ObjectPosition {
+    type SpecifiedValue = SpecifiedObjectPosition;
+}
+
+impl FromSpecified for ObjectPosition {
+    fn from_specified(specified: &SpecifiedObjectPosition, context: &CascadeContext) -> Self {
+        let [x, y] = &specified.0;
+        ObjectPosition {
+            x: LengthOrPercentage::from_specified(x, context),
+            y: LengthOrPercentage::from_specified(y, context),
+        }
+    }
+}
@@ -4,12 +4,21 @@ use cssparser::{Parser, Token};
 use std::fmt;
 use std::ops;
 
+/// A length in fixed-point “app units” of 1/60 CSS `px`, like in Gecko and Servo.
+///
+/// Layout arithmetic on integers is exact,
+/// so results do not depend on the order of operations, on the platform,
+/// or on how many lengths were accumulated along a long flow.
+/// Conversions from and to `f32` happen when parsing style and when painting.
+/// Arithmetic saturates instead of overflowing.
 #[repr(transparent)]
-#[derive(Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Copy, Clone, Default, Eq, Ord, PartialEq, PartialOrd)]
 pub(crate) struct Length {
-    pub px: f32,
+    app_units: i32,
 }
 
+const APP_UNITS_PER_PX: i32 = 60;
+
 /// <https://drafts.csswg.org/css-values/#percentages>
 #[repr(transparent)]
 #[derive(Copy, Clone, SpecifiedAsComputed)]
@@ -60,7 +69,7 @@ impl Parse for SpecifiedLength {
     fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
         match parser.next()? {
             Token::Dimension { value, unit, .. } => match_ignore_ascii_case!(unit,
-                "px" => Ok(SpecifiedLength::Absolute(Length { px: *value })),
+                "px" => Ok(SpecifiedLength::Absolute(Length::from_px(*value))),
                 "em" => Ok(SpecifiedLength::Em(*value)),
                 _ => {
                     let u = unit.clone();
@@ -68,7 +77,7 @@ impl Parse for SpecifiedLength {
                 }
             ),
             Token::Number { value, .. } if *value == 0. => {
-                Ok(SpecifiedLength::Absolute(Length { px: 0. }))
+                Ok(SpecifiedLength::Absolute(Length::zero()))
             }
             token => {
                 let t = token.clone();
@@ -99,32 +108,59 @@ impl Parse for Percentage {
     }
 }
 
+impl Percentage {
+    pub(in crate::style) fn from_unit_value(unit_value: f32) -> Self {
+        Percentage { unit_value }
+    }
+
+    /// 1. for `100%`
+    pub fn unit_value(self) -> f32 {
+        self.unit_value
+    }
+}
+
 impl Length {
-    pub fn zero() -> Self {
-        Length { px: 0. }
+    pub const fn zero() -> Self {
+        Length { app_units: 0 }
     }
 
-    pub fn max(self, other: Self) -> Self {
+    /// Same as `from_px`, but usable in constants.
+    pub const fn from_whole_px(px: i32) -> Self {
         Length {
-            px: self.px.max(other.px),
+            app_units: px * APP_UNITS_PER_PX,
         }
     }
 
-    pub fn min(self, other: Self) -> Self {
+    /// Rounds to the nearest app unit.
+    pub fn from_px(px: f32) -> Self {
         Length {
-            px: self.px.min(other.px),
+            app_units: (px * APP_UNITS_PER_PX as f32).round() as i32,
         }
     }
 
+    pub fn px(self) -> f32 {
+        self.app_units as f32 / APP_UNITS_PER_PX as f32
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        Ord::max(self, other)
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        Ord::min(self, other)
+    }
+
     pub fn max_assign(&mut self, other: Self) {
-        *self = self.max(other)
+        *self = Length::max(*self, other)
     }
 }
 
 impl ops::Neg for Length {
     type Output = Self;
     fn neg(self) -> Self {
-        Length { px: -self.px }
+        Length {
+            app_units: self.app_units.saturating_neg(),
+        }
     }
 }
 
@@ -132,7 +168,7 @@ impl ops::Add for Length {
     type Output = Self;
     fn add(self, other: Self) -> Self {
         Length {
-            px: self.px + other.px,
+            app_units: self.app_units.saturating_add(other.app_units),
         }
     }
 }
@@ -141,20 +177,20 @@ impl ops::Sub for Length {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
         Length {
-            px: self.px - other.px,
+            app_units: self.app_units.saturating_sub(other.app_units),
         }
     }
 }
 
 impl ops::AddAssign for Length {
     fn add_assign(&mut self, other: Self) {
-        self.px += other.px
+        self.app_units = self.app_units.saturating_add(other.app_units)
     }
 }
 
 impl ops::SubAssign for Length {
     fn sub_assign(&mut self, other: Self) {
-        self.px -= other.px
+        self.app_units = self.app_units.saturating_sub(other.app_units)
     }
 }
 
@@ -163,7 +199,7 @@ impl ops::Mul<f32> for Length {
 
     fn mul(self, other: f32) -> Self {
         Length {
-            px: self.px * other,
+            app_units: (self.app_units as f32 * other).round() as i32,
         }
     }
 }
@@ -181,20 +217,20 @@ impl ops::Div<f32> for Length {
 
     fn div(self, other: f32) -> Self {
         Length {
-            px: self.px / other,
+            app_units: (self.app_units as f32 / other).round() as i32,
         }
     }
 }
 
 impl From<Length> for euclid::Length<f32, crate::primitives::CssPx> {
     fn from(l: Length) -> Self {
-        euclid::Length::new(l.px)
+        euclid::Length::new(l.px())
     }
 }
 
 impl fmt::Debug for Length {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.px.fmt(f)?;
+        self.px().fmt(f)?;
         fmt::Write::write_str(f, "px")
     }
 }
@@ -257,3 +293,49 @@ impl LengthOrAuto {
         }
     }
 }
+
+#[test]
+fn app_units() {
+    // Lengths are rounded to the nearest 1/60 px
+    assert_eq!(Length::from_px(1. / 60.).app_units, 1);
+    assert_eq!(Length::from_px(0.4 / 60.), Length::zero());
+    assert_eq!(Length::from_px(0.6 / 60.).app_units, 1);
+    assert_eq!(Length::from_px(-0.6 / 60.).app_units, -1);
+    assert_eq!(Length::from_whole_px(3), Length::from_px(3.));
+    assert_eq!(Length::from_whole_px(1) * 0.5, Length::from_px(0.5));
+    assert_eq!((Length::from_whole_px(1) / 7.).app_units, 9);
+    assert_eq!(Length::from_px(12.5).px(), 12.5);
+    let css_px: euclid::Length<f32, crate::primitives::CssPx> = Length::from_px(0.25).into();
+    assert_eq!(css_px.get(), 0.25);
+    // Then sums do not accumulate rounding errors
+    let tenth = Length::from_px(0.1);
+    let sum = (0..1000).fold(Length::zero(), |sum, _| sum + tenth);
+    assert_eq!(sum, Length::from_whole_px(100));
+    assert_eq!(Length::from_px(1. / 3.) * 3., Length::from_whole_px(1));
+}
+
+#[test]
+fn app_units_saturate() {
+    let max = Length {
+        app_units: i32::MAX,
+    };
+    let min = Length {
+        app_units: i32::MIN,
+    };
+    let px = Length::from_whole_px(1);
+    assert_eq!(max + px, max);
+    assert_eq!(min - px, min);
+    let mut length = max;
+    length += px;
+    assert_eq!(length, max);
+    length = min;
+    length -= px;
+    assert_eq!(length, min);
+    assert_eq!(-min, max);
+    assert_eq!(max * 2., max);
+    assert_eq!(min / 0.5, min);
+    assert_eq!(Length::from_px(1e30), max);
+    assert_eq!(Length::from_px(-1e30), min);
+    assert_eq!(Length::from_px(f32::INFINITY), max);
+    assert_eq!(Length::from_px(f32::NAN), Length::zero());
+}
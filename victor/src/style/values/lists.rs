@@ -0,0 +1,193 @@
+use crate::style::errors::PropertyParseError;
+use cssparser::{Parser, Token};
+use std::sync::Arc;
+
+parse_one_or_more!(ListStyle {
+    style_type,
+    position,
+});
+
+/// https://drafts.csswg.org/css-lists-3/#list-style-property
+///
+/// FIXME: `list-style-image`
+#[derive(Default)]
+pub(in crate::style) struct ListStyle {
+    pub style_type: Option<ListStyleType>,
+    pub position: Option<ListStylePosition>,
+}
+
+/// https://drafts.csswg.org/css-lists-3/#text-markers
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) enum ListStyleType {
+    None,
+    Name(CounterStyleName),
+    /// Used as the marker as-is, without a suffix
+    String(String),
+}
+
+/// https://drafts.csswg.org/css-lists-3/#list-style-position-property
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum ListStylePosition {
+    Outside,
+    Inside,
+}
+
+/// https://drafts.csswg.org/css-counter-styles-3/#typedef-counter-style-name
+///
+/// Names are case-sensitive, except those of predefined counter styles,
+/// which are stored ASCII-lowercased.
+///
+/// FIXME: `symbols()` anonymous counter styles
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CounterStyleName(pub String);
+
+/// A `counter-reset`, `counter-increment` or `counter-set` value:
+/// https://drafts.csswg.org/css-lists-3/#auto-numbering
+///
+/// Counter names with their value, `None` if omitted. Empty for `none`.
+///
+/// FIXME: `reversed()` counters in `counter-reset`
+#[derive(Clone, SpecifiedAsComputed)]
+pub(crate) struct CounterChanges(pub Arc<[(String, Option<i32>)]>);
+
+impl ListStyleType {
+    pub fn disc() -> Self {
+        ListStyleType::Name(CounterStyleName("disc".into()))
+    }
+}
+
+impl CounterChanges {
+    pub fn none() -> Self {
+        CounterChanges(Arc::new([]))
+    }
+}
+
+/// Predefined counter styles, from `predefined_counter_styles.css`:
+/// https://drafts.csswg.org/css-counter-styles-3/#predefined-counters
+pub(in crate::style) const PREDEFINED_COUNTER_STYLES: &[&str] = &[
+    "decimal",
+    "decimal-leading-zero",
+    "arabic-indic",
+    "armenian",
+    "upper-armenian",
+    "lower-armenian",
+    "bengali",
+    "cambodian",
+    "khmer",
+    "cjk-decimal",
+    "devanagari",
+    "georgian",
+    "gujarati",
+    "gurmukhi",
+    "hebrew",
+    "kannada",
+    "lao",
+    "malayalam",
+    "mongolian",
+    "myanmar",
+    "oriya",
+    "persian",
+    "lower-roman",
+    "upper-roman",
+    "tamil",
+    "telugu",
+    "thai",
+    "tibetan",
+    "lower-alpha",
+    "lower-latin",
+    "upper-alpha",
+    "upper-latin",
+    "lower-greek",
+    "hiragana",
+    "hiragana-iroha",
+    "katakana",
+    "katakana-iroha",
+    "disc",
+    "circle",
+    "square",
+    "disclosure-open",
+    "disclosure-closed",
+    "cjk-earthly-branch",
+    "cjk-heavenly-stem",
+];
+
+impl CounterStyleName {
+    pub fn decimal() -> Self {
+        CounterStyleName("decimal".into())
+    }
+
+    pub(in crate::style) fn parse<'i, 't>(
+        parser: &mut Parser<'i, 't>,
+    ) -> Result<Self, PropertyParseError<'i>> {
+        let location = parser.current_source_location();
+        let ident = parser.expect_ident()?.clone();
+        Self::from_ident(&ident)
+            .ok_or_else(|| location.new_unexpected_token_error(Token::Ident(ident)))
+    }
+
+    /// `None` for `none` and CSS-wide keywords, which are not valid names.
+    pub(in crate::style) fn from_ident(ident: &str) -> Option<Self> {
+        let lowercase = ident.to_ascii_lowercase();
+        if is_reserved_ident(&lowercase) || lowercase == "none" {
+            None
+        } else if PREDEFINED_COUNTER_STYLES.contains(&&*lowercase) {
+            Some(CounterStyleName(lowercase))
+        } else {
+            Some(CounterStyleName(ident.to_owned()))
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-values-4/#custom-idents
+fn is_reserved_ident(lowercase: &str) -> bool {
+    matches!(
+        lowercase,
+        "initial" | "inherit" | "unset" | "revert" | "default"
+    )
+}
+
+impl super::Parse for ListStyleType {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(ListStyleType::None);
+        }
+        if let Ok(string) = parser.r#try(|p| p.expect_string_cloned()) {
+            return Ok(ListStyleType::String(string.to_string()));
+        }
+        Ok(ListStyleType::Name(CounterStyleName::parse(parser)?))
+    }
+}
+
+/// https://drafts.csswg.org/css-lists-3/#typedef-counter-name
+pub(in crate::style) fn parse_counter_name<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<String, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let ident = parser.expect_ident()?.clone();
+    let lowercase = ident.to_ascii_lowercase();
+    if is_reserved_ident(&lowercase) || lowercase == "none" {
+        return Err(location.new_unexpected_token_error(Token::Ident(ident)));
+    }
+    Ok(ident.to_string())
+}
+
+impl super::Parse for CounterChanges {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(CounterChanges::none());
+        }
+        let mut changes = Vec::new();
+        loop {
+            let name = if changes.is_empty() {
+                parse_counter_name(parser)?
+            } else if let Ok(name) = parser.r#try(parse_counter_name) {
+                name
+            } else {
+                break;
+            };
+            let value = parser.r#try(|p| p.expect_integer()).ok();
+            changes.push((name, value))
+        }
+        Ok(CounterChanges(changes.into()))
+    }
+}
@@ -3,17 +3,59 @@ use crate::style::properties::ComputedValues;
 use crate::style::properties::{ComputedValuesForEarlyCascade, ComputedValuesForLateCascade};
 use cssparser::Parser;
 
+macro_rules! parse_one_or_more {
+    ($type: ty { $( $field: ident ),+ $(,)? }) => {
+        impl crate::style::values::Parse for $type {
+            fn parse<'i, 't>(parser: &mut Parser<'i, 't>)
+                -> Result<Self, PropertyParseError<'i>>
+            {
+                let mut values = Self::default();
+                let mut any = false;
+                loop {
+                    $(
+                        if values.$field.is_none() {
+                            if let Ok(value) = parser.r#try(crate::style::values::Parse::parse) {
+                                values.$field = Some(value);
+                                any = true;
+                                continue
+                            }
+                        }
+                    )+
+                    break
+                }
+                if any {
+                    Ok(values)
+                } else {
+                    Err(parser.new_error_for_next_token())
+                }
+            }
+        }
+    };
+}
+
 mod background;
 mod border;
 mod box_;
 mod color;
+mod compositing;
+mod filters;
 mod fonts;
+mod generated;
 mod generic;
+mod images;
 mod length;
+mod lists;
+mod shapes;
+mod text;
+mod transforms;
 mod writing_modes;
 
-pub(super) use self::{background::*, generic::*};
-pub(crate) use self::{border::*, box_::*, color::*, fonts::*, length::*, writing_modes::*};
+pub(super) use self::generic::*;
+pub(crate) use self::{
+    background::*, border::*, box_::*, color::*, compositing::*, filters::*, fonts::*,
+    generated::*, images::*, length::*, lists::*, shapes::*, text::*, transforms::*,
+    writing_modes::*,
+};
 
 pub(super) trait Parse: Sized {
     fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>>;
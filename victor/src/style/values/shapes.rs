@@ -0,0 +1,393 @@
+use super::{
+    CascadeContext, FromSpecified, LengthOrPercentage, Percentage, SpecifiedLengthOrPercentage,
+    SpecifiedValue,
+};
+use crate::style::errors::PropertyParseError;
+use cssparser::{Parser, Token};
+use std::sync::Arc;
+
+/// https://drafts.csswg.org/css-shapes/#shape-outside-property
+///
+/// FIXME: `<image>` shapes, `shape-margin` and `shape-image-threshold`.
+#[derive(Clone)]
+pub(crate) enum ShapeOutside {
+    None,
+    /// A basic shape in the given reference box, or that box itself
+    Shape(Option<BasicShape>, ShapeBox),
+}
+
+#[derive(Clone)]
+pub(in crate::style) enum SpecifiedShapeOutside {
+    None,
+    Shape(
+        Option<GenericBasicShape<SpecifiedLengthOrPercentage>>,
+        ShapeBox,
+    ),
+}
+
+/// https://drafts.fxtf.org/css-masking/#the-clip-path
+///
+/// FIXME: `url()` references to SVG `<clipPath>` elements.
+#[derive(Clone)]
+pub(crate) enum ClipPath {
+    None,
+    /// A basic shape in the given reference box, or that box itself
+    Shape(Option<BasicShape>, ShapeBox),
+}
+
+#[derive(Clone)]
+pub(in crate::style) enum SpecifiedClipPath {
+    None,
+    Shape(
+        Option<GenericBasicShape<SpecifiedLengthOrPercentage>>,
+        ShapeBox,
+    ),
+}
+
+/// https://drafts.csswg.org/css-shapes/#typedef-shape-box
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub(crate) enum ShapeBox {
+    Margin,
+    Border,
+    Padding,
+    Content,
+}
+
+impl super::Parse for ShapeBox {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        let ident = parser.expect_ident()?;
+        match &**ident {
+            "margin-box" => Ok(ShapeBox::Margin),
+            "border-box" => Ok(ShapeBox::Border),
+            "padding-box" => Ok(ShapeBox::Padding),
+            "content-box" => Ok(ShapeBox::Content),
+            _ => {
+                let token = Token::Ident(ident.clone());
+                Err(parser.new_unexpected_token_error(token))
+            }
+        }
+    }
+}
+
+pub(crate) type BasicShape = GenericBasicShape<LengthOrPercentage>;
+
+/// https://drafts.csswg.org/css-shapes/#basic-shape-functions
+///
+/// Coordinates are from the top-left corner of the reference box,
+/// and percentages are relative to its size.
+#[derive(Clone)]
+pub(crate) enum GenericBasicShape<L> {
+    /// Offsets from the top, right, bottom and left edges of the reference box.
+    ///
+    /// FIXME: rounded corners are parsed but ignored.
+    Inset([L; 4]),
+    /// A percentage radius is relative to the diagonal of the reference box, divided by √2.
+    Circle {
+        radius: ShapeRadius<L>,
+        center: [L; 2],
+    },
+    /// Percentage radii are relative to the width and height of the reference box.
+    Ellipse {
+        radii: [ShapeRadius<L>; 2],
+        center: [L; 2],
+    },
+    /// The x and y coordinates of each vertex.
+    /// The fill rule does not change the float area, which has no holes.
+    Polygon {
+        even_odd: bool,
+        vertices: Arc<[[L; 2]]>,
+    },
+}
+
+/// https://drafts.csswg.org/css-shapes/#typedef-shape-radius
+#[derive(Clone)]
+pub(crate) enum ShapeRadius<L> {
+    Length(L),
+    ClosestSide,
+    FarthestSide,
+}
+
+impl<L> GenericBasicShape<L> {
+    fn map<M>(&self, f: impl Fn(&L) -> M) -> GenericBasicShape<M> {
+        let pair = |[x, y]: &[L; 2]| [f(x), f(y)];
+        let radius = |radius: &ShapeRadius<L>| match radius {
+            ShapeRadius::Length(length) => ShapeRadius::Length(f(length)),
+            ShapeRadius::ClosestSide => ShapeRadius::ClosestSide,
+            ShapeRadius::FarthestSide => ShapeRadius::FarthestSide,
+        };
+        match self {
+            GenericBasicShape::Inset([top, right, bottom, left]) => {
+                GenericBasicShape::Inset([f(top), f(right), f(bottom), f(left)])
+            }
+            GenericBasicShape::Circle { radius: r, center } => GenericBasicShape::Circle {
+                radius: radius(r),
+                center: pair(center),
+            },
+            GenericBasicShape::Ellipse {
+                radii: [rx, ry],
+                center,
+            } => GenericBasicShape::Ellipse {
+                radii: [radius(rx), radius(ry)],
+                center: pair(center),
+            },
+            GenericBasicShape::Polygon { even_odd, vertices } => GenericBasicShape::Polygon {
+                even_odd: *even_odd,
+                vertices: vertices.iter().map(pair).collect(),
+            },
+        }
+    }
+}
+
+impl super::Parse for SpecifiedShapeOutside {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(SpecifiedShapeOutside::None);
+        }
+        // <basic-shape> || <shape-box>
+        let (shape, reference_box) = parse_shape_and_box(parser, super::Parse::parse)?;
+        Ok(SpecifiedShapeOutside::Shape(
+            shape,
+            reference_box.unwrap_or(ShapeBox::Margin),
+        ))
+    }
+}
+
+impl super::Parse for SpecifiedClipPath {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(SpecifiedClipPath::None);
+        }
+        // <basic-shape> || <geometry-box>
+        let (shape, reference_box) = parse_shape_and_box(parser, parse_geometry_box)?;
+        Ok(SpecifiedClipPath::Shape(
+            shape,
+            reference_box.unwrap_or(ShapeBox::Border),
+        ))
+    }
+}
+
+/// `<basic-shape> || <box>`, where at least one of them is present
+fn parse_shape_and_box<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+    parse_box: impl Fn(&mut Parser<'i, 't>) -> Result<ShapeBox, PropertyParseError<'i>>,
+) -> Result<
+    (
+        Option<GenericBasicShape<SpecifiedLengthOrPercentage>>,
+        Option<ShapeBox>,
+    ),
+    PropertyParseError<'i>,
+> {
+    let mut shape = parser.r#try(parse_basic_shape).ok();
+    let reference_box = parser.r#try(|parser| parse_box(parser)).ok();
+    if shape.is_none() {
+        shape = parser.r#try(parse_basic_shape).ok();
+    }
+    if shape.is_none() && reference_box.is_none() {
+        return Err(parser.new_error_for_next_token());
+    }
+    Ok((shape, reference_box))
+}
+
+/// https://drafts.fxtf.org/css-masking/#typedef-geometry-box
+///
+/// The SVG boxes are those of the CSS box: `fill-box` is the content box,
+/// and `stroke-box` and `view-box` are the border box.
+fn parse_geometry_box<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<ShapeBox, PropertyParseError<'i>> {
+    if let Ok(shape_box) = parser.r#try(super::Parse::parse) {
+        return Ok(shape_box);
+    }
+    let ident = parser.expect_ident()?;
+    match &**ident {
+        "fill-box" => Ok(ShapeBox::Content),
+        "stroke-box" | "view-box" => Ok(ShapeBox::Border),
+        _ => {
+            let token = Token::Ident(ident.clone());
+            Err(parser.new_unexpected_token_error(token))
+        }
+    }
+}
+
+fn parse_basic_shape<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<GenericBasicShape<SpecifiedLengthOrPercentage>, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let name = parser.expect_function()?.clone();
+    parser.parse_nested_block(|parser| {
+        match_ignore_ascii_case!(&name,
+            "inset" => {
+                // Like `margin`: top, right, bottom, left
+                let top: SpecifiedLengthOrPercentage = super::Parse::parse(parser)?;
+                let right = parser.r#try(super::Parse::parse).ok();
+                let bottom = parser.r#try(super::Parse::parse).ok();
+                let left = parser.r#try(super::Parse::parse).ok();
+                if parser.r#try(|p| p.expect_ident_matching("round")).is_ok() {
+                    while !parser.is_exhausted() {
+                        parser.next()?;
+                    }
+                }
+                let right = right.unwrap_or_else(|| top.clone());
+                let bottom = bottom.unwrap_or_else(|| top.clone());
+                let left = left.unwrap_or_else(|| right.clone());
+                Ok(GenericBasicShape::Inset([top, right, bottom, left]))
+            },
+            "circle" => {
+                let radius = parser.r#try(parse_shape_radius).unwrap_or(ShapeRadius::ClosestSide);
+                let center = parse_optional_center(parser)?;
+                Ok(GenericBasicShape::Circle { radius, center })
+            },
+            "ellipse" => {
+                let radii = match parser.r#try(parse_shape_radius) {
+                    Ok(rx) => [rx, parse_shape_radius(parser)?],
+                    Err(_) => [ShapeRadius::ClosestSide, ShapeRadius::ClosestSide],
+                };
+                let center = parse_optional_center(parser)?;
+                Ok(GenericBasicShape::Ellipse { radii, center })
+            },
+            "polygon" => {
+                let mut fill_rule = |keyword| parser.r#try(|p| p.expect_ident_matching(keyword)).is_ok();
+                let even_odd = fill_rule("evenodd");
+                if even_odd || fill_rule("nonzero") {
+                    parser.expect_comma()?;
+                }
+                let vertices = parser.parse_comma_separated(|parser| {
+                    let x = super::Parse::parse(parser)?;
+                    let y = super::Parse::parse(parser)?;
+                    Ok([x, y])
+                })?;
+                Ok(GenericBasicShape::Polygon { even_odd, vertices: vertices.into() })
+            },
+            _ => Err(location.new_unexpected_token_error(Token::Function(name.clone()))),
+        )
+    })
+}
+
+fn parse_shape_radius<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<ShapeRadius<SpecifiedLengthOrPercentage>, PropertyParseError<'i>> {
+    if parser
+        .r#try(|p| p.expect_ident_matching("closest-side"))
+        .is_ok()
+    {
+        return Ok(ShapeRadius::ClosestSide);
+    }
+    if parser
+        .r#try(|p| p.expect_ident_matching("farthest-side"))
+        .is_ok()
+    {
+        return Ok(ShapeRadius::FarthestSide);
+    }
+    Ok(ShapeRadius::Length(super::Parse::parse(parser)?))
+}
+
+/// `[ at <position> ]?`, centered by default
+fn parse_optional_center<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<[SpecifiedLengthOrPercentage; 2], PropertyParseError<'i>> {
+    if parser.r#try(|p| p.expect_ident_matching("at")).is_err() {
+        return Ok([percentage(0.5), percentage(0.5)]);
+    }
+    parse_position(parser)
+}
+
+/// `<position>`, as horizontal and vertical offsets:
+/// https://drafts.csswg.org/css-values/#position
+///
+/// FIXME: only one- and two-value positions, without offsets from keywords.
+pub(super) fn parse_position<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<[SpecifiedLengthOrPercentage; 2], PropertyParseError<'i>> {
+    let first: PositionComponent = super::Parse::parse(parser)?;
+    let second = parser.r#try(super::Parse::parse).ok();
+    use PositionComponent::*;
+    let (x, y) = match (first, second) {
+        (first @ Top, None) | (first @ Bottom, None) => (Center, first),
+        (first, None) => (first, Center),
+        // A vertical keyword first, or a horizontal keyword second
+        (first @ Top, Some(second)) | (first @ Bottom, Some(second)) => (second, first),
+        (first @ Center, Some(second @ Left)) | (first @ Center, Some(second @ Right)) => {
+            (second, first)
+        }
+        (first, Some(second)) => (first, second),
+    };
+    match (x.horizontal(), y.vertical()) {
+        (Some(x), Some(y)) => Ok([x, y]),
+        _ => Err(parser.new_error_for_next_token()),
+    }
+}
+
+#[derive(Parse)]
+enum PositionComponent {
+    Left,
+    Center,
+    Right,
+    Top,
+    Bottom,
+    Length(SpecifiedLengthOrPercentage),
+}
+
+impl PositionComponent {
+    fn horizontal(self) -> Option<SpecifiedLengthOrPercentage> {
+        match self {
+            PositionComponent::Left => Some(percentage(0.)),
+            PositionComponent::Center => Some(percentage(0.5)),
+            PositionComponent::Right => Some(percentage(1.)),
+            PositionComponent::Length(length) => Some(length),
+            PositionComponent::Top | PositionComponent::Bottom => None,
+        }
+    }
+
+    fn vertical(self) -> Option<SpecifiedLengthOrPercentage> {
+        match self {
+            PositionComponent::Top => Some(percentage(0.)),
+            PositionComponent::Center => Some(percentage(0.5)),
+            PositionComponent::Bottom => Some(percentage(1.)),
+            PositionComponent::Length(length) => Some(length),
+            PositionComponent::Left | PositionComponent::Right => None,
+        }
+    }
+}
+
+fn percentage(unit_value: f32) -> SpecifiedLengthOrPercentage {
+    SpecifiedLengthOrPercentage::Percentage(Percentage::from_unit_value(unit_value))
+}
+
+impl SpecifiedValue for ShapeOutside {
+    type SpecifiedValue = SpecifiedShapeOutside;
+}
+
+impl FromSpecified for ShapeOutside {
+    fn from_specified(specified: &SpecifiedShapeOutside, context: &CascadeContext) -> Self {
+        match specified {
+            SpecifiedShapeOutside::None => ShapeOutside::None,
+            SpecifiedShapeOutside::Shape(shape, reference_box) => {
+                ShapeOutside::Shape(shape_from_specified(shape, context), *reference_box)
+            }
+        }
+    }
+}
+
+impl SpecifiedValue for ClipPath {
+    type SpecifiedValue = SpecifiedClipPath;
+}
+
+impl FromSpecified for ClipPath {
+    fn from_specified(specified: &SpecifiedClipPath, context: &CascadeContext) -> Self {
+        match specified {
+            SpecifiedClipPath::None => ClipPath::None,
+            SpecifiedClipPath::Shape(shape, reference_box) => {
+                ClipPath::Shape(shape_from_specified(shape, context), *reference_box)
+            }
+        }
+    }
+}
+
+fn shape_from_specified(
+    shape: &Option<GenericBasicShape<SpecifiedLengthOrPercentage>>,
+    context: &CascadeContext,
+) -> Option<BasicShape> {
+    shape
+        .as_ref()
+        .map(|shape| shape.map(|length| LengthOrPercentage::from_specified(length, context)))
+}
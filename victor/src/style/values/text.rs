@@ -0,0 +1,226 @@
+use super::border::SpecifiedLineWidth;
+use super::{CascadeContext, FromSpecified, Length, SpecifiedLength, SpecifiedValue};
+use crate::style::errors::{PropertyParseError, PropertyParseErrorKind};
+use crate::style::properties::ComputedValues;
+use cssparser::{Color, Parser, RGBA};
+use std::sync::Arc;
+
+parse_one_or_more!(TextStroke { width, color });
+parse_one_or_more!(TextDecoration { line, color });
+
+/// https://compat.spec.whatwg.org/#the-webkit-text-stroke
+#[derive(Default)]
+pub(in crate::style) struct TextStroke {
+    pub width: Option<SpecifiedLineWidth>,
+    pub color: Option<Color>,
+}
+
+/// https://drafts.csswg.org/css-text-3/#white-space-property
+///
+/// FIXME: support `pre`, `pre-wrap`, `pre-line` and `break-spaces`,
+/// which also change how white space is collapsed during box construction.
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum WhiteSpace {
+    Normal,
+    Nowrap,
+}
+
+/// https://drafts.csswg.org/css-text-decor-3/#text-shadow-property
+///
+/// Shadows in the order they were specified, from top to bottom. Empty for `none`.
+#[derive(Clone)]
+pub(crate) struct TextShadow(pub Arc<[Shadow]>);
+
+#[derive(Clone)]
+pub(crate) struct Shadow {
+    pub color: Color,
+    pub offset_x: Length,
+    pub offset_y: Length,
+    pub blur_radius: Length,
+}
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedTextShadow(Arc<[SpecifiedShadow]>);
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedShadow {
+    color: Option<Color>,
+    offset_x: SpecifiedLength,
+    offset_y: SpecifiedLength,
+    blur_radius: Option<SpecifiedLength>,
+}
+
+impl TextShadow {
+    pub fn none() -> Self {
+        TextShadow(Arc::new([]))
+    }
+}
+
+impl super::Parse for SpecifiedTextShadow {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(SpecifiedTextShadow(Arc::new([])));
+        }
+        let shadows = parser.parse_comma_separated(super::Parse::parse)?;
+        Ok(SpecifiedTextShadow(shadows.into()))
+    }
+}
+
+/// Also the argument of the `drop-shadow()` filter function.
+impl super::Parse for SpecifiedShadow {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        // [ <color>? && <length>{2,3} ]
+        let mut color = parser.r#try(Color::parse).ok();
+        let offset_x = SpecifiedLength::parse(parser)?;
+        let offset_y = SpecifiedLength::parse(parser)?;
+        let blur_radius = parser.r#try(SpecifiedLength::parse).ok();
+        let is_negative = |length: &SpecifiedLength| match length {
+            SpecifiedLength::Absolute(length) => *length < Length::zero(),
+            SpecifiedLength::Em(value) => *value < 0.,
+        };
+        if blur_radius.as_ref().is_some_and(is_negative) {
+            return Err(parser.new_custom_error(PropertyParseErrorKind::NegativeValue));
+        }
+        if color.is_none() {
+            color = parser.r#try(Color::parse).ok()
+        }
+        Ok(SpecifiedShadow {
+            color,
+            offset_x,
+            offset_y,
+            blur_radius,
+        })
+    }
+}
+
+impl SpecifiedValue for TextShadow {
+    type SpecifiedValue = SpecifiedTextShadow;
+}
+
+impl FromSpecified for TextShadow {
+    fn from_specified(specified: &SpecifiedTextShadow, context: &CascadeContext) -> Self {
+        TextShadow(
+            specified
+                .0
+                .iter()
+                .map(|shadow| Shadow::from_specified(shadow, context))
+                .collect(),
+        )
+    }
+}
+
+impl SpecifiedValue for Shadow {
+    type SpecifiedValue = SpecifiedShadow;
+}
+
+impl FromSpecified for Shadow {
+    fn from_specified(shadow: &SpecifiedShadow, context: &CascadeContext) -> Self {
+        let length = |length| Length::from_specified(length, context);
+        Shadow {
+            // The color is resolved when painting, since `color` is inherited separately.
+            color: shadow.color.unwrap_or(Color::CurrentColor),
+            offset_x: length(&shadow.offset_x),
+            offset_y: length(&shadow.offset_y),
+            blur_radius: shadow.blur_radius.as_ref().map_or(Length::zero(), length),
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-text-decor-3/#text-decoration-property
+///
+/// FIXME: `text-decoration-style`
+#[derive(Default)]
+pub(in crate::style) struct TextDecoration {
+    pub line: Option<TextDecorationLine>,
+    pub color: Option<Color>,
+}
+
+/// https://drafts.csswg.org/css-text-decor-3/#text-decoration-line-property
+#[derive(Copy, Clone, Eq, PartialEq, SpecifiedAsComputed)]
+pub(crate) struct TextDecorationLine {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecorationLine {
+    pub const NONE: Self = TextDecorationLine {
+        underline: false,
+        overline: false,
+        line_through: false,
+    };
+}
+
+impl super::Parse for TextDecorationLine {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(TextDecorationLine::NONE);
+        }
+        // [ underline || overline || line-through ]
+        let mut line = TextDecorationLine::NONE;
+        let mut next = Some(parser.expect_ident()?.clone());
+        while let Some(ident) = next {
+            let flag = match &*ident {
+                "underline" => Some(&mut line.underline),
+                "overline" => Some(&mut line.overline),
+                "line-through" => Some(&mut line.line_through),
+                _ => None,
+            };
+            match flag {
+                // Each keyword can only appear once
+                Some(flag) if !*flag => *flag = true,
+                _ => {
+                    let token = cssparser::Token::Ident(ident);
+                    return Err(parser.new_unexpected_token_error(token));
+                }
+            }
+            next = parser.r#try(|parser| parser.expect_ident().cloned()).ok();
+        }
+        Ok(line)
+    }
+}
+
+/// https://drafts.csswg.org/css-text-decor-4/#text-decoration-skip-ink-property
+///
+/// FIXME: `all`, which also skips the ink of CJK characters
+#[derive(Copy, Clone, Eq, Parse, PartialEq, SpecifiedAsComputed)]
+pub(crate) enum TextDecorationSkipInk {
+    Auto,
+    None,
+}
+
+/// The lines that text of a box is decorated with, in the color of the box that specified them:
+/// its own `text-decoration-line` and that of the ancestors it is in-flow content of.
+///
+/// https://drafts.csswg.org/css-text-decor-3/#line-decoration
+#[derive(Copy, Clone, Default)]
+pub(crate) struct TextDecorationsInEffect {
+    pub underline: Option<RGBA>,
+    pub overline: Option<RGBA>,
+    pub line_through: Option<RGBA>,
+}
+
+impl TextDecorationsInEffect {
+    /// Starts from the decorations inherited from the parent,
+    /// which `ComputedValues::new` copies like an inherited property.
+    ///
+    /// FIXME: decorations should also not propagate to the contents of atomic inlines.
+    pub fn fixup(style: &mut ComputedValues) {
+        // Decorations are not propagated to out-of-flow descendants
+        if style.box_.position.is_absolutely_positioned() || style.box_.float.is_floating() {
+            style.text_decorations_in_effect = TextDecorationsInEffect::default()
+        }
+        let line = style.text_decoration.text_decoration_line;
+        let color = style.to_rgba(style.text_decoration.text_decoration_color);
+        let decorations = &mut style.text_decorations_in_effect;
+        for (specified, in_effect) in &mut [
+            (line.underline, &mut decorations.underline),
+            (line.overline, &mut decorations.overline),
+            (line.line_through, &mut decorations.line_through),
+        ] {
+            if *specified {
+                **in_effect = Some(color)
+            }
+        }
+    }
+}
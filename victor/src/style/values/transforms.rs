@@ -0,0 +1,208 @@
+use super::shapes::parse_position;
+use super::{
+    CascadeContext, FromSpecified, Length, LengthOrPercentage, Parse, Percentage,
+    SpecifiedLengthOrPercentage, SpecifiedValue,
+};
+use crate::style::errors::PropertyParseError;
+use cssparser::{Parser, Token};
+use std::sync::Arc;
+
+/// https://drafts.csswg.org/css-transforms-1/#transform-property
+///
+/// Transform functions in the order they were specified. Empty for `none`.
+///
+/// FIXME: 3D functions are not supported.
+#[derive(Clone)]
+pub(crate) struct Transform(pub Arc<[TransformFunction]>);
+
+/// https://drafts.csswg.org/css-transforms-1/#two-d-transform-functions
+///
+/// `translateX()`, `scaleY()`, etc. are parsed as their two-dimensional forms.
+#[derive(Clone)]
+pub(crate) enum TransformFunction {
+    /// Percentages are relative to the size of the border box.
+    Translate(LengthOrPercentage, LengthOrPercentage),
+    Scale(f32, f32),
+    /// Clockwise, in degrees
+    Rotate(f32),
+    Matrix([f32; 6]),
+}
+
+/// https://drafts.csswg.org/css-transforms-1/#transform-origin-property
+///
+/// Percentages are relative to the size of the border box.
+///
+/// FIXME: the third, `z` value of 3D transforms is not supported.
+#[derive(Clone)]
+pub(crate) struct TransformOrigin {
+    pub x: LengthOrPercentage,
+    pub y: LengthOrPercentage,
+}
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedTransform(Arc<[SpecifiedTransformFunction]>);
+
+#[derive(Clone)]
+pub(in crate::style) struct SpecifiedTransformOrigin([SpecifiedLengthOrPercentage; 2]);
+
+#[derive(Clone)]
+pub(in crate::style) enum SpecifiedTransformFunction {
+    Translate(SpecifiedLengthOrPercentage, SpecifiedLengthOrPercentage),
+    Scale(f32, f32),
+    Rotate(f32),
+    Matrix([f32; 6]),
+}
+
+impl Transform {
+    pub fn none() -> Self {
+        Transform(Arc::new([]))
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TransformOrigin {
+    pub fn center() -> Self {
+        let center = LengthOrPercentage::Percentage(Percentage::from_unit_value(0.5));
+        TransformOrigin {
+            x: center,
+            y: center,
+        }
+    }
+}
+
+/// The size in degrees of an `<angle>` with this unit:
+/// https://drafts.csswg.org/css-values/#angles
+pub(super) fn degrees(value: f32, unit: &str) -> Option<f32> {
+    match_ignore_ascii_case!(unit,
+        "deg" => Some(value),
+        "grad" => Some(value * 0.9),
+        "rad" => Some(value.to_degrees()),
+        "turn" => Some(value * 360.),
+        _ => None,
+    )
+}
+
+fn parse_angle<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<f32, PropertyParseError<'i>> {
+    let location = parser.current_source_location();
+    let token = parser.next()?.clone();
+    let degrees = match &token {
+        Token::Dimension { value, unit, .. } => degrees(*value, unit),
+        // Unitless zero is allowed for compatibility:
+        // https://drafts.csswg.org/css-values/#angles
+        Token::Number { value, .. } if *value == 0. => Some(0.),
+        _ => None,
+    };
+    degrees.ok_or_else(|| location.new_unexpected_token_error(token))
+}
+
+fn parse_function<'i, 't>(
+    parser: &mut Parser<'i, 't>,
+) -> Result<SpecifiedTransformFunction, PropertyParseError<'i>> {
+    use SpecifiedTransformFunction::*;
+    let location = parser.current_source_location();
+    let name = parser.expect_function()?.clone();
+    parser.parse_nested_block(|parser| {
+        let zero = || SpecifiedLengthOrPercentage::Length(Length::zero().into());
+        match_ignore_ascii_case!(&name,
+            "translate" => {
+                let x = SpecifiedLengthOrPercentage::parse(parser)?;
+                let y = parser
+                    .r#try(|parser| {
+                        parser.expect_comma()?;
+                        SpecifiedLengthOrPercentage::parse(parser)
+                    })
+                    .unwrap_or_else(|_| zero());
+                Ok(Translate(x, y))
+            },
+            "translatex" => Ok(Translate(SpecifiedLengthOrPercentage::parse(parser)?, zero())),
+            "translatey" => Ok(Translate(zero(), SpecifiedLengthOrPercentage::parse(parser)?)),
+            "scale" => {
+                let x = parser.expect_number()?;
+                let y = parser
+                    .r#try(|parser| {
+                        parser.expect_comma()?;
+                        parser.expect_number()
+                    })
+                    .unwrap_or(x);
+                Ok(Scale(x, y))
+            },
+            "scalex" => Ok(Scale(parser.expect_number()?, 1.)),
+            "scaley" => Ok(Scale(1., parser.expect_number()?)),
+            "rotate" => Ok(Rotate(parse_angle(parser)?)),
+            "matrix" => {
+                let mut matrix = [parser.expect_number()?, 0., 0., 0., 0., 0.];
+                for value in &mut matrix[1..] {
+                    parser.expect_comma()?;
+                    *value = parser.expect_number()?;
+                }
+                Ok(Matrix(matrix))
+            },
+            _ => Err(location.new_unexpected_token_error(Token::Function(name.clone()))),
+        )
+    })
+}
+
+impl Parse for SpecifiedTransform {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        if parser.r#try(|p| p.expect_ident_matching("none")).is_ok() {
+            return Ok(SpecifiedTransform(Arc::new([])));
+        }
+        // <transform-function>+
+        let mut functions = vec![parse_function(parser)?];
+        while let Ok(function) = parser.r#try(parse_function) {
+            functions.push(function)
+        }
+        Ok(SpecifiedTransform(functions.into()))
+    }
+}
+
+impl SpecifiedValue for Transform {
+    type SpecifiedValue = SpecifiedTransform;
+}
+
+impl FromSpecified for Transform {
+    fn from_specified(specified: &SpecifiedTransform, context: &CascadeContext) -> Self {
+        let length = |length| LengthOrPercentage::from_specified(length, context);
+        Transform(
+            specified
+                .0
+                .iter()
+                .map(|function| match function {
+                    SpecifiedTransformFunction::Translate(x, y) => {
+                        TransformFunction::Translate(length(x), length(y))
+                    }
+                    SpecifiedTransformFunction::Scale(x, y) => TransformFunction::Scale(*x, *y),
+                    SpecifiedTransformFunction::Rotate(degrees) => {
+                        TransformFunction::Rotate(*degrees)
+                    }
+                    SpecifiedTransformFunction::Matrix(matrix) => {
+                        TransformFunction::Matrix(*matrix)
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Parse for SpecifiedTransformOrigin {
+    fn parse<'i, 't>(parser: &mut Parser<'i, 't>) -> Result<Self, PropertyParseError<'i>> {
+        Ok(SpecifiedTransformOrigin(parse_position(parser)?))
+    }
+}
+
+impl SpecifiedValue for TransformOrigin {
+    type SpecifiedValue = SpecifiedTransformOrigin;
+}
+
+impl FromSpecified for TransformOrigin {
+    fn from_specified(specified: &SpecifiedTransformOrigin, context: &CascadeContext) -> Self {
+        let [x, y] = &specified.0;
+        TransformOrigin {
+            x: LengthOrPercentage::from_specified(x, context),
+            y: LengthOrPercentage::from_specified(y, context),
+        }
+    }
+}
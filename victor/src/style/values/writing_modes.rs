@@ -1,10 +1,11 @@
-#[derive(Debug, Copy, Clone, PartialEq, Parse)]
+/// https://drafts.csswg.org/css-writing-modes/#direction
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Parse, SpecifiedAsComputed)]
 pub(crate) enum Direction {
     Ltr,
     Rtl,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Parse)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Parse)]
 pub(crate) enum WritingMode {
     HorizontalTb,
     VerticalRl,
@@ -0,0 +1,227 @@
+//! Splitting text into items: runs that each have a single script, bidi level, and font.
+//!
+//! Line breaking, shaping, and painting all work on items,
+//! so that they agree on where runs of text start and end.
+
+use crate::fonts::{Font, FontContext, GlyphId};
+use std::sync::Arc;
+
+pub(crate) struct Item<'a> {
+    pub text: &'a str,
+    /// https://unicode.org/reports/tr9/#BD2
+    ///
+    /// Even for left-to-right text, odd for right-to-left text.
+    pub bidi_level: u8,
+    pub font: &'a Arc<Font>,
+}
+
+/// Split `text` into items, in logical order.
+///
+/// Each character uses the first of `fonts` that has a glyph for it,
+/// or the first font if none does.
+/// Spaces, punctuation, and combining marks stay in the font of the characters before them
+/// when it has a glyph for them.
+///
+/// FIXME: return the script of each item once shaping uses OpenType features,
+/// which are selected by script.
+pub(crate) fn itemize<'a>(
+    text: &'a str,
+    right_to_left: bool,
+    fonts: &'a [Arc<Font>],
+    font_context: &'a FontContext,
+) -> impl Iterator<Item = Item<'a>> + 'a {
+    let mut chars = text
+        .char_indices()
+        .zip(bidi_levels(text, right_to_left))
+        .peekable();
+    std::iter::from_fn(move || {
+        let &((start, first), bidi_level) = chars.peek()?;
+        let mut script = Script::of(first);
+        let font = font_for(first, None, fonts, font_context);
+        let mut end = text.len();
+        while let Some(&((index, ch), level)) = chars.peek() {
+            let ch_script = Script::of(ch);
+            if level != bidi_level
+                || (script.is_specific() && ch_script.is_specific() && ch_script != script)
+                || !Arc::ptr_eq(font_for(ch, Some(font), fonts, font_context), font)
+            {
+                end = index;
+                break;
+            }
+            if !script.is_specific() {
+                script = ch_script
+            }
+            chars.next();
+        }
+        Some(Item {
+            text: &text[start..end],
+            bidi_level,
+            font,
+        })
+    })
+}
+
+fn font_for<'a>(
+    ch: char,
+    previous: Option<&'a Arc<Font>>,
+    fonts: &'a [Arc<Font>],
+    font_context: &FontContext,
+) -> &'a Arc<Font> {
+    let has_glyph = |font: &Arc<Font>| {
+        font_context
+            .glyph(font, ch)
+            .is_ok_and(|(id, _)| id != GlyphId(0))
+    };
+    if let Some(previous) = previous {
+        if !Script::of(ch).is_specific() && has_glyph(previous) {
+            return previous;
+        }
+    }
+    fonts
+        .iter()
+        .find(|font| has_glyph(font))
+        .unwrap_or(&fonts[0])
+}
+
+/// https://unicode.org/reports/tr9/#Resolving_Neutral_Types
+///
+/// Simplified: letters are strong, and all other characters are neutral.
+/// A neutral between two strong characters of the same direction takes that direction,
+/// otherwise it takes the paragraph’s direction.
+/// Combining marks take the level of the character before them.
+///
+/// FIXME: numbers, explicit embeddings and isolates, and bracket pairs.
+fn bidi_levels(text: &str, right_to_left: bool) -> Vec<u8> {
+    let paragraph_level = right_to_left as u8;
+    let level_for = |rtl: bool| {
+        if rtl == right_to_left {
+            paragraph_level
+        } else {
+            paragraph_level + 1
+        }
+    };
+    // `Some(true)` for strong right-to-left characters
+    let strong: Vec<Option<bool>> = text.chars().map(strong_direction).collect();
+    let mut next_strong = vec![right_to_left; strong.len()];
+    let mut following = right_to_left;
+    for (next, &direction) in next_strong.iter_mut().zip(&strong).rev() {
+        *next = following;
+        following = direction.unwrap_or(following);
+    }
+    let mut previous_strong = right_to_left;
+    let mut previous_level = paragraph_level;
+    text.chars()
+        .zip(strong)
+        .zip(next_strong)
+        .map(|((ch, direction), next)| {
+            let level = match direction {
+                Some(rtl) => {
+                    previous_strong = rtl;
+                    level_for(rtl)
+                }
+                None if Script::of(ch) == Script::Inherited => previous_level,
+                None if previous_strong == next => level_for(next),
+                None => paragraph_level,
+            };
+            previous_level = level;
+            level
+        })
+        .collect()
+}
+
+/// Whether `ch` is a strong character, and if so whether it is right-to-left
+fn strong_direction(ch: char) -> Option<bool> {
+    if !ch.is_alphabetic() {
+        return None;
+    }
+    Some(matches!(
+        ch as u32,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF
+    ))
+}
+
+/// https://unicode.org/reports/tr24/
+///
+/// Only some scripts are distinguished, letters of others are `Other`.
+/// FIXME: use the full `Scripts.txt` data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Script {
+    /// Spaces, punctuation, digits, and symbols
+    Common,
+    /// Combining marks
+    Inherited,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+    Other,
+}
+
+impl Script {
+    fn of(ch: char) -> Self {
+        match ch as u32 {
+            0x0300..=0x036F
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F => Script::Inherited,
+            _ if !ch.is_alphabetic() => Script::Common,
+            0x0000..=0x024F | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF => Script::Latin,
+            0xFF21..=0xFF3A | 0xFF41..=0xFF5A => Script::Latin,
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x0400..=0x052F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Script::Cyrillic,
+            0x0530..=0x058F => Script::Armenian,
+            0x0590..=0x05FF | 0xFB1D..=0xFB4F => Script::Hebrew,
+            0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF => Script::Arabic,
+            0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Script::Arabic,
+            0x0900..=0x097F => Script::Devanagari,
+            0x0E00..=0x0E7F => Script::Thai,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+            0x3040..=0x309F => Script::Hiragana,
+            0x30A0..=0x30FF | 0x31F0..=0x31FF => Script::Katakana,
+            0x2E80..=0x2FDF | 0x3005 | 0x3007 | 0x3400..=0x4DBF | 0x4E00..=0x9FFF => Script::Han,
+            0xF900..=0xFAFF | 0x20000..=0x3134F => Script::Han,
+            _ => Script::Other,
+        }
+    }
+
+    /// Common and inherited characters take the script of the text around them.
+    fn is_specific(self) -> bool {
+        !matches!(self, Script::Common | Script::Inherited)
+    }
+}
+
+#[test]
+fn items() {
+    let context = FontContext::new();
+    let fonts = [crate::fonts::BITSTREAM_VERA_SANS.clone()];
+    let items = |text, right_to_left| {
+        itemize(text, right_to_left, &fonts, &context)
+            .map(|item| (item.text, item.bidi_level))
+            .collect::<Vec<_>>()
+    };
+    assert!(items("", false).is_empty());
+    assert_eq!(items("Hello, world!", false), [("Hello, world!", 0)]);
+    assert_eq!(
+        items("abc אבג, def", false),
+        [("abc ", 0), ("אבג", 1), (", def", 0)]
+    );
+    assert_eq!(
+        items("אבג abc. ", true),
+        [("אבג ", 1), ("abc", 2), (". ", 1)]
+    );
+    assert_eq!(items("é Ελλάδα", false), [("é ", 0), ("Ελλάδα", 0)]);
+    assert_eq!(
+        items("e\u{301}, ב\u{5bc}", false),
+        [("e\u{301}, ", 0), ("ב\u{5bc}", 1)]
+    );
+}
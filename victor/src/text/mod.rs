@@ -1,7 +1,12 @@
-use crate::fonts::{Em, Font, FontError, GlyphId};
+#[cfg(feature = "html")]
+use crate::fonts::Substitutions;
+use crate::fonts::{Em, Font, FontContext, FontError, GlyphId};
 use crate::primitives::Length;
 use std::sync::Arc;
 
+#[cfg(feature = "html")]
+pub(crate) mod itemize;
+
 #[derive(Clone)]
 pub struct ShapedSegment {
     pub(crate) font: Arc<Font>,
@@ -52,6 +57,41 @@ impl ShapedSegment {
         Ok(())
     }
 
+    /// Same as `append_char`, with glyph lookups cached in `font_context`.
+    pub fn append_char_in(
+        &mut self,
+        ch: char,
+        font_context: &FontContext,
+    ) -> Result<(), FontError> {
+        let (id, advance_width) = font_context.glyph(&self.font, ch)?;
+        self.advance_width += advance_width;
+        self.glyphs.push(id);
+        Ok(())
+    }
+
+    /// Apply OpenType substitutions to the glyphs appended so far, which must be in logical order,
+    /// and update the advance width for the new glyphs.
+    ///
+    /// If the width of a substitute cannot be read from a malformed font,
+    /// the glyphs are left unsubstituted.
+    #[cfg(feature = "html")]
+    pub(crate) fn substitute(&mut self, substitutions: &Substitutions) {
+        if substitutions.is_empty() {
+            return;
+        }
+        let mut glyphs = self.glyphs.clone();
+        substitutions.apply(&mut glyphs);
+        let advance_width = glyphs.iter().try_fold(Length::new(0.), |width, &id| {
+            self.font
+                .glyph_width(id)
+                .map(|glyph_width| width + glyph_width)
+        });
+        if let Ok(advance_width) = advance_width {
+            self.glyphs = glyphs;
+            self.advance_width = advance_width
+        }
+    }
+
     pub fn save(&self) -> ShapedSegmentState {
         ShapedSegmentState {
             glyphs: self.glyphs.len(),
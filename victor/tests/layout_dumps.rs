@@ -0,0 +1,44 @@
+//! Layout tests that do not render to pixels, so they do not need Poppler:
+//! each `layout-dumps/*.html` file is laid out, and its fragment tree dump
+//! is compared with the `.txt` file of the same name.
+//!
+//! Run with `UPDATE_LAYOUT_DUMPS=1` in the environment to write the dumps instead,
+//! then review their diff.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use victor::dom::Document;
+use victor::fonts::FontContext;
+
+#[test]
+fn layout_dumps() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("layout-dumps");
+    let update = env::var_os("UPDATE_LAYOUT_DUMPS").is_some();
+    let mut failures = Vec::new();
+    for entry in dir.read_dir().unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("html") {
+            continue;
+        }
+        let html = fs::read(&path).unwrap();
+        let dump = Document::parse_html(&html).dump_fragment_tree(FontContext::shared());
+        let expected_path = path.with_extension("txt");
+        if update {
+            fs::write(&expected_path, &dump).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if dump != expected {
+            failures.push(format!(
+                "{}\nExpected:\n{}\nGot:\n{}",
+                expected_path.display(),
+                expected,
+                dump
+            ))
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}